@@ -4,6 +4,7 @@ use std::ops;
 use std::path::PathBuf;
 use std::ptr;
 use std::slice;
+use std::str;
 use std::sync::Once;
 use std::{f64, i32};
 
@@ -33,11 +34,15 @@ use glib::{
 
 use glib_sys;
 use gobject_sys::{self, GEnumValue, GFlagsValue};
+use pango;
+use pango_sys;
 
 use rsvg_internals::{
-    rsvg_log, DefsLookupErrorKind, Dpi, Handle, IntrinsicDimensions,
-    LoadOptions, LoadingError, RenderingError, RsvgDimensionData, RsvgLength, RsvgPositionData,
-    RsvgSizeFunc, SharedImageSurface, SizeCallback, SurfaceType, ViewBox,
+    rsvg_log, AnimatedImageFormat, AnimationOptions, ApplicationFonts, ColorScheme,
+    DefsLookupErrorKind, Dpi, ElementContext, ElementOverrides, Handle, IntrinsicDimensions,
+    LoadOptions, LoadingError, MediaFeatures, RenderHooks, RenderQuality, RenderingError,
+    RsvgDimensionData, RsvgLength, RsvgPositionData, RsvgSizeFunc, SharedImageSurface,
+    SizeCallback, SurfaceType, ViewBox,
 };
 
 use crate::pixbuf_utils::{empty_pixbuf, pixbuf_from_surface};
@@ -55,6 +60,7 @@ mod handle_flags {
             const NONE            = 0;
             const UNLIMITED       = 1 << 0;
             const KEEP_IMAGE_DATA = 1 << 1;
+            const MULTILINE_TEXT  = 1 << 2;
         }
     }
 
@@ -103,6 +109,7 @@ mod handle_flags {
 struct LoadFlags {
     pub unlimited_size: bool,
     pub keep_image_data: bool,
+    pub multiline_text: bool,
 }
 
 pub use self::handle_flags::*;
@@ -112,6 +119,7 @@ impl From<HandleFlags> for LoadFlags {
         LoadFlags {
             unlimited_size: hflags.contains(HandleFlags::UNLIMITED),
             keep_image_data: hflags.contains(HandleFlags::KEEP_IMAGE_DATA),
+            multiline_text: hflags.contains(HandleFlags::MULTILINE_TEXT),
         }
     }
 }
@@ -128,6 +136,10 @@ impl From<LoadFlags> for HandleFlags {
             hflags.insert(HandleFlags::KEEP_IMAGE_DATA);
         }
 
+        if lflags.multiline_text {
+            hflags.insert(HandleFlags::MULTILINE_TEXT);
+        }
+
         hflags
     }
 }
@@ -237,6 +249,40 @@ impl From<ViewBox> for RsvgRectangle {
     }
 }
 
+// Keep this in sync with rsvg.h:RsvgAnimationFormat
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub enum RsvgAnimationFormat {
+    Apng,
+    Gif,
+}
+
+impl From<RsvgAnimationFormat> for AnimatedImageFormat {
+    fn from(f: RsvgAnimationFormat) -> AnimatedImageFormat {
+        match f {
+            RsvgAnimationFormat::Apng => AnimatedImageFormat::Apng,
+            RsvgAnimationFormat::Gif => AnimatedImageFormat::Gif,
+        }
+    }
+}
+
+// Keep this in sync with rsvg.h:RsvgColorScheme
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub enum RsvgColorScheme {
+    Light,
+    Dark,
+}
+
+impl From<RsvgColorScheme> for ColorScheme {
+    fn from(s: RsvgColorScheme) -> ColorScheme {
+        match s {
+            RsvgColorScheme::Light => ColorScheme::Light,
+            RsvgColorScheme::Dark => ColorScheme::Dark,
+        }
+    }
+}
+
 /// Contains all the interior mutability for a RsvgHandle to be called
 /// from the C API.
 pub struct CHandle {
@@ -248,6 +294,8 @@ struct CHandleInner {
     dpi: Dpi,
     load_flags: LoadFlags,
     base_url: BaseUrl,
+    color_scheme: ColorScheme,
+    fonts: Option<ApplicationFonts>,
     size_callback: SizeCallback,
     is_testing: bool,
 }
@@ -379,6 +427,8 @@ impl ObjectSubclass for CHandle {
                 dpi: Dpi::default(),
                 load_flags: LoadFlags::default(),
                 base_url: BaseUrl::default(),
+                color_scheme: ColorScheme::default(),
+                fonts: None,
                 size_callback: SizeCallback::default(),
                 is_testing: false,
             }),
@@ -539,9 +589,19 @@ impl CHandle {
     fn load_options(&self) -> LoadOptions {
         let inner = self.inner.borrow();
 
-        LoadOptions::new(inner.base_url.get().map(|u| (*u).clone()))
+        let load_options = LoadOptions::new(inner.base_url.get().map(|u| (*u).clone()))
             .with_unlimited_size(inner.load_flags.unlimited_size)
             .keep_image_data(inner.load_flags.keep_image_data)
+            .with_multiline_text(inner.load_flags.multiline_text)
+            .with_media_features(MediaFeatures {
+                color_scheme: inner.color_scheme,
+                ..MediaFeatures::default()
+            });
+
+        match inner.fonts {
+            Some(ref fonts) => load_options.with_fonts(fonts.clone()),
+            None => load_options,
+        }
     }
 
     fn set_size_callback(
@@ -664,6 +724,51 @@ impl CHandle {
         }
     }
 
+    fn set_time(&self, seconds: f64) {
+        if let Ok(handle) = self.get_handle_ref() {
+            handle.set_time(Some(seconds));
+        }
+    }
+
+    fn set_turbulence_seed(&self, seed: i32) {
+        if let Ok(handle) = self.get_handle_ref() {
+            handle.set_turbulence_seed(Some(seed));
+        }
+    }
+
+    fn set_color_scheme(&self, color_scheme: ColorScheme) {
+        self.inner.borrow_mut().color_scheme = color_scheme;
+
+        if let LoadState::ClosedOk { ref handle } = *self.load_state.borrow() {
+            handle.set_color_scheme(color_scheme);
+        }
+    }
+
+    fn set_font_map(&self, font_map: Option<pango::FontMap>) {
+        match *self.load_state.borrow() {
+            LoadState::Start => (),
+            _ => {
+                rsvg_g_critical("Please set the font map before loading any data into RsvgHandle");
+                return;
+            }
+        }
+
+        self.inner.borrow_mut().fonts = font_map.map(ApplicationFonts::from_font_map);
+    }
+
+    fn set_stylesheet(&self, css: &str) -> Result<(), LoadingError> {
+        match *self.load_state.borrow() {
+            LoadState::ClosedOk { ref handle } => handle.set_stylesheet(css),
+
+            _ => {
+                rsvg_g_critical(
+                    "handle must already be loaded in order to call rsvg_handle_set_stylesheet()",
+                );
+                Err(LoadingError::Unknown)
+            }
+        }
+    }
+
     fn has_sub(&self, id: &str) -> Result<bool, RenderingError> {
         let handle = self.get_handle_ref()?;
         handle.has_sub(id).map_err(warn_on_invalid_id)
@@ -747,7 +852,15 @@ impl CHandle {
 
         let handle = self.get_handle_ref()?;
         let inner = self.inner.borrow();
-        handle.render_document(cr, viewport, inner.dpi, inner.is_testing)
+        handle.render_document(
+            cr,
+            viewport,
+            inner.dpi,
+            &RenderHooks::default(),
+            RenderQuality::Full,
+            &ElementOverrides::default(),
+            inner.is_testing,
+        )
     }
 
     fn get_geometry_for_layer(
@@ -774,7 +887,16 @@ impl CHandle {
         let handle = self.get_handle_ref()?;
         let inner = self.inner.borrow();
         handle
-            .render_layer(cr, id, viewport, inner.dpi, inner.is_testing)
+            .render_layer(
+                cr,
+                id,
+                viewport,
+                inner.dpi,
+                &RenderHooks::default(),
+                RenderQuality::Full,
+                &ElementOverrides::default(),
+                inner.is_testing,
+            )
             .map_err(warn_on_invalid_id)
     }
 
@@ -785,7 +907,7 @@ impl CHandle {
         let handle = self.get_handle_ref()?;
         let inner = self.inner.borrow();
         handle
-            .get_geometry_for_element(id, inner.dpi, inner.is_testing)
+            .get_geometry_for_element(id, inner.dpi, ElementContext::default(), inner.is_testing)
             .map(|(i, l)| (RsvgRectangle::from(i), RsvgRectangle::from(l)))
             .map_err(warn_on_invalid_id)
     }
@@ -801,10 +923,42 @@ impl CHandle {
         let handle = self.get_handle_ref()?;
         let inner = self.inner.borrow();
         handle
-            .render_element(cr, id, element_viewport, inner.dpi, inner.is_testing)
+            .render_element(
+                cr,
+                id,
+                element_viewport,
+                inner.dpi,
+                ElementContext::default(),
+                &RenderHooks::default(),
+                RenderQuality::Full,
+                &ElementOverrides::default(),
+                inner.is_testing,
+            )
             .map_err(warn_on_invalid_id)
     }
 
+    fn render_animation(
+        &self,
+        width: i32,
+        height: i32,
+        format: AnimatedImageFormat,
+        options: &AnimationOptions,
+    ) -> Result<Vec<u8>, RenderingError> {
+        let handle = self.get_handle_ref()?;
+        let inner = self.inner.borrow();
+        handle.render_animation(
+            width,
+            height,
+            inner.dpi,
+            format,
+            options,
+            &RenderHooks::default(),
+            RenderQuality::Full,
+            &ElementOverrides::default(),
+            inner.is_testing,
+        )
+    }
+
     fn get_intrinsic_dimensions(&self) -> Result<IntrinsicDimensions, RenderingError> {
         let handle = self.get_handle_ref()?;
         Ok(handle.get_intrinsic_dimensions())
@@ -875,7 +1029,7 @@ pub unsafe extern "C" fn rsvg_rust_handle_flags_get_type() -> glib_sys::GType {
     struct GFlagsValueWrapper(GFlagsValue);
     unsafe impl Sync for GFlagsValueWrapper {}
 
-    static VALUES: [GFlagsValueWrapper; 4] = [
+    static VALUES: [GFlagsValueWrapper; 5] = [
         GFlagsValueWrapper(GFlagsValue {
             value: 0, // handle_flags::HandleFlags::NONE.bits(),
             value_name: b"RSVG_HANDLE_FLAGS_NONE\0" as *const u8 as *const _,
@@ -891,6 +1045,11 @@ pub unsafe extern "C" fn rsvg_rust_handle_flags_get_type() -> glib_sys::GType {
             value_name: b"RSVG_HANDLE_FLAG_KEEP_IMAGE_DATA\0" as *const u8 as *const _,
             value_nick: b"flag-keep-image-data\0" as *const u8 as *const _,
         }),
+        GFlagsValueWrapper(GFlagsValue {
+            value: 1 << 2, // HandleFlags::MULTILINE_TEXT.to_glib(),
+            value_name: b"RSVG_HANDLE_FLAG_MULTILINE_TEXT\0" as *const u8 as *const _,
+            value_nick: b"flag-multiline-text\0" as *const u8 as *const _,
+        }),
         GFlagsValueWrapper(GFlagsValue {
             value: 0,
             value_name: 0 as *const _,
@@ -968,6 +1127,70 @@ pub unsafe extern "C" fn rsvg_rust_handle_get_dpi_y(raw_handle: *const RsvgHandl
     rhandle.get_dpi_y()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn rsvg_rust_handle_set_time(raw_handle: *const RsvgHandle, seconds: f64) {
+    let rhandle = get_rust_handle(raw_handle);
+    rhandle.set_time(seconds);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rsvg_rust_handle_set_turbulence_seed(
+    raw_handle: *const RsvgHandle,
+    seed: libc::c_int,
+) {
+    let rhandle = get_rust_handle(raw_handle);
+    rhandle.set_turbulence_seed(seed);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rsvg_rust_handle_set_color_scheme(
+    raw_handle: *const RsvgHandle,
+    color_scheme: RsvgColorScheme,
+) {
+    let rhandle = get_rust_handle(raw_handle);
+    rhandle.set_color_scheme(ColorScheme::from(color_scheme));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rsvg_rust_handle_set_font_map(
+    raw_handle: *const RsvgHandle,
+    font_map: *mut pango_sys::PangoFontMap,
+) {
+    let rhandle = get_rust_handle(raw_handle);
+    rhandle.set_font_map(from_glib_none(font_map));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rsvg_rust_handle_set_stylesheet(
+    raw_handle: *const RsvgHandle,
+    css: *const u8,
+    css_len: usize,
+    error: *mut *mut glib_sys::GError,
+) -> glib_sys::gboolean {
+    let rhandle = get_rust_handle(raw_handle);
+
+    let css = if css_len == 0 {
+        ""
+    } else {
+        match str::from_utf8(slice::from_raw_parts(css, css_len)) {
+            Ok(s) => s,
+            Err(_) => {
+                set_gerror(error, 0, "CSS is not valid UTF-8");
+                return false.to_glib();
+            }
+        }
+    };
+
+    match rhandle.set_stylesheet(css) {
+        Ok(()) => true.to_glib(),
+
+        Err(e) => {
+            set_gerror(error, 0, &format!("{}", e));
+            false.to_glib()
+        }
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rsvg_rust_handle_set_size_callback(
     raw_handle: *const RsvgHandle,
@@ -1457,6 +1680,46 @@ pub unsafe extern "C" fn rsvg_rust_handle_render_element(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn rsvg_rust_handle_write_animation(
+    handle: *mut RsvgHandle,
+    stream: *mut gio_sys::GOutputStream,
+    format: RsvgAnimationFormat,
+    width: libc::c_int,
+    height: libc::c_int,
+    frame_rate: f64,
+    duration: f64,
+    loops: libc::c_uint,
+    error: *mut *mut glib_sys::GError,
+) -> glib_sys::gboolean {
+    let rhandle = get_rust_handle(handle);
+    let stream: gio::OutputStream = from_glib_none(stream);
+
+    let options = AnimationOptions {
+        frame_rate,
+        duration,
+        loops,
+    };
+
+    let data = match rhandle.render_animation(width, height, format.into(), &options) {
+        Ok(data) => data,
+
+        Err(e) => {
+            set_gerror(error, 0, &format!("{}", e));
+            return false.to_glib();
+        }
+    };
+
+    match stream.write_all(&data, None) {
+        Ok(_) => true.to_glib(),
+
+        Err(e) => {
+            set_gerror(error, 0, &format!("{}", e));
+            false.to_glib()
+        }
+    }
+}
+
 /// Detects whether a `*const libc::c_char` is a path or a URI
 ///
 /// `rsvg_handle_new_from_file()` takes a `filename` argument, and advertises
@@ -35,6 +35,8 @@ pub use crate::c_api::{
     rsvg_rust_handle_set_dpi_y,
     rsvg_rust_handle_set_size_callback,
     rsvg_rust_handle_set_testing,
+    rsvg_rust_handle_set_time,
+    rsvg_rust_handle_set_turbulence_seed,
     rsvg_rust_handle_write,
 };
 
@@ -316,6 +316,7 @@
 
 #include <string.h>
 #include <limits.h>
+#include <math.h>
 #include <stdlib.h>
 #include <glib/gprintf.h>
 #include <glib/gi18n-lib.h>
@@ -334,6 +335,14 @@ extern void rsvg_rust_handle_set_base_url (RsvgHandle *raw_handle, const char *u
 extern void rsvg_rust_handle_set_base_gfile (RsvgHandle *raw_handle, GFile *file);
 extern const char *rsvg_rust_handle_get_base_url (RsvgHandle *raw_handle);
 extern guint rsvg_rust_handle_set_testing (RsvgHandle *raw_handle, gboolean testing);
+extern void rsvg_rust_handle_set_time (RsvgHandle *raw_handle, double seconds);
+extern void rsvg_rust_handle_set_turbulence_seed (RsvgHandle *raw_handle, int seed);
+extern void rsvg_rust_handle_set_color_scheme (RsvgHandle *raw_handle, RsvgColorScheme color_scheme);
+extern void rsvg_rust_handle_set_font_map (RsvgHandle *raw_handle, PangoFontMap *font_map);
+extern gboolean rsvg_rust_handle_set_stylesheet (RsvgHandle   *raw_handle,
+                                                 const guint8 *css,
+                                                 gsize         css_len,
+                                                 GError      **error);
 extern gboolean rsvg_rust_handle_read_stream_sync (RsvgHandle *handle,
                                                    GInputStream *stream,
                                                    GCancellable *cancellable,
@@ -404,6 +413,15 @@ extern gboolean rsvg_rust_handle_render_element (RsvgHandle           *handle,
                                                  const char           *id,
                                                  const RsvgRectangle  *element_viewport,
                                                  GError              **error);
+extern gboolean rsvg_rust_handle_write_animation (RsvgHandle          *handle,
+                                                  GOutputStream       *stream,
+                                                  RsvgAnimationFormat  format,
+                                                  int                  width,
+                                                  int                  height,
+                                                  double               frame_rate,
+                                                  double               duration,
+                                                  guint                loops,
+                                                  GError             **error);
 
 
 
@@ -977,6 +995,146 @@ rsvg_handle_get_position_sub (RsvgHandle *handle,
     return rsvg_rust_handle_get_position_sub (handle, position_data, id);
 }
 
+/**
+ * rsvg_handle_set_time:
+ * @handle: a #RsvgHandle
+ * @seconds: time in the document's timeline, in seconds
+ *
+ * Seeks the SMIL animations in the SVG document to @seconds.  Subsequent rendering
+ * and geometry calls on the @handle will use the animated values at that time,
+ * for elements like <literal>&lt;animate&gt;</literal>, <literal>&lt;set&gt;</literal>,
+ * <literal>&lt;animateTransform&gt;</literal> and <literal>&lt;animateMotion&gt;</literal>,
+ * and for CSS animations defined with <literal>@keyframes</literal>.
+ *
+ * Until this function is called, animations are ignored and the document is rendered
+ * with its static values.  Animations that begin on an event never start.
+ *
+ * This must be called after the @handle is loaded.  @seconds must be a finite number.
+ *
+ * Since: 2.48
+ */
+void
+rsvg_handle_set_time (RsvgHandle *handle, double seconds)
+{
+    g_return_if_fail (RSVG_IS_HANDLE (handle));
+    g_return_if_fail (isfinite (seconds));
+
+    rsvg_rust_handle_set_time (handle, seconds);
+}
+
+/**
+ * rsvg_handle_set_turbulence_seed:
+ * @handle: a #RsvgHandle
+ * @seed: seed for the pseudo-random numbers
+ *
+ * Makes all the <literal>&lt;feTurbulence&gt;</literal> filter primitives in the
+ * SVG document use @seed instead of their <literal>seed</literal> attribute.
+ * Subsequent rendering calls on the @handle will use it.  This can be used to vary
+ * procedural textures between renders, or to make them deterministic regardless
+ * of the document's own seeds.
+ *
+ * This must be called after the @handle is loaded.
+ *
+ * Since: 2.48
+ */
+void
+rsvg_handle_set_turbulence_seed (RsvgHandle *handle, int seed)
+{
+    g_return_if_fail (RSVG_IS_HANDLE (handle));
+
+    rsvg_rust_handle_set_turbulence_seed (handle, seed);
+}
+
+/**
+ * rsvg_handle_set_color_scheme:
+ * @handle: a #RsvgHandle
+ * @color_scheme: the user's preference for light or dark themes
+ *
+ * Sets the value of the <literal>prefers-color-scheme</literal> media feature,
+ * so that <literal>@media (prefers-color-scheme: dark)</literal> rules in the
+ * document's styles, and in the stylesheet from rsvg_handle_set_stylesheet(),
+ * apply or not.  By default the color scheme is %RSVG_COLOR_SCHEME_LIGHT.
+ *
+ * This can be called before or after the @handle is loaded; call it again when
+ * the application's theme changes, and the styles of the document get computed
+ * again without loading it again.
+ *
+ * Since: 2.48
+ */
+void
+rsvg_handle_set_color_scheme (RsvgHandle *handle, RsvgColorScheme color_scheme)
+{
+    g_return_if_fail (RSVG_IS_HANDLE (handle));
+    g_return_if_fail (color_scheme == RSVG_COLOR_SCHEME_LIGHT
+                      || color_scheme == RSVG_COLOR_SCHEME_DARK);
+
+    rsvg_rust_handle_set_color_scheme (handle, color_scheme);
+}
+
+/**
+ * rsvg_handle_set_font_map:
+ * @handle: a #RsvgHandle
+ * @font_map: (allow-none): a #PangoFontMap, or %NULL to use the system's fonts
+ *
+ * Sets the font map for rendering the text in the document, instead of the default
+ * font map with the system's fonts.
+ *
+ * This lets applications render with a private set of fonts, for example the ones
+ * in a directory that they add to a fontconfig configuration for a #PangoFcFontMap.
+ * Renders are then the same on every machine, even without any fonts installed.
+ *
+ * Fonts can't be added to a font map from the application, so the
+ * <literal>@font-face</literal> rules in the document are ignored.
+ *
+ * This must be called before loading data into the @handle.
+ *
+ * Since: 2.48
+ */
+void
+rsvg_handle_set_font_map (RsvgHandle *handle, PangoFontMap *font_map)
+{
+    g_return_if_fail (RSVG_IS_HANDLE (handle));
+    g_return_if_fail (font_map == NULL || PANGO_IS_FONT_MAP (font_map));
+
+    rsvg_rust_handle_set_font_map (handle, font_map);
+}
+
+/**
+ * rsvg_handle_set_stylesheet:
+ * @handle: A #RsvgHandle.
+ * @css: (array length=css_len): String with CSS data; must be valid UTF-8.
+ * @css_len: Length of the @css data in bytes.
+ * @error: (optional): return location for errors.
+ *
+ * Sets a CSS stylesheet to use in addition to the document's own styles.
+ *
+ * The stylesheet has the user origin of the CSS cascade, so the document's own
+ * styles override it, except for declarations marked with <literal>!important</literal>.
+ * This is useful for recoloring symbolic icons without modifying the SVG source.
+ *
+ * Calling this function again replaces the previous stylesheet; pass an empty
+ * string to remove it.
+ *
+ * This must be called after the @handle is loaded.
+ *
+ * Returns: %TRUE on success, %FALSE on error.  Errors are returned
+ * in the @error argument.
+ *
+ * Since: 2.48
+ */
+gboolean
+rsvg_handle_set_stylesheet (RsvgHandle   *handle,
+                            const guint8 *css,
+                            gsize         css_len,
+                            GError      **error)
+{
+    g_return_val_if_fail (RSVG_IS_HANDLE (handle), FALSE);
+    g_return_val_if_fail (css != NULL || css_len == 0, FALSE);
+    g_return_val_if_fail (error == NULL || *error == NULL, FALSE);
+
+    return rsvg_rust_handle_set_stylesheet (handle, css, css_len, error);
+}
+
 /**
  * rsvg_handle_has_sub:
  * @handle: a #RsvgHandle
@@ -1501,6 +1659,68 @@ rsvg_handle_render_element (RsvgHandle           *handle,
     return rsvg_rust_handle_render_element (handle, cr, id, element_viewport, error);
 }
 
+/**
+ * rsvg_handle_write_animation:
+ * @handle: An #RsvgHandle
+ * @stream: A #GOutputStream to which the image will be written
+ * @format: File format for the image
+ * @width: Width of the image in pixels
+ * @height: Height of the image in pixels
+ * @frame_rate: Number of frames per second; must be greater than zero
+ * @duration: Length of the animation in seconds; must not be negative
+ * @loops: Number of times that the animation is played, or 0 to play it forever
+ * @error: (optional): a location to store a #GError, or %NULL
+ *
+ * Renders the document's SMIL and CSS animations as an animated image.
+ *
+ * The animations are sampled @frame_rate times per second, from the start of the
+ * document's timeline until @duration seconds.  Each frame is rendered by fitting
+ * the whole document into an image of @width by @height pixels, as in
+ * rsvg_handle_render_document().  The time set with rsvg_handle_set_time() is
+ * not changed.
+ *
+ * The whole image is encoded in memory before it is written to the @stream.  An
+ * animation can have at most 10000 frames; this function returns an error for
+ * longer animations, and for values of @frame_rate or @duration that are not
+ * finite.
+ *
+ * API ordering: This function must be called on a fully-loaded @handle.  See
+ * the section <ulink url="#API-ordering">API ordering</ulink> for details.
+ *
+ * Returns: %TRUE on success, or %FALSE on error.
+ *
+ * Since: 2.48
+ */
+gboolean
+rsvg_handle_write_animation (RsvgHandle          *handle,
+                             GOutputStream       *stream,
+                             RsvgAnimationFormat  format,
+                             int                  width,
+                             int                  height,
+                             double               frame_rate,
+                             double               duration,
+                             guint                loops,
+                             GError             **error)
+{
+    g_return_val_if_fail (RSVG_IS_HANDLE (handle), FALSE);
+    g_return_val_if_fail (G_IS_OUTPUT_STREAM (stream), FALSE);
+    g_return_val_if_fail (format == RSVG_ANIMATION_FORMAT_APNG
+                          || format == RSVG_ANIMATION_FORMAT_GIF, FALSE);
+    g_return_val_if_fail (frame_rate > 0.0, FALSE);
+    g_return_val_if_fail (duration >= 0.0, FALSE);
+    g_return_val_if_fail (error == NULL || *error == NULL, FALSE);
+
+    return rsvg_rust_handle_write_animation (handle,
+                                             stream,
+                                             format,
+                                             width,
+                                             height,
+                                             frame_rate,
+                                             duration,
+                                             loops,
+                                             error);
+}
+
 /**
  * rsvg_handle_internal_set_testing:
  * @handle: a #RsvgHandle
@@ -32,6 +32,7 @@
 
 #include <cairo.h>
 #include <gdk-pixbuf/gdk-pixbuf.h>
+#include <pango/pango.h>
 
 G_BEGIN_DECLS
 
@@ -217,6 +218,66 @@ gboolean rsvg_handle_get_position_sub (RsvgHandle       *handle,
 RSVG_API
 gboolean rsvg_handle_has_sub (RsvgHandle *handle, const char *id);
 
+RSVG_API
+void rsvg_handle_set_time (RsvgHandle *handle, double seconds);
+
+RSVG_API
+void rsvg_handle_set_turbulence_seed (RsvgHandle *handle, int seed);
+
+/**
+ * RsvgColorScheme:
+ * @RSVG_COLOR_SCHEME_LIGHT: The user prefers a light theme.
+ * @RSVG_COLOR_SCHEME_DARK: The user prefers a dark theme.
+ *
+ * Values of the <literal>prefers-color-scheme</literal> media feature, for
+ * rsvg_handle_set_color_scheme().
+ *
+ * Since: 2.48
+ */
+typedef enum {
+    RSVG_COLOR_SCHEME_LIGHT,
+    RSVG_COLOR_SCHEME_DARK
+} RsvgColorScheme;
+
+RSVG_API
+void rsvg_handle_set_color_scheme (RsvgHandle *handle, RsvgColorScheme color_scheme);
+
+RSVG_API
+void rsvg_handle_set_font_map (RsvgHandle *handle, PangoFontMap *font_map);
+
+RSVG_API
+gboolean rsvg_handle_set_stylesheet (RsvgHandle   *handle,
+                                     const guint8 *css,
+                                     gsize         css_len,
+                                     GError      **error);
+
+/**
+ * RsvgAnimationFormat:
+ * @RSVG_ANIMATION_FORMAT_APNG: Animated PNG
+ * @RSVG_ANIMATION_FORMAT_GIF: GIF.  Frames are reduced to a fixed palette of 252
+ *   colors with dithering, and pixels that are less than half opaque become
+ *   transparent.
+ *
+ * File formats for rsvg_handle_write_animation().
+ *
+ * Since: 2.48
+ */
+typedef enum {
+    RSVG_ANIMATION_FORMAT_APNG,
+    RSVG_ANIMATION_FORMAT_GIF
+} RsvgAnimationFormat;
+
+RSVG_API
+gboolean rsvg_handle_write_animation (RsvgHandle          *handle,
+                                      GOutputStream       *stream,
+                                      RsvgAnimationFormat  format,
+                                      int                  width,
+                                      int                  height,
+                                      double               frame_rate,
+                                      double               duration,
+                                      guint                loops,
+                                      GError             **error);
+
 /**
  * RsvgUnit:
  * @RSVG_UNIT_PERCENT: percentage values; where <literal>1.0</literal> means 100%.
@@ -228,6 +289,17 @@ gboolean rsvg_handle_has_sub (RsvgHandle *handle, const char *id);
  * @RSVG_UNIT_MM: millimeters
  * @RSVG_UNIT_PT: points, or 1/72 inch
  * @RSVG_UNIT_PC: picas, or 1/6 inch (12 points)
+ * @RSVG_UNIT_VW: percentage of the width of the toplevel viewport, where
+ *   <literal>1.0</literal> means 1%.  Since: 2.48
+ * @RSVG_UNIT_VH: percentage of the height of the toplevel viewport, where
+ *   <literal>1.0</literal> means 1%.  Since: 2.48
+ * @RSVG_UNIT_VMIN: percentage of the smaller dimension of the toplevel viewport, where
+ *   <literal>1.0</literal> means 1%.  Since: 2.48
+ * @RSVG_UNIT_VMAX: percentage of the larger dimension of the toplevel viewport, where
+ *   <literal>1.0</literal> means 1%.  Since: 2.48
+ * @RSVG_UNIT_CH: advance of the "0" glyph in the current font.  Since: 2.48
+ * @RSVG_UNIT_REM: font size of the root element.  Since: 2.48
+ * @RSVG_UNIT_Q: quarter-millimeters.  Since: 2.48
  *
  * Units for the #RsvgLength struct.  These have the same meaning as <ulink
  * url="https://www.w3.org/TR/CSS21/syndata.html#length-units">CSS length
@@ -242,7 +314,14 @@ typedef enum {
     RSVG_UNIT_CM,
     RSVG_UNIT_MM,
     RSVG_UNIT_PT,
-    RSVG_UNIT_PC
+    RSVG_UNIT_PC,
+    RSVG_UNIT_VW,
+    RSVG_UNIT_VH,
+    RSVG_UNIT_VMIN,
+    RSVG_UNIT_VMAX,
+    RSVG_UNIT_CH,
+    RSVG_UNIT_REM,
+    RSVG_UNIT_Q
 } RsvgUnit;
 
 /**
@@ -300,12 +379,19 @@ void rsvg_handle_get_intrinsic_dimensions (RsvgHandle *handle,
  *  url="https://www.cairographics.org/manual/cairo-cairo-surface-t.html#cairo-surface-set-mime-data">the
  *  Cairo documentation</ulink> for details.
  *  Since: 2.40.3
+ * @RSVG_HANDLE_FLAG_MULTILINE_TEXT: Make newlines in the text of
+ *  <literal>&lt;text&gt;</literal> elements start new lines, one em below the
+ *  previous line, instead of collapsing them.  Newlines that just indent the
+ *  text inside the element are ignored.  This is an extension to SVG, meant for
+ *  hand-written files.
+ *  Since: 2.48
  */
 typedef enum /*< flags >*/
 {
     RSVG_HANDLE_FLAGS_NONE           = 0,
     RSVG_HANDLE_FLAG_UNLIMITED       = 1 << 0,
-    RSVG_HANDLE_FLAG_KEEP_IMAGE_DATA = 1 << 1
+    RSVG_HANDLE_FLAG_KEEP_IMAGE_DATA = 1 << 1,
+    RSVG_HANDLE_FLAG_MULTILINE_TEXT  = 1 << 2
 } RsvgHandleFlags;
 
 RSVG_API
@@ -152,21 +152,65 @@ use rsvg_internals;
 use url::Url;
 
 use std::path::Path;
+use std::rc::Rc;
 
 use gio::{Cancellable, FileExt};
 
-use rsvg_internals::{Dpi, Handle, LoadOptions};
+use rsvg_internals::{Dpi, Handle, ImageResolver, LoadOptions};
 
 pub use rsvg_internals::{
+    AnimatedImageFormat,
+    AnimationOptions,
+    AttributeChange,
+    ClipShape,
+    ColorScheme,
+    ComplexityReport,
+    DeclarationInfo,
     DefsLookupErrorKind,
+    DiffElement,
+    DocumentCache,
+    DocumentDiff,
+    ElementChange,
+    ElementContext,
+    ElementOverrides,
+    FontFallback,
+    FontFallbackKind,
+    FontFamilyMap,
+    GenericFontFamily,
+    GlyphRun,
+    HookAction,
     HrefError,
     Length as InternalLength,
     LengthUnit,
     LoadingError,
+    MarkerPosition,
+    MarkerType,
+    MediaFeatures,
+    Origin,
+    PathDataRecovery,
+    PathPoint,
+    PathSegment,
+    PositionedGlyph,
+    PostScriptLevel,
+    PostScriptOptions,
+    RenderHookElement,
+    RenderHooks,
+    RenderQuality,
     RenderingError,
     RsvgLength as Length,
+    RuleInfo,
+    SelectorError,
+    SelectorInfo,
+    StrokeMetrics,
+    SvgProfile,
+    ValidationIssue,
+    ValidationIssueKind,
+    ValidationReport,
 };
 
+#[cfg(feature = "text")]
+pub use rsvg_internals::ApplicationFonts;
+
 /// Builder for loading an [`SvgHandle`][SvgHandle].
 ///
 /// This is the starting point for using librsvg.  This struct
@@ -180,6 +224,18 @@ pub use rsvg_internals::{
 pub struct Loader {
     unlimited_size: bool,
     keep_image_data: bool,
+    languages: Option<Vec<String>>,
+    path_data_recovery: PathDataRecovery,
+    oklab_gradients: bool,
+    multiline_text: bool,
+    image_resolver: Option<ImageResolver>,
+    media_features: MediaFeatures,
+    font_families: FontFamilyMap,
+    #[cfg(feature = "text")]
+    fonts: Option<ApplicationFonts>,
+    max_pattern_tile_size: i32,
+    resource_budget: Option<u64>,
+    cache: Option<DocumentCache>,
 }
 
 impl Loader {
@@ -193,6 +249,31 @@ impl Loader {
     /// surfaces that support including image data in compressed
     /// formats, like PDF.
     ///
+    /// * [`languages`](#method.with_languages) default to the user's
+    /// languages from the environment.
+    ///
+    /// * [`path_data_recovery`](#method.with_path_data_recovery) defaults to
+    /// `PathDataRecovery::RenderPrefix`, as mandated by the SVG specification.
+    ///
+    /// * [`oklab_gradients`](#method.with_oklab_gradients) defaults to `false`.
+    ///
+    /// * [`multiline_text`](#method.with_multiline_text) defaults to `false`.
+    ///
+    /// * [`media_features`](#method.with_media_features) default to a screen with
+    /// an unknown size, a resolution of 96 DPI, and a light color scheme.
+    ///
+    /// * [`font_families`](#method.with_font_families) default to the fonts that the
+    /// system's font configuration chooses.
+    ///
+    /// * [`fonts`](#method.with_fonts) default to the system's fonts.
+    ///
+    /// * [`max_pattern_tile_size`](#method.with_max_pattern_tile_size) defaults to 4096
+    /// pixels.
+    ///
+    /// * There is no [`resource_budget`](#method.with_resource_budget).
+    ///
+    /// * No [`cache`](#method.with_cache) is used, so each load parses the document.
+    ///
     /// # Example:
     ///
     /// ```
@@ -206,6 +287,18 @@ impl Loader {
         Loader {
             unlimited_size: false,
             keep_image_data: false,
+            languages: None,
+            path_data_recovery: PathDataRecovery::RenderPrefix,
+            oklab_gradients: false,
+            multiline_text: false,
+            image_resolver: None,
+            media_features: MediaFeatures::default(),
+            font_families: FontFamilyMap::default(),
+            #[cfg(feature = "text")]
+            fonts: None,
+            max_pattern_tile_size: 4096,
+            resource_budget: None,
+            cache: None,
         }
     }
 
@@ -271,6 +364,326 @@ impl Loader {
         self
     }
 
+    /// Sets the languages used to evaluate `systemLanguage` attributes.
+    ///
+    /// SVG documents can use the `systemLanguage` attribute, often together
+    /// with a `<switch>` element, to render different content depending on the
+    /// user's language.  By default librsvg takes the user's languages from the
+    /// environment, using the `LANGUAGE`, `LC_ALL`, `LC_MESSAGES`, and `LANG`
+    /// environment variables.  Use this method to render a document as it would
+    /// appear for a different set of languages.
+    ///
+    /// Each element of `languages` is a [BCP47] language tag like `"en"` or
+    /// `"es-MX"`, in order of preference.  Invalid language tags are ignored.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_languages(&["es-MX", "en"])
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [BCP47]: http://www.ietf.org/rfc/bcp/bcp47.txt
+    pub fn with_languages<S: AsRef<str>>(mut self, languages: &[S]) -> Self {
+        self.languages = Some(languages.iter().map(|l| l.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Controls how to handle errors in the path data of `<path>` elements.
+    ///
+    /// The SVG specification says that a path whose `d` attribute has an error
+    /// must be rendered up to the first error; this is
+    /// `PathDataRecovery::RenderPrefix`.  Use `PathDataRecovery::Strict` to not
+    /// render such paths at all, or `PathDataRecovery::SkipToken` to skip over
+    /// each erroneous part up to the next path command, and keep parsing from
+    /// there.
+    ///
+    /// In all cases, the errors are logged with their position in the path data
+    /// when the `RSVG_LOG` environment variable is set.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg::{self, PathDataRecovery};
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_path_data_recovery(PathDataRecovery::SkipToken)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_path_data_recovery(mut self, recovery: PathDataRecovery) -> Self {
+        self.path_data_recovery = recovery;
+        self
+    }
+
+    /// Interpolates gradients with `color-interpolation: auto` in the OKLab color space.
+    ///
+    /// Gradients get interpolated in sRGB by default, or in linear RGB if the
+    /// gradient element has `color-interpolation: linearRGB`.  Interpolating in
+    /// sRGB can make gradients between saturated colors go gray in the middle;
+    /// OKLab is a perceptual color space that avoids this.  Gradients that ask
+    /// for `sRGB` or `linearRGB` explicitly are not affected by this option.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_oklab_gradients(true)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_oklab_gradients(mut self, oklab: bool) -> Self {
+        self.oklab_gradients = oklab;
+        self
+    }
+
+    /// Makes newlines in the text of `<text>` elements start new lines.
+    ///
+    /// SVG collapses newlines in text into nothing, or into spaces with
+    /// `xml:space="preserve"`, so text written on several lines in a hand-written
+    /// file normally ends up on a single line.  With this option, each newline starts
+    /// a new line of text at the horizontal position where the current line started,
+    /// `1em` below it.  Newlines that just indent the text inside the element are
+    /// ignored.  This is an extension to SVG; other renderers will not show the
+    /// extra lines.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_multiline_text(true)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_multiline_text(mut self, multiline: bool) -> Self {
+        self.multiline_text = multiline;
+        self
+    }
+
+    /// Sets the environment against which CSS media queries are evaluated.
+    ///
+    /// Rules in `@media` blocks, stylesheets from `<style>` elements with a `media`
+    /// attribute, and `@import` rules with media queries only apply if their media
+    /// queries match these features.  For example, an application can render the
+    /// dark variant of an icon by setting the `color_scheme` to `ColorScheme::Dark`,
+    /// which makes `@media (prefers-color-scheme: dark)` match.
+    ///
+    /// Since CSS is processed while loading the document, loading it again is the
+    /// only way to change the media features.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg::{self, ColorScheme, MediaFeatures};
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_media_features(MediaFeatures {
+    ///         width: Some(48.0),
+    ///         height: Some(48.0),
+    ///         color_scheme: ColorScheme::Dark,
+    ///         ..MediaFeatures::default()
+    ///     })
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_media_features(mut self, features: MediaFeatures) -> Self {
+        self.media_features = features;
+        self
+    }
+
+    /// Configures the fonts for generic font families like `sans-serif`.
+    ///
+    /// Generic families, and text whose `font-family` is not specified, are normally
+    /// resolved by the system's font configuration, which may choose different fonts on
+    /// different machines, or have no sensible choice on servers without a desktop
+    /// configuration.  With this, the renders of a handle use the fonts that the
+    /// application chooses.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg::{self, FontFamilyMap, GenericFontFamily};
+    ///
+    /// let mut font_families = FontFamilyMap::new();
+    /// font_families.set_default_family("DejaVu Serif");
+    /// font_families.set_generic_family(GenericFontFamily::SansSerif, "DejaVu Sans");
+    /// font_families.set_generic_family(GenericFontFamily::Monospace, "DejaVu Sans Mono");
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_font_families(font_families)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_font_families(mut self, font_families: FontFamilyMap) -> Self {
+        self.font_families = font_families;
+        self
+    }
+
+    /// Renders text with the fonts that the application supplies, instead of the system's fonts.
+    ///
+    /// The fonts can be the ones in a directory of font files, or the ones in a
+    /// `pango::FontMap` that the application set up.  Since no fonts need to be installed,
+    /// this is useful to render documents the same way on every machine, for example in
+    /// containers for server-side rendering.
+    ///
+    /// The fonts from the `@font-face` rules in documents are still available, except
+    /// with a `pango::FontMap` from the application, to which fonts can't be added.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use librsvg::{self, ApplicationFonts};
+    ///
+    /// let fonts = ApplicationFonts::from_directory("/srv/fonts").unwrap();
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_fonts(fonts)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "text")]
+    pub fn with_fonts(mut self, fonts: ApplicationFonts) -> Self {
+        self.fonts = Some(fonts);
+        self
+    }
+
+    /// Sets the maximum width and height of the surfaces for `<pattern>` tiles.
+    ///
+    /// Pattern tiles are rendered at the resolution of the surface where the pattern
+    /// gets painted, including its device scale.  A `patternTransform` with a huge
+    /// scale factor could make them enormous, so each side of a tile is limited to
+    /// `size` pixels; larger tiles are rendered at a lower resolution and scaled up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is not positive.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_max_pattern_tile_size(1024)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_max_pattern_tile_size(mut self, size: i32) -> Self {
+        assert!(size > 0);
+
+        self.max_pattern_tile_size = size;
+        self
+    }
+
+    /// Limits the work that librsvg may do to render the document.
+    ///
+    /// While rendering, librsvg keeps count of the pixels in the temporary surfaces it
+    /// allocates, of the pixels covered by the shapes it fills and strokes, and of the
+    /// pixels processed by each filter primitive.  If their sum exceeds `budget`, the
+    /// render is aborted with `RenderingError::ResourceBudgetExceeded`.
+    ///
+    /// This is useful for services that render untrusted documents, as it bounds the
+    /// worst-case cost of each render.  The count starts over for each render.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_resource_budget(100_000_000)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_resource_budget(mut self, budget: u64) -> Self {
+        self.resource_budget = Some(budget);
+        self
+    }
+
+    /// Supplies the images for `<image>` elements from the application.
+    ///
+    /// The `resolver` gets called with the `href` of each `<image>` element, exactly as
+    /// it appears in the document, like `photo://user-avatar`.  If it returns a surface,
+    /// that surface is rendered instead of loading the image from the `href`; if it
+    /// returns `None`, the image is loaded as usual.  This lets an SVG document be used
+    /// as a template with images that are only available in memory.
+    ///
+    /// The resolver is called at most once for each distinct `href`, the first time that
+    /// an image with that `href` is rendered.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use cairo;
+    /// use librsvg;
+    ///
+    /// let avatar = cairo::ImageSurface::create(cairo::Format::ARgb32, 64, 64).unwrap();
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .with_image_resolver(move |href| {
+    ///         if href == "photo://user-avatar" {
+    ///             Some(avatar.clone())
+    ///         } else {
+    ///             None
+    ///         }
+    ///     })
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    /// ```
+    pub fn with_image_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&str) -> Option<cairo::ImageSurface> + 'static,
+    {
+        self.image_resolver = Some(Rc::new(resolver));
+        self
+    }
+
+    /// Loads documents through a cache of parsed documents.
+    ///
+    /// If the cache already has a document for the same data, loaded with the same
+    /// options and base URL, the returned handle shares that document instead of parsing
+    /// the data again.  This is useful for servers that render the same icons many times.
+    ///
+    /// Changes to one of the handles, like [`SvgHandle::set_element_visible`], are not
+    /// seen by the others: the first such change makes that handle parse its own copy of
+    /// the document.  Loaders with an [image resolver](#method.with_image_resolver) never
+    /// share documents, since each loader has its own resolver.
+    ///
+    /// [`SvgHandle::set_element_visible`]: struct.SvgHandle.html#method.set_element_visible
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg::{self, DocumentCache};
+    ///
+    /// let cache = DocumentCache::new();
+    ///
+    /// let first = librsvg::Loader::new()
+    ///     .with_cache(&cache)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// // This does not parse the file again
+    /// let second = librsvg::Loader::new()
+    ///     .with_cache(&cache)
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    pub fn with_cache(mut self, cache: &DocumentCache) -> Self {
+        self.cache = Some(cache.clone());
+        self
+    }
+
     /// Reads an SVG document from `path`.
     ///
     /// # Example:
@@ -351,38 +764,354 @@ impl Loader {
             None
         };
 
-        let load_options = LoadOptions::new(base_url)
+        let mut load_options = LoadOptions::new(base_url)
             .with_unlimited_size(self.unlimited_size)
-            .keep_image_data(self.keep_image_data);
+            .keep_image_data(self.keep_image_data)
+            .with_path_data_recovery(self.path_data_recovery)
+            .with_oklab_gradients(self.oklab_gradients)
+            .with_multiline_text(self.multiline_text)
+            .with_media_features(self.media_features)
+            .with_font_families(self.font_families)
+            .with_max_pattern_tile_size(self.max_pattern_tile_size);
+
+        if let Some(ref languages) = self.languages {
+            load_options = load_options.with_languages(languages);
+        }
+
+        if let Some(budget) = self.resource_budget {
+            load_options = load_options.with_resource_budget(budget);
+        }
+
+        if let Some(ref resolver) = self.image_resolver {
+            load_options = load_options.with_image_resolver(resolver.clone());
+        }
+
+        #[cfg(feature = "text")]
+        {
+            if let Some(fonts) = self.fonts {
+                load_options = load_options.with_fonts(fonts);
+            }
+        }
+
+        let stream = stream.as_ref();
+        let cancellable = cancellable.map(|c| c.as_ref());
+
+        let handle = if let Some(ref cache) = self.cache {
+            cache.load(&load_options, stream, cancellable)?
+        } else {
+            Handle::from_stream(&load_options, stream, cancellable)?
+        };
+
+        Ok(SvgHandle(handle))
+    }
+
+    /// Reads a fragment of SVG, like a few shapes without a toplevel `<svg>` element.
+    ///
+    /// The `fragment` gets placed inside an `<svg>` element of `width` by `height` user
+    /// units, which is also its `viewBox`, so that applications can render small
+    /// snippets that they generate, like icons or paths, without wrapping them in a
+    /// whole document.  The SVG namespace is the default one in the fragment, and the
+    /// `xlink` prefix is declared, so elements need no namespace declarations.
+    ///
+    /// The fragment cannot have an XML declaration or a DOCTYPE.  Relative URLs in it
+    /// cannot be resolved, as it has no base URL.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` or `height` are not positive, finite numbers.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .read_fragment(r#"<path d="M 2 2 L 14 8 L 2 14 Z" fill="blue"/>"#, 16.0, 16.0)
+    ///     .unwrap();
+    /// ```
+    pub fn read_fragment(
+        self,
+        fragment: &str,
+        width: f64,
+        height: f64,
+    ) -> Result<SvgHandle, LoadingError> {
+        assert!(width > 0.0 && width.is_finite());
+        assert!(height > 0.0 && height.is_finite());
+
+        let document = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+             xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+             width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">{}</svg>",
+            fragment,
+            w = width,
+            h = height,
+        );
+
+        let bytes = glib::Bytes::from(document.as_bytes());
+        let stream = gio::MemoryInputStream::new_from_bytes(&bytes);
+
+        self.read_stream(&stream, None::<&gio::File>, None::<&Cancellable>)
+    }
+}
+
+fn url_from_file(file: &gio::File) -> Result<Url, LoadingError> {
+    Ok(Url::parse(&file.get_uri()).map_err(|_| LoadingError::BadUrl)?)
+}
+
+/// Handle used to hold SVG data in memory.
+///
+/// You can create this from one of the `read` methods in
+/// [`Loader`](#struct.Loader.html).
+pub struct SvgHandle(Handle);
+
+impl SvgHandle {
+    /// Checks if the SVG has an element with the specified `id`.
+    ///
+    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
+    /// a leading `#` character.
+    ///
+    /// The purpose of the `Err()` case in the return value is to indicate an
+    /// incorrectly-formatted `id` argument.
+    pub fn has_element_with_id(&self, id: &str) -> Result<bool, RenderingError> {
+        self.0.has_sub(id)
+    }
+
+    /// Checks if the element with the specified `id` would be painted when rendering
+    /// the whole document.
+    ///
+    /// This lets tools detect "dead" layers.  An element is not rendered if it or any
+    /// of its ancestors has `display: none` or is not visible, is inside a `<defs>`,
+    /// `<clipPath>`, `<mask>`, `<marker>`, `<pattern>`, or `<symbol>`, fails the
+    /// conditional processing attributes (`systemLanguage` and friends, or is not the
+    /// chosen child of a `<switch>`), or is inside a zero-sized `<svg>`.
+    ///
+    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
+    /// a leading `#` character.
+    ///
+    /// Returns `Err(RenderingError::InvalidId)` if there is no element with that `id`.
+    pub fn is_element_visible(&self, id: &str) -> Result<bool, RenderingError> {
+        self.0.is_element_visible(id)
+    }
+
+    /// Shows or hides the element with the specified `id`.
+    ///
+    /// This is meant for documents with layers, like technical drawings, where users
+    /// switch layers on and off frequently.  Hiding an element has the same effect as
+    /// setting `display: none` on it; showing it turns a `display: none` from the
+    /// document into `display: inline`, so that layers which are hidden in the file
+    /// can be shown.  Its ancestors must be visible for the element to be rendered.
+    ///
+    /// Changing the visibility is cheap: only the element's subtree gets its styles
+    /// computed again, and the document does not need to be reloaded.  The change
+    /// affects all the renderers for this handle, and the results of
+    /// [`is_element_visible`](#method.is_element_visible) and
+    /// [`get_computed_property`](#method.get_computed_property).  Use
+    /// [`reset_element_visibility`](#method.reset_element_visibility) to undo it.
+    ///
+    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
+    /// a leading `#` character.
+    ///
+    /// Returns `Err(RenderingError::InvalidId)` if there is no element with that `id`.
+    pub fn set_element_visible(&self, id: &str, visible: bool) -> Result<(), RenderingError> {
+        self.0.set_element_visible(id, Some(visible))
+    }
+
+    /// Undoes the effect of [`set_element_visible`](#method.set_element_visible) on
+    /// the element with the specified `id`, so that it uses the `display` property
+    /// from the document again.
+    ///
+    /// Returns `Err(RenderingError::InvalidId)` if there is no element with that `id`.
+    pub fn reset_element_visibility(&self, id: &str) -> Result<(), RenderingError> {
+        self.0.set_element_visible(id, None)
+    }
+
+    /// Marks the element with the specified `id` as expected to change between renders,
+    /// like the `will-change` property in CSS, or removes the mark.
+    ///
+    /// This is a hint for applications that render the same document repeatedly while
+    /// changing a few elements, for example with
+    /// [`set_element_visible`](#method.set_element_visible).  Intermediate results that
+    /// librsvg keeps between renders, like the output of filters, are organized so that
+    /// changing a marked element only discards the results that depend on it; changing
+    /// any other element discards all of them.  The mark does not change how the
+    /// document looks.
+    ///
+    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
+    /// a leading `#` character.
+    ///
+    /// Returns `Err(RenderingError::InvalidId)` if there is no element with that `id`.
+    pub fn set_will_change(&self, id: &str, will_change: bool) -> Result<(), RenderingError> {
+        self.0.set_will_change(id, will_change)
+    }
+
+    /// Returns the computed value of a CSS property for the element with the specified `id`.
+    ///
+    /// This is similar to `getComputedStyle()` in web browsers: the result is the
+    /// value after the cascade, that is, after applying stylesheets, presentation
+    /// attributes, `style` attributes, and inheritance.  The value is serialized as
+    /// CSS text, for example `"rgb(255, 0, 0)"` for a `fill` or `"2px"` for a
+    /// `stroke-width`.  This is useful for debugging styling issues.
+    ///
+    /// `property` is the name of a CSS property like `"fill"` or `"stroke-width"`.
+    /// Returns `Ok(None)` if librsvg does not support that property.
+    ///
+    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
+    /// a leading `#` character.
+    ///
+    /// Returns `Err(RenderingError::InvalidId)` if there is no element with that `id`.
+    pub fn get_computed_property(
+        &self,
+        id: &str,
+        property: &str,
+    ) -> Result<Option<String>, RenderingError> {
+        self.0.get_computed_property(id, property)
+    }
+
+    /// Selects the element that matches the `:target` pseudo-class in CSS.
+    ///
+    /// Documents with several states, like tabs or layered diagrams, can use
+    /// selectors like `#layer2:target` to style the element which is referenced by
+    /// the fragment of the document's URL.  Initially, the target is the element
+    /// named by the fragment of the base URL, for example `layer2` if the document was
+    /// loaded from `file:///diagram.svg#layer2`.  This function lets the application
+    /// switch between states; pass `None` to have no target element.
+    ///
+    /// Styles get computed again for the whole document, and the change affects all
+    /// the renderers for this handle.
+    ///
+    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
+    /// a leading `#` character.
+    ///
+    /// Returns `Err(RenderingError::InvalidId)` if there is no element with that `id`.
+    pub fn set_target(&self, id: Option<&str>) -> Result<(), RenderingError> {
+        self.0.set_target(id)
+    }
+
+    /// Returns the `id` of the element that matches the `:target` pseudo-class,
+    /// without a leading `#` character, or `None` if there is no target element.
+    ///
+    /// See [`set_target`](#method.set_target) for details.
+    pub fn target(&self) -> Option<String> {
+        self.0.get_target()
+    }
+
+    /// Sets a CSS stylesheet to use in addition to the document's own styles.
+    ///
+    /// The stylesheet has user origin, per the CSS cascade, so the document's
+    /// styles override it, except for declarations with `!important`.  This is
+    /// useful to recolor symbolic icons, for example, without modifying the SVG
+    /// source:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), librsvg::LoadingError> {
+    /// let svg_handle = librsvg::Loader::new().read_path("example.svg")?;
+    ///
+    /// svg_handle.set_stylesheet("path { fill: #3584e4 !important; }")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Calling this again replaces the previous stylesheet; pass an empty string to
+    /// remove it.  `@import` rules in the stylesheet are resolved relative to the
+    /// document's base URL, and `@media` rules are evaluated against the media
+    /// features given to the [`Loader`](struct.Loader.html), with the color scheme
+    /// from [`set_color_scheme`](#method.set_color_scheme).  This way, a stylesheet
+    /// can recolor an icon differently for light and dark themes.
+    ///
+    /// Returns an error if the `css` cannot be parsed.
+    pub fn set_stylesheet(&self, css: &str) -> Result<(), LoadingError> {
+        self.0.set_stylesheet(css)
+    }
+
+    /// Changes the value of the `prefers-color-scheme` media feature.
+    ///
+    /// Initially this is the `color_scheme` of the
+    /// [`MediaFeatures`](struct.MediaFeatures.html) given to the
+    /// [`Loader`](struct.Loader.html).  Call this when the application's theme
+    /// changes, so that `@media (prefers-color-scheme: dark)` rules in the document's
+    /// styles and in the stylesheet from [`set_stylesheet`](#method.set_stylesheet)
+    /// are applied or not without loading the SVG again:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), librsvg::LoadingError> {
+    /// use librsvg::ColorScheme;
+    ///
+    /// let svg_handle = librsvg::Loader::new().read_path("example.svg")?;
+    ///
+    /// svg_handle.set_color_scheme(ColorScheme::Dark);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Styles get computed again for the whole document, and the change affects all
+    /// the renderers for this handle.  Referenced SVG documents, like the ones used by
+    /// `<use>` elements, keep the color scheme with which they were loaded.
+    pub fn set_color_scheme(&self, color_scheme: ColorScheme) {
+        self.0.set_color_scheme(color_scheme);
+    }
+
+    /// Returns the value of the `prefers-color-scheme` media feature.
+    ///
+    /// See [`set_color_scheme`](#method.set_color_scheme) for details.
+    pub fn color_scheme(&self) -> ColorScheme {
+        self.0.get_color_scheme()
+    }
 
-        Ok(SvgHandle(Handle::from_stream(
-            &load_options,
-            stream.as_ref(),
-            cancellable.map(|c| c.as_ref()),
-        )?))
+    /// Returns the rules from the CSS stylesheets in the SVG.
+    ///
+    /// This includes the contents of `<style>` elements and of stylesheets referenced
+    /// with `xml-stylesheet` processing instructions or `@import`, in the order in which
+    /// they participate in the cascade.  Each [`RuleInfo`][RuleInfo] lists the rule's
+    /// selectors, the declarations that librsvg understood, and the source text of the
+    /// declarations that it ignored.
+    ///
+    /// This lets linting tools find selectors that do not match any element, or
+    /// properties that librsvg does not support, using librsvg's own CSS parser.
+    ///
+    /// [RuleInfo]: struct.RuleInfo.html
+    pub fn get_stylesheet_rules(&self) -> Vec<RuleInfo> {
+        self.0.get_stylesheet_rules()
     }
-}
 
-fn url_from_file(file: &gio::File) -> Result<Url, LoadingError> {
-    Ok(Url::parse(&file.get_uri()).map_err(|_| LoadingError::BadUrl)?)
-}
+    /// Computes the structural differences between this SVG and `other`.
+    ///
+    /// The [`DocumentDiff`][DocumentDiff] lists the elements that were added or removed
+    /// in `other`, and the ones whose attributes or text changed.  Elements are matched
+    /// by their `id`, or by their position in the tree if they don't have one.
+    ///
+    /// This is meant for visual regression tools, to explain why the rendered pixels
+    /// of two versions of a document differ.  Note that the diff is structural: changes
+    /// in external resources, like referenced images or stylesheets, are not detected.
+    ///
+    /// [DocumentDiff]: struct.DocumentDiff.html
+    pub fn diff(&self, other: &SvgHandle) -> DocumentDiff {
+        self.0.diff(&other.0)
+    }
 
-/// Handle used to hold SVG data in memory.
-///
-/// You can create this from one of the `read` methods in
-/// [`Loader`](#struct.Loader.html).
-pub struct SvgHandle(Handle);
+    /// Checks the SVG against a profile of the SVG specification, without rendering it.
+    ///
+    /// The [`ValidationReport`][ValidationReport] lists, in document order, the elements
+    /// that are not part of the `profile` or that librsvg does not support, the
+    /// presentation attributes for properties that are not in the profile, and the
+    /// attributes with invalid values.  The checks reuse the parsers that librsvg runs
+    /// when loading the SVG; properties in stylesheets are not checked.
+    ///
+    /// [ValidationReport]: struct.ValidationReport.html
+    pub fn validate(&self, profile: SvgProfile) -> ValidationReport {
+        self.0.validate(profile)
+    }
 
-impl SvgHandle {
-    /// Checks if the SVG has an element with the specified `id`.
+    /// Returns statistics about the elements in the SVG.
     ///
-    /// Note that the `id` must be a plain fragment identifier like `#foo`, with
-    /// a leading `#` character.
+    /// The [`ComplexityReport`][ComplexityReport] has the number of elements of each
+    /// type, how deeply they are nested, and the number of filters, path segments, and
+    /// references to other elements or files.  It is computed when the SVG is loaded, so
+    /// services that render untrusted documents can use it to reject or deprioritize
+    /// pathological ones before rendering them.
     ///
-    /// The purpose of the `Err()` case in the return value is to indicate an
-    /// incorrectly-formatted `id` argument.
-    pub fn has_element_with_id(&self, id: &str) -> Result<bool, RenderingError> {
-        self.0.has_sub(id)
+    /// [ComplexityReport]: struct.ComplexityReport.html
+    pub fn complexity(&self) -> ComplexityReport {
+        self.0.get_complexity()
     }
 }
 
@@ -390,6 +1119,12 @@ impl SvgHandle {
 pub struct CairoRenderer<'a> {
     handle: &'a SvgHandle,
     dpi: Dpi,
+    time: Option<f64>,
+    turbulence_seed: Option<i32>,
+    element_context: ElementContext,
+    render_hooks: RenderHooks,
+    quality: RenderQuality,
+    element_overrides: ElementOverrides,
 }
 
 // Note that these are different than the C API's default, which is 90.
@@ -434,6 +1169,12 @@ impl<'a> CairoRenderer<'a> {
         CairoRenderer {
             handle,
             dpi: Dpi::new(DEFAULT_DPI_X, DEFAULT_DPI_Y),
+            time: None,
+            turbulence_seed: None,
+            element_context: ElementContext::default(),
+            render_hooks: RenderHooks::default(),
+            quality: RenderQuality::default(),
+            element_overrides: ElementOverrides::default(),
         }
     }
 
@@ -447,11 +1188,151 @@ impl<'a> CairoRenderer<'a> {
         assert!(dpi_y > 0.0);
 
         CairoRenderer {
-            handle: self.handle,
             dpi: Dpi::new(dpi_x, dpi_y),
+            ..self
+        }
+    }
+
+    /// Configures the time at which SMIL and CSS animations are rendered.
+    ///
+    /// SVG documents can animate their elements with `<animate>`, `<set>`,
+    /// `<animateTransform>` and `<animateMotion>`, or with CSS `@keyframes` and the
+    /// `animation` property.  This renders a snapshot of the document `seconds` after
+    /// the start of its timeline.  By default, animations are ignored and the document
+    /// is rendered with its static values.
+    ///
+    /// Animations that begin on an event, like a mouse click, never start.  A `seconds`
+    /// that is infinite or NaN is ignored, and the renderer keeps its previous time.
+    pub fn with_time(self, seconds: f64) -> Self {
+        if !seconds.is_finite() {
+            return self;
+        }
+
+        CairoRenderer {
+            time: Some(seconds),
+            ..self
+        }
+    }
+
+    /// Configures the seed of the pseudo-random numbers for `feTurbulence` filters.
+    ///
+    /// The `seed` replaces the `seed` attribute of all the `feTurbulence` elements in the
+    /// document, so that procedural textures can be varied between renders without
+    /// modifying the document.  By default, each element uses its own `seed` attribute.
+    pub fn with_turbulence_seed(self, seed: i32) -> Self {
+        CairoRenderer {
+            turbulence_seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Configures how much of their ancestors' context individual elements are rendered with.
+    ///
+    /// This affects [`render_element`] and [`geometry_for_element`].  By default, an
+    /// element inherits property values like `fill` from its ancestors, but not their
+    /// transformations or effects.  Use `ElementContext::Isolated` to render the element as
+    /// if it were the only one in the document, or `ElementContext::Full` to render it
+    /// with its ancestors' transformations, clipping paths, masks, filters and opacity.
+    ///
+    /// [`render_element`]: #method.render_element
+    /// [`geometry_for_element`]: #method.geometry_for_element
+    pub fn with_element_context(self, element_context: ElementContext) -> Self {
+        CairoRenderer {
+            element_context,
+            ..self
+        }
+    }
+
+    /// Configures callbacks to run before and after drawing individual elements.
+    ///
+    /// Each hook is keyed by a CSS selector, and gets called with the cairo context in
+    /// the user space of the element that is being drawn, and with the element's computed
+    /// property values.  This can be used to draw annotations over elements, or to draw
+    /// placeholder content instead of them.  Hooks are not called when computing
+    /// geometries.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// let mut hooks = librsvg::RenderHooks::new();
+    ///
+    /// // Draw a gray box instead of the logo
+    /// hooks
+    ///     .add_pre_draw_hook("#logo", |cr, _element| {
+    ///         cr.rectangle(0.0, 0.0, 100.0, 100.0);
+    ///         cr.set_source_rgb(0.5, 0.5, 0.5);
+    ///         cr.fill();
+    ///         librsvg::HookAction::Skip
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let renderer = librsvg::CairoRenderer::new(&svg_handle).with_render_hooks(hooks);
+    /// ```
+    pub fn with_render_hooks(self, render_hooks: RenderHooks) -> Self {
+        CairoRenderer {
+            render_hooks,
+            ..self
+        }
+    }
+
+    /// Configures how faithfully the document is rendered.
+    ///
+    /// Use `RenderQuality::Approximate` for quick previews, for example while the user is
+    /// zooming or scrubbing through an animation, and follow up with a render at the
+    /// default `RenderQuality::Full` when they are done.  Approximate renders run filters
+    /// at a lower resolution, scale patterns and images with a fast filter, and skip
+    /// shapes that are smaller than a pixel.
+    pub fn with_quality(self, quality: RenderQuality) -> Self {
+        CairoRenderer { quality, ..self }
+    }
+
+    /// Configures the opacity and visibility of individual elements.
+    ///
+    /// This is meant for viewers with a panel of layers, where several views may show the
+    /// same document with different layers switched on.  Unlike
+    /// [`SvgHandle::set_element_visible`], the overrides don't change the document: they
+    /// only apply to this renderer, and don't affect
+    /// [`SvgHandle::get_computed_property`].  An opacity override replaces the element's
+    /// `opacity` property.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg;
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// let mut overrides = librsvg::ElementOverrides::new();
+    /// overrides.set_visible("#annotations", false);
+    /// overrides.set_opacity("#background", 0.5);
+    ///
+    /// let renderer = librsvg::CairoRenderer::new(&svg_handle).with_element_overrides(overrides);
+    /// ```
+    ///
+    /// [`SvgHandle::set_element_visible`]: struct.SvgHandle.html#method.set_element_visible
+    /// [`SvgHandle::get_computed_property`]: struct.SvgHandle.html#method.get_computed_property
+    pub fn with_element_overrides(self, element_overrides: ElementOverrides) -> Self {
+        CairoRenderer {
+            element_overrides,
+            ..self
         }
     }
 
+    // Several renderers with different times may share the same handle, so the
+    // animations are seeked before every operation.
+    fn seek(&self) {
+        self.handle.0.set_time(self.time);
+        self.handle.0.set_turbulence_seed(self.turbulence_seed);
+    }
+
     /// Queries the `width`, `height`, and `viewBox` attributes in an SVG document.
     ///
     /// If you are calling this function to compute a scaling factor to render the SVG,
@@ -487,7 +1368,185 @@ impl<'a> CairoRenderer<'a> {
         cr: &cairo::Context,
         viewport: &cairo::Rectangle,
     ) -> Result<(), RenderingError> {
-        self.handle.0.render_document(cr, viewport, self.dpi, false)
+        self.seek();
+        self.handle.0.render_document(
+            cr,
+            viewport,
+            self.dpi,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        )
+    }
+
+    /// Renders the whole SVG document fitted to a viewport, with a zoom factor and panning
+    ///
+    /// This is a convenience for viewers that let the user zoom and pan around an SVG
+    /// document.  The document is first fitted to the `viewport` just like in
+    /// [`render_document`], then scaled by `zoom` around the viewport's origin, and
+    /// finally moved by (`pan_x`, `pan_y`) in the coordinates of the `cr`.  The
+    /// result is clipped to the `viewport`.
+    ///
+    /// A `zoom` of 1.0 and a pan of (0.0, 0.0) produce the same result as
+    /// [`render_document`].
+    ///
+    /// Returns `RenderingError::Cairo(cairo::Status::InvalidSize)` if `zoom` is not a
+    /// finite number greater than zero.
+    ///
+    /// The `cr` must be in a `cairo::Status::Success` state, or this function
+    /// will not render anything, and instead will return
+    /// `RenderingError::Cairo` with the `cr`'s current error state.
+    ///
+    /// [`render_document`]: #method.render_document
+    pub fn render_document_with_zoom(
+        &self,
+        cr: &cairo::Context,
+        viewport: &cairo::Rectangle,
+        zoom: f64,
+        pan_x: f64,
+        pan_y: f64,
+    ) -> Result<(), RenderingError> {
+        if !(zoom > 0.0 && zoom.is_finite()) {
+            return Err(RenderingError::Cairo(cairo::Status::InvalidSize));
+        }
+
+        self.seek();
+
+        let zoomed_viewport = cairo::Rectangle {
+            x: viewport.x + pan_x,
+            y: viewport.y + pan_y,
+            width: viewport.width * zoom,
+            height: viewport.height * zoom,
+        };
+
+        cr.save();
+        cr.rectangle(viewport.x, viewport.y, viewport.width, viewport.height);
+        cr.clip();
+
+        let res = self.handle.0.render_document(
+            cr,
+            &zoomed_viewport,
+            self.dpi,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        );
+
+        cr.restore();
+
+        res
+    }
+
+    /// Renders the document's animations as an animated PNG or GIF image
+    ///
+    /// The animations are sampled at `options.frame_rate` frames per second, from the
+    /// start of the document's timeline until `options.duration` seconds, and each frame
+    /// is rendered by fitting the whole document into a `width` by `height` image.  The
+    /// time configured with [`with_time`] is ignored.
+    ///
+    /// Returns the contents of the encoded image file.  Returns
+    /// `RenderingError::Cairo(cairo::Status::InvalidSize)` if the size is invalid, if the
+    /// frame rate is not a finite number greater than zero, if the duration is not a
+    /// finite number greater than or equal to zero, or if the animation would have more
+    /// than 10,000 frames.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg::{AnimatedImageFormat, AnimationOptions};
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// let options = AnimationOptions {
+    ///     frame_rate: 10.0,
+    ///     duration: 2.0,
+    ///     loops: 0,
+    /// };
+    ///
+    /// let apng = librsvg::CairoRenderer::new(&svg_handle)
+    ///     .render_animation(100, 100, AnimatedImageFormat::Apng, &options)
+    ///     .unwrap();
+    ///
+    /// std::fs::write("output.png", &apng).unwrap();
+    /// ```
+    ///
+    /// [`with_time`]: #method.with_time
+    pub fn render_animation(
+        &self,
+        width: i32,
+        height: i32,
+        format: AnimatedImageFormat,
+        options: &AnimationOptions,
+    ) -> Result<Vec<u8>, RenderingError> {
+        self.handle.0.render_animation(
+            width,
+            height,
+            self.dpi,
+            format,
+            options,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        )
+    }
+
+    /// Renders the whole document as PostScript or Encapsulated PostScript
+    ///
+    /// The document is fitted into a page of `width` by `height` points, as with
+    /// [`render_document`].  If `options.tight_bounding_box` is set, the page is cropped
+    /// to the ink extents of the drawing instead, which is useful for EPS files that get
+    /// embedded in other documents.
+    ///
+    /// Returns the contents of the PostScript file.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use librsvg::{PostScriptLevel, PostScriptOptions};
+    ///
+    /// let svg_handle = librsvg::Loader::new()
+    ///     .read_path("example.svg")
+    ///     .unwrap();
+    ///
+    /// let options = PostScriptOptions {
+    ///     level: PostScriptLevel::Level2,
+    ///     eps: true,
+    ///     tight_bounding_box: true,
+    /// };
+    ///
+    /// let eps = librsvg::CairoRenderer::new(&svg_handle)
+    ///     .render_postscript(640.0, 480.0, &options)
+    ///     .unwrap();
+    ///
+    /// std::fs::write("output.eps", &eps).unwrap();
+    /// ```
+    ///
+    /// [`render_document`]: #method.render_document
+    pub fn render_postscript(
+        &self,
+        width: f64,
+        height: f64,
+        options: &PostScriptOptions,
+    ) -> Result<Vec<u8>, RenderingError> {
+        assert!(width > 0.0 && width.is_finite());
+        assert!(height > 0.0 && height.is_finite());
+
+        self.seek();
+        self.handle.0.render_postscript(
+            width,
+            height,
+            self.dpi,
+            options,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        )
     }
 
     /// Computes the (ink_rect, logical_rect) of an SVG element, as if
@@ -519,12 +1578,280 @@ impl<'a> CairoRenderer<'a> {
         id: Option<&str>,
         viewport: &cairo::Rectangle,
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
+        self.seek();
         self.handle
             .0
             .get_geometry_for_layer(id, viewport, self.dpi, false)
             .map(|(i, l)| (i.into(), l.into()))
     }
 
+    /// Computes the ink rectangle of what the SVG draws inside a region of interest, as
+    /// if the SVG were rendered to a specific viewport.
+    ///
+    /// The `region` is in the same coordinate system as the `viewport`, and so is the
+    /// returned rectangle.  Returns `None` if nothing is drawn inside the region.
+    ///
+    /// Unlike [`geometry_for_layer`](#method.geometry_for_layer), this takes clipping
+    /// paths, masks, filters and transparency into account, because it renders the
+    /// region and finds the pixels that got painted.  The result is aligned to whole
+    /// pixels from the region's origin.  Viewers can use this to crop a document to its
+    /// visible contents, or to fit a part of it to a window.
+    ///
+    /// This renders the document into a temporary image as large as the region, so it
+    /// is as expensive as a normal render.
+    pub fn ink_rect_in_region(
+        &self,
+        viewport: &cairo::Rectangle,
+        region: &cairo::Rectangle,
+    ) -> Result<Option<cairo::Rectangle>, RenderingError> {
+        self.seek();
+        self.handle.0.get_ink_rect_in_region(
+            viewport,
+            region,
+            self.dpi,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        )
+    }
+
+    /// Returns where the markers of an SVG element and its descendants are placed, as
+    /// if the SVG were rendered to a specific viewport.
+    ///
+    /// Element IDs should look like an URL fragment identifier, as for
+    /// [`geometry_for_layer`](#method.geometry_for_layer), and the positions are in
+    /// the same coordinate system as the rectangles which that function returns.
+    /// You can pass `None` for the `id` to get the markers of the whole SVG.
+    ///
+    /// Each [`MarkerPosition`] has the vertex where a marker's reference point is
+    /// placed, and the direction of the path at that vertex.  Applications can use
+    /// this to hit-test markers like arrowheads.
+    ///
+    /// [`MarkerPosition`]: struct.MarkerPosition.html
+    pub fn marker_positions_for_layer(
+        &self,
+        id: Option<&str>,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Vec<MarkerPosition>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_marker_positions_for_layer(id, viewport, self.dpi, false)
+    }
+
+    /// Returns the shaped glyphs of the text in an SVG element and its descendants, as
+    /// if the SVG were rendered to a specific viewport.
+    ///
+    /// Element IDs should look like an URL fragment identifier, as for
+    /// [`geometry_for_layer`](#method.geometry_for_layer).  You can pass `None` for the
+    /// `id` to get the glyphs of the whole SVG.
+    ///
+    /// Each [`GlyphRun`] has the font that text layout chose, the glyph ids and their
+    /// positions, and a transformation to the same coordinate system as the rectangles
+    /// which `geometry_for_layer` returns.  PDF generators and plotters can use this to
+    /// emit real text instead of the outlines of the glyphs.
+    ///
+    /// [`GlyphRun`]: struct.GlyphRun.html
+    pub fn glyph_runs_for_layer(
+        &self,
+        id: Option<&str>,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Vec<GlyphRun>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_glyph_runs_for_layer(id, viewport, self.dpi, false)
+    }
+
+    /// Returns the runs of text which didn't render with the font family they asked
+    /// for, as if the SVG were rendered to a specific viewport.
+    ///
+    /// Each [`FontFallback`] has the characters that Pango rendered with another font,
+    /// or for which no installed font has glyphs, so they came out as "missing glyph"
+    /// boxes.  Automated pipelines can use this to detect that the fonts a document
+    /// needs are not installed.  The same information gets logged when the
+    /// `RSVG_LOG` environment variable is set.
+    ///
+    /// [`FontFallback`]: struct.FontFallback.html
+    pub fn font_fallbacks(
+        &self,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Vec<FontFallback>, RenderingError> {
+        self.seek();
+        self.handle.0.get_font_fallbacks(viewport, self.dpi, false)
+    }
+
+    /// Returns the clip region of an SVG element, as if the SVG were rendered to a
+    /// specific viewport.
+    ///
+    /// The clip region is the intersection of all the [`ClipShape`]s in the returned
+    /// list.  These come from the `clip-path` property of the element and its
+    /// ancestors, and from the viewports of elements like `svg` or `symbol` that clip
+    /// their contents.  An empty list means that the element is not clipped, or that it
+    /// is not rendered.  Applications can use this to turn clipping into geometric
+    /// trimming, for example for print.
+    ///
+    /// Element IDs should look like an URL fragment identifier, as for
+    /// [`geometry_for_layer`](#method.geometry_for_layer), and the shapes are in the
+    /// same coordinate system as the rectangles which that function returns.
+    ///
+    /// [`ClipShape`]: struct.ClipShape.html
+    pub fn clip_region_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Vec<ClipShape>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_clip_region_for_element(id, viewport, self.dpi, false)
+    }
+
+    /// Returns the transformation from the document's user space to the device space of
+    /// `cr`, as used by [`render_document`] with the same `cr` and `viewport`.
+    ///
+    /// The document's user space is the coordinate system of the toplevel `<svg>`
+    /// element's contents, after its `viewBox` and `preserveAspectRatio` have been
+    /// applied.  The transformation includes the current transformation matrix of `cr`
+    /// and the scaling of physical units by the renderer's DPI.  Applications can invert
+    /// it to map mouse clicks to document coordinates, or use it to draw annotations
+    /// aligned with the document.
+    ///
+    /// Returns `None` if the document would not be rendered, for example, if its
+    /// `viewBox` is empty.
+    ///
+    /// [`render_document`]: #method.render_document
+    pub fn document_transform(
+        &self,
+        cr: &cairo::Context,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Option<cairo::Matrix>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_transform_for_element(cr, None, viewport, self.dpi, false)
+    }
+
+    /// Returns the transformation from the user space of an SVG element to the device
+    /// space of `cr`, as used by [`render_document`] with the same `cr` and `viewport`.
+    ///
+    /// The element's user space is the coordinate system in which its geometry, like the
+    /// `x` and `y` of a `rect`, is specified, after its `transform` has been applied.
+    /// This is the same coordinate system in which render hooks get called.
+    ///
+    /// Element IDs should look like an URL fragment identifier, as for
+    /// [`geometry_for_layer`](#method.geometry_for_layer).  Returns `None` if the element
+    /// is not rendered.  If the element is instanced more than once with `<use>`, returns
+    /// the transformation of the first instance that gets rendered.
+    ///
+    /// [`render_document`]: #method.render_document
+    pub fn transform_for_element(
+        &self,
+        cr: &cairo::Context,
+        id: &str,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Option<cairo::Matrix>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_transform_for_element(cr, Some(id), viewport, self.dpi, false)
+    }
+
+    /// Returns how a shape element gets stroked, as used by [`render_document`] with the
+    /// same `cr` and `viewport`.
+    ///
+    /// The [`StrokeMetrics`] have the stroke's width, line caps and joins, and dash
+    /// pattern as they are computed for rendering, and the transformation from the
+    /// coordinate system in which they apply to the device space of `cr`.  Applications
+    /// that post-process the geometry from
+    /// [`path_segments_for_element`](#method.path_segments_for_element), like plotters,
+    /// can use this to reproduce the stroke exactly.
+    ///
+    /// Element IDs should look like an URL fragment identifier, as for
+    /// [`geometry_for_layer`](#method.geometry_for_layer).  Returns `None` if the element
+    /// is not a shape, if it has `stroke="none"`, or if it is not rendered.  If the
+    /// element is instanced more than once with `<use>`, returns the stroke of the first
+    /// instance that gets rendered.
+    ///
+    /// [`render_document`]: #method.render_document
+    /// [`StrokeMetrics`]: struct.StrokeMetrics.html
+    pub fn stroke_for_element(
+        &self,
+        cr: &cairo::Context,
+        id: &str,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Option<StrokeMetrics>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_stroke_for_element(cr, id, viewport, self.dpi, false)
+    }
+
+    /// Returns the path of a shape element as a list of [`PathSegment`] with absolute
+    /// coordinates.
+    ///
+    /// Element IDs should look like an URL fragment identifier, as for
+    /// [`geometry_for_layer`](#method.geometry_for_layer).  Basic shapes like `rect`,
+    /// `circle`, `ellipse`, `line`, `polyline` and `polygon` are converted to paths,
+    /// and elliptical arcs are converted to cubic Bézier curves, so applications can
+    /// process the geometry of any shape without parsing its attributes.
+    ///
+    /// The segments are in the element's own user space, that is, before its
+    /// `transform` is applied.  The `viewport` is used to resolve percentage lengths,
+    /// as if the SVG were rendered to it.  Returns an empty list if the element is
+    /// not a shape, or if it is not rendered.
+    ///
+    /// [`PathSegment`]: enum.PathSegment.html
+    pub fn path_segments_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+    ) -> Result<Vec<PathSegment>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_path_segments_for_element(id, viewport, self.dpi, false)
+    }
+
+    /// Returns the length of the path of a shape element, like SVG's `getTotalLength()`.
+    ///
+    /// The length is measured in the element's user space, along the same path that
+    /// [`path_segments_for_element`](#method.path_segments_for_element) returns.
+    /// Returns 0 if the element is not a shape.
+    pub fn path_length_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+    ) -> Result<f64, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_path_length_for_element(id, viewport, self.dpi, false)
+    }
+
+    /// Returns the point at a distance along the path of a shape element, like SVG's
+    /// `getPointAtLength()`.
+    ///
+    /// The [`PathPoint`] also has the direction of the tangent to the path at that
+    /// point.  The `length` is in the element's user space, as for
+    /// [`path_length_for_element`](#method.path_length_for_element), and it gets
+    /// clamped to the ends of the path.  Returns `None` if the element is not a shape,
+    /// or if its path is empty.
+    ///
+    /// [`PathPoint`]: struct.PathPoint.html
+    pub fn point_at_length_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+        length: f64,
+    ) -> Result<Option<PathPoint>, RenderingError> {
+        self.seek();
+        self.handle
+            .0
+            .get_point_at_length_for_element(id, viewport, length, self.dpi, false)
+    }
+
     /// Renders a single SVG element in the same place as for a whole SVG document
     ///
     /// This is equivalent to `render_document`, but renders only a single element and its
@@ -550,9 +1877,17 @@ impl<'a> CairoRenderer<'a> {
         id: Option<&str>,
         viewport: &cairo::Rectangle,
     ) -> Result<(), RenderingError> {
-        self.handle
-            .0
-            .render_layer(cr, id, viewport, self.dpi, false)
+        self.seek();
+        self.handle.0.render_layer(
+            cr,
+            id,
+            viewport,
+            self.dpi,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        )
     }
 
     /// Computes the (ink_rect, logical_rect) of a single SVG element
@@ -561,6 +1896,7 @@ impl<'a> CairoRenderer<'a> {
     /// its transformation matrix, this other function will compute the element's geometry
     /// as if it were being rendered under an identity transformation by itself.  That is,
     /// the resulting geometry is as if the element got extracted by itself from the SVG.
+    /// You can change this with [`with_element_context`](#method.with_element_context).
     ///
     /// This function is the counterpart to `render_element`.
     ///
@@ -589,9 +1925,10 @@ impl<'a> CairoRenderer<'a> {
         &self,
         id: Option<&str>,
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
+        self.seek();
         self.handle
             .0
-            .get_geometry_for_element(id, self.dpi, false)
+            .get_geometry_for_element(id, self.dpi, self.element_context, false)
             .map(|(i, l)| (i.into(), l.into()))
     }
 
@@ -608,6 +1945,9 @@ impl<'a> CairoRenderer<'a> {
     /// The `element_viewport` gives the position and size at which the named element will
     /// be rendered.  FIXME: mention proportional scaling.
     ///
+    /// How much of the context of the element's ancestors gets applied is configured
+    /// with [`with_element_context`](#method.with_element_context).
+    ///
     /// The `cr` must be in a `cairo::Status::Success` state, or this function
     /// will not render anything, and instead will return
     /// `RenderingError::Cairo` with the `cr`'s current error state.
@@ -617,8 +1957,17 @@ impl<'a> CairoRenderer<'a> {
         id: Option<&str>,
         element_viewport: &cairo::Rectangle,
     ) -> Result<(), RenderingError> {
-        self.handle
-            .0
-            .render_element(cr, id, element_viewport, self.dpi, false)
+        self.seek();
+        self.handle.0.render_element(
+            cr,
+            id,
+            element_viewport,
+            self.dpi,
+            self.element_context,
+            &self.render_hooks,
+            self.quality,
+            &self.element_overrides,
+            false,
+        )
     }
 }
@@ -1,10 +1,43 @@
 use cairo;
-use librsvg::{CairoRenderer, DefsLookupErrorKind, HrefError, RenderingError};
+use glib;
+use librsvg::{
+    AnimatedImageFormat,
+    ApplicationFonts,
+    AnimationOptions,
+    AttributeChange,
+    CairoRenderer,
+    ColorScheme,
+    DeclarationInfo,
+    DefsLookupErrorKind,
+    DiffElement,
+    DocumentCache,
+    ElementOverrides,
+    FontFallbackKind,
+    FontFamilyMap,
+    GenericFontFamily,
+    HookAction,
+    HrefError,
+    Loader,
+    MediaFeatures,
+    Origin,
+    PathDataRecovery,
+    PostScriptLevel,
+    PostScriptOptions,
+    RenderHooks,
+    RenderQuality,
+    RenderingError,
+    SvgProfile,
+    ValidationIssueKind,
+};
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use rsvg_internals::rect::RectangleExt;
 use rsvg_internals::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
 
 mod utils;
-use self::utils::{compare_to_surface, load_svg};
+use self::utils::{compare_to_surface, load_svg, load_svg_with, render_document, SurfaceSize};
 
 #[test]
 fn has_element_with_id_works() {
@@ -89,6 +122,200 @@ fn render_layer() {
     compare_to_surface(&output_surf, &reference_surf, "render_layer");
 }
 
+#[test]
+fn render_hooks_work() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect id="foo" x="10" y="10" width="30" height="30" fill="#00ff00"/>
+  <g transform="translate(50, 50)">
+    <rect id="bar" x="0" y="0" width="30" height="30" fill="#0000ff"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let num_rects = Rc::new(Cell::new(0));
+    let fills = Rc::new(Cell::new(None));
+
+    let mut hooks = RenderHooks::new();
+
+    // Replace #foo with a red placeholder
+    hooks
+        .add_pre_draw_hook("#foo", |cr, element| {
+            assert_eq!(element.element_name(), "rect");
+            assert_eq!(element.id(), Some(String::from("foo")));
+
+            cr.rectangle(0.0, 0.0, 20.0, 20.0);
+            cr.set_source_rgba(1.0, 0.0, 0.0, 1.0);
+            cr.fill();
+
+            HookAction::Skip
+        })
+        .unwrap();
+
+    {
+        let num_rects = num_rects.clone();
+        let fills = fills.clone();
+
+        // Hooks get the element's user space, so this is drawn over #bar
+        hooks
+            .add_post_draw_hook("g > rect", move |cr, element| {
+                num_rects.set(num_rects.get() + 1);
+                fills.set(element.get_computed_property("fill"));
+
+                cr.rectangle(0.0, 0.0, 10.0, 10.0);
+                cr.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+                cr.fill();
+            })
+            .unwrap();
+    }
+
+    assert!(hooks
+        .add_pre_draw_hook("rect[", |_, _| HookAction::Continue)
+        .is_err());
+
+    let renderer = CairoRenderer::new(&svg).with_render_hooks(hooks);
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    let res = {
+        let cr = cairo::Context::new(&output);
+        let viewport = cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+
+        renderer.render_document(&cr, &viewport)
+    };
+
+    let output_surf = res
+        .and_then(|_| Ok(SharedImageSurface::new(output, SurfaceType::SRgb).unwrap()))
+        .unwrap();
+
+    assert_eq!(num_rects.get(), 1);
+    assert_eq!(fills.take(), Some(String::from("rgb(0, 0, 255)")));
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 20.0, 20.0);
+        cr.set_source_rgba(1.0, 0.0, 0.0, 1.0);
+        cr.fill();
+
+        cr.rectangle(50.0, 50.0, 30.0, 30.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.fill();
+
+        cr.rectangle(50.0, 50.0, 10.0, 10.0);
+        cr.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "render_hooks_work");
+}
+
+#[test]
+fn placeholders_are_drawn_for_unsupported_elements() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:foo="http://example.com/foo" width="100" height="100">
+  <foreignObject x="10" y="10" width="30" height="30">
+    <rect width="100" height="100" fill="red"/>
+  </foreignObject>
+  <foo:widget x="50" y="50" width="20" height="40"/>
+  <foo:sizeless x="0" y="0"/>
+</svg>
+"##,
+    );
+
+    let names = Rc::new(RefCell::new(Vec::new()));
+
+    let mut hooks = RenderHooks::new();
+
+    {
+        let names = names.clone();
+
+        hooks.set_placeholder_callback(move |cr, element, rect| {
+            names.borrow_mut().push(element.element_name());
+
+            cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+            cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+            cr.fill();
+        });
+    }
+
+    let renderer = CairoRenderer::new(&svg).with_render_hooks(hooks);
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    let res = {
+        let cr = cairo::Context::new(&output);
+        let viewport = cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+
+        renderer.render_document(&cr, &viewport)
+    };
+
+    let output_surf = res
+        .and_then(|_| Ok(SharedImageSurface::new(output, SurfaceType::SRgb).unwrap()))
+        .unwrap();
+
+    assert_eq!(*names.borrow(), vec!["foreignObject", "widget"]);
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(10.0, 10.0, 30.0, 30.0);
+        cr.rectangle(50.0, 50.0, 20.0, 40.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "placeholders_are_drawn_for_unsupported_elements",
+    );
+
+    // Without a placeholder, unsupported elements are not rendered at all
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let empty_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+    let empty_surf = SharedImageSurface::new(empty_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &empty_surf,
+        "placeholders_are_drawn_for_unsupported_elements_none",
+    );
+}
+
 #[test]
 fn untransformed_element() {
     // This has a rectangle inside a transformed group.  The rectangle
@@ -178,3 +405,1641 @@ fn untransformed_element() {
         "untransformed_element",
     );
 }
+
+#[test]
+fn is_element_visible_works() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <rect id="in_defs" width="10" height="10"/>
+  </defs>
+  <rect id="plain" width="10" height="10"/>
+  <g display="none">
+    <rect id="display_none" width="10" height="10"/>
+  </g>
+  <rect id="hidden" width="10" height="10" visibility="hidden"/>
+  <g id="hidden_group" visibility="hidden">
+    <rect id="hidden_child" width="10" height="10"/>
+    <rect id="visible_child" width="10" height="10" visibility="visible"/>
+  </g>
+  <switch>
+    <rect id="chosen" width="10" height="10"/>
+    <rect id="not_chosen" width="10" height="10"/>
+  </switch>
+  <rect id="unknown_extension" width="10" height="10" requiredExtensions="http://example.com/foo"/>
+  <svg width="0" height="10">
+    <rect id="zero_size" width="10" height="10"/>
+  </svg>
+</svg>
+"##,
+    );
+
+    assert!(svg.is_element_visible("#plain").unwrap());
+    assert!(svg.is_element_visible("#chosen").unwrap());
+    assert!(svg.is_element_visible("#visible_child").unwrap());
+
+    assert!(!svg.is_element_visible("#in_defs").unwrap());
+    assert!(!svg.is_element_visible("#display_none").unwrap());
+    assert!(!svg.is_element_visible("#hidden").unwrap());
+    assert!(!svg.is_element_visible("#hidden_group").unwrap());
+    assert!(!svg.is_element_visible("#hidden_child").unwrap());
+    assert!(!svg.is_element_visible("#not_chosen").unwrap());
+    assert!(!svg.is_element_visible("#unknown_extension").unwrap());
+    assert!(!svg.is_element_visible("#zero_size").unwrap());
+
+    assert_eq!(
+        svg.is_element_visible("#nonexistent"),
+        Err(RenderingError::InvalidId(DefsLookupErrorKind::NotFound))
+    );
+}
+
+#[test]
+fn set_element_visible_toggles_layers() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g id="walls">
+    <rect id="wall" width="10" height="10"/>
+  </g>
+  <g id="wiring" style="display:none">
+    <rect id="wire" width="10" height="10"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let visible = |id| svg.is_element_visible(id).unwrap();
+
+    assert!(visible("#wall"));
+    assert!(!visible("#wire"));
+
+    svg.set_element_visible("#walls", false).unwrap();
+    svg.set_element_visible("#wiring", true).unwrap();
+
+    assert!(!visible("#wall"));
+    assert!(visible("#wire"));
+    assert_eq!(
+        svg.get_computed_property("#walls", "display").unwrap(),
+        Some("none".to_string())
+    );
+
+    svg.reset_element_visibility("#walls").unwrap();
+    svg.reset_element_visibility("#wiring").unwrap();
+
+    assert!(visible("#wall"));
+    assert!(!visible("#wire"));
+
+    assert_eq!(
+        svg.set_element_visible("#nonexistent", true),
+        Err(RenderingError::InvalidId(DefsLookupErrorKind::NotFound))
+    );
+}
+
+#[test]
+fn document_cache_shares_identical_documents() {
+    let load = |cache: &DocumentCache, data: &'static [u8], recovery| {
+        load_svg_with(
+            Loader::new()
+                .with_path_data_recovery(recovery)
+                .with_cache(cache),
+            data,
+        )
+    };
+
+    let data: &'static [u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect id="foo" width="10" height="10"/>
+</svg>
+"#;
+
+    let other_data: &'static [u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect id="bar" width="10" height="10"/>
+</svg>
+"#;
+
+    let cache = DocumentCache::new();
+    assert!(cache.is_empty());
+
+    let first = load(&cache, data, PathDataRecovery::RenderPrefix);
+    let second = load(&cache, data, PathDataRecovery::RenderPrefix);
+    assert_eq!(cache.len(), 1);
+
+    // Changing one handle does not change the others
+    first.set_element_visible("#foo", false).unwrap();
+    assert!(!first.is_element_visible("#foo").unwrap());
+    assert!(second.is_element_visible("#foo").unwrap());
+
+    second.set_stylesheet("rect { fill: lime; }").unwrap();
+    assert_eq!(
+        second.get_computed_property("#foo", "fill").unwrap(),
+        Some(String::from("rgb(0, 255, 0)"))
+    );
+    assert_eq!(
+        first.get_computed_property("#foo", "fill").unwrap(),
+        Some(String::from("rgb(0, 0, 0)"))
+    );
+
+    // New handles still get the document as it was loaded
+    let third = load(&cache, data, PathDataRecovery::RenderPrefix);
+    assert_eq!(cache.len(), 1);
+    assert!(third.is_element_visible("#foo").unwrap());
+    assert_eq!(
+        third.get_computed_property("#foo", "fill").unwrap(),
+        Some(String::from("rgb(0, 0, 0)"))
+    );
+
+    // Different data or different options are parsed separately
+    let other = load(&cache, other_data, PathDataRecovery::RenderPrefix);
+    assert!(other.has_element_with_id("#bar").unwrap());
+    assert_eq!(cache.len(), 2);
+
+    let strict = load(&cache, data, PathDataRecovery::Strict);
+    assert!(strict.is_element_visible("#foo").unwrap());
+    assert_eq!(cache.len(), 3);
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn set_will_change_keeps_renders_up_to_date() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" x="0" y="0" width="1" height="1">
+    <feOffset/>
+  </filter>
+  <g filter="url(#filter)">
+    <rect width="100" height="100" fill="lime"/>
+    <rect id="marker" x="25" y="25" width="50" height="50" fill="blue"/>
+  </g>
+</svg>
+"##,
+    );
+
+    svg.set_will_change("#marker", true).unwrap();
+
+    let render = || {
+        render_document(
+            &svg,
+            SurfaceSize(100, 100),
+            |_cr| (),
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 100.0,
+            },
+        )
+        .unwrap()
+    };
+
+    let reference = |with_marker| {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+        {
+            let cr = cairo::Context::new(&surface);
+            cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+            cr.paint();
+
+            if with_marker {
+                cr.rectangle(25.0, 25.0, 50.0, 50.0);
+                cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+                cr.fill();
+            }
+        }
+
+        SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap()
+    };
+
+    compare_to_surface(&render(), &reference(true), "will_change_1");
+
+    svg.set_element_visible("#marker", false).unwrap();
+    compare_to_surface(&render(), &reference(false), "will_change_2");
+
+    svg.reset_element_visibility("#marker").unwrap();
+    compare_to_surface(&render(), &reference(true), "will_change_3");
+
+    assert_eq!(
+        svg.set_will_change("#nonexistent", true),
+        Err(RenderingError::InvalidId(DefsLookupErrorKind::NotFound))
+    );
+}
+
+#[test]
+fn element_overrides_apply_only_to_renderer() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect id="shown" width="50" height="100" fill="lime"/>
+  <rect id="hidden" x="50" width="50" height="100" fill="lime" display="none"/>
+  <rect id="faded" y="50" width="100" height="50" fill="blue" opacity="0.5"/>
+</svg>
+"##,
+    );
+
+    let mut overrides = ElementOverrides::new();
+    overrides.set_visible("#shown", false);
+    overrides.set_visible("#hidden", true);
+    overrides.set_opacity("#faded", 1.0);
+
+    let renderer = CairoRenderer::new(&svg).with_element_overrides(overrides);
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&output);
+        let viewport = cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+
+        renderer.render_document(&cr, &viewport).unwrap();
+    }
+
+    let output_surf = SharedImageSurface::new(output, SurfaceType::SRgb).unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(50.0, 0.0, 50.0, 50.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+
+        cr.rectangle(0.0, 50.0, 100.0, 50.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "element_overrides");
+
+    // The document is unchanged
+    assert!(svg.is_element_visible("#shown").unwrap());
+    assert!(!svg.is_element_visible("#hidden").unwrap());
+    assert_eq!(
+        svg.get_computed_property("#faded", "opacity").unwrap(),
+        Some("0.5".to_string())
+    );
+}
+
+#[test]
+fn get_computed_property_works() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    .red { fill: red; }
+  </style>
+  <g stroke-width="4" font-weight="bold">
+    <rect id="rect" class="red" width="10" height="10" stroke="url(#grad) blue"/>
+  </g>
+  <text id="text" text-decoration="underline line-through" stroke-dasharray="1 2mm">hello</text>
+</svg>
+"##,
+    );
+
+    let prop = |id, name| svg.get_computed_property(id, name).unwrap();
+
+    assert_eq!(prop("#rect", "fill"), Some("rgb(255, 0, 0)".to_string()));
+    assert_eq!(
+        prop("#rect", "stroke"),
+        Some("url(#grad) rgb(0, 0, 255)".to_string())
+    );
+    assert_eq!(prop("#rect", "stroke-width"), Some("4px".to_string()));
+    assert_eq!(prop("#rect", "font-weight"), Some("bold".to_string()));
+    assert_eq!(prop("#rect", "display"), Some("inline".to_string()));
+    assert_eq!(prop("#rect", "opacity"), Some("1".to_string()));
+    assert_eq!(prop("#rect", "clip-path"), Some("none".to_string()));
+
+    assert_eq!(
+        prop("#text", "text-decoration"),
+        Some("underline line-through".to_string())
+    );
+    assert_eq!(
+        prop("#text", "stroke-dasharray"),
+        Some("1px, 2mm".to_string())
+    );
+
+    assert_eq!(prop("#rect", "not-a-property"), None);
+
+    assert_eq!(
+        svg.get_computed_property("#nonexistent", "fill"),
+        Err(RenderingError::InvalidId(DefsLookupErrorKind::NotFound))
+    );
+}
+
+#[test]
+fn marker_shorthand_works_in_stylesheets() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    .edge { marker: url(#arrow); }
+    .edge.plain { marker-mid: none !important; }
+    .edge.plain { marker: url(#dot); }
+    #inheriting { marker: inherit; }
+  </style>
+  <path id="edge" class="edge" d="M 10 10 L 50 50 L 90 10"/>
+  <path id="plain" class="edge plain" d="M 10 10 L 50 50 L 90 10"/>
+  <g marker-start="url(#dot)">
+    <path id="inheriting" marker-start="url(#arrow)" d="M 10 10 L 50 50 L 90 10"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let prop = |id, name| svg.get_computed_property(id, name).unwrap().unwrap();
+
+    assert_eq!(prop("#edge", "marker-start"), "url(#arrow)");
+    assert_eq!(prop("#edge", "marker-mid"), "url(#arrow)");
+    assert_eq!(prop("#edge", "marker-end"), "url(#arrow)");
+
+    // The shorthand doesn't override a longhand that was declared as important
+    assert_eq!(prop("#plain", "marker-start"), "url(#dot)");
+    assert_eq!(prop("#plain", "marker-mid"), "none");
+    assert_eq!(prop("#plain", "marker-end"), "url(#dot)");
+
+    assert_eq!(prop("#inheriting", "marker-start"), "url(#dot)");
+    assert_eq!(prop("#inheriting", "marker-mid"), "none");
+}
+
+#[test]
+fn get_stylesheet_rules_works() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    rect, .unused { fill: red !important; stroke-width: 2; foo: bar; }
+    #baz { stroke: inherit; }
+  </style>
+  <rect id="baz" width="10" height="10"/>
+</svg>
+"##,
+    );
+
+    let rules = svg.get_stylesheet_rules();
+    assert_eq!(rules.len(), 2);
+
+    let rule = &rules[0];
+    assert_eq!(rule.origin, Origin::Author);
+
+    assert_eq!(rule.selectors.len(), 2);
+    assert_eq!(rule.selectors[0].selector, "rect");
+    assert!(rule.selectors[0].matches_any_element);
+    assert_eq!(rule.selectors[1].selector, ".unused");
+    assert!(!rule.selectors[1].matches_any_element);
+
+    assert_eq!(
+        rule.declarations,
+        vec![
+            DeclarationInfo {
+                property: "fill".to_string(),
+                value: "rgb(255, 0, 0)".to_string(),
+                important: true,
+            },
+            DeclarationInfo {
+                property: "stroke-width".to_string(),
+                value: "2px".to_string(),
+                important: false,
+            },
+        ]
+    );
+
+    assert_eq!(rule.invalid_declarations, vec!["foo: bar".to_string()]);
+
+    let rule = &rules[1];
+    assert_eq!(rule.selectors[0].selector, "#baz");
+    assert!(rule.selectors[0].matches_any_element);
+    assert!(rule.selectors[0].specificity > rules[0].selectors[0].specificity);
+    assert_eq!(rule.declarations[0].value, "inherit");
+}
+
+#[test]
+fn diff_works() {
+    let old = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g id="layer">
+    <rect width="10" height="10" fill="red"/>
+    <circle r="5"/>
+  </g>
+  <text id="label">Hello</text>
+  <rect id="moved" width="5" height="5"/>
+</svg>
+"##,
+    );
+
+    let new = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g id="layer">
+    <rect width="10" height="20" stroke="blue"/>
+    <ellipse rx="5" ry="5"/>
+    <rect id="moved" width="5" height="5"/>
+  </g>
+  <text id="label">World</text>
+</svg>
+"##,
+    );
+
+    assert!(old.diff(&old).is_empty());
+
+    let diff = old.diff(&new);
+
+    assert_eq!(
+        diff.removed,
+        vec![DiffElement {
+            element_name: "circle".to_string(),
+            id: None,
+            path: "#layer/circle[1]".to_string(),
+        }]
+    );
+
+    assert_eq!(
+        diff.added,
+        vec![DiffElement {
+            element_name: "ellipse".to_string(),
+            id: None,
+            path: "#layer/ellipse[1]".to_string(),
+        }]
+    );
+
+    assert_eq!(diff.changed.len(), 3);
+
+    let rect = &diff.changed[0];
+    assert_eq!(rect.element.path, "#layer/rect[1]");
+    assert_eq!(rect.old_path, "#layer/rect[1]");
+    assert!(!rect.text_changed);
+    assert_eq!(
+        rect.attributes,
+        vec![
+            AttributeChange {
+                name: "fill".to_string(),
+                old_value: Some("red".to_string()),
+                new_value: None,
+            },
+            AttributeChange {
+                name: "height".to_string(),
+                old_value: Some("10".to_string()),
+                new_value: Some("20".to_string()),
+            },
+            AttributeChange {
+                name: "stroke".to_string(),
+                old_value: None,
+                new_value: Some("blue".to_string()),
+            },
+        ]
+    );
+
+    let moved = &diff.changed[1];
+    assert_eq!(moved.element.id, Some("moved".to_string()));
+    assert_eq!(moved.element.path, "#layer/rect[2]");
+    assert_eq!(moved.old_path, "/svg[1]/rect[1]");
+    assert!(moved.attributes.is_empty());
+
+    let label = &diff.changed[2];
+    assert_eq!(label.element.id, Some("label".to_string()));
+    assert!(label.attributes.is_empty());
+    assert!(label.text_changed);
+}
+
+#[test]
+fn image_resolver_supplies_images() {
+    let avatar = cairo::ImageSurface::create(cairo::Format::ARgb32, 20, 20).unwrap();
+
+    {
+        let cr = cairo::Context::new(&avatar);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.paint();
+    }
+
+    let num_calls = Rc::new(Cell::new(0));
+
+    let svg = {
+        let num_calls = num_calls.clone();
+
+        load_svg_with(
+            Loader::new().with_image_resolver(move |href| {
+                num_calls.set(num_calls.get() + 1);
+
+                if href == "photo://user-avatar" {
+                    Some(avatar.clone())
+                } else {
+                    None
+                }
+            }),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     width="100" height="100">
+  <image xlink:href="photo://user-avatar" x="10" y="10" width="20" height="20"/>
+  <image xlink:href="photo://user-avatar" x="50" y="50" width="20" height="20"/>
+</svg>
+"##,
+        )
+    };
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    assert_eq!(num_calls.get(), 1);
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.rectangle(10.0, 10.0, 20.0, 20.0);
+        cr.rectangle(50.0, 50.0, 20.0, 20.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "image_resolver_supplies_images",
+    );
+}
+
+#[test]
+fn with_languages_overrides_system_language() {
+    let load = |languages: &[&str]| {
+        load_svg_with(
+            Loader::new().with_languages(languages),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <switch>
+    <rect id="spanish" systemLanguage="es" width="10" height="10"/>
+    <rect id="english" systemLanguage="en" width="10" height="10"/>
+    <rect id="fallback" width="10" height="10"/>
+  </switch>
+</svg>
+"##,
+        )
+    };
+
+    let svg = load(&["es", "en"]);
+    assert!(svg.is_element_visible("#spanish").unwrap());
+    assert!(!svg.is_element_visible("#english").unwrap());
+
+    let svg = load(&["en"]);
+    assert!(!svg.is_element_visible("#spanish").unwrap());
+    assert!(svg.is_element_visible("#english").unwrap());
+
+    let svg = load(&["de"]);
+    assert!(svg.is_element_visible("#fallback").unwrap());
+}
+
+#[test]
+fn switch_chooses_best_language_match() {
+    let load = |languages: &[&str]| {
+        load_svg_with(
+            Loader::new().with_languages(languages),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <switch>
+    <rect id="british" systemLanguage="en-GB" width="10" height="10"/>
+    <rect id="english" systemLanguage="en" width="10" height="10"/>
+    <rect id="mexican" systemLanguage="es-MX" width="10" height="10"/>
+    <rect id="fallback" width="10" height="10"/>
+  </switch>
+</svg>
+"##,
+        )
+    };
+
+    let visible = |languages: &[&str]| {
+        let svg = load(languages);
+
+        ["#british", "#english", "#mexican", "#fallback"]
+            .iter()
+            .cloned()
+            .filter(|id| svg.is_element_visible(id).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // The first child that matches is not necessarily the best match
+    assert_eq!(visible(&["en"]), ["#english"]);
+    assert_eq!(visible(&["en-GB"]), ["#british"]);
+
+    // Falls back to other regions of the same language
+    assert_eq!(visible(&["en-US"]), ["#english"]);
+    assert_eq!(visible(&["es-ES"]), ["#mexican"]);
+
+    // Earlier languages in the list win, even if they only match approximately
+    assert_eq!(visible(&["es", "en-GB"]), ["#mexican"]);
+    assert_eq!(visible(&["fr", "en-AU"]), ["#english"]);
+
+    assert_eq!(visible(&["de"]), ["#fallback"]);
+}
+
+#[test]
+fn path_data_recovery_modes() {
+    let logical_rect = |recovery: PathDataRecovery| {
+        let svg = load_svg_with(
+            Loader::new().with_path_data_recovery(recovery),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <path id="path" d="M10 10 H 50 V 30 x V 50 H 10 Z"/>
+</svg>
+"##,
+        );
+
+        let renderer = CairoRenderer::new(&svg);
+        let (_, logical_r) = renderer.geometry_for_element(Some("#path")).unwrap();
+        logical_r
+    };
+
+    assert_eq!(
+        logical_rect(PathDataRecovery::Strict),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+        }
+    );
+
+    assert_eq!(
+        logical_rect(PathDataRecovery::RenderPrefix),
+        cairo::Rectangle {
+            x: 10.0,
+            y: 10.0,
+            width: 40.0,
+            height: 20.0,
+        }
+    );
+
+    assert_eq!(
+        logical_rect(PathDataRecovery::SkipToken),
+        cairo::Rectangle {
+            x: 10.0,
+            y: 10.0,
+            width: 40.0,
+            height: 40.0,
+        }
+    );
+}
+
+#[test]
+fn multiline_text_starts_new_lines() {
+    let logical_rect = |multiline: bool| {
+        let svg = load_svg_with(
+            Loader::new().with_multiline_text(multiline),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="200">
+  <text id="text" x="10" y="50" font-size="20">
+    Hello
+    world
+  </text>
+</svg>
+"##,
+        );
+
+        let renderer = CairoRenderer::new(&svg);
+        let (_, logical_r) = renderer.geometry_for_element(Some("#text")).unwrap();
+        logical_r
+    };
+
+    let single = logical_rect(false);
+    let multiline = logical_rect(true);
+
+    // "Helloworld" on one line, versus "Hello" and "world" on two lines
+    assert_eq!(multiline.x, single.x);
+    assert!(multiline.width < single.width);
+    assert!(multiline.height >= single.height + 19.0);
+}
+
+#[test]
+fn text_path_follows_the_path() {
+    let ink_rect = |text_path: &str| {
+        let document = format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="200">
+  <path id="path" d="M 50 0 L 50 200"/>
+  <text id="text" font-family="sans-serif" font-size="20">{}</text>
+</svg>
+"##,
+            text_path
+        );
+
+        let svg = load_svg_with(Loader::new(), document.as_bytes());
+
+        let renderer = CairoRenderer::new(&svg);
+        let (ink_r, _) = renderer.geometry_for_element(Some("#text")).unwrap();
+        ink_r
+    };
+
+    // The path goes down, so the text runs downwards from the middle of the path,
+    // with the glyphs on the right of the path
+    let left = ink_rect(r##"<textPath xlink:href="#path" startOffset="50%">Hello</textPath>"##);
+    assert!(left.height > left.width);
+    assert!(left.x > 45.0 && left.x + left.width < 75.0);
+    assert!(left.y > 99.0);
+
+    // The path attribute works like a reference to a path
+    let inline =
+        ink_rect(r##"<textPath path="M 50 0 L 50 200" startOffset="50%">Hello</textPath>"##);
+    assert_eq!(inline, left);
+
+    // With side="right" the text runs along the reversed path, so it goes up from the
+    // middle of the path, on the other side of it
+    let right = ink_rect(
+        r##"<textPath xlink:href="#path" startOffset="50%" side="right">Hello</textPath>"##,
+    );
+    assert!(right.height > right.width);
+    assert!(right.x > 25.0 && right.x + right.width < 55.0);
+    assert!(right.y + right.height < 101.0);
+}
+
+#[test]
+fn text_length_fits_text() {
+    let ink_rect = |attributes: &str| {
+        let document = format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="100">
+  <text id="text" x="10" y="50" font-family="sans-serif" font-size="20" {}>Hello world</text>
+</svg>
+"##,
+            attributes
+        );
+
+        let svg = load_svg_with(Loader::new(), document.as_bytes());
+
+        let renderer = CairoRenderer::new(&svg);
+        let (ink_r, _) = renderer.geometry_for_element(Some("#text")).unwrap();
+        ink_r
+    };
+
+    let natural = ink_rect("");
+
+    // Spacing out the characters moves them, but doesn't change their size
+    let narrow = ink_rect(r#"textLength="100""#);
+    let wide = ink_rect(r#"textLength="300""#);
+    assert!(narrow.width < natural.width);
+    assert!(wide.width > natural.width);
+    assert!(wide.width - narrow.width > 150.0);
+    assert!((wide.height - narrow.height).abs() < 0.01);
+
+    // Scaling the glyphs stretches the whole text from its start
+    let scaled_narrow = ink_rect(r#"textLength="100" lengthAdjust="spacingAndGlyphs""#);
+    let scaled_wide = ink_rect(r#"textLength="300" lengthAdjust="spacingAndGlyphs""#);
+    assert!((scaled_wide.width / scaled_narrow.width - 3.0).abs() < 0.01);
+    assert!((scaled_wide.height - natural.height).abs() < 0.01);
+    assert!(scaled_narrow.x > 9.99 && scaled_wide.x > 9.99);
+}
+
+#[test]
+fn ink_rect_in_region_only_counts_what_is_drawn_there() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="20" height="20"/>
+  <rect x="60" y="60" width="20" height="20"/>
+  <rect x="0" y="0" width="100" height="100" fill="none" stroke="none"/>
+</svg>
+"##,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+    let viewport = cairo::Rectangle::new(0.0, 0.0, 100.0, 100.0);
+
+    assert_eq!(
+        renderer
+            .ink_rect_in_region(&viewport, &cairo::Rectangle::new(0.0, 0.0, 50.0, 50.0))
+            .unwrap(),
+        Some(cairo::Rectangle::new(10.0, 10.0, 20.0, 20.0))
+    );
+
+    assert_eq!(
+        renderer
+            .ink_rect_in_region(&viewport, &cairo::Rectangle::new(20.0, 20.0, 80.0, 80.0))
+            .unwrap(),
+        Some(cairo::Rectangle::new(20.0, 20.0, 60.0, 60.0))
+    );
+
+    // Nothing is drawn between the rectangles, even if that's inside their bounding box
+    assert_eq!(
+        renderer
+            .ink_rect_in_region(&viewport, &cairo::Rectangle::new(35.0, 35.0, 20.0, 20.0))
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn resource_budget_aborts_expensive_renders() {
+    let render_with_budget = |budget: u64| {
+        let svg = load_svg_with(
+            Loader::new().with_resource_budget(budget),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+  <rect width="100" height="100" fill="lime"/>
+</svg>
+"##,
+        );
+
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+        let cr = cairo::Context::new(&surface);
+
+        CairoRenderer::new(&svg).render_document(
+            &cr,
+            &cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 100.0,
+            },
+        )
+    };
+
+    // Ten shapes that cover the whole 100x100 canvas
+    assert!(render_with_budget(100_000).is_ok());
+    assert_eq!(
+        render_with_budget(50_000),
+        Err(RenderingError::ResourceBudgetExceeded)
+    );
+}
+
+#[test]
+fn read_fragment_places_snippet_in_viewport() {
+    let svg = Loader::new()
+        .read_fragment(
+            r##"<rect id="rect" x="2" y="4" width="6" height="8"/>
+                <use id="use" xlink:href="#rect" x="10"/>"##,
+            20.0,
+            20.0,
+        )
+        .unwrap();
+
+    let renderer = CairoRenderer::new(&svg);
+
+    // The fragment's coordinates get scaled to the viewport
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 40.0,
+        height: 40.0,
+    };
+
+    let (_, logical_r) = renderer.geometry_for_layer(Some("#use"), &viewport).unwrap();
+    assert_eq!(
+        logical_r,
+        cairo::Rectangle {
+            x: 24.0,
+            y: 8.0,
+            width: 12.0,
+            height: 16.0,
+        }
+    );
+
+    assert!(Loader::new().read_fragment("<rect", 10.0, 10.0).is_err());
+}
+
+#[test]
+fn set_target_selects_target_element() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    .tab { display: none; }
+    .tab:target { display: inline; }
+    #tab1:not(:target) rect { fill: blue; }
+  </style>
+  <g id="tab1" class="tab">
+    <rect id="rect1" width="10" height="10"/>
+  </g>
+  <g id="tab2" class="tab">
+    <rect id="rect2" width="10" height="10"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let visible = |id| svg.is_element_visible(id).unwrap();
+
+    assert_eq!(svg.target(), None);
+    assert!(!visible("#rect1"));
+    assert!(!visible("#rect2"));
+
+    svg.set_target(Some("#tab2")).unwrap();
+    assert_eq!(svg.target(), Some("tab2".to_string()));
+    assert!(!visible("#rect1"));
+    assert!(visible("#rect2"));
+
+    svg.set_target(Some("#tab1")).unwrap();
+    assert!(visible("#rect1"));
+    assert!(!visible("#rect2"));
+    assert_eq!(
+        svg.get_computed_property("#rect1", "fill").unwrap(),
+        Some("rgb(0, 0, 0)".to_string())
+    );
+
+    svg.set_target(None).unwrap();
+    assert!(!visible("#rect1"));
+    assert_eq!(
+        svg.get_computed_property("#rect1", "fill").unwrap(),
+        Some("rgb(0, 0, 255)".to_string())
+    );
+
+    assert_eq!(
+        svg.set_target(Some("#nonexistent")),
+        Err(RenderingError::InvalidId(DefsLookupErrorKind::NotFound))
+    );
+}
+
+#[test]
+fn user_stylesheet_is_cascaded() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+  <style>
+    .success { fill: green; stroke: red; }
+  </style>
+  <path id="plain" d="M0,0 h8 v8 z"/>
+  <path id="success" class="success" d="M0,0 h8 v8 z"/>
+  <path id="styled" style="stroke: red" d="M0,0 h8 v8 z"/>
+</svg>
+"##,
+    );
+
+    let prop = |id, name| svg.get_computed_property(id, name).unwrap().unwrap();
+
+    svg.set_stylesheet(
+        "path { fill: blue; stroke: blue; } #success { stroke: #00ff00 !important }",
+    )
+    .unwrap();
+
+    // the document's styles override the user's, except for !important ones
+    assert_eq!(prop("#plain", "fill"), "rgb(0, 0, 255)");
+    assert_eq!(prop("#success", "fill"), "rgb(0, 128, 0)");
+    assert_eq!(prop("#success", "stroke"), "rgb(0, 255, 0)");
+    assert_eq!(prop("#styled", "stroke"), "rgb(255, 0, 0)");
+
+    // the stylesheet gets replaced
+    svg.set_stylesheet("").unwrap();
+
+    assert_eq!(prop("#plain", "fill"), "rgb(0, 0, 0)");
+    assert_eq!(prop("#success", "stroke"), "rgb(255, 0, 0)");
+    assert_eq!(prop("#styled", "stroke"), "rgb(255, 0, 0)");
+}
+
+#[test]
+fn media_queries_use_media_features() {
+    let props = |features: MediaFeatures| {
+        let svg = load_svg_with(
+            Loader::new().with_media_features(features),
+            br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    rect { fill: black; }
+    @media (prefers-color-scheme: dark) {
+      rect { fill: white; }
+    }
+    @media print {
+      rect { fill: red; }
+    }
+  </style>
+  <style media="(min-width: 64px)">
+    rect { stroke: blue; }
+  </style>
+  <rect id="rect" width="10" height="10"/>
+</svg>
+"##,
+        );
+
+        (
+            svg.get_computed_property("#rect", "fill").unwrap().unwrap(),
+            svg.get_computed_property("#rect", "stroke")
+                .unwrap()
+                .unwrap(),
+        )
+    };
+
+    assert_eq!(
+        props(MediaFeatures::default()),
+        ("rgb(0, 0, 0)".to_string(), "none".to_string())
+    );
+
+    assert_eq!(
+        props(MediaFeatures {
+            width: Some(128.0),
+            height: Some(128.0),
+            color_scheme: ColorScheme::Dark,
+            ..MediaFeatures::default()
+        }),
+        (
+            "rgb(255, 255, 255)".to_string(),
+            "rgb(0, 0, 255)".to_string()
+        )
+    );
+}
+
+#[test]
+fn color_scheme_can_change_after_loading() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    rect { fill: black; }
+    @media (prefers-color-scheme: dark) {
+      rect { fill: white; }
+    }
+  </style>
+  <style media="(prefers-color-scheme: dark)">
+    rect { stroke: yellow; }
+  </style>
+  <rect id="rect" width="10" height="10"/>
+</svg>
+"##,
+    );
+
+    let prop = |name| svg.get_computed_property("#rect", name).unwrap().unwrap();
+
+    svg.set_stylesheet("@media (prefers-color-scheme: dark) { rect { opacity: 0.5; } }")
+        .unwrap();
+
+    assert_eq!(svg.color_scheme(), ColorScheme::Light);
+    assert_eq!(prop("fill"), "rgb(0, 0, 0)");
+    assert_eq!(prop("stroke"), "none");
+    assert_eq!(prop("opacity"), "1");
+
+    svg.set_color_scheme(ColorScheme::Dark);
+
+    assert_eq!(svg.color_scheme(), ColorScheme::Dark);
+    assert_eq!(prop("fill"), "rgb(255, 255, 255)");
+    assert_eq!(prop("stroke"), "rgb(255, 255, 0)");
+    assert_eq!(prop("opacity"), "0.5");
+
+    svg.set_color_scheme(ColorScheme::Light);
+
+    assert_eq!(prop("fill"), "rgb(0, 0, 0)");
+    assert_eq!(prop("stroke"), "none");
+    assert_eq!(prop("opacity"), "1");
+}
+
+#[test]
+fn css_namespaces_work() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:foo="http://example.com/foo" width="100" height="100">
+  <style>
+    @namespace foo url(http://example.com/foo);
+    @namespace svg "http://www.w3.org/2000/svg";
+    rect { fill: blue; }
+    foo|rect { fill: red; }
+    svg|circle { fill: lime; }
+    *|ellipse { fill: yellow; }
+  </style>
+  <rect id="svg_rect" width="10" height="10"/>
+  <foo:rect id="foo_rect"/>
+  <circle id="svg_circle" r="10"/>
+  <ellipse id="svg_ellipse" rx="10" ry="10"/>
+  <foo:ellipse id="foo_ellipse"/>
+</svg>
+"##,
+    );
+
+    let fill = |id| svg.get_computed_property(id, "fill").unwrap().unwrap();
+
+    assert_eq!(fill("#svg_rect"), "rgb(0, 0, 255)");
+    assert_eq!(fill("#foo_rect"), "rgb(255, 0, 0)");
+    assert_eq!(fill("#svg_circle"), "rgb(0, 255, 0)");
+    assert_eq!(fill("#svg_ellipse"), "rgb(255, 255, 0)");
+    assert_eq!(fill("#foo_ellipse"), "rgb(255, 255, 0)");
+}
+
+#[test]
+fn css_selectors_work() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+  <style>
+    @namespace xlink "http://www.w3.org/1999/xlink";
+    #group rect.box { fill: red; }
+    rect { fill: black; }
+    g > rect:first-child { stroke: blue; }
+    rect:nth-child(2n+1) { stroke-width: 3; }
+    rect + rect[data-kind="thing"] { fill: lime; }
+    rect[data-kind^="wid"] ~ circle { fill: yellow; }
+    use[xlink|href$="circle"] { fill: aqua; }
+    circle:not(.special) { stroke: green; }
+    g circle.special { fill: purple; }
+  </style>
+  <g id="group">
+    <rect id="first" class="box" width="10" height="10"/>
+    <rect id="second" data-kind="thing" width="10" height="10"/>
+    <rect id="third" data-kind="widget" width="10" height="10"/>
+    <circle id="plain" r="5"/>
+    <circle id="special" class="special" r="5"/>
+  </g>
+  <use id="use" xlink:href="#plain"/>
+  <use id="use_circle" xlink:href="#special_circle"/>
+</svg>
+"##,
+    );
+
+    let get = |id, prop| svg.get_computed_property(id, prop).unwrap().unwrap();
+
+    // the more specific selector wins, regardless of source order
+    assert_eq!(get("#first", "fill"), "rgb(255, 0, 0)");
+
+    assert_eq!(get("#first", "stroke"), "rgb(0, 0, 255)");
+    assert_eq!(get("#second", "stroke"), "none");
+
+    // sibling combinators and :nth-child() skip the whitespace between elements
+    assert_eq!(get("#first", "stroke-width"), "3px");
+    assert_eq!(get("#second", "stroke-width"), "1px");
+    assert_eq!(get("#third", "stroke-width"), "3px");
+
+    assert_eq!(get("#second", "fill"), "rgb(0, 255, 0)");
+    assert_eq!(get("#third", "fill"), "rgb(0, 0, 0)");
+    assert_eq!(get("#plain", "fill"), "rgb(255, 255, 0)");
+
+    assert_eq!(get("#plain", "stroke"), "rgb(0, 128, 0)");
+    assert_eq!(get("#special", "stroke"), "none");
+    assert_eq!(get("#special", "fill"), "rgb(128, 0, 128)");
+
+    assert_eq!(get("#use", "fill"), "rgb(0, 0, 0)");
+    assert_eq!(get("#use_circle", "fill"), "rgb(0, 255, 255)");
+}
+
+#[test]
+fn css_default_namespace_applies_to_type_selectors() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:foo="http://example.com/foo" width="100" height="100">
+  <style>
+    @namespace url(http://example.com/foo);
+    rect { fill: red; }
+  </style>
+  <rect id="svg_rect" width="10" height="10"/>
+  <foo:rect id="foo_rect"/>
+</svg>
+"##,
+    );
+
+    let fill = |id| svg.get_computed_property(id, "fill").unwrap().unwrap();
+
+    assert_eq!(fill("#svg_rect"), "rgb(0, 0, 0)");
+    assert_eq!(fill("#foo_rect"), "rgb(255, 0, 0)");
+}
+
+#[test]
+fn render_animation_writes_apng_and_gif() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect width="10" height="10" fill="lime">
+    <animate attributeName="x" from="0" to="90" dur="1s"/>
+  </rect>
+</svg>
+"##,
+    );
+
+    let options = AnimationOptions {
+        frame_rate: 4.0,
+        duration: 1.0,
+        loops: 0,
+    };
+
+    let renderer = CairoRenderer::new(&svg);
+
+    let apng = renderer
+        .render_animation(50, 50, AnimatedImageFormat::Apng, &options)
+        .unwrap();
+
+    assert_eq!(&apng[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&apng[37..41], b"acTL");
+    assert_eq!(&apng[41..45], &[0, 0, 0, 4]);
+    assert_eq!(&apng[apng.len() - 8..apng.len() - 4], b"IEND");
+
+    let gif = renderer
+        .render_animation(50, 50, AnimatedImageFormat::Gif, &options)
+        .unwrap();
+
+    assert_eq!(&gif[..6], b"GIF89a");
+    assert_eq!(&gif[6..10], &[50, 0, 50, 0]);
+    assert_eq!(gif.last(), Some(&0x3b));
+
+    assert_eq!(
+        renderer.render_animation(0, 50, AnimatedImageFormat::Gif, &options),
+        Err(RenderingError::Cairo(cairo::Status::InvalidSize))
+    );
+
+    for &(frame_rate, duration) in &[
+        (0.0, 1.0),
+        (std::f64::INFINITY, 1.0),
+        (10.0, std::f64::NAN),
+        (10.0, -1.0),
+        (1000.0, 1000.0),
+    ] {
+        let options = AnimationOptions {
+            frame_rate,
+            duration,
+            loops: 0,
+        };
+
+        assert_eq!(
+            renderer.render_animation(50, 50, AnimatedImageFormat::Gif, &options),
+            Err(RenderingError::Cairo(cairo::Status::InvalidSize))
+        );
+    }
+}
+
+#[test]
+fn render_postscript_writes_ps_and_eps() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="20" width="30" height="40" fill="lime"/>
+</svg>
+"##,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+
+    let contains =
+        |output: &[u8], s: &str| output.windows(s.len()).any(|window| window == s.as_bytes());
+
+    let ps = renderer
+        .render_postscript(100.0, 100.0, &PostScriptOptions::default())
+        .unwrap();
+
+    assert!(ps.starts_with(b"%!PS-Adobe-3.0\n"));
+    assert!(contains(&ps, "%%LanguageLevel: 3"));
+
+    let eps = renderer
+        .render_postscript(
+            100.0,
+            100.0,
+            &PostScriptOptions {
+                level: PostScriptLevel::Level2,
+                eps: true,
+                tight_bounding_box: true,
+            },
+        )
+        .unwrap();
+
+    assert!(eps.starts_with(b"%!PS-Adobe-3.0 EPSF-3.0\n"));
+    assert!(contains(&eps, "%%LanguageLevel: 2"));
+    assert!(contains(&eps, "%%BoundingBox: 0 0 30 40"));
+}
+
+#[test]
+fn approximate_quality_degrades_rendering() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="blur">
+    <feGaussianBlur stdDeviation="2"/>
+  </filter>
+  <rect x="10" y="10" width="40" height="40" fill="lime" filter="url(#blur)"/>
+  <rect x="80" y="80" width="0.5" height="0.5" fill="black"/>
+</svg>
+"##,
+    );
+
+    let render = |quality: RenderQuality| {
+        let renderer = CairoRenderer::new(&svg).with_quality(quality);
+
+        let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+        {
+            let cr = cairo::Context::new(&output);
+            let viewport = cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 100.0,
+            };
+
+            renderer.render_document(&cr, &viewport).unwrap();
+        }
+
+        SharedImageSurface::new(output, SurfaceType::SRgb).unwrap()
+    };
+
+    let full = render(RenderQuality::Full);
+    let approximate = render(RenderQuality::Approximate);
+
+    // The filtered element is still rendered, but the tiny one is skipped
+    assert_eq!(full.get_pixel(30, 30).a, 255);
+    assert_eq!(approximate.get_pixel(30, 30).a, 255);
+    assert!(approximate.get_pixel(9, 30).a > 0);
+
+    assert!(full.get_pixel(80, 80).a > 0);
+    assert_eq!(approximate.get_pixel(80, 80).a, 0);
+}
+
+#[test]
+fn font_fallbacks_are_reported() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="40" font-family="sans-serif">Hello</text>
+  <text x="10" y="80" font-family="No Such Font Family">Hello</text>
+</svg>
+"#,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+    let viewport = cairo::Rectangle::new(0.0, 0.0, 200.0, 100.0);
+    let fallbacks = renderer.font_fallbacks(&viewport).unwrap();
+
+    // The generic family maps to an installed font, so only the second element
+    // falls back.
+    assert_eq!(fallbacks.len(), 1);
+    assert_eq!(fallbacks[0].kind, FontFallbackKind::DifferentFont);
+    assert_eq!(fallbacks[0].characters, "Hello");
+    assert_eq!(fallbacks[0].requested_family, "No Such Font Family");
+    assert_ne!(fallbacks[0].font_family, "No Such Font Family");
+}
+
+#[test]
+fn generic_font_families_can_be_mapped() {
+    let mut font_families = FontFamilyMap::new();
+    font_families.set_generic_family(GenericFontFamily::SansSerif, "No Such Font Family");
+
+    let svg = load_svg_with(
+        Loader::new().with_font_families(font_families),
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="40" font-family="sans-serif">Hello</text>
+  <text x="10" y="80" font-family="monospace">Hello</text>
+</svg>
+"#,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+    let viewport = cairo::Rectangle::new(0.0, 0.0, 200.0, 100.0);
+    let fallbacks = renderer.font_fallbacks(&viewport).unwrap();
+
+    // Only the mapped generic family asks for the missing font.
+    assert_eq!(fallbacks.len(), 1);
+    assert_eq!(fallbacks[0].kind, FontFallbackKind::DifferentFont);
+    assert_eq!(fallbacks[0].requested_family, "sans-serif");
+    assert_ne!(fallbacks[0].font_family, "No Such Font Family");
+}
+
+#[test]
+fn font_face_rules_load_embedded_fonts() {
+    let font =
+        glib::base64_encode(include_bytes!("../../tests/resources/Roboto-Bold.ttf")).unwrap();
+
+    let document = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <style>
+    @font-face {{
+      font-family: "Embedded Font";
+      src: url(data:font/ttf;base64,{}) format("truetype");
+    }}
+  </style>
+  <text id="text" x="10" y="50" font-family="Embedded Font" font-size="20">Hello</text>
+</svg>
+"#,
+        font
+    );
+
+    let svg = load_svg_with(Loader::new(), document.as_bytes());
+
+    let renderer = CairoRenderer::new(&svg);
+    let viewport = cairo::Rectangle::new(0.0, 0.0, 200.0, 100.0);
+
+    let runs = renderer
+        .glyph_runs_for_layer(Some("#text"), &viewport)
+        .unwrap();
+
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].font_family, "Roboto");
+
+    // The font's own family name is different, but it is what the document asked for
+    assert!(renderer.font_fallbacks(&viewport).unwrap().is_empty());
+}
+
+#[test]
+fn application_fonts_replace_the_system_fonts() {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/resources");
+    let fonts = ApplicationFonts::from_directory(directory).unwrap();
+
+    let svg = load_svg_with(
+        Loader::new().with_fonts(fonts),
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" font-family="sans-serif" font-size="20">Hello</text>
+</svg>
+"#,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+    let viewport = cairo::Rectangle::new(0.0, 0.0, 200.0, 100.0);
+
+    let runs = renderer
+        .glyph_runs_for_layer(Some("#text"), &viewport)
+        .unwrap();
+
+    // The directory only has Roboto, so it gets used even for sans-serif
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].font_family, "Roboto");
+
+    assert!(ApplicationFonts::from_directory("/nonexistent/fonts").is_err());
+}
+
+#[test]
+fn glyph_runs_have_positioned_glyphs() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <rect id="rect" x="0" y="0" width="10" height="10"/>
+  <text id="text" x="10" y="50" font-family="sans-serif" font-size="20">Hi</text>
+</svg>
+"#,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+    let viewport = cairo::Rectangle::new(0.0, 0.0, 400.0, 200.0);
+
+    assert!(renderer
+        .glyph_runs_for_layer(Some("#rect"), &viewport)
+        .unwrap()
+        .is_empty());
+
+    let runs = renderer
+        .glyph_runs_for_layer(Some("#text"), &viewport)
+        .unwrap();
+
+    assert_eq!(runs.len(), 1);
+
+    let run = &runs[0];
+    assert_eq!(run.text, "Hi");
+    assert_eq!(run.glyphs.len(), 2);
+    assert!((run.font_size - 20.0).abs() < 0.01);
+
+    // The viewport is twice the size of the SVG, so the first glyph's origin is at
+    // the text's position, scaled by 2.
+    let first = &run.glyphs[0];
+    let (x, y) = run.transform.transform_point(first.x, first.y);
+    assert!((x - 20.0).abs() < 0.01);
+    assert!((y - 100.0).abs() < 0.01);
+
+    let second = &run.glyphs[1];
+    assert!(second.x > first.x);
+    assert_eq!(second.cluster, 1);
+}
+
+#[test]
+fn text_decoration_is_painted_with_the_fill_of_its_element() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text x="10" y="50" font-family="sans-serif" font-size="40" fill="#0000ff"
+        text-decoration="underline">AA<tspan fill="#ff0000">HH</tspan></text>
+</svg>
+"##,
+    );
+
+    let output = render_document(
+        &svg,
+        SurfaceSize(200, 100),
+        |_| (),
+        cairo::Rectangle::new(0.0, 0.0, 200.0, 100.0),
+    )
+    .unwrap();
+
+    // Below the baseline of the tspan there is nothing but the underline of the text
+    let underline: Vec<_> = (51..70)
+        .map(|y| output.get_pixel(90, y))
+        .filter(|p| p.a > 0)
+        .collect();
+
+    assert!(!underline.is_empty());
+    assert!(underline.iter().all(|p| p.r == 0 && p.b > 0));
+}
+
+#[test]
+fn validate_reports_constructs_outside_the_profile() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <title>Test</title>
+  <rect id="a" width="10" height="10" paint-order="stroke" fill="#nope"/>
+  <rect id="b" width="10" height="10" filter="none"/>
+  <cursor id="c"/>
+  <rect id="d" width="10" height="10" transform="rotate(oops)"/>
+</svg>
+"##,
+    );
+
+    let kinds = |profile| {
+        svg.validate(profile)
+            .issues
+            .into_iter()
+            .map(|issue| (issue.kind, issue.id.unwrap_or_default(), issue.attribute))
+            .collect::<Vec<_>>()
+    };
+
+    let attr = |name: &str| Some(name.to_string());
+
+    assert_eq!(
+        kinds(SvgProfile::Svg11Full),
+        vec![
+            (
+                ValidationIssueKind::PropertyNotInProfile,
+                "a".to_string(),
+                attr("paint-order")
+            ),
+            (
+                ValidationIssueKind::InvalidPropertyValue,
+                "a".to_string(),
+                attr("fill")
+            ),
+            (
+                ValidationIssueKind::UnsupportedElement,
+                "c".to_string(),
+                None
+            ),
+            (
+                ValidationIssueKind::InvalidAttribute,
+                "d".to_string(),
+                attr("transform")
+            ),
+        ]
+    );
+
+    let tiny = kinds(SvgProfile::SvgTiny12);
+    assert!(tiny.contains(&(
+        ValidationIssueKind::PropertyNotInProfile,
+        "b".to_string(),
+        attr("filter")
+    )));
+    assert!(tiny.contains(&(
+        ValidationIssueKind::ElementNotInProfile,
+        "c".to_string(),
+        None
+    )));
+
+    assert!(!svg.validate(SvgProfile::Svg2).is_valid());
+}
+
+#[test]
+fn complexity_report_describes_the_tree() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+  <defs>
+    <filter id="f">
+      <feGaussianBlur stdDeviation="2"/>
+    </filter>
+    <linearGradient id="g"/>
+  </defs>
+  <g>
+    <g>
+      <path id="p" d="M 0 0 L 10 0 L 10 10 Z" fill="url(#g)" filter="url(#f)"/>
+    </g>
+  </g>
+  <use xlink:href="#p"/>
+</svg>
+"##,
+    );
+
+    let report = svg.complexity();
+
+    assert_eq!(report.num_elements, 9);
+    assert_eq!(report.element_counts["g"], 2);
+    assert_eq!(report.element_counts["feGaussianBlur"], 1);
+    assert_eq!(report.element_counts.get("rect"), None);
+    assert_eq!(report.max_depth, 4);
+    assert_eq!(report.num_filters, 1);
+    assert_eq!(report.num_path_segments, 4);
+    assert_eq!(report.num_references, 3);
+}
@@ -2,11 +2,16 @@ use cairo;
 
 use librsvg::{
     CairoRenderer,
+    ClipShape,
     DefsLookupErrorKind,
+    ElementContext,
     HrefError,
     IntrinsicDimensions,
     Length,
     LengthUnit,
+    MarkerType,
+    PathPoint,
+    PathSegment,
     RenderingError,
 };
 
@@ -55,6 +60,28 @@ fn has_intrinsic_dimensions() {
     );
 }
 
+#[test]
+fn css_width_and_height_override_attributes() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20" style="width: 5cm">
+  <style>
+    svg { height: 40px; }
+  </style>
+</svg>
+"#,
+    );
+
+    assert_eq!(
+        CairoRenderer::new(&svg).intrinsic_dimensions(),
+        IntrinsicDimensions {
+            width: Some(Length::new(5.0, LengthUnit::Cm)),
+            height: Some(Length::new(40.0, LengthUnit::Px)),
+            vbox: None,
+        }
+    );
+}
+
 #[test]
 fn root_geometry_with_percent_viewport() {
     let svg = load_svg(
@@ -207,3 +234,545 @@ fn layer_geometry_for_invalid_id() {
         _ => panic!(),
     }
 }
+
+#[test]
+fn marker_positions_for_polygon() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <marker id="m" markerWidth="2" markerHeight="2">
+      <rect width="2" height="2"/>
+    </marker>
+  </defs>
+  <polygon id="poly" points="10 10 50 10 50 50" marker-start="url(#m)" marker-mid="url(#m)" marker-end="url(#m)"/>
+</svg>
+"##,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let renderer = CairoRenderer::new(&svg);
+    let positions: Vec<_> = renderer
+        .marker_positions_for_layer(Some("#poly"), &viewport)
+        .unwrap()
+        .iter()
+        .map(|p| (p.marker_type, p.x, p.y))
+        .collect();
+
+    assert_eq!(
+        positions,
+        vec![
+            (MarkerType::Start, 10.0, 10.0),
+            (MarkerType::Middle, 50.0, 10.0),
+            (MarkerType::Middle, 50.0, 50.0),
+            (MarkerType::End, 10.0, 10.0),
+        ]
+    );
+}
+
+#[test]
+fn path_segments_of_shapes() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g transform="translate(50 50)">
+    <rect id="rect" x="10%" y="10" width="20" height="10" transform="scale(2)"/>
+  </g>
+  <path id="path" d="M 10 20 h 10 a 10 10 0 0 1 10 10 z"/>
+  <g id="group"/>
+</svg>
+"##,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let renderer = CairoRenderer::new(&svg);
+
+    assert_eq!(
+        renderer
+            .path_segments_for_element("#rect", &viewport)
+            .unwrap(),
+        vec![
+            PathSegment::MoveTo(10.0, 10.0),
+            PathSegment::LineTo(30.0, 10.0),
+            PathSegment::LineTo(30.0, 20.0),
+            PathSegment::LineTo(10.0, 20.0),
+            PathSegment::LineTo(10.0, 10.0),
+            PathSegment::ClosePath,
+        ]
+    );
+
+    let segments = renderer
+        .path_segments_for_element("#path", &viewport)
+        .unwrap();
+
+    assert_eq!(segments.len(), 4);
+    assert_eq!(segments[0], PathSegment::MoveTo(10.0, 20.0));
+    assert_eq!(segments[1], PathSegment::LineTo(20.0, 20.0));
+
+    match segments[2] {
+        PathSegment::CurveTo(_, _, _, _, x, y) => {
+            assert!((x - 30.0).abs() < 1e-6 && (y - 30.0).abs() < 1e-6)
+        }
+        _ => panic!("the arc should have been converted to a curve"),
+    }
+
+    assert_eq!(segments[3], PathSegment::ClosePath);
+
+    assert!(renderer
+        .path_segments_for_element("#group", &viewport)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn measures_paths_of_shapes() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect id="rect" x="10" y="10" width="30" height="20" transform="translate(50 50)"/>
+  <line id="line" x1="0" y1="0" x2="30" y2="40"/>
+  <g id="group"/>
+</svg>
+"##,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let renderer = CairoRenderer::new(&svg);
+
+    assert_eq!(
+        renderer
+            .path_length_for_element("#rect", &viewport)
+            .unwrap(),
+        100.0
+    );
+
+    let PathPoint { x, y, angle } = renderer
+        .point_at_length_for_element("#rect", &viewport, 40.0)
+        .unwrap()
+        .unwrap();
+    assert_eq!((x, y), (40.0, 20.0));
+    assert!((angle - 90.0).abs() < 1e-6);
+
+    assert_eq!(
+        renderer
+            .path_length_for_element("#line", &viewport)
+            .unwrap(),
+        50.0
+    );
+    assert_eq!(
+        renderer
+            .point_at_length_for_element("#line", &viewport, 100.0)
+            .unwrap()
+            .map(|p| (p.x, p.y)),
+        Some((30.0, 40.0))
+    );
+
+    assert_eq!(
+        renderer
+            .path_length_for_element("#group", &viewport)
+            .unwrap(),
+        0.0
+    );
+    assert!(renderer
+        .point_at_length_for_element("#group", &viewport, 0.0)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn clip_region_intersects_ancestor_clips() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <clipPath id="square">
+      <rect x="10" y="10" width="50" height="50"/>
+    </clipPath>
+    <clipPath id="bbox" clipPathUnits="objectBoundingBox" clip-rule="evenodd">
+      <rect width="0.5" height="1"/>
+    </clipPath>
+  </defs>
+  <g transform="translate(10 0)" clip-path="url(#square)">
+    <rect id="rect" x="20" y="20" width="40" height="40" clip-path="url(#bbox)"/>
+    <rect id="unclipped" x="20" y="20" width="40" height="40"/>
+  </g>
+  <rect id="outside" width="10" height="10"/>
+</svg>
+"##,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let renderer = CairoRenderer::new(&svg);
+
+    // The extents of the shapes are enough to check where they are
+    let extents = |shape: &ClipShape| {
+        let (mut x0, mut y0, mut x1, mut y1) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+
+        for segment in &shape.segments {
+            let points = match *segment {
+                PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => vec![(x, y)],
+                PathSegment::CurveTo(x1, y1, x2, y2, x, y) => vec![(x1, y1), (x2, y2), (x, y)],
+                PathSegment::ClosePath => vec![],
+            };
+
+            for (x, y) in points {
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x);
+                y1 = y1.max(y);
+            }
+        }
+
+        (x0, y0, x1, y1, shape.fill_rule)
+    };
+
+    let region = renderer
+        .clip_region_for_element("#rect", &viewport)
+        .unwrap();
+
+    assert_eq!(
+        region.iter().map(extents).collect::<Vec<_>>(),
+        vec![
+            (20.0, 10.0, 70.0, 60.0, cairo::FillRule::Winding),
+            (30.0, 20.0, 50.0, 60.0, cairo::FillRule::EvenOdd),
+        ]
+    );
+
+    let region = renderer
+        .clip_region_for_element("#unclipped", &viewport)
+        .unwrap();
+    assert_eq!(region.len(), 1);
+
+    assert!(renderer
+        .clip_region_for_element("#outside", &viewport)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn transforms_map_user_space_to_device_space() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 50 25">
+  <g transform="translate(10 0)">
+    <rect id="rect" transform="scale(2)" width="10" height="10"/>
+  </g>
+  <rect id="hidden" display="none" width="10" height="10"/>
+</svg>
+"#,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 200.0,
+        height: 100.0,
+    };
+
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+    let cr = cairo::Context::new(&surface);
+    cr.translate(5.0, 5.0);
+
+    let renderer = CairoRenderer::new(&svg);
+
+    let components = |m: cairo::Matrix| (m.xx, m.yx, m.xy, m.yy, m.x0, m.y0);
+
+    let document = renderer.document_transform(&cr, &viewport).unwrap();
+    assert_eq!(
+        document.map(components),
+        Some((4.0, 0.0, 0.0, 4.0, 5.0, 5.0))
+    );
+
+    let rect = renderer
+        .transform_for_element(&cr, "#rect", &viewport)
+        .unwrap();
+    assert_eq!(rect.map(components), Some((8.0, 0.0, 0.0, 8.0, 45.0, 5.0)));
+
+    assert!(renderer
+        .transform_for_element(&cr, "#hidden", &viewport)
+        .unwrap()
+        .is_none());
+
+    match renderer.transform_for_element(&cr, "#nonexistent", &viewport) {
+        Err(RenderingError::InvalidId(DefsLookupErrorKind::NotFound)) => (),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn stroke_metrics_of_shapes() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" viewBox="0 0 50 50">
+  <g id="group" stroke="black">
+    <line id="dashed" transform="scale(4 1)" x1="0" y1="10" x2="10" y2="10"
+          stroke-width="3" stroke-dasharray="4 2" stroke-dashoffset="1"
+          stroke-linecap="round" stroke-linejoin="bevel" stroke-miterlimit="8"/>
+    <rect id="non-scaling" width="10" height="10" stroke-width="2"
+          vector-effect="non-scaling-stroke"/>
+  </g>
+  <rect id="no-stroke" width="10" height="10"/>
+</svg>
+"#,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+    let cr = cairo::Context::new(&surface);
+
+    let renderer = CairoRenderer::new(&svg);
+
+    let components = |m: cairo::Matrix| (m.xx, m.yx, m.xy, m.yy, m.x0, m.y0);
+
+    let dashed = renderer
+        .stroke_for_element(&cr, "#dashed", &viewport)
+        .unwrap()
+        .unwrap();
+    assert_eq!(dashed.width, 3.0);
+    assert_eq!(dashed.effective_width, 12.0);
+    assert_eq!(dashed.line_cap, cairo::LineCap::Round);
+    assert_eq!(dashed.line_join, cairo::LineJoin::Bevel);
+    assert_eq!(dashed.miter_limit, 8.0);
+    assert_eq!(dashed.dashes, vec![4.0, 2.0]);
+    assert_eq!(dashed.dash_offset, 1.0);
+    assert_eq!(components(dashed.transform), (8.0, 0.0, 0.0, 2.0, 0.0, 0.0));
+
+    let non_scaling = renderer
+        .stroke_for_element(&cr, "#non-scaling", &viewport)
+        .unwrap()
+        .unwrap();
+    assert_eq!(non_scaling.width, 2.0);
+    assert_eq!(non_scaling.effective_width, 2.0);
+    assert!(non_scaling.dashes.is_empty());
+    assert_eq!(
+        components(non_scaling.transform),
+        (1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    );
+
+    assert!(renderer
+        .stroke_for_element(&cr, "#no-stroke", &viewport)
+        .unwrap()
+        .is_none());
+
+    assert!(renderer
+        .stroke_for_element(&cr, "#group", &viewport)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn layer_geometry_with_animations_at_time() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect id="foo" x="0" y="0" width="10" height="10" stroke="black" stroke-width="2">
+    <animate attributeName="stroke-width" from="2" to="6" dur="4s"/>
+    <animateTransform attributeName="transform" type="translate" from="0 0" to="100 0"
+                      dur="4s"/>
+  </rect>
+</svg>
+"#,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    let (ink_r, logical_r) = CairoRenderer::new(&svg)
+        .with_time(2.0)
+        .geometry_for_layer(Some("#foo"), &viewport)
+        .unwrap();
+
+    assert_eq!(
+        ink_r,
+        cairo::Rectangle {
+            x: 48.0,
+            y: -2.0,
+            width: 14.0,
+            height: 14.0,
+        }
+    );
+
+    assert_eq!(
+        logical_r,
+        cairo::Rectangle {
+            x: 50.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        }
+    );
+
+    // Times that are not finite are ignored
+    let (_, nan_logical_r) = CairoRenderer::new(&svg)
+        .with_time(2.0)
+        .with_time(std::f64::NAN)
+        .geometry_for_layer(Some("#foo"), &viewport)
+        .unwrap();
+
+    assert_eq!(nan_logical_r, logical_r);
+
+    // Without a time, the animations are not applied
+    let (ink_r, _) = CairoRenderer::new(&svg)
+        .geometry_for_layer(Some("#foo"), &viewport)
+        .unwrap();
+
+    assert_eq!(
+        ink_r,
+        cairo::Rectangle {
+            x: -1.0,
+            y: -1.0,
+            width: 12.0,
+            height: 12.0,
+        }
+    );
+}
+
+#[test]
+fn layer_geometry_with_css_animations_at_time() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    @keyframes grow {
+      from { stroke-width: 2px; }
+      to { stroke-width: 10px; }
+    }
+
+    #foo { animation: grow 4s linear infinite; }
+  </style>
+  <rect id="foo" x="0" y="0" width="10" height="10" stroke="black" stroke-width="1"/>
+</svg>
+"#,
+    );
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 100.0,
+        height: 100.0,
+    };
+
+    // 5s is a quarter of the way into the second iteration
+    let (ink_r, _) = CairoRenderer::new(&svg)
+        .with_time(5.0)
+        .geometry_for_layer(Some("#foo"), &viewport)
+        .unwrap();
+
+    assert_eq!(
+        ink_r,
+        cairo::Rectangle {
+            x: -2.0,
+            y: -2.0,
+            width: 14.0,
+            height: 14.0,
+        }
+    );
+}
+
+#[test]
+fn element_geometry_with_element_context() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g transform="scale(2)" stroke="black" stroke-width="4">
+    <rect id="foo" x="10" y="10" width="10" height="10"/>
+  </g>
+</svg>
+"#,
+    );
+
+    let geometry = |context| {
+        CairoRenderer::new(&svg)
+            .with_element_context(context)
+            .geometry_for_element(Some("#foo"))
+            .unwrap()
+    };
+
+    assert_eq!(
+        geometry(ElementContext::Isolated),
+        (
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 10.0,
+                height: 10.0,
+            },
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 10.0,
+                height: 10.0,
+            }
+        )
+    );
+
+    assert_eq!(
+        geometry(ElementContext::InheritedStyles),
+        (
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 14.0,
+                height: 14.0,
+            },
+            cairo::Rectangle {
+                x: 2.0,
+                y: 2.0,
+                width: 10.0,
+                height: 10.0,
+            }
+        )
+    );
+
+    assert_eq!(
+        geometry(ElementContext::Full),
+        (
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 28.0,
+                height: 28.0,
+            },
+            cairo::Rectangle {
+                x: 4.0,
+                y: 4.0,
+                width: 20.0,
+                height: 20.0,
+            }
+        )
+    );
+}
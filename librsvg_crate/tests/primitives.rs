@@ -1,10 +1,11 @@
 use cairo;
+use librsvg::{CairoRenderer, Loader};
 
 mod utils;
 
 use rsvg_internals::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
 
-use self::utils::{compare_to_surface, load_svg, render_document, SurfaceSize};
+use self::utils::{compare_to_surface, load_svg, load_svg_with, render_document, SurfaceSize};
 
 #[test]
 fn simple_opacity_with_transform() {
@@ -208,6 +209,63 @@ fn markers_with_scale() {
     );
 }
 
+#[test]
+fn marker_with_auto_start_reverse_points_backwards_at_start() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <marker id="arrow" refX="5" refY="5" markerWidth="10" markerHeight="10"
+          markerUnits="userSpaceOnUse" orient="auto-start-reverse">
+    <path d="M 10 5 L 0 10 V 0 Z" fill="blue"/>
+  </marker>
+  <path d="M 20 50 L 80 50" stroke="none"
+        marker-start="url(#arrow)" marker-end="url(#arrow)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.set_source_rgb(0.0, 0.0, 1.0);
+
+        // The start marker points to the left, away from the path
+        cr.move_to(15.0, 50.0);
+        cr.line_to(25.0, 45.0);
+        cr.line_to(25.0, 55.0);
+        cr.fill();
+
+        // The end marker points to the right, along the path
+        cr.move_to(85.0, 50.0);
+        cr.line_to(75.0, 55.0);
+        cr.line_to(75.0, 45.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "marker_with_auto_start_reverse_points_backwards_at_start",
+    );
+}
+
 #[test]
 fn opacity_inside_transformed_group() {
     let svg = load_svg(
@@ -375,3 +433,2269 @@ fn nested_masks() {
 
     compare_to_surface(&output_surf, &reference_surf, "nested_masks");
 }
+
+#[test]
+fn mask_type_alpha_uses_alpha_channel() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <mask id="luminance" maskUnits="userSpaceOnUse" x="0" y="0" width="100" height="100">
+    <rect x="10" y="10" width="30" height="30" fill="black" fill-opacity="0.5"/>
+  </mask>
+  <mask id="alpha" mask-type="alpha" maskUnits="userSpaceOnUse" x="0" y="0" width="100" height="100">
+    <rect x="50" y="50" width="30" height="30" fill="black" fill-opacity="0.5"/>
+  </mask>
+  <rect width="100" height="100" fill="blue" mask="url(#luminance)"/>
+  <rect width="100" height="100" fill="blue" mask="url(#alpha)"/>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // The black luminance mask hides everything, and the alpha mask only uses the
+        // opacity of its content.
+        cr.rectangle(50.0, 50.0, 30.0, 30.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 0.5);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "mask_type_alpha_uses_alpha_channel");
+}
+
+#[test]
+fn mix_blend_mode_blends_with_backdrop_unless_isolated() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect width="100" height="100" fill="#ffff00"/>
+  <rect x="10" y="10" width="30" height="30" fill="#00ffff" style="mix-blend-mode: multiply"/>
+  <g isolation="isolate">
+    <rect x="50" y="50" width="30" height="30" fill="#00ffff" mix-blend-mode="multiply"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 100.0, 100.0);
+        cr.set_source_rgba(1.0, 1.0, 0.0, 1.0);
+        cr.fill();
+
+        // Yellow multiplied by cyan
+        cr.rectangle(10.0, 10.0, 30.0, 30.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+
+        // The isolated group has a transparent backdrop, so its contents are not blended
+        // with the yellow rectangle.
+        cr.rectangle(50.0, 50.0, 30.0, 30.0);
+        cr.set_source_rgba(0.0, 1.0, 1.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "mix_blend_mode_blends_with_backdrop_unless_isolated",
+    );
+}
+
+#[test]
+fn pattern_with_scaled_pattern_transform_is_sharp() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <pattern id="pat" patternUnits="userSpaceOnUse" width="2" height="2" patternTransform="scale(10)">
+      <rect width="1" height="1" fill="black"/>
+    </pattern>
+  </defs>
+  <rect width="100" height="100" fill="url(#pat)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        for y in 0..5 {
+            for x in 0..5 {
+                cr.rectangle(f64::from(x * 20), f64::from(y * 20), 10.0, 10.0);
+            }
+        }
+
+        cr.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_with_scaled_pattern_transform_is_sharp",
+    );
+}
+
+#[test]
+fn pattern_with_rotated_and_stretched_pattern_transform_is_sharp() {
+    // The pattern's x axis gets stretched by 25 and ends up vertical, so the
+    // vertical stripe in the tile becomes a series of 25px-tall horizontal bands.
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <pattern id="pat" patternUnits="userSpaceOnUse" width="2" height="2"
+             patternTransform="rotate(90) scale(25, 1)">
+      <rect width="1" height="2" fill="black"/>
+    </pattern>
+  </defs>
+  <rect width="100" height="100" fill="url(#pat)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 100.0, 25.0);
+        cr.rectangle(0.0, 50.0, 100.0, 25.0);
+        cr.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_with_rotated_and_stretched_pattern_transform_is_sharp",
+    );
+}
+
+#[test]
+fn pattern_content_filled_with_another_pattern() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40">
+  <defs>
+    <pattern id="inner" patternUnits="userSpaceOnUse" width="10" height="10">
+      <rect width="5" height="5" fill="black"/>
+    </pattern>
+    <pattern id="outer" patternUnits="userSpaceOnUse" width="20" height="20">
+      <rect width="20" height="20" fill="url(#inner)"/>
+    </pattern>
+  </defs>
+  <rect width="40" height="40" fill="url(#outer)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(40, 40),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 40.0,
+            height: 40.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 40, 40).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                cr.rectangle(f64::from(x * 10), f64::from(y * 10), 5.0, 5.0);
+            }
+        }
+
+        cr.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_content_filled_with_another_pattern",
+    );
+}
+
+#[test]
+fn pattern_honors_fill_and_stroke_opacity() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40">
+  <defs>
+    <pattern id="pat" patternUnits="userSpaceOnUse" width="10" height="10">
+      <rect width="10" height="10" fill="blue"/>
+    </pattern>
+  </defs>
+  <rect width="20" height="40" fill="url(#pat)" fill-opacity="0.5"/>
+  <line x1="30" y1="0" x2="30" y2="40" stroke="url(#pat)" stroke-width="10" stroke-opacity="0.25"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(40, 40),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 40.0,
+            height: 40.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 40, 40).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 20.0, 40.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 0.5);
+        cr.fill();
+
+        cr.rectangle(25.0, 0.0, 10.0, 40.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 0.25);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_honors_fill_and_stroke_opacity",
+    );
+}
+
+#[test]
+fn pattern_with_overflow_visible_draws_outside_of_the_tile() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40">
+  <defs>
+    <pattern id="clipped" patternUnits="userSpaceOnUse" width="10" height="10">
+      <rect x="-5" width="10" height="10" fill="lime"/>
+    </pattern>
+    <pattern id="visible" patternUnits="userSpaceOnUse" width="10" height="10" overflow="visible">
+      <rect x="-5" width="10" height="10" fill="lime"/>
+    </pattern>
+  </defs>
+  <rect width="40" height="20" fill="url(#clipped)"/>
+  <rect y="20" width="40" height="20" fill="url(#visible)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(40, 40),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 40.0,
+            height: 40.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 40, 40).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+
+        for i in 0..4 {
+            cr.rectangle(f64::from(i) * 10.0, 0.0, 5.0, 20.0);
+        }
+
+        cr.rectangle(0.0, 20.0, 40.0, 20.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_with_overflow_visible_draws_outside_of_the_tile",
+    );
+}
+
+#[test]
+fn pattern_tiles_are_reused_at_different_positions() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40">
+  <defs>
+    <pattern id="pat" patternUnits="objectBoundingBox" width="0.5" height="0.5">
+      <rect width="5" height="5" fill="black"/>
+    </pattern>
+  </defs>
+  <rect width="20" height="20" fill="url(#pat)"/>
+  <rect x="20" y="20" width="20" height="20" fill="url(#pat)"/>
+  <rect x="20" y="0" width="20" height="20" fill="url(#pat)" fill-opacity="0.5"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(40, 40),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 40.0,
+            height: 40.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 40, 40).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        for &(x, y, alpha) in &[(0.0, 0.0, 1.0), (20.0, 20.0, 1.0), (20.0, 0.0, 0.5)] {
+            for j in 0..2 {
+                for i in 0..2 {
+                    cr.rectangle(x + f64::from(i * 10), y + f64::from(j * 10), 5.0, 5.0);
+                }
+            }
+
+            cr.set_source_rgba(0.0, 0.0, 0.0, alpha);
+            cr.fill();
+        }
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_tiles_are_reused_at_different_positions",
+    );
+}
+
+#[test]
+fn reference_cycle_through_pattern_content_uses_fallback_color() {
+    // "inner" takes its children from "outer", whose content refers back
+    // to "inner"; the cycle must be caught and the fallback color used.
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="40" height="40">
+  <defs>
+    <pattern id="outer" patternUnits="userSpaceOnUse" width="20" height="20">
+      <rect width="20" height="20" fill="url(#outer) lime"/>
+    </pattern>
+    <pattern id="inner" xlink:href="#outer"/>
+  </defs>
+  <rect width="40" height="40" fill="url(#inner)"/>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(40, 40),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 40.0,
+            height: 40.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 40, 40).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 40.0, 40.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "reference_cycle_through_pattern_content_uses_fallback_color",
+    );
+}
+
+#[test]
+fn gradient_transform_is_composed_with_element_transform() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="10">
+  <defs>
+    <linearGradient id="grad" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="20" y2="0"
+                    gradientTransform="scale(2)" transform="translate(10, 0)">
+      <stop offset="0" stop-color="black"/>
+      <stop offset="1" stop-color="white"/>
+    </linearGradient>
+  </defs>
+  <rect width="100" height="10" fill="url(#grad)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 10),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 10.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 10).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // scale(2) first, then translate(10, 0)
+        let mut matrix = cairo::Matrix::new(2.0, 0.0, 0.0, 2.0, 10.0, 0.0);
+        matrix.invert();
+
+        let gradient = cairo::LinearGradient::new(0.0, 0.0, 20.0, 0.0);
+        gradient.set_matrix(matrix);
+        gradient.set_extend(cairo::Extend::Pad);
+        gradient.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, 1.0);
+        gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 1.0);
+
+        cr.rectangle(0.0, 0.0, 100.0, 10.0);
+        cr.set_source(&gradient);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "gradient_transform_is_composed_with_element_transform",
+    );
+}
+
+#[test]
+fn pattern_transform_is_composed_with_element_transform() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="20">
+  <defs>
+    <pattern id="pat" patternUnits="userSpaceOnUse" width="10" height="10"
+             patternTransform="scale(2)" transform="translate(5, 0)">
+      <rect width="5" height="10" fill="black"/>
+    </pattern>
+  </defs>
+  <rect width="100" height="20" fill="url(#pat)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 20),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 20.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 20).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // Tiles are 20px wide after the scale(2), and then get shifted by 5px
+        for i in 0..5 {
+            cr.rectangle(f64::from(5 + i * 20), 0.0, 10.0, 20.0);
+        }
+
+        cr.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "pattern_transform_is_composed_with_element_transform",
+    );
+}
+
+#[test]
+fn mesh_gradient_with_uniform_color() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <meshgradient id="mesh" gradientUnits="userSpaceOnUse" x="0" y="0">
+      <meshrow>
+        <meshpatch>
+          <stop path="l 50,0" stop-color="lime"/>
+          <stop path="l 0,100" stop-color="lime"/>
+          <stop path="l -50,0" stop-color="lime"/>
+          <stop path="l 0,-100" stop-color="lime"/>
+        </meshpatch>
+        <meshpatch>
+          <stop path="l 50,0"/>
+          <stop path="L 100,100" stop-color="lime"/>
+          <stop path="l -50,0" stop-color="lime"/>
+        </meshpatch>
+      </meshrow>
+    </meshgradient>
+  </defs>
+  <rect width="100" height="100" fill="url(#mesh)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 100.0, 100.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "mesh_gradient_with_uniform_color",
+    );
+}
+
+#[test]
+fn radial_gradient_with_reflect_spread_method() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <radialGradient id="grad" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="10"
+                    spreadMethod="reflect">
+      <stop offset="0" stop-color="black"/>
+      <stop offset="1" stop-color="white"/>
+    </radialGradient>
+  </defs>
+  <rect width="100" height="100" fill="url(#grad)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // Cairo does the right thing for concentric circles
+        let gradient = cairo::RadialGradient::new(50.0, 50.0, 0.0, 50.0, 50.0, 10.0);
+        gradient.set_extend(cairo::Extend::Reflect);
+        gradient.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, 1.0);
+        gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 1.0);
+
+        cr.rectangle(0.0, 0.0, 100.0, 100.0);
+        cr.set_source(&gradient);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "radial_gradient_with_reflect_spread_method",
+    );
+}
+
+#[test]
+fn radial_gradient_with_offset_focus_and_reflect_spread_method() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <radialGradient id="grad" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="20"
+                    fx="40" fy="45" spreadMethod="reflect">
+      <stop offset="0" stop-color="black"/>
+      <stop offset="1" stop-color="white"/>
+    </radialGradient>
+  </defs>
+  <rect width="100" height="100" fill="url(#grad)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // The image backend extends the cone from the focus to the circle
+        let gradient = cairo::RadialGradient::new(40.0, 45.0, 0.0, 50.0, 50.0, 20.0);
+        gradient.set_extend(cairo::Extend::Reflect);
+        gradient.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, 1.0);
+        gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 1.0);
+
+        cr.rectangle(0.0, 0.0, 100.0, 100.0);
+        cr.set_source(&gradient);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "radial_gradient_with_offset_focus_and_reflect_spread_method",
+    );
+}
+
+#[test]
+fn radial_gradient_with_focus_outside_the_circle() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <radialGradient id="grad" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="20"
+                    fx="90" fy="50">
+      <stop offset="0" stop-color="black"/>
+      <stop offset="1" stop-color="white"/>
+    </radialGradient>
+  </defs>
+  <rect width="100" height="100" fill="url(#grad)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // The focus gets moved to the edge of the circle
+        let gradient = cairo::RadialGradient::new(70.0, 50.0, 0.0, 50.0, 50.0, 20.0);
+        gradient.set_extend(cairo::Extend::Pad);
+        gradient.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, 1.0);
+        gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 1.0);
+
+        cr.rectangle(0.0, 0.0, 100.0, 100.0);
+        cr.set_source(&gradient);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "radial_gradient_with_focus_outside_the_circle",
+    );
+}
+
+#[test]
+fn hatch_with_vertical_lines() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <hatch id="hatch" hatchUnits="userSpaceOnUse" pitch="10">
+      <hatchpath offset="5" stroke="lime" stroke-width="2"/>
+    </hatch>
+  </defs>
+  <rect width="100" height="100" fill="url(#hatch)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        for i in 0..10 {
+            cr.rectangle(f64::from(i) * 10.0 + 4.0, 0.0, 2.0, 100.0);
+        }
+
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "hatch_with_vertical_lines");
+}
+
+#[test]
+fn hatch_with_fill_opacity() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <hatch id="hatch" hatchUnits="userSpaceOnUse" pitch="10">
+      <hatchpath offset="5" stroke="lime" stroke-width="2"/>
+    </hatch>
+  </defs>
+  <rect width="100" height="100" fill="url(#hatch)" fill-opacity="0.5"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        for i in 0..10 {
+            cr.rectangle(f64::from(i) * 10.0 + 4.0, 0.0, 2.0, 100.0);
+        }
+
+        cr.set_source_rgba(0.0, 1.0, 0.0, 0.5);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "hatch_with_fill_opacity");
+}
+
+#[test]
+fn marker_content_uses_context_fill() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <marker id="marker" markerUnits="userSpaceOnUse" markerWidth="10" markerHeight="10"
+            refX="5" refY="5">
+      <rect width="10" height="10" fill="context-fill"/>
+    </marker>
+  </defs>
+  <path d="M 20 50 L 80 50" fill="lime" stroke="none"
+        marker-start="url(#marker)" marker-end="url(#marker)"/>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(15.0, 45.0, 10.0, 10.0);
+        cr.rectangle(75.0, 45.0, 10.0, 10.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "marker_content_uses_context_fill",
+    );
+}
+
+#[test]
+fn marker_clips_to_viewport_not_to_view_box() {
+    // The viewBox is narrower than the marker's viewport, so with the default
+    // xMidYMid meet the viewport extends 5 units past each side of the viewBox.
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <marker id="marker" markerUnits="userSpaceOnUse" markerWidth="20" markerHeight="20"
+            viewBox="0 0 10 20" refX="5" refY="10">
+      <rect x="-10" y="-10" width="30" height="40" fill="lime"/>
+    </marker>
+  </defs>
+  <path d="M 50 50 L 60 50" stroke="none" marker-start="url(#marker)"/>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(40.0, 40.0, 20.0, 20.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "marker_clips_to_viewport_not_to_view_box",
+    );
+}
+
+#[test]
+fn marker_stroke_width_units_use_the_non_scaling_stroke_width() {
+    // The stroke is 2 pixels wide regardless of the scale, so the marker is 3 * 2 pixels
+    // wide instead of 3 * 2 * 4.
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <marker id="marker" markerUnits="strokeWidth" markerWidth="3" markerHeight="3"
+            refX="1.5" refY="1.5">
+      <rect width="3" height="3" fill="lime"/>
+    </marker>
+  </defs>
+  <g transform="scale(4)">
+    <path d="M 5 10 L 20 10" fill="none" stroke="none" stroke-width="2"
+          vector-effect="non-scaling-stroke" marker-start="url(#marker)"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(17.0, 37.0, 6.0, 6.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "marker_stroke_width_units_use_the_non_scaling_stroke_width",
+    );
+}
+
+#[test]
+fn paint_order_stroke_goes_under_fill() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="20" y="20" width="60" height="60" fill="lime" stroke="blue" stroke-width="20"
+        paint-order="stroke"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(10.0, 10.0, 80.0, 80.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.fill();
+
+        cr.rectangle(20.0, 20.0, 60.0, 60.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "paint_order_stroke_goes_under_fill",
+    );
+}
+
+#[test]
+fn paint_order_markers_share_the_group_opacity() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <marker id="marker" markerUnits="userSpaceOnUse" markerWidth="30" markerHeight="30"
+            refX="15" refY="15">
+      <rect width="30" height="30" fill="lime"/>
+    </marker>
+  </defs>
+  <path d="M 40 25 H 85" stroke="blue" stroke-width="10" marker-start="url(#marker)"
+        opacity="0.5" paint-order="fill markers stroke"/>
+  <path d="M 40 75 H 85" stroke="blue" stroke-width="10" marker-start="url(#marker)"
+        opacity="0.5" paint-order="markers"/>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        for y in &[25.0, 75.0] {
+            cr.push_group();
+
+            cr.rectangle(25.0, y - 15.0, 30.0, 30.0);
+            cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+            cr.fill();
+
+            cr.rectangle(40.0, y - 5.0, 45.0, 10.0);
+            cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+            cr.fill();
+
+            cr.pop_group_to_source();
+            cr.paint_with_alpha(0.5);
+        }
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "paint_order_markers_share_the_group_opacity",
+    );
+}
+
+#[test]
+fn object_bounding_box_units_with_zero_height_bbox_render_nothing() {
+    // Horizontal lines have a bounding box with zero height, so all the
+    // effects that use objectBoundingBox units make them disappear.
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <clipPath id="clip" clipPathUnits="objectBoundingBox">
+      <rect width="1" height="1"/>
+    </clipPath>
+    <mask id="mask">
+      <rect x="-1" y="-1" width="3" height="3" fill="white"/>
+    </mask>
+    <filter id="filter">
+      <feOffset/>
+    </filter>
+    <pattern id="pattern" width="1" height="1">
+      <rect width="100" height="100" fill="red"/>
+    </pattern>
+  </defs>
+  <rect x="10" y="60" width="80" height="30" fill="lime"/>
+  <line x1="10" y1="10" x2="90" y2="10" stroke="red" stroke-width="5" clip-path="url(#clip)"/>
+  <line x1="10" y1="20" x2="90" y2="20" stroke="red" stroke-width="5" mask="url(#mask)"/>
+  <line x1="10" y1="30" x2="90" y2="30" stroke="red" stroke-width="5" filter="url(#filter)"/>
+  <line x1="10" y1="40" x2="90" y2="40" stroke="url(#pattern)" stroke-width="5"/>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(10.0, 60.0, 80.0, 30.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "object_bounding_box_units_with_zero_height_bbox_render_nothing",
+    );
+}
+
+#[test]
+fn offset_path_places_and_rotates_element() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect width="20" height="10" fill="lime"
+        style="offset-path: path('M 50 10 L 50 90'); offset-distance: 50%"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // offset-rotate is auto, so the rect is rotated to follow the path downwards
+        cr.rectangle(40.0, 50.0, 10.0, 20.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "offset_path_places_and_rotates_element",
+    );
+}
+
+#[test]
+fn non_scaling_stroke_has_constant_device_width() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g transform="scale(4)">
+    <line x1="5" y1="10" x2="20" y2="10" stroke="lime" stroke-width="2"
+          vector-effect="non-scaling-stroke"/>
+  </g>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(20.0, 39.0, 60.0, 2.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "non_scaling_stroke_has_constant_device_width",
+    );
+}
+
+#[test]
+fn viewport_units_refer_to_toplevel_viewport() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+  <svg x="10" y="10" width="20" height="20">
+    <rect width="50vw" height="10vmax" fill="lime"/>
+  </svg>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 50),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 50.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 50).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // The nested <svg> clips its content, but doesn't change what 1vw means
+        cr.rectangle(10.0, 10.0, 20.0, 10.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "viewport_units_refer_to_toplevel_viewport",
+    );
+}
+
+#[test]
+fn css_transform_overrides_transform_attribute() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    #square {
+      transform: translate(20px, 10%) rotate(0.5turn);
+      transform-origin: 20px 20px;
+    }
+  </style>
+  <rect id="square" x="10" y="10" width="20" height="20" fill="lime" transform="translate(50, 50)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        // The square rotates in place around its center, and then moves by (20, 10)
+        cr.rectangle(30.0, 20.0, 20.0, 20.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "css_transform_overrides_transform_attribute",
+    );
+}
+
+#[test]
+fn css_d_property_overrides_d_attribute() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    #square {
+      d: path("M 50 50 h 20 v 20 h -20 z");
+    }
+  </style>
+  <path id="square" d="M 10 10 h 20 v 20 h -20 z" fill="lime"/>
+  <path d="M 10 60 h 10 v 10 h -10 z" style="d: none" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(50.0, 50.0, 20.0, 20.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "css_d_property_overrides_d_attribute",
+    );
+}
+
+#[test]
+fn geometry_properties_override_attributes() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <style>
+    rect { x: 10px; y: 10px; width: 30px; height: 20px; }
+    circle { cx: 70px; cy: 70px; r: 10px; }
+  </style>
+  <rect x="50" y="50" width="10" height="10" fill="lime"/>
+  <circle cx="20" cy="80" r="5" fill="lime"/>
+  <ellipse cx="20" cy="80" rx="5" ry="5" style="cx: 80px; cy: 20px; rx: 10px; ry: 5px"
+           fill="lime"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="30" height="20" fill="lime"/>
+  <circle cx="70" cy="70" r="10" fill="lime"/>
+  <ellipse cx="80" cy="20" rx="10" ry="5" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "geometry_properties_override_attributes",
+    );
+}
+
+#[test]
+fn auto_radius_takes_the_other_radius() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="40" height="30" rx="8" fill="lime"/>
+  <rect x="60" y="10" width="30" height="40" ry="auto" style="rx: 6px" fill="lime"/>
+  <ellipse cx="30" cy="75" rx="15" ry="auto" fill="lime"/>
+  <ellipse cx="75" cy="75" rx="auto" style="ry: 10px" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="40" height="30" rx="8" ry="8" fill="lime"/>
+  <rect x="60" y="10" width="30" height="40" rx="6" ry="6" fill="lime"/>
+  <circle cx="30" cy="75" r="15" fill="lime"/>
+  <circle cx="75" cy="75" r="10" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "auto_radius_takes_the_other_radius",
+    );
+}
+
+#[test]
+fn clip_path_basic_shapes() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="40" height="40" fill="lime"
+        style="clip-path: circle(20px at 30px 30px) fill-box"/>
+  <rect x="60" y="10" width="30" height="30" fill="lime" style="clip-path: inset(10px)"/>
+  <rect x="10" y="60" width="30" height="30" fill="lime"
+        style="clip-path: polygon(0 0, 100% 0, 0 100%)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <clipPath id="circle">
+    <circle cx="40" cy="40" r="20"/>
+  </clipPath>
+  <clipPath id="polygon">
+    <path d="M 10 60 L 40 60 L 10 90 Z"/>
+  </clipPath>
+  <rect x="10" y="10" width="40" height="40" fill="lime" clip-path="url(#circle)"/>
+  <rect x="70" y="20" width="10" height="10" fill="lime"/>
+  <rect x="10" y="60" width="30" height="30" fill="lime" clip-path="url(#polygon)"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "clip_path_basic_shapes");
+}
+
+#[test]
+fn fe_tile_repeats_the_exact_subregion_when_scaled() {
+    // Each tile is 10 user units wide, half lime and half transparent; at this scale
+    // that is 12.5 pixels, so the tiles don't fall on whole pixels.
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="125" height="125">
+  <defs>
+    <filter id="filter" filterUnits="userSpaceOnUse" x="0" y="0" width="100" height="100">
+      <feFlood flood-color="lime" x="0" y="0" width="5" height="100" result="flood"/>
+      <feOffset in="flood" x="0" y="0" width="10" height="100" result="tile"/>
+      <feTile in="tile"/>
+    </filter>
+  </defs>
+  <g transform="scale(1.25)">
+    <rect width="100" height="100" fill="red" filter="url(#filter)"/>
+  </g>
+</svg>
+"##,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(125, 125),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 125.0,
+            height: 125.0,
+        },
+    )
+    .unwrap();
+
+    // Look at the middle of each half of every tile, away from the antialiased edges;
+    // if the tiles were rounded to whole pixels, they would drift from these positions.
+    for i in 0..10 {
+        let tile_x = f64::from(i) * 12.5;
+
+        let lime = output_surf.get_pixel((tile_x + 3.125) as u32, 60);
+        assert_eq!((lime.r, lime.g, lime.b, lime.a), (0, 255, 0, 255));
+
+        let transparent = output_surf.get_pixel((tile_x + 9.375) as u32, 60);
+        assert_eq!(transparent.a, 0);
+    }
+}
+
+#[test]
+fn cached_filter_results_are_invalidated_by_style_changes() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" x="0" y="0" width="1" height="1">
+    <feFlood flood-color="lime"/>
+  </filter>
+  <rect x="10" y="10" width="80" height="80" filter="url(#filter)"/>
+</svg>
+"#,
+    );
+
+    let render = |svg| {
+        render_document(
+            svg,
+            SurfaceSize(100, 100),
+            |_cr| (),
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 100.0,
+            },
+        )
+        .unwrap()
+    };
+
+    let reference = |r, g, b| {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+        {
+            let cr = cairo::Context::new(&surface);
+            cr.rectangle(10.0, 10.0, 80.0, 80.0);
+            cr.set_source_rgba(r, g, b, 1.0);
+            cr.fill();
+        }
+
+        SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap()
+    };
+
+    let lime = reference(0.0, 1.0, 0.0);
+
+    // The second time around, the result comes from the cache
+    compare_to_surface(&render(&svg), &lime, "cached_filter_results_1");
+    compare_to_surface(&render(&svg), &lime, "cached_filter_results_2");
+
+    // The source graphic doesn't change, but the filter does
+    svg.set_stylesheet("feFlood { flood-color: blue !important; }")
+        .unwrap();
+
+    compare_to_surface(
+        &render(&svg),
+        &reference(0.0, 0.0, 1.0),
+        "cached_filter_results_3",
+    );
+}
+
+#[test]
+fn resolved_paint_servers_are_invalidated_by_style_changes() {
+    let svg = load_svg(
+        br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+  <linearGradient id="stops">
+    <stop offset="0" stop-color="lime"/>
+    <stop offset="1" stop-color="lime"/>
+  </linearGradient>
+  <radialGradient id="gradient" xlink:href="#stops"/>
+  <rect x="10" y="10" width="80" height="80" fill="url(#gradient)"/>
+</svg>
+"##,
+    );
+
+    let render = |svg| {
+        render_document(
+            svg,
+            SurfaceSize(100, 100),
+            |_cr| (),
+            cairo::Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 100.0,
+            },
+        )
+        .unwrap()
+    };
+
+    let reference = |r, g, b| {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+        {
+            let cr = cairo::Context::new(&surface);
+            cr.rectangle(10.0, 10.0, 80.0, 80.0);
+            cr.set_source_rgba(r, g, b, 1.0);
+            cr.fill();
+        }
+
+        SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap()
+    };
+
+    let lime = reference(0.0, 1.0, 0.0);
+
+    // The radial gradient gets its stops from the linear one; the second time around,
+    // the resolved gradient comes from the cache.
+    compare_to_surface(&render(&svg), &lime, "resolved_paint_servers_1");
+    compare_to_surface(&render(&svg), &lime, "resolved_paint_servers_2");
+
+    svg.set_stylesheet("stop { stop-color: blue !important; }")
+        .unwrap();
+
+    compare_to_surface(
+        &render(&svg),
+        &reference(0.0, 0.0, 1.0),
+        "resolved_paint_servers_3",
+    );
+}
+
+#[test]
+fn elements_with_huge_transforms_are_skipped() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <g transform="scale(1e30)">
+    <g transform="scale(1e30)">
+      <g transform="scale(1e30)">
+        <g transform="scale(1e30)">
+          <g transform="scale(1e30)">
+            <rect id="overflow" transform="scale(1e30)" width="10" height="10" fill="red"/>
+          </g>
+        </g>
+      </g>
+    </g>
+  </g>
+  <rect x="1e30" y="1e30" width="1e30" height="1e30" fill="red"/>
+  <rect x="10" y="10" width="80" height="80" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(10.0, 10.0, 80.0, 80.0);
+        cr.set_source_rgba(0.0, 1.0, 0.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "elements_with_huge_transforms_are_skipped",
+    );
+}
+
+#[test]
+fn css_filter_functions() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="30" height="30" fill="red" style="filter: invert()"/>
+  <rect x="60" y="10" width="30" height="30" fill="lime" style="filter: opacity(50%)"/>
+  <rect x="10" y="50" width="30" height="30" fill="lime"
+        style="filter: drop-shadow(blue 10px 10px) grayscale(0)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="30" height="30" fill="cyan"/>
+  <rect x="60" y="10" width="30" height="30" fill="lime" opacity="0.5"/>
+  <rect x="20" y="60" width="30" height="30" fill="blue"/>
+  <rect x="10" y="50" width="30" height="30" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "css_filter_functions");
+}
+
+#[test]
+fn drop_shadow_primitive() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" x="0" y="0" width="2" height="2">
+    <feDropShadow dx="10" dy="20" stdDeviation="0" flood-color="blue" flood-opacity="0.5"/>
+  </filter>
+  <rect x="10" y="10" width="30" height="30" fill="lime" filter="url(#filter)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="20" y="30" width="30" height="30" fill="blue" fill-opacity="0.5"/>
+  <rect x="10" y="10" width="30" height="30" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "drop_shadow_primitive");
+}
+
+#[test]
+fn background_image_honors_enable_background() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" filterUnits="userSpaceOnUse" x="0" y="0" width="100" height="100">
+    <feOffset in="BackgroundImage" dx="50"/>
+  </filter>
+  <rect width="50" height="50" fill="blue"/>
+  <rect width="1" height="1" filter="url(#filter)"/>
+  <g enable-background="new 0 0 25 100">
+    <rect y="50" width="50" height="50" fill="lime"/>
+    <rect width="1" height="1" filter="url(#filter)"/>
+  </g>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    // Only the contents of the group before the filtered element, and inside its
+    // background region, are part of the BackgroundImage
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect width="50" height="50" fill="blue"/>
+  <rect y="50" width="50" height="50" fill="lime"/>
+  <rect x="50" y="50" width="25" height="50" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "background_image_honors_enable_background",
+    );
+}
+
+#[test]
+fn independent_filter_primitives_see_results_in_document_order() {
+    // The feOffset which redefines "a" doesn't depend on anything, so it gets rendered
+    // along with the first two primitives; the first feComposite must still see the
+    // first "a".
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" x="0" y="0" width="2" height="2">
+    <feOffset in="SourceGraphic" dx="30" dy="30" result="a"/>
+    <feOffset in="SourceGraphic" result="b"/>
+    <feComposite in="a" in2="b" result="c"/>
+    <feOffset in="SourceGraphic" dx="30" result="a"/>
+    <feComposite in="a" in2="c"/>
+  </filter>
+  <rect x="10" y="10" width="30" height="30" fill="lime" filter="url(#filter)"/>
+</svg>
+"#,
+    );
+
+    let output_surf = render_document(
+        &svg,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <rect x="10" y="10" width="30" height="30" fill="lime"/>
+  <rect x="40" y="10" width="30" height="30" fill="lime"/>
+  <rect x="40" y="40" width="30" height="30" fill="lime"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "independent_filter_primitives_see_results_in_document_order",
+    );
+}
+
+#[test]
+fn turbulence_seed_can_be_overridden() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" x="0" y="0" width="1" height="1">
+    <feTurbulence baseFrequency="0.05" numOctaves="2" seed="1"/>
+  </filter>
+  <rect width="100" height="100" filter="url(#filter)"/>
+</svg>
+"#,
+    );
+
+    let output = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+
+    {
+        let cr = cairo::Context::new(&output);
+        let viewport = cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        };
+
+        CairoRenderer::new(&svg)
+            .with_turbulence_seed(7)
+            .render_document(&cr, &viewport)
+            .unwrap();
+    }
+
+    let output_surf = SharedImageSurface::new(output, SurfaceType::SRgb).unwrap();
+
+    let reference = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <filter id="filter" x="0" y="0" width="1" height="1">
+    <feTurbulence baseFrequency="0.05" numOctaves="2" seed="7"/>
+  </filter>
+  <rect width="100" height="100" filter="url(#filter)"/>
+</svg>
+"#,
+    );
+
+    let reference_surf = render_document(
+        &reference,
+        SurfaceSize(100, 100),
+        |_cr| (),
+        cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 100.0,
+        },
+    )
+    .unwrap();
+
+    compare_to_surface(&output_surf, &reference_surf, "turbulence_seed_can_be_overridden");
+}
+
+#[test]
+fn word_spacing_widens_spaces() {
+    let ink_rect = |style: &str| {
+        let document = format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="100">
+  <text id="text" x="10" y="50" font-family="sans-serif" font-size="20" style="{}">a b c</text>
+</svg>
+"##,
+            style
+        );
+
+        let svg = load_svg_with(Loader::new(), document.as_bytes());
+
+        let renderer = CairoRenderer::new(&svg);
+        let (ink_r, _) = renderer.geometry_for_element(Some("#text")).unwrap();
+        ink_r
+    };
+
+    let normal = ink_rect("word-spacing: normal");
+    let spaced = ink_rect("word-spacing: 20px");
+    let tight = ink_rect("word-spacing: -2px");
+
+    // Two spaces, each one 20px wider
+    assert!((spaced.width - normal.width - 40.0).abs() < 0.1);
+    assert!((normal.width - tight.width - 4.0).abs() < 0.1);
+    assert!((spaced.x - normal.x).abs() < 0.01);
+}
+
+#[test]
+fn white_space_controls_spaces_and_newlines() {
+    let logical_rect = |style: &str| {
+        let document = format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200">
+  <text id="text" x="10" y="50" font-family="sans-serif" font-size="20" style="{}">a    b
+c</text>
+</svg>
+"##,
+            style
+        );
+
+        let svg = load_svg_with(Loader::new(), document.as_bytes());
+
+        let renderer = CairoRenderer::new(&svg);
+        let (_, logical_r) = renderer.geometry_for_element(Some("#text")).unwrap();
+        logical_r
+    };
+
+    let normal = logical_rect("white-space: normal");
+    let nowrap = logical_rect("white-space: nowrap");
+    let pre = logical_rect("white-space: pre");
+    let pre_line = logical_rect("white-space: pre-line");
+
+    // "a bc" versus "a b c" on one line
+    assert!(nowrap.width > normal.width);
+    assert!((nowrap.height - normal.height).abs() < 0.01);
+
+    // "a    b" and "c" on two lines, versus "a b" and "c"
+    assert!(pre.height >= normal.height + 19.0);
+    assert!((pre_line.height - pre.height).abs() < 0.01);
+    assert!(pre.width > pre_line.width);
+}
+
+#[test]
+fn right_to_left_text_ends_at_its_anchor() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="400" height="100">
+  <text id="ltr" x="200" y="50" font-family="sans-serif" font-size="20">abc</text>
+  <text id="rtl" x="200" y="50" font-family="sans-serif" font-size="20" direction="rtl">abc</text>
+</svg>
+"#,
+    );
+
+    let renderer = CairoRenderer::new(&svg);
+
+    let (_, ltr) = renderer.geometry_for_element(Some("#ltr")).unwrap();
+    let (_, rtl) = renderer.geometry_for_element(Some("#rtl")).unwrap();
+
+    assert!((ltr.x - 200.0).abs() < 0.01);
+    assert!((rtl.x + rtl.width - 200.0).abs() < 0.01);
+    assert!((rtl.width - ltr.width).abs() < 0.01);
+}
+
+#[test]
+fn bidi_override_reverses_the_characters() {
+    let glyph_x = |unicode_bidi: &str, ch: char| {
+        let document = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+  <text id="text" x="10" y="50" font-family="sans-serif" font-size="20">
+    <tspan direction="rtl" unicode-bidi="{}">Hi</tspan>
+  </text>
+</svg>
+"#,
+            unicode_bidi
+        );
+
+        let svg = load_svg_with(Loader::new(), document.as_bytes());
+
+        let renderer = CairoRenderer::new(&svg);
+        let viewport = cairo::Rectangle::new(0.0, 0.0, 200.0, 100.0);
+
+        let runs = renderer
+            .glyph_runs_for_layer(Some("#text"), &viewport)
+            .unwrap();
+
+        runs.iter()
+            .flat_map(|run| {
+                run.glyphs
+                    .iter()
+                    .filter(|glyph| run.text[glyph.cluster..].starts_with(ch))
+                    .map(move |glyph| run.transform.transform_point(glyph.x, glyph.y).0)
+            })
+            .next()
+            .unwrap()
+    };
+
+    // Latin letters stay left-to-right in an embedding, but not in an override
+    assert!(glyph_x("embed", 'H') < glyph_x("embed", 'i'));
+    assert!(glyph_x("bidi-override", 'H') > glyph_x("bidi-override", 'i'));
+}
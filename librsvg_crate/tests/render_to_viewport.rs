@@ -1,4 +1,5 @@
 use cairo;
+use librsvg::{CairoRenderer, RenderingError};
 
 mod utils;
 
@@ -274,3 +275,84 @@ fn mask_on_transformed_viewport() {
         "mask_on_transformed_viewport",
     );
 }
+
+#[test]
+fn render_document_with_zoom_and_pan() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="48" height="48">
+  <rect x="8" y="8" width="32" height="32" fill="blue"/>
+</svg>
+"#,
+    );
+
+    let output_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 48, 48).unwrap();
+
+    {
+        let cr = cairo::Context::new(&output_surf);
+
+        CairoRenderer::new(&svg)
+            .render_document_with_zoom(
+                &cr,
+                &cairo::Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 48.0,
+                    height: 48.0,
+                },
+                2.0,
+                -16.0,
+                -16.0,
+            )
+            .unwrap();
+    }
+
+    let output_surf = SharedImageSurface::new(output_surf, SurfaceType::SRgb).unwrap();
+
+    let reference_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 48, 48).unwrap();
+
+    {
+        let cr = cairo::Context::new(&reference_surf);
+
+        cr.rectangle(0.0, 0.0, 48.0, 48.0);
+        cr.set_source_rgba(0.0, 0.0, 1.0, 1.0);
+        cr.fill();
+    }
+
+    let reference_surf = SharedImageSurface::new(reference_surf, SurfaceType::SRgb).unwrap();
+
+    compare_to_surface(
+        &output_surf,
+        &reference_surf,
+        "render_document_with_zoom_and_pan",
+    );
+}
+
+#[test]
+fn render_document_with_invalid_zoom_fails() {
+    let svg = load_svg(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="48" height="48">
+  <rect x="8" y="8" width="32" height="32" fill="blue"/>
+</svg>
+"#,
+    );
+
+    let output_surf = cairo::ImageSurface::create(cairo::Format::ARgb32, 48, 48).unwrap();
+    let cr = cairo::Context::new(&output_surf);
+    let renderer = CairoRenderer::new(&svg);
+
+    let viewport = cairo::Rectangle {
+        x: 0.0,
+        y: 0.0,
+        width: 48.0,
+        height: 48.0,
+    };
+
+    for &zoom in &[0.0, -1.0, std::f64::NAN, std::f64::INFINITY] {
+        assert_eq!(
+            renderer.render_document_with_zoom(&cr, &viewport, zoom, 0.0, 0.0),
+            Err(RenderingError::Cairo(cairo::Status::InvalidSize))
+        );
+    }
+}
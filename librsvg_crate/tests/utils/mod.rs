@@ -20,10 +20,15 @@ use self::compare_surfaces::compare_surfaces;
 pub use self::compare_surfaces::BufferDiff;
 
 pub fn load_svg(input: &'static [u8]) -> SvgHandle {
-    let bytes = glib::Bytes::from_static(input);
+    load_svg_with(Loader::new(), input)
+}
+
+/// Like `load_svg()`, but with a `loader` that the test configured.
+pub fn load_svg_with(loader: Loader, input: &[u8]) -> SvgHandle {
+    let bytes = glib::Bytes::from(input);
     let stream = gio::MemoryInputStream::new_from_bytes(&bytes);
 
-    Loader::new().read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>).unwrap()
+    loader.read_stream(&stream, None::<&gio::File>, None::<&gio::Cancellable>).unwrap()
 }
 
 #[derive(Copy, Clone)]
@@ -203,6 +203,100 @@ get_lookup_id_from_command_line (const char *lookup_id)
 
     return export_lookup_id;
 }
+
+/* Parses an export area specified as "x0:y0:x1:y1", in the same way as
+ * Inkscape's --export-area option.
+ */
+static gboolean
+parse_export_area (const char *str, RsvgRectangle *area)
+{
+    double x0, y0, x1, y1;
+    char *end;
+
+    x0 = g_ascii_strtod (str, &end);
+    if (end == str || *end != ':')
+        return FALSE;
+
+    str = end + 1;
+    y0 = g_ascii_strtod (str, &end);
+    if (end == str || *end != ':')
+        return FALSE;
+
+    str = end + 1;
+    x1 = g_ascii_strtod (str, &end);
+    if (end == str || *end != ':')
+        return FALSE;
+
+    str = end + 1;
+    y1 = g_ascii_strtod (str, &end);
+    if (end == str || *end != '\0')
+        return FALSE;
+
+    if (x1 <= x0 || y1 <= y0)
+        return FALSE;
+
+    area->x = x0;
+    area->y = y0;
+    area->width = x1 - x0;
+    area->height = y1 - y0;
+
+    return TRUE;
+}
+
+/* Computes the ink extents of the whole drawing, in the coordinate space
+ * of a viewport of the document's natural size.
+ */
+static gboolean
+get_drawing_area (RsvgHandle *handle, RsvgDimensionData *dimensions, RsvgRectangle *area, GError **error)
+{
+    RsvgRectangle viewport;
+    RsvgRectangle logical_rect;
+
+    viewport.x = 0.0;
+    viewport.y = 0.0;
+    viewport.width = dimensions->width;
+    viewport.height = dimensions->height;
+
+    return rsvg_handle_get_geometry_for_layer (handle, NULL, &viewport, area, &logical_rect, error);
+}
+
+/* Renders the document's animations to an animated image, and writes it to the
+ * output file.  Exits on error.
+ */
+static void
+write_animation (RsvgHandle *handle,
+                 RsvgAnimationFormat format,
+                 int width,
+                 int height,
+                 double frame_rate,
+                 double duration,
+                 guint loops,
+                 FILE *output_file)
+{
+    GOutputStream *stream;
+    GError *error = NULL;
+    gsize size;
+
+    stream = g_memory_output_stream_new_resizable ();
+
+    if (!rsvg_handle_write_animation (handle, stream, format, width, height,
+                                      frame_rate, duration, loops, &error)) {
+        g_printerr (_("Could not render animation: "));
+        display_error (error);
+        exit (1);
+    }
+
+    g_output_stream_close (stream, NULL, NULL);
+
+    size = g_memory_output_stream_get_data_size (G_MEMORY_OUTPUT_STREAM (stream));
+    if (fwrite (g_memory_output_stream_get_data (G_MEMORY_OUTPUT_STREAM (stream)),
+                1, size, output_file) != size) {
+        g_printerr (_("Error writing the animation\n"));
+        exit (1);
+    }
+
+    g_object_unref (stream);
+}
  
 int
 main (int argc, char **argv)
@@ -219,13 +313,27 @@ main (int argc, char **argv)
     char *format = NULL;
     char *output = NULL;
     char *export_id = NULL;
+    char *export_area_str = NULL;
+    gboolean export_area_drawing = FALSE;
+    gboolean has_export_area = FALSE;
+    RsvgRectangle export_area;
     int keep_aspect_ratio = FALSE;
     guint32 background_color = 0;
     char *background_color_str = NULL;
+    char *turbulence_seed_str = NULL;
+    gboolean has_turbulence_seed = FALSE;
+    int turbulence_seed = 0;
     gboolean using_stdin = FALSE;
     gboolean unlimited = FALSE;
     gboolean keep_image_data = FALSE;
     gboolean no_keep_image_data = FALSE;
+    double frame_rate = 24.0;
+    double duration = 1.0;
+    int loops = 0;
+    int ps_level = 3;
+    gboolean tight_bounding_box = FALSE;
+    gboolean is_animation = FALSE;
+    RsvgAnimationFormat animation_format = RSVG_ANIMATION_FORMAT_APNG;
     GError *error = NULL;
 
     gboolean success = TRUE;
@@ -270,15 +378,31 @@ main (int argc, char **argv)
         {"height", 'h', 0, G_OPTION_ARG_INT, &height,
          N_("height [optional; defaults to the SVG's height]"), N_("<int>")},
         {"format", 'f', 0, G_OPTION_ARG_STRING, &format,
-         N_("save format [optional; defaults to 'png']"), N_("[png, pdf, ps, eps, svg, xml, recording]")},
+         N_("save format [optional; defaults to 'png']"), N_("[png, pdf, ps, eps, svg, xml, recording, apng, gif]")},
         {"output", 'o', 0, G_OPTION_ARG_STRING, &output,
          N_("output filename [optional; defaults to stdout]"), NULL},
         {"export-id", 'i', 0, G_OPTION_ARG_STRING, &export_id,
          N_("SVG id of object to export [optional; defaults to exporting all objects]"), N_("<object id>")},
+        {"export-area", 0, 0, G_OPTION_ARG_STRING, &export_area_str,
+         N_("area of the canvas to export, in document pixels [optional; defaults to the whole canvas]"), N_("x0:y0:x1:y1")},
+        {"export-area-drawing", 0, 0, G_OPTION_ARG_NONE, &export_area_drawing,
+         N_("export the area covered by the drawing's ink, not the whole canvas"), NULL},
+        {"frame-rate", 0, 0, G_OPTION_ARG_DOUBLE, &frame_rate,
+         N_("frames per second for apng and gif output [optional; defaults to 24]"), N_("<float>")},
+        {"duration", 0, 0, G_OPTION_ARG_DOUBLE, &duration,
+         N_("length of the animation in seconds for apng and gif output [optional; defaults to 1]"), N_("<float>")},
+        {"loops", 0, 0, G_OPTION_ARG_INT, &loops,
+         N_("number of times to play the animation, or 0 to loop forever [optional; defaults to 0]"), N_("<int>")},
+        {"ps-level", 0, 0, G_OPTION_ARG_INT, &ps_level,
+         N_("PostScript language level for ps and eps output [optional; defaults to 3]"), N_("[2, 3]")},
+        {"tight-bounding-box", 0, 0, G_OPTION_ARG_NONE, &tight_bounding_box,
+         N_("crop ps and eps output to the drawing's ink extents"), NULL},
         {"keep-aspect-ratio", 'a', 0, G_OPTION_ARG_NONE, &keep_aspect_ratio,
          N_("whether to preserve the aspect ratio [optional; defaults to FALSE]"), NULL},
         {"background-color", 'b', 0, G_OPTION_ARG_STRING, &background_color_str,
          N_("set the background color [optional; defaults to None]"), N_("[black, white, #abccee, #aaa...]")},
+        {"turbulence-seed", 0, 0, G_OPTION_ARG_STRING, &turbulence_seed_str,
+         N_("seed for all feTurbulence filters [optional; defaults to each element's seed attribute]"), N_("<int>")},
         {"unlimited", 'u', 0, G_OPTION_ARG_NONE, &unlimited, N_("Allow huge SVG files"), NULL},
         {"keep-image-data", 0, 0, G_OPTION_ARG_NONE, &keep_image_data, N_("Keep image data"), NULL},
         {"no-keep-image-data", 0, 0, G_OPTION_ARG_NONE, &no_keep_image_data, N_("Don't keep image data"), NULL},
@@ -337,6 +461,74 @@ main (int argc, char **argv)
         exit (1);
     }
 
+    if (ps_level != 2 && ps_level != 3) {
+        g_printerr (_("The PostScript level must be 2 or 3.\n"));
+        exit (1);
+    }
+
+    if (tight_bounding_box) {
+        if (!format || !(g_str_equal (format, "ps") || g_str_equal (format, "eps"))) {
+            g_printerr (_("--tight-bounding-box can only be used with ps and eps output.\n"));
+            exit (1);
+        }
+
+        if (export_id != NULL || export_area_str != NULL) {
+            g_printerr (_("--tight-bounding-box cannot be used with --export-id or --export-area.\n"));
+            exit (1);
+        }
+
+        /* The page gets cropped to the ink extents, just like with --export-area-drawing */
+        export_area_drawing = TRUE;
+    }
+
+    if ((export_area_str != NULL) + (export_area_drawing != FALSE) + (export_id != NULL) > 1) {
+        g_printerr (_("Only one of --export-id, --export-area, and --export-area-drawing may be specified.\n"));
+        exit (1);
+    }
+
+    if (export_area_str != NULL) {
+        if (!parse_export_area (export_area_str, &export_area)) {
+            g_printerr (_("Invalid export area \"%s\"; it must be specified as x0:y0:x1:y1 with x0 < x1 and y0 < y1.\n"),
+                        export_area_str);
+            exit (1);
+        }
+
+        has_export_area = TRUE;
+    }
+
+    if (turbulence_seed_str != NULL) {
+        gint64 seed;
+
+        errno = 0;
+        seed = g_ascii_strtoll (turbulence_seed_str, &endptr, 10);
+        if (errno != 0 || endptr == turbulence_seed_str || *endptr != '\0'
+            || seed < G_MININT || seed > G_MAXINT) {
+            g_printerr (_("Invalid turbulence seed \"%s\"; it must be an integer.\n"),
+                        turbulence_seed_str);
+            exit (1);
+        }
+
+        turbulence_seed = (int) seed;
+        has_turbulence_seed = TRUE;
+    }
+
+    if (format != NULL && (g_str_equal (format, "apng") || g_str_equal (format, "gif"))) {
+        is_animation = TRUE;
+
+        if (g_str_equal (format, "gif"))
+            animation_format = RSVG_ANIMATION_FORMAT_GIF;
+
+        if (export_id != NULL || export_area_str != NULL || export_area_drawing || background_color_str != NULL) {
+            g_printerr (_("--export-id, --export-area, --export-area-drawing, and --background-color cannot be used with animated output.\n"));
+            exit (1);
+        }
+
+        if (frame_rate <= 0.0 || duration < 0.0 || loops < 0) {
+            g_printerr (_("The frame rate must be positive, and the duration and loops must not be negative.\n"));
+            exit (1);
+        }
+    }
+
     if (dpi_x <= 0.0) {
         dpi_x = 90.0;
     }
@@ -403,6 +595,9 @@ main (int argc, char **argv)
 
         rsvg_handle_set_dpi_x_y (rsvg, dpi_x, dpi_y);
 
+        if (has_turbulence_seed)
+            rsvg_handle_set_turbulence_seed (rsvg, turbulence_seed);
+
         export_lookup_id = get_lookup_id_from_command_line (export_id);
         if (export_lookup_id != NULL
             && !rsvg_handle_has_sub (rsvg, export_lookup_id)) {
@@ -423,6 +618,26 @@ main (int argc, char **argv)
                 exit (1);
             }
 
+            if (export_area_drawing) {
+                if (!get_drawing_area (rsvg, &dimensions, &export_area, &error)) {
+                    g_printerr (_("Could not compute the drawing area for file %s: "), args[i]);
+                    display_error (error);
+                    exit (1);
+                }
+
+                if (export_area.width <= 0.0 || export_area.height <= 0.0) {
+                    g_printerr (_("The SVG %s has an empty drawing area\n"), args[i]);
+                    exit (1);
+                }
+
+                has_export_area = TRUE;
+            }
+
+            if (has_export_area) {
+                dimensions.width = ceil (export_area.width);
+                dimensions.height = ceil (export_area.height);
+            }
+
             unscaled_width = dimensions.width;
             unscaled_height = dimensions.height;
 
@@ -466,6 +681,13 @@ main (int argc, char **argv)
                 exit (1);
             }
 
+            if (is_animation) {
+                write_animation (rsvg, animation_format, scaled_width, scaled_height,
+                                 frame_rate, duration, loops, output_file);
+                g_object_unref (rsvg);
+                break;
+            }
+
             if (!format || !strcmp (format, "png"))
                 surface = cairo_image_surface_create (CAIRO_FORMAT_ARGB32,
                                                       scaled_width, scaled_height);
@@ -513,6 +735,8 @@ main (int argc, char **argv)
                                                               scaled_width, scaled_height);
                 if(!strcmp (format, "eps"))
                     cairo_ps_surface_set_eps(surface, TRUE);
+                cairo_ps_surface_restrict_to_level (surface,
+                                                    ps_level == 2 ? CAIRO_PS_LEVEL_2 : CAIRO_PS_LEVEL_3);
             }
 #endif
 #ifdef CAIRO_HAS_SVG_SURFACE
@@ -577,6 +801,9 @@ main (int argc, char **argv)
 
             /* Move the whole thing to 0, 0 so the object to export is at the origin */
             cairo_translate (cr, -pos.x, -pos.y);
+        } else if (has_export_area) {
+            /* Likewise, move the export area to the origin */
+            cairo_translate (cr, -export_area.x, -export_area.y);
         }
 
         if (!rsvg_handle_render_cairo_sub (rsvg, cr, export_lookup_id)) {
@@ -612,6 +839,8 @@ main (int argc, char **argv)
     fclose (output_file);
 
     g_strfreev (args);
+    g_free (export_area_str);
+    g_free (turbulence_seed_str);
 
     return 0;
 }
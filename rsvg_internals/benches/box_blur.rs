@@ -6,6 +6,7 @@ use cairo;
 use rsvg_internals;
 
 use rsvg_internals::rect::IRect;
+use rsvg_internals::surface_utils::box_blur_simd;
 use rsvg_internals::surface_utils::shared_surface::{
     AlphaOnly,
     Horizontal,
@@ -63,5 +64,43 @@ fn bench_box_blur(c: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, bench_box_blur);
+fn bench_box_blur_simd(c: &mut Criterion) {
+    if !box_blur_simd::is_available() {
+        return;
+    }
+
+    c.bench_function_over_inputs(
+        "box_blur_simd 9",
+        |b, &vertical| {
+            let input_surface =
+                cairo::ImageSurface::create(cairo::Format::ARgb32, SURFACE_SIDE, SURFACE_SIDE)
+                    .unwrap();
+            let input_surface = SharedImageSurface::new(input_surface, SurfaceType::SRgb).unwrap();
+
+            let mut output_surface =
+                cairo::ImageSurface::create(cairo::Format::ARgb32, SURFACE_SIDE, SURFACE_SIDE)
+                    .unwrap();
+            const KERNEL_SIZE: usize = 9;
+
+            let f = if vertical {
+                SharedImageSurface::box_blur_loop_simd::<Vertical>
+            } else {
+                SharedImageSurface::box_blur_loop_simd::<Horizontal>
+            };
+
+            b.iter(|| {
+                f(
+                    &input_surface,
+                    &mut output_surface,
+                    BOUNDS,
+                    KERNEL_SIZE,
+                    KERNEL_SIZE / 2,
+                )
+            })
+        },
+        vec![false, true],
+    );
+}
+
+criterion_group!(benches, bench_box_blur, bench_box_blur_simd);
 criterion_main!(benches);
@@ -31,6 +31,11 @@ impl Angle {
         self.0
     }
 
+    /// The angle pointing in the opposite direction
+    pub fn flip(self) -> Angle {
+        Angle(Angle::normalize(self.0 + PI))
+    }
+
     pub fn bisect(&self, other: Angle) -> Angle {
         let half_delta = (other.0 - self.0) * 0.5;
 
@@ -57,7 +62,7 @@ impl Angle {
 // angle:
 // https://www.w3.org/TR/SVG/types.html#DataTypeAngle
 //
-// angle ::= number ("deg" | "grad" | "rad")?
+// angle ::= number ("deg" | "grad" | "rad" | "turn")?
 //
 impl Parse for Angle {
     fn parse(parser: &mut Parser<'_, '_>) -> Result<Angle, ValueErrorKind> {
@@ -79,9 +84,10 @@ impl Parse for Angle {
                         "deg" => Angle::from_degrees(value),
                         "grad" => Angle::from_degrees(value * 360.0 / 400.0),
                         "rad" => Angle::new(value),
+                        "turn" => Angle::from_degrees(value * 360.0),
                         _ => {
                             return Err(ValueErrorKind::parse_error(
-                                "expected 'deg' | 'grad' | 'rad'",
+                                "expected 'deg' | 'grad' | 'rad' | 'turn'",
                             ));
                         }
                     }
@@ -115,12 +121,19 @@ mod tests {
             Angle::parse_str("-400grad"),
             Ok(Angle::from_degrees(-360.0))
         );
+        assert_eq!(Angle::parse_str("0.25turn"), Ok(Angle::from_degrees(90.0)));
 
         assert!(Angle::parse_str("").is_err());
         assert!(Angle::parse_str("foo").is_err());
         assert!(Angle::parse_str("300foo").is_err());
     }
 
+    #[test]
+    fn flips_angle() {
+        assert_eq!(Angle::new(0.0).flip(), Angle::new(PI));
+        assert_eq!(Angle::from_degrees(270.0).flip(), Angle::from_degrees(90.0));
+    }
+
     fn test_bisection_angle(
         expected: f64,
         incoming_vx: f64,
@@ -0,0 +1,683 @@
+//! Encoding of animated documents as APNG or GIF images.
+//!
+//! An animated document is sampled at a fixed frame rate; each frame is rendered to an
+//! image surface and handed to an [`AnimatedImageEncoder`].  Frames are encoded as they
+//! arrive, so only the encoded output is kept in memory.
+//!
+//! APNG frames are compressed with cairo's PNG encoder, and their image data is then
+//! repackaged into the chunks that [APNG] uses for animation.
+//!
+//! [GIF] only supports 256 colors per frame, so frames are reduced to a fixed palette
+//! with ordered dithering.  Pixels that are less than half opaque become transparent.
+//!
+//! [`AnimatedImageEncoder`]: struct.AnimatedImageEncoder.html
+//! [APNG]: https://wiki.mozilla.org/APNG_Specification
+//! [GIF]: https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+
+use cairo;
+use std::collections::HashMap;
+
+use crate::limits;
+use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
+
+/// File format for animations
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AnimatedImageFormat {
+    /// Animated PNG
+    Apng,
+
+    /// GIF
+    Gif,
+}
+
+/// How to sample an animated document into frames
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AnimationOptions {
+    /// Number of frames per second
+    pub frame_rate: f64,
+
+    /// Length of the animation, in seconds
+    pub duration: f64,
+
+    /// Number of times that the animation is played, or 0 to play it forever
+    pub loops: u32,
+}
+
+impl Default for AnimationOptions {
+    /// One second at 24 frames per second, played forever
+    fn default() -> AnimationOptions {
+        AnimationOptions {
+            frame_rate: 24.0,
+            duration: 1.0,
+            loops: 0,
+        }
+    }
+}
+
+impl AnimationOptions {
+    /// Whether the animation can be rendered.
+    ///
+    /// The frame rate must be finite and greater than zero, the duration must be finite
+    /// and not negative, and there can be at most `limits::MAX_ANIMATION_FRAMES` frames.
+    pub fn is_valid(&self) -> bool {
+        self.frame_rate > 0.0
+            && self.frame_rate.is_finite()
+            && self.duration >= 0.0
+            && self.duration.is_finite()
+            && (self.duration * self.frame_rate).ceil() <= f64::from(limits::MAX_ANIMATION_FRAMES)
+    }
+
+    /// Number of frames in the animation; there is always at least one.
+    ///
+    /// The result is only meaningful if the options are valid; see `is_valid()`.
+    pub fn num_frames(&self) -> u32 {
+        (self.duration * self.frame_rate).ceil().max(1.0) as u32
+    }
+
+    /// Time of the frame with the specified `index` in the document's timeline
+    pub fn frame_time(&self, index: u32) -> f64 {
+        f64::from(index) / self.frame_rate
+    }
+}
+
+/// Encodes a sequence of frames into an animated image
+pub struct AnimatedImageEncoder {
+    format: AnimatedImageFormat,
+    width: i32,
+    height: i32,
+    options: AnimationOptions,
+    output: Vec<u8>,
+    frames_written: u32,
+
+    // Sequence number for the next APNG animation chunk
+    apng_sequence: u32,
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+impl AnimatedImageEncoder {
+    /// Creates an encoder for frames of the specified size.
+    ///
+    /// # Panics
+    /// Panics if the size is not positive, or if it does not fit in 16 bits.
+    pub fn new(
+        format: AnimatedImageFormat,
+        width: i32,
+        height: i32,
+        options: &AnimationOptions,
+    ) -> AnimatedImageEncoder {
+        assert!(width > 0 && width <= 0xffff);
+        assert!(height > 0 && height <= 0xffff);
+
+        let mut encoder = AnimatedImageEncoder {
+            format,
+            width,
+            height,
+            options: *options,
+            output: Vec::new(),
+            frames_written: 0,
+            apng_sequence: 0,
+        };
+
+        match format {
+            // The APNG header is written with the first frame, since it comes from the
+            // PNG that cairo generates.
+            AnimatedImageFormat::Apng => (),
+            AnimatedImageFormat::Gif => encoder.write_gif_header(),
+        }
+
+        encoder
+    }
+
+    /// Appends a frame to the animation.
+    ///
+    /// The `surface` must be in `ARgb32` format, have the size that was passed to
+    /// `new()`, and not be referenced from anywhere else.
+    pub fn add_frame(&mut self, surface: cairo::ImageSurface) -> Result<(), cairo::Status> {
+        assert_eq!(surface.get_width(), self.width);
+        assert_eq!(surface.get_height(), self.height);
+
+        match self.format {
+            AnimatedImageFormat::Apng => self.add_apng_frame(surface)?,
+            AnimatedImageFormat::Gif => self.add_gif_frame(surface)?,
+        }
+
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    /// Finishes the animation and returns the encoded image.
+    pub fn finish(mut self) -> Vec<u8> {
+        match self.format {
+            AnimatedImageFormat::Apng => write_png_chunk(&mut self.output, b"IEND", &[]),
+            AnimatedImageFormat::Gif => self.output.push(0x3b), // trailer
+        }
+
+        self.output
+    }
+
+    fn add_apng_frame(&mut self, surface: cairo::ImageSurface) -> Result<(), cairo::Status> {
+        let mut png = Vec::new();
+
+        surface.write_to_png(&mut png).map_err(|e| match e {
+            cairo::IoError::Cairo(status) => status,
+            cairo::IoError::Io(_) => cairo::Status::WriteError,
+        })?;
+
+        let chunks = png_chunks(&png).ok_or(cairo::Status::PngError)?;
+
+        if self.frames_written == 0 {
+            let ihdr = chunks
+                .iter()
+                .find(|(chunk_type, _)| chunk_type == b"IHDR")
+                .ok_or(cairo::Status::PngError)?;
+
+            let mut actl = Vec::new();
+            actl.extend_from_slice(&self.options.num_frames().to_be_bytes());
+            actl.extend_from_slice(&self.options.loops.to_be_bytes());
+
+            self.output.extend_from_slice(&PNG_SIGNATURE);
+            write_png_chunk(&mut self.output, b"IHDR", ihdr.1);
+            write_png_chunk(&mut self.output, b"acTL", &actl);
+        }
+
+        // Frame delays are specified as a fraction of a second, with 16-bit values.
+        let delay_ms = (1000.0 / self.options.frame_rate)
+            .round()
+            .max(1.0)
+            .min(f64::from(u16::max_value())) as u16;
+
+        let mut fctl = Vec::new();
+        fctl.extend_from_slice(&self.next_apng_sequence().to_be_bytes());
+        fctl.extend_from_slice(&(self.width as u32).to_be_bytes());
+        fctl.extend_from_slice(&(self.height as u32).to_be_bytes());
+        fctl.extend_from_slice(&0u32.to_be_bytes()); // x_offset
+        fctl.extend_from_slice(&0u32.to_be_bytes()); // y_offset
+        fctl.extend_from_slice(&delay_ms.to_be_bytes());
+        fctl.extend_from_slice(&1000u16.to_be_bytes());
+        fctl.push(0); // dispose_op: APNG_DISPOSE_OP_NONE
+        fctl.push(0); // blend_op: APNG_BLEND_OP_SOURCE
+        write_png_chunk(&mut self.output, b"fcTL", &fctl);
+
+        for (_, data) in chunks
+            .iter()
+            .filter(|(chunk_type, _)| chunk_type == b"IDAT")
+        {
+            if self.frames_written == 0 {
+                // The first frame is also the default image for non-APNG decoders
+                write_png_chunk(&mut self.output, b"IDAT", data);
+            } else {
+                let mut fdat = Vec::with_capacity(data.len() + 4);
+                fdat.extend_from_slice(&self.next_apng_sequence().to_be_bytes());
+                fdat.extend_from_slice(data);
+                write_png_chunk(&mut self.output, b"fdAT", &fdat);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next_apng_sequence(&mut self) -> u32 {
+        let sequence = self.apng_sequence;
+        self.apng_sequence += 1;
+        sequence
+    }
+
+    fn write_gif_header(&mut self) {
+        let out = &mut self.output;
+
+        out.extend_from_slice(b"GIF89a");
+
+        // Logical screen descriptor, with a global color table of 256 entries
+        out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        out.extend_from_slice(&(self.height as u16).to_le_bytes());
+        out.push(0xf7);
+        out.push(0); // background color index
+        out.push(0); // pixel aspect ratio
+
+        out.extend_from_slice(&gif_palette());
+
+        // The NETSCAPE2.0 extension specifies the number of repetitions after the
+        // first time that the animation is played, or 0 to play it forever.
+        if self.options.loops != 1 {
+            let repetitions = self.options.loops.saturating_sub(1).min(0xffff) as u16;
+
+            out.extend_from_slice(&[0x21, 0xff, 0x0b]);
+            out.extend_from_slice(b"NETSCAPE2.0");
+            out.extend_from_slice(&[0x03, 0x01]);
+            out.extend_from_slice(&repetitions.to_le_bytes());
+            out.push(0);
+        }
+    }
+
+    fn add_gif_frame(&mut self, surface: cairo::ImageSurface) -> Result<(), cairo::Status> {
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb)?;
+
+        // Frame delays are specified in hundredths of a second.
+        let delay = (100.0 / self.options.frame_rate)
+            .round()
+            .max(1.0)
+            .min(f64::from(u16::max_value())) as u16;
+
+        let out = &mut self.output;
+
+        // Graphic control extension: restore to the background (transparent) after
+        // each frame, and use the transparent color index.
+        out.extend_from_slice(&[0x21, 0xf9, 0x04, 0x09]);
+        out.extend_from_slice(&delay.to_le_bytes());
+        out.push(GIF_TRANSPARENT_INDEX);
+        out.push(0);
+
+        // Image descriptor for the whole canvas, using the global color table
+        out.push(0x2c);
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        out.extend_from_slice(&(self.height as u16).to_le_bytes());
+        out.push(0);
+
+        let mut indices = Vec::with_capacity((self.width * self.height) as usize);
+
+        for y in 0..self.height as u32 {
+            for x in 0..self.width as u32 {
+                let pixel = surface.get_pixel(x, y).unpremultiply();
+                indices.push(gif_palette_index(pixel.r, pixel.g, pixel.b, pixel.a, x, y));
+            }
+        }
+
+        lzw_encode(&indices, out);
+
+        Ok(())
+    }
+}
+
+/// Splits a PNG file into `(chunk_type, data)` pairs, or returns `None` if it is malformed.
+fn png_chunks(png: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    if png.len() < PNG_SIGNATURE.len() || png[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = &png[PNG_SIGNATURE.len()..];
+
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return None;
+        }
+
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let chunk_type = [rest[4], rest[5], rest[6], rest[7]];
+
+        if rest.len() < 12 + len {
+            return None;
+        }
+
+        chunks.push((chunk_type, &rest[8..8 + len]));
+        rest = &rest[12 + len..];
+    }
+
+    Some(chunks)
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+
+    let crc = crc32(chunk_type.iter().chain(data.iter()));
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 used by PNG; see the PNG specification, annex D.
+fn crc32<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let crc = bytes.fold(0xffff_ffff_u32, |crc, &byte| {
+        let mut c = crc ^ u32::from(byte);
+
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+
+        c
+    });
+
+    crc ^ 0xffff_ffff
+}
+
+const GIF_TRANSPARENT_INDEX: u8 = 0;
+
+// Levels of the color cube in the GIF palette; green gets more levels since the eye
+// is more sensitive to it.  6 * 7 * 6 = 252 colors, plus the transparent color.
+const GIF_RED_LEVELS: u32 = 6;
+const GIF_GREEN_LEVELS: u32 = 7;
+const GIF_BLUE_LEVELS: u32 = 6;
+
+fn gif_palette() -> Vec<u8> {
+    let mut palette = vec![0; 256 * 3];
+
+    let level = |i: u32, levels: u32| (i * 255 / (levels - 1)) as u8;
+
+    for r in 0..GIF_RED_LEVELS {
+        for g in 0..GIF_GREEN_LEVELS {
+            for b in 0..GIF_BLUE_LEVELS {
+                let index = gif_cube_index(r, g, b) as usize;
+
+                palette[index * 3] = level(r, GIF_RED_LEVELS);
+                palette[index * 3 + 1] = level(g, GIF_GREEN_LEVELS);
+                palette[index * 3 + 2] = level(b, GIF_BLUE_LEVELS);
+            }
+        }
+    }
+
+    palette
+}
+
+fn gif_cube_index(r: u32, g: u32, b: u32) -> u8 {
+    (1 + (r * GIF_GREEN_LEVELS + g) * GIF_BLUE_LEVELS + b) as u8
+}
+
+// 4x4 Bayer matrix for ordered dithering
+const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+fn gif_palette_index(r: u8, g: u8, b: u8, a: u8, x: u32, y: u32) -> u8 {
+    if a < 128 {
+        return GIF_TRANSPARENT_INDEX;
+    }
+
+    let threshold = (f64::from(BAYER[(y % 4) as usize][(x % 4) as usize]) + 0.5) / 16.0;
+
+    let quantize = |c: u8, levels: u32| {
+        let v = f64::from(c) / 255.0 * f64::from(levels - 1);
+        ((v + threshold).floor() as u32).min(levels - 1)
+    };
+
+    gif_cube_index(
+        quantize(r, GIF_RED_LEVELS),
+        quantize(g, GIF_GREEN_LEVELS),
+        quantize(b, GIF_BLUE_LEVELS),
+    )
+}
+
+/// Writes bits in the least-significant-bit-first order that GIF uses.
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    num_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.acc |= u32::from(code) << self.num_bits;
+        self.num_bits += size;
+
+        while self.num_bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.num_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.num_bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+
+        self.bytes
+    }
+}
+
+/// Compresses palette indices with GIF's variant of LZW, and writes them as data sub-blocks.
+fn lzw_encode(indices: &[u8], out: &mut Vec<u8>) {
+    const MIN_CODE_SIZE: u32 = 8;
+    const CLEAR: u16 = 1 << MIN_CODE_SIZE;
+    const END: u16 = CLEAR + 1;
+    const FIRST_CODE: u16 = CLEAR + 2;
+    const MAX_CODE: u16 = 4096;
+
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        acc: 0,
+        num_bits: 0,
+    };
+
+    let mut table = HashMap::new();
+    let mut code_size = MIN_CODE_SIZE + 1;
+    let mut next_code = FIRST_CODE;
+
+    writer.write(CLEAR, code_size);
+
+    let mut prefix: Option<u16> = None;
+
+    for &index in indices {
+        let p = match prefix {
+            None => {
+                prefix = Some(u16::from(index));
+                continue;
+            }
+
+            Some(p) => p,
+        };
+
+        if let Some(&code) = table.get(&(p, index)) {
+            prefix = Some(code);
+            continue;
+        }
+
+        writer.write(p, code_size);
+
+        // The decoder adds table entries one code behind us, so the code size grows
+        // when the table is full *before* adding this entry.
+        if u32::from(next_code) >= 1 << code_size && code_size < 12 {
+            code_size += 1;
+        }
+
+        if next_code < MAX_CODE {
+            table.insert((p, index), next_code);
+            next_code += 1;
+        } else {
+            writer.write(CLEAR, code_size);
+            table.clear();
+            code_size = MIN_CODE_SIZE + 1;
+            next_code = FIRST_CODE;
+        }
+
+        prefix = Some(u16::from(index));
+    }
+
+    if let Some(p) = prefix {
+        writer.write(p, code_size);
+
+        if u32::from(next_code) >= 1 << code_size && code_size < 12 {
+            code_size += 1;
+        }
+    }
+
+    writer.write(END, code_size);
+
+    out.push(MIN_CODE_SIZE as u8);
+
+    for block in writer.finish().chunks(255) {
+        out.push(block.len() as u8);
+        out.extend_from_slice(block);
+    }
+
+    out.push(0); // block terminator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_number_of_frames() {
+        let options = AnimationOptions {
+            frame_rate: 10.0,
+            duration: 2.05,
+            loops: 0,
+        };
+
+        assert_eq!(options.num_frames(), 21);
+        assert_eq!(options.frame_time(5), 0.5);
+
+        let options = AnimationOptions {
+            frame_rate: 10.0,
+            duration: 0.0,
+            loops: 0,
+        };
+
+        assert_eq!(options.num_frames(), 1);
+    }
+
+    #[test]
+    fn validates_options() {
+        let options = |frame_rate, duration| AnimationOptions {
+            frame_rate,
+            duration,
+            loops: 0,
+        };
+
+        assert!(options(10.0, 2.0).is_valid());
+        assert!(options(10.0, 0.0).is_valid());
+        assert!(options(10.0, 1000.0).is_valid());
+
+        assert!(!options(0.0, 2.0).is_valid());
+        assert!(!options(-10.0, 2.0).is_valid());
+        assert!(!options(std::f64::NAN, 2.0).is_valid());
+        assert!(!options(std::f64::INFINITY, 2.0).is_valid());
+        assert!(!options(10.0, -1.0).is_valid());
+        assert!(!options(10.0, std::f64::NAN).is_valid());
+        assert!(!options(10.0, std::f64::INFINITY).is_valid());
+
+        // Too many frames
+        assert!(!options(10.0, 1000.1).is_valid());
+        assert!(!options(1e9, 1e9).is_valid());
+    }
+
+    #[test]
+    fn computes_png_crc() {
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+    }
+
+    /// Decodes GIF's LZW into palette indices, to check the encoder.
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let min_code_size = u32::from(data[0]);
+
+        let mut bytes = Vec::new();
+        let mut rest = &data[1..];
+
+        while rest[0] != 0 {
+            let len = rest[0] as usize;
+            bytes.extend_from_slice(&rest[1..=len]);
+            rest = &rest[len + 1..];
+        }
+
+        let clear = 1u16 << min_code_size;
+        let end = clear + 1;
+
+        let mut output = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<Vec<u8>> = None;
+
+        let mut acc = 0u32;
+        let mut num_bits = 0;
+        let mut bytes = bytes.into_iter();
+
+        loop {
+            while num_bits < code_size {
+                acc |= u32::from(bytes.next().unwrap()) << num_bits;
+                num_bits += 8;
+            }
+
+            let code = (acc & ((1 << code_size) - 1)) as u16;
+            acc >>= code_size;
+            num_bits -= code_size;
+
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+
+            if code == end {
+                break;
+            }
+
+            let entry = if (code as usize) < table.len() {
+                table[code as usize].clone()
+            } else {
+                let mut p = previous.clone().unwrap();
+                p.push(p[0]);
+                p
+            };
+
+            output.extend_from_slice(&entry);
+
+            if let Some(mut p) = previous {
+                if table.len() < 4096 {
+                    p.push(entry[0]);
+                    table.push(p);
+                }
+            }
+
+            if table.len() >= 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+
+            previous = Some(entry);
+        }
+
+        output
+    }
+
+    #[test]
+    fn lzw_roundtrips() {
+        let inputs: Vec<Vec<u8>> = vec![
+            vec![0],
+            vec![1, 2, 3, 4],
+            vec![7; 10000],
+            (0..20000).map(|i| ((i * 7919) % 251) as u8).collect(),
+            (0..100_000).map(|i| ((i / 3) % 256) as u8).collect(),
+        ];
+
+        for input in inputs {
+            let mut encoded = Vec::new();
+            lzw_encode(&input, &mut encoded);
+            assert_eq!(lzw_decode(&encoded), input);
+        }
+    }
+
+    #[test]
+    fn writes_apng_chunks() {
+        let options = AnimationOptions {
+            frame_rate: 10.0,
+            duration: 0.2,
+            loops: 3,
+        };
+
+        let mut encoder = AnimatedImageEncoder::new(AnimatedImageFormat::Apng, 4, 4, &options);
+
+        for _ in 0..options.num_frames() {
+            let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 4, 4).unwrap();
+            encoder.add_frame(surface).unwrap();
+        }
+
+        let png = encoder.finish();
+        let chunks = png_chunks(&png).unwrap();
+        let types: Vec<&[u8; 4]> = chunks.iter().map(|(t, _)| t).collect();
+
+        assert_eq!(&types[..3], &[b"IHDR", b"acTL", b"fcTL"]);
+        assert_eq!(types.iter().filter(|t| **t == b"fcTL").count(), 2);
+        assert!(types.iter().any(|t| *t == b"fdAT"));
+        assert_eq!(types.last(), Some(&b"IEND"));
+
+        let actl = chunks[1].1;
+        assert_eq!(actl, &[0, 0, 0, 2, 0, 0, 0, 3]);
+    }
+}
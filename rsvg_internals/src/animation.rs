@@ -0,0 +1,1168 @@
+//! SMIL animation elements: `<animate>`, `<set>`, `<animateTransform>` and `<animateMotion>`.
+//!
+//! https://www.w3.org/TR/SVG11/animate.html
+//!
+//! We don't run a clock.  Instead, the caller seeks the document to a specific time with
+//! `seek()`, which computes the value of each animation at that time and stores it in
+//! the animation's target element, as an override of the element's specified values or of
+//! its `transform` attribute.  The tree is then re-cascaded so that rendering picks up the
+//! animated values.
+//!
+//! Only the basic timing model is supported: `begin` and `end` offset values, `dur`,
+//! `repeatCount`, `repeatDur`, and `fill`.  Event, syncbase and wallclock values never
+//! trigger an animation.  The `paced` and `spline` calc modes are treated as `linear`.
+
+use cairo::Matrix;
+use cssparser::{Parser, ParserInput, RGBA};
+use markup5ever::{expanded_name, local_name, namespace_url, ns, LocalName, QualName};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::allowed_url::Fragment;
+use crate::angle::Angle;
+use crate::css::Stylesheet;
+use crate::css_animation;
+use crate::error::*;
+use crate::motion_path::MotionPath;
+use crate::node::*;
+use crate::parsers::{Parse, ParseValue};
+use crate::properties::{parse_property, ComputedValues, ParsedProperty};
+use crate::property_bag::PropertyBag;
+
+/// Parses a SMIL clock value into seconds.
+///
+/// https://www.w3.org/TR/SMIL3/smil-timing.html#Timing-ClockValueSyntax
+fn parse_clock_value(s: &str) -> Result<f64, ValueErrorKind> {
+    let error = || ValueErrorKind::parse_error("invalid clock value");
+
+    let parse_number = |s: &str| {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            s.parse::<f64>().map_err(|_| error())
+        } else {
+            Err(error())
+        }
+    };
+
+    let s = s.trim();
+
+    if s.contains(':') {
+        let parts = s.split(':').collect::<Vec<_>>();
+
+        let (hours, minutes, seconds) = match parts.as_slice() {
+            [h, m, s] => (parse_number(h)?, parse_number(m)?, parse_number(s)?),
+            [m, s] => (0.0, parse_number(m)?, parse_number(s)?),
+            _ => return Err(error()),
+        };
+
+        if minutes >= 60.0 || seconds >= 60.0 {
+            return Err(error());
+        }
+
+        Ok(hours * 3600.0 + minutes * 60.0 + seconds)
+    } else {
+        // "ms" must come before "s"
+        let metrics = [("ms", 0.001), ("min", 60.0), ("h", 3600.0), ("s", 1.0)];
+
+        let (number, scale) = metrics
+            .iter()
+            .find(|&&(suffix, _)| s.ends_with(suffix))
+            .map(|&(suffix, scale)| (&s[..s.len() - suffix.len()], scale))
+            .unwrap_or((s, 1.0));
+
+        parse_number(number).map(|n| n * scale)
+    }
+}
+
+/// Parses an offset value, a clock value with an optional sign.
+fn parse_offset_value(s: &str) -> Result<f64, ValueErrorKind> {
+    let s = s.trim();
+
+    if s.starts_with('-') {
+        parse_clock_value(&s[1..]).map(|t| -t)
+    } else if s.starts_with('+') {
+        parse_clock_value(&s[1..])
+    } else {
+        parse_clock_value(s)
+    }
+}
+
+/// Parses the list of times in a `begin` or `end` attribute.
+///
+/// Values that are not offsets are skipped, since nothing will trigger them.
+fn parse_time_list(s: &str) -> Vec<f64> {
+    let mut times = s
+        .split(';')
+        .filter_map(|v| match parse_offset_value(v) {
+            Ok(t) => Some(t),
+            Err(_) => {
+                rsvg_log!(
+                    "(ignoring unsupported animation time value \"{}\")",
+                    v.trim()
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    times
+}
+
+/// Parses the `dur` and `repeatDur` attributes; an indefinite duration is infinite.
+fn parse_duration(s: &str) -> Result<f64, ValueErrorKind> {
+    match s.trim() {
+        "indefinite" | "media" => Ok(std::f64::INFINITY),
+        s => match parse_clock_value(s)? {
+            d if d > 0.0 => Ok(d),
+            _ => Err(ValueErrorKind::value_error("duration must be positive")),
+        },
+    }
+}
+
+/// Parses the `repeatCount` attribute; an indefinite count is infinite.
+fn parse_repeat_count(s: &str) -> Result<f64, ValueErrorKind> {
+    match s.trim() {
+        "indefinite" => Ok(std::f64::INFINITY),
+        s => match s.parse::<f64>() {
+            Ok(n) if n > 0.0 => Ok(n),
+            Ok(_) => Err(ValueErrorKind::value_error("repeatCount must be positive")),
+            Err(_) => Err(ValueErrorKind::parse_error(
+                "expected a number or \"indefinite\"",
+            )),
+        },
+    }
+}
+
+/// Parses the `keyTimes` attribute
+fn parse_key_times(s: &str) -> Result<Vec<f64>, ValueErrorKind> {
+    let times = s
+        .trim()
+        .trim_end_matches(';')
+        .split(';')
+        .map(|v| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|t| t.is_finite())
+                .ok_or_else(|| ValueErrorKind::parse_error("expected a number"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if times.first() != Some(&0.0)
+        || times.iter().any(|&t| t < 0.0 || t > 1.0)
+        || times.windows(2).any(|w| w[1] < w[0])
+    {
+        return Err(ValueErrorKind::value_error(
+            "keyTimes must start at 0 and increase up to 1",
+        ));
+    }
+
+    Ok(times)
+}
+
+/// Splits a value into its numbers and the pieces of text around them.
+///
+/// There is always one more piece of text than numbers.
+fn split_numbers(s: &str) -> (Vec<&str>, Vec<f64>) {
+    let bytes = s.as_bytes();
+    let is_digit = |i: usize| i < bytes.len() && bytes[i].is_ascii_digit();
+
+    let mut texts = Vec::new();
+    let mut numbers = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let mut j = i;
+
+        if bytes[j] == b'+' || bytes[j] == b'-' {
+            j += 1;
+        }
+
+        let mantissa_start = j;
+
+        while is_digit(j) {
+            j += 1;
+        }
+
+        if j < bytes.len() && bytes[j] == b'.' {
+            j += 1;
+
+            while is_digit(j) {
+                j += 1;
+            }
+        }
+
+        if !bytes[mantissa_start..j].iter().any(u8::is_ascii_digit) {
+            i = start + 1;
+            continue;
+        }
+
+        // Only take the exponent if there are digits after it, so that "1em" works.
+        if j < bytes.len() && (bytes[j] == b'e' || bytes[j] == b'E') {
+            let mut k = j + 1;
+
+            if k < bytes.len() && (bytes[k] == b'+' || bytes[k] == b'-') {
+                k += 1;
+            }
+
+            if is_digit(k) {
+                while is_digit(k) {
+                    k += 1;
+                }
+
+                j = k;
+            }
+        }
+
+        match s[start..j].parse::<f64>() {
+            Ok(n) => {
+                texts.push(&s[text_start..start]);
+                numbers.push(n);
+                text_start = j;
+                i = j;
+            }
+
+            Err(_) => i = start + 1,
+        }
+    }
+
+    texts.push(&s[text_start..]);
+
+    (texts, numbers)
+}
+
+fn parse_color(s: &str) -> Option<RGBA> {
+    match cssparser::Color::parse_str(s.trim()) {
+        Ok(cssparser::Color::RGBA(rgba)) => Some(rgba),
+        _ => None,
+    }
+}
+
+/// Combines two values number by number, or channel by channel for colors.
+///
+/// Returns `None` if the values don't have the same shape.
+fn combine<F>(a: &str, b: &str, f: F) -> Option<String>
+where
+    F: Fn(f64, f64) -> f64,
+{
+    if let (Some(a), Some(b)) = (parse_color(a), parse_color(b)) {
+        let channel =
+            |x: u8, y: u8| f(f64::from(x), f64::from(y)).round().max(0.0).min(255.0) as u8;
+
+        return Some(format!(
+            "rgba({}, {}, {}, {})",
+            channel(a.red, b.red),
+            channel(a.green, b.green),
+            channel(a.blue, b.blue),
+            f64::from(channel(a.alpha, b.alpha)) / 255.0
+        ));
+    }
+
+    let (texts_a, numbers_a) = split_numbers(a);
+    let (texts_b, numbers_b) = split_numbers(b);
+
+    if texts_a != texts_b {
+        return None;
+    }
+
+    let mut result = String::from(texts_a[0]);
+
+    for ((x, y), text) in numbers_a.iter().zip(numbers_b).zip(&texts_a[1..]) {
+        result.push_str(&f(*x, y).to_string());
+        result.push_str(text);
+    }
+
+    Some(result)
+}
+
+/// Interpolates between two values; `None` means that they can't be interpolated.
+pub(crate) fn interpolate(a: &str, b: &str, t: f64) -> Option<String> {
+    combine(a, b, |x, y| x + (y - x) * t)
+}
+
+/// Adds two values, for `by` animations.
+fn add(a: &str, b: &str) -> Option<String> {
+    combine(a, b, |x, y| x + y)
+}
+
+/// Replaces each number in a value.
+fn map_numbers<F>(s: &str, mut f: F) -> String
+where
+    F: FnMut(usize, f64) -> f64,
+{
+    let (texts, numbers) = split_numbers(s);
+
+    let mut result = String::from(texts[0]);
+
+    for (i, (n, text)) in numbers.iter().zip(&texts[1..]).enumerate() {
+        result.push_str(&f(i, *n).to_string());
+        result.push_str(text);
+    }
+
+    result
+}
+
+/// The timing attributes of an animation element
+#[derive(Debug, Clone, PartialEq)]
+struct Timing {
+    begin: Vec<f64>,
+    end: Vec<f64>,
+    dur: f64,
+    repeat_count: Option<f64>,
+    repeat_dur: Option<f64>,
+    freeze: bool,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            begin: vec![0.0],
+            end: Vec::new(),
+            dur: std::f64::INFINITY,
+            repeat_count: None,
+            repeat_dur: None,
+            freeze: false,
+        }
+    }
+}
+
+impl Timing {
+    fn active_duration(&self) -> f64 {
+        match (self.repeat_count, self.repeat_dur) {
+            (None, None) => self.dur,
+
+            (count, dur) => {
+                let by_count = count.map_or(std::f64::INFINITY, |c| c * self.dur);
+                by_count.min(dur.unwrap_or(std::f64::INFINITY))
+            }
+        }
+    }
+
+    /// Returns how far along its simple duration the animation is at `time`, from 0 to 1.
+    ///
+    /// Returns `None` if the animation has no effect at that time.
+    fn simple_fraction(&self, time: f64) -> Option<f64> {
+        let begin = *self.begin.iter().rev().find(|&&b| b <= time)?;
+
+        let mut active = self.active_duration();
+
+        if let Some(end) = self.end.iter().find(|&&e| e >= begin) {
+            active = active.min(end - begin);
+        }
+
+        let elapsed = time - begin;
+
+        if elapsed < active {
+            Some(self.fraction_at(elapsed))
+        } else if self.freeze {
+            // An animation that ends at the end of an iteration freezes at its last value,
+            // not at its first one.
+            match self.fraction_at(active) {
+                f if f == 0.0 && active > 0.0 && self.dur.is_finite() => Some(1.0),
+                f => Some(f),
+            }
+        } else {
+            None
+        }
+    }
+
+    fn fraction_at(&self, elapsed: f64) -> f64 {
+        if self.dur.is_finite() {
+            (elapsed % self.dur) / self.dur
+        } else {
+            0.0
+        }
+    }
+}
+
+// calcMode attribute
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CalcMode {
+    Discrete,
+    Linear,
+    Paced,
+    Spline,
+}
+
+impl Parse for CalcMode {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<CalcMode, ValueErrorKind> {
+        let ident = parser.expect_ident()?;
+
+        match ident.as_ref() {
+            "discrete" => Ok(CalcMode::Discrete),
+            "linear" => Ok(CalcMode::Linear),
+            "paced" => Ok(CalcMode::Paced),
+            "spline" => Ok(CalcMode::Spline),
+            _ => Err(ValueErrorKind::parse_error(
+                "expected \"discrete\", \"linear\", \"paced\" or \"spline\"",
+            )),
+        }
+    }
+}
+
+/// The `values`, `from`, `to` and `by` attributes
+#[derive(Debug, Default, Clone, PartialEq)]
+struct AnimationValues {
+    values: Option<Vec<String>>,
+    from: Option<String>,
+    to: Option<String>,
+    by: Option<String>,
+}
+
+impl AnimationValues {
+    /// Returns the list of values to animate through.
+    ///
+    /// The `base` value of the animated attribute is needed for to- and by-animations.
+    fn resolve(&self, base: Option<&str>) -> Option<Vec<String>> {
+        if let Some(ref values) = self.values {
+            return Some(values.clone());
+        }
+
+        match (&self.from, &self.to, &self.by) {
+            (Some(from), Some(to), _) => Some(vec![from.clone(), to.clone()]),
+            (Some(from), None, Some(by)) => Some(vec![from.clone(), add(from, by)?]),
+            (None, Some(to), _) => Some(vec![base?.to_string(), to.clone()]),
+            (None, None, Some(by)) => Some(vec![base?.to_string(), add(base?, by)?]),
+            _ => None,
+        }
+    }
+}
+
+/// The attributes that all animation elements have in common
+#[derive(Default)]
+struct Animation {
+    target: Option<Fragment>,
+    attribute_name: Option<QualName>,
+    timing: Timing,
+    calc_mode: Option<CalcMode>,
+    key_times: Option<Vec<f64>>,
+    values: AnimationValues,
+    additive: bool,
+}
+
+impl Animation {
+    fn set_atts(&mut self, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(xlink "href") => {
+                    self.target = Some(Fragment::parse(value).attribute(attr)?)
+                }
+                expanded_name!(svg "attributeName") => {
+                    self.attribute_name =
+                        Some(QualName::new(None, ns!(svg), LocalName::from(value.trim())))
+                }
+                expanded_name!(svg "begin") => self.timing.begin = parse_time_list(value),
+                expanded_name!(svg "end") => self.timing.end = parse_time_list(value),
+                expanded_name!(svg "dur") => {
+                    self.timing.dur = parse_duration(value).attribute(attr)?
+                }
+                expanded_name!(svg "repeatCount") => {
+                    self.timing.repeat_count = Some(parse_repeat_count(value).attribute(attr)?)
+                }
+                expanded_name!(svg "repeatDur") => {
+                    self.timing.repeat_dur = Some(parse_duration(value).attribute(attr)?)
+                }
+                expanded_name!(svg "fill") => {
+                    self.timing.freeze = match value.trim() {
+                        "freeze" => true,
+                        "remove" => false,
+                        _ => {
+                            return Err(NodeError::parse_error(
+                                attr,
+                                "expected \"freeze\" or \"remove\"",
+                            ))
+                        }
+                    }
+                }
+                expanded_name!(svg "calcMode") => self.calc_mode = Some(attr.parse(value)?),
+                expanded_name!(svg "keyTimes") => {
+                    self.key_times = Some(parse_key_times(value).attribute(attr)?)
+                }
+                expanded_name!(svg "values") => {
+                    self.values.values = Some(
+                        value
+                            .trim()
+                            .trim_end_matches(';')
+                            .split(';')
+                            .map(|v| v.trim().to_string())
+                            .collect(),
+                    )
+                }
+                expanded_name!(svg "from") => self.values.from = Some(value.trim().to_string()),
+                expanded_name!(svg "to") => self.values.to = Some(value.trim().to_string()),
+                expanded_name!(svg "by") => self.values.by = Some(value.trim().to_string()),
+                expanded_name!(svg "additive") => {
+                    self.additive = match value.trim() {
+                        "sum" => true,
+                        "replace" => false,
+                        _ => {
+                            return Err(NodeError::parse_error(
+                                attr,
+                                "expected \"sum\" or \"replace\"",
+                            ))
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The element being animated: the one referenced by `xlink:href`, or else the parent.
+    fn target_node(&self, node: &RsvgNode, ids: &HashMap<String, RsvgNode>) -> Option<RsvgNode> {
+        match self.target {
+            Some(ref fragment) if fragment.uri().is_none() => ids.get(fragment.fragment()).cloned(),
+            Some(_) => None,
+            None => node.parent(),
+        }
+    }
+
+    /// Picks the pair of values to interpolate between at `fraction` of the simple duration,
+    /// and how far between them the animation is.
+    ///
+    /// The pair is the same value twice when no interpolation is needed.
+    fn select(&self, n: usize, discrete: bool, fraction: f64) -> Option<(usize, usize, f64)> {
+        if n == 0 {
+            return None;
+        }
+
+        let keys = match self.key_times {
+            Some(ref keys) => keys.clone(),
+            None if discrete => (0..n).map(|i| i as f64 / n as f64).collect(),
+            None if n > 1 => (0..n).map(|i| i as f64 / (n - 1) as f64).collect(),
+            None => vec![0.0],
+        };
+
+        if keys.len() != n || (!discrete && n > 1 && keys[n - 1] != 1.0) {
+            rsvg_log!("(ignoring animation whose keyTimes don't match its values)");
+            return None;
+        }
+
+        if discrete {
+            let i = keys.iter().rposition(|&k| k <= fraction).unwrap_or(0);
+            return Some((i, i, 0.0));
+        }
+
+        match (0..n - 1).find(|&i| fraction < keys[i + 1]) {
+            Some(i) => {
+                let span = keys[i + 1] - keys[i];
+                let t = if span > 0.0 {
+                    (fraction - keys[i]) / span
+                } else {
+                    0.0
+                };
+
+                Some((i, i + 1, t.max(0.0)))
+            }
+
+            None => Some((n - 1, n - 1, 0.0)),
+        }
+    }
+
+    /// Computes the animated value at `fraction` of the simple duration.
+    fn value_at(&self, base: Option<&str>, fraction: f64) -> Option<String> {
+        let values = self.values.resolve(base)?;
+
+        // Values which can't be interpolated, like keywords, always animate discretely.
+        let discrete = self.calc_mode == Some(CalcMode::Discrete)
+            || !values
+                .windows(2)
+                .all(|w| interpolate(&w[0], &w[1], 0.0).is_some());
+
+        let (i, j, t) = self.select(values.len(), discrete, fraction)?;
+
+        if i == j {
+            Some(values[i].clone())
+        } else {
+            interpolate(&values[i], &values[j], t)
+        }
+    }
+}
+
+/// What an animation does to its target element at a given time
+enum Effect {
+    Property(ParsedProperty),
+    Transform { affine: Matrix, additive: bool },
+    Motion(Matrix),
+}
+
+#[derive(Default)]
+pub struct Animate {
+    animation: Animation,
+}
+
+impl NodeTrait for Animate {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        self.animation.set_atts(pbag)
+    }
+}
+
+impl Animate {
+    fn effect_at(&self, target: &RsvgNode, time: f64) -> Option<Effect> {
+        property_effect(&self.animation, target, time)
+    }
+}
+
+#[derive(Default)]
+pub struct Set {
+    animation: Animation,
+}
+
+impl NodeTrait for Set {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        self.animation.set_atts(pbag)?;
+
+        // <set> only has a "to" value, which applies for the whole duration.
+        let to = self.animation.values.to.take();
+
+        self.animation.values = AnimationValues {
+            values: to.map(|to| vec![to]),
+            ..Default::default()
+        };
+        self.animation.calc_mode = Some(CalcMode::Discrete);
+        self.animation.key_times = None;
+
+        Ok(())
+    }
+}
+
+impl Set {
+    fn effect_at(&self, target: &RsvgNode, time: f64) -> Option<Effect> {
+        property_effect(&self.animation, target, time)
+    }
+}
+
+fn property_effect(animation: &Animation, target: &RsvgNode, time: f64) -> Option<Effect> {
+    let name = animation.attribute_name.as_ref()?;
+    let fraction = animation.timing.simple_fraction(time)?;
+
+    let base = CascadedValues::new_from_node(target)
+        .get()
+        .get_property_value(&name.local);
+
+    let value = animation.value_at(base.as_ref().map(String::as_str), fraction)?;
+
+    let mut input = ParserInput::new(&value);
+    let mut parser = Parser::new(&mut input);
+
+    match parse_property(name, &mut parser, false) {
+        Ok(prop) => Some(Effect::Property(prop)),
+
+        Err(ValueErrorKind::UnknownProperty) => {
+            rsvg_log!("(animating attribute \"{}\" is not supported)", name.local);
+            None
+        }
+
+        Err(e) => {
+            rsvg_log!(
+                "(ignoring invalid animated value \"{}\" for \"{}\": {})",
+                value,
+                name.local,
+                e
+            );
+            None
+        }
+    }
+}
+
+// type attribute of <animateTransform>
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TransformType {
+    Translate,
+    Scale,
+    Rotate,
+    SkewX,
+    SkewY,
+}
+
+impl Default for TransformType {
+    fn default() -> TransformType {
+        TransformType::Translate
+    }
+}
+
+impl Parse for TransformType {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<TransformType, ValueErrorKind> {
+        let ident = parser.expect_ident()?;
+
+        match ident.as_ref() {
+            "translate" => Ok(TransformType::Translate),
+            "scale" => Ok(TransformType::Scale),
+            "rotate" => Ok(TransformType::Rotate),
+            "skewX" => Ok(TransformType::SkewX),
+            "skewY" => Ok(TransformType::SkewY),
+            _ => Err(ValueErrorKind::parse_error(
+                "expected \"translate\", \"scale\", \"rotate\", \"skewX\" or \"skewY\"",
+            )),
+        }
+    }
+}
+
+impl TransformType {
+    fn function_name(self) -> &'static str {
+        match self {
+            TransformType::Translate => "translate",
+            TransformType::Scale => "scale",
+            TransformType::Rotate => "rotate",
+            TransformType::SkewX => "skewX",
+            TransformType::SkewY => "skewY",
+        }
+    }
+
+    /// Returns the parameters with the same shape as `value` that produce an identity
+    /// transform, for use as the base value of to- and by-animations.  The center
+    /// of a rotation is kept.
+    fn identity_like(self, value: &str) -> String {
+        map_numbers(value, |i, n| match self {
+            TransformType::Scale => 1.0,
+            TransformType::Rotate if i > 0 => n,
+            _ => 0.0,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct AnimateTransform {
+    animation: Animation,
+    kind: TransformType,
+}
+
+impl NodeTrait for AnimateTransform {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(svg "type") => self.kind = attr.parse(value)?,
+                _ => (),
+            }
+        }
+
+        self.animation.set_atts(pbag)
+    }
+}
+
+impl AnimateTransform {
+    fn effect_at(&self, time: f64) -> Option<Effect> {
+        match self.animation.attribute_name {
+            Some(ref name) if name.expanded() == expanded_name!(svg "transform") => (),
+            Some(ref name) => {
+                rsvg_log!("(animating attribute \"{}\" is not supported)", name.local);
+                return None;
+            }
+            None => return None,
+        }
+
+        let fraction = self.animation.timing.simple_fraction(time)?;
+
+        let values = &self.animation.values;
+        let base = values
+            .to
+            .as_ref()
+            .or_else(|| values.by.as_ref())
+            .map(|v| self.kind.identity_like(v));
+
+        let params = self
+            .animation
+            .value_at(base.as_ref().map(String::as_str), fraction)?;
+
+        match cairo::Matrix::parse_str(&format!("{}({})", self.kind.function_name(), params)) {
+            Ok(affine) => Some(Effect::Transform {
+                affine,
+                additive: self.animation.additive,
+            }),
+
+            Err(e) => {
+                rsvg_log!(
+                    "(ignoring invalid animated transform \"{}\": {})",
+                    params,
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+// rotate attribute of <animateMotion>
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MotionRotate {
+    Auto,
+    AutoReverse,
+    Angle(Angle),
+}
+
+impl Default for MotionRotate {
+    fn default() -> MotionRotate {
+        MotionRotate::Angle(Angle::new(0.0))
+    }
+}
+
+impl Parse for MotionRotate {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<MotionRotate, ValueErrorKind> {
+        if parser
+            .try_parse(|p| p.expect_ident_matching("auto"))
+            .is_ok()
+        {
+            Ok(MotionRotate::Auto)
+        } else if parser
+            .try_parse(|p| p.expect_ident_matching("auto-reverse"))
+            .is_ok()
+        {
+            Ok(MotionRotate::AutoReverse)
+        } else {
+            Angle::parse(parser).map(MotionRotate::Angle)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AnimateMotion {
+    animation: Animation,
+    path: Option<MotionPath>,
+    rotate: MotionRotate,
+}
+
+impl NodeTrait for AnimateMotion {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(svg "path") => {
+                    self.path = Some(MotionPath::from_path_data(value).attribute(attr)?)
+                }
+                expanded_name!(svg "rotate") => self.rotate = attr.parse(value)?,
+                _ => (),
+            }
+        }
+
+        self.animation.set_atts(pbag)
+    }
+}
+
+impl AnimateMotion {
+    fn effect_at(&self, time: f64) -> Option<Effect> {
+        let fraction = self.animation.timing.simple_fraction(time)?;
+
+        let ((x, y), direction) = match self.path {
+            // The path is always traversed at a constant speed, as for calcMode="paced".
+            Some(ref path) => path.point_at_fraction(fraction)?,
+            None => self.point_from_values(fraction)?,
+        };
+
+        let rotation = match self.rotate {
+            MotionRotate::Auto => direction.radians(),
+            MotionRotate::AutoReverse => direction.radians() + PI,
+            MotionRotate::Angle(angle) => angle.radians(),
+        };
+
+        let mut affine = Matrix::identity();
+        affine.translate(x, y);
+        affine.rotate(rotation);
+
+        Some(Effect::Motion(affine))
+    }
+
+    fn point_from_values(&self, fraction: f64) -> Option<((f64, f64), Angle)> {
+        let points = self
+            .animation
+            .values
+            .resolve(Some("0,0"))?
+            .iter()
+            .map(|v| match split_numbers(v).1.as_slice() {
+                [x, y] => Some((*x, *y)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let discrete = self.animation.calc_mode == Some(CalcMode::Discrete);
+        let (i, j, t) = self.animation.select(points.len(), discrete, fraction)?;
+
+        let (x0, y0) = points[i];
+        let (x1, y1) = points[j];
+
+        let direction = if i != j {
+            Angle::from_vector(x1 - x0, y1 - y0)
+        } else if i > 0 {
+            let (xp, yp) = points[i - 1];
+            Angle::from_vector(x0 - xp, y0 - yp)
+        } else {
+            Angle::new(0.0)
+        };
+
+        Some(((x0 + (x1 - x0) * t, y0 + (y1 - y0) * t), direction))
+    }
+}
+
+/// Computes the effect of an animation element at `time`, and the element it applies to.
+fn effect_at(
+    node: &RsvgNode,
+    ids: &HashMap<String, RsvgNode>,
+    time: f64,
+) -> Option<(RsvgNode, Effect)> {
+    let node_data = node.borrow();
+
+    if node_data.is_in_error() || !node_data.get_cond() {
+        return None;
+    }
+
+    match node_data.get_type() {
+        NodeType::Animate => {
+            let animate = node_data.get_impl::<Animate>();
+            let target = animate.animation.target_node(node, ids)?;
+            let effect = animate.effect_at(&target, time)?;
+            Some((target, effect))
+        }
+
+        NodeType::Set => {
+            let set = node_data.get_impl::<Set>();
+            let target = set.animation.target_node(node, ids)?;
+            let effect = set.effect_at(&target, time)?;
+            Some((target, effect))
+        }
+
+        NodeType::AnimateTransform => {
+            let animate = node_data.get_impl::<AnimateTransform>();
+            let target = animate.animation.target_node(node, ids)?;
+            Some((target, animate.effect_at(time)?))
+        }
+
+        NodeType::AnimateMotion => {
+            let animate = node_data.get_impl::<AnimateMotion>();
+            let target = animate.animation.target_node(node, ids)?;
+            Some((target, animate.effect_at(time)?))
+        }
+
+        _ => None,
+    }
+}
+
+/// Seeks all the animations in the tree to `time`, in seconds, and re-cascades the tree.
+///
+/// This includes the CSS animations of elements, whose `@keyframes` rules come from
+/// the `stylesheets`.
+///
+/// Passing `None` removes the effect of all animations, so that the document is
+/// rendered with its static values.
+pub fn seek(
+    root: &RsvgNode,
+    ids: &HashMap<String, RsvgNode>,
+    stylesheets: &[Stylesheet],
+    time: Option<f64>,
+) {
+    let mut root = root.clone();
+
+    for mut node in root.descendants() {
+        node.borrow_mut().clear_animated_values();
+    }
+
+    // To- and by-animations need the base values of their target properties.
+    root.cascade(&ComputedValues::default());
+
+    let time = match time {
+        Some(t) => t,
+        None => return,
+    };
+
+    let mut animated = false;
+    let mut motions = Vec::new();
+
+    // CSS animations go first, so that SMIL animations of the same property override them.
+    for mut node in root.descendants() {
+        let props = css_animation::animated_properties(
+            CascadedValues::new_from_node(&node).get(),
+            stylesheets,
+            time,
+        );
+
+        if !props.is_empty() {
+            animated = true;
+
+            let mut node_data = node.borrow_mut();
+
+            for prop in props {
+                node_data.set_animated_property(prop);
+            }
+        }
+    }
+
+    for node in root.descendants() {
+        let (mut target, effect) = match effect_at(&node, ids, time) {
+            Some(e) => e,
+            None => continue,
+        };
+
+        animated = true;
+
+        match effect {
+            Effect::Property(prop) => target.borrow_mut().set_animated_property(prop),
+
+            Effect::Transform { affine, additive } => {
+                let mut target_data = target.borrow_mut();
+
+                let affine = if additive {
+                    Matrix::multiply(&affine, &target_data.get_transform())
+                } else {
+                    affine
+                };
+
+                target_data.set_animated_transform(affine);
+            }
+
+            // Motion is applied after all the transform animations, regardless of
+            // document order.
+            Effect::Motion(affine) => motions.push((target, affine)),
+        }
+    }
+
+    for (mut target, motion) in motions {
+        let mut target_data = target.borrow_mut();
+        let affine = Matrix::multiply(&target_data.get_transform(), &motion);
+        target_data.set_animated_transform(affine);
+    }
+
+    if animated {
+        root.cascade(&ComputedValues::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clock_values() {
+        assert_eq!(parse_clock_value("02:30:03"), Ok(9003.0));
+        assert_eq!(parse_clock_value("50:00:10.25"), Ok(180010.25));
+        assert_eq!(parse_clock_value("02:33"), Ok(153.0));
+        assert_eq!(parse_clock_value("00:10.5"), Ok(10.5));
+        assert_eq!(parse_clock_value("3.2h"), Ok(11520.0));
+        assert_eq!(parse_clock_value("45min"), Ok(2700.0));
+        assert_eq!(parse_clock_value("30s"), Ok(30.0));
+        assert_eq!(parse_clock_value("1500ms"), Ok(1.5));
+        assert_eq!(parse_clock_value("12.467"), Ok(12.467));
+
+        assert!(parse_clock_value("").is_err());
+        assert!(parse_clock_value("s").is_err());
+        assert!(parse_clock_value("-5s").is_err());
+        assert!(parse_clock_value("00:60").is_err());
+        assert!(parse_clock_value("1:2:3:4").is_err());
+    }
+
+    #[test]
+    fn parses_time_lists() {
+        assert_eq!(parse_time_list("2s; -1s; +500ms"), vec![-1.0, 0.5, 2.0]);
+        assert_eq!(parse_time_list("foo.click; 1s"), vec![1.0]);
+        assert!(parse_time_list("indefinite").is_empty());
+    }
+
+    #[test]
+    fn parses_key_times() {
+        assert_eq!(parse_key_times("0; 0.25; 1"), Ok(vec![0.0, 0.25, 1.0]));
+        assert_eq!(parse_key_times("0;1;"), Ok(vec![0.0, 1.0]));
+
+        assert!(parse_key_times("0.5; 1").is_err());
+        assert!(parse_key_times("0; 0.5; 0.25").is_err());
+        assert!(parse_key_times("0; 2").is_err());
+        assert!(parse_key_times("0; NaN; 1").is_err());
+        assert!(parse_key_times("0; inf").is_err());
+    }
+
+    #[test]
+    fn computes_simple_fraction() {
+        let timing = Timing {
+            begin: vec![1.0],
+            dur: 2.0,
+            repeat_count: Some(2.0),
+            ..Default::default()
+        };
+
+        assert_eq!(timing.simple_fraction(0.5), None);
+        assert_eq!(timing.simple_fraction(1.0), Some(0.0));
+        assert_eq!(timing.simple_fraction(2.0), Some(0.5));
+        assert_eq!(timing.simple_fraction(4.0), Some(0.5));
+        assert_eq!(timing.simple_fraction(5.0), None);
+
+        let frozen = Timing {
+            freeze: true,
+            ..timing.clone()
+        };
+
+        assert_eq!(frozen.simple_fraction(5.0), Some(1.0));
+
+        let ended = Timing {
+            end: vec![2.5],
+            freeze: true,
+            ..timing
+        };
+
+        assert_eq!(ended.simple_fraction(10.0), Some(0.75));
+    }
+
+    #[test]
+    fn interpolates_values() {
+        assert_eq!(interpolate("10px", "20px", 0.5), Some("15px".to_string()));
+        assert_eq!(
+            interpolate("0 50 50", "360 50 50", 0.25),
+            Some("90 50 50".to_string())
+        );
+        assert_eq!(interpolate("1e2", "3e2", 0.5), Some("200".to_string()));
+        assert_eq!(interpolate("1em", "3em", 0.5), Some("2em".to_string()));
+        assert_eq!(
+            interpolate("red", "#0000ff", 0.5),
+            Some("rgba(128, 0, 128, 1)".to_string())
+        );
+
+        assert_eq!(interpolate("visible", "hidden", 0.5), None);
+        assert_eq!(interpolate("10px", "20%", 0.5), None);
+        assert_eq!(interpolate("10 20", "10", 0.5), None);
+
+        assert_eq!(add("10,20", "5,-5"), Some("15,15".to_string()));
+    }
+
+    #[test]
+    fn selects_values_to_interpolate() {
+        let linear = Animation::default();
+
+        assert_eq!(linear.select(3, false, 0.25), Some((0, 1, 0.5)));
+        assert_eq!(linear.select(3, false, 0.75), Some((1, 2, 0.5)));
+        assert_eq!(linear.select(3, false, 1.0), Some((2, 2, 0.0)));
+        assert_eq!(linear.select(3, true, 0.5), Some((1, 1, 0.0)));
+        assert_eq!(linear.select(3, true, 0.9), Some((2, 2, 0.0)));
+
+        let keyed = Animation {
+            key_times: Some(vec![0.0, 0.75, 1.0]),
+            ..Default::default()
+        };
+
+        assert_eq!(keyed.select(3, false, 0.375), Some((0, 1, 0.5)));
+        assert_eq!(keyed.select(3, false, 0.875), Some((1, 2, 0.5)));
+        assert_eq!(keyed.select(2, false, 0.5), None);
+    }
+
+    #[test]
+    fn resolves_animation_values() {
+        let values = AnimationValues {
+            to: Some("10".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            values.resolve(Some("2")),
+            Some(vec!["2".to_string(), "10".to_string()])
+        );
+        assert_eq!(values.resolve(None), None);
+
+        let values = AnimationValues {
+            from: Some("1".to_string()),
+            by: Some("2".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            values.resolve(None),
+            Some(vec!["1".to_string(), "3".to_string()])
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Character data in the document tree.
+
+use std::cell::{Ref, RefCell};
+
+use crate::node::{NodeResult, NodeTrait, RsvgNode};
+use crate::properties::ComputedValues;
+use crate::property_bag::PropertyBag;
+use crate::property_defs::{WhiteSpace, XmlSpace};
+use crate::space::{
+    xml_space_normalize,
+    xml_space_normalize_lines,
+    NormalizeDefault,
+    XmlSpaceNormalize,
+};
+
+/// In SVG text elements, we use `NodeChars` to store character data.  For example,
+/// an element like `<text>Foo Bar</text>` will be a `Text` with a single child,
+/// and the child will be a `NodeChars` with "Foo Bar" for its contents.
+///
+/// Text elements can contain `<tspan>` sub-elements.  In this case,
+/// those `tspan` nodes will also contain `NodeChars` children.
+///
+/// A text or tspan element can contain more than one `NodeChars` child, for example,
+/// if there is an XML comment that splits the character contents in two:
+///
+/// ```xml
+/// <text>
+///   This sentence will create a NodeChars.
+///   <!-- this comment is ignored -->
+///   This sentence will cretea another NodeChars.
+/// </text>
+/// ```
+///
+/// When rendering a text element, it will take care of concatenating the strings
+/// in its `NodeChars` children as appropriate, depending on the
+/// `xml:space="preserve"` attribute.  A `NodeChars` stores the characters verbatim
+/// as they come out of the XML parser, after ensuring that they are valid UTF-8.
+
+pub struct NodeChars {
+    string: RefCell<String>,
+    space_normalized: RefCell<Option<String>>,
+}
+
+impl NodeChars {
+    pub fn new() -> NodeChars {
+        NodeChars {
+            string: RefCell::new(String::new()),
+            space_normalized: RefCell::new(None),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.string.borrow().is_empty()
+    }
+
+    pub fn append(&self, s: &str) {
+        self.string.borrow_mut().push_str(s);
+        *self.space_normalized.borrow_mut() = None;
+    }
+
+    /// Returns the string with its whitespace normalized according to `xml:space`.
+    ///
+    /// The normalized string is cached until more characters get appended.
+    pub fn space_normalized_string(
+        &self,
+        node: &RsvgNode,
+        values: &ComputedValues,
+    ) -> Ref<'_, String> {
+        if self.space_normalized.borrow().is_none() {
+            let mode = xml_space_mode(node, values);
+
+            *self.space_normalized.borrow_mut() =
+                Some(xml_space_normalize(mode, &self.string.borrow()));
+        }
+
+        Ref::map(self.space_normalized.borrow(), |s| s.as_ref().unwrap())
+    }
+
+    /// Returns the lines of the string, with their whitespace normalized according
+    /// to `xml:space`.
+    ///
+    /// This is used for multi-line text, where newlines in the character data are
+    /// not collapsed.
+    pub fn space_normalized_lines(&self, node: &RsvgNode, values: &ComputedValues) -> Vec<String> {
+        xml_space_normalize_lines(xml_space_mode(node, values), &self.string.borrow())
+    }
+
+    pub fn get_string(&self) -> String {
+        self.string.borrow().clone()
+    }
+}
+
+// The `white-space` property from SVG 2 takes precedence over `xml:space`, except
+// when it has its initial value.  Since `normal` can't be told apart from an
+// unspecified `white-space`, documents that only use `xml:space="preserve"` keep
+// rendering as they did before `white-space` was supported.
+fn xml_space_mode(node: &RsvgNode, values: &ComputedValues) -> XmlSpaceNormalize {
+    let elements = NormalizeDefault {
+        has_element_before: node.previous_sibling().is_some(),
+        has_element_after: node.next_sibling().is_some(),
+    };
+
+    match (values.white_space, values.xml_space) {
+        (WhiteSpace::Normal, XmlSpace::Default) => XmlSpaceNormalize::Default(elements),
+        (WhiteSpace::Normal, XmlSpace::Preserve) => XmlSpaceNormalize::Preserve,
+        (WhiteSpace::Nowrap, _) => XmlSpaceNormalize::Collapse(elements),
+        (WhiteSpace::Pre, _) | (WhiteSpace::PreWrap, _) => XmlSpaceNormalize::Preserve,
+        (WhiteSpace::PreLine, _) => XmlSpaceNormalize::PreLine(elements),
+    }
+}
+
+impl NodeTrait for NodeChars {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, _: &PropertyBag<'_>) -> NodeResult {
+        Ok(())
+    }
+}
@@ -1,16 +1,742 @@
 use cairo;
+use cssparser::{Parser, Token};
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
 
+use crate::allowed_url::Fragment;
 use crate::bbox::BoundingBox;
 use crate::coord_units::CoordUnits;
-use crate::drawing_ctx::DrawingCtx;
-use crate::error::RenderingError;
+use crate::drawing_ctx::{DrawingCtx, ViewParams};
+use crate::error::{RenderingError, ValueErrorKind};
+use crate::iri::IRI;
+use crate::length::*;
+use crate::motion_path::MotionPath;
 use crate::node::{CascadedValues, NodeDraw, NodeResult, NodeTrait, RsvgNode};
-use crate::parsers::ParseValue;
+use crate::parsers::{CssParserExt, Parse, ParseValue};
+use crate::path_builder::{PathBuilder, PathSegment};
+use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
+use crate::property_defs::FillRule;
+use crate::property_macros::ToCssValue;
+use crate::rect::RectangleExt;
+use crate::shapes::{ellipse_path, rect_path};
 
 coord_units!(ClipPathUnits, CoordUnits::UserSpaceOnUse);
 
+/// The value of the `clip-path` property
+///
+/// https://www.w3.org/TR/css-masking-1/#the-clip-path
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipSource {
+    None,
+
+    /// Reference to a `clipPath` element
+    Url(Fragment),
+
+    /// A CSS basic shape, which doesn't need a `clipPath` element
+    Shape(BasicShape),
+}
+
+impl Default for ClipSource {
+    fn default() -> ClipSource {
+        ClipSource::None
+    }
+}
+
+impl ClipSource {
+    /// Returns the reference to a `clipPath` element, or `None`
+    pub fn get(&self) -> Option<&Fragment> {
+        match *self {
+            ClipSource::Url(ref f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Returns the basic shape, or `None`
+    pub fn shape(&self) -> Option<&BasicShape> {
+        match *self {
+            ClipSource::Shape(ref shape) => Some(shape),
+            _ => None,
+        }
+    }
+}
+
+impl Parse for ClipSource {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<ClipSource, ValueErrorKind> {
+        if let Ok(iri) = parser.try_parse(IRI::parse) {
+            match iri {
+                IRI::None => Ok(ClipSource::None),
+                IRI::Resource(f) => Ok(ClipSource::Url(f)),
+            }
+        } else {
+            Ok(ClipSource::Shape(BasicShape::parse(parser)?))
+        }
+    }
+}
+
+impl ToCssValue for ClipSource {
+    fn to_css_value(&self) -> String {
+        match *self {
+            ClipSource::None => "none".to_string(),
+            ClipSource::Url(ref f) => format!("url({})", f),
+            ClipSource::Shape(ref shape) => shape.to_css_value(),
+        }
+    }
+}
+
+/// The reference box of a basic shape, against which percentages are resolved
+///
+/// The CSS boxes map to the SVG ones as the spec says for SVG elements:
+/// `content-box` and `padding-box` are the `fill-box`, and `border-box` and
+/// `margin-box` are the `stroke-box`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GeometryBox {
+    FillBox,
+    StrokeBox,
+    ViewBox,
+}
+
+impl Default for GeometryBox {
+    fn default() -> GeometryBox {
+        GeometryBox::StrokeBox
+    }
+}
+
+impl Parse for GeometryBox {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<GeometryBox, ValueErrorKind> {
+        let ident = parser.expect_ident()?;
+
+        match ident.as_ref() {
+            "fill-box" | "content-box" | "padding-box" => Ok(GeometryBox::FillBox),
+            "stroke-box" | "border-box" | "margin-box" => Ok(GeometryBox::StrokeBox),
+            "view-box" => Ok(GeometryBox::ViewBox),
+            _ => Err(ValueErrorKind::parse_error("expected a geometry box")),
+        }
+    }
+}
+
+impl ToCssValue for GeometryBox {
+    fn to_css_value(&self) -> String {
+        match *self {
+            GeometryBox::FillBox => "fill-box",
+            GeometryBox::StrokeBox => "stroke-box",
+            GeometryBox::ViewBox => "view-box",
+        }
+        .to_string()
+    }
+}
+
+/// A basic shape with its reference box, like `circle(50%) fill-box`
+///
+/// Without a shape function, the reference box itself is the clip region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicShape {
+    pub function: Option<ShapeFunction>,
+    pub reference_box: GeometryBox,
+}
+
+impl Parse for BasicShape {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<BasicShape, ValueErrorKind> {
+        let first_box = parser.try_parse(GeometryBox::parse).ok();
+
+        let function = if first_box.is_some() && parser.is_exhausted() {
+            None
+        } else {
+            Some(ShapeFunction::parse(parser)?)
+        };
+
+        let reference_box = match first_box {
+            Some(b) => b,
+            None => parser.try_parse(GeometryBox::parse).unwrap_or_default(),
+        };
+
+        Ok(BasicShape {
+            function,
+            reference_box,
+        })
+    }
+}
+
+impl ToCssValue for BasicShape {
+    fn to_css_value(&self) -> String {
+        match self.function {
+            None => self.reference_box.to_css_value(),
+            Some(ref f) if self.reference_box == GeometryBox::default() => f.to_css_value(),
+            Some(ref f) => format!("{} {}", f.to_css_value(), self.reference_box.to_css_value()),
+        }
+    }
+}
+
+impl BasicShape {
+    /// Clips the cairo context to the shape.
+    ///
+    /// The context must be in the user space of the element being clipped, whose
+    /// bounding box is `bbox`.
+    pub fn to_cairo_context(
+        &self,
+        draw_ctx: &DrawingCtx,
+        values: &ComputedValues,
+        bbox: &BoundingBox,
+    ) -> Result<(), RenderingError> {
+        let params = draw_ctx.get_view_params();
+
+        let rect = match self.reference_box {
+            GeometryBox::FillBox => bbox.rect,
+            GeometryBox::StrokeBox => bbox.ink_rect,
+            GeometryBox::ViewBox => Some(cairo::Rectangle::new(
+                0.0,
+                0.0,
+                params.view_box_width,
+                params.view_box_height,
+            )),
+        }
+        .unwrap_or_else(|| cairo::Rectangle::new(0.0, 0.0, 0.0, 0.0));
+
+        // Percentages in the shape refer to the reference box, so it acts as the viewport
+        let mut box_params = ViewParams::new(params.dpi_x, params.dpi_y, rect.width, rect.height);
+        box_params.viewport_width = params.viewport_width;
+        box_params.viewport_height = params.viewport_height;
+
+        // The shape's coordinates are relative to the origin of the reference box
+        let cr = draw_ctx.get_cairo_context();
+        cr.save();
+        cr.translate(rect.x, rect.y);
+
+        let res = match self.function {
+            Some(ref f) => f.to_cairo(&cr, values, &box_params),
+            None => rect_path(0.0, 0.0, rect.width, rect.height, 0.0, 0.0)
+                .to_cairo(&cr)
+                .map(|_| FillRule::NonZero),
+        };
+
+        cr.restore();
+
+        cr.set_fill_rule(cairo::FillRule::from(res?));
+        draw_ctx.add_clip_shape();
+        cr.clip();
+
+        Ok(())
+    }
+}
+
+/// The radius of a `circle()` or `ellipse()`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ShapeRadius<N: Normalize> {
+    Length(Length<N>),
+    ClosestSide,
+    FarthestSide,
+}
+
+impl<N: Normalize> Default for ShapeRadius<N> {
+    fn default() -> ShapeRadius<N> {
+        ShapeRadius::ClosestSide
+    }
+}
+
+impl<N: Normalize> Parse for ShapeRadius<N> {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<ShapeRadius<N>, ValueErrorKind> {
+        if parser
+            .try_parse(|p| p.expect_ident_matching("closest-side"))
+            .is_ok()
+        {
+            Ok(ShapeRadius::ClosestSide)
+        } else if parser
+            .try_parse(|p| p.expect_ident_matching("farthest-side"))
+            .is_ok()
+        {
+            Ok(ShapeRadius::FarthestSide)
+        } else {
+            Ok(ShapeRadius::Length(
+                Length::parse(parser).and_then(Length::check_nonnegative)?,
+            ))
+        }
+    }
+}
+
+impl<N: Normalize> ToCssValue for ShapeRadius<N> {
+    fn to_css_value(&self) -> String {
+        match *self {
+            ShapeRadius::Length(ref l) => l.to_css_value(),
+            ShapeRadius::ClosestSide => "closest-side".to_string(),
+            ShapeRadius::FarthestSide => "farthest-side".to_string(),
+        }
+    }
+}
+
+impl<N: Normalize> ShapeRadius<N> {
+    /// Resolves the radius for a center at `center` in a reference box of `size`, along
+    /// the radius' direction.
+    fn resolve(&self, center: f64, size: f64, values: &ComputedValues, params: &ViewParams) -> f64 {
+        match *self {
+            ShapeRadius::Length(ref l) => l.normalize(values, params),
+            ShapeRadius::ClosestSide => closest_side(center, size),
+            ShapeRadius::FarthestSide => farthest_side(center, size),
+        }
+    }
+}
+
+/// Distance from `center` to the closest side of a reference box of `size`, along one axis
+fn closest_side(center: f64, size: f64) -> f64 {
+    center.abs().min((size - center).abs())
+}
+
+/// Distance from `center` to the farthest side of a reference box of `size`, along one axis
+fn farthest_side(center: f64, size: f64) -> f64 {
+    center.abs().max((size - center).abs())
+}
+
+/// A shape function of the `clip-path` property
+///
+/// https://www.w3.org/TR/css-shapes-1/#basic-shape-functions
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeFunction {
+    Circle {
+        r: ShapeRadius<Both>,
+        cx: Length<Horizontal>,
+        cy: Length<Vertical>,
+    },
+
+    Ellipse {
+        rx: ShapeRadius<Horizontal>,
+        ry: ShapeRadius<Vertical>,
+        cx: Length<Horizontal>,
+        cy: Length<Vertical>,
+    },
+
+    Inset {
+        top: Length<Vertical>,
+        right: Length<Horizontal>,
+        bottom: Length<Vertical>,
+        left: Length<Horizontal>,
+        round: Option<(Length<Horizontal>, Length<Vertical>)>,
+    },
+
+    Polygon {
+        fill_rule: Option<FillRule>,
+        points: Vec<(Length<Horizontal>, Length<Vertical>)>,
+    },
+
+    Path {
+        fill_rule: Option<FillRule>,
+        path: MotionPath,
+    },
+}
+
+impl Parse for ShapeFunction {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<ShapeFunction, ValueErrorKind> {
+        let name = parser.expect_function()?.clone();
+
+        parser
+            .parse_nested_block(|p| {
+                let res = match name.as_ref() {
+                    "circle" => parse_circle_args(p),
+                    "ellipse" => parse_ellipse_args(p),
+                    "inset" => parse_inset_args(p),
+                    "polygon" => parse_polygon_args(p),
+                    "path" => parse_path_args(p),
+                    _ => Err(ValueErrorKind::parse_error(
+                        "expected circle|ellipse|inset|polygon|path",
+                    )),
+                };
+
+                res.map_err(|e| p.new_custom_error(e))
+            })
+            .map_err(ValueErrorKind::from)
+    }
+}
+
+fn parse_circle_args(parser: &mut Parser<'_, '_>) -> Result<ShapeFunction, ValueErrorKind> {
+    let r = parser.try_parse(ShapeRadius::parse).unwrap_or_default();
+    let (cx, cy) = parse_optional_position(parser)?;
+
+    Ok(ShapeFunction::Circle { r, cx, cy })
+}
+
+fn parse_ellipse_args(parser: &mut Parser<'_, '_>) -> Result<ShapeFunction, ValueErrorKind> {
+    let (rx, ry) = if let Ok(rx) = parser.try_parse(ShapeRadius::parse) {
+        (rx, ShapeRadius::parse(parser)?)
+    } else {
+        (ShapeRadius::default(), ShapeRadius::default())
+    };
+
+    let (cx, cy) = parse_optional_position(parser)?;
+
+    Ok(ShapeFunction::Ellipse { rx, ry, cx, cy })
+}
+
+/// Parses a `<length-percentage>` for both directions, for the `inset()` offsets and radius.
+fn parse_both_directions(
+    parser: &mut Parser<'_, '_>,
+) -> Result<(Length<Horizontal>, Length<Vertical>), ValueErrorKind> {
+    let state = parser.state();
+    let horizontal = Length::parse(parser)?;
+    parser.reset(&state);
+    let vertical = Length::parse(parser)?;
+
+    Ok((horizontal, vertical))
+}
+
+fn parse_inset_args(parser: &mut Parser<'_, '_>) -> Result<ShapeFunction, ValueErrorKind> {
+    let mut offsets = vec![parse_both_directions(parser)?];
+
+    while offsets.len() < 4 {
+        if let Ok(offset) = parser.try_parse(parse_both_directions) {
+            offsets.push(offset);
+        } else {
+            break;
+        }
+    }
+
+    // Like the margin shorthand: top, right, bottom, left
+    let top = offsets[0].1;
+    let right = offsets.get(1).unwrap_or(&offsets[0]).0;
+    let bottom = offsets.get(2).unwrap_or(&offsets[0]).1;
+    let left = offsets
+        .get(3)
+        .or_else(|| offsets.get(1))
+        .unwrap_or(&offsets[0])
+        .0;
+
+    let round = if parser
+        .try_parse(|p| p.expect_ident_matching("round"))
+        .is_ok()
+    {
+        let (rx, ry) = parse_both_directions(parser)?;
+        Some((rx.check_nonnegative()?, ry.check_nonnegative()?))
+    } else {
+        None
+    };
+
+    Ok(ShapeFunction::Inset {
+        top,
+        right,
+        bottom,
+        left,
+        round,
+    })
+}
+
+/// Parses an optional `<fill-rule>` followed by a comma, for `polygon()` and `path()`.
+fn parse_optional_fill_rule(
+    parser: &mut Parser<'_, '_>,
+) -> Result<Option<FillRule>, ValueErrorKind> {
+    if let Ok(fill_rule) = parser.try_parse(FillRule::parse) {
+        parser.expect_comma()?;
+        Ok(Some(fill_rule))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_polygon_args(parser: &mut Parser<'_, '_>) -> Result<ShapeFunction, ValueErrorKind> {
+    let fill_rule = parse_optional_fill_rule(parser)?;
+
+    let mut points = Vec::new();
+
+    loop {
+        let x = Length::parse(parser)?;
+        let y = Length::parse(parser)?;
+        points.push((x, y));
+
+        if parser.is_exhausted() {
+            break;
+        }
+
+        parser.expect_comma()?;
+    }
+
+    Ok(ShapeFunction::Polygon { fill_rule, points })
+}
+
+fn parse_path_args(parser: &mut Parser<'_, '_>) -> Result<ShapeFunction, ValueErrorKind> {
+    let fill_rule = parse_optional_fill_rule(parser)?;
+    let source = parser.expect_string()?.as_ref().to_string();
+    let path = MotionPath::from_path_data(&source)?;
+
+    Ok(ShapeFunction::Path { fill_rule, path })
+}
+
+fn percent<N: Normalize>(p: f64) -> Length<N> {
+    Length::new(p, LengthUnit::Percent)
+}
+
+/// One component of a `<position>`, as a percentage or length, and whether a keyword
+/// says which direction it is for
+enum PositionComponent {
+    Horizontal(Length<Horizontal>),
+    Vertical(Length<Vertical>),
+    Either(Length<Horizontal>, Length<Vertical>),
+}
+
+fn parse_position_component(
+    parser: &mut Parser<'_, '_>,
+) -> Result<PositionComponent, ValueErrorKind> {
+    let keyword = parser.try_parse(|p| {
+        let ident = p.expect_ident()?;
+
+        match ident.as_ref() {
+            "left" => Ok(PositionComponent::Horizontal(percent(0.0))),
+            "right" => Ok(PositionComponent::Horizontal(percent(1.0))),
+            "top" => Ok(PositionComponent::Vertical(percent(0.0))),
+            "bottom" => Ok(PositionComponent::Vertical(percent(1.0))),
+            "center" => Ok(PositionComponent::Either(percent(0.5), percent(0.5))),
+            _ => Err(ValueErrorKind::parse_error("expected a position")),
+        }
+    });
+
+    if let Ok(component) = keyword {
+        Ok(component)
+    } else {
+        let (x, y) = parse_both_directions(parser)?;
+        Ok(PositionComponent::Either(x, y))
+    }
+}
+
+/// Parses an optional `at <position>` with one or two components, and returns the
+/// center of the reference box if it is not there.
+fn parse_optional_position(
+    parser: &mut Parser<'_, '_>,
+) -> Result<(Length<Horizontal>, Length<Vertical>), ValueErrorKind> {
+    use self::PositionComponent::*;
+
+    let center = (percent(0.5), percent(0.5));
+
+    if parser.try_parse(|p| p.expect_ident_matching("at")).is_err() {
+        return Ok(center);
+    }
+
+    let first = parse_position_component(parser)?;
+
+    let second = if parser.is_exhausted() {
+        Either(center.0, center.1)
+    } else {
+        parse_position_component(parser)?
+    };
+
+    match (first, second) {
+        (Horizontal(x), Vertical(y))
+        | (Horizontal(x), Either(_, y))
+        | (Either(x, _), Vertical(y))
+        | (Vertical(y), Horizontal(x))
+        | (Vertical(y), Either(x, _))
+        | (Either(_, y), Horizontal(x)) => Ok((x, y)),
+
+        (Either(x, _), Either(_, y)) => Ok((x, y)),
+
+        _ => Err(ValueErrorKind::parse_error("invalid position")),
+    }
+}
+
+impl ToCssValue for ShapeFunction {
+    fn to_css_value(&self) -> String {
+        let fill_rule_prefix = |fill_rule: &Option<FillRule>| {
+            fill_rule.map_or_else(String::new, |f| format!("{}, ", f.to_css_value()))
+        };
+
+        match *self {
+            ShapeFunction::Circle {
+                ref r,
+                ref cx,
+                ref cy,
+            } => format!(
+                "circle({} at {} {})",
+                r.to_css_value(),
+                cx.to_css_value(),
+                cy.to_css_value()
+            ),
+
+            ShapeFunction::Ellipse {
+                ref rx,
+                ref ry,
+                ref cx,
+                ref cy,
+            } => format!(
+                "ellipse({} {} at {} {})",
+                rx.to_css_value(),
+                ry.to_css_value(),
+                cx.to_css_value(),
+                cy.to_css_value()
+            ),
+
+            ShapeFunction::Inset {
+                ref top,
+                ref right,
+                ref bottom,
+                ref left,
+                ref round,
+            } => {
+                let round = round.map_or_else(String::new, |(rx, ry)| {
+                    if rx.to_css_value() == ry.to_css_value() {
+                        format!(" round {}", rx.to_css_value())
+                    } else {
+                        format!(" round {} / {}", rx.to_css_value(), ry.to_css_value())
+                    }
+                });
+
+                format!(
+                    "inset({} {} {} {}{})",
+                    top.to_css_value(),
+                    right.to_css_value(),
+                    bottom.to_css_value(),
+                    left.to_css_value(),
+                    round
+                )
+            }
+
+            ShapeFunction::Polygon {
+                ref fill_rule,
+                ref points,
+            } => {
+                let points = points
+                    .iter()
+                    .map(|(x, y)| format!("{} {}", x.to_css_value(), y.to_css_value()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!("polygon({}{})", fill_rule_prefix(fill_rule), points)
+            }
+
+            ShapeFunction::Path {
+                ref fill_rule,
+                ref path,
+            } => {
+                let mut source = String::new();
+                cssparser::serialize_string(path.source(), &mut source).unwrap();
+
+                format!("path({}{})", fill_rule_prefix(fill_rule), source)
+            }
+        }
+    }
+}
+
+impl ShapeFunction {
+    /// Appends the outline of the shape to the cairo context's path, relative to the origin
+    /// of the reference box, and returns the shape's fill rule.
+    ///
+    /// The `params` must have the reference box as their view box, so that percentages
+    /// get resolved against it.
+    fn to_cairo(
+        &self,
+        cr: &cairo::Context,
+        values: &ComputedValues,
+        params: &ViewParams,
+    ) -> Result<FillRule, cairo::Status> {
+        let width = params.view_box_width;
+        let height = params.view_box_height;
+
+        // Storage for the shapes that are not a path()
+        let owned_builder;
+
+        let (builder, fill_rule) = match *self {
+            ShapeFunction::Circle {
+                ref r,
+                ref cx,
+                ref cy,
+            } => {
+                let cx = cx.normalize(values, params);
+                let cy = cy.normalize(values, params);
+
+                let r = match *r {
+                    ShapeRadius::Length(ref l) => l.normalize(values, params),
+                    ShapeRadius::ClosestSide => {
+                        closest_side(cx, width).min(closest_side(cy, height))
+                    }
+                    ShapeRadius::FarthestSide => {
+                        farthest_side(cx, width).max(farthest_side(cy, height))
+                    }
+                };
+
+                owned_builder = ellipse_path(cx, cy, r, r);
+                (&owned_builder, FillRule::NonZero)
+            }
+
+            ShapeFunction::Ellipse {
+                ref rx,
+                ref ry,
+                ref cx,
+                ref cy,
+            } => {
+                let cx = cx.normalize(values, params);
+                let cy = cy.normalize(values, params);
+                let rx = rx.resolve(cx, width, values, params);
+                let ry = ry.resolve(cy, height, values, params);
+
+                owned_builder = ellipse_path(cx, cy, rx, ry);
+                (&owned_builder, FillRule::NonZero)
+            }
+
+            ShapeFunction::Inset {
+                ref top,
+                ref right,
+                ref bottom,
+                ref left,
+                ref round,
+            } => {
+                let top = top.normalize(values, params);
+                let right = right.normalize(values, params);
+                let bottom = bottom.normalize(values, params);
+                let left = left.normalize(values, params);
+
+                let (rx, ry) = round.map_or((0.0, 0.0), |(rx, ry)| {
+                    (rx.normalize(values, params), ry.normalize(values, params))
+                });
+
+                let w = (width - left - right).max(0.0);
+                let h = (height - top - bottom).max(0.0);
+
+                owned_builder = rect_path(left, top, w, h, rx, ry);
+                (&owned_builder, FillRule::NonZero)
+            }
+
+            ShapeFunction::Polygon {
+                ref fill_rule,
+                ref points,
+            } => {
+                let mut builder = PathBuilder::new();
+
+                for (i, (x, y)) in points.iter().enumerate() {
+                    let x = x.normalize(values, params);
+                    let y = y.normalize(values, params);
+
+                    if i == 0 {
+                        builder.move_to(x, y);
+                    } else {
+                        builder.line_to(x, y);
+                    }
+                }
+
+                builder.close_path();
+
+                owned_builder = builder;
+                (&owned_builder, fill_rule.unwrap_or(FillRule::NonZero))
+            }
+
+            ShapeFunction::Path {
+                ref fill_rule,
+                ref path,
+            } => (path.builder(), fill_rule.unwrap_or(FillRule::NonZero)),
+        };
+
+        if !builder.is_empty() {
+            builder.to_cairo(cr)?;
+        }
+
+        Ok(fill_rule)
+    }
+}
+
+/// One of the shapes whose intersection is the clip region of an element
+///
+/// This can come from a `clipPath` element, or from the viewport of an element
+/// like `svg`, `symbol` or `marker`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipShape {
+    /// The outline of the shape
+    pub segments: Vec<PathSegment>,
+
+    /// How the inside of the outline is determined, from the `clip-rule` property
+    pub fill_rule: cairo::FillRule,
+}
+
 #[derive(Default)]
 pub struct ClipPath {
     units: ClipPathUnits,
@@ -27,9 +753,16 @@ impl ClipPath {
         draw_ctx: &mut DrawingCtx,
         bbox: &BoundingBox,
     ) -> Result<(), RenderingError> {
-        if self.units == ClipPathUnits(CoordUnits::ObjectBoundingBox) && bbox.rect.is_none() {
-            // The node being clipped is empty / doesn't have a
-            // bounding box, so there's nothing to clip!
+        if self.units == ClipPathUnits(CoordUnits::ObjectBoundingBox)
+            && bbox.rect.map_or(true, |r| r.is_empty())
+        {
+            // The node being clipped is empty, or its bounding box has
+            // zero width or height, so the clipping path has no area
+            // and it clips away everything.
+            let cr = draw_ctx.get_cairo_context();
+            cr.rectangle(0.0, 0.0, 0.0, 0.0);
+            draw_ctx.add_clip_shape();
+            cr.clip();
             return Ok(());
         }
 
@@ -55,6 +788,7 @@ impl ClipPath {
                 // here we don't push a layer because we are clipping
                 let res = node.draw_children(&cascaded, dc, true);
 
+                dc.add_clip_shape();
                 cr.clip();
 
                 res
@@ -79,3 +813,65 @@ impl NodeTrait for ClipPath {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clip_source() {
+        assert_eq!(ClipSource::parse_str("none"), Ok(ClipSource::None));
+        assert_eq!(
+            ClipSource::parse_str("url(#foo)"),
+            Ok(ClipSource::Url(Fragment::new(None, "foo".to_string())))
+        );
+        assert_eq!(
+            ClipSource::parse_str("fill-box"),
+            Ok(ClipSource::Shape(BasicShape {
+                function: None,
+                reference_box: GeometryBox::FillBox,
+            }))
+        );
+        assert!(ClipSource::parse_str("foo").is_err());
+    }
+
+    fn serialized(s: &str) -> String {
+        ClipSource::parse_str(s).unwrap().to_css_value()
+    }
+
+    #[test]
+    fn parses_basic_shapes() {
+        assert_eq!(serialized("circle()"), "circle(closest-side at 50% 50%)");
+        assert_eq!(
+            serialized("circle(10px at left top) view-box"),
+            "circle(10px at 0% 0%) view-box"
+        );
+        assert_eq!(
+            serialized("padding-box ellipse(farthest-side 20% at bottom 5px)"),
+            "ellipse(farthest-side 20% at 5px 100%) fill-box"
+        );
+        assert_eq!(
+            serialized("inset(1px 2px round 3px)"),
+            "inset(1px 2px 1px 2px round 3px)"
+        );
+        assert_eq!(
+            serialized("polygon(evenodd, 0 0, 100% 0, 50% 100%) border-box"),
+            "polygon(evenodd, 0px 0px, 100% 0px, 50% 100%)"
+        );
+        assert_eq!(
+            serialized("path('M 0 0 L 10 10 Z')"),
+            "path(\"M 0 0 L 10 10 Z\")"
+        );
+    }
+
+    #[test]
+    fn errors_on_invalid_basic_shapes() {
+        assert!(ClipSource::parse_str("circle(-10px)").is_err());
+        assert!(ClipSource::parse_str("circle(at)").is_err());
+        assert!(ClipSource::parse_str("ellipse(10px)").is_err());
+        assert!(ClipSource::parse_str("inset()").is_err());
+        assert!(ClipSource::parse_str("polygon(0 0, 10px)").is_err());
+        assert!(ClipSource::parse_str("path(nonzero 'M 0 0')").is_err());
+        assert!(ClipSource::parse_str("square(10px)").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! Statistics about the structure of a document.
+//!
+//! Services that render untrusted documents want to reject or deprioritize the
+//! pathological ones before spending any time rendering them.  The structure of the
+//! tree is a good first estimate of the cost, so we compute it once, when the document
+//! is loaded.
+
+use markup5ever::{expanded_name, local_name, namespace_url, ns};
+use rctree::NodeEdge;
+use std::collections::BTreeMap;
+
+use crate::node::{NodeType, RsvgNode};
+use crate::shapes::Path;
+
+/// Statistics about the elements of a document
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ComplexityReport {
+    /// Number of elements, by element name
+    pub element_counts: BTreeMap<String, usize>,
+
+    /// Total number of elements
+    pub num_elements: usize,
+
+    /// Deepest nesting of elements; the toplevel `<svg>` is at depth 1
+    pub max_depth: usize,
+
+    /// Number of `<filter>` elements
+    pub num_filters: usize,
+
+    /// Total number of segments in the path data of `<path>` elements
+    pub num_path_segments: usize,
+
+    /// Number of references to other elements or to external files, from `href`
+    /// attributes and from `url()` values in attributes
+    pub num_references: usize,
+}
+
+/// Walks the tree under `root` and collects its statistics.
+pub fn compute_complexity(root: &RsvgNode) -> ComplexityReport {
+    let mut report = ComplexityReport::default();
+    let mut depth = 0;
+
+    // Documents can be deeply nested, so don't recurse
+    for edge in root.traverse() {
+        match edge {
+            NodeEdge::Start(node) => {
+                depth += 1;
+                add_element(&mut report, &node, depth);
+            }
+
+            NodeEdge::End(_) => depth -= 1,
+        }
+    }
+
+    report
+}
+
+fn add_element(report: &mut ComplexityReport, node: &RsvgNode, depth: usize) {
+    let data = node.borrow();
+
+    let node_type = data.get_type();
+    if node_type == NodeType::Chars {
+        return;
+    }
+
+    *report
+        .element_counts
+        .entry(data.element_name().local.to_string())
+        .or_insert(0) += 1;
+
+    report.num_elements += 1;
+    report.max_depth = report.max_depth.max(depth);
+
+    match node_type {
+        NodeType::Filter => report.num_filters += 1,
+
+        NodeType::Path => {
+            report.num_path_segments += data.get_impl::<Path>().builder().to_segments().len()
+        }
+
+        _ => (),
+    }
+
+    report.num_references += data
+        .get_attributes()
+        .iter()
+        .map(|(attr, value)| match attr.expanded() {
+            expanded_name!(xlink "href") | expanded_name!(svg "href") => 1,
+            _ => value.matches("url(").count(),
+        })
+        .sum::<usize>();
+}
@@ -64,8 +64,35 @@ impl RequiredFeatures {
     }
 }
 
+/// How closely a language tag matches one of the user's languages
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchQuality {
+    /// The tag is the user's language, like `en-US` for `en-US`
+    Exact,
+
+    /// The tag is more specific than the user's language, like `de-LU` for `de`
+    Specific,
+
+    /// The tag is more general than the user's language, like `en` for `en-US`
+    General,
+
+    /// The tag is for another region of the user's language, like `en-GB` for `en-US`
+    SameLanguage,
+}
+
+/// How well a `systemLanguage` attribute matches the user's languages
+///
+/// Ranks compare first by the position of the user's language in the list of
+/// preferred languages, and then by how closely it is matched.  Lower ranks are
+/// better matches.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LanguageRank {
+    preference: usize,
+    quality: MatchQuality,
+}
+
 #[derive(Debug, PartialEq)]
-pub struct SystemLanguage(pub bool);
+pub struct SystemLanguage(pub Option<LanguageRank>);
 
 impl SystemLanguage {
     /// Parse a `systemLanguage` attribute and match it against a given `Locale`
@@ -73,10 +100,15 @@ impl SystemLanguage {
     /// The [`systemLanguage`] conditional attribute is a
     /// comma-separated list of [BCP47] Language Tags.  This function
     /// parses the attribute and matches the result against a given
-    /// `locale`.  If there is a match, i.e. if the given locale
-    /// supports one of the languages listed in the `systemLanguage`
-    /// attribute, then the `SystemLanguage.0` will be `true`;
-    /// otherwise it will be `false`.
+    /// `locale`.  If any of the languages listed in the `systemLanguage`
+    /// attribute is acceptable for the locale, then `SystemLanguage.0`
+    /// will have the rank of the best match; otherwise it will be `None`.
+    ///
+    /// Besides the matches from [BCP47], like `de-LU` for a user who
+    /// prefers `de`, this does a best-effort fallback to other tags for the
+    /// same language, so that `en` or `en-GB` are acceptable for a user who
+    /// prefers `en-US`.  These fallbacks rank lower than an exact match for
+    /// the same preferred language.
     ///
     /// Normally, calling code will pass `&Locale::current()` for the
     /// `locale` attribute; this is the user's current locale.
@@ -84,60 +116,74 @@ impl SystemLanguage {
     /// [`systemLanguage`]: https://www.w3.org/TR/SVG/struct.html#ConditionalProcessingSystemLanguageAttribute
     /// [BCP47]: http://www.ietf.org/rfc/bcp/bcp47.txt
     pub fn from_attribute(s: &str, locale: &Locale) -> Result<SystemLanguage, ValueErrorKind> {
-        s.split(',').map(LanguageTag::from_str).try_fold(
-            // start with no match
-            SystemLanguage(false),
-            // The accumulator is Result<SystemLanguage, ValueErrorKind>
-            |acc, tag_result| match tag_result {
-                Ok(language_tag) => {
-                    let have_match = acc.0;
-                    if have_match {
-                        Ok(SystemLanguage(have_match))
-                    } else {
-                        locale_accepts_language_tag(locale, &language_tag)
-                            .map(SystemLanguage)
-                    }
-                }
-
-                Err(e) => Err(ValueErrorKind::parse_error(&format!(
-                    "invalid language tag: \"{}\"", e)
-                )),
-            },
-        )
+        let language_tags = s
+            .split(',')
+            .map(|tag| {
+                LanguageTag::from_str(tag).map_err(|e| {
+                    ValueErrorKind::parse_error(&format!("invalid language tag: \"{}\"", e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let locale_ranges = locale
+            .tags_for("messages")
+            .filter(|range| *range != LanguageRange::invariant());
+
+        for (preference, locale_range) in locale_ranges.enumerate() {
+            let locale_tag = locale_range_to_tag(&locale_range)?;
+
+            // The user's languages are in order of preference, so the first one that
+            // matches at all determines the rank.
+            let best_quality = language_tags
+                .iter()
+                .filter_map(|tag| match_quality(&locale_tag, tag))
+                .min();
+
+            if let Some(quality) = best_quality {
+                return Ok(SystemLanguage(Some(LanguageRank {
+                    preference,
+                    quality,
+                })));
+            }
+        }
+
+        Ok(SystemLanguage(None))
     }
 }
 
-fn locale_accepts_language_tag(
-    locale: &Locale,
-    language_tag: &LanguageTag,
-) -> Result<bool, ValueErrorKind> {
-    for locale_range in locale.tags_for("messages") {
-        if locale_range == LanguageRange::invariant() {
-            continue;
-        }
+fn locale_range_to_tag(locale_range: &LanguageRange<'_>) -> Result<LanguageTag, ValueErrorKind> {
+    let str_locale_range = locale_range.as_ref();
 
-        let str_locale_range = locale_range.as_ref();
+    let locale_tag = LanguageTag::from_str(str_locale_range).map_err(|e| {
+        ValueErrorKind::parse_error(&format!(
+            "invalid language tag \"{}\" in locale: {}",
+            str_locale_range, e
+        ))
+    })?;
 
-        let locale_tag = LanguageTag::from_str(str_locale_range).map_err(|e| {
-            ValueErrorKind::parse_error(&format!(
-                "invalid language tag \"{}\" in locale: {}",
-                str_locale_range, e
-            ))
-        })?;
+    if !locale_tag.is_language_range() {
+        return Err(ValueErrorKind::value_error(&format!(
+            "language tag \"{}\" is not a language range",
+            locale_tag
+        )));
+    }
 
-        if !locale_tag.is_language_range() {
-            return Err(ValueErrorKind::value_error(&format!(
-                "language tag \"{}\" is not a language range",
-                locale_tag
-            )));
-        }
+    Ok(locale_tag)
+}
 
-        if locale_tag.matches(language_tag) {
-            return Ok(true);
+fn match_quality(locale_tag: &LanguageTag, language_tag: &LanguageTag) -> Option<MatchQuality> {
+    if locale_tag == language_tag {
+        Some(MatchQuality::Exact)
+    } else if locale_tag.matches(language_tag) {
+        Some(MatchQuality::Specific)
+    } else if language_tag.is_language_range() && language_tag.matches(locale_tag) {
+        Some(MatchQuality::General)
+    } else {
+        match (&locale_tag.language, &language_tag.language) {
+            (Some(a), Some(b)) if a.eq_ignore_ascii_case(b) => Some(MatchQuality::SameLanguage),
+            _ => None,
         }
     }
-
-    Ok(false)
 }
 
 #[cfg(test)]
@@ -185,48 +231,90 @@ mod tests {
     fn system_language() {
         let user_prefers = Locale::new("de,en-US").unwrap();
 
+        let rank = |preference, quality| {
+            Ok(SystemLanguage(Some(LanguageRank {
+                preference,
+                quality,
+            })))
+        };
+
         assert!(SystemLanguage::from_attribute("", &user_prefers).is_err());
 
         assert!(SystemLanguage::from_attribute("12345", &user_prefers).is_err());
 
         assert_eq!(
             SystemLanguage::from_attribute("fr", &user_prefers),
-            Ok(SystemLanguage(false))
+            Ok(SystemLanguage(None))
         );
 
         assert_eq!(
-            SystemLanguage::from_attribute("en", &user_prefers),
-            Ok(SystemLanguage(false))
+            SystemLanguage::from_attribute("de", &user_prefers),
+            rank(0, MatchQuality::Exact)
         );
 
         assert_eq!(
-            SystemLanguage::from_attribute("de", &user_prefers),
-            Ok(SystemLanguage(true))
+            SystemLanguage::from_attribute("en-US", &user_prefers),
+            rank(1, MatchQuality::Exact)
         );
 
         assert_eq!(
-            SystemLanguage::from_attribute("en-US", &user_prefers),
-            Ok(SystemLanguage(true))
+            SystemLanguage::from_attribute("DE", &user_prefers),
+            rank(0, MatchQuality::Exact)
         );
 
         assert_eq!(
-            SystemLanguage::from_attribute("en-GB", &user_prefers),
-            Ok(SystemLanguage(false))
+            SystemLanguage::from_attribute("de-LU", &user_prefers),
+            rank(0, MatchQuality::Specific)
         );
 
         assert_eq!(
-            SystemLanguage::from_attribute("DE", &user_prefers),
-            Ok(SystemLanguage(true))
+            SystemLanguage::from_attribute("fr, de", &user_prefers),
+            rank(0, MatchQuality::Exact)
         );
 
         assert_eq!(
-            SystemLanguage::from_attribute("de-LU", &user_prefers),
-            Ok(SystemLanguage(true))
+            SystemLanguage::from_attribute("en-US, de-LU", &user_prefers),
+            rank(0, MatchQuality::Specific)
         );
+    }
+
+    #[test]
+    fn system_language_falls_back_to_other_regions() {
+        let user_prefers = Locale::new("de,en-US").unwrap();
 
         assert_eq!(
-            SystemLanguage::from_attribute("fr, de", &user_prefers),
-            Ok(SystemLanguage(true))
+            SystemLanguage::from_attribute("en", &user_prefers),
+            Ok(SystemLanguage(Some(LanguageRank {
+                preference: 1,
+                quality: MatchQuality::General,
+            })))
+        );
+
+        assert_eq!(
+            SystemLanguage::from_attribute("en-GB", &user_prefers),
+            Ok(SystemLanguage(Some(LanguageRank {
+                preference: 1,
+                quality: MatchQuality::SameLanguage,
+            })))
         );
+
+        assert_eq!(
+            SystemLanguage::from_attribute("en-GB, en", &user_prefers),
+            Ok(SystemLanguage(Some(LanguageRank {
+                preference: 1,
+                quality: MatchQuality::General,
+            })))
+        );
+
+        let user_prefers = Locale::new("de-DE,en-US").unwrap();
+        let rank = |s| SystemLanguage::from_attribute(s, &user_prefers).unwrap().0;
+
+        // A fallback for a more preferred language is better than an exact match
+        // for a less preferred one
+        assert!(rank("de-AT") < rank("en-US"));
+
+        assert!(rank("de-DE") < rank("de"));
+        assert!(rank("de") < rank("de-AT"));
+        assert_eq!(rank("fr-FR"), None);
     }
 }
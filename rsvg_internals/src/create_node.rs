@@ -2,7 +2,9 @@ use markup5ever::{expanded_name, local_name, namespace_url, ns, QualName};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 
+use crate::animation::{Animate, AnimateMotion, AnimateTransform, Set};
 use crate::clip_path::ClipPath;
+#[cfg(feature = "filters")]
 use crate::filters::{
     blend::FeBlend,
     color_matrix::FeColorMatrix,
@@ -10,6 +12,7 @@ use crate::filters::{
     composite::FeComposite,
     convolve_matrix::FeConvolveMatrix,
     displacement_map::FeDisplacementMap,
+    drop_shadow::FeDropShadow,
     flood::FeFlood,
     gaussian_blur::FeGaussianBlur,
     image::FeImage,
@@ -24,19 +27,24 @@ use crate::filters::{
     turbulence::FeTurbulence,
 };
 
+#[cfg(feature = "filters")]
 use crate::filter::Filter;
 use crate::gradient::{LinearGradient, RadialGradient, Stop};
+use crate::hatch::{Hatch, HatchPath};
+#[cfg(feature = "raster-images")]
 use crate::image::Image;
 use crate::link::Link;
 use crate::marker::Marker;
 use crate::mask::Mask;
+use crate::mesh_gradient::{MeshGradient, MeshPatch, MeshRow};
 use crate::node::*;
 use crate::pattern::Pattern;
 use crate::property_bag::PropertyBag;
 use crate::shapes::{Circle, Ellipse, Line, Path, Polygon, Polyline, Rect};
-use crate::structure::{Group, NonRendering, Svg, Switch, Symbol, Use};
+use crate::structure::{Group, NonRendering, Svg, Switch, Symbol, Unsupported, Use};
 use crate::style::Style;
-use crate::text::{TRef, TSpan, Text};
+#[cfg(feature = "text")]
+use crate::text::{TRef, TSpan, Text, TextPath};
 
 macro_rules! n {
     ($name:ident, $node_type:ident) => {
@@ -56,57 +64,99 @@ macro_rules! n {
 mod creators {
     use super::*;
 
+    n!(create_animate,                  Animate);
+    n!(create_animate_motion,           AnimateMotion);
+    n!(create_animate_transform,        AnimateTransform);
     n!(create_circle,                   Circle);
     n!(create_clip_path,                ClipPath);
     n!(create_defs,                     NonRendering);
     n!(create_ellipse,                  Ellipse);
+    #[cfg(feature = "filters")]
     n!(create_fe_blend,                 FeBlend);
+    #[cfg(feature = "filters")]
     n!(create_fe_color_matrix,          FeColorMatrix);
+    #[cfg(feature = "filters")]
     n!(create_fe_component_transfer,    FeComponentTransfer);
+    #[cfg(feature = "filters")]
     n!(create_fe_func_a,                FeFuncA);
+    #[cfg(feature = "filters")]
     n!(create_fe_func_b,                FeFuncB);
+    #[cfg(feature = "filters")]
     n!(create_fe_func_g,                FeFuncG);
+    #[cfg(feature = "filters")]
     n!(create_fe_func_r,                FeFuncR);
+    #[cfg(feature = "filters")]
     n!(create_fe_composite,             FeComposite);
+    #[cfg(feature = "filters")]
     n!(create_fe_convolve_matrix,       FeConvolveMatrix);
+    #[cfg(feature = "filters")]
     n!(create_fe_diffuse_lighting,      FeDiffuseLighting);
+    #[cfg(feature = "filters")]
     n!(create_fe_distant_light,         FeDistantLight);
+    #[cfg(feature = "filters")]
     n!(create_fe_displacement_map,      FeDisplacementMap);
+    #[cfg(feature = "filters")]
+    n!(create_fe_drop_shadow,           FeDropShadow);
+    #[cfg(feature = "filters")]
     n!(create_fe_flood,                 FeFlood);
+    #[cfg(feature = "filters")]
     n!(create_fe_gaussian_blur,         FeGaussianBlur);
+    #[cfg(feature = "filters")]
     n!(create_fe_image,                 FeImage);
+    #[cfg(feature = "filters")]
     n!(create_fe_merge,                 FeMerge);
+    #[cfg(feature = "filters")]
     n!(create_fe_merge_node,            FeMergeNode);
+    #[cfg(feature = "filters")]
     n!(create_fe_morphology,            FeMorphology);
+    #[cfg(feature = "filters")]
     n!(create_fe_offset,                FeOffset);
+    #[cfg(feature = "filters")]
     n!(create_fe_point_light,           FePointLight);
+    #[cfg(feature = "filters")]
     n!(create_fe_specular_lighting,     FeSpecularLighting);
+    #[cfg(feature = "filters")]
     n!(create_fe_spot_light,            FeSpotLight);
+    #[cfg(feature = "filters")]
     n!(create_fe_tile,                  FeTile);
+    #[cfg(feature = "filters")]
     n!(create_fe_turbulence,            FeTurbulence);
+    #[cfg(feature = "filters")]
     n!(create_filter,                   Filter);
     n!(create_group,                    Group);
+    n!(create_hatch,                    Hatch);
+    n!(create_hatch_path,               HatchPath);
+    #[cfg(feature = "raster-images")]
     n!(create_image,                    Image);
     n!(create_line,                     Line);
     n!(create_linear_gradient,          LinearGradient);
     n!(create_link,                     Link);
     n!(create_marker,                   Marker);
     n!(create_mask,                     Mask);
-    n!(create_non_rendering,            NonRendering);
+    n!(create_mesh_gradient,            MeshGradient);
+    n!(create_mesh_patch,               MeshPatch);
+    n!(create_mesh_row,                 MeshRow);
     n!(create_path,                     Path);
     n!(create_pattern,                  Pattern);
     n!(create_polygon,                  Polygon);
     n!(create_polyline,                 Polyline);
     n!(create_radial_gradient,          RadialGradient);
     n!(create_rect,                     Rect);
+    n!(create_set,                      Set);
     n!(create_stop,                     Stop);
     n!(create_style,                    Style);
     n!(create_svg,                      Svg);
     n!(create_switch,                   Switch);
     n!(create_symbol,                   Symbol);
+    #[cfg(feature = "text")]
     n!(create_text,                     Text);
+    #[cfg(feature = "text")]
+    n!(create_text_path,                TextPath);
+    #[cfg(feature = "text")]
     n!(create_tref,                     TRef);
+    #[cfg(feature = "text")]
     n!(create_tspan,                    TSpan);
+    n!(create_unsupported,              Unsupported);
     n!(create_use,                      Use);
 
     /* Hack to make multiImage sort-of work
@@ -127,19 +177,21 @@ use creators::*;
 
 type NodeCreateFn = fn(element_name: &QualName, id: Option<&str>, class: Option<&str>) -> RsvgNode;
 
-// Lines in comments are elements that we don't support.
+// Lines in comments are elements that we don't support.  Elements which are
+// supported only with certain cargo features are in their own tables below.
 #[cfg_attr(rustfmt, rustfmt_skip)]
 static NODE_CREATORS: Lazy<HashMap<&'static str, (bool, NodeCreateFn)>> = Lazy::new(|| {
-    let creators_table: Vec<(&str, bool, NodeCreateFn)> = vec![
+    #[allow(unused_mut)]
+    let mut creators_table: Vec<(&str, bool, NodeCreateFn)> = vec![
         // name, supports_class, create_fn
         ("a",                   true,  create_link),
         /* ("altGlyph",         true,  ), */
         /* ("altGlyphDef",      false, ), */
         /* ("altGlyphItem",     false, ), */
-        /* ("animate",          false, ), */
+        ("animate",             false, create_animate),
         /* ("animateColor",     false, ), */
-        /* ("animateMotion",    false, ), */
-        /* ("animateTransform", false, ), */
+        ("animateMotion",       false, create_animate_motion),
+        ("animateTransform",    false, create_animate_transform),
         ("circle",              true,  create_circle),
         ("clipPath",            true,  create_clip_path),
         /* ("color-profile",    false, ), */
@@ -147,31 +199,6 @@ static NODE_CREATORS: Lazy<HashMap<&'static str, (bool, NodeCreateFn)>> = Lazy::
         ("defs",                true,  create_defs),
         /* ("desc",             true,  ), */
         ("ellipse",             true,  create_ellipse),
-        ("feBlend",             true,  create_fe_blend),
-        ("feColorMatrix",       true,  create_fe_color_matrix),
-        ("feComponentTransfer", true,  create_fe_component_transfer),
-        ("feComposite",         true,  create_fe_composite),
-        ("feConvolveMatrix",    true,  create_fe_convolve_matrix),
-        ("feDiffuseLighting",   true,  create_fe_diffuse_lighting),
-        ("feDisplacementMap",   true,  create_fe_displacement_map),
-        ("feDistantLight",      false, create_fe_distant_light),
-        ("feFuncA",             false, create_fe_func_a),
-        ("feFuncB",             false, create_fe_func_b),
-        ("feFuncG",             false, create_fe_func_g),
-        ("feFuncR",             false, create_fe_func_r),
-        ("feFlood",             true,  create_fe_flood),
-        ("feGaussianBlur",      true,  create_fe_gaussian_blur),
-        ("feImage",             true,  create_fe_image),
-        ("feMerge",             true,  create_fe_merge),
-        ("feMergeNode",         false, create_fe_merge_node),
-        ("feMorphology",        true,  create_fe_morphology),
-        ("feOffset",            true,  create_fe_offset),
-        ("fePointLight",        false, create_fe_point_light),
-        ("feSpecularLighting",  true,  create_fe_specular_lighting),
-        ("feSpotLight",         false, create_fe_spot_light),
-        ("feTile",              true,  create_fe_tile),
-        ("feTurbulence",        true,  create_fe_turbulence),
-        ("filter",              true,  create_filter),
         /* ("font",             true,  ), */
         /* ("font-face",        false, ), */
         /* ("font-face-format", false, ), */
@@ -182,12 +209,16 @@ static NODE_CREATORS: Lazy<HashMap<&'static str, (bool, NodeCreateFn)>> = Lazy::
         ("g",                   true,  create_group),
         /* ("glyph",            true,  ), */
         /* ("glyphRef",         true,  ), */
+        ("hatch",               true,  create_hatch),
+        ("hatchpath",           true,  create_hatch_path),
         /* ("hkern",            false, ), */
-        ("image",               true,  create_image),
         ("line",                true,  create_line),
         ("linearGradient",      true,  create_linear_gradient),
         ("marker",              true,  create_marker),
         ("mask",                true,  create_mask),
+        ("meshgradient",        true,  create_mesh_gradient),
+        ("meshpatch",           false, create_mesh_patch),
+        ("meshrow",             false, create_mesh_row),
         /* ("metadata",         false, ), */
         /* ("missing-glyph",    true,  ), */
         /* ("mpath",            false, ), */
@@ -199,7 +230,7 @@ static NODE_CREATORS: Lazy<HashMap<&'static str, (bool, NodeCreateFn)>> = Lazy::
         ("radialGradient",      true,  create_radial_gradient),
         ("rect",                true,  create_rect),
         /* ("script",           false, ), */
-        /* ("set",              false, ), */
+        ("set",                 false, create_set),
         ("stop",                true,  create_stop),
         ("style",               false, create_style),
         /* ("subImage",         false, create_sub_image), */
@@ -207,19 +238,73 @@ static NODE_CREATORS: Lazy<HashMap<&'static str, (bool, NodeCreateFn)>> = Lazy::
         ("svg",                 true,  create_svg),
         ("switch",              true,  create_switch),
         ("symbol",              true,  create_symbol),
-        ("text",                true,  create_text),
-        /* ("textPath",         true,  ), */
         /* ("title",            true,  ), */
-        ("tref",                true,  create_tref),
-        ("tspan",               true,  create_tspan),
         ("use",                 true,  create_use),
         /* ("view",             false, ), */
         /* ("vkern",            false, ), */
     ];
 
+    #[cfg(feature = "filters")]
+    {
+        let filter_creators: Vec<(&str, bool, NodeCreateFn)> = vec![
+            ("feBlend",             true,  create_fe_blend),
+            ("feColorMatrix",       true,  create_fe_color_matrix),
+            ("feComponentTransfer", true,  create_fe_component_transfer),
+            ("feComposite",         true,  create_fe_composite),
+            ("feConvolveMatrix",    true,  create_fe_convolve_matrix),
+            ("feDiffuseLighting",   true,  create_fe_diffuse_lighting),
+            ("feDisplacementMap",   true,  create_fe_displacement_map),
+            ("feDistantLight",      false, create_fe_distant_light),
+            ("feDropShadow",        true,  create_fe_drop_shadow),
+            ("feFuncA",             false, create_fe_func_a),
+            ("feFuncB",             false, create_fe_func_b),
+            ("feFuncG",             false, create_fe_func_g),
+            ("feFuncR",             false, create_fe_func_r),
+            ("feFlood",             true,  create_fe_flood),
+            ("feGaussianBlur",      true,  create_fe_gaussian_blur),
+            ("feImage",             true,  create_fe_image),
+            ("feMerge",             true,  create_fe_merge),
+            ("feMergeNode",         false, create_fe_merge_node),
+            ("feMorphology",        true,  create_fe_morphology),
+            ("feOffset",            true,  create_fe_offset),
+            ("fePointLight",        false, create_fe_point_light),
+            ("feSpecularLighting",  true,  create_fe_specular_lighting),
+            ("feSpotLight",         false, create_fe_spot_light),
+            ("feTile",              true,  create_fe_tile),
+            ("feTurbulence",        true,  create_fe_turbulence),
+            ("filter",              true,  create_filter),
+        ];
+        creators_table.extend(filter_creators);
+    }
+
+    #[cfg(feature = "raster-images")]
+    {
+        let image_creators: Vec<(&str, bool, NodeCreateFn)> = vec![
+            ("image",               true,  create_image),
+        ];
+        creators_table.extend(image_creators);
+    }
+
+    #[cfg(feature = "text")]
+    {
+        let text_creators: Vec<(&str, bool, NodeCreateFn)> = vec![
+            ("text",                true,  create_text),
+            ("textPath",            true,  create_text_path),
+            ("tref",                true,  create_tref),
+            ("tspan",               true,  create_tspan),
+        ];
+        creators_table.extend(text_creators);
+    }
+
     creators_table.into_iter().map(|(n, s, f)| (n, (s, f))).collect()
 });
 
+/// Whether librsvg knows about the element called `name`; other elements are created as
+/// non-rendering nodes.
+pub fn is_supported_element(name: &QualName) -> bool {
+    name.ns == ns!(svg) && NODE_CREATORS.contains_key(name.local.as_ref())
+}
+
 pub fn create_node(name: &QualName, pbag: &PropertyBag) -> RsvgNode {
     let mut id = None;
     let mut class = None;
@@ -237,13 +322,13 @@ pub fn create_node(name: &QualName, pbag: &PropertyBag) -> RsvgNode {
             // hack in the SVG namespace for supported element names
             Some(&(supports_class, create_fn)) => (supports_class, create_fn),
 
-            // Whenever we encounter a node we don't understand, represent it as a
-            // non-rendering node.  This is like a group, but it doesn't do any rendering of
+            // Whenever we encounter a node we don't understand, represent it as an
+            // unsupported node.  This is like a group, but it doesn't do any rendering of
             // children.  The effect is that we will ignore all children of unknown elements.
-            None => (true, create_non_rendering as NodeCreateFn),
+            None => (true, create_unsupported as NodeCreateFn),
         }
     } else {
-        (true, create_non_rendering as NodeCreateFn)
+        (true, create_unsupported as NodeCreateFn)
     };
 
     if !supports_class {
@@ -325,8 +410,10 @@ mod sizes {
         print_size!(Switch);
         print_size!(Symbol);
         print_size!(Text);
+        print_size!(TextPath);
         print_size!(TRef);
         print_size!(TSpan);
+        print_size!(Unsupported);
         print_size!(Use);
     }
 }
@@ -26,7 +26,11 @@
 //!
 //! `@import` is an **at-rule**.  This rule has a prelude, but no block.
 //! There are other at-rules like `@media` and some of them may have a block,
-//! but librsvg doesn't support those yet.
+//! but librsvg doesn't support most of those yet.  Besides `@import`, librsvg
+//! supports `@namespace`, which declares namespace prefixes for use in selectors,
+//! `@keyframes`, which defines CSS animations; see the `css_animation` module,
+//! `@media`, whose rules only apply if its media queries match; see the `media` module,
+//! and `@font-face`, which loads fonts for the document; see the `font_face` module.
 //!
 //! The prelude of the following rule is `foo, .bar`.
 //! It is a **selector list** with two **selectors**, one for
@@ -72,14 +76,23 @@
 //! `matches_selector()`.  This takes an opaque representation of an
 //! element, plus a selector, and returns a bool.  We iterate through
 //! the rules in the stylesheets and gather the matches; then sort the
-//! matches by specificity and apply the result to each element.
+//! matches by specificity and apply the result to each element.  The sort
+//! is stable, so matches with the same specificity keep their source order.
+//!
+//! The `selectors` crate handles the selector syntax by itself: compound
+//! selectors, combinators, attribute selectors, `:not()`, and the
+//! tree-structural pseudo-classes like `:first-child` and `:nth-child()`.
+//! We parse `:target` ourselves; it matches the element that is named by
+//! the fragment of the document's URL.  Pseudo-classes that depend on user
+//! interaction, like `:hover`, and pseudo-elements are not supported.
 
 use cssparser::*;
 use selectors::attr::{AttrSelectorOperation, CaseSensitivity, NamespaceConstraint};
 use selectors::matching::{ElementSelectorFlags, MatchingContext, MatchingMode, QuirksMode};
-use selectors::{self, OpaqueElement, SelectorImpl, SelectorList};
+use selectors::{self, NthIndexCache, OpaqueElement, SelectorImpl, SelectorList};
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
 use std::str;
 
@@ -87,11 +100,15 @@ use markup5ever::{namespace_url, ns, LocalName, Namespace, Prefix, QualName};
 use url::Url;
 
 use crate::allowed_url::AllowedUrl;
+use crate::chars::NodeChars;
+use crate::css_animation::{Keyframe, Keyframes};
 use crate::error::*;
+use crate::font_face::{FontFace, FontFaceRule};
 use crate::io::{self, BinaryData};
+use crate::media::{MediaFeatures, MediaQueryList};
 use crate::node::{NodeCascade, NodeType, RsvgNode};
-use crate::properties::{parse_property, ComputedValues, ParsedProperty};
-use crate::text::NodeChars;
+use crate::properties::{parse_property, ComputedValues, ParsedProperty, SpecifiedValue};
+use crate::property_macros::ToCssValue;
 
 /// A parsed CSS declaration
 ///
@@ -146,9 +163,15 @@ impl<'i> AtRuleParser<'i> for DeclParser {
     type Error = ValueErrorKind;
 }
 
-/// Dummy struct to implement cssparser::QualifiedRuleParser and
-/// cssparser::AtRuleParser
-pub struct RuleParser;
+/// Implements cssparser::QualifiedRuleParser and cssparser::AtRuleParser
+///
+/// It keeps track of the namespaces declared with `@namespace` rules, so that
+/// selectors like `foo|bar` can be resolved while parsing the rest of the stylesheet.
+#[derive(Clone, Default)]
+pub struct RuleParser {
+    default_namespace: Option<Namespace>,
+    namespaces: HashMap<Prefix, Namespace>,
+}
 
 /// Errors from the CSS parsing process
 pub enum CssParseErrorKind<'i> {
@@ -165,16 +188,28 @@ impl<'i> From<selectors::parser::SelectorParseErrorKind<'i>> for CssParseErrorKi
 pub struct QualifiedRule {
     selectors: SelectorList<Selector>,
     declarations: Vec<Declaration>,
+
+    // Source text of the declarations that could not be parsed, either
+    // because the property is not supported or because its value is invalid.
+    invalid_declarations: Vec<String>,
 }
 
 /// Prelude of at-rule used in the AtRuleParser.
 pub enum AtRulePrelude {
-    Import(String),
+    Import(String, MediaQueryList),
+    Namespace(Option<Prefix>, Namespace),
+    Keyframes(String),
+    Media(MediaQueryList),
+    FontFace,
 }
 
 /// A CSS at-rule (or ruleset)
 pub enum AtRule {
-    Import(String),
+    Import(String, MediaQueryList),
+    Namespace,
+    Keyframes(Keyframes),
+    Media(MediaQueryList, Vec<Rule>),
+    FontFace(FontFaceRule),
 }
 
 /// A CSS rule (or ruleset)
@@ -189,18 +224,32 @@ impl<'i> selectors::Parser<'i> for RuleParser {
     type Error = CssParseErrorKind<'i>;
 
     fn default_namespace(&self) -> Option<<Self::Impl as SelectorImpl>::NamespaceUrl> {
-        Some(ns!(svg))
+        // Unlike in HTML, type selectors without a namespace only match SVG elements
+        // unless the stylesheet declares a different default namespace.
+        Some(self.default_namespace.clone().unwrap_or(ns!(svg)))
     }
 
+    // Prefixes in selectors come from the stylesheet's `@namespace` rules, not from
+    // the XML namespace declarations in the document.
     fn namespace_for_prefix(
         &self,
-        _prefix: &<Self::Impl as SelectorImpl>::NamespacePrefix,
+        prefix: &<Self::Impl as SelectorImpl>::NamespacePrefix,
     ) -> Option<<Self::Impl as SelectorImpl>::NamespaceUrl> {
-        // FIXME: Do we need to keep a lookup table extracted from libxml2's
-        // XML namespaces?
-        //
-        // Or are CSS namespaces completely different, declared elsewhere?
-        None
+        self.namespaces.get(prefix).cloned()
+    }
+
+    fn parse_non_ts_pseudo_class(
+        &self,
+        location: SourceLocation,
+        name: CowRcStr<'i>,
+    ) -> Result<NonTSPseudoClass, cssparser::ParseError<'i, Self::Error>> {
+        if name.eq_ignore_ascii_case("target") {
+            Ok(NonTSPseudoClass::Target)
+        } else {
+            Err(location.new_custom_error(
+                selectors::parser::SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name),
+            ))
+        }
     }
 }
 
@@ -241,22 +290,33 @@ impl<'i> QualifiedRuleParser<'i> for RuleParser {
         _location: SourceLocation,
         input: &mut Parser<'i, 't>,
     ) -> Result<Self::QualifiedRule, cssparser::ParseError<'i, Self::Error>> {
-        let declarations = DeclarationListParser::new(input, DeclParser)
-            .filter_map(Result::ok) // ignore invalid property name or value
-            .collect();
+        let mut declarations = Vec::new();
+        let mut invalid_declarations = Vec::new();
+
+        for result in DeclarationListParser::new(input, DeclParser) {
+            match result {
+                Ok(decl) => declarations.push(decl),
+
+                // ignore invalid property name or value, but remember it for introspection
+                Err((_, source)) => invalid_declarations
+                    .push(source.trim().trim_end_matches(';').trim().to_string()),
+            }
+        }
 
         Ok(Rule::QualifiedRule(QualifiedRule {
             selectors: prelude,
             declarations,
+            invalid_declarations,
         }))
     }
 }
 
 // Required by `cssparser::RuleListParser`.
 //
-// This only handles the `@import` at-rule.
+// This only handles the `@import`, `@namespace`, `@keyframes`, `@media` and `@font-face`
+// at-rules.
 impl<'i> AtRuleParser<'i> for RuleParser {
-    type PreludeBlock = ();
+    type PreludeBlock = AtRulePrelude;
     type PreludeNoBlock = AtRulePrelude;
     type AtRule = Rule;
     type Error = CssParseErrorKind<'i>;
@@ -269,11 +329,45 @@ impl<'i> AtRuleParser<'i> for RuleParser {
     {
         match_ignore_ascii_case! { &name,
             "import" => {
-                // FIXME: at the moment we ignore media queries
                 let url = input.expect_url_or_string()?.as_ref().to_owned();
-                Ok(AtRuleType::WithoutBlock(AtRulePrelude::Import(url)))
+                let queries = MediaQueryList::parse_forgiving(input);
+                Ok(AtRuleType::WithoutBlock(AtRulePrelude::Import(url, queries)))
+            },
+
+            "namespace" => {
+                let prefix = input
+                    .try_parse(|p| p.expect_ident_cloned())
+                    .ok()
+                    .map(|p| Prefix::from(p.as_ref()));
+                let url = Namespace::from(input.expect_url_or_string()?.as_ref());
+                Ok(AtRuleType::WithoutBlock(AtRulePrelude::Namespace(prefix, url)))
             },
 
+            "keyframes" => {
+                let name = match input.try_parse(|p| p.expect_string_cloned()) {
+                    Ok(s) => s.as_ref().to_owned(),
+                    Err(_) => {
+                        let location = input.current_source_location();
+                        let ident = input.expect_ident_cloned()?;
+
+                        if ident.eq_ignore_ascii_case("none") {
+                            return Err(location.new_unexpected_token_error(Token::Ident(ident)));
+                        }
+
+                        ident.as_ref().to_owned()
+                    }
+                };
+
+                Ok(AtRuleType::WithBlock(AtRulePrelude::Keyframes(name)))
+            },
+
+            "media" => {
+                let queries = MediaQueryList::parse_forgiving(input);
+                Ok(AtRuleType::WithBlock(AtRulePrelude::Media(queries)))
+            },
+
+            "font-face" => Ok(AtRuleType::WithBlock(AtRulePrelude::FontFace)),
+
             _ => Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name))),
         }
     }
@@ -283,21 +377,152 @@ impl<'i> AtRuleParser<'i> for RuleParser {
         prelude: Self::PreludeNoBlock,
         _location: SourceLocation,
     ) -> Self::AtRule {
-        let AtRulePrelude::Import(url) = prelude;
-        Rule::AtRule(AtRule::Import(url))
+        match prelude {
+            AtRulePrelude::Import(url, queries) => Rule::AtRule(AtRule::Import(url, queries)),
+
+            AtRulePrelude::Namespace(prefix, url) => {
+                // Register the namespace right away, since it applies to the
+                // selectors in the rest of the stylesheet.
+                match prefix {
+                    Some(prefix) => {
+                        self.namespaces.insert(prefix, url);
+                    }
+
+                    None => self.default_namespace = Some(url),
+                }
+
+                Rule::AtRule(AtRule::Namespace)
+            }
+
+            AtRulePrelude::Keyframes(_) => unreachable!("@keyframes always has a block"),
+            AtRulePrelude::Media(_) => unreachable!("@media always has a block"),
+            AtRulePrelude::FontFace => unreachable!("@font-face always has a block"),
+        }
+    }
+
+    fn parse_block<'t>(
+        &mut self,
+        prelude: Self::PreludeBlock,
+        _location: SourceLocation,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::AtRule, ParseError<'i, Self::Error>> {
+        match prelude {
+            AtRulePrelude::Keyframes(name) => {
+                let frames = RuleListParser::new_for_nested_rule(input, KeyframeListParser)
+                    .filter_map(Result::ok) // ignore invalid keyframes
+                    .collect();
+
+                Ok(Rule::AtRule(AtRule::Keyframes(Keyframes { name, frames })))
+            }
+
+            AtRulePrelude::Media(queries) => {
+                // Nested rules see the namespaces declared so far
+                let rules = RuleListParser::new_for_nested_rule(input, self.clone())
+                    .filter_map(Result::ok) // ignore invalid rules
+                    .collect();
+
+                Ok(Rule::AtRule(AtRule::Media(queries, rules)))
+            }
+
+            AtRulePrelude::FontFace => FontFaceRule::parse_block(input)
+                .map(|face| Rule::AtRule(AtRule::FontFace(face)))
+                .ok_or_else(|| input.new_error(BasicParseErrorKind::AtRuleBodyInvalid)),
+
+            _ => unreachable!("only @keyframes, @media and @font-face have a block"),
+        }
+    }
+}
+
+/// Implements cssparser::QualifiedRuleParser for the keyframes inside `@keyframes`
+///
+/// The prelude of each keyframe is a list of offsets like `from, 50%`.
+struct KeyframeListParser;
+
+impl<'i> QualifiedRuleParser<'i> for KeyframeListParser {
+    type Prelude = Vec<f64>;
+    type QualifiedRule = Keyframe;
+    type Error = ValueErrorKind;
+
+    fn parse_prelude<'t>(
+        &mut self,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::Prelude, cssparser::ParseError<'i, Self::Error>> {
+        input.parse_comma_separated(|p| {
+            let location = p.current_source_location();
+
+            match *p.next()? {
+                Token::Ident(ref ident) if ident.eq_ignore_ascii_case("from") => Ok(0.0),
+                Token::Ident(ref ident) if ident.eq_ignore_ascii_case("to") => Ok(1.0),
+
+                Token::Percentage { unit_value, .. } if unit_value >= 0.0 && unit_value <= 1.0 => {
+                    Ok(f64::from(unit_value))
+                }
+
+                ref t => Err(location.new_unexpected_token_error(t.clone())),
+            }
+        })
+    }
+
+    fn parse_block<'t>(
+        &mut self,
+        prelude: Self::Prelude,
+        _location: SourceLocation,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::QualifiedRule, cssparser::ParseError<'i, Self::Error>> {
+        let mut timing_function = None;
+        let mut declarations = Vec::new();
+
+        for decl in DeclarationListParser::new(input, DeclParser).filter_map(Result::ok) {
+            // Declarations with !important and the animation properties are ignored in
+            // keyframes, except for the timing function between this keyframe and the next.
+            if decl.important {
+                continue;
+            }
+
+            match decl.property {
+                ParsedProperty::AnimationTimingFunction(SpecifiedValue::Specified(ref f)) => {
+                    timing_function = Some(*f.0.get(0));
+                }
+
+                _ if decl.prop_name.local.starts_with("animation")
+                    || decl.prop_name.local.starts_with("transition") => {}
+
+                _ => declarations.push(decl),
+            }
+        }
+
+        Ok(Keyframe {
+            offsets: prelude,
+            timing_function,
+            declarations,
+        })
     }
 }
 
-/// Dummy type required by the SelectorImpl trait.
+// Keyframes can't contain at-rules; we use the dummy implementations from cssparser.
+impl<'i> AtRuleParser<'i> for KeyframeListParser {
+    type PreludeBlock = ();
+    type PreludeNoBlock = ();
+    type AtRule = Keyframe;
+    type Error = ValueErrorKind;
+}
+
+/// Pseudo-classes that are not tree-structural, like `:target`
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct NonTSPseudoClass;
+pub enum NonTSPseudoClass {
+    /// The element whose `id` is the fragment of the document's URL, or which was
+    /// selected with `Document::set_target()`
+    Target,
+}
 
 impl ToCss for NonTSPseudoClass {
-    fn to_css<W>(&self, _dest: &mut W) -> fmt::Result
+    fn to_css<W>(&self, dest: &mut W) -> fmt::Result
     where
         W: fmt::Write,
     {
-        Ok(())
+        match *self {
+            NonTSPseudoClass::Target => dest.write_str(":target"),
+        }
     }
 }
 
@@ -335,7 +560,8 @@ impl selectors::parser::PseudoElement for PseudoElement {
 pub struct Selector;
 
 impl SelectorImpl for Selector {
-    type ExtraMatchingData = ();
+    /// The `id` of the element that matches `:target`, if any
+    type ExtraMatchingData = Option<String>;
     type AttrValue = String;
     type Identifier = LocalName;
     type ClassName = LocalName;
@@ -410,7 +636,7 @@ impl selectors::Element for RsvgElement {
                 return sibling.map(|n| n.into());
             }
 
-            sibling = self.0.previous_sibling();
+            sibling = sib.previous_sibling();
         }
 
         None
@@ -425,7 +651,7 @@ impl selectors::Element for RsvgElement {
                 return sibling.map(|n| n.into());
             }
 
-            sibling = self.0.next_sibling();
+            sibling = sib.next_sibling();
         }
 
         None
@@ -451,25 +677,47 @@ impl selectors::Element for RsvgElement {
 
     fn attr_matches(
         &self,
-        _ns: &NamespaceConstraint<&Namespace>,
-        _local_name: &LocalName,
-        _operation: &AttrSelectorOperation<&String>,
+        ns: &NamespaceConstraint<&Namespace>,
+        local_name: &LocalName,
+        operation: &AttrSelectorOperation<&String>,
     ) -> bool {
-        // unsupported
-        false
+        self.0
+            .borrow()
+            .get_attributes()
+            .iter()
+            .any(|(attr, value)| {
+                // Unprefixed attributes are stored with the element's namespace, but
+                // for CSS they are in no namespace.
+                let attr_ns = if attr.prefix.is_none() {
+                    ns!()
+                } else {
+                    attr.ns.clone()
+                };
+
+                let ns_matches = match *ns {
+                    NamespaceConstraint::Any => true,
+                    NamespaceConstraint::Specific(ns) => *ns == attr_ns,
+                };
+
+                attr.local == *local_name && ns_matches && operation.eval_str(value)
+            })
     }
 
     fn match_non_ts_pseudo_class<F>(
         &self,
-        _pc: &<Self::Impl as SelectorImpl>::NonTSPseudoClass,
-        _context: &mut MatchingContext<Self::Impl>,
+        pc: &<Self::Impl as SelectorImpl>::NonTSPseudoClass,
+        context: &mut MatchingContext<Self::Impl>,
         _flags_setter: &mut F,
     ) -> bool
     where
         F: FnMut(&Self, ElementSelectorFlags),
     {
-        // unsupported
-        false
+        match *pc {
+            NonTSPseudoClass::Target => match (&context.extra_data, self.0.borrow().get_id()) {
+                (Some(target), Some(id)) => target == id,
+                _ => false,
+            },
+        }
     }
 
     fn match_pseudo_element(
@@ -543,17 +791,76 @@ impl selectors::Element for RsvgElement {
 ///
 /// This is used when sorting selector matches according to their origin and specificity.
 #[allow(unused)]
-#[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Origin {
     UserAgent,
     User,
     Author,
 }
 
+/// What is needed to load the `@import` rules of a stylesheet
+struct ImportContext<'a> {
+    // Base URL of the document, which determines which files can be loaded
+    base_url: Option<&'a Url>,
+
+    media: &'a MediaFeatures,
+
+    // URLs of the stylesheets being loaded, to avoid import cycles
+    loading: Vec<AllowedUrl>,
+}
+
 /// A parsed CSS stylesheet
 pub struct Stylesheet {
     origin: Origin,
     qualified_rules: Vec<QualifiedRule>,
+    keyframes: Vec<Keyframes>,
+    font_faces: Vec<FontFace>,
+}
+
+/// Description of a qualified rule in a stylesheet, for introspection
+///
+/// This is a plain representation of what librsvg's CSS parser made out of a rule.
+/// Rules from stylesheets included with `@import` appear in place of the `@import`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleInfo {
+    /// Origin of the stylesheet that contains the rule
+    pub origin: Origin,
+
+    /// The selectors in the rule's prelude, in the order in which they appear
+    pub selectors: Vec<SelectorInfo>,
+
+    /// The declarations that librsvg parsed successfully
+    pub declarations: Vec<DeclarationInfo>,
+
+    /// Source text of the declarations that librsvg ignored, either because the
+    /// property is not supported or because its value could not be parsed
+    pub invalid_declarations: Vec<String>,
+}
+
+/// Description of a single selector in a rule, for introspection
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorInfo {
+    /// The selector serialized back to CSS
+    pub selector: String,
+
+    /// The selector's specificity, as used when sorting matches in the cascade
+    pub specificity: u32,
+
+    /// Whether the selector matches any element in the document
+    pub matches_any_element: bool,
+}
+
+/// Description of a parsed declaration, for introspection
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclarationInfo {
+    /// Name of the property, like `fill`
+    pub property: String,
+
+    /// The parsed value serialized back to CSS
+    pub value: String,
+
+    /// Whether the declaration has `!important`
+    pub important: bool,
 }
 
 /// A match during the selector matching process
@@ -600,16 +907,28 @@ impl Stylesheet {
         Stylesheet {
             origin,
             qualified_rules: Vec::new(),
+            keyframes: Vec::new(),
+            font_faces: Vec::new(),
         }
     }
 
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    /// The fonts from the stylesheet's `@font-face` rules
+    pub fn font_faces(&self) -> &[FontFace] {
+        &self.font_faces
+    }
+
     pub fn from_data(
         buf: &str,
         base_url: Option<&Url>,
         origin: Origin,
+        media: &MediaFeatures,
     ) -> Result<Self, LoadingError> {
         let mut stylesheet = Stylesheet::new(origin);
-        stylesheet.parse(buf, base_url)?;
+        stylesheet.parse(buf, base_url, media)?;
         Ok(stylesheet)
     }
 
@@ -617,38 +936,108 @@ impl Stylesheet {
         href: &str,
         base_url: Option<&Url>,
         origin: Origin,
+        media: &MediaFeatures,
     ) -> Result<Self, LoadingError> {
         let mut stylesheet = Stylesheet::new(origin);
-        stylesheet.load(href, base_url)?;
+        stylesheet.load(href, base_url, media)?;
         Ok(stylesheet)
     }
 
     /// Parses a CSS stylesheet from a string
     ///
     /// The `base_url` is required for `@import` rules, so that librsvg
-    /// can determine if the requested path is allowed.
-    fn parse(&mut self, buf: &str, base_url: Option<&Url>) -> Result<(), LoadingError> {
+    /// can determine if the requested path is allowed.  Rules in `@media` blocks
+    /// are only added if their media queries match the `media` features.
+    fn parse(
+        &mut self,
+        buf: &str,
+        base_url: Option<&Url>,
+        media: &MediaFeatures,
+    ) -> Result<(), LoadingError> {
+        let mut ctx = ImportContext {
+            base_url,
+            media,
+            loading: Vec::new(),
+        };
+
+        self.parse_with_imports(buf, base_url, &mut ctx)
+    }
+
+    /// Parses a stylesheet referenced by an URL
+    fn load(
+        &mut self,
+        href: &str,
+        base_url: Option<&Url>,
+        media: &MediaFeatures,
+    ) -> Result<(), LoadingError> {
+        let mut ctx = ImportContext {
+            base_url,
+            media,
+            loading: Vec::new(),
+        };
+
+        self.load_with_imports(href, base_url, &mut ctx)
+    }
+
+    /// Parses a stylesheet whose own URL is `stylesheet_url`, loading its `@import` rules.
+    ///
+    /// Imported URLs are resolved relative to the stylesheet that imports them, but
+    /// whether they can be loaded depends on the document's base URL, as for any
+    /// other external reference.
+    fn parse_with_imports(
+        &mut self,
+        buf: &str,
+        stylesheet_url: Option<&Url>,
+        ctx: &mut ImportContext<'_>,
+    ) -> Result<(), LoadingError> {
         let mut input = ParserInput::new(buf);
         let mut parser = Parser::new(&mut input);
 
-        RuleListParser::new_for_stylesheet(&mut parser, RuleParser)
+        // https://www.w3.org/TR/css-cascade-3/#at-import
+        //
+        // @import rules must precede all other rules; later ones are ignored.
+        let mut imports_allowed = true;
+
+        RuleListParser::new_for_stylesheet(&mut parser, RuleParser::default())
             .filter_map(Result::ok) // ignore invalid rules
             .for_each(|rule| match rule {
-                Rule::AtRule(AtRule::Import(url)) => {
-                    // ignore invalid imports
-                    let _ = self.load(&url, base_url);
+                Rule::AtRule(AtRule::Import(url, queries)) => {
+                    if !imports_allowed {
+                        rsvg_log!("(ignoring @import \"{}\" after other rules)", url);
+                    } else if queries.matches(ctx.media) {
+                        // ignore invalid imports
+                        let _ = self.load_with_imports(&url, stylesheet_url, ctx);
+                    }
+                }
+                rule => {
+                    imports_allowed = false;
+                    self.add_rule(rule, stylesheet_url, ctx);
                 }
-                Rule::QualifiedRule(qr) => self.qualified_rules.push(qr),
             });
 
         Ok(())
     }
 
-    /// Parses a stylesheet referenced by an URL
-    fn load(&mut self, href: &str, base_url: Option<&Url>) -> Result<(), LoadingError> {
-        let aurl = AllowedUrl::from_href(href, base_url).map_err(|_| LoadingError::BadUrl)?;
+    fn load_with_imports(
+        &mut self,
+        href: &str,
+        stylesheet_url: Option<&Url>,
+        ctx: &mut ImportContext<'_>,
+    ) -> Result<(), LoadingError> {
+        let url = Url::options()
+            .base_url(stylesheet_url)
+            .parse(href)
+            .map_err(|_| LoadingError::BadUrl)?;
+
+        let aurl =
+            AllowedUrl::from_href(url.as_str(), ctx.base_url).map_err(|_| LoadingError::BadUrl)?;
+
+        if ctx.loading.contains(&aurl) {
+            rsvg_log!("\"{}\" imports itself; ignoring", aurl);
+            return Err(LoadingError::BadCss);
+        }
 
-        io::acquire_data(&aurl, None)
+        let utf8 = io::acquire_data(&aurl, None)
             .and_then(|data| {
                 let BinaryData {
                     data: bytes,
@@ -670,8 +1059,37 @@ impl Stylesheet {
                     );
                     LoadingError::BadCss
                 })
-            })
-            .and_then(|utf8| self.parse(&utf8, base_url))
+            })?;
+
+        ctx.loading.push(aurl.clone());
+        let res = self.parse_with_imports(&utf8, Some(&aurl), ctx);
+        ctx.loading.pop();
+
+        res
+    }
+
+    /// Adds a rule other than `@import` to the stylesheet.
+    ///
+    /// The URLs in the rule are relative to the `stylesheet_url`.
+    fn add_rule(&mut self, rule: Rule, stylesheet_url: Option<&Url>, ctx: &ImportContext<'_>) {
+        match rule {
+            Rule::AtRule(AtRule::Import(url, _)) => {
+                rsvg_log!("(ignoring @import \"{}\" inside @media)", url);
+            }
+            Rule::AtRule(AtRule::Namespace) => (), // already handled by the RuleParser
+            Rule::AtRule(AtRule::Keyframes(k)) => self.keyframes.push(k),
+            Rule::AtRule(AtRule::Media(queries, rules)) => {
+                if queries.matches(ctx.media) {
+                    for rule in rules {
+                        self.add_rule(rule, stylesheet_url, ctx);
+                    }
+                }
+            }
+            Rule::AtRule(AtRule::FontFace(face)) => self
+                .font_faces
+                .push(face.resolve(stylesheet_url, ctx.base_url)),
+            Rule::QualifiedRule(qr) => self.qualified_rules.push(qr),
+        }
     }
 
     /// Appends the style declarations that match a specified node to a given vector
@@ -703,21 +1121,127 @@ impl Stylesheet {
             }
         }
     }
+
+    /// Describes the stylesheet's rules, checking the selectors against the tree in `root`
+    ///
+    /// The element with the `target` id, if any, matches `:target`.
+    pub fn get_rules_info(&self, root: &RsvgNode, target: Option<&str>) -> Vec<RuleInfo> {
+        let mut nth_index_cache = NthIndexCache::default();
+        let mut match_ctx = new_matching_context(Some(&mut nth_index_cache), target);
+
+        self.qualified_rules
+            .iter()
+            .map(|rule| RuleInfo {
+                origin: self.origin,
+
+                selectors: rule
+                    .selectors
+                    .0
+                    .iter()
+                    .map(|selector| SelectorInfo {
+                        selector: selector.to_css_string(),
+                        specificity: selector.specificity(),
+                        matches_any_element: root.descendants().any(|node| {
+                            selectors::matching::matches_selector(
+                                selector,
+                                0,
+                                None,
+                                &RsvgElement(node),
+                                &mut match_ctx,
+                                &mut |_, _| {},
+                            )
+                        }),
+                    })
+                    .collect(),
+
+                declarations: rule
+                    .declarations
+                    .iter()
+                    .map(|decl| DeclarationInfo {
+                        property: decl.prop_name.local.to_string(),
+                        value: decl.property.to_css_value(),
+                        important: decl.important,
+                    })
+                    .collect(),
+
+                invalid_declarations: rule.invalid_declarations.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A selector list that is matched against single elements, outside of a stylesheet
+pub struct ElementSelector(SelectorList<Selector>);
+
+impl ElementSelector {
+    /// Parses a selector list like `#foo, g > rect.bar`
+    pub fn parse(s: &str) -> Result<ElementSelector, SelectorError> {
+        let mut input = ParserInput::new(s);
+        let mut parser = Parser::new(&mut input);
+
+        SelectorList::parse(&RuleParser::default(), &mut parser)
+            .map(ElementSelector)
+            .map_err(|_| SelectorError(s.to_string()))
+    }
+
+    /// Returns whether any of the selectors in the list matches the `node`
+    pub fn matches(&self, node: &RsvgNode) -> bool {
+        let mut match_ctx = new_matching_context(None, None);
+
+        (self.0).0.iter().any(|selector| {
+            selectors::matching::matches_selector(
+                selector,
+                0,
+                None,
+                &RsvgElement(node.clone()),
+                &mut match_ctx,
+                &mut |_, _| {},
+            )
+        })
+    }
+}
+
+/// Creates a context for matching selectors
+///
+/// The `nth_index_cache` speeds up matching of selectors like `:nth-child()` when
+/// they get matched against many elements of the same tree.
+fn new_matching_context<'a>(
+    nth_index_cache: Option<&'a mut NthIndexCache>,
+    target: Option<&str>,
+) -> MatchingContext<'a, Selector> {
+    let mut match_ctx = MatchingContext::new(
+        MatchingMode::Normal,
+        // FIXME: how the fuck does one set up a bloom filter here?
+        None,
+        nth_index_cache,
+        QuirksMode::NoQuirks,
+    );
+
+    match_ctx.extra_data = target.map(String::from);
+    match_ctx
+}
+
+/// Looks up the `@keyframes` rule with the specified `name` in the stylesheets
+///
+/// If there is more than one rule with that name, the last one wins.
+pub fn find_keyframes<'a>(stylesheets: &'a [Stylesheet], name: &str) -> Option<&'a Keyframes> {
+    stylesheets
+        .iter()
+        .rev()
+        .flat_map(|stylesheet| stylesheet.keyframes.iter().rev())
+        .find(|keyframes| keyframes.name == name)
 }
 
 /// Runs the CSS cascade on the specified tree from all the stylesheets
-pub fn cascade(root: &mut RsvgNode, stylesheets: &[Stylesheet]) {
+///
+/// The element with the `target` id, if any, matches `:target`.
+pub fn cascade(root: &mut RsvgNode, stylesheets: &[Stylesheet], target: Option<&str>) {
+    let mut nth_index_cache = NthIndexCache::default();
+
     for mut node in root.descendants() {
         let mut matches = Vec::new();
 
-        let mut match_ctx = MatchingContext::new(
-            MatchingMode::Normal,
-            // FIXME: how the fuck does one set up a bloom filter here?
-            None,
-            // n_index_cache,
-            None,
-            QuirksMode::NoQuirks,
-        );
+        let mut match_ctx = new_matching_context(Some(&mut nth_index_cache), target);
 
         for stylesheet in stylesheets {
             stylesheet.get_matches(&node, &mut match_ctx, &mut matches);
@@ -0,0 +1,1041 @@
+//! CSS Animations and Transitions: `@keyframes`, and the `animation-*` and `transition-*`
+//! properties.
+//!
+//! https://www.w3.org/TR/css-animations-1/
+//!
+//! https://www.w3.org/TR/css-transitions-1/
+//!
+//! Just like for SMIL animations, we don't run a clock.  When the caller seeks the document
+//! to a specific time, `animation::seek()` calls `animated_properties()` for each element
+//! to compute the values of its CSS animations at that time, and they get stored in the
+//! element as overrides of its specified values.  All animations start at time zero.
+//!
+//! Transitions only run when the value of a property changes, which never happens in a
+//! static document; their properties are parsed, but they have no effect.
+
+use cssparser::{
+    self,
+    _cssparser_internal_to_lowercase,
+    match_ignore_ascii_case,
+    Parser,
+    ParserInput,
+};
+use std::f64;
+
+use crate::css::{find_keyframes, Declaration, Stylesheet};
+use crate::error::*;
+use crate::parsers::Parse;
+use crate::properties::{parse_property, ComputedValues, ParsedProperty};
+use crate::property_macros::ToCssValue;
+
+/// A comma-separated list of values, as used by the `animation-*` and `transition-*`
+/// properties.
+///
+/// The lists of the longhand properties are matched up by index to the list of
+/// `animation-name`; shorter lists repeat themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommaSeparated<T>(pub Vec<T>);
+
+impl<T> CommaSeparated<T> {
+    pub fn get(&self, index: usize) -> &T {
+        &self.0[index % self.0.len()]
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> CommaSeparated<U> {
+        CommaSeparated(self.0.iter().map(f).collect())
+    }
+}
+
+impl<T: Parse> Parse for CommaSeparated<T> {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<CommaSeparated<T>, ValueErrorKind> {
+        parser
+            .parse_comma_separated(|p| T::parse(p).map_err(|e| p.new_custom_error(e)))
+            .map(CommaSeparated)
+            .map_err(ValueErrorKind::from)
+    }
+}
+
+impl<T: ToCssValue> ToCssValue for CommaSeparated<T> {
+    fn to_css_value(&self) -> String {
+        self.0
+            .iter()
+            .map(ToCssValue::to_css_value)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A CSS `<time>` value, in seconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Time(pub f64);
+
+impl Parse for Time {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<Time, ValueErrorKind> {
+        match *parser.next()? {
+            cssparser::Token::Dimension {
+                value, ref unit, ..
+            } if unit.eq_ignore_ascii_case("s") => Ok(Time(f64::from(value))),
+
+            cssparser::Token::Dimension {
+                value, ref unit, ..
+            } if unit.eq_ignore_ascii_case("ms") => Ok(Time(f64::from(value) / 1000.0)),
+
+            _ => Err(ValueErrorKind::parse_error("expected time value")),
+        }
+    }
+}
+
+impl ToCssValue for Time {
+    fn to_css_value(&self) -> String {
+        format!("{}s", self.0)
+    }
+}
+
+/// Position of the jumps in a `steps()` timing function
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StepPosition {
+    JumpStart,
+    JumpEnd,
+    JumpNone,
+    JumpBoth,
+}
+
+/// An easing function, as used by `animation-timing-function`
+///
+/// https://www.w3.org/TR/css-easing-1/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimingFunction {
+    Linear,
+    CubicBezier(f64, f64, f64, f64),
+    Steps(u32, StepPosition),
+}
+
+const EASE: TimingFunction = TimingFunction::CubicBezier(0.25, 0.1, 0.25, 1.0);
+const EASE_IN: TimingFunction = TimingFunction::CubicBezier(0.42, 0.0, 1.0, 1.0);
+const EASE_OUT: TimingFunction = TimingFunction::CubicBezier(0.0, 0.0, 0.58, 1.0);
+const EASE_IN_OUT: TimingFunction = TimingFunction::CubicBezier(0.42, 0.0, 0.58, 1.0);
+
+impl Default for TimingFunction {
+    fn default() -> TimingFunction {
+        EASE
+    }
+}
+
+impl TimingFunction {
+    /// Computes the output progress for an input progress `t` in [0, 1].
+    pub fn evaluate(&self, t: f64) -> f64 {
+        match *self {
+            TimingFunction::Linear => t,
+
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => {
+                fn bezier(p1: f64, p2: f64, s: f64) -> f64 {
+                    let r = 1.0 - s;
+                    3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+                }
+
+                // The x coordinate is monotonic since x1 and x2 are in [0, 1], so
+                // we can just bisect to find the curve's parameter for t.
+                let (mut lo, mut hi) = (0.0, 1.0);
+
+                for _ in 0..64 {
+                    let mid = (lo + hi) / 2.0;
+
+                    if bezier(x1, x2, mid) < t {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+
+                bezier(y1, y2, (lo + hi) / 2.0)
+            }
+
+            TimingFunction::Steps(steps, position) => {
+                let steps = f64::from(steps);
+
+                let jumps = match position {
+                    StepPosition::JumpStart | StepPosition::JumpEnd => steps,
+                    StepPosition::JumpNone => steps - 1.0,
+                    StepPosition::JumpBoth => steps + 1.0,
+                };
+
+                let mut step = (t * steps).floor();
+
+                if position == StepPosition::JumpStart || position == StepPosition::JumpBoth {
+                    step += 1.0;
+                }
+
+                step.max(0.0).min(jumps) / jumps
+            }
+        }
+    }
+}
+
+impl Parse for TimingFunction {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<TimingFunction, ValueErrorKind> {
+        if let Ok(ident) = parser.try_parse(|p| p.expect_ident_cloned()) {
+            return match_ignore_ascii_case! { &ident,
+                "linear" => Ok(TimingFunction::Linear),
+                "ease" => Ok(EASE),
+                "ease-in" => Ok(EASE_IN),
+                "ease-out" => Ok(EASE_OUT),
+                "ease-in-out" => Ok(EASE_IN_OUT),
+                "step-start" => Ok(TimingFunction::Steps(1, StepPosition::JumpStart)),
+                "step-end" => Ok(TimingFunction::Steps(1, StepPosition::JumpEnd)),
+                _ => Err(ValueErrorKind::parse_error("invalid timing function")),
+            };
+        }
+
+        let name = parser.expect_function()?.clone();
+
+        parser
+            .parse_nested_block(|p| {
+                match_ignore_ascii_case! { &name,
+                    "cubic-bezier" => {
+                        let mut numbers = [0.0; 4];
+
+                        for (i, n) in numbers.iter_mut().enumerate() {
+                            if i > 0 {
+                                p.expect_comma()?;
+                            }
+
+                            *n = f64::from(p.expect_number()?);
+                        }
+
+                        let [x1, y1, x2, y2] = numbers;
+
+                        if x1 < 0.0 || x1 > 1.0 || x2 < 0.0 || x2 > 1.0 {
+                            return Err(p.new_custom_error(ValueErrorKind::value_error(
+                                "cubic-bezier() x values must be in [0, 1]",
+                            )));
+                        }
+
+                        Ok(TimingFunction::CubicBezier(x1, y1, x2, y2))
+                    },
+
+                    "steps" => {
+                        let steps = p.expect_integer()?;
+
+                        let position = if p.try_parse(|p| p.expect_comma()).is_ok() {
+                            let ident = p.expect_ident_cloned()?;
+
+                            match_ignore_ascii_case! { &ident,
+                                "jump-start" | "start" => StepPosition::JumpStart,
+                                "jump-end" | "end" => StepPosition::JumpEnd,
+                                "jump-none" => StepPosition::JumpNone,
+                                "jump-both" => StepPosition::JumpBoth,
+                                _ => return Err(p.new_custom_error(
+                                    ValueErrorKind::parse_error("invalid step position"),
+                                )),
+                            }
+                        } else {
+                            StepPosition::JumpEnd
+                        };
+
+                        let min_steps = if position == StepPosition::JumpNone { 2 } else { 1 };
+
+                        if steps < min_steps {
+                            return Err(p.new_custom_error(ValueErrorKind::value_error(
+                                "invalid number of steps",
+                            )));
+                        }
+
+                        Ok(TimingFunction::Steps(steps as u32, position))
+                    },
+
+                    _ => Err(p.new_custom_error(
+                        ValueErrorKind::parse_error("invalid timing function"),
+                    )),
+                }
+            })
+            .map_err(ValueErrorKind::from)
+    }
+}
+
+impl ToCssValue for TimingFunction {
+    fn to_css_value(&self) -> String {
+        match *self {
+            TimingFunction::Linear => "linear".to_string(),
+            f if f == EASE => "ease".to_string(),
+            f if f == EASE_IN => "ease-in".to_string(),
+            f if f == EASE_OUT => "ease-out".to_string(),
+            f if f == EASE_IN_OUT => "ease-in-out".to_string(),
+
+            TimingFunction::CubicBezier(x1, y1, x2, y2) => {
+                format!("cubic-bezier({}, {}, {}, {})", x1, y1, x2, y2)
+            }
+
+            TimingFunction::Steps(steps, position) => {
+                let position = match position {
+                    StepPosition::JumpStart => "start",
+                    StepPosition::JumpEnd => "end",
+                    StepPosition::JumpNone => "jump-none",
+                    StepPosition::JumpBoth => "jump-both",
+                };
+
+                format!("steps({}, {})", steps, position)
+            }
+        }
+    }
+}
+
+/// Generates an enum for a keyword value, with its `Parse` and `ToCssValue` impls.
+macro_rules! keyword_value {
+    ($(#[$attr:meta])* $name:ident, default: $default:ident, $($str:expr => $variant:ident,)+) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl Default for $name {
+            fn default() -> $name {
+                $name::$default
+            }
+        }
+
+        impl Parse for $name {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<$name, ValueErrorKind> {
+                let ident = parser.expect_ident_cloned()?;
+
+                match_ignore_ascii_case! { &ident,
+                    $($str => Ok($name::$variant),)+
+                    _ => Err(ValueErrorKind::parse_error("unexpected value")),
+                }
+            }
+        }
+
+        impl ToCssValue for $name {
+            fn to_css_value(&self) -> String {
+                match *self {
+                    $($name::$variant => $str,)+
+                }
+                .to_string()
+            }
+        }
+    };
+}
+
+keyword_value!(
+    /// A value of `animation-direction`
+    PlaybackDirection,
+    default: Normal,
+
+    "normal" => Normal,
+    "reverse" => Reverse,
+    "alternate" => Alternate,
+    "alternate-reverse" => AlternateReverse,
+);
+
+keyword_value!(
+    /// A value of `animation-fill-mode`
+    FillMode,
+    default: None,
+
+    "none" => None,
+    "forwards" => Forwards,
+    "backwards" => Backwards,
+    "both" => Both,
+);
+
+keyword_value!(
+    /// A value of `animation-play-state`
+    PlayState,
+    default: Running,
+
+    "running" => Running,
+    "paused" => Paused,
+);
+
+/// A value of `animation-iteration-count`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IterationCount {
+    Count(f64),
+    Infinite,
+}
+
+impl Default for IterationCount {
+    fn default() -> IterationCount {
+        IterationCount::Count(1.0)
+    }
+}
+
+impl Parse for IterationCount {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<IterationCount, ValueErrorKind> {
+        if parser
+            .try_parse(|p| p.expect_ident_matching("infinite"))
+            .is_ok()
+        {
+            return Ok(IterationCount::Infinite);
+        }
+
+        let count = f64::from(parser.expect_number()?);
+
+        if count < 0.0 {
+            return Err(ValueErrorKind::value_error(
+                "iteration count must not be negative",
+            ));
+        }
+
+        Ok(IterationCount::Count(count))
+    }
+}
+
+impl ToCssValue for IterationCount {
+    fn to_css_value(&self) -> String {
+        match *self {
+            IterationCount::Count(n) => n.to_css_value(),
+            IterationCount::Infinite => "infinite".to_string(),
+        }
+    }
+}
+
+/// A value of `animation-name`; `None` is for the `none` keyword.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KeyframesName(pub Option<String>);
+
+impl Parse for KeyframesName {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<KeyframesName, ValueErrorKind> {
+        if let Ok(s) = parser.try_parse(|p| p.expect_string_cloned()) {
+            return Ok(KeyframesName(Some(s.as_ref().to_string())));
+        }
+
+        let ident = parser.expect_ident_cloned()?;
+
+        if ident.eq_ignore_ascii_case("none") {
+            Ok(KeyframesName(None))
+        } else {
+            Ok(KeyframesName(Some(ident.as_ref().to_string())))
+        }
+    }
+}
+
+impl ToCssValue for KeyframesName {
+    fn to_css_value(&self) -> String {
+        match self.0 {
+            Some(ref name) => name.clone(),
+            None => "none".to_string(),
+        }
+    }
+}
+
+/// A value of `transition-property`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransitionTarget {
+    All,
+    None,
+    Property(String),
+}
+
+impl Default for TransitionTarget {
+    fn default() -> TransitionTarget {
+        TransitionTarget::All
+    }
+}
+
+impl Parse for TransitionTarget {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<TransitionTarget, ValueErrorKind> {
+        let ident = parser.expect_ident_cloned()?;
+
+        match_ignore_ascii_case! { &ident,
+            "all" => Ok(TransitionTarget::All),
+            "none" => Ok(TransitionTarget::None),
+            "initial" | "inherit" | "unset" | "default" => {
+                Err(ValueErrorKind::parse_error("invalid property name"))
+            },
+            _ => Ok(TransitionTarget::Property(ident.as_ref().to_string())),
+        }
+    }
+}
+
+impl ToCssValue for TransitionTarget {
+    fn to_css_value(&self) -> String {
+        match *self {
+            TransitionTarget::All => "all".to_string(),
+            TransitionTarget::None => "none".to_string(),
+            TransitionTarget::Property(ref name) => name.clone(),
+        }
+    }
+}
+
+/// One item of the `animation` shorthand property
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SingleAnimation {
+    pub duration: Time,
+    pub timing_function: TimingFunction,
+    pub delay: Time,
+    pub iteration_count: IterationCount,
+    pub direction: PlaybackDirection,
+    pub fill_mode: FillMode,
+    pub play_state: PlayState,
+    pub name: KeyframesName,
+}
+
+impl Default for Time {
+    fn default() -> Time {
+        Time(0.0)
+    }
+}
+
+/// Tries to parse a component of a shorthand into `slot`, unless it was already parsed.
+fn parse_component<T: Parse>(parser: &mut Parser<'_, '_>, slot: &mut Option<T>) -> bool {
+    if slot.is_some() {
+        return false;
+    }
+
+    *slot = parser.try_parse(T::parse).ok();
+    slot.is_some()
+}
+
+impl Parse for SingleAnimation {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<SingleAnimation, ValueErrorKind> {
+        let mut duration = None;
+        let mut delay = None;
+        let mut timing_function = None;
+        let mut iteration_count = None;
+        let mut direction = None;
+        let mut fill_mode = None;
+        let mut play_state = None;
+        let mut name = None;
+
+        // The components can be in any order; the first time is the duration and the
+        // second one is the delay.  Keywords take precedence over the animation's name.
+        while !parser.is_exhausted() {
+            let parsed = parse_component(parser, &mut duration)
+                || parse_component(parser, &mut delay)
+                || parse_component(parser, &mut timing_function)
+                || parse_component(parser, &mut iteration_count)
+                || parse_component(parser, &mut direction)
+                || parse_component(parser, &mut fill_mode)
+                || parse_component(parser, &mut play_state)
+                || parse_component(parser, &mut name);
+
+            if !parsed {
+                return Err(ValueErrorKind::parse_error("invalid animation"));
+            }
+        }
+
+        Ok(SingleAnimation {
+            duration: duration.unwrap_or_default(),
+            timing_function: timing_function.unwrap_or_default(),
+            delay: delay.unwrap_or_default(),
+            iteration_count: iteration_count.unwrap_or_default(),
+            direction: direction.unwrap_or_default(),
+            fill_mode: fill_mode.unwrap_or_default(),
+            play_state: play_state.unwrap_or_default(),
+            name: name.unwrap_or_default(),
+        })
+    }
+}
+
+impl ToCssValue for SingleAnimation {
+    fn to_css_value(&self) -> String {
+        [
+            self.duration.to_css_value(),
+            self.timing_function.to_css_value(),
+            self.delay.to_css_value(),
+            self.iteration_count.to_css_value(),
+            self.direction.to_css_value(),
+            self.fill_mode.to_css_value(),
+            self.play_state.to_css_value(),
+            self.name.to_css_value(),
+        ]
+        .join(" ")
+    }
+}
+
+/// One item of the `transition` shorthand property
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SingleTransition {
+    pub property: TransitionTarget,
+    pub duration: Time,
+    pub timing_function: TimingFunction,
+    pub delay: Time,
+}
+
+impl Parse for SingleTransition {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<SingleTransition, ValueErrorKind> {
+        let mut property = None;
+        let mut duration = None;
+        let mut delay = None;
+        let mut timing_function = None;
+
+        while !parser.is_exhausted() {
+            let parsed = parse_component(parser, &mut duration)
+                || parse_component(parser, &mut delay)
+                || parse_component(parser, &mut timing_function)
+                || parse_component(parser, &mut property);
+
+            if !parsed {
+                return Err(ValueErrorKind::parse_error("invalid transition"));
+            }
+        }
+
+        Ok(SingleTransition {
+            property: property.unwrap_or_default(),
+            duration: duration.unwrap_or_default(),
+            timing_function: timing_function.unwrap_or_default(),
+            delay: delay.unwrap_or_default(),
+        })
+    }
+}
+
+impl ToCssValue for SingleTransition {
+    fn to_css_value(&self) -> String {
+        [
+            self.property.to_css_value(),
+            self.duration.to_css_value(),
+            self.timing_function.to_css_value(),
+            self.delay.to_css_value(),
+        ]
+        .join(" ")
+    }
+}
+
+/// A keyframe rule inside `@keyframes`, like `from, 50% { fill: red; }`
+pub struct Keyframe {
+    /// Offsets in [0, 1] of the keyframe's selectors
+    pub offsets: Vec<f64>,
+
+    /// The `animation-timing-function` for the interval that starts at this keyframe
+    pub timing_function: Option<TimingFunction>,
+
+    pub declarations: Vec<Declaration>,
+}
+
+/// A `@keyframes` rule
+pub struct Keyframes {
+    pub name: String,
+    pub frames: Vec<Keyframe>,
+}
+
+impl Keyframes {
+    /// Computes the animated values of the properties in the keyframes at `progress`.
+    ///
+    /// The `values` of the element are used for the properties that don't have a
+    /// `0%` or `100%` keyframe.
+    fn values_at(
+        &self,
+        values: &ComputedValues,
+        progress: f64,
+        timing_function: &TimingFunction,
+        acc: &mut Vec<ParsedProperty>,
+    ) {
+        let mut names = Vec::new();
+
+        for decl in self.frames.iter().flat_map(|f| f.declarations.iter()) {
+            if !names.contains(&&decl.prop_name) {
+                names.push(&decl.prop_name);
+            }
+        }
+
+        for name in names {
+            let mut stops: Vec<(f64, String, Option<&TimingFunction>)> = Vec::new();
+
+            for frame in &self.frames {
+                if let Some(decl) = frame
+                    .declarations
+                    .iter()
+                    .rev()
+                    .find(|d| d.prop_name == *name)
+                {
+                    for &offset in &frame.offsets {
+                        // later keyframes with the same offset take precedence
+                        stops.retain(|s| s.0 != offset);
+                        stops.push((
+                            offset,
+                            decl.property.to_css_value(),
+                            frame.timing_function.as_ref(),
+                        ));
+                    }
+                }
+            }
+
+            stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            if let Some(base) = values.get_property_value(&name.local) {
+                if stops[0].0 > 0.0 {
+                    stops.insert(0, (0.0, base.clone(), None));
+                }
+
+                if stops[stops.len() - 1].0 < 1.0 {
+                    stops.push((1.0, base, None));
+                }
+            }
+
+            let value = value_at(&stops, progress, timing_function);
+
+            let mut input = ParserInput::new(&value);
+            let mut parser = Parser::new(&mut input);
+
+            match parse_property(name, &mut parser, true) {
+                Ok(prop) => acc.push(prop),
+
+                Err(e) => rsvg_log!(
+                    "(ignoring invalid animated value \"{}\" for \"{}\": {})",
+                    value,
+                    name.local,
+                    e
+                ),
+            }
+        }
+    }
+}
+
+/// Interpolates between the keyframe values in `stops`, which are sorted by offset.
+///
+/// Values which can't be interpolated flip from one to the other halfway.
+fn value_at(
+    stops: &[(f64, String, Option<&TimingFunction>)],
+    progress: f64,
+    timing_function: &TimingFunction,
+) -> String {
+    if progress <= stops[0].0 {
+        return stops[0].1.clone();
+    }
+
+    for w in stops.windows(2) {
+        let (ref from, ref to) = (&w[0], &w[1]);
+
+        if progress <= to.0 {
+            let t = (progress - from.0) / (to.0 - from.0);
+            let t = from.2.unwrap_or(timing_function).evaluate(t);
+
+            return crate::animation::interpolate(&from.1, &to.1, t).unwrap_or_else(|| {
+                if t < 0.5 {
+                    from.1.clone()
+                } else {
+                    to.1.clone()
+                }
+            });
+        }
+    }
+
+    stops[stops.len() - 1].1.clone()
+}
+
+/// Computes the directed progress in [0, 1] of an animation's current iteration at
+/// `time`, or `None` if the animation does not apply at that time.
+///
+/// https://www.w3.org/TR/web-animations-1/#core-animation-model-calculations
+fn iteration_progress(
+    duration: f64,
+    delay: f64,
+    iteration_count: IterationCount,
+    direction: PlaybackDirection,
+    fill_mode: FillMode,
+    time: f64,
+) -> Option<f64> {
+    let duration = duration.max(0.0);
+
+    let count = match iteration_count {
+        IterationCount::Count(n) => n,
+        IterationCount::Infinite => f64::INFINITY,
+    };
+
+    let active_duration = if duration == 0.0 {
+        0.0
+    } else {
+        duration * count
+    };
+
+    let local_time = time - delay;
+
+    let (iteration, progress) = if local_time < 0.0 {
+        match fill_mode {
+            FillMode::Backwards | FillMode::Both => (0.0, 0.0),
+            _ => return None,
+        }
+    } else if local_time >= active_duration {
+        match fill_mode {
+            FillMode::Forwards | FillMode::Both => {
+                // The end of the last iteration is 100% of it, not 0% of the next one
+                if count == 0.0 {
+                    (0.0, 0.0)
+                } else if count.is_infinite() {
+                    (0.0, 1.0)
+                } else {
+                    let iteration = count.ceil() - 1.0;
+                    (iteration, count - iteration)
+                }
+            }
+
+            _ => return None,
+        }
+    } else {
+        let overall = local_time / duration;
+        let iteration = overall.floor();
+        (iteration, overall - iteration)
+    };
+
+    let odd = iteration % 2.0 == 1.0;
+
+    let reversed = match direction {
+        PlaybackDirection::Normal => false,
+        PlaybackDirection::Reverse => true,
+        PlaybackDirection::Alternate => odd,
+        PlaybackDirection::AlternateReverse => !odd,
+    };
+
+    if reversed {
+        Some(1.0 - progress)
+    } else {
+        Some(progress)
+    }
+}
+
+/// Computes the animated values of an element's CSS animations at `time`, in seconds.
+///
+/// The `values` are the element's computed values without the effect of animations.
+/// Later animations in `animation-name` take precedence over earlier ones.
+pub fn animated_properties(
+    values: &ComputedValues,
+    stylesheets: &[Stylesheet],
+    time: f64,
+) -> Vec<ParsedProperty> {
+    let mut result = Vec::new();
+
+    for (i, name) in values.animation_name.0 .0.iter().enumerate() {
+        let keyframes = match name.0 {
+            Some(ref name) => match find_keyframes(stylesheets, name) {
+                Some(k) => k,
+                None => continue,
+            },
+
+            None => continue,
+        };
+
+        // A paused animation stays at the start of the timeline
+        let time = match *values.animation_play_state.0.get(i) {
+            PlayState::Running => time,
+            PlayState::Paused => 0.0,
+        };
+
+        let progress = iteration_progress(
+            values.animation_duration.0.get(i).0,
+            values.animation_delay.0.get(i).0,
+            *values.animation_iteration_count.0.get(i),
+            *values.animation_direction.0.get(i),
+            *values.animation_fill_mode.0.get(i),
+            time,
+        );
+
+        if let Some(progress) = progress {
+            keyframes.values_at(
+                values,
+                progress,
+                values.animation_timing_function.0.get(i),
+                &mut result,
+            );
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::{Origin, Stylesheet};
+    use crate::media::MediaFeatures;
+
+    #[test]
+    fn parses_time() {
+        assert_eq!(Time::parse_str("2s"), Ok(Time(2.0)));
+        assert_eq!(Time::parse_str("250ms"), Ok(Time(0.25)));
+        assert_eq!(Time::parse_str("-1S"), Ok(Time(-1.0)));
+        assert!(Time::parse_str("2").is_err());
+        assert!(Time::parse_str("2px").is_err());
+    }
+
+    #[test]
+    fn parses_timing_functions() {
+        assert_eq!(
+            TimingFunction::parse_str("linear"),
+            Ok(TimingFunction::Linear)
+        );
+        assert_eq!(TimingFunction::parse_str("ease-in"), Ok(EASE_IN));
+        assert_eq!(
+            TimingFunction::parse_str("cubic-bezier(0.1, -2, 0.9, 3)"),
+            Ok(TimingFunction::CubicBezier(0.1, -2.0, 0.9, 3.0))
+        );
+        assert_eq!(
+            TimingFunction::parse_str("steps(4)"),
+            Ok(TimingFunction::Steps(4, StepPosition::JumpEnd))
+        );
+        assert_eq!(
+            TimingFunction::parse_str("steps(3, jump-both)"),
+            Ok(TimingFunction::Steps(3, StepPosition::JumpBoth))
+        );
+        assert_eq!(
+            TimingFunction::parse_str("step-start"),
+            Ok(TimingFunction::Steps(1, StepPosition::JumpStart))
+        );
+
+        assert!(TimingFunction::parse_str("cubic-bezier(2, 0, 0, 1)").is_err());
+        assert!(TimingFunction::parse_str("steps(0)").is_err());
+        assert!(TimingFunction::parse_str("steps(1, jump-none)").is_err());
+        assert!(TimingFunction::parse_str("bounce").is_err());
+    }
+
+    #[test]
+    fn evaluates_timing_functions() {
+        assert_eq!(TimingFunction::Linear.evaluate(0.3), 0.3);
+
+        assert!((EASE_IN_OUT.evaluate(0.5) - 0.5).abs() < 1e-9);
+        assert!(EASE_IN.evaluate(0.25) < 0.25);
+        assert!(EASE_OUT.evaluate(0.25) > 0.25);
+        assert!(EASE.evaluate(0.0).abs() < 1e-9);
+        assert!((EASE.evaluate(1.0) - 1.0).abs() < 1e-9);
+
+        let steps = TimingFunction::Steps(4, StepPosition::JumpEnd);
+        assert_eq!(steps.evaluate(0.0), 0.0);
+        assert_eq!(steps.evaluate(0.3), 0.25);
+        assert_eq!(steps.evaluate(1.0), 1.0);
+
+        let steps = TimingFunction::Steps(4, StepPosition::JumpStart);
+        assert_eq!(steps.evaluate(0.0), 0.25);
+        assert_eq!(steps.evaluate(0.3), 0.5);
+
+        let steps = TimingFunction::Steps(3, StepPosition::JumpNone);
+        assert_eq!(steps.evaluate(0.0), 0.0);
+        assert_eq!(steps.evaluate(0.5), 0.5);
+        assert_eq!(steps.evaluate(1.0), 1.0);
+    }
+
+    #[test]
+    fn parses_animation_shorthand() {
+        let list =
+            CommaSeparated::<SingleAnimation>::parse_str("spin 1s linear infinite, fade 2s 500ms")
+                .unwrap();
+
+        assert_eq!(
+            list.0[0],
+            SingleAnimation {
+                duration: Time(1.0),
+                timing_function: TimingFunction::Linear,
+                iteration_count: IterationCount::Infinite,
+                name: KeyframesName(Some("spin".to_string())),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            list.0[1],
+            SingleAnimation {
+                duration: Time(2.0),
+                delay: Time(0.5),
+                name: KeyframesName(Some("fade".to_string())),
+                ..Default::default()
+            }
+        );
+
+        assert!(CommaSeparated::<SingleAnimation>::parse_str("spin 1s 2s 3s").is_err());
+    }
+
+    #[test]
+    fn parses_transition_shorthand() {
+        assert_eq!(
+            SingleTransition::parse_str("opacity 2s ease-in 1s"),
+            Ok(SingleTransition {
+                property: TransitionTarget::Property("opacity".to_string()),
+                duration: Time(2.0),
+                timing_function: EASE_IN,
+                delay: Time(1.0),
+            })
+        );
+    }
+
+    #[test]
+    fn computes_iteration_progress() {
+        use self::FillMode as F;
+        use self::PlaybackDirection as D;
+
+        let once = IterationCount::Count(1.0);
+        let twice = IterationCount::Count(2.0);
+
+        assert_eq!(
+            iteration_progress(2.0, 0.0, once, D::Normal, F::None, 0.5),
+            Some(0.25)
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, once, D::Reverse, F::None, 0.5),
+            Some(0.75)
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, twice, D::Normal, F::None, 2.5),
+            Some(0.25)
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, twice, D::Alternate, F::None, 2.5),
+            Some(0.75)
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, IterationCount::Infinite, D::Normal, F::None, 9.0),
+            Some(0.5)
+        );
+
+        // delays and fill modes
+        assert_eq!(
+            iteration_progress(2.0, 1.0, once, D::Normal, F::None, 0.5),
+            None
+        );
+        assert_eq!(
+            iteration_progress(2.0, 1.0, once, D::Normal, F::Backwards, 0.5),
+            Some(0.0)
+        );
+        assert_eq!(
+            iteration_progress(2.0, -1.0, once, D::Normal, F::None, 0.0),
+            Some(0.5)
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, once, D::Normal, F::None, 3.0),
+            None
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, once, D::Normal, F::Forwards, 3.0),
+            Some(1.0)
+        );
+        assert_eq!(
+            iteration_progress(2.0, 0.0, twice, D::Alternate, F::Both, 5.0),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn computes_keyframe_values() {
+        let stylesheet = Stylesheet::from_data(
+            "@keyframes pulse {
+               from { stroke-width: 2px; }
+               25%, 75% { stroke-width: 10px; opacity: 0.5; }
+               50% { opacity: 0; animation-timing-function: steps(1); }
+             }",
+            None,
+            Origin::Author,
+            &MediaFeatures::default(),
+        )
+        .unwrap();
+
+        let stylesheets = [stylesheet];
+        let keyframes = find_keyframes(&stylesheets, "pulse").unwrap();
+        let values = ComputedValues::default();
+
+        let at = |progress| {
+            let mut props = Vec::new();
+            keyframes.values_at(&values, progress, &TimingFunction::Linear, &mut props);
+            props
+                .iter()
+                .map(ToCssValue::to_css_value)
+                .collect::<Vec<_>>()
+        };
+
+        // the implicit 0% keyframe for opacity has the element's own value
+        assert_eq!(at(0.125), vec!["6px", "0.75"]);
+        assert_eq!(at(0.5), vec!["10px", "0"]);
+
+        // steps(1) holds the value of the 50% keyframe until 75%
+        assert_eq!(at(0.7), vec!["10px", "0"]);
+
+        assert_eq!(at(0.875), vec!["5.5px", "0.75"]);
+    }
+}
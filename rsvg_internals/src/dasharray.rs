@@ -3,6 +3,7 @@ use cssparser::Parser;
 use crate::error::*;
 use crate::length::*;
 use crate::parsers::{CssParserExt, Parse};
+use crate::property_macros::ToCssValue;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Dasharray {
@@ -42,6 +43,19 @@ impl Parse for Dasharray {
     }
 }
 
+impl ToCssValue for Dasharray {
+    fn to_css_value(&self) -> String {
+        match *self {
+            Dasharray::None => "none".to_string(),
+            Dasharray::Array(ref dashes) => dashes
+                .iter()
+                .map(ToCssValue::to_css_value)
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
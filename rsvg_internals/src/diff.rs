@@ -0,0 +1,249 @@
+//! Structural differences between two documents.
+//!
+//! This is for visual regression tools, to explain which elements changed when the
+//! rendered pixels of two versions of a document differ.
+//!
+//! Elements are matched between documents by their `id`.  Elements without an `id` are
+//! matched by their path in the tree, relative to their closest ancestor that has an
+//! `id`, so that inserting an element only affects the paths of its siblings.
+
+use markup5ever::QualName;
+use std::collections::{HashMap, HashSet};
+
+use crate::chars::NodeChars;
+use crate::node::{NodeType, RsvgNode};
+
+/// An element that appears in a [`DocumentDiff`]
+///
+/// [`DocumentDiff`]: struct.DocumentDiff.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffElement {
+    /// Name of the element, like `rect`
+    pub element_name: String,
+
+    /// The element's `id` attribute, if it has one
+    pub id: Option<String>,
+
+    /// Path to the element like `#layer1/g[1]/rect[2]`, relative to its closest ancestor
+    /// with an `id`, or like `/svg[1]/rect[1]` if there is no such ancestor.
+    ///
+    /// Indices count from 1 among the sibling elements with the same name.
+    pub path: String,
+}
+
+/// A change in the value of an attribute
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeChange {
+    /// Name of the attribute, like `fill` or `xlink:href`
+    pub name: String,
+
+    /// Value in the old document, or `None` if the attribute was added
+    pub old_value: Option<String>,
+
+    /// Value in the new document, or `None` if the attribute was removed
+    pub new_value: Option<String>,
+}
+
+/// An element that is in both documents, but with differences
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementChange {
+    /// The element as it is in the new document
+    pub element: DiffElement,
+
+    /// Path of the element in the old document; it differs from `element.path` if
+    /// an element with an `id` moved to another place in the tree.
+    pub old_path: String,
+
+    /// Attributes that were added, removed or changed, sorted by name
+    pub attributes: Vec<AttributeChange>,
+
+    /// Whether the text content of the element changed, ignoring differences in
+    /// whitespace; this only considers the element's own text, not that of its
+    /// child elements.
+    pub text_changed: bool,
+}
+
+/// Structural differences between two documents
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentDiff {
+    /// Elements that are only in the new document, in document order
+    pub added: Vec<DiffElement>,
+
+    /// Elements that are only in the old document, in document order
+    pub removed: Vec<DiffElement>,
+
+    /// Elements that are in both documents but differ, in the new document's order
+    pub changed: Vec<ElementChange>,
+}
+
+impl DocumentDiff {
+    /// Returns whether the documents have the same structure.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+struct Entry {
+    key: String,
+    element: DiffElement,
+    node: RsvgNode,
+}
+
+fn attribute_name(attr: &QualName) -> String {
+    match attr.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, attr.local),
+        None => attr.local.to_string(),
+    }
+}
+
+/// Appends the elements in the subtree of `node`, whose path is `path`, to `entries`.
+fn collect_elements(
+    node: &RsvgNode,
+    path: String,
+    keys: &mut HashSet<String>,
+    entries: &mut Vec<Entry>,
+) {
+    let id = node.borrow().get_id().map(str::to_string);
+
+    // Ids should be unique, but if they aren't, fall back to the path for the duplicates.
+    let key = match id {
+        Some(ref id) if !keys.contains(&format!("#{}", id)) => format!("#{}", id),
+        _ => path.clone(),
+    };
+
+    keys.insert(key.clone());
+
+    let children_prefix = match id {
+        Some(ref id) => format!("#{}", id),
+        None => path.clone(),
+    };
+
+    entries.push(Entry {
+        key,
+        element: DiffElement {
+            element_name: node.borrow().element_name().local.to_string(),
+            id,
+            path,
+        },
+        node: node.clone(),
+    });
+
+    let mut counts = HashMap::new();
+
+    for child in node
+        .children()
+        .filter(|c| c.borrow().get_type() != NodeType::Chars)
+    {
+        let name = child.borrow().element_name().local.to_string();
+        let count = counts.entry(name.clone()).or_insert(0);
+        *count += 1;
+
+        let child_path = format!("{}/{}[{}]", children_prefix, name, count);
+        collect_elements(&child, child_path, keys, entries);
+    }
+}
+
+fn elements(root: &RsvgNode) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let path = format!("/{}[1]", root.borrow().element_name().local);
+
+    collect_elements(root, path, &mut HashSet::new(), &mut entries);
+    entries
+}
+
+fn text_content(node: &RsvgNode) -> String {
+    node.children()
+        .filter(|c| c.borrow().get_type() == NodeType::Chars)
+        .map(|c| c.borrow().get_impl::<NodeChars>().get_string())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn attribute_changes(old: &RsvgNode, new: &RsvgNode) -> Vec<AttributeChange> {
+    let old_attributes: HashMap<_, _> = old
+        .borrow()
+        .get_attributes()
+        .iter()
+        .map(|(attr, value)| (attribute_name(attr), value.clone()))
+        .collect();
+
+    let new_attributes: HashMap<_, _> = new
+        .borrow()
+        .get_attributes()
+        .iter()
+        .map(|(attr, value)| (attribute_name(attr), value.clone()))
+        .collect();
+
+    let mut names: Vec<_> = old_attributes.keys().chain(new_attributes.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old_value = old_attributes.get(name);
+            let new_value = new_attributes.get(name);
+
+            if old_value == new_value {
+                None
+            } else {
+                Some(AttributeChange {
+                    name: name.clone(),
+                    old_value: old_value.cloned(),
+                    new_value: new_value.cloned(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Computes the structural differences from the tree at `old` to the tree at `new`.
+pub fn diff_trees(old: &RsvgNode, new: &RsvgNode) -> DocumentDiff {
+    let old_elements = elements(old);
+    let new_elements = elements(new);
+
+    let old_by_key: HashMap<_, _> = old_elements.iter().map(|e| (&e.key, e)).collect();
+    let new_by_key: HashMap<_, _> = new_elements.iter().map(|e| (&e.key, e)).collect();
+
+    // An element with the same id but a different name is a different element
+    let same_element = |a: &Entry, b: &Entry| a.element.element_name == b.element.element_name;
+
+    let mut diff = DocumentDiff::default();
+
+    for old_entry in &old_elements {
+        match new_by_key.get(&old_entry.key) {
+            Some(new_entry) if same_element(old_entry, new_entry) => (),
+            _ => diff.removed.push(old_entry.element.clone()),
+        }
+    }
+
+    for new_entry in &new_elements {
+        let old_entry = match old_by_key.get(&new_entry.key) {
+            Some(old_entry) if same_element(old_entry, new_entry) => old_entry,
+
+            _ => {
+                diff.added.push(new_entry.element.clone());
+                continue;
+            }
+        };
+
+        let attributes = attribute_changes(&old_entry.node, &new_entry.node);
+        let text_changed = text_content(&old_entry.node) != text_content(&new_entry.node);
+
+        if !attributes.is_empty()
+            || text_changed
+            || old_entry.element.path != new_entry.element.path
+        {
+            diff.changed.push(ElementChange {
+                element: new_entry.element.clone(),
+                old_path: old_entry.element.path.clone(),
+                attributes,
+                text_changed,
+            });
+        }
+    }
+
+    diff
+}
@@ -1,24 +1,79 @@
+#[cfg(feature = "raster-images")]
 use gdk_pixbuf::{PixbufLoader, PixbufLoaderExt};
 use gio;
 use markup5ever::{LocalName, Namespace, QualName};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::rc::Rc;
+use url::Url;
 
 use crate::allowed_url::{AllowedUrl, AllowedUrlError, Fragment};
+use crate::animation;
+use crate::chars::NodeChars;
+use crate::complexity::{compute_complexity, ComplexityReport};
 use crate::create_node::create_node;
-use crate::css::{cascade, Origin, Stylesheet};
+use crate::css::{cascade, Origin, RuleInfo, Stylesheet};
 use crate::error::LoadingError;
+#[cfg(feature = "filters")]
+use crate::filters::cache::FilterCache;
+#[cfg(feature = "text")]
+use crate::font_face::{ApplicationFonts, DocumentFonts};
+#[cfg(feature = "raster-images")]
+use crate::handle::ImageResolver;
 use crate::handle::LoadOptions;
-use crate::io::{self, BinaryData};
-use crate::node::{NodeData, NodeType, RsvgNode};
+use crate::io;
+#[cfg(feature = "raster-images")]
+use crate::io::BinaryData;
+use crate::media::{ColorScheme, MediaFeatures, MediaQueryList};
+use crate::node::{CascadedValues, NodeData, NodeType, RsvgNode};
+use crate::paint_server::PaintServerCache;
 use crate::property_bag::PropertyBag;
+use crate::shapes::Path;
 use crate::structure::{IntrinsicDimensions, Svg};
-use crate::surface_utils::shared_surface::SharedImageSurface;
-use crate::text::NodeChars;
+#[cfg(feature = "raster-images")]
+use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
 use crate::xml::xml_load_from_possibly_compressed_stream;
 
+/// Where a stylesheet came from
+#[derive(Clone)]
+enum StylesheetSource {
+    /// CSS text with its origin, from a `<style>` element with its `media` queries, or
+    /// from the user stylesheet
+    Data(String, Origin, Option<MediaQueryList>),
+
+    /// The `href` of an `xml-stylesheet` processing instruction
+    Href(String),
+}
+
+impl StylesheetSource {
+    fn origin(&self) -> Origin {
+        match *self {
+            StylesheetSource::Data(_, origin, _) => origin,
+            StylesheetSource::Href(_) => Origin::Author,
+        }
+    }
+
+    /// Parses the stylesheet, or returns `None` if its `media` queries don't match.
+    fn load(
+        &self,
+        base_url: Option<&Url>,
+        media: &MediaFeatures,
+    ) -> Result<Option<Stylesheet>, LoadingError> {
+        match *self {
+            StylesheetSource::Data(_, _, Some(ref queries)) if !queries.matches(media) => Ok(None),
+
+            StylesheetSource::Data(ref text, origin, _) => {
+                Stylesheet::from_data(text, base_url, origin, media).map(Some)
+            }
+
+            StylesheetSource::Href(ref href) => {
+                Stylesheet::from_href(href, base_url, Origin::Author, media).map(Some)
+            }
+        }
+    }
+}
+
 /// A loaded SVG file and its derived data
 ///
 /// This contains the tree of nodes (SVG elements), the mapping
@@ -28,14 +83,48 @@ pub struct Document {
 
     ids: HashMap<String, RsvgNode>,
 
+    // Kept after the cascade so that callers can introspect them, and so that
+    // the cascade can be run again when the user stylesheet changes
+    stylesheets: RefCell<Vec<Stylesheet>>,
+
+    // Where the stylesheets came from, to parse them again when the media features change
+    stylesheet_sources: RefCell<Vec<StylesheetSource>>,
+
+    // Environment for media queries; starts as the one in the load_options
+    media_features: Cell<MediaFeatures>,
+
+    // Time to which the animations are seeked, or None for the static document
+    time: Cell<Option<f64>>,
+
+    // Id of the element that matches :target
+    target: RefCell<Option<String>>,
+
+    // Overrides the seed attribute of all feTurbulence elements
+    turbulence_seed: Cell<Option<i32>>,
+
     // These require interior mutability because we load the extern
     // resources all over the place.  Eventually we'll be able to do this
     // once, at loading time, and keep this immutable.
     externs: RefCell<Resources>,
+    #[cfg(feature = "raster-images")]
     images: RefCell<Images>,
 
+    // Results of filters from previous renders
+    #[cfg(feature = "filters")]
+    filter_cache: RefCell<FilterCache>,
+
+    // Paint servers whose chains of fallbacks were already resolved
+    paint_server_cache: RefCell<PaintServerCache>,
+
+    // Fonts from the stylesheets' @font-face rules, loaded the first time text is drawn
+    #[cfg(feature = "text")]
+    fonts: RefCell<Option<DocumentFonts>>,
+
     // Once we do not need to load externs, we can drop this as well
     load_options: LoadOptions,
+
+    // Statistics about the tree, computed at load time
+    complexity: ComplexityReport,
 }
 
 impl Document {
@@ -67,11 +156,243 @@ impl Document {
         }
     }
 
+    pub fn complexity(&self) -> &ComplexityReport {
+        &self.complexity
+    }
+
+    pub fn load_options(&self) -> &LoadOptions {
+        &self.load_options
+    }
+
+    /// Returns a font map with the fonts from the `@font-face` rules in the stylesheets
+    /// and the application's fonts, or `None` to use the system's fonts.
+    #[cfg(feature = "text")]
+    pub fn font_map(&self) -> Option<pango::FontMap> {
+        let mut fonts = self.fonts.borrow_mut();
+        let app_fonts = self.load_options.fonts.as_ref();
+
+        if fonts.is_none() {
+            let stylesheets = self.stylesheets.borrow();
+            let faces: Vec<_> = stylesheets.iter().flat_map(|s| s.font_faces()).collect();
+
+            *fonts = Some(DocumentFonts::load(&faces, app_fonts));
+        }
+
+        fonts
+            .as_ref()
+            .and_then(DocumentFonts::font_map)
+            .or_else(|| app_fonts.map(ApplicationFonts::font_map))
+            .cloned()
+    }
+
+    /// Whether one of the `@font-face` rules in the stylesheets defines the `family`
+    #[cfg(feature = "text")]
+    pub fn has_font_face(&self, family: &str) -> bool {
+        self.stylesheets
+            .borrow()
+            .iter()
+            .flat_map(|s| s.font_faces())
+            .any(|face| face.family.eq_ignore_ascii_case(family))
+    }
+
     pub fn lookup_node_by_id(&self, id: &str) -> Option<RsvgNode> {
         self.ids.get(id).map(|n| (*n).clone())
     }
 
+    /// Describes the rules from all the document's stylesheets, in cascade order
+    pub fn get_stylesheet_rules(&self) -> Vec<RuleInfo> {
+        let target = self.target.borrow();
+
+        self.stylesheets
+            .borrow()
+            .iter()
+            .flat_map(|stylesheet| {
+                stylesheet.get_rules_info(&self.tree, target.as_ref().map(String::as_str))
+            })
+            .collect()
+    }
+
+    /// Seeks the document's animations to `time`, in seconds.
+    ///
+    /// Passing `None` renders the document without animations, as it is by default.
+    pub fn set_time(&self, time: Option<f64>) {
+        if self.time.get() != time {
+            self.time.set(time);
+            animation::seek(&self.tree, &self.ids, &self.stylesheets.borrow(), time);
+            self.invalidate_caches();
+        }
+    }
+
+    /// Replaces the user stylesheet with the CSS in `css`, and runs the cascade again.
+    ///
+    /// The user stylesheet has the "user" origin, so its rules apply before the
+    /// document's own styles, except for `!important` declarations.
+    pub fn set_user_stylesheet(&self, css: &str) -> Result<(), LoadingError> {
+        let source = StylesheetSource::Data(css.to_string(), Origin::User, None);
+
+        let stylesheet = source.load(
+            self.load_options.base_url.as_ref(),
+            &self.media_features.get(),
+        )?;
+
+        {
+            let mut sources = self.stylesheet_sources.borrow_mut();
+            sources.retain(|s| s.origin() != Origin::User);
+            sources.insert(0, source);
+        }
+
+        {
+            let mut stylesheets = self.stylesheets.borrow_mut();
+            stylesheets.retain(|s| s.origin() != Origin::User);
+
+            if let Some(stylesheet) = stylesheet {
+                stylesheets.insert(0, stylesheet);
+            }
+        }
+
+        self.forget_fonts();
+        self.restyle();
+
+        Ok(())
+    }
+
+    /// Returns the environment against which media queries are evaluated.
+    pub fn media_features(&self) -> MediaFeatures {
+        self.media_features.get()
+    }
+
+    /// Changes the value of the `prefers-color-scheme` media feature, and runs the
+    /// cascade again.
+    ///
+    /// All the stylesheets, including the user stylesheet, are parsed again so that
+    /// their `@media` rules are evaluated for the new `color_scheme`.
+    pub fn set_color_scheme(&self, color_scheme: ColorScheme) {
+        let mut features = self.media_features.get();
+
+        if features.color_scheme != color_scheme {
+            features.color_scheme = color_scheme;
+            self.media_features.set(features);
+            self.reload_stylesheets();
+        }
+    }
+
+    /// Parses the stylesheets again from their sources, and runs the cascade again.
+    fn reload_stylesheets(&self) {
+        let base_url = self.load_options.base_url.as_ref();
+        let media = self.media_features.get();
+
+        // FIXME: handle CSS errors
+        let stylesheets = self
+            .stylesheet_sources
+            .borrow()
+            .iter()
+            .filter_map(|source| source.load(base_url, &media).ok().and_then(|x| x))
+            .collect();
+
+        *self.stylesheets.borrow_mut() = stylesheets;
+
+        self.forget_fonts();
+        self.restyle();
+    }
+
+    /// Drops the fonts from `@font-face` rules, so they get loaded again from the
+    /// new stylesheets.
+    fn forget_fonts(&self) {
+        #[cfg(feature = "text")]
+        self.fonts.borrow_mut().take();
+    }
+
+    /// Returns the id of the element that matches the `:target` pseudo-class, if any.
+    pub fn get_target(&self) -> Option<String> {
+        self.target.borrow().clone()
+    }
+
+    /// Makes the element with the `target` id match the `:target` pseudo-class, and runs
+    /// the cascade again.
+    ///
+    /// Initially, this is the fragment of the document's base URL.
+    pub fn set_target(&self, target: Option<&str>) {
+        if self.target.borrow().as_ref().map(String::as_str) != target {
+            *self.target.borrow_mut() = target.map(String::from);
+            self.restyle();
+        }
+    }
+
+    /// Runs the CSS cascade again, from the presentation attributes of the elements.
+    fn restyle(&self) {
+        let stylesheets = self.stylesheets.borrow();
+        let target = self.target.borrow();
+        let mut root = self.tree.clone();
+
+        for mut node in root.descendants() {
+            node.borrow_mut().reset_specified_values();
+        }
+
+        cascade(&mut root, &stylesheets, target.as_ref().map(String::as_str));
+
+        // Animations depend on the base values and on the stylesheets' @keyframes
+        if let Some(time) = self.time.get() {
+            animation::seek(&self.tree, &self.ids, &stylesheets, Some(time));
+        }
+
+        self.invalidate_caches();
+    }
+
+    /// Drops the results cached from previous renders; call this after changing the styles.
+    pub fn invalidate_caches(&self) {
+        #[cfg(feature = "filters")]
+        self.filter_cache.borrow_mut().clear();
+
+        self.paint_server_cache.borrow_mut().clear();
+    }
+
+    /// Drops the results cached from previous renders that depend on elements marked with
+    /// `set_will_change()`; this is enough after changing only those elements.
+    pub fn invalidate_will_change_caches(&self) {
+        #[cfg(feature = "filters")]
+        self.filter_cache.borrow_mut().clear_will_change();
+
+        // Resolving paint servers is cheap; don't bother finding out which ones
+        // depend on the changed elements.
+        self.paint_server_cache.borrow_mut().clear();
+    }
+
+    #[cfg(feature = "filters")]
+    pub fn filter_cache(&self) -> RefMut<'_, FilterCache> {
+        self.filter_cache.borrow_mut()
+    }
+
+    pub fn paint_server_cache(&self) -> RefMut<'_, PaintServerCache> {
+        self.paint_server_cache.borrow_mut()
+    }
+
+    /// Returns the time to which the document's animations are seeked, if any.
+    pub fn get_time(&self) -> Option<f64> {
+        self.time.get()
+    }
+
+    /// Makes all `feTurbulence` elements use `seed` instead of their `seed` attribute, or
+    /// restores the attributes with `None`.
+    pub fn set_turbulence_seed(&self, seed: Option<i32>) {
+        if self.turbulence_seed.get() != seed {
+            self.turbulence_seed.set(seed);
+            self.invalidate_caches();
+        }
+    }
+
+    /// Returns the seed that overrides the `seed` attribute of `feTurbulence` elements, if any.
+    pub fn get_turbulence_seed(&self) -> Option<i32> {
+        self.turbulence_seed.get()
+    }
+
+    #[cfg(feature = "raster-images")]
     pub fn lookup_image(&self, href: &str) -> Result<SharedImageSurface, LoadingError> {
+        if let Some(ref resolver) = self.load_options.image_resolver {
+            if let Some(res) = self.images.borrow_mut().resolve(resolver, href) {
+                return res;
+            }
+        }
+
         let aurl = AllowedUrl::from_href(href, self.load_options.base_url.as_ref())
             .map_err(|_| LoadingError::BadUrl)?;
 
@@ -83,7 +404,11 @@ impl Document {
         let node_data = root.borrow();
 
         assert!(node_data.get_type() == NodeType::Svg);
-        node_data.get_impl::<Svg>().get_intrinsic_dimensions()
+
+        let cascaded = CascadedValues::new_from_node(&root);
+        node_data
+            .get_impl::<Svg>()
+            .get_intrinsic_dimensions(cascaded.get())
     }
 }
 
@@ -143,17 +468,35 @@ impl Resources {
     }
 }
 
+#[cfg(feature = "raster-images")]
 struct Images {
     images: HashMap<AllowedUrl, Result<SharedImageSurface, LoadingError>>,
+
+    // Results from the application's image resolver, keyed by href; `None` means
+    // that the application did not provide an image for that href.
+    resolved: HashMap<String, Option<Result<SharedImageSurface, LoadingError>>>,
 }
 
+#[cfg(feature = "raster-images")]
 impl Images {
     fn new() -> Images {
         Images {
             images: Default::default(),
+            resolved: Default::default(),
         }
     }
 
+    fn resolve(
+        &mut self,
+        resolver: &ImageResolver,
+        href: &str,
+    ) -> Option<Result<SharedImageSurface, LoadingError>> {
+        self.resolved
+            .entry(href.to_string())
+            .or_insert_with(|| resolver(href).map(|surface| copy_resolved_image(&surface)))
+            .clone()
+    }
+
     fn lookup(
         &mut self,
         load_options: &LoadOptions,
@@ -170,6 +513,31 @@ impl Images {
     }
 }
 
+/// Copies an application-supplied image into a surface that we can share.
+///
+/// The application may keep references to the surface it gave us, and it may be in
+/// any format, so we can't use it directly.
+#[cfg(feature = "raster-images")]
+fn copy_resolved_image(surface: &cairo::ImageSurface) -> Result<SharedImageSurface, LoadingError> {
+    let width = surface.get_width();
+    let height = surface.get_height();
+
+    if width == 0 || height == 0 {
+        return Err(LoadingError::EmptyData);
+    }
+
+    let copy = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+    {
+        let cr = cairo::Context::new(&copy);
+        cr.set_source_surface(surface, 0.0, 0.0);
+        cr.paint();
+    }
+
+    Ok(SharedImageSurface::new(copy, SurfaceType::SRgb)?)
+}
+
+#[cfg(feature = "raster-images")]
 fn load_image(
     load_options: &LoadOptions,
     aurl: &AllowedUrl,
@@ -211,6 +579,7 @@ pub struct DocumentBuilder {
     tree: Option<RsvgNode>,
     ids: HashMap<String, RsvgNode>,
     stylesheets: Vec<Stylesheet>,
+    stylesheet_sources: Vec<StylesheetSource>,
 }
 
 impl DocumentBuilder {
@@ -220,6 +589,7 @@ impl DocumentBuilder {
             tree: None,
             ids: HashMap::new(),
             stylesheets: Vec::new(),
+            stylesheet_sources: Vec::new(),
         }
     }
 
@@ -235,12 +605,7 @@ impl DocumentBuilder {
             return Err(LoadingError::BadStylesheet);
         }
 
-        // FIXME: handle CSS errors
-        if let Ok(stylesheet) =
-            Stylesheet::from_href(href, self.load_options.base_url.as_ref(), Origin::Author)
-        {
-            self.stylesheets.push(stylesheet);
-        }
+        self.add_stylesheet(StylesheetSource::Href(href.to_string()));
 
         Ok(())
     }
@@ -253,6 +618,12 @@ impl DocumentBuilder {
     ) -> RsvgNode {
         let mut node = create_node(name, pbag);
 
+        if node.borrow().get_type() == NodeType::Path {
+            node.borrow_mut()
+                .get_impl_mut::<Path>()
+                .set_path_data_recovery(self.load_options.path_data_recovery);
+        }
+
         if let Some(id) = node.borrow().get_id() {
             // This is so we don't overwrite an existing id
             self.ids
@@ -274,13 +645,26 @@ impl DocumentBuilder {
         node
     }
 
-    pub fn append_stylesheet_from_text(&mut self, text: &str) {
+    /// Adds the stylesheet from a `<style>` element, if its `media` queries match.
+    pub fn append_stylesheet_from_text(&mut self, text: &str, media: Option<&MediaQueryList>) {
+        self.add_stylesheet(StylesheetSource::Data(
+            text.to_string(),
+            Origin::Author,
+            media.cloned(),
+        ));
+    }
+
+    /// Parses a stylesheet, and remembers its source in case the media features change.
+    fn add_stylesheet(&mut self, source: StylesheetSource) {
         // FIXME: handle CSS errors
-        if let Ok(stylesheet) =
-            Stylesheet::from_data(text, self.load_options.base_url.as_ref(), Origin::Author)
-        {
+        if let Ok(Some(stylesheet)) = source.load(
+            self.load_options.base_url.as_ref(),
+            &self.load_options.media_features,
+        ) {
             self.stylesheets.push(stylesheet);
         }
+
+        self.stylesheet_sources.push(source);
     }
 
     pub fn append_characters(&mut self, text: &str, parent: &mut RsvgNode) {
@@ -328,21 +712,42 @@ impl DocumentBuilder {
             tree,
             ids,
             stylesheets,
-            ..
+            stylesheet_sources,
         } = self;
 
         match tree {
             None => Err(LoadingError::SvgHasNoElements),
             Some(mut root) => {
                 if root.borrow().get_type() == NodeType::Svg {
-                    cascade(&mut root, &stylesheets);
+                    let target = load_options
+                        .base_url
+                        .as_ref()
+                        .and_then(|url| url.fragment())
+                        .map(String::from);
+
+                    cascade(&mut root, &stylesheets, target.as_ref().map(String::as_str));
+
+                    let complexity = compute_complexity(&root);
 
                     Ok(Document {
                         tree: root.clone(),
                         ids,
+                        stylesheets: RefCell::new(stylesheets),
+                        stylesheet_sources: RefCell::new(stylesheet_sources),
+                        media_features: Cell::new(load_options.media_features),
+                        time: Cell::new(None),
+                        target: RefCell::new(target),
+                        turbulence_seed: Cell::new(None),
                         externs: RefCell::new(Resources::new()),
+                        #[cfg(feature = "raster-images")]
                         images: RefCell::new(Images::new()),
+                        #[cfg(feature = "filters")]
+                        filter_cache: Default::default(),
+                        paint_server_cache: Default::default(),
+                        #[cfg(feature = "text")]
+                        fonts: RefCell::new(None),
                         load_options: load_options.clone(),
+                        complexity,
                     })
                 } else {
                     Err(LoadingError::RootElementIsNotSvg)
@@ -0,0 +1,153 @@
+//! Cache of loaded documents, keyed by their contents and load options.
+//!
+//! Servers that render the same icons over and over can load them through a
+//! `DocumentCache`, so that identical byte streams are only parsed once.  The cache
+//! finds documents by a hash of the data, and then compares the data itself, so
+//! different documents never get mixed up even if their hashes collide.
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use gio::{self, InputStreamExt, MemoryInputStream};
+use glib::{self, Cast};
+
+use crate::document::Document;
+use crate::error::LoadingError;
+use crate::handle::{Handle, LoadOptions};
+
+/// Size of the chunks in which the data is read from the stream
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The data and options from which a document in the cache was loaded
+#[derive(Clone)]
+pub(crate) struct SharedSource {
+    data: Rc<Vec<u8>>,
+    load_options: LoadOptions,
+}
+
+impl SharedSource {
+    /// Parses the data into a new document, which is not shared with anyone.
+    pub(crate) fn load_document(
+        &self,
+        cancellable: Option<&gio::Cancellable>,
+    ) -> Result<Document, LoadingError> {
+        let bytes = glib::Bytes::from(&self.data[..]);
+        let stream = MemoryInputStream::new_from_bytes(&bytes);
+
+        Document::load_from_stream(
+            &self.load_options,
+            &stream.upcast::<gio::InputStream>(),
+            cancellable,
+        )
+    }
+}
+
+struct CacheEntry {
+    source: SharedSource,
+    document: Rc<Document>,
+}
+
+/// A cache of loaded documents
+///
+/// Loading data that was already loaded with equivalent options returns a handle for
+/// the same parsed document.  The handles still behave as if each had its own
+/// document: the first time that state is set on one of them, like with
+/// `set_element_visible()`, that handle parses the data again into a private document,
+/// and the shared one is left untouched.
+///
+/// Clones of a `DocumentCache` refer to the same cache.  Entries are never evicted;
+/// use `clear()` to free them.
+#[derive(Clone, Default)]
+pub struct DocumentCache {
+    entries: Rc<RefCell<HashMap<u64, Vec<CacheEntry>>>>,
+}
+
+impl DocumentCache {
+    pub fn new() -> DocumentCache {
+        DocumentCache::default()
+    }
+
+    /// Reads all of the `stream` and returns a handle for its document.
+    ///
+    /// The document is parsed only if the cache does not have one for the same data and
+    /// equivalent `load_options`.  Errors are not cached.
+    pub fn load(
+        &self,
+        load_options: &LoadOptions,
+        stream: &gio::InputStream,
+        cancellable: Option<&gio::Cancellable>,
+    ) -> Result<Handle, LoadingError> {
+        let data = read_all(stream, cancellable)?;
+
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some(entry) = self.entries.borrow().get(&key).and_then(|entries| {
+            entries.iter().find(|e| {
+                *e.source.data == data && e.source.load_options.is_equivalent_to(load_options)
+            })
+        }) {
+            return Ok(Handle::from_shared_document(
+                entry.document.clone(),
+                entry.source.clone(),
+            ));
+        }
+
+        let source = SharedSource {
+            data: Rc::new(data),
+            load_options: load_options.clone(),
+        };
+
+        let document = Rc::new(source.load_document(cancellable)?);
+
+        self.entries
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push(CacheEntry {
+                source: source.clone(),
+                document: document.clone(),
+            });
+
+        Ok(Handle::from_shared_document(document, source))
+    }
+
+    /// Returns the number of documents in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the documents from the cache.
+    ///
+    /// Handles that were already returned keep their documents.
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+}
+
+fn read_all(
+    stream: &gio::InputStream,
+    cancellable: Option<&gio::Cancellable>,
+) -> Result<Vec<u8>, LoadingError> {
+    let mut data = Vec::new();
+
+    loop {
+        let bytes = stream.read_bytes(READ_CHUNK_SIZE, cancellable)?;
+
+        if bytes.is_empty() {
+            break;
+        }
+
+        data.extend_from_slice(&bytes);
+    }
+
+    Ok(data)
+}
@@ -1,30 +1,61 @@
 use cairo;
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
 use std::convert::TryFrom;
 use std::rc::{Rc, Weak};
 
+#[cfg(feature = "filters")]
+use locale_config::Locale;
+
 use crate::allowed_url::Fragment;
+use crate::angle::Angle;
 use crate::aspect_ratio::AspectRatio;
 use crate::bbox::BoundingBox;
-use crate::clip_path::{ClipPath, ClipPathUnits};
+use crate::clip_path::{ClipPath, ClipPathUnits, ClipShape};
 use crate::coord_units::CoordUnits;
 use crate::dasharray::Dasharray;
 use crate::document::Document;
 use crate::dpi::Dpi;
+use crate::element_overrides::ElementOverrides;
 use crate::error::{AcquireError, RenderingError};
-use crate::filters;
+use crate::filter_functions::FilterValue;
+#[cfg(feature = "filters")]
+use crate::filters::{self, cache::FilterCache};
+use crate::font_props::{FontFallback, FontFamilyMap};
+use crate::glyph_run::GlyphRun;
 use crate::gradient::{LinearGradient, RadialGradient};
+use crate::hatch::Hatch;
 use crate::limits;
+use crate::marker::{MarkerPosition, MarkerType};
 use crate::mask::Mask;
+use crate::mesh_gradient::MeshGradient;
 use crate::node::{CascadedValues, NodeDraw, NodeType, RsvgNode};
-use crate::paint_server::{PaintServer, PaintSource};
-use crate::pattern::Pattern;
+use crate::paint_server::{PaintServer, PaintServerCache, PaintSource};
+use crate::path_builder::{PathBuilder, PathSegment};
+use crate::pattern::{Pattern, PatternTileKey};
 use crate::properties::ComputedValues;
 use crate::property_defs::{
-    ClipRule, FillRule, ShapeRendering, StrokeDasharray, StrokeLinecap, StrokeLinejoin,
+    ClipRule,
+    FillRule,
+    Isolation,
+    MixBlendMode,
+    PaintTarget,
+    ShapeRendering,
+    StrokeDasharray,
+    StrokeLinecap,
+    StrokeLinejoin,
+    VectorEffect,
+    Visibility,
 };
-use crate::rect::RectangleExt;
+#[cfg(not(feature = "filters"))]
+use crate::property_macros::ToCssValue;
+use crate::rect::{RectangleExt, TransformRect};
+use crate::render_hooks::RenderHooks;
+use crate::resources::ResourceTracker;
+use crate::sanitize;
+#[cfg(any(feature = "filters", feature = "raster-images"))]
 use crate::surface_utils::shared_surface::SharedImageSurface;
+#[cfg(feature = "filters")]
+use crate::surface_utils::shared_surface::SurfaceType;
 use crate::unit_interval::UnitInterval;
 use crate::viewbox::ViewBox;
 
@@ -37,21 +68,29 @@ use crate::viewbox::ViewBox;
 /// viewport pushed with `DrawingCtx::push_view_box()` will remain in place until the
 /// returned `ViewParams` is dropped; at that point, the `DrawingCtx` will resume using its
 /// previous viewport.
+///
+/// It also holds the size of the toplevel viewport, which is what viewport-percentage
+/// units like `vw` refer to, even inside nested viewports.
 pub struct ViewParams {
     pub dpi_x: f64,
     pub dpi_y: f64,
     pub view_box_width: f64,
     pub view_box_height: f64,
+    pub viewport_width: f64,
+    pub viewport_height: f64,
     view_box_stack: Option<Weak<RefCell<Vec<ViewBox>>>>,
 }
 
 impl ViewParams {
+    /// Creates a `ViewParams` whose view box is also the toplevel viewport.
     pub fn new(dpi_x: f64, dpi_y: f64, view_box_width: f64, view_box_height: f64) -> ViewParams {
         ViewParams {
             dpi_x,
             dpi_y,
             view_box_width,
             view_box_height,
+            viewport_width: view_box_width,
+            viewport_height: view_box_height,
             view_box_stack: None,
         }
     }
@@ -74,6 +113,92 @@ pub enum ClipMode {
     ClipToVbox,
 }
 
+/// How faithfully a document gets rendered.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderQuality {
+    /// Everything is rendered at full quality.  This is the default.
+    Full,
+
+    /// Expensive features are degraded, for quick previews that will be followed by a
+    /// full-quality render.  Filters run at a lower resolution, patterns and images are
+    /// scaled with a fast filter, and shapes smaller than a pixel are not painted.
+    Approximate,
+}
+
+impl Default for RenderQuality {
+    fn default() -> RenderQuality {
+        RenderQuality::Full
+    }
+}
+
+/// How the outline of a shape gets stroked, as computed for rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeMetrics {
+    /// Width of the stroke, in the coordinate system given by `transform`
+    pub width: f64,
+
+    /// Width of the stroke once `transform` is applied to it
+    ///
+    /// If `transform` scales differently along each axis, the stroke does not have a
+    /// uniform width; this is the width scaled by the geometric mean of the scale
+    /// factors, that is, by the square root of the determinant of `transform`.
+    pub effective_width: f64,
+
+    pub line_cap: cairo::LineCap,
+    pub line_join: cairo::LineJoin,
+    pub miter_limit: f64,
+
+    /// Lengths of the dashes and gaps, in the coordinate system given by `transform`
+    ///
+    /// This is empty if the stroke is solid.
+    pub dashes: Vec<f64>,
+
+    /// Distance into the dash pattern at which the stroke starts
+    pub dash_offset: f64,
+
+    /// Transformation from the coordinate system in which the stroke is computed to
+    /// device space
+    ///
+    /// This is the element's user space, except for `vector-effect:
+    /// non-scaling-stroke`, where it is the device space itself.
+    pub transform: cairo::Matrix,
+}
+
+/// Scale factor for the resolution of filters in `RenderQuality::Approximate`
+#[cfg(feature = "filters")]
+const APPROXIMATE_FILTER_SCALE: f64 = 0.5;
+
+/// Maximum number of rendered pattern tiles that are kept for reuse during a render
+const MAX_PATTERN_TILES: usize = 64;
+
+/// The paint of a context element, which is what `context-fill` and
+/// `context-stroke` refer to.
+///
+/// Each paint is stored with the `color` of the context element, for
+/// when the paint is `currentColor`.
+#[derive(Clone)]
+struct ContextPaint {
+    fill: (PaintServer, cssparser::RGBA),
+    stroke: (PaintServer, cssparser::RGBA),
+}
+
+/// Keeps track of the clips in effect while drawing, to find out the clip region of
+/// the `target` element
+struct ClipRecorder {
+    target: RsvgNode,
+    shapes: Vec<ClipShape>,
+    region: Vec<ClipShape>,
+}
+
+/// The user space whose transformation is being recorded for the coordinate system API
+enum TransformTarget {
+    /// The user space of the toplevel `<svg>`'s contents, after its `viewBox` is applied
+    Document,
+
+    /// The user space of an element, after its `transform` is applied
+    Element(RsvgNode),
+}
+
 pub struct DrawingCtx {
     document: Rc<Document>,
 
@@ -86,13 +211,55 @@ pub struct DrawingCtx {
     // elements via <use>, recursive patterns, etc.  See limits.rs for details.
     num_elements_acquired: usize,
 
+    // Pixels allocated, filled and filtered so far; see resources.rs
+    resources: ResourceTracker,
+
     cr_stack: Vec<cairo::Context>,
     cr: cairo::Context,
 
+    // Groups with `enable-background: new`, where the BackgroundImage starts accumulating
+    background_roots: Vec<BackgroundRoot>,
+
     view_box_stack: Rc<RefCell<Vec<ViewBox>>>,
 
     drawsub_stack: Vec<RsvgNode>,
 
+    context_paint_stack: Vec<ContextPaint>,
+
+    // Where markers got placed, if they are being recorded for the geometry API
+    marker_positions: Option<Vec<MarkerPosition>>,
+
+    // The element whose path is being recorded for the path data API, and its segments
+    recorded_path: Option<(RsvgNode, Vec<PathSegment>)>,
+
+    // The clip region of an element, if it is being recorded for the geometry API
+    clip_recorder: RefCell<Option<ClipRecorder>>,
+
+    // The element whose stroke is being recorded for the geometry API, and its stroke
+    recorded_stroke: Option<(RsvgNode, Option<StrokeMetrics>)>,
+
+    // The user space being recorded for the coordinate system API, and its transformation
+    recorded_transform: Option<(TransformTarget, Option<cairo::Matrix>)>,
+
+    // Text runs that didn't render with the requested font, if they are being recorded
+    font_fallbacks: Option<Vec<FontFallback>>,
+
+    // Shaped text, if it is being recorded for the glyph run API
+    glyph_runs: Option<Vec<GlyphRun>>,
+
+    render_hooks: RenderHooks,
+
+    // Pattern tiles that were rendered already, so that shapes filled with the same
+    // pattern don't render it again
+    pattern_tiles: Vec<(PatternTileKey, cairo::Surface)>,
+
+    quality: RenderQuality,
+
+    element_overrides: ElementOverrides,
+
+    // Scale of the surfaces in the filter being run, with respect to device space
+    filter_scale: f64,
+
     acquired_nodes: AcquiredNodes,
 
     measuring: bool,
@@ -148,6 +315,7 @@ impl DrawingCtx {
         view_box_stack.push(vbox);
 
         let acquired_nodes = AcquiredNodes::new(document.clone());
+        let resources = ResourceTracker::new(document.load_options().resource_budget);
 
         let mut draw_ctx = DrawingCtx {
             document,
@@ -155,10 +323,25 @@ impl DrawingCtx {
             rect,
             dpi,
             num_elements_acquired: 0,
+            resources,
             cr_stack: Vec::new(),
+            background_roots: Vec::new(),
             cr: cr.clone(),
             view_box_stack: Rc::new(RefCell::new(view_box_stack)),
             drawsub_stack: Vec::new(),
+            context_paint_stack: Vec::new(),
+            marker_positions: None,
+            recorded_path: None,
+            recorded_stroke: None,
+            clip_recorder: RefCell::new(None),
+            recorded_transform: None,
+            font_fallbacks: None,
+            glyph_runs: None,
+            render_hooks: RenderHooks::default(),
+            pattern_tiles: Vec::new(),
+            quality: RenderQuality::default(),
+            element_overrides: ElementOverrides::default(),
+            filter_scale: 1.0,
             acquired_nodes,
             measuring,
             testing,
@@ -183,6 +366,66 @@ impl DrawingCtx {
         self.testing
     }
 
+    /// Whether gradients with `color-interpolation: auto` should be interpolated in OKLab
+    pub fn oklab_gradients(&self) -> bool {
+        self.document.load_options().oklab_gradients
+    }
+
+    /// Accounting of the resources used by this render, to enforce the resource budget
+    pub fn resources(&self) -> &ResourceTracker {
+        &self.resources
+    }
+
+    /// Maximum width and height of the surfaces for pattern tiles, in device pixels
+    pub fn max_pattern_tile_size(&self) -> i32 {
+        self.document.load_options().max_pattern_tile_size
+    }
+
+    /// Whether newlines in text elements start new lines, instead of being collapsed
+    pub fn multiline_text(&self) -> bool {
+        self.document.load_options().multiline_text
+    }
+
+    /// The seed that overrides the `seed` attribute of `feTurbulence` elements, if any
+    #[cfg(feature = "filters")]
+    pub fn turbulence_seed(&self) -> Option<i32> {
+        self.document.get_turbulence_seed()
+    }
+
+    /// The application's replacements for the generic font families
+    pub fn font_families(&self) -> &FontFamilyMap {
+        &self.document.load_options().font_families
+    }
+
+    /// The font map with the fonts from the document's `@font-face` rules, if it has any
+    #[cfg(feature = "text")]
+    pub fn font_map(&self) -> Option<pango::FontMap> {
+        self.document.font_map()
+    }
+
+    /// Whether one of the document's `@font-face` rules defines the `family`
+    #[cfg(feature = "text")]
+    pub fn has_font_face(&self, family: &str) -> bool {
+        self.document.has_font_face(family)
+    }
+
+    /// The document's cache of filter results, which persists between renders
+    #[cfg(feature = "filters")]
+    pub fn filter_cache(&self) -> RefMut<'_, FilterCache> {
+        self.document.filter_cache()
+    }
+
+    /// The document's cache of resolved paint servers, which persists between renders
+    pub fn paint_server_cache(&self) -> RefMut<'_, PaintServerCache> {
+        self.document.paint_server_cache()
+    }
+
+    /// The user's locale, for nodes created while rendering
+    #[cfg(feature = "filters")]
+    pub fn locale(&self) -> &Locale {
+        self.document.load_options().locale()
+    }
+
     pub fn get_cairo_context(&self) -> cairo::Context {
         self.cr.clone()
     }
@@ -230,6 +473,7 @@ impl DrawingCtx {
         &self,
     ) -> Result<cairo::ImageSurface, RenderingError> {
         let (w, h) = self.size_for_temporary_surface();
+        self.resources.add_surface(w, h)?;
 
         Ok(cairo::ImageSurface::create(cairo::Format::ARgb32, w, h)?)
     }
@@ -239,6 +483,7 @@ impl DrawingCtx {
         surface: &cairo::Surface,
     ) -> Result<cairo::Surface, RenderingError> {
         let (w, h) = self.size_for_temporary_surface();
+        self.resources.add_surface(w, h)?;
 
         let surface = cairo::Surface::create_similar(surface, cairo::Content::ColorAlpha, w, h);
 
@@ -258,12 +503,15 @@ impl DrawingCtx {
         let view_box_stack = self.view_box_stack.borrow();
         let last = view_box_stack.len() - 1;
         let stack_top = &view_box_stack[last];
+        let toplevel = &view_box_stack[0];
 
         ViewParams {
             dpi_x: self.dpi.x(),
             dpi_y: self.dpi.y(),
             view_box_width: stack_top.width,
             view_box_height: stack_top.height,
+            viewport_width: toplevel.width,
+            viewport_height: toplevel.height,
             view_box_stack: None,
         }
     }
@@ -276,15 +524,17 @@ impl DrawingCtx {
     /// The viewport will stay in place, and will be the one returned by
     /// `get_view_params()`, until the returned `ViewParams` is dropped.
     pub fn push_view_box(&self, width: f64, height: f64) -> ViewParams {
-        self.view_box_stack
-            .borrow_mut()
-            .push(ViewBox::new(0.0, 0.0, width, height));
+        let mut view_box_stack = self.view_box_stack.borrow_mut();
+        view_box_stack.push(ViewBox::new(0.0, 0.0, width, height));
+        let toplevel = &view_box_stack[0];
 
         ViewParams {
             dpi_x: self.dpi.x(),
             dpi_y: self.dpi.y(),
             view_box_width: width,
             view_box_height: height,
+            viewport_width: toplevel.width,
+            viewport_height: toplevel.height,
             view_box_stack: Some(Rc::downgrade(&self.view_box_stack)),
         }
     }
@@ -296,6 +546,18 @@ impl DrawingCtx {
         preserve_aspect_ratio: AspectRatio,
         clip_mode: Option<ClipMode>,
     ) -> Option<ViewParams> {
+        let cairo::Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } = *viewport;
+
+        if ![x, y, width, height].iter().all(|v| v.is_finite()) {
+            rsvg_log!("(not establishing a viewport with non-finite dimensions)");
+            return None;
+        }
+
         if let Some(ref clip) = clip_mode {
             if *clip == ClipMode::ClipToViewport {
                 self.clip(viewport.x, viewport.y, viewport.width, viewport.height);
@@ -305,6 +567,14 @@ impl DrawingCtx {
         preserve_aspect_ratio
             .viewport_to_viewbox_transform(vbox, viewport)
             .and_then(|matrix| {
+                if !sanitize::is_valid_matrix(&cairo::Matrix::multiply(
+                    &matrix,
+                    &self.cr.get_matrix(),
+                )) {
+                    rsvg_log!("(not establishing a viewport with an invalid transform)");
+                    return None;
+                }
+
                 self.cr.transform(matrix);
 
                 if let Some(vbox) = vbox {
@@ -402,16 +672,20 @@ impl DrawingCtx {
         } else {
             self.with_saved_cr(&mut |dc| {
                 let clip_uri = values.clip_path.0.get();
+                let clip_shape = values.clip_path.0.shape();
                 let mask = values.mask.0.get();
 
                 // The `filter` property does not apply to masks.
                 let filter = if node.borrow().get_type() == NodeType::Mask {
-                    None
+                    &FilterValue::None
                 } else {
-                    values.filter.0.get()
+                    &values.filter.0
                 };
 
-                let UnitInterval(opacity) = values.opacity.0;
+                let opacity = dc
+                    .element_overrides
+                    .opacity(node)
+                    .unwrap_or((values.opacity.0).0);
 
                 let affine_at_start = dc.cr.get_matrix();
 
@@ -421,10 +695,21 @@ impl DrawingCtx {
                 // Here we are clipping in user space, so the bbox doesn't matter
                 dc.clip_to_node(&clip_in_user_space, &dc.empty_bbox())?;
 
+                let enable_background = &values.enable_background;
+                let blend_mode = values.mix_blend_mode;
+
+                // A group with `enable-background: new` gets its own surface, so that
+                // its contents can be told apart from what was drawn before it.  Likewise,
+                // `isolation: isolate` keeps the group's contents from blending with its
+                // backdrop, and a blend mode needs the whole group drawn before blending it.
                 let needs_temporary_surface = !(opacity == 1.0
                     && filter.is_none()
                     && mask.is_none()
-                    && clip_in_object_space.is_none());
+                    && clip_in_object_space.is_none()
+                    && clip_shape.is_none()
+                    && !enable_background.new
+                    && values.isolation == Isolation::Auto
+                    && blend_mode == MixBlendMode::Normal);
 
                 if needs_temporary_surface {
                     // Compute our assortment of affines
@@ -437,7 +722,7 @@ impl DrawingCtx {
 
                     // Create temporary surface and its cr
 
-                    let cr = if filter.is_some() {
+                    let cr = if !filter.is_none() {
                         cairo::Context::new(&*dc.create_surface_for_toplevel_viewport()?)
                     } else {
                         cairo::Context::new(
@@ -449,6 +734,14 @@ impl DrawingCtx {
 
                     dc.push_cairo_context(cr);
 
+                    if enable_background.new {
+                        dc.background_roots.push(BackgroundRoot {
+                            depth: dc.cr_stack.len(),
+                            affine: affines.for_temporary_surface,
+                            region: enable_background.region,
+                        });
+                    }
+
                     // Draw!
 
                     let mut res = draw_fn(dc);
@@ -461,11 +754,11 @@ impl DrawingCtx {
 
                     // Filter
 
-                    let source_surface = if let Some(filter_uri) = filter {
+                    let source_surface = if !filter.is_none() {
                         let child_surface =
                             cairo::ImageSurface::try_from(dc.cr.get_target()).unwrap();
                         let img_surface =
-                            dc.run_filter(filter_uri, node, values, &child_surface, bbox)?;
+                            dc.run_filter(filter, node, values, &child_surface, bbox)?;
                         // turn into a Surface
                         (*img_surface).clone()
                     } else {
@@ -474,16 +767,28 @@ impl DrawingCtx {
 
                     dc.pop_cairo_context();
 
-                    // Set temporary surface as source
+                    if enable_background.new {
+                        dc.background_roots.pop();
+                    }
+
+                    // Set temporary surface as source, and blend it with the backdrop
 
                     dc.cr.set_matrix(affines.compositing);
                     dc.cr.set_source_surface(&source_surface, 0.0, 0.0);
+                    dc.cr.set_operator(cairo::Operator::from(blend_mode));
 
                     // Clip
 
                     dc.cr.set_matrix(affines.outside_temporary_surface);
+                    let num_clip_shapes = dc.num_recorded_clip_shapes();
                     let _: () = dc.clip_to_node(&clip_in_object_space, &bbox)?;
 
+                    if let Some(shape) = clip_shape {
+                        shape.to_cairo_context(dc, values, &bbox)?;
+                    }
+
+                    dc.snapshot_clip_region(node, num_clip_shapes);
+
                     // Mask
 
                     if let Some(fragment) = mask {
@@ -527,7 +832,9 @@ impl DrawingCtx {
 
                     res
                 } else {
-                    draw_fn(dc)
+                    let res = draw_fn(dc);
+                    dc.snapshot_clip_region(node, dc.num_recorded_clip_shapes());
+                    res
                 }
             })
         }
@@ -539,6 +846,30 @@ impl DrawingCtx {
         initial_with_offset
     }
 
+    /// Computes an element's transformation from the CSS `transform` and
+    /// `transform-origin` properties.
+    ///
+    /// Returns `None` if the `transform` property is not specified, in which case the
+    /// element's `transform` attribute applies instead.
+    pub fn get_css_transform(&self, values: &ComputedValues) -> Option<cairo::Matrix> {
+        let functions = values.transform.functions.as_ref()?;
+
+        let params = self.get_view_params();
+        let ox = values.transform_origin.x.normalize(values, &params);
+        let oy = values.transform_origin.y.normalize(values, &params);
+
+        let mut affine = cairo::Matrix::identity();
+        affine.translate(ox, oy);
+
+        for function in functions {
+            affine = cairo::Matrix::multiply(&function.to_matrix(values, &params), &affine);
+        }
+
+        affine.translate(-ox, -oy);
+
+        Some(affine)
+    }
+
     /// Saves the current Cairo matrix, runs the draw_fn, and restores the matrix
     ///
     /// This is slightly cheaper than a `cr.save()` / `cr.restore()`
@@ -567,29 +898,51 @@ impl DrawingCtx {
         &mut self,
         draw_fn: &mut dyn FnMut(&mut DrawingCtx) -> Result<BoundingBox, RenderingError>,
     ) -> Result<BoundingBox, RenderingError> {
+        let num_clip_shapes = self.num_recorded_clip_shapes();
+
         self.cr.save();
         let res = draw_fn(self);
         self.cr.restore();
+
+        // Clips go away with the restored context
+        if let Some(ref mut recorder) = *self.clip_recorder.borrow_mut() {
+            recorder.shapes.truncate(num_clip_shapes);
+        }
+
         res
     }
 
+    #[cfg(feature = "filters")]
     fn run_filter(
         &mut self,
-        filter_uri: &Fragment,
+        filter: &FilterValue,
         node: &RsvgNode,
         values: &ComputedValues,
         child_surface: &cairo::ImageSurface,
         node_bbox: BoundingBox,
     ) -> Result<cairo::ImageSurface, RenderingError> {
+        let filter_uri = match *filter {
+            FilterValue::None => return Ok(child_surface.clone()),
+
+            FilterValue::Url(ref filter_uri) => filter_uri,
+
+            FilterValue::Functions(ref functions) => {
+                let filter_node = filters::functions::filter_node_for_functions(
+                    functions, node, values, &node_bbox, self,
+                );
+
+                return self.run_filter_node(&filter_node, node, values, child_surface, node_bbox);
+            }
+        };
+
         match self.acquire_node(filter_uri, &[NodeType::Filter]) {
             Ok(acquired) => {
                 let filter_node = acquired.get();
 
-                if !filter_node.borrow().is_in_error() {
-                    // FIXME: deal with out of memory here
-                    filters::render(&filter_node, values, child_surface, self, node_bbox)
-                } else {
+                if filter_node.borrow().is_in_error() {
                     Ok(child_surface.clone())
+                } else {
+                    self.run_filter_node(&filter_node, node, values, child_surface, node_bbox)
                 }
             }
 
@@ -611,6 +964,107 @@ impl DrawingCtx {
         }
     }
 
+    #[cfg(feature = "filters")]
+    fn run_filter_node(
+        &mut self,
+        filter_node: &RsvgNode,
+        node: &RsvgNode,
+        values: &ComputedValues,
+        child_surface: &cairo::ImageSurface,
+        node_bbox: BoundingBox,
+    ) -> Result<cairo::ImageSurface, RenderingError> {
+        if self.is_approximate() {
+            self.run_filter_at_scale(
+                filter_node,
+                node,
+                values,
+                child_surface,
+                node_bbox,
+                APPROXIMATE_FILTER_SCALE,
+            )
+        } else {
+            // The child surface is still the target of the current cairo context, so it
+            // has to be copied to get a unique reference for safe access to the pixel data.
+            let source_surface = filters::copy_to_shared_surface(child_surface)?;
+
+            // FIXME: deal with out of memory here
+            filters::render(filter_node, node, values, source_surface, self, node_bbox)
+        }
+    }
+
+    /// Runs a filter on a copy of `child_surface` that is scaled by `scale`, and scales the
+    /// result back to the size of `child_surface`.
+    #[cfg(feature = "filters")]
+    fn run_filter_at_scale(
+        &mut self,
+        filter_node: &RsvgNode,
+        node: &RsvgNode,
+        values: &ComputedValues,
+        child_surface: &cairo::ImageSurface,
+        node_bbox: BoundingBox,
+        scale: f64,
+    ) -> Result<cairo::ImageSurface, RenderingError> {
+        let width = child_surface.get_width();
+        let height = child_surface.get_height();
+
+        let scaled_surface = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            ((f64::from(width) * scale).ceil() as i32).max(1),
+            ((f64::from(height) * scale).ceil() as i32).max(1),
+        )?;
+
+        {
+            let cr = cairo::Context::new(&scaled_surface);
+            cr.scale(scale, scale);
+            cr.set_source_surface(child_surface, 0.0, 0.0);
+            cr.paint();
+        }
+
+        let affine = self.cr.get_matrix();
+        let scale_matrix = cairo::Matrix::new(scale, 0.0, 0.0, scale, 0.0, 0.0);
+        self.cr
+            .set_matrix(cairo::Matrix::multiply(&affine, &scale_matrix));
+        self.filter_scale = scale;
+
+        // Nothing else refers to the scaled surface, so it needs no copy.
+        let source_surface = SharedImageSurface::new(scaled_surface, SurfaceType::SRgb)?;
+
+        let res = filters::render(filter_node, node, values, source_surface, self, node_bbox);
+
+        self.filter_scale = 1.0;
+        self.cr.set_matrix(affine);
+
+        let scaled_output = res?;
+        let output = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+        {
+            let cr = cairo::Context::new(&output);
+            cr.scale(1.0 / scale, 1.0 / scale);
+            cr.set_source_surface(&scaled_output, 0.0, 0.0);
+            cr.paint();
+        }
+
+        Ok(output)
+    }
+
+    #[cfg(not(feature = "filters"))]
+    fn run_filter(
+        &mut self,
+        filter: &FilterValue,
+        node: &RsvgNode,
+        _values: &ComputedValues,
+        child_surface: &cairo::ImageSurface,
+        _node_bbox: BoundingBox,
+    ) -> Result<cairo::ImageSurface, RenderingError> {
+        rsvg_log!(
+            "ignoring filter \"{}\" on element {} since librsvg was built without filters",
+            filter.to_css_value(),
+            node,
+        );
+
+        Ok(child_surface.clone())
+    }
+
     fn set_color(
         &self,
         color: cssparser::Color,
@@ -637,6 +1091,8 @@ impl DrawingCtx {
             &[
                 NodeType::LinearGradient,
                 NodeType::RadialGradient,
+                NodeType::MeshGradient,
+                NodeType::Hatch,
                 NodeType::Pattern,
             ],
         )
@@ -669,6 +1125,14 @@ impl DrawingCtx {
                                 .borrow()
                                 .get_impl::<RadialGradient>()
                                 .resolve_fallbacks_and_set_pattern(&node, self, opacity, bbox)?,
+                            NodeType::MeshGradient => node
+                                .borrow()
+                                .get_impl::<MeshGradient>()
+                                .resolve_fallbacks_and_set_pattern(&node, self, opacity, bbox)?,
+                            NodeType::Hatch => node
+                                .borrow()
+                                .get_impl::<Hatch>()
+                                .resolve_fallbacks_and_set_pattern(&node, self, opacity, bbox)?,
                             NodeType::Pattern => node
                                 .borrow()
                                 .get_impl::<Pattern>()
@@ -681,13 +1145,19 @@ impl DrawingCtx {
                         return Err(RenderingError::InstancingLimit);
                     }
 
+                    // The paint server is already being rendered further up the
+                    // stack, for example by a pattern whose content uses it.
+                    Err(AcquireError::CircularReference(node)) => {
+                        rsvg_log!("circular reference in paint server {}", node);
+                    }
+
                     Err(_) => (),
                 }
 
                 if !had_paint_server && alternate.is_some() {
                     self.set_color(alternate.unwrap(), opacity, current_color);
                     had_paint_server = true;
-                } else {
+                } else if !had_paint_server {
                     rsvg_log!(
                         "pattern \"{}\" was not found and there was no fallback alternate",
                         iri
@@ -702,82 +1172,482 @@ impl DrawingCtx {
                 Ok(true)
             }
 
+            PaintServer::ContextFill | PaintServer::ContextStroke => {
+                let (ps, current_color) = self.resolve_context_paint(ps, current_color);
+                self.set_source_paint_server(&ps, opacity, bbox, current_color)
+            }
+
             PaintServer::None => Ok(false),
         }
     }
 
-    pub fn setup_cr_for_stroke(&self, cr: &cairo::Context, values: &ComputedValues) {
-        let params = self.get_view_params();
+    /// Replaces `context-fill` and `context-stroke` with the paint of the current
+    /// context element; there is no such paint outside of a context element.
+    fn resolve_context_paint(
+        &self,
+        ps: &PaintServer,
+        current_color: cssparser::RGBA,
+    ) -> (PaintServer, cssparser::RGBA) {
+        let context = self.context_paint_stack.last();
+
+        match *ps {
+            PaintServer::ContextFill => context
+                .map(|c| c.fill.clone())
+                .unwrap_or((PaintServer::None, current_color)),
+
+            PaintServer::ContextStroke => context
+                .map(|c| c.stroke.clone())
+                .unwrap_or((PaintServer::None, current_color)),
+
+            _ => (ps.clone(), current_color),
+        }
+    }
+
+    pub fn set_render_hooks(&mut self, hooks: &RenderHooks) {
+        self.render_hooks = hooks.clone();
+    }
+
+    pub fn get_render_hooks(&self) -> &RenderHooks {
+        &self.render_hooks
+    }
 
-        cr.set_line_width(values.stroke_width.0.normalize(values, &params));
-        cr.set_miter_limit(values.stroke_miterlimit.0);
-        cr.set_line_cap(cairo::LineCap::from(values.stroke_line_cap));
-        cr.set_line_join(cairo::LineJoin::from(values.stroke_line_join));
+    /// Returns a pattern tile that was already rendered with the same `key`.
+    pub fn lookup_pattern_tile(&self, key: &PatternTileKey) -> Option<cairo::Surface> {
+        // The content of a pattern may use context-fill or context-stroke, which depend
+        // on the element being painted.
+        if !self.context_paint_stack.is_empty() {
+            return None;
+        }
+
+        self.pattern_tiles
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, surface)| surface.clone())
+    }
+
+    /// Keeps a rendered pattern tile for reuse during the rest of the render.
+    pub fn cache_pattern_tile(&mut self, key: PatternTileKey, surface: &cairo::Surface) {
+        if self.context_paint_stack.is_empty() && self.pattern_tiles.len() < MAX_PATTERN_TILES {
+            self.pattern_tiles.push((key, surface.clone()));
+        }
+    }
+
+    pub fn set_quality(&mut self, quality: RenderQuality) {
+        self.quality = quality;
+    }
+
+    pub fn is_approximate(&self) -> bool {
+        self.quality == RenderQuality::Approximate
+    }
+
+    pub fn set_element_overrides(&mut self, overrides: &ElementOverrides) {
+        self.element_overrides = overrides.clone();
+    }
+
+    /// Starts recording the positions of markers, to be retrieved with `take_marker_positions()`
+    pub fn record_marker_positions(&mut self) {
+        self.marker_positions = Some(Vec::new());
+    }
+
+    pub fn take_marker_positions(&mut self) -> Vec<MarkerPosition> {
+        self.marker_positions.take().unwrap_or_default()
+    }
+
+    /// Records that a marker is placed at `(x, y)` in the current user space, if
+    /// marker positions are being recorded.
+    pub fn add_marker_position(&mut self, marker_type: MarkerType, x: f64, y: f64, angle: Angle) {
+        if let Some(ref mut positions) = self.marker_positions {
+            let mut affine = self.initial_affine;
+            affine.invert();
+            let affine = cairo::Matrix::multiply(&self.cr.get_matrix(), &affine);
+
+            let (x, y) = affine.transform_point(x, y);
+            let (vx, vy) = affine.transform_distance(angle.radians().cos(), angle.radians().sin());
+
+            positions.push(MarkerPosition {
+                marker_type,
+                x,
+                y,
+                angle: Angle::from_vector(vx, vy).radians().to_degrees(),
+            });
+        }
+    }
+
+    /// Starts recording the path of `node`, to be retrieved with `take_recorded_path()`
+    pub fn record_path_of(&mut self, node: &RsvgNode) {
+        self.recorded_path = Some((node.clone(), Vec::new()));
+    }
+
+    pub fn take_recorded_path(&mut self) -> Vec<PathSegment> {
+        self.recorded_path
+            .take()
+            .map(|(_, segments)| segments)
+            .unwrap_or_default()
+    }
+
+    /// Records the path of a shape in its own user space, if it is the element whose
+    /// path is being recorded.
+    pub fn add_path(&mut self, node: &RsvgNode, builder: &PathBuilder) {
+        if let Some((ref target, ref mut segments)) = self.recorded_path {
+            if target == node {
+                *segments = builder.to_segments();
+            }
+        }
+    }
+
+    /// Starts recording the stroke of `node`, to be retrieved with `take_recorded_stroke()`
+    pub fn record_stroke_of(&mut self, node: &RsvgNode) {
+        self.recorded_stroke = Some((node.clone(), None));
+    }
+
+    pub fn take_recorded_stroke(&mut self) -> Option<StrokeMetrics> {
+        self.recorded_stroke.take().and_then(|(_, stroke)| stroke)
+    }
+
+    /// Records the stroke of a shape with `values`, if it is the element whose stroke is
+    /// being recorded and it has a stroke.
+    ///
+    /// Only the first time that the element is drawn counts, for elements that get
+    /// instanced more than once with `<use>`.
+    pub fn add_stroke(&mut self, node: &RsvgNode, values: &ComputedValues) {
+        let is_target = match self.recorded_stroke {
+            Some((ref target, None)) => target == node,
+            _ => false,
+        };
+
+        if is_target && values.stroke.0 != PaintServer::None {
+            let stroke = self.stroke_metrics(&self.cr, values);
+            self.recorded_stroke = Some((node.clone(), Some(stroke)));
+        }
+    }
+
+    /// Starts recording the transformation from the user space of `node` to device space,
+    /// or from the document's user space if `node` is `None`, to be retrieved with
+    /// `take_recorded_transform()`
+    pub fn record_transform_of(&mut self, node: Option<&RsvgNode>) {
+        let target = match node {
+            Some(node) => TransformTarget::Element(node.clone()),
+            None => TransformTarget::Document,
+        };
+
+        self.recorded_transform = Some((target, None));
+    }
 
-        if let StrokeDasharray(Dasharray::Array(ref dashes)) = values.stroke_dasharray {
-            let normalized_dashes: Vec<f64> = dashes
+    pub fn take_recorded_transform(&mut self) -> Option<cairo::Matrix> {
+        self.recorded_transform
+            .take()
+            .and_then(|(_, affine)| affine)
+    }
+
+    pub fn is_recording_document_transform(&self) -> bool {
+        match self.recorded_transform {
+            Some((TransformTarget::Document, _)) => true,
+            _ => false,
+        }
+    }
+
+    /// Records the current transformation as the document's user space, if it is
+    /// being recorded.
+    pub fn add_document_transform(&mut self) {
+        if let Some((TransformTarget::Document, ref mut affine)) = self.recorded_transform {
+            *affine = Some(self.cr.get_matrix());
+        }
+    }
+
+    /// Records the current transformation as the user space of `node`, if it is the
+    /// element whose transformation is being recorded.
+    ///
+    /// Only the first time that the element is drawn counts, for elements that get
+    /// instanced more than once with `<use>`.
+    pub fn add_element_transform(&mut self, node: &RsvgNode) {
+        if let Some((TransformTarget::Element(ref target), ref mut affine)) =
+            self.recorded_transform
+        {
+            if target == node && affine.is_none() {
+                *affine = Some(self.cr.get_matrix());
+            }
+        }
+    }
+
+    /// Starts recording the text runs that fall back to a different font, to be
+    /// retrieved with `take_font_fallbacks()`
+    pub fn record_font_fallbacks(&mut self) {
+        self.font_fallbacks = Some(Vec::new());
+    }
+
+    pub fn take_font_fallbacks(&mut self) -> Vec<FontFallback> {
+        self.font_fallbacks.take().unwrap_or_default()
+    }
+
+    pub fn is_recording_font_fallbacks(&self) -> bool {
+        self.font_fallbacks.is_some()
+    }
+
+    pub fn add_font_fallback(&mut self, fallback: FontFallback) {
+        if let Some(ref mut fallbacks) = self.font_fallbacks {
+            fallbacks.push(fallback);
+        }
+    }
+
+    /// Starts recording the glyph runs of text elements, to be retrieved with
+    /// `take_glyph_runs()`
+    pub fn record_glyph_runs(&mut self) {
+        self.glyph_runs = Some(Vec::new());
+    }
+
+    pub fn take_glyph_runs(&mut self) -> Vec<GlyphRun> {
+        self.glyph_runs.take().unwrap_or_default()
+    }
+
+    pub fn is_recording_glyph_runs(&self) -> bool {
+        self.glyph_runs.is_some()
+    }
+
+    /// Records a glyph run, if glyph runs are being recorded.
+    ///
+    /// `affine` maps the run's coordinates to device space; it gets converted to map
+    /// them to the coordinates of the viewport instead.
+    pub fn add_glyph_run(&mut self, mut run: GlyphRun, affine: cairo::Matrix) {
+        if let Some(ref mut runs) = self.glyph_runs {
+            let mut initial = self.initial_affine;
+            initial.invert();
+            run.transform = cairo::Matrix::multiply(&affine, &initial);
+
+            runs.push(run);
+        }
+    }
+
+    /// Starts recording the clip region of `node`, to be retrieved with
+    /// `take_recorded_clip_region()`
+    pub fn record_clip_region_of(&mut self, node: &RsvgNode) {
+        *self.clip_recorder.borrow_mut() = Some(ClipRecorder {
+            target: node.clone(),
+            shapes: Vec::new(),
+            region: Vec::new(),
+        });
+    }
+
+    pub fn take_recorded_clip_region(&mut self) -> Vec<ClipShape> {
+        self.clip_recorder
+            .borrow_mut()
+            .take()
+            .map(|recorder| recorder.region)
+            .unwrap_or_default()
+    }
+
+    fn num_recorded_clip_shapes(&self) -> usize {
+        self.clip_recorder
+            .borrow()
+            .as_ref()
+            .map_or(0, |recorder| recorder.shapes.len())
+    }
+
+    /// Records the current path as a clip shape, if a clip region is being recorded.
+    ///
+    /// This must be called right before clipping to the current path.
+    pub fn add_clip_shape(&self) {
+        if let Some(ref mut recorder) = *self.clip_recorder.borrow_mut() {
+            let mut affine = self.initial_affine;
+            affine.invert();
+            let affine = cairo::Matrix::multiply(&self.cr.get_matrix(), &affine);
+
+            let segments = self
+                .cr
+                .copy_path()
                 .iter()
-                .map(|l| l.normalize(values, &params))
+                .map(|segment| match segment {
+                    cairo::PathSegment::MoveTo((x, y)) => {
+                        let (x, y) = affine.transform_point(x, y);
+                        PathSegment::MoveTo(x, y)
+                    }
+                    cairo::PathSegment::LineTo((x, y)) => {
+                        let (x, y) = affine.transform_point(x, y);
+                        PathSegment::LineTo(x, y)
+                    }
+                    cairo::PathSegment::CurveTo((x1, y1), (x2, y2), (x, y)) => {
+                        let (x1, y1) = affine.transform_point(x1, y1);
+                        let (x2, y2) = affine.transform_point(x2, y2);
+                        let (x, y) = affine.transform_point(x, y);
+                        PathSegment::CurveTo(x1, y1, x2, y2, x, y)
+                    }
+                    cairo::PathSegment::ClosePath => PathSegment::ClosePath,
+                })
                 .collect();
 
-            let total_length = normalized_dashes.iter().fold(0.0, |acc, &len| acc + len);
+            recorder.shapes.push(ClipShape {
+                segments,
+                fill_rule: self.cr.get_fill_rule(),
+            });
+        }
+    }
 
-            if total_length > 0.0 {
-                let offset = values.stroke_dashoffset.0.normalize(values, &params);
-                cr.set_dash(&normalized_dashes, offset);
-            } else {
-                cr.set_dash(&[], 0.0);
+    /// Saves the clips in effect as the clip region of `node`, if it is the element
+    /// whose clip region is being recorded.
+    ///
+    /// The shapes from `first_late_shape` on are clips in object space, which only
+    /// get applied after drawing the element and its descendants, so they also get
+    /// added to the clip region of a descendant.
+    fn snapshot_clip_region(&self, node: &RsvgNode, first_late_shape: usize) {
+        if let Some(ref mut recorder) = *self.clip_recorder.borrow_mut() {
+            if recorder.target == *node {
+                recorder.region = recorder.shapes.clone();
+            } else if recorder.target.ancestors().any(|a| a == *node) {
+                let late_shapes = recorder.shapes[first_late_shape..].to_vec();
+                recorder.region.extend(late_shapes);
             }
         }
     }
 
+    /// Runs `draw_fn` with the element whose computed `values` are given as the context
+    /// element, so that `context-fill` and `context-stroke` resolve to its paint.
+    ///
+    /// This is used to draw the markers of a shape.
+    pub fn with_context_paint(
+        &mut self,
+        values: &ComputedValues,
+        draw_fn: &mut dyn FnMut(&mut DrawingCtx) -> Result<BoundingBox, RenderingError>,
+    ) -> Result<BoundingBox, RenderingError> {
+        let context_paint = ContextPaint {
+            fill: self.resolve_context_paint(&values.fill.0, values.color.0),
+            stroke: self.resolve_context_paint(&values.stroke.0, values.color.0),
+        };
+
+        self.context_paint_stack.push(context_paint);
+        let res = draw_fn(self);
+        self.context_paint_stack.pop();
+
+        res
+    }
+
+    /// Computes the stroke parameters of an element with `values`, drawn with `cr`.
+    pub fn stroke_metrics(&self, cr: &cairo::Context, values: &ComputedValues) -> StrokeMetrics {
+        let params = self.get_view_params();
+
+        let width = values.stroke_width.0.normalize(values, &params);
+        let transform = stroke_matrix(cr, values);
+
+        let (dashes, dash_offset) =
+            if let StrokeDasharray(Dasharray::Array(ref dashes)) = values.stroke_dasharray {
+                let normalized_dashes: Vec<f64> = dashes
+                    .iter()
+                    .map(|l| l.normalize(values, &params))
+                    .collect();
+
+                let total_length = normalized_dashes.iter().fold(0.0, |acc, &len| acc + len);
+
+                if total_length > 0.0 {
+                    let offset = values.stroke_dashoffset.0.normalize(values, &params);
+                    (normalized_dashes, offset)
+                } else {
+                    (Vec::new(), 0.0)
+                }
+            } else {
+                (Vec::new(), 0.0)
+            };
+
+        let det = transform.xx * transform.yy - transform.xy * transform.yx;
+
+        StrokeMetrics {
+            width,
+            effective_width: width * det.abs().sqrt(),
+            line_cap: cairo::LineCap::from(values.stroke_line_cap),
+            line_join: cairo::LineJoin::from(values.stroke_line_join),
+            miter_limit: values.stroke_miterlimit.0,
+            dashes,
+            dash_offset,
+            transform,
+        }
+    }
+
+    pub fn setup_cr_for_stroke(&self, cr: &cairo::Context, values: &ComputedValues) {
+        let stroke = self.stroke_metrics(cr, values);
+
+        cr.set_line_width(stroke.width);
+        cr.set_miter_limit(stroke.miter_limit);
+        cr.set_line_cap(stroke.line_cap);
+        cr.set_line_join(stroke.line_join);
+
+        if let StrokeDasharray(Dasharray::Array(_)) = values.stroke_dasharray {
+            cr.set_dash(&stroke.dashes, stroke.dash_offset);
+        }
+    }
+
+    /// Fills and strokes the current path, in the order given by the `paint-order` property.
+    ///
+    /// The `bbox` of the path is computed by the caller, for example with
+    /// `geometry::path_bbox()`; paint servers use it for their coordinate systems, and it
+    /// is returned on success.
+    ///
+    /// When it is time to paint the markers, `paint_markers` gets called; the current path
+    /// is restored afterwards.
     pub fn stroke_and_fill(
         &mut self,
         cr: &cairo::Context,
         values: &ComputedValues,
+        bbox: BoundingBox,
+        paint_markers: &mut dyn FnMut(&mut DrawingCtx) -> Result<(), RenderingError>,
     ) -> Result<BoundingBox, RenderingError> {
         cr.set_antialias(cairo::Antialias::from(values.shape_rendering));
 
         self.setup_cr_for_stroke(cr, values);
 
-        // Update the bbox in the rendering context.  Below, we actually set the
-        // fill/stroke patterns on the cairo_t.  That process requires the
-        // rendering context to have an updated bbox; for example, for the
-        // coordinate system in patterns.
-        let bbox = compute_stroke_and_fill_box(cr, values);
+        // Shapes smaller than a pixel are not worth painting in a preview
+        let skip_painting = self.is_approximate() && is_smaller_than_a_pixel(&bbox);
 
         let current_color = values.color.0;
 
-        let res = self
-            .set_source_paint_server(&values.fill.0, values.fill_opacity.0, &bbox, current_color)
-            .and_then(|had_paint_server| {
-                if had_paint_server {
-                    if values.stroke.0 == PaintServer::None {
-                        cr.fill();
-                    } else {
-                        cr.fill_preserve();
-                    }
+        let res = values
+            .paint_order
+            .targets
+            .iter()
+            .try_for_each(|target| match *target {
+                PaintTarget::Fill | PaintTarget::Stroke if skip_painting => Ok(()),
+
+                PaintTarget::Fill => self
+                    .set_source_paint_server(
+                        &values.fill.0,
+                        values.fill_opacity.0,
+                        &bbox,
+                        current_color,
+                    )
+                    .and_then(|had_paint_server| {
+                        if had_paint_server {
+                            self.resources
+                                .add_filled_area(painted_area(cr, cr.fill_extents()))?;
+                            cr.fill_preserve();
+                        }
+                        Ok(())
+                    }),
+
+                PaintTarget::Stroke => self
+                    .set_source_paint_server(
+                        &values.stroke.0,
+                        values.stroke_opacity.0,
+                        &bbox,
+                        current_color,
+                    )
+                    .and_then(|had_paint_server| {
+                        if had_paint_server {
+                            let extents = with_stroke_matrix(cr, values, || {
+                                painted_area(cr, cr.stroke_extents())
+                            });
+                            self.resources.add_filled_area(extents)?;
+                            with_stroke_matrix(cr, values, || cr.stroke_preserve());
+                        }
+                        Ok(())
+                    }),
+
+                PaintTarget::Markers => {
+                    let path = cr.copy_path();
+                    let res = paint_markers(self);
+                    cr.new_path();
+                    cr.append_path(&path);
+                    res
                 }
-
-                Ok(())
-            })
-            .and_then(|_| {
-                self.set_source_paint_server(
-                    &values.stroke.0,
-                    values.stroke_opacity.0,
-                    &bbox,
-                    current_color,
-                )
-                .and_then(|had_paint_server| {
-                    if had_paint_server {
-                        cr.stroke();
-                    }
-                    Ok(())
-                })
             });
 
         // clear the path in case stroke == fill == None; otherwise
-        // we leave it around from computing the bounding box
+        // we leave it around on the context
         cr.new_path();
 
         res.and_then(|_: ()| Ok(bbox))
@@ -787,39 +1657,66 @@ impl DrawingCtx {
         let cr = self.get_cairo_context();
 
         cr.rectangle(x, y, w, h);
+        self.add_clip_shape();
         cr.clip();
     }
 
+    /// Draws the BackgroundImage for the element being filtered on `surface`.
+    ///
+    /// This is what was drawn before the element, since the start of the nearest ancestor
+    /// with `enable-background: new`, and clipped to that ancestor's background region.
+    /// Without such an ancestor, the BackgroundImage is transparent black.
+    ///
+    /// Note that SVG 2 has deprecated the enable-background property, and replaced it with an
+    /// "isolation" property from the CSS Compositing and Blending spec.
+    ///
+    /// Deprecation:
+    ///   https://www.w3.org/TR/filter-effects-1/#AccessBackgroundImage
+    ///
+    /// BackgroundImage, BackgroundAlpha in the "in" attribute of filter primitives:
+    ///   https://www.w3.org/TR/filter-effects-1/#attr-valuedef-in-backgroundimage
+    ///
+    /// CSS Compositing and Blending, "isolation" property:
+    ///   https://www.w3.org/TR/compositing-1/#isolation
     pub fn get_snapshot(&self, surface: &cairo::ImageSurface) {
-        // TODO: as far as I can tell this should not render elements past the last (topmost) one
-        // with enable-background: new (because technically we shouldn't have been caching them).
-        // Right now there are no enable-background checks whatsoever.
-        //
-        // Addendum: SVG 2 has deprecated the enable-background property, and replaced it with an
-        // "isolation" property from the CSS Compositing and Blending spec.
-        //
-        // Deprecation:
-        //   https://www.w3.org/TR/filter-effects-1/#AccessBackgroundImage
-        //
-        // BackgroundImage, BackgroundAlpha in the "in" attribute of filter primitives:
-        //   https://www.w3.org/TR/filter-effects-1/#attr-valuedef-in-backgroundimage
-        //
-        // CSS Compositing and Blending, "isolation" property:
-        //   https://www.w3.org/TR/compositing-1/#isolation
+        // The element being filtered may itself have `enable-background: new`; its
+        // background is still the one of its ancestors.
+        let root = match self
+            .background_roots
+            .iter()
+            .rev()
+            .find(|root| root.depth < self.cr_stack.len())
+        {
+            Some(root) => root,
+            None => return,
+        };
+
         let cr = cairo::Context::new(&surface);
-        for (depth, draw) in self.cr_stack.iter().enumerate() {
+        let scale = self.filter_scale;
+        let scale_matrix = cairo::Matrix::new(scale, 0.0, 0.0, scale, 0.0, 0.0);
+
+        if let Some(region) = root.region {
+            cr.set_matrix(cairo::Matrix::multiply(&root.affine, &scale_matrix));
+            cr.rectangle(region.x, region.y, region.width, region.height);
+            cr.clip();
+        }
+
+        for (depth, draw) in self.cr_stack.iter().enumerate().skip(root.depth) {
             let affines = CompositingAffines::new(
                 draw.get_matrix(),
                 self.initial_affine_with_offset(),
                 depth,
             );
 
-            cr.set_matrix(affines.for_snapshot);
+            let affine = cairo::Matrix::multiply(&affines.for_snapshot, &scale_matrix);
+
+            cr.set_matrix(affine);
             cr.set_source_surface(&draw.get_target(), 0.0, 0.0);
             cr.paint();
         }
     }
 
+    #[cfg(feature = "raster-images")]
     pub fn lookup_image(&self, href: &str) -> Result<SharedImageSurface, RenderingError> {
         self.document
             .lookup_image(href)
@@ -870,7 +1767,15 @@ impl DrawingCtx {
         };
 
         let values = cascaded.get();
-        let res = if draw && values.is_visible() {
+
+        // An override only changes the element's `display`, like `Handle::set_element_visible()`
+        let visible = match self.element_overrides.visible(node) {
+            Some(true) => values.visibility == Visibility::Visible,
+            Some(false) => false,
+            None => values.is_visible(),
+        };
+
+        let res = if draw && visible {
             node.draw(cascaded, self, clipping)
         } else {
             Ok(self.empty_bbox())
@@ -891,6 +1796,18 @@ impl DrawingCtx {
     }
 }
 
+/// A group with `enable-background: new`
+struct BackgroundRoot {
+    /// Index in the `cr_stack` of the group's temporary surface
+    depth: usize,
+
+    /// Transformation from the group's user space to its temporary surface
+    affine: cairo::Matrix,
+
+    /// Region of the group's user space where the background is kept
+    region: Option<cairo::Rectangle>,
+}
+
 #[derive(Debug)]
 pub struct CompositingAffines {
     pub outside_temporary_surface: cairo::Matrix,
@@ -946,48 +1863,53 @@ impl CompositingAffines {
     }
 }
 
-fn compute_stroke_and_fill_box(cr: &cairo::Context, values: &ComputedValues) -> BoundingBox {
-    let affine = cr.get_matrix();
-
-    let mut bbox = BoundingBox::new(&affine);
-
-    // Dropping the precision of cairo's bezier subdivision, yielding 2x
-    // _rendering_ time speedups, are these rather expensive operations
-    // really needed here? */
-    let backup_tolerance = cr.get_tolerance();
-    cr.set_tolerance(1.0);
-
-    // Bounding box for fill
-    //
-    // Unlike the case for stroke, for fills we always compute the bounding box.
-    // In GNOME we have SVGs for symbolic icons where each icon has a bounding
-    // rectangle with no fill and no stroke, and inside it there are the actual
-    // paths for the icon's shape.  We need to be able to compute the bounding
-    // rectangle's extents, even when it has no fill nor stroke.
-
-    let (x0, y0, x1, y1) = cr.fill_extents();
-    let fb = BoundingBox::new(&affine).with_ink_rect(cairo::Rectangle::from_extents(x0, y0, x1, y1));
-    bbox.insert(&fb);
-
-    // Bounding box for stroke
+/// Whether the ink rectangle of `bbox` is smaller than a pixel in both dimensions.
+fn is_smaller_than_a_pixel(bbox: &BoundingBox) -> bool {
+    bbox.ink_rect.map_or(true, |r| {
+        let r = bbox.affine.transform_rect(&r);
+        r.width < 1.0 && r.height < 1.0
+    })
+}
 
-    if values.stroke.0 != PaintServer::None {
-        let (x0, y0, x1, y1) = cr.stroke_extents();
-        let sb = BoundingBox::new(&affine).with_ink_rect(cairo::Rectangle::from_extents(x0, y0, x1, y1));
-        bbox.insert(&sb);
+/// Returns the matrix in which strokes get computed.
+///
+/// This is the user space, except for `vector-effect: non-scaling-stroke`, where
+/// it is the device space, so that the stroke's width and dashes do not depend
+/// on the current transformation.
+pub fn stroke_matrix(cr: &cairo::Context, values: &ComputedValues) -> cairo::Matrix {
+    match values.vector_effect {
+        VectorEffect::None => cr.get_matrix(),
+        VectorEffect::NonScalingStroke => cairo::Matrix::identity(),
     }
+}
 
-    // objectBoundingBox
-
-    let (x0, y0, x1, y1) = cr.path_extents();
-    let ob = BoundingBox::new(&affine).with_rect(cairo::Rectangle::from_extents(x0, y0, x1, y1));
-    bbox.insert(&ob);
-
-    // restore tolerance
-
-    cr.set_tolerance(backup_tolerance);
+/// Area in device pixels of the `extents` in user space, once they are clipped.
+fn painted_area(cr: &cairo::Context, extents: (f64, f64, f64, f64)) -> f64 {
+    let (x0, y0, x1, y1) = extents;
+    let (cx0, cy0, cx1, cy1) = cr.clip_extents();
+
+    cairo::Rectangle::from_extents(x0, y0, x1, y1)
+        .intersection(&cairo::Rectangle::from_extents(cx0, cy0, cx1, cy1))
+        .map_or(0.0, |r| {
+            let r = cr.get_matrix().transform_rect(&r);
+            r.width * r.height
+        })
+}
 
-    bbox
+/// Runs `stroke_fn` with the cairo matrix set to the `stroke_matrix()`.
+///
+/// The current path is already in device space, so only the stroke's
+/// parameters are affected.  Sources must be set before calling this, as their
+/// pattern space gets locked to the matrix in effect at that time.
+pub fn with_stroke_matrix<T, F>(cr: &cairo::Context, values: &ComputedValues, stroke_fn: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let matrix = cr.get_matrix();
+    cr.set_matrix(stroke_matrix(cr, values));
+    let res = stroke_fn();
+    cr.set_matrix(matrix);
+    res
 }
 
 impl From<StrokeLinejoin> for cairo::LineJoin {
@@ -1137,7 +2059,8 @@ fn node_is_accessed_by_reference(node: &RsvgNode) -> bool {
     use NodeType::*;
 
     match node.borrow().get_type() {
-        ClipPath | Filter | LinearGradient | Marker | Mask | Pattern | RadialGradient => true,
+        ClipPath | Filter | Hatch | LinearGradient | Marker | Mask | MeshGradient | Pattern
+        | RadialGradient => true,
 
         _ => false,
     }
@@ -0,0 +1,105 @@
+//! Opacity and visibility of individual elements, overridden at render time.
+//!
+//! Unlike `Handle::set_element_visible()`, these overrides don't change the document;
+//! they only apply to the renders that use them.  This is for viewers that show a panel
+//! of layers over a document that several views share.
+
+use std::collections::HashMap;
+
+use crate::node::RsvgNode;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+struct ElementOverride {
+    opacity: Option<f64>,
+    visible: Option<bool>,
+}
+
+/// A set of opacity and visibility overrides, keyed by element id
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ElementOverrides {
+    elements: HashMap<String, ElementOverride>,
+}
+
+impl ElementOverrides {
+    pub fn new() -> ElementOverrides {
+        ElementOverrides::default()
+    }
+
+    /// Replaces the `opacity` property of the element with the specified `id`.
+    ///
+    /// The `opacity` is clamped to the `[0.0, 1.0]` range.
+    pub fn set_opacity(&mut self, id: &str, opacity: f64) {
+        self.entry(id).opacity = Some(opacity.max(0.0).min(1.0));
+    }
+
+    /// Shows or hides the element with the specified `id`.
+    ///
+    /// Hiding an element has the same effect as `display: none`; showing it ignores a
+    /// `display: none` from the document.  Its ancestors must be visible for the element
+    /// to be rendered.
+    pub fn set_visible(&mut self, id: &str, visible: bool) {
+        self.entry(id).visible = Some(visible);
+    }
+
+    /// Removes the overrides for the element with the specified `id`.
+    pub fn remove(&mut self, id: &str) {
+        self.elements.remove(strip_hash(id));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn opacity(&self, node: &RsvgNode) -> Option<f64> {
+        self.lookup(node).and_then(|o| o.opacity)
+    }
+
+    pub fn visible(&self, node: &RsvgNode) -> Option<bool> {
+        self.lookup(node).and_then(|o| o.visible)
+    }
+
+    fn entry(&mut self, id: &str) -> &mut ElementOverride {
+        self.elements.entry(strip_hash(id).to_string()).or_default()
+    }
+
+    fn lookup(&self, node: &RsvgNode) -> Option<&ElementOverride> {
+        if self.elements.is_empty() {
+            return None;
+        }
+
+        let data = node.borrow();
+        data.get_id().and_then(|id| self.elements.get(id))
+    }
+}
+
+/// Ids may be written as fragments, like `#foo`, as in the rest of the API.
+fn strip_hash(id: &str) -> &str {
+    if id.starts_with('#') {
+        &id[1..]
+    } else {
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ids_with_and_without_hash() {
+        let mut overrides = ElementOverrides::new();
+        overrides.set_opacity("#foo", 2.0);
+        overrides.set_visible("foo", false);
+
+        assert_eq!(
+            overrides.elements.get("foo"),
+            Some(&ElementOverride {
+                opacity: Some(1.0),
+                visible: Some(false),
+            })
+        );
+
+        overrides.remove("#foo");
+        assert!(overrides.is_empty());
+    }
+}
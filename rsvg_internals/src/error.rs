@@ -2,7 +2,7 @@ use std::error::{self, Error};
 use std::fmt;
 
 use cairo;
-use cssparser::{BasicParseError, BasicParseErrorKind};
+use cssparser::{BasicParseError, BasicParseErrorKind, ParseError, ParseErrorKind};
 use glib;
 use markup5ever::QualName;
 
@@ -111,6 +111,19 @@ impl<'a> From<BasicParseError<'a>> for ValueErrorKind {
     }
 }
 
+impl<'a> From<ParseError<'a, ValueErrorKind>> for ValueErrorKind {
+    fn from(e: ParseError<'a, ValueErrorKind>) -> ValueErrorKind {
+        match e.kind {
+            ParseErrorKind::Basic(kind) => ValueErrorKind::from(BasicParseError {
+                kind,
+                location: e.location,
+            }),
+
+            ParseErrorKind::Custom(e) => e,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum DefsLookupErrorKind {
     HrefError(HrefError),
@@ -126,6 +139,7 @@ pub enum RenderingError {
     InvalidId(DefsLookupErrorKind),
     InvalidHref,
     OutOfMemory,
+    ResourceBudgetExceeded,
     HandleIsNotLoaded,
 }
 
@@ -226,6 +240,22 @@ impl From<HrefError> for ValueErrorKind {
     }
 }
 
+/// Error returned when a CSS selector string cannot be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorError(pub String);
+
+impl error::Error for SelectorError {
+    fn description(&self) -> &str {
+        "invalid selector"
+    }
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid selector: {}", self.0)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum LoadingError {
     NoDataPassedToParser,
@@ -293,6 +323,7 @@ impl error::Error for RenderingError {
             RenderingError::InvalidId(_) => "invalid id",
             RenderingError::InvalidHref => "invalid href",
             RenderingError::OutOfMemory => "out of memory",
+            RenderingError::ResourceBudgetExceeded => "resource budget exceeded",
             RenderingError::HandleIsNotLoaded => "SVG data is not loaded into handle",
         }
     }
@@ -307,6 +338,7 @@ impl fmt::Display for RenderingError {
             | RenderingError::InstancingLimit
             | RenderingError::InvalidHref
             | RenderingError::OutOfMemory
+            | RenderingError::ResourceBudgetExceeded
             | RenderingError::HandleIsNotLoaded => write!(f, "{}", self.description()),
         }
     }
@@ -0,0 +1,297 @@
+//! The value of the `filter` property, which can be a reference to a `<filter>` element
+//! or a list of CSS filter functions like `blur(4px) saturate(0.5)`.
+//!
+//! Filter functions don't need a `<filter>` element; the `filters` module translates
+//! them into the equivalent chain of filter primitives.
+//!
+//! https://www.w3.org/TR/filter-effects-1/#FilterProperty
+
+use cssparser::{self, Parser, Token};
+
+use crate::allowed_url::Fragment;
+use crate::angle::Angle;
+use crate::error::ValueErrorKind;
+use crate::iri::IRI;
+use crate::length::*;
+use crate::parsers::{finite_f32, Parse};
+use crate::property_macros::ToCssValue;
+
+/// The value of the `filter` property
+///
+/// A `url()` can only be used by itself, not as part of a list of filter functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    None,
+
+    /// Reference to a `<filter>` element
+    Url(Fragment),
+
+    /// Filter functions, applied in order
+    Functions(Vec<FilterFunction>),
+}
+
+impl Default for FilterValue {
+    fn default() -> FilterValue {
+        FilterValue::None
+    }
+}
+
+impl FilterValue {
+    pub fn is_none(&self) -> bool {
+        *self == FilterValue::None
+    }
+}
+
+impl Parse for FilterValue {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<FilterValue, ValueErrorKind> {
+        if let Ok(iri) = parser.try_parse(IRI::parse) {
+            return match iri {
+                IRI::None => Ok(FilterValue::None),
+                IRI::Resource(f) => Ok(FilterValue::Url(f)),
+            };
+        }
+
+        let mut functions = vec![FilterFunction::parse(parser)?];
+
+        while !parser.is_exhausted() {
+            functions.push(FilterFunction::parse(parser)?);
+        }
+
+        Ok(FilterValue::Functions(functions))
+    }
+}
+
+impl ToCssValue for FilterValue {
+    fn to_css_value(&self) -> String {
+        match *self {
+            FilterValue::None => "none".to_string(),
+            FilterValue::Url(ref f) => format!("url({})", f),
+            FilterValue::Functions(ref functions) => functions
+                .iter()
+                .map(ToCssValue::to_css_value)
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// A CSS filter function
+///
+/// The amounts of `grayscale()`, `invert()`, `opacity()` and `sepia()` are clamped to
+/// 1 when parsing, as the spec says to do for the computed value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterFunction {
+    Blur(Length<Both>),
+    Brightness(f64),
+    Contrast(f64),
+    DropShadow {
+        color: cssparser::Color,
+        dx: Length<Horizontal>,
+        dy: Length<Vertical>,
+        std_deviation: Length<Both>,
+    },
+    Grayscale(f64),
+    HueRotate(Angle),
+    Invert(f64),
+    Opacity(f64),
+    Saturate(f64),
+    Sepia(f64),
+}
+
+impl Parse for FilterFunction {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<FilterFunction, ValueErrorKind> {
+        let name = parser.expect_function()?.clone();
+
+        parser
+            .parse_nested_block(|p| {
+                let res = match name.as_ref() {
+                    "blur" => parse_blur_args(p),
+                    "brightness" => parse_amount(p, false).map(FilterFunction::Brightness),
+                    "contrast" => parse_amount(p, false).map(FilterFunction::Contrast),
+                    "drop-shadow" => parse_drop_shadow_args(p),
+                    "grayscale" => parse_amount(p, true).map(FilterFunction::Grayscale),
+                    "hue-rotate" => parse_hue_rotate_args(p),
+                    "invert" => parse_amount(p, true).map(FilterFunction::Invert),
+                    "opacity" => parse_amount(p, true).map(FilterFunction::Opacity),
+                    "saturate" => parse_amount(p, false).map(FilterFunction::Saturate),
+                    "sepia" => parse_amount(p, true).map(FilterFunction::Sepia),
+                    _ => Err(ValueErrorKind::parse_error("expected a filter function")),
+                };
+
+                res.map_err(|e| p.new_custom_error(e))
+            })
+            .map_err(ValueErrorKind::from)
+    }
+}
+
+/// Parses the optional `<number> | <percentage>` argument of the color filter functions,
+/// which defaults to 1.
+fn parse_amount(parser: &mut Parser<'_, '_>, clamp: bool) -> Result<f64, ValueErrorKind> {
+    if parser.is_exhausted() {
+        return Ok(1.0);
+    }
+
+    let amount = match *parser.next()? {
+        Token::Number { value, .. } => f64::from(finite_f32(value)?),
+        Token::Percentage { unit_value, .. } => f64::from(finite_f32(unit_value)?),
+        _ => return Err(ValueErrorKind::parse_error("expected number or percentage")),
+    };
+
+    parser.expect_exhausted()?;
+
+    if amount < 0.0 {
+        return Err(ValueErrorKind::value_error("amount can't be negative"));
+    }
+
+    if clamp {
+        Ok(amount.min(1.0))
+    } else {
+        Ok(amount)
+    }
+}
+
+/// Parses a nonnegative length without percentages, for the standard deviation of blurs.
+fn parse_blur_radius(parser: &mut Parser<'_, '_>) -> Result<Length<Both>, ValueErrorKind> {
+    let length = Length::<Both>::parse(parser)?.check_nonnegative()?;
+
+    if length.unit == LengthUnit::Percent {
+        return Err(ValueErrorKind::parse_error(
+            "blur radius can't be a percentage",
+        ));
+    }
+
+    Ok(length)
+}
+
+fn parse_blur_args(parser: &mut Parser<'_, '_>) -> Result<FilterFunction, ValueErrorKind> {
+    let std_deviation = if parser.is_exhausted() {
+        Length::default()
+    } else {
+        parse_blur_radius(parser)?
+    };
+
+    parser.expect_exhausted()?;
+
+    Ok(FilterFunction::Blur(std_deviation))
+}
+
+fn parse_hue_rotate_args(parser: &mut Parser<'_, '_>) -> Result<FilterFunction, ValueErrorKind> {
+    if parser.is_exhausted() {
+        Ok(FilterFunction::HueRotate(Angle::new(0.0)))
+    } else {
+        Ok(FilterFunction::HueRotate(Angle::parse(parser)?))
+    }
+}
+
+fn parse_drop_shadow_args(parser: &mut Parser<'_, '_>) -> Result<FilterFunction, ValueErrorKind> {
+    let mut color = parser.try_parse(cssparser::Color::parse).ok();
+
+    let dx = Length::<Horizontal>::parse(parser)?;
+    let dy = Length::<Vertical>::parse(parser)?;
+    let std_deviation = parser
+        .try_parse(parse_blur_radius)
+        .unwrap_or_else(|_| Length::default());
+
+    if color.is_none() {
+        color = parser.try_parse(cssparser::Color::parse).ok();
+    }
+
+    parser.expect_exhausted()?;
+
+    Ok(FilterFunction::DropShadow {
+        color: color.unwrap_or(cssparser::Color::CurrentColor),
+        dx,
+        dy,
+        std_deviation,
+    })
+}
+
+impl ToCssValue for FilterFunction {
+    fn to_css_value(&self) -> String {
+        match *self {
+            FilterFunction::Blur(ref l) => format!("blur({})", l.to_css_value()),
+            FilterFunction::Brightness(a) => format!("brightness({})", a),
+            FilterFunction::Contrast(a) => format!("contrast({})", a),
+            FilterFunction::DropShadow {
+                ref color,
+                ref dx,
+                ref dy,
+                ref std_deviation,
+            } => format!(
+                "drop-shadow({} {} {} {})",
+                color.to_css_value(),
+                dx.to_css_value(),
+                dy.to_css_value(),
+                std_deviation.to_css_value(),
+            ),
+            FilterFunction::Grayscale(a) => format!("grayscale({})", a),
+            FilterFunction::HueRotate(a) => format!("hue-rotate({}deg)", a.radians().to_degrees()),
+            FilterFunction::Invert(a) => format!("invert({})", a),
+            FilterFunction::Opacity(a) => format!("opacity({})", a),
+            FilterFunction::Saturate(a) => format!("saturate({})", a),
+            FilterFunction::Sepia(a) => format!("sepia({})", a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialized(s: &str) -> String {
+        FilterValue::parse_str(s).unwrap().to_css_value()
+    }
+
+    #[test]
+    fn parses_filter_value() {
+        assert_eq!(FilterValue::parse_str("none"), Ok(FilterValue::None));
+        assert_eq!(
+            FilterValue::parse_str("url(#foo)"),
+            Ok(FilterValue::Url(Fragment::new(None, "foo".to_string())))
+        );
+        assert_eq!(
+            FilterValue::parse_str("blur(4px) saturate(50%)"),
+            Ok(FilterValue::Functions(vec![
+                FilterFunction::Blur(Length::new(4.0, LengthUnit::Px)),
+                FilterFunction::Saturate(0.5),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_filter_functions() {
+        assert_eq!(serialized("blur()"), "blur(0px)");
+        assert_eq!(serialized("brightness()"), "brightness(1)");
+        assert_eq!(serialized("contrast(200%)"), "contrast(2)");
+        assert_eq!(serialized("grayscale(2)"), "grayscale(1)");
+        assert_eq!(serialized("hue-rotate()"), "hue-rotate(0deg)");
+        assert_eq!(
+            serialized("invert(0.5) opacity(25%) sepia()"),
+            "invert(0.5) opacity(0.25) sepia(1)"
+        );
+        assert_eq!(
+            serialized("drop-shadow(2px 2px 3px black)"),
+            "drop-shadow(rgb(0, 0, 0) 2px 2px 3px)"
+        );
+        assert_eq!(
+            serialized("drop-shadow(red 1px 2px)"),
+            "drop-shadow(rgb(255, 0, 0) 1px 2px 0px)"
+        );
+        assert_eq!(
+            serialized("drop-shadow(1px 2px)"),
+            "drop-shadow(currentcolor 1px 2px 0px)"
+        );
+    }
+
+    #[test]
+    fn invalid_filter_functions_yield_error() {
+        assert!(FilterValue::parse_str("").is_err());
+        assert!(FilterValue::parse_str("foo(1)").is_err());
+        assert!(FilterValue::parse_str("blur(-1px)").is_err());
+        assert!(FilterValue::parse_str("blur(10%)").is_err());
+        assert!(FilterValue::parse_str("saturate(-1)").is_err());
+        assert!(FilterValue::parse_str("saturate(1 2)").is_err());
+        assert!(FilterValue::parse_str("drop-shadow(1px)").is_err());
+        assert!(FilterValue::parse_str("blur(1px) url(#foo)").is_err());
+    }
+}
@@ -5,10 +5,12 @@ use crate::drawing_ctx::DrawingCtx;
 use crate::error::NodeError;
 use crate::node::{NodeResult, NodeTrait, RsvgNode};
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::shared_surface::SharedImageSurface;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
 use super::input::Input;
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, PrimitiveWithInput};
 
 /// Enumeration of the possible blending modes.
@@ -80,12 +82,13 @@ impl FilterEffect for FeBlend {
     ) -> Result<FilterResult, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
         let input_2 = ctx.get_input(draw_ctx, self.in2.as_ref())?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
             .add_input(&input_2)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         // If we're combining two alpha-only surfaces, the result is alpha-only. Otherwise the
         // result is whatever the non-alpha-only type we're working on (which can be either sRGB or
@@ -121,10 +124,15 @@ impl FilterEffect for FeBlend {
             output: FilterOutput {
                 surface: SharedImageSurface::new(output_surface, surface_type)?,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links_with_inputs(vec![self.base.in_.clone(), self.in2.clone()])
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         true
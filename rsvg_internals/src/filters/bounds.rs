@@ -73,15 +73,15 @@ impl<'a> BoundsBuilder<'a> {
         self
     }
 
-    /// Returns the final pixel bounds.
+    /// Returns the final exact bounds, which may have fractional pixel coordinates.
     #[inline]
-    pub fn into_irect(self, draw_ctx: &mut DrawingCtx) -> IRect {
+    pub fn into_rect(self, draw_ctx: &mut DrawingCtx) -> cairo::Rectangle {
         let mut bbox = self.apply_properties(draw_ctx);
 
         let effects_region = self.ctx.effects_region();
         bbox.clip(&effects_region);
 
-        bbox.rect.unwrap().into()
+        bbox.rect.unwrap()
     }
 
     /// Returns the final pixel bounds without clipping to the filter effects region.
@@ -0,0 +1,191 @@
+//! Cache of filter results between renders of the same document.
+//!
+//! Re-rendering a document after a small change, like toggling the visibility of one
+//! element, would otherwise run every filter in the document again.  Filters like large
+//! blurs are expensive, so we keep the output of recent filter chains, keyed by
+//! everything that they depend on: the pixels of the source graphic, the element being
+//! filtered, the transformation and the viewport.
+//!
+//! The source graphic is kept along with each result and compared pixel by pixel on
+//! lookup, but only after everything else in the key matched, so that renders which don't
+//! hit the cache don't pay for reading the whole surface.  Since every entry holds two
+//! surfaces, the cache is limited by the number of bytes that they take up rather than by
+//! the number of entries.
+//!
+//! Anything that changes the document's styles or animations must call `clear()`, since
+//! the key doesn't include the filter primitives' own properties.
+//!
+//! Results that depend on elements which the application marked as expected to change
+//! between renders are kept apart from the others.  They would be replaced soon anyway,
+//! so they shouldn't push the other results out of the cache, and changing one of those
+//! elements only needs to drop them with `clear_will_change()`.
+//!
+//! The cache also keeps the `<filter>` nodes that get built for the CSS filter functions,
+//! so that their results can be found again.
+
+use std::collections::VecDeque;
+
+use cairo;
+
+use crate::drawing_ctx::ViewParams;
+use crate::filters::functions::ElementSpec;
+use crate::node::RsvgNode;
+use crate::surface_utils::shared_surface::SharedImageSurface;
+
+/// Maximum number of bytes of surfaces to keep, counting both the results and the source
+/// graphics that they were computed from.
+const MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// Maximum number of bytes of surfaces to keep for results that depend on elements marked
+/// as expected to change.
+const MAX_WILL_CHANGE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Everything that the output of a filter chain depends on, besides the document's styles
+pub struct FilterCacheKey {
+    filter_node: RsvgNode,
+    node: RsvgNode,
+    affine: cairo::Matrix,
+    bbox: Option<cairo::Rectangle>,
+    view_params: (f64, f64, f64, f64),
+    source: SharedImageSurface,
+}
+
+impl FilterCacheKey {
+    pub fn new(
+        filter_node: &RsvgNode,
+        node: &RsvgNode,
+        affine: cairo::Matrix,
+        bbox: Option<cairo::Rectangle>,
+        params: &ViewParams,
+        source: &SharedImageSurface,
+    ) -> FilterCacheKey {
+        FilterCacheKey {
+            filter_node: filter_node.clone(),
+            node: node.clone(),
+            affine,
+            bbox,
+            view_params: (
+                params.dpi_x,
+                params.dpi_y,
+                params.view_box_width,
+                params.view_box_height,
+            ),
+            source: source.clone(),
+        }
+    }
+}
+
+impl PartialEq for FilterCacheKey {
+    fn eq(&self, other: &FilterCacheKey) -> bool {
+        self.filter_node == other.filter_node
+            && self.node == other.node
+            && self.affine == other.affine
+            && self.bbox == other.bbox
+            && self.view_params == other.view_params
+            && same_pixels(&self.source, &other.source)
+    }
+}
+
+fn same_pixels(a: &SharedImageSurface, b: &SharedImageSurface) -> bool {
+    a.width() == b.width()
+        && a.height() == b.height()
+        && a.surface_type() == b.surface_type()
+        && (0..a.height() as u32).all(|y| a.row(y) == b.row(y))
+}
+
+/// Number of bytes taken up by the surfaces of a cache entry
+fn entry_size(key: &FilterCacheKey, surface: &cairo::ImageSurface) -> usize {
+    let source_size = key.source.stride() as usize * key.source.height() as usize;
+    let surface_size = surface.get_stride() as usize * surface.get_height() as usize;
+
+    source_size + surface_size
+}
+
+/// The most recent filter results of a document
+#[derive(Default)]
+pub struct FilterCache {
+    entries: VecDeque<(FilterCacheKey, cairo::ImageSurface)>,
+    entries_size: usize,
+
+    // Results that depend on elements marked as expected to change
+    will_change_entries: VecDeque<(FilterCacheKey, cairo::ImageSurface)>,
+    will_change_entries_size: usize,
+
+    // Filter nodes for the filter functions of an element, and the description they were
+    // built from
+    function_filters: Vec<(RsvgNode, ElementSpec, RsvgNode)>,
+}
+
+impl FilterCache {
+    pub fn lookup(&self, key: &FilterCacheKey) -> Option<cairo::ImageSurface> {
+        self.entries
+            .iter()
+            .chain(self.will_change_entries.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, surface)| surface.clone())
+    }
+
+    /// Adds a filter result; `will_change` says whether it depends on an element that is
+    /// marked as expected to change.
+    ///
+    /// The oldest results are dropped to make room for the new one.  Results that are too
+    /// large to fit in the cache at all are not kept.
+    pub fn insert(&mut self, key: FilterCacheKey, surface: cairo::ImageSurface, will_change: bool) {
+        let (entries, entries_size, max_bytes) = if will_change {
+            (
+                &mut self.will_change_entries,
+                &mut self.will_change_entries_size,
+                MAX_WILL_CHANGE_BYTES,
+            )
+        } else {
+            (&mut self.entries, &mut self.entries_size, MAX_BYTES)
+        };
+
+        let size = entry_size(&key, &surface);
+        if size > max_bytes {
+            return;
+        }
+
+        while *entries_size + size > max_bytes {
+            let (k, s) = entries.pop_front().unwrap();
+            *entries_size -= entry_size(&k, &s);
+        }
+
+        entries.push_back((key, surface));
+        *entries_size += size;
+    }
+
+    pub fn lookup_node(&self, node: &RsvgNode, spec: &ElementSpec) -> Option<RsvgNode> {
+        self.function_filters
+            .iter()
+            .find(|(n, s, _)| n == node && s == spec)
+            .map(|(_, _, filter_node)| filter_node.clone())
+    }
+
+    pub fn insert_node(&mut self, node: &RsvgNode, spec: ElementSpec, filter_node: RsvgNode) {
+        self.function_filters.retain(|(n, _, _)| n != node);
+        self.function_filters
+            .push((node.clone(), spec, filter_node));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.entries_size = 0;
+        self.clear_will_change();
+        self.function_filters.clear();
+    }
+
+    /// Drops only the results that depend on elements marked as expected to change.
+    pub fn clear_will_change(&mut self) {
+        self.will_change_entries.clear();
+        self.will_change_entries_size = 0;
+    }
+}
+
+/// Whether `node`, or any of its ancestors or descendants, is marked as expected to change
+/// between renders.
+pub fn depends_on_will_change(node: &RsvgNode) -> bool {
+    node.ancestors()
+        .chain(node.descendants())
+        .any(|n| n.borrow().get_will_change())
+}
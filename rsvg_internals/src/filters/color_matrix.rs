@@ -8,13 +8,15 @@ use crate::node::{NodeResult, NodeTrait, RsvgNode};
 use crate::number_list::{NumberList, NumberListError, NumberListLength};
 use crate::parsers;
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::{
     iterators::Pixels, shared_surface::SharedImageSurface, ImageSurfaceDataExt, Pixel,
 };
 use crate::util::clamp;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
-use super::{FilterEffect, FilterError, PrimitiveWithInput};
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, FilterJob, PrimitiveWithInput};
 
 /// Color matrix operation types.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -154,68 +156,80 @@ impl NodeTrait for FeColorMatrix {
 }
 
 impl FilterEffect for FeColorMatrix {
-    fn render(
+    fn prepare(
         &self,
         _node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError> {
+    ) -> Result<Option<FilterJob>, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
-        let mut output_surface = ImageSurface::create(
-            cairo::Format::ARgb32,
-            ctx.source_graphic().width(),
-            ctx.source_graphic().height(),
-        )?;
+        let input_surface = input.surface().clone();
+        let (width, height) = (ctx.source_graphic().width(), ctx.source_graphic().height());
+        let matrix = self.matrix;
+        let name = self.base.result.clone();
 
-        let output_stride = output_surface.get_stride() as usize;
-        {
-            let mut output_data = output_surface.get_data().unwrap();
-
-            for (x, y, pixel) in Pixels::new(input.surface(), bounds) {
-                let alpha = f64::from(pixel.a) / 255f64;
-
-                let pixel_vec = if alpha == 0.0 {
-                    Vector5::new(0.0, 0.0, 0.0, 0.0, 1.0)
-                } else {
-                    Vector5::new(
-                        f64::from(pixel.r) / 255f64 / alpha,
-                        f64::from(pixel.g) / 255f64 / alpha,
-                        f64::from(pixel.b) / 255f64 / alpha,
-                        alpha,
-                        1.0,
-                    )
-                };
-                let mut new_pixel_vec = Vector5::zeros();
-                self.matrix.mul_to(&pixel_vec, &mut new_pixel_vec);
+        let job: FilterJob = Box::new(move || {
+            let mut output_surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
 
-                let new_alpha = clamp(new_pixel_vec[3], 0.0, 1.0);
+            let output_stride = output_surface.get_stride() as usize;
+            {
+                let mut output_data = output_surface.get_data().unwrap();
+
+                for (x, y, pixel) in Pixels::new(&input_surface, bounds) {
+                    let alpha = f64::from(pixel.a) / 255f64;
+
+                    let pixel_vec = if alpha == 0.0 {
+                        Vector5::new(0.0, 0.0, 0.0, 0.0, 1.0)
+                    } else {
+                        Vector5::new(
+                            f64::from(pixel.r) / 255f64 / alpha,
+                            f64::from(pixel.g) / 255f64 / alpha,
+                            f64::from(pixel.b) / 255f64 / alpha,
+                            alpha,
+                            1.0,
+                        )
+                    };
+                    let mut new_pixel_vec = Vector5::zeros();
+                    matrix.mul_to(&pixel_vec, &mut new_pixel_vec);
 
-                let premultiply = |x: f64| ((clamp(x, 0.0, 1.0) * new_alpha * 255f64) + 0.5) as u8;
+                    let new_alpha = clamp(new_pixel_vec[3], 0.0, 1.0);
 
-                let output_pixel = Pixel {
-                    r: premultiply(new_pixel_vec[0]),
-                    g: premultiply(new_pixel_vec[1]),
-                    b: premultiply(new_pixel_vec[2]),
-                    a: ((new_alpha * 255f64) + 0.5) as u8,
-                };
+                    let premultiply =
+                        |x: f64| ((clamp(x, 0.0, 1.0) * new_alpha * 255f64) + 0.5) as u8;
 
-                output_data.set_pixel(output_stride, output_pixel, x, y);
+                    let output_pixel = Pixel {
+                        r: premultiply(new_pixel_vec[0]),
+                        g: premultiply(new_pixel_vec[1]),
+                        b: premultiply(new_pixel_vec[2]),
+                        a: ((new_alpha * 255f64) + 0.5) as u8,
+                    };
+
+                    output_data.set_pixel(output_stride, output_pixel, x, y);
+                }
             }
-        }
 
-        Ok(FilterResult {
-            name: self.base.result.clone(),
-            output: FilterOutput {
-                surface: SharedImageSurface::new(output_surface, input.surface().surface_type())?,
-                bounds,
-            },
-        })
+            Ok(FilterResult {
+                name,
+                output: FilterOutput {
+                    surface: SharedImageSurface::new(output_surface, input_surface.surface_type())?,
+                    bounds,
+                    subregion,
+                },
+            })
+        });
+
+        Ok(Some(job))
+    }
+
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
     }
 
     #[inline]
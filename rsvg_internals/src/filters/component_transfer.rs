@@ -9,6 +9,7 @@ use crate::node::{NodeResult, NodeTrait, NodeType, RsvgNode};
 use crate::number_list::{NumberList, NumberListError, NumberListLength};
 use crate::parsers;
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::{
     iterators::Pixels,
     shared_surface::SharedImageSurface,
@@ -18,6 +19,7 @@ use crate::surface_utils::{
 use crate::util::clamp;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, PrimitiveWithInput};
 
 /// The `feComponentTransfer` filter primitive.
@@ -131,6 +133,58 @@ fn gamma(params: &FunctionParameters<'_>, value: f64) -> f64 {
     params.amplitude * value.powf(params.exponent) + params.offset
 }
 
+/// Clamps the result of a component transfer function to `[0, 1]`.
+///
+/// The gamma function can yield NaN, for example `0 * inf` for a zero amplitude
+/// and a negative exponent at zero; such results are taken as zero.
+fn clamp_result(value: f64) -> f64 {
+    if value.is_nan() {
+        0.0
+    } else {
+        clamp(value, 0.0, 1.0)
+    }
+}
+
+/// Precomputed results of a component transfer function for a color channel.
+///
+/// Color components are premultiplied, so the result for a pixel depends on both
+/// its component value and its alpha.  Each row of the table, which corresponds to
+/// an alpha value, is computed the first time that alpha value is seen; images
+/// usually only have a few distinct alpha values.
+struct ColorTable {
+    values: Vec<u8>,
+    computed: [bool; 256],
+}
+
+impl ColorTable {
+    fn new() -> ColorTable {
+        ColorTable {
+            values: vec![0; 256 * 256],
+            computed: [false; 256],
+        }
+    }
+
+    #[inline]
+    fn get<C>(&mut self, compute: &C, value: u8, alpha: u8, new_alpha: f64) -> u8
+    where
+        C: Fn(u8, f64, f64) -> u8,
+    {
+        let row = usize::from(alpha) * 256;
+
+        if !self.computed[usize::from(alpha)] {
+            let a = f64::from(alpha) / 255f64;
+
+            for (v, result) in self.values[row..row + 256].iter_mut().enumerate() {
+                *result = compute(v as u8, a, new_alpha);
+            }
+
+            self.computed[usize::from(alpha)] = true;
+        }
+
+        self.values[row + usize::from(value)]
+    }
+}
+
 trait FeComponentTransferFunc {
     /// Returns the component transfer function.
     fn function(&self) -> Function;
@@ -294,11 +348,12 @@ impl FilterEffect for FeComponentTransfer {
         draw_ctx: &mut DrawingCtx,
     ) -> Result<FilterResult, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         // Create the output surface.
         let mut output_surface = ImageSurface::create(
@@ -360,10 +415,14 @@ impl FilterEffect for FeComponentTransfer {
             move |value, alpha, new_alpha| {
                 let value = f64::from(value) / 255f64;
 
-                let unpremultiplied = if alpha == 0f64 { 0f64 } else { value / alpha };
+                // Rounding in premultiplied surfaces can make this slightly larger than 1.
+                let unpremultiplied = if alpha == 0f64 {
+                    0f64
+                } else {
+                    clamp(value / alpha, 0f64, 1f64)
+                };
 
-                let new_value = compute(&params, unpremultiplied);
-                let new_value = clamp(new_value, 0f64, 1f64);
+                let new_value = clamp_result(compute(&params, unpremultiplied));
 
                 ((new_value * new_alpha * 255f64) + 0.5) as u8
             }
@@ -373,10 +432,17 @@ impl FilterEffect for FeComponentTransfer {
         let compute_g = compute_func::<FeFuncG>(&func_g);
         let compute_b = compute_func::<FeFuncB>(&func_b);
 
-        // Alpha gets special handling since everything else depends on it.
+        // Alpha gets special handling since everything else depends on it.  There are
+        // only 256 possible alpha values, so compute all the results upfront.
         let compute_a = func_a.function();
         let params_a = func_a.function_parameters();
-        let compute_a = |alpha| compute_a(&params_a, alpha);
+        let new_alphas: Vec<f64> = (0..256)
+            .map(|a| clamp_result(compute_a(&params_a, f64::from(a) / 255f64)))
+            .collect();
+
+        let mut table_r = ColorTable::new();
+        let mut table_g = ColorTable::new();
+        let mut table_b = ColorTable::new();
 
         // Do the actual processing.
         let output_stride = output_surface.get_stride() as usize;
@@ -384,13 +450,12 @@ impl FilterEffect for FeComponentTransfer {
             let mut output_data = output_surface.get_data().unwrap();
 
             for (x, y, pixel) in Pixels::new(input.surface(), bounds) {
-                let alpha = f64::from(pixel.a) / 255f64;
-                let new_alpha = compute_a(alpha);
+                let new_alpha = new_alphas[usize::from(pixel.a)];
 
                 let output_pixel = Pixel {
-                    r: compute_r(pixel.r, alpha, new_alpha),
-                    g: compute_g(pixel.g, alpha, new_alpha),
-                    b: compute_b(pixel.b, alpha, new_alpha),
+                    r: table_r.get(&compute_r, pixel.r, pixel.a, new_alpha),
+                    g: table_g.get(&compute_g, pixel.g, pixel.a, new_alpha),
+                    b: table_b.get(&compute_b, pixel.b, pixel.a, new_alpha),
                     a: ((new_alpha * 255f64) + 0.5) as u8,
                 };
 
@@ -403,11 +468,79 @@ impl FilterEffect for FeComponentTransfer {
             output: FilterOutput {
                 surface: SharedImageSurface::new(output_surface, input.surface().surface_type())?,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
+    }
+
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(table_values: &Vec<f64>) -> FunctionParameters<'_> {
+        FunctionParameters {
+            table_values,
+            slope: 1.0,
+            intercept: 0.0,
+            amplitude: 1.0,
+            exponent: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    #[test]
+    fn table_interpolates_between_values() {
+        let values = vec![0.0, 1.0, 0.0];
+        let p = params(&values);
+
+        assert_eq!(table(&p, 0.0), 0.0);
+        assert_eq!(table(&p, 0.25), 0.5);
+        assert_eq!(table(&p, 0.5), 1.0);
+        assert_eq!(table(&p, 1.0), 0.0);
+
+        let values = vec![0.75];
+        assert_eq!(table(&params(&values), 0.5), 0.75);
+    }
+
+    #[test]
+    fn discrete_picks_step_values() {
+        let values = vec![0.2, 0.4, 0.6, 0.8];
+        let p = params(&values);
+
+        assert_eq!(discrete(&p, 0.0), 0.2);
+        assert_eq!(discrete(&p, 0.3), 0.4);
+        assert_eq!(discrete(&p, 0.99), 0.8);
+        assert_eq!(discrete(&p, 1.0), 0.8);
+    }
+
+    #[test]
+    fn gamma_edge_cases_are_clamped() {
+        let values = Vec::new();
+
+        let mut p = params(&values);
+        p.exponent = 0.0;
+        assert_eq!(clamp_result(gamma(&p, 0.0)), 1.0);
+
+        p.exponent = -1.0;
+        assert_eq!(clamp_result(gamma(&p, 0.0)), 1.0);
+
+        p.amplitude = 0.0;
+        assert_eq!(clamp_result(gamma(&p, 0.0)), 0.0);
+
+        p.amplitude = 2.0;
+        p.exponent = 1.0;
+        p.offset = -0.5;
+        assert_eq!(clamp_result(gamma(&p, 0.0)), 0.0);
+        assert_eq!(clamp_result(gamma(&p, 0.5)), 0.5);
+        assert_eq!(clamp_result(gamma(&p, 1.0)), 1.0);
+    }
+}
@@ -18,7 +18,8 @@ use crate::util::clamp;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
 use super::input::Input;
-use super::{FilterEffect, FilterError, PrimitiveWithInput};
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, FilterJob, PrimitiveWithInput};
 
 /// Enumeration of the possible compositing operations.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
@@ -130,20 +131,21 @@ pub fn composite_arithmetic(
 }
 
 impl FilterEffect for FeComposite {
-    fn render(
+    fn prepare(
         &self,
         _node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError> {
+    ) -> Result<Option<FilterJob>, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
         let input_2 = ctx.get_input(draw_ctx, self.in2.as_ref())?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
             .add_input(&input_2)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         // If we're combining two alpha-only surfaces, the result is alpha-only. Otherwise the
         // result is whatever the non-alpha-only type we're working on (which can be either sRGB or
@@ -162,47 +164,62 @@ impl FilterEffect for FeComposite {
             input.surface().surface_type()
         };
 
-        let output_surface = if self.operator == Operator::Arithmetic {
-            let mut output_surface = ImageSurface::create(
-                cairo::Format::ARgb32,
-                input.surface().width(),
-                input.surface().height(),
-            )?;
-
-            composite_arithmetic(
-                input.surface(),
-                input_2.surface(),
-                &mut output_surface,
-                bounds,
-                self.k1,
-                self.k2,
-                self.k3,
-                self.k4,
-            );
-
-            output_surface
-        } else {
-            let output_surface = input_2.surface().copy_surface(bounds)?;
+        let input_surface = input.surface().clone();
+        let input_2_surface = input_2.surface().clone();
+        let operator = self.operator;
+        let (k1, k2, k3, k4) = (self.k1, self.k2, self.k3, self.k4);
+        let name = self.base.result.clone();
 
-            let cr = cairo::Context::new(&output_surface);
-            let r = cairo::Rectangle::from(bounds);
-            cr.rectangle(r.x, r.y, r.width, r.height);
-            cr.clip();
+        let job: FilterJob = Box::new(move || {
+            let output_surface = if operator == Operator::Arithmetic {
+                let mut output_surface = ImageSurface::create(
+                    cairo::Format::ARgb32,
+                    input_surface.width(),
+                    input_surface.height(),
+                )?;
 
-            input.surface().set_as_source_surface(&cr, 0f64, 0f64);
-            cr.set_operator(self.operator.into());
-            cr.paint();
+                composite_arithmetic(
+                    &input_surface,
+                    &input_2_surface,
+                    &mut output_surface,
+                    bounds,
+                    k1,
+                    k2,
+                    k3,
+                    k4,
+                );
 
-            output_surface
-        };
+                output_surface
+            } else {
+                let output_surface = input_2_surface.copy_surface(bounds)?;
+
+                let cr = cairo::Context::new(&output_surface);
+                let r = cairo::Rectangle::from(bounds);
+                cr.rectangle(r.x, r.y, r.width, r.height);
+                cr.clip();
+
+                input_surface.set_as_source_surface(&cr, 0f64, 0f64);
+                cr.set_operator(operator.into());
+                cr.paint();
+
+                output_surface
+            };
+
+            Ok(FilterResult {
+                name,
+                output: FilterOutput {
+                    surface: SharedImageSurface::new(output_surface, surface_type)?,
+                    bounds,
+                    subregion,
+                },
+            })
+        });
+
+        Ok(Some(job))
+    }
 
-        Ok(FilterResult {
-            name: self.base.result.clone(),
-            output: FilterOutput {
-                surface: SharedImageSurface::new(output_surface, surface_type)?,
-                bounds,
-            },
-        })
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links_with_inputs(vec![self.base.in_.clone(), self.in2.clone()])
     }
 
     #[inline]
@@ -1,5 +1,4 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
 use std::f64;
 
 use cairo;
@@ -24,8 +23,11 @@ pub struct FilterOutput {
     /// The surface after the filter primitive was applied.
     pub surface: SharedImageSurface,
 
-    /// The filter primitive subregion.
+    /// The filter primitive subregion, rounded out to whole pixels.
     pub bounds: IRect,
+
+    /// The exact filter primitive subregion, in pixels.
+    pub subregion: cairo::Rectangle,
 }
 
 /// A filter primitive result.
@@ -47,6 +49,19 @@ pub enum FilterInput {
     PrimitiveOutput(FilterOutput),
 }
 
+/// A surface converted to another color space by `FilterContext::get_input()`.
+struct ConvertedSurface {
+    /// The surface that was converted.
+    ///
+    /// Keeping a reference to it ensures that its pixel data is not freed and reused by a
+    /// different surface while the conversion is cached.
+    source: SharedImageSurface,
+    /// The region that was converted.
+    bounds: IRect,
+    /// The result of the conversion, whose type is the target color space.
+    converted: SharedImageSurface,
+}
+
 /// The filter rendering context.
 pub struct FilterContext {
     /// The <filter> node.
@@ -57,12 +72,16 @@ pub struct FilterContext {
     computed_from_node_being_filtered: ComputedValues,
     /// The source graphic surface.
     source_surface: SharedImageSurface,
-    /// Output of the last filter primitive.
-    last_result: Option<FilterOutput>,
-    /// Surfaces of the previous filter primitives by name.
-    previous_results: HashMap<String, FilterOutput>,
+    /// Results of the filter primitives by their position in the chain; `None` for the
+    /// primitives that haven't been rendered yet, or that failed.
+    results: Vec<Option<FilterResult>>,
+    /// Position of the filter primitive being rendered, which only sees the results of the
+    /// primitives before it.
+    current_primitive: usize,
     /// The background surface. Computed lazily.
     background_surface: RefCell<Option<Result<SharedImageSurface, FilterError>>>,
+    /// Whether a primitive rendered an element that is expected to change between renders.
+    uses_will_change: Cell<bool>,
     /// The filter effects region.
     effects_region: BoundingBox,
     /// Whether the currently rendered filter primitive uses linear RGB for color operations.
@@ -70,6 +89,11 @@ pub struct FilterContext {
     /// This affects `get_input()` and `store_result()` which should perform linearization and
     /// unlinearization respectively when this is set to `true`.
     processing_linear_rgb: bool,
+    /// Input surfaces that were converted between sRGB and linear RGB.
+    ///
+    /// Several primitives in a chain often read the same input, like `SourceGraphic` or the
+    /// previous result, and they would otherwise convert it again every time.
+    conversions: RefCell<Vec<ConvertedSurface>>,
 
     /// The filter element affine matrix.
     ///
@@ -155,9 +179,10 @@ impl FilterContext {
             node_bbox,
             computed_from_node_being_filtered: computed_from_node_being_filtered.clone(),
             source_surface,
-            last_result: None,
-            previous_results: HashMap::new(),
+            results: Vec::new(),
+            current_primitive: 0,
             background_surface: RefCell::new(None),
+            uses_will_change: Cell::new(false),
             effects_region: filter.compute_effects_region(
                 computed_from_node_being_filtered,
                 draw_ctx,
@@ -166,6 +191,7 @@ impl FilterContext {
                 f64::from(height),
             ),
             processing_linear_rgb: false,
+            conversions: RefCell::new(Vec::new()),
             _affine: affine,
             paffine,
         }
@@ -177,10 +203,26 @@ impl FilterContext {
         &self.computed_from_node_being_filtered
     }
 
+    /// Sets the position in the chain of the filter primitive that is about to be rendered.
+    ///
+    /// Primitives may be rendered out of order; this makes the inputs of the primitive refer
+    /// to the results of the primitives before it.
+    #[inline]
+    pub fn set_current_primitive(&mut self, index: usize) {
+        self.current_primitive = index;
+    }
+
+    /// Returns the results of the primitives before the current one, latest first.
+    fn previous_results(&self) -> impl Iterator<Item = &FilterResult> {
+        let end = self.current_primitive.min(self.results.len());
+
+        self.results[..end].iter().rev().filter_map(Option::as_ref)
+    }
+
     /// Returns the surface corresponding to the last filter primitive's result.
     #[inline]
     pub fn last_result(&self) -> Option<&FilterOutput> {
-        self.last_result.as_ref()
+        self.previous_results().next().map(|result| &result.output)
     }
 
     /// Returns the surface corresponding to the source graphic.
@@ -197,6 +239,23 @@ impl FilterContext {
             .map_err(FilterError::CairoError)
     }
 
+    /// Whether any primitive used the `BackgroundImage` or `BackgroundAlpha` inputs.
+    ///
+    /// The result of such a filter depends on what was drawn before the element.
+    pub fn uses_background(&self) -> bool {
+        self.background_surface.borrow().is_some()
+    }
+
+    /// Records that a primitive rendered an element that is marked as expected to change,
+    /// like `feImage` does with the element that it references.
+    pub fn set_uses_will_change(&self) {
+        self.uses_will_change.set(true);
+    }
+
+    pub fn uses_will_change(&self) -> bool {
+        self.uses_will_change.get()
+    }
+
     /// Returns the surface corresponding to the background image snapshot.
     pub fn background_image(
         &self,
@@ -250,7 +309,9 @@ impl FilterContext {
     /// Returns the output of the filter primitive by its result name.
     #[inline]
     pub fn filter_output(&self, name: &str) -> Option<&FilterOutput> {
-        self.previous_results.get(name)
+        self.previous_results()
+            .find(|result| result.name.as_ref().map(String::as_str) == Some(name))
+            .map(|result| &result.output)
     }
 
     /// Converts this `FilterContext` into the surface corresponding to the output of the filter
@@ -260,8 +321,18 @@ impl FilterContext {
     // TODO: sRGB conversion should probably be done by the caller.
     #[inline]
     pub fn into_output(self) -> Result<SharedImageSurface, cairo::Status> {
-        match self.last_result {
-            Some(FilterOutput { surface, bounds }) => surface.to_srgb(bounds),
+        let last_output = self
+            .results
+            .into_iter()
+            .rev()
+            .flatten()
+            .next()
+            .map(|result| result.output);
+
+        match last_output {
+            Some(FilterOutput {
+                surface, bounds, ..
+            }) => surface.to_srgb(bounds),
             None => {
                 let empty_surface = cairo::ImageSurface::create(
                     cairo::Format::ARgb32,
@@ -277,15 +348,14 @@ impl FilterContext {
         }
     }
 
-    /// Stores a filter primitive result into the context.
+    /// Stores the result of the filter primitive at position `index` in the chain.
     #[inline]
-    pub fn store_result(&mut self, result: FilterResult) -> Result<(), FilterError> {
-        if let Some(name) = result.name {
-            self.previous_results.insert(name, result.output.clone());
+    pub fn store_result(&mut self, index: usize, result: FilterResult) {
+        if self.results.len() <= index {
+            self.results.resize(index + 1, None);
         }
 
-        self.last_result = Some(result.output);
-        Ok(())
+        self.results[index] = Some(result);
     }
 
     /// Returns the paffine matrix.
@@ -427,15 +497,17 @@ impl FilterContext {
             FilterInput::PrimitiveOutput(FilterOutput {
                 ref surface,
                 ref bounds,
+                ..
             }) => (surface, *bounds),
         };
 
-        let surface = if self.processing_linear_rgb {
-            surface.to_linear_rgb(bounds)
+        let surface_type = if self.processing_linear_rgb {
+            SurfaceType::LinearRgb
         } else {
-            surface.to_srgb(bounds)
+            SurfaceType::SRgb
         };
-        surface
+
+        self.convert_surface(surface, bounds, surface_type)
             .map_err(FilterError::CairoError)
             .map(|surface| match raw {
                 FilterInput::StandardInput(_) => FilterInput::StandardInput(surface),
@@ -445,6 +517,50 @@ impl FilterContext {
             })
     }
 
+    /// Converts `surface` to the color space of `surface_type`, reusing the result of an
+    /// earlier conversion of the same surface and bounds.
+    ///
+    /// Alpha-only surfaces and surfaces which are already in the target color space are
+    /// returned as is.
+    fn convert_surface(
+        &self,
+        surface: &SharedImageSurface,
+        bounds: IRect,
+        surface_type: SurfaceType,
+    ) -> Result<SharedImageSurface, cairo::Status> {
+        if surface.is_alpha_only() || surface.surface_type() == surface_type {
+            return Ok(surface.clone());
+        }
+
+        let cached = self.conversions.borrow().iter().find_map(|c| {
+            if c.source.is_same_surface(surface)
+                && c.bounds == bounds
+                && c.converted.surface_type() == surface_type
+            {
+                Some(c.converted.clone())
+            } else {
+                None
+            }
+        });
+
+        if let Some(converted) = cached {
+            return Ok(converted);
+        }
+
+        let converted = match surface_type {
+            SurfaceType::LinearRgb => surface.to_linear_rgb(bounds)?,
+            _ => surface.to_srgb(bounds)?,
+        };
+
+        self.conversions.borrow_mut().push(ConvertedSurface {
+            source: surface.clone(),
+            bounds,
+            converted: converted.clone(),
+        });
+
+        Ok(converted)
+    }
+
     /// Calls the given closure with linear RGB processing enabled.
     #[inline]
     pub fn with_linear_rgb<T, F: FnOnce(&mut FilterContext) -> T>(&mut self, f: F) -> T {
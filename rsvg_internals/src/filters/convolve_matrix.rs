@@ -17,6 +17,7 @@ use crate::surface_utils::{
 use crate::util::clamp;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, PrimitiveWithInput};
 
 /// The `feConvolveMatrix` filter primitive.
@@ -224,11 +225,12 @@ impl FilterEffect for FeConvolveMatrix {
         draw_ctx: &mut DrawingCtx,
     ) -> Result<FilterResult, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
-        let mut bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let mut bounds = IRect::from(subregion);
         let original_bounds = bounds;
 
         let mut input_surface = if self.preserve_alpha {
@@ -346,10 +348,15 @@ impl FilterEffect for FeConvolveMatrix {
             output: FilterOutput {
                 surface: output_surface,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         true
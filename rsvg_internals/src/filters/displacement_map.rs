@@ -6,9 +6,11 @@ use crate::error::{AttributeResultExt, NodeError};
 use crate::node::{NodeResult, NodeTrait, RsvgNode};
 use crate::parsers;
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::{iterators::Pixels, shared_surface::SharedImageSurface};
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, Input, PrimitiveWithInput};
 
 /// Enumeration of the color channels the displacement map can source.
@@ -76,12 +78,13 @@ impl FilterEffect for FeDisplacementMap {
     ) -> Result<FilterResult, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
         let displacement_input = ctx.get_input(draw_ctx, self.in2.as_ref())?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
             .add_input(&displacement_input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         // Displacement map's values need to be non-premultiplied.
         let displacement_surface = displacement_input.surface().unpremultiply(bounds)?;
@@ -131,10 +134,15 @@ impl FilterEffect for FeDisplacementMap {
             output: FilterOutput {
                 surface: SharedImageSurface::new(output_surface, input.surface().surface_type())?,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links_with_inputs(vec![self.base.in_.clone(), self.in2.clone()])
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         // Performance TODO: this converts in back and forth to linear RGB while technically it's
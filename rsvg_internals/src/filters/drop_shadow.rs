@@ -0,0 +1,159 @@
+use cairo::{self, ImageSurface};
+use cssparser;
+use markup5ever::{expanded_name, local_name, namespace_url, ns};
+
+use crate::drawing_ctx::DrawingCtx;
+use crate::error::{AttributeResultExt, NodeError};
+use crate::node::{CascadedValues, NodeResult, NodeTrait, RsvgNode};
+use crate::parsers;
+use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
+use crate::srgb;
+use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
+
+use super::context::{FilterContext, FilterOutput, FilterResult};
+use super::gaussian_blur::blur;
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, PrimitiveWithInput};
+
+/// The `feDropShadow` filter primitive.
+///
+/// This is a shorthand for blurring the alpha of the input, offsetting it, filling it with
+/// `flood-color` and `flood-opacity`, and drawing the input on top.
+pub struct FeDropShadow {
+    base: PrimitiveWithInput,
+    dx: f64,
+    dy: f64,
+    std_deviation: (f64, f64),
+}
+
+impl Default for FeDropShadow {
+    /// Constructs a new `DropShadow` with the default properties from the spec.
+    #[inline]
+    fn default() -> FeDropShadow {
+        FeDropShadow {
+            base: PrimitiveWithInput::new::<Self>(),
+            dx: 2.0,
+            dy: 2.0,
+            std_deviation: (2.0, 2.0),
+        }
+    }
+}
+
+impl NodeTrait for FeDropShadow {
+    impl_node_as_filter_effect!();
+
+    fn set_atts(&mut self, parent: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        self.base.set_atts(parent, pbag)?;
+
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(svg "dx") => self.dx = parsers::number(value).attribute(attr)?,
+                expanded_name!(svg "dy") => self.dy = parsers::number(value).attribute(attr)?,
+                expanded_name!(svg "stdDeviation") => {
+                    self.std_deviation = parsers::number_optional_number(value)
+                        .attribute(attr.clone())
+                        .and_then(|(x, y)| {
+                            if x >= 0.0 && y >= 0.0 {
+                                Ok((x, y))
+                            } else {
+                                Err(NodeError::value_error(attr, "values can't be negative"))
+                            }
+                        })?
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FilterEffect for FeDropShadow {
+    fn render(
+        &self,
+        node: &RsvgNode,
+        ctx: &FilterContext,
+        draw_ctx: &mut DrawingCtx,
+    ) -> Result<FilterResult, FilterError> {
+        let input = self.base.get_input(ctx, draw_ctx)?;
+        let subregion = self
+            .base
+            .get_bounds(ctx)
+            .add_input(&input)
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
+
+        let (std_x, std_y) = self.std_deviation;
+        let alpha = input.surface().extract_alpha(bounds)?;
+        let shadow = blur(&alpha, bounds, ctx.paffine(), std_x, std_y)?.into_image_surface()?;
+
+        let (ox, oy) = ctx.paffine().transform_distance(self.dx, self.dy);
+
+        let cascaded = CascadedValues::new_from_node(node);
+        let values = cascaded.get();
+
+        let color = match values.flood_color.0 {
+            cssparser::Color::CurrentColor => values.color.0,
+            cssparser::Color::RGBA(rgba) => rgba,
+        };
+        let opacity = (values.flood_opacity.0).0;
+
+        // The shadow gets composited with the input, so its color must be in the same
+        // color space.
+        let surface_type = input.surface().surface_type();
+        let channel = |c: u8| {
+            let c = f64::from(c) / 255.0;
+
+            if surface_type == SurfaceType::LinearRgb {
+                srgb::linearize_f64(c)
+            } else {
+                c
+            }
+        };
+
+        let output_surface = ImageSurface::create(
+            cairo::Format::ARgb32,
+            ctx.source_graphic().width(),
+            ctx.source_graphic().height(),
+        )?;
+
+        {
+            let cr = cairo::Context::new(&output_surface);
+            let r = cairo::Rectangle::from(bounds);
+            cr.rectangle(r.x, r.y, r.width, r.height);
+            cr.clip();
+
+            if opacity > 0.0 {
+                cr.set_source_rgba(
+                    channel(color.red),
+                    channel(color.green),
+                    channel(color.blue),
+                    opacity,
+                );
+                cr.mask_surface(&shadow, ox, oy);
+            }
+
+            input.surface().set_as_source_surface(&cr, 0.0, 0.0);
+            cr.paint();
+        }
+
+        Ok(FilterResult {
+            name: self.base.result.clone(),
+            output: FilterOutput {
+                surface: SharedImageSurface::new(output_surface, surface_type)?,
+                bounds,
+                subregion,
+            },
+        })
+    }
+
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
+    }
+
+    #[inline]
+    fn is_affected_by_color_interpolation_filters(&self) -> bool {
+        true
+    }
+}
@@ -21,6 +21,8 @@ pub enum FilterError {
     LightingInputTooSmall,
     /// Child node was in error.
     ChildNodeInError,
+    /// The filter primitive implements neither `render()` nor `prepare()`.
+    NotImplemented,
 }
 
 impl Error for FilterError {
@@ -35,6 +37,7 @@ impl Error for FilterError {
                 "lighting filter input surface is too small (less than 2×2 pixels)"
             }
             FilterError::ChildNodeInError => "child node was in error",
+            FilterError::NotImplemented => "filter primitive is not implemented",
         }
     }
 
@@ -4,9 +4,11 @@ use cssparser;
 use crate::drawing_ctx::DrawingCtx;
 use crate::node::{CascadedValues, NodeResult, NodeTrait, RsvgNode};
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, Primitive};
 
 /// The `feFlood` filter primitive.
@@ -40,7 +42,8 @@ impl FilterEffect for FeFlood {
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
     ) -> Result<FilterResult, FilterError> {
-        let bounds = self.base.get_bounds(ctx).into_irect(draw_ctx);
+        let subregion = self.base.get_bounds(ctx).into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         let output_surface = ImageSurface::create(
             cairo::Format::ARgb32,
@@ -77,10 +80,15 @@ impl FilterEffect for FeFlood {
             output: FilterOutput {
                 surface: SharedImageSurface::new(output_surface, SurfaceType::SRgb)?,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links_with_inputs(Vec::new())
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         false
@@ -0,0 +1,342 @@
+//! Filter primitive chains for the CSS filter functions.
+//!
+//! A `filter` property like `blur(4px) saturate(0.5)` gets rendered by building a
+//! `<filter>` node with the equivalent primitives, as the Filter Effects spec defines
+//! them, and running it like any other filter.  The nodes are kept in the document's
+//! `FilterCache`, so that the results of previous renders can be reused.
+//!
+//! https://www.w3.org/TR/filter-effects-1/#ShorthandEquivalents
+
+use markup5ever::{namespace_url, ns, LocalName, QualName};
+
+use crate::bbox::BoundingBox;
+use crate::create_node::create_node;
+use crate::drawing_ctx::{DrawingCtx, ViewParams};
+use crate::filter_functions::FilterFunction;
+use crate::node::{NodeCascade, RsvgNode};
+use crate::properties::ComputedValues;
+use crate::property_bag::PropertyBag;
+
+/// Description of a filter primitive element, with its attributes and children
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementSpec {
+    name: &'static str,
+    attributes: Vec<(&'static str, String)>,
+    children: Vec<ElementSpec>,
+}
+
+impl ElementSpec {
+    fn new(name: &'static str, attributes: Vec<(&'static str, String)>) -> ElementSpec {
+        ElementSpec {
+            name,
+            attributes,
+            children: Vec::new(),
+        }
+    }
+
+    fn with_children(self, children: Vec<ElementSpec>) -> ElementSpec {
+        ElementSpec { children, ..self }
+    }
+
+    fn create(&self, parent: Option<&RsvgNode>, draw_ctx: &DrawingCtx) -> RsvgNode {
+        let name = QualName::new(None, ns!(svg), LocalName::from(self.name));
+
+        let attributes: Vec<(QualName, String)> = self
+            .attributes
+            .iter()
+            .map(|&(attr, ref value)| {
+                (
+                    QualName::new(None, ns!(svg), LocalName::from(attr)),
+                    value.clone(),
+                )
+            })
+            .collect();
+
+        let pbag = PropertyBag::new_from_attributes(&attributes);
+
+        let mut node = create_node(&name, &pbag);
+        node.borrow_mut().set_atts(parent, &pbag, draw_ctx.locale());
+
+        for child in &self.children {
+            let child_node = child.create(Some(&node), draw_ctx);
+            node.append(child_node);
+        }
+
+        node
+    }
+}
+
+/// Returns a `<filter>` node that applies the `functions` to `node`.
+///
+/// The node is cascaded from `values`, the computed values of the element being filtered.
+pub fn filter_node_for_functions(
+    functions: &[FilterFunction],
+    node: &RsvgNode,
+    values: &ComputedValues,
+    node_bbox: &BoundingBox,
+    draw_ctx: &DrawingCtx,
+) -> RsvgNode {
+    let params = draw_ctx.get_view_params();
+
+    let mut primitives = Vec::new();
+    let mut margin = (0.0, 0.0);
+    let mut input = String::from("SourceGraphic");
+
+    for (i, function) in functions.iter().enumerate() {
+        let result = format!("function{}", i);
+        let (x, y) =
+            function_primitives(function, values, &params, &input, &result, &mut primitives);
+
+        margin.0 += x;
+        margin.1 += y;
+        input = result;
+    }
+
+    let spec =
+        ElementSpec::new("filter", filter_region(node_bbox, margin)).with_children(primitives);
+
+    if let Some(filter_node) = draw_ctx.filter_cache().lookup_node(node, &spec) {
+        return filter_node;
+    }
+
+    let mut filter_node = spec.create(None, draw_ctx);
+    filter_node.cascade(values);
+
+    draw_ctx
+        .filter_cache()
+        .insert_node(node, spec, filter_node.clone());
+
+    filter_node
+}
+
+/// Computes the filter effects region, which is the element's bounding box grown by
+/// `margin`, the distance in user space that the primitives can move pixels.
+///
+/// The region is in `objectBoundingBox` units; for an empty bounding box, this uses the
+/// default region, since nothing gets rendered anyway.
+fn filter_region(node_bbox: &BoundingBox, margin: (f64, f64)) -> Vec<(&'static str, String)> {
+    let rect = match node_bbox.rect {
+        Some(rect) if rect.width > 0.0 && rect.height > 0.0 => rect,
+        _ => return Vec::new(),
+    };
+
+    let ink_rect = node_bbox.ink_rect.unwrap_or(rect);
+    let (margin_x, margin_y) = margin;
+
+    let x = (ink_rect.x - margin_x - rect.x) / rect.width;
+    let y = (ink_rect.y - margin_y - rect.y) / rect.height;
+    let width = (ink_rect.width + 2.0 * margin_x) / rect.width;
+    let height = (ink_rect.height + 2.0 * margin_y) / rect.height;
+
+    vec![
+        ("x", x.to_string()),
+        ("y", y.to_string()),
+        ("width", width.to_string()),
+        ("height", height.to_string()),
+    ]
+}
+
+/// Appends the primitives for `function` to `primitives`, reading from `input` and
+/// writing to `result`.
+///
+/// Returns how far the primitives can move pixels horizontally and vertically.
+fn function_primitives(
+    function: &FilterFunction,
+    values: &ComputedValues,
+    params: &ViewParams,
+    input: &str,
+    result: &str,
+    primitives: &mut Vec<ElementSpec>,
+) -> (f64, f64) {
+    let io = |mut attributes: Vec<(&'static str, String)>| {
+        attributes.push(("in", input.to_string()));
+        attributes.push(("result", result.to_string()));
+        attributes
+    };
+
+    match *function {
+        FilterFunction::Blur(ref std_deviation) => {
+            let std_deviation = std_deviation.normalize(values, params);
+
+            primitives.push(ElementSpec::new(
+                "feGaussianBlur",
+                io(vec![("stdDeviation", std_deviation.to_string())]),
+            ));
+
+            (3.0 * std_deviation, 3.0 * std_deviation)
+        }
+
+        FilterFunction::Brightness(amount) => {
+            primitives.push(component_transfer(
+                io(Vec::new()),
+                &["feFuncR", "feFuncG", "feFuncB"],
+                vec![
+                    ("type", "linear".to_string()),
+                    ("slope", amount.to_string()),
+                ],
+            ));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::Contrast(amount) => {
+            primitives.push(component_transfer(
+                io(Vec::new()),
+                &["feFuncR", "feFuncG", "feFuncB"],
+                vec![
+                    ("type", "linear".to_string()),
+                    ("slope", amount.to_string()),
+                    ("intercept", (0.5 - 0.5 * amount).to_string()),
+                ],
+            ));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::DropShadow {
+            ref color,
+            ref dx,
+            ref dy,
+            ref std_deviation,
+        } => {
+            let dx = dx.normalize(values, params);
+            let dy = dy.normalize(values, params);
+            let std_deviation = std_deviation.normalize(values, params);
+
+            let color = match *color {
+                cssparser::Color::CurrentColor => values.color.0,
+                cssparser::Color::RGBA(rgba) => rgba,
+            };
+
+            primitives.push(ElementSpec::new(
+                "feDropShadow",
+                io(vec![
+                    ("dx", dx.to_string()),
+                    ("dy", dy.to_string()),
+                    ("stdDeviation", std_deviation.to_string()),
+                    (
+                        "flood-color",
+                        format!("rgb({}, {}, {})", color.red, color.green, color.blue),
+                    ),
+                    ("flood-opacity", color.alpha_f32().to_string()),
+                ]),
+            ));
+
+            (
+                3.0 * std_deviation + dx.abs(),
+                3.0 * std_deviation + dy.abs(),
+            )
+        }
+
+        FilterFunction::Grayscale(amount) => {
+            let g = 1.0 - amount;
+
+            #[cfg_attr(rustfmt, rustfmt_skip)]
+            let matrix = [
+                0.2126 + 0.7874 * g, 0.7152 - 0.7152 * g, 0.0722 - 0.0722 * g, 0.0, 0.0,
+                0.2126 - 0.2126 * g, 0.7152 + 0.2848 * g, 0.0722 - 0.0722 * g, 0.0, 0.0,
+                0.2126 - 0.2126 * g, 0.7152 - 0.7152 * g, 0.0722 + 0.9278 * g, 0.0, 0.0,
+                0.0,                 0.0,                 0.0,                 1.0, 0.0,
+            ];
+
+            primitives.push(color_matrix(io(Vec::new()), &matrix));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::HueRotate(angle) => {
+            primitives.push(ElementSpec::new(
+                "feColorMatrix",
+                io(vec![
+                    ("type", "hueRotate".to_string()),
+                    ("values", angle.radians().to_degrees().to_string()),
+                ]),
+            ));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::Invert(amount) => {
+            primitives.push(component_transfer(
+                io(Vec::new()),
+                &["feFuncR", "feFuncG", "feFuncB"],
+                vec![
+                    ("type", "table".to_string()),
+                    ("tableValues", format!("{} {}", amount, 1.0 - amount)),
+                ],
+            ));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::Opacity(amount) => {
+            primitives.push(component_transfer(
+                io(Vec::new()),
+                &["feFuncA"],
+                vec![
+                    ("type", "table".to_string()),
+                    ("tableValues", format!("0 {}", amount)),
+                ],
+            ));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::Saturate(s) => {
+            // Not with type="saturate", since that only allows values up to 1
+            #[cfg_attr(rustfmt, rustfmt_skip)]
+            let matrix = [
+                0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s, 0.0, 0.0,
+                0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s, 0.0, 0.0,
+                0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s, 0.0, 0.0,
+                0.0,               0.0,               0.0,               1.0, 0.0,
+            ];
+
+            primitives.push(color_matrix(io(Vec::new()), &matrix));
+
+            (0.0, 0.0)
+        }
+
+        FilterFunction::Sepia(amount) => {
+            let g = 1.0 - amount;
+
+            #[cfg_attr(rustfmt, rustfmt_skip)]
+            let matrix = [
+                0.393 + 0.607 * g, 0.769 - 0.769 * g, 0.189 - 0.189 * g, 0.0, 0.0,
+                0.349 - 0.349 * g, 0.686 + 0.314 * g, 0.168 - 0.168 * g, 0.0, 0.0,
+                0.272 - 0.272 * g, 0.534 - 0.534 * g, 0.131 + 0.869 * g, 0.0, 0.0,
+                0.0,               0.0,               0.0,               1.0, 0.0,
+            ];
+
+            primitives.push(color_matrix(io(Vec::new()), &matrix));
+
+            (0.0, 0.0)
+        }
+    }
+}
+
+fn color_matrix(mut attributes: Vec<(&'static str, String)>, matrix: &[f64; 20]) -> ElementSpec {
+    let values = matrix
+        .iter()
+        .map(f64::to_string)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    attributes.push(("type", "matrix".to_string()));
+    attributes.push(("values", values));
+
+    ElementSpec::new("feColorMatrix", attributes)
+}
+
+fn component_transfer(
+    attributes: Vec<(&'static str, String)>,
+    funcs: &[&'static str],
+    func_attributes: Vec<(&'static str, String)>,
+) -> ElementSpec {
+    let children = funcs
+        .iter()
+        .map(|&name| ElementSpec::new(name, func_attributes.clone()))
+        .collect();
+
+    ElementSpec::new("feComponentTransfer", attributes).with_children(children)
+}
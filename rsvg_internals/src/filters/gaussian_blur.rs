@@ -1,6 +1,7 @@
 use std::cmp::min;
 use std::f64;
 
+use cairo;
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
 use nalgebra::{DMatrix, Dynamic, VecStorage};
 
@@ -16,7 +17,8 @@ use crate::surface_utils::{
 };
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
-use super::{FilterEffect, FilterError, PrimitiveWithInput};
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, FilterJob, PrimitiveWithInput};
 
 /// The maximum gaussian blur kernel size.
 ///
@@ -196,62 +198,90 @@ fn gaussian_blur(
     )?)
 }
 
+/// Blurs the `bounds` of `input_surface` with the standard deviations given in the
+/// primitive units of a filter, whose transformation is `paffine`.
+///
+/// This is shared by the primitives that blur, like `feGaussianBlur` and `feDropShadow`.
+pub fn blur(
+    input_surface: &SharedImageSurface,
+    bounds: IRect,
+    paffine: cairo::Matrix,
+    std_x: f64,
+    std_y: f64,
+) -> Result<SharedImageSurface, FilterError> {
+    let (std_x, std_y) = paffine.transform_distance(std_x, std_y);
+
+    // The deviation can become negative here due to the transform.
+    let std_x = std_x.abs();
+    let std_y = std_y.abs();
+
+    // Performance TODO: gaussian blur is frequently used for shadows, operating on SourceAlpha
+    // (so the image is alpha-only). We can use this to not waste time processing the other
+    // channels.
+
+    // Horizontal convolution.
+    let horiz_result_surface = if std_x != 0.0 {
+        // The spec says for deviation >= 2.0 three box blurs can be used as an optimization.
+        if std_x >= 2.0 {
+            three_box_blurs::<Horizontal>(input_surface, bounds, std_x)?
+        } else {
+            gaussian_blur(input_surface, bounds, std_x, false)?
+        }
+    } else {
+        input_surface.clone()
+    };
+
+    // Vertical convolution.
+    if std_y != 0.0 {
+        // The spec says for deviation >= 2.0 three box blurs can be used as an optimization.
+        if std_y >= 2.0 {
+            three_box_blurs::<Vertical>(&horiz_result_surface, bounds, std_y)
+        } else {
+            gaussian_blur(&horiz_result_surface, bounds, std_y, true)
+        }
+    } else {
+        Ok(horiz_result_surface)
+    }
+}
+
 impl FilterEffect for FeGaussianBlur {
-    fn render(
+    fn prepare(
         &self,
         _node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError> {
+    ) -> Result<Option<FilterJob>, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
+        let input_surface = input.surface().clone();
+        let paffine = ctx.paffine();
         let (std_x, std_y) = self.std_deviation;
-        let (std_x, std_y) = ctx.paffine().transform_distance(std_x, std_y);
-
-        // The deviation can become negative here due to the transform.
-        let std_x = std_x.abs();
-        let std_y = std_y.abs();
-
-        // Performance TODO: gaussian blur is frequently used for shadows, operating on SourceAlpha
-        // (so the image is alpha-only). We can use this to not waste time processing the other
-        // channels.
-
-        // Horizontal convolution.
-        let horiz_result_surface = if std_x != 0.0 {
-            // The spec says for deviation >= 2.0 three box blurs can be used as an optimization.
-            if std_x >= 2.0 {
-                three_box_blurs::<Horizontal>(input.surface(), bounds, std_x)?
-            } else {
-                gaussian_blur(input.surface(), bounds, std_x, false)?
-            }
-        } else {
-            input.surface().clone()
-        };
-
-        // Vertical convolution.
-        let output_surface = if std_y != 0.0 {
-            // The spec says for deviation >= 2.0 three box blurs can be used as an optimization.
-            if std_y >= 2.0 {
-                three_box_blurs::<Vertical>(&horiz_result_surface, bounds, std_y)?
-            } else {
-                gaussian_blur(&horiz_result_surface, bounds, std_y, true)?
-            }
-        } else {
-            horiz_result_surface
-        };
-
-        Ok(FilterResult {
-            name: self.base.result.clone(),
-            output: FilterOutput {
-                surface: output_surface,
-                bounds,
-            },
-        })
+        let name = self.base.result.clone();
+
+        let job: FilterJob = Box::new(move || {
+            let output_surface = blur(&input_surface, bounds, paffine, std_x, std_y)?;
+
+            Ok(FilterResult {
+                name,
+                output: FilterOutput {
+                    surface: output_surface,
+                    bounds,
+                    subregion,
+                },
+            })
+        });
+
+        Ok(Some(job))
+    }
+
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
     }
 
     #[inline]
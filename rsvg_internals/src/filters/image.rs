@@ -5,15 +5,19 @@ use crate::allowed_url::{Fragment, Href};
 use crate::aspect_ratio::AspectRatio;
 use crate::drawing_ctx::DrawingCtx;
 use crate::error::{NodeError, RenderingError};
+#[cfg(feature = "raster-images")]
 use crate::float_eq_cairo::ApproxEqCairo;
 use crate::node::{CascadedValues, NodeResult, NodeTrait, RsvgNode};
 use crate::parsers::ParseValue;
 use crate::property_bag::PropertyBag;
 use crate::rect::IRect;
 use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
+#[cfg(feature = "raster-images")]
 use crate::viewbox::ViewBox;
 
+use super::cache::depends_on_will_change;
 use super::context::{FilterContext, FilterOutput, FilterResult};
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, Primitive};
 
 /// The `feImage` filter primitive.
@@ -49,6 +53,10 @@ impl FeImage {
             .map_err(|_| FilterError::InvalidInput)?;
         let drawable = acquired_drawable.get();
 
+        if depends_on_will_change(&drawable) {
+            ctx.set_uses_will_change();
+        }
+
         let surface = ImageSurface::create(
             cairo::Format::ARgb32,
             ctx.source_graphic().width(),
@@ -96,6 +104,7 @@ impl FeImage {
     }
 
     /// Renders the filter if the source is an external image.
+    #[cfg(feature = "raster-images")]
     fn render_external_image(
         &self,
         ctx: &FilterContext,
@@ -155,6 +164,24 @@ impl FeImage {
 
         Ok(output_surface)
     }
+
+    /// Raster images are not supported in this build, so this always fails.
+    #[cfg(not(feature = "raster-images"))]
+    fn render_external_image(
+        &self,
+        _ctx: &FilterContext,
+        _draw_ctx: &DrawingCtx,
+        _bounds: &IRect,
+        _unclipped_bounds: &IRect,
+        href: &Href,
+    ) -> Result<ImageSurface, FilterError> {
+        rsvg_log!(
+            "feImage cannot load {:?} since librsvg was built without raster images",
+            href
+        );
+
+        Err(FilterError::InvalidInput)
+    }
 }
 
 impl NodeTrait for FeImage {
@@ -192,7 +219,8 @@ impl FilterEffect for FeImage {
         draw_ctx: &mut DrawingCtx,
     ) -> Result<FilterResult, FilterError> {
         let bounds_builder = self.base.get_bounds(ctx);
-        let bounds = bounds_builder.into_irect(draw_ctx);
+        let subregion = bounds_builder.into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         if let Some(href) = self.href.as_ref() {
             let output_surface = match href {
@@ -208,6 +236,7 @@ impl FilterEffect for FeImage {
                 output: FilterOutput {
                     surface: SharedImageSurface::new(output_surface, SurfaceType::SRgb)?,
                     bounds,
+                    subregion,
                 },
             })
         } else {
@@ -215,6 +244,10 @@ impl FilterEffect for FeImage {
         }
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links_with_inputs(Vec::new())
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         false
@@ -12,8 +12,8 @@ use crate::util::clamp;
 /// A light source with affine transformations applied.
 pub enum LightSource {
     Distant {
-        azimuth: f64,
-        elevation: f64,
+        /// Unit vector towards the light, which is the same for all the image samples.
+        light_vector: Vector3<f64>,
     },
     Point {
         origin: Vector3<f64>,
@@ -31,15 +31,7 @@ impl LightSource {
     #[inline]
     pub fn vector(&self, x: f64, y: f64, z: f64) -> Vector3<f64> {
         match self {
-            LightSource::Distant { azimuth, elevation } => {
-                let azimuth = azimuth.to_radians();
-                let elevation = elevation.to_radians();
-                Vector3::new(
-                    azimuth.cos() * elevation.cos(),
-                    azimuth.sin() * elevation.cos(),
-                    elevation.sin(),
-                )
-            }
+            LightSource::Distant { light_vector } => *light_vector,
             LightSource::Point { origin } | LightSource::Spot { origin, .. } => {
                 let mut v = origin - Vector3::new(x, y, z);
                 let _ = v.try_normalize_mut(0.0);
@@ -96,9 +88,15 @@ pub struct FeDistantLight {
 
 impl FeDistantLight {
     pub fn transform(&self, _ctx: &FilterContext) -> LightSource {
+        let azimuth = self.azimuth.to_radians();
+        let elevation = self.elevation.to_radians();
+
         LightSource::Distant {
-            azimuth: self.azimuth,
-            elevation: self.elevation,
+            light_vector: Vector3::new(
+                azimuth.cos() * elevation.cos(),
+                azimuth.sin() * elevation.cos(),
+                elevation.sin(),
+            ),
         }
     }
 }
@@ -152,7 +150,6 @@ impl NodeTrait for FePointLight {
     }
 }
 
-#[derive(Default)]
 pub struct FeSpotLight {
     x: f64,
     y: f64,
@@ -164,6 +161,22 @@ pub struct FeSpotLight {
     limiting_cone_angle: Option<f64>,
 }
 
+impl Default for FeSpotLight {
+    fn default() -> FeSpotLight {
+        FeSpotLight {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            points_at_x: 0.0,
+            points_at_y: 0.0,
+            points_at_z: 0.0,
+            // Per the spec, the default specularExponent for spot lights is 1.
+            specular_exponent: 1.0,
+            limiting_cone_angle: None,
+        }
+    }
+}
+
 impl FeSpotLight {
     pub fn transform(&self, ctx: &FilterContext) -> LightSource {
         let (x, y) = ctx.paffine().transform_point(self.x, self.y);
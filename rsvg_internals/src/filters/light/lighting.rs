@@ -12,21 +12,14 @@ use crate::error::{AttributeResultExt, NodeError};
 use crate::filters::{
     context::{FilterContext, FilterOutput, FilterResult},
     light::{
-        bottom_left_normal,
-        bottom_right_normal,
-        bottom_row_normal,
-        interior_normal,
-        left_column_normal,
         light_source::FeDistantLight,
         light_source::FePointLight,
         light_source::FeSpotLight,
         light_source::LightSource,
-        right_column_normal,
-        top_left_normal,
-        top_right_normal,
-        top_row_normal,
         Normal,
+        NormalMap,
     },
+    schedule::PrimitiveLinks,
     FilterEffect,
     FilterError,
     PrimitiveWithInput,
@@ -35,6 +28,7 @@ use crate::node::{CascadedValues, NodeResult, NodeTrait, NodeType, RsvgNode};
 use crate::parsers;
 use crate::property_bag::PropertyBag;
 use crate::property_defs::ColorInterpolationFilters;
+use crate::rect::IRect;
 use crate::surface_utils::{
     shared_surface::{SharedImageSurface, SurfaceType},
     ImageSurfaceDataExt,
@@ -280,18 +274,25 @@ macro_rules! impl_lighting_filter {
                 draw_ctx: &mut DrawingCtx,
             ) -> Result<FilterResult, FilterError> {
                 let input = self.common().base.get_input(ctx, draw_ctx)?;
-                let mut bounds = self
+                let subregion = self
                     .common()
                     .base
                     .get_bounds(ctx)
                     .add_input(&input)
-                    .into_irect(draw_ctx);
+                    .into_rect(draw_ctx);
+                let mut bounds = IRect::from(subregion);
                 let original_bounds = bounds;
 
-                let scale = self
-                    .common()
-                    .kernel_unit_length
-                    .map(|(dx, dy)| ctx.paffine().transform_distance(dx, dy));
+                // The size of the kernel units in pixels.  Measure each axis separately so
+                // that rotations and flips in the transform don't mix them up or make them
+                // negative.
+                let scale = self.common().kernel_unit_length.map(|(dx, dy)| {
+                    let paffine = ctx.paffine();
+                    let (xx, xy) = paffine.transform_distance(dx, 0.0);
+                    let (yx, yy) = paffine.transform_distance(0.0, dy);
+
+                    (xx.hypot(xy), yx.hypot(yy))
+                });
 
                 let cascaded = CascadedValues::new_from_node(node);
                 let values = cascaded.get();
@@ -325,141 +326,48 @@ macro_rules! impl_lighting_filter {
                     input_surface.height(),
                 )?;
 
+                // Both passes below only need the alpha channel of the input.
+                let alpha_surface = input_surface.extract_alpha(bounds)?;
+                let normal_map = NormalMap::new(&alpha_surface, bounds);
+                let surface_scale = self.common().surface_scale;
+
                 let output_stride = output_surface.get_stride() as usize;
                 {
                     let mut output_data = output_surface.get_data().unwrap();
-                    let output_slice = &mut *output_data;
 
-                    let compute_output_pixel =
-                        |mut output_slice: &mut [u8], base_y, x, y, normal: Normal| {
-                            let pixel = input_surface.get_pixel(x, y);
+                    // Each row is independent, so compute them in parallel.
+                    let first_pixel = (bounds.y0 as usize) * output_stride;
+                    let one_past_last_pixel = (bounds.y1 as usize) * output_stride;
 
-                            let scaled_x = f64::from(x) * ox;
+                    output_data[first_pixel..one_past_last_pixel]
+                        .par_chunks_mut(output_stride)
+                        .zip(bounds.y0 as u32..bounds.y1 as u32)
+                        .for_each(|(mut output_row, y)| {
+                            let alpha_row = &alpha_surface.row(y)[bounds.x0 as usize..];
+                            let normals = normal_map.row(y);
                             let scaled_y = f64::from(y) * oy;
-                            let z = f64::from(pixel.a) / 255.0 * self.common().surface_scale;
-                            let light_vector = light_source.vector(scaled_x, scaled_y, z);
-                            let light_color = light_source.color(lighting_color, light_vector);
-
-                            // compute the factor just once for the three colors
-                            let factor = self.compute_factor(normal, light_vector);
-                            let compute = |x| (clamp(factor * f64::from(x), 0.0, 255.0) + 0.5) as u8;
-
-                            let r = compute(light_color.red);
-                            let g = compute(light_color.green);
-                            let b = compute(light_color.blue);
-                            let a = $alpha_func(r, g, b);
-
-                            let output_pixel = Pixel { r, g, b, a };
-
-                            output_slice.set_pixel(output_stride, output_pixel, x, y - base_y);
-                        };
-
-                    // Top left.
-                    compute_output_pixel(
-                        output_slice,
-                        0,
-                        bounds.x0 as u32,
-                        bounds.y0 as u32,
-                        top_left_normal(&input_surface, bounds),
-                    );
-
-                    // Top right.
-                    compute_output_pixel(
-                        output_slice,
-                        0,
-                        bounds.x1 as u32 - 1,
-                        bounds.y0 as u32,
-                        top_right_normal(&input_surface, bounds),
-                    );
-
-                    // Bottom left.
-                    compute_output_pixel(
-                        output_slice,
-                        0,
-                        bounds.x0 as u32,
-                        bounds.y1 as u32 - 1,
-                        bottom_left_normal(&input_surface, bounds),
-                    );
-
-                    // Bottom right.
-                    compute_output_pixel(
-                        output_slice,
-                        0,
-                        bounds.x1 as u32 - 1,
-                        bounds.y1 as u32 - 1,
-                        bottom_right_normal(&input_surface, bounds),
-                    );
-
-                    if bounds.x1 - bounds.x0 >= 3 {
-                        // Top row.
-                        for x in bounds.x0 as u32 + 1..bounds.x1 as u32 - 1 {
-                            compute_output_pixel(
-                                output_slice,
-                                0,
-                                x,
-                                bounds.y0 as u32,
-                                top_row_normal(&input_surface, bounds, x),
-                            );
-                        }
-
-                        // Bottom row.
-                        for x in bounds.x0 as u32 + 1..bounds.x1 as u32 - 1 {
-                            compute_output_pixel(
-                                output_slice,
-                                0,
-                                x,
-                                bounds.y1 as u32 - 1,
-                                bottom_row_normal(&input_surface, bounds, x),
-                            );
-                        }
-                    }
-
-                    if bounds.y1 - bounds.y0 >= 3 {
-                        // Left column.
-                        for y in bounds.y0 as u32 + 1..bounds.y1 as u32 - 1 {
-                            compute_output_pixel(
-                                output_slice,
-                                0,
-                                bounds.x0 as u32,
-                                y,
-                                left_column_normal(&input_surface, bounds, y),
-                            );
-                        }
-
-                        // Right column.
-                        for y in bounds.y0 as u32 + 1..bounds.y1 as u32 - 1 {
-                            compute_output_pixel(
-                                output_slice,
-                                0,
-                                bounds.x1 as u32 - 1,
-                                y,
-                                right_column_normal(&input_surface, bounds, y),
-                            );
-                        }
-                    }
-
-                    if bounds.width() >= 3 && bounds.height() >= 3 {
-                        // Interior pixels.
-                        let first_row = bounds.y0 as u32 + 1;
-                        let one_past_last_row = bounds.y1 as u32 - 1;
-                        let first_pixel = (first_row as usize) * output_stride;
-                        let one_past_last_pixel = (one_past_last_row as usize) * output_stride;
-
-                        output_slice[first_pixel..one_past_last_pixel]
-                            .par_chunks_mut(output_stride)
-                            .zip(first_row..one_past_last_row)
-                            .for_each(|(slice, y)| {
-                                for x in bounds.x0 as u32 + 1..bounds.x1 as u32 - 1 {
-                                    compute_output_pixel(
-                                        slice,
-                                        y,
-                                        x,
-                                        y,
-                                        interior_normal(&input_surface, bounds, x, y),
-                                    );
-                                }
-                            });
-                    }
+
+                            for (i, x) in (bounds.x0 as u32..bounds.x1 as u32).enumerate() {
+                                let scaled_x = f64::from(x) * ox;
+                                let z = f64::from(alpha_row[i]) / 255.0 * surface_scale;
+                                let light_vector = light_source.vector(scaled_x, scaled_y, z);
+                                let light_color = light_source.color(lighting_color, light_vector);
+
+                                // compute the factor just once for the three colors
+                                let factor = self.compute_factor(normals[i], light_vector);
+                                let compute =
+                                    |x| (clamp(factor * f64::from(x), 0.0, 255.0) + 0.5) as u8;
+
+                                let r = compute(light_color.red);
+                                let g = compute(light_color.green);
+                                let b = compute(light_color.blue);
+                                let a = $alpha_func(r, g, b);
+
+                                let output_pixel = Pixel { r, g, b, a };
+
+                                output_row.set_pixel(output_stride, output_pixel, x, 0);
+                            }
+                        });
                 }
 
                 let cascaded = CascadedValues::new_from_node(node);
@@ -492,10 +400,15 @@ macro_rules! impl_lighting_filter {
                     output: FilterOutput {
                         surface: output_surface,
                         bounds,
+                        subregion,
                     },
                 })
             }
 
+            fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+                self.common().base.links()
+            }
+
             #[inline]
             fn is_affected_by_color_interpolation_filters(&self) -> bool {
                 true
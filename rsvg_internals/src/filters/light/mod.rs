@@ -1,5 +1,6 @@
 //! Light filters and nodes.
 use nalgebra::Vector2;
+use rayon::prelude::*;
 
 use crate::rect::IRect;
 use crate::surface_utils::shared_surface::SharedImageSurface;
@@ -251,3 +252,166 @@ pub fn bottom_right_normal(surface: &SharedImageSurface, bounds: IRect) -> Norma
         -top_left - 2 * top + left + 2 * center,
     )
 }
+
+/// Computes and returns the normal vector for any pixel for light filters.
+///
+/// This picks the kernel for the pixel's position in `bounds`: corners and edges
+/// use the reduced kernels from the specification, and the rest use the interior one.
+#[inline]
+pub fn surface_normal(surface: &SharedImageSurface, bounds: IRect, x: u32, y: u32) -> Normal {
+    let left = x as i32 == bounds.x0;
+    let right = x as i32 + 1 == bounds.x1;
+    let top = y as i32 == bounds.y0;
+    let bottom = y as i32 + 1 == bounds.y1;
+
+    match (left, right, top, bottom) {
+        (true, _, true, _) => top_left_normal(surface, bounds),
+        (_, true, true, _) => top_right_normal(surface, bounds),
+        (true, _, _, true) => bottom_left_normal(surface, bounds),
+        (_, true, _, true) => bottom_right_normal(surface, bounds),
+        (_, _, true, _) => top_row_normal(surface, bounds, x),
+        (_, _, _, true) => bottom_row_normal(surface, bounds, x),
+        (true, _, _, _) => left_column_normal(surface, bounds, y),
+        (_, true, _, _) => right_column_normal(surface, bounds, y),
+        _ => interior_normal(surface, bounds, x, y),
+    }
+}
+
+/// The normals of all the pixels in a rectangle of a surface.
+///
+/// The lighting primitives compute the normals in a pass of their own, before the lighting
+/// pass, so that both passes are simple loops over rows which can run in parallel.
+pub struct NormalMap {
+    bounds: IRect,
+    normals: Vec<Normal>,
+}
+
+impl NormalMap {
+    /// Computes the normals of the pixels in `bounds` from the alpha channel of `surface`.
+    ///
+    /// The surface is usually the alpha-only result of `SharedImageSurface::extract_alpha()`,
+    /// which has one byte per pixel instead of four.
+    pub fn new(surface: &SharedImageSurface, bounds: IRect) -> NormalMap {
+        let width = bounds.width() as usize;
+        let height = bounds.height() as usize;
+
+        let zero = Normal {
+            factor: Vector2::zeros(),
+            normal: Vector2::zeros(),
+        };
+
+        let mut normals = vec![zero; width * height];
+
+        normals
+            .par_chunks_mut(width)
+            .zip(bounds.y0 as u32..bounds.y1 as u32)
+            .for_each(|(row, y)| {
+                for (normal, x) in row.iter_mut().zip(bounds.x0 as u32..bounds.x1 as u32) {
+                    *normal = surface_normal(surface, bounds, x, y);
+                }
+            });
+
+        NormalMap { bounds, normals }
+    }
+
+    /// Returns the normals of row `y`, from the left edge of the bounds.
+    #[inline]
+    pub fn row(&self, y: u32) -> &[Normal] {
+        assert!(y as i32 >= self.bounds.y0 && (y as i32) < self.bounds.y1);
+
+        let width = self.bounds.width() as usize;
+        let start = (y as i32 - self.bounds.y0) as usize * width;
+        &self.normals[start..start + width]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::surface_utils::{shared_surface::SurfaceType, ImageSurfaceDataExt, Pixel};
+
+    #[test]
+    fn surface_normal_of_ramp_is_constant() {
+        const WIDTH: i32 = 5;
+        const HEIGHT: i32 = 4;
+
+        let mut surface =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, WIDTH, HEIGHT).unwrap();
+
+        // An alpha ramp along the x axis, which has the same slope everywhere.
+        {
+            let stride = surface.get_stride() as usize;
+            let mut data = surface.get_data().unwrap();
+
+            for y in 0..HEIGHT as u32 {
+                for x in 0..WIDTH as u32 {
+                    let a = (x * 50) as u8;
+                    let pixel = Pixel {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a,
+                    };
+                    data.set_pixel(stride, pixel, x, y);
+                }
+            }
+        }
+
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap();
+        let bounds = IRect::from_size(WIDTH, HEIGHT);
+
+        for y in 0..HEIGHT as u32 {
+            for x in 0..WIDTH as u32 {
+                let normal = surface_normal(&surface, bounds, x, y);
+
+                assert_eq!(f64::from(normal.normal.x) * normal.factor.x, -100.0);
+                assert_eq!(normal.normal.y, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn normal_map_matches_surface_normal() {
+        const WIDTH: i32 = 6;
+        const HEIGHT: i32 = 5;
+
+        let mut surface =
+            cairo::ImageSurface::create(cairo::Format::ARgb32, WIDTH, HEIGHT).unwrap();
+
+        {
+            let stride = surface.get_stride() as usize;
+            let mut data = surface.get_data().unwrap();
+
+            for y in 0..HEIGHT as u32 {
+                for x in 0..WIDTH as u32 {
+                    let a = ((x * 37 + y * 91) % 256) as u8;
+                    let pixel = Pixel {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a,
+                    };
+                    data.set_pixel(stride, pixel, x, y);
+                }
+            }
+        }
+
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap();
+        let bounds = IRect::new(1, 1, WIDTH, HEIGHT - 1);
+
+        let alpha = surface.extract_alpha(bounds).unwrap();
+        let normal_map = NormalMap::new(&alpha, bounds);
+
+        for y in bounds.y0 as u32..bounds.y1 as u32 {
+            let row = normal_map.row(y);
+
+            for (i, x) in (bounds.x0 as u32..bounds.x1 as u32).enumerate() {
+                let expected = surface_normal(&surface, bounds, x, y);
+
+                assert_eq!(row[i].normal, expected.normal);
+                assert_eq!(row[i].factor, expected.factor);
+            }
+        }
+    }
+}
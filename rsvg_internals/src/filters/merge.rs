@@ -9,6 +9,7 @@ use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
 use super::input::Input;
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, Primitive};
 
 /// The `feMerge` filter primitive.
@@ -87,7 +88,15 @@ impl FeMergeNode {
             input.surface().surface_type()
         };
 
-        let output_surface = output_surface.into_image_surface()?;
+        // Alpha-only results may be in A8 surfaces, which can't hold the colors of the input.
+        let output_surface = if output_surface.format() == cairo::Format::ARgb32 {
+            output_surface.into_image_surface()?
+        } else {
+            output_surface.copy_surface(IRect::from_size(
+                output_surface.width(),
+                output_surface.height(),
+            ))?
+        };
 
         {
             let cr = cairo::Context::new(&output_surface);
@@ -127,7 +136,8 @@ impl FilterEffect for FeMerge {
             )?;
             bounds = bounds.add_input(&input);
         }
-        let bounds = bounds.into_irect(draw_ctx);
+        let subregion = bounds.into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         // Now merge them all.
         let mut output_surface = None;
@@ -160,10 +170,21 @@ impl FilterEffect for FeMerge {
             output: FilterOutput {
                 surface: output_surface,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, node: &RsvgNode) -> PrimitiveLinks {
+        let inputs = node
+            .children()
+            .filter(|c| c.borrow().get_type() == NodeType::FeMergeNode)
+            .map(|c| c.borrow().get_impl::<FeMergeNode>().in_.clone())
+            .collect();
+
+        self.base.links_with_inputs(inputs)
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         true
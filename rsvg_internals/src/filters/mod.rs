@@ -1,8 +1,9 @@
 use std::ops::Deref;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use cairo;
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
+use rayon::prelude::*;
 
 use crate::bbox::BoundingBox;
 use crate::coord_units::CoordUnits;
@@ -15,11 +16,15 @@ use crate::parsers::ParseValue;
 use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
 use crate::property_defs::ColorInterpolationFilters;
+use crate::rect::RectangleExt;
 use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
 
 mod bounds;
 use self::bounds::BoundsBuilder;
 
+pub mod cache;
+use self::cache::{depends_on_will_change, FilterCacheKey};
+
 pub mod context;
 use self::context::{FilterContext, FilterInput, FilterResult};
 
@@ -29,18 +34,50 @@ use self::error::FilterError;
 mod input;
 use self::input::Input;
 
+pub mod schedule;
+use self::schedule::PrimitiveLinks;
+
+/// The pixel operations of a filter primitive, ready to run on any thread.
+pub type FilterJob = Box<dyn FnOnce() -> Result<FilterResult, FilterError> + Send>;
+
 /// A filter primitive interface.
 pub trait FilterEffect: NodeTrait {
     /// Renders this filter primitive.
     ///
     /// If this filter primitive can't be rendered for whatever reason (for instance, a required
     /// property hasn't been provided), an error is returned.
+    ///
+    /// Primitives which implement `prepare()` don't need to implement this.  For those which
+    /// implement neither, this returns `FilterError::NotImplemented`.
     fn render(
         &self,
         node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError>;
+    ) -> Result<FilterResult, FilterError> {
+        match self.prepare(node, ctx, draw_ctx)? {
+            Some(job) => job(),
+            None => Err(FilterError::NotImplemented),
+        }
+    }
+
+    /// Prepares this filter primitive for rendering on another thread.
+    ///
+    /// This does the work that needs the drawing context, like getting the inputs and
+    /// computing the subregion, and returns the pixel operations as a job.  Primitives which
+    /// return `None` get rendered with `render()` on the calling thread instead.
+    fn prepare(
+        &self,
+        _node: &RsvgNode,
+        _ctx: &FilterContext,
+        _draw_ctx: &mut DrawingCtx,
+    ) -> Result<Option<FilterJob>, FilterError> {
+        Ok(None)
+    }
+
+    /// Returns the inputs and the result name of this filter primitive, to find out which
+    /// primitives in a chain can be rendered in parallel.
+    fn links(&self, node: &RsvgNode) -> PrimitiveLinks;
 
     /// Returns `true` if this filter primitive is affected by the `color-interpolation-filters`
     /// property.
@@ -64,7 +101,9 @@ pub mod component_transfer;
 pub mod composite;
 pub mod convolve_matrix;
 pub mod displacement_map;
+pub mod drop_shadow;
 pub mod flood;
+pub mod functions;
 pub mod gaussian_blur;
 pub mod image;
 pub mod light;
@@ -107,6 +146,15 @@ impl Primitive {
     fn get_bounds<'a>(&self, ctx: &'a FilterContext) -> BoundsBuilder<'a> {
         BoundsBuilder::new(ctx, self.x, self.y, self.width, self.height)
     }
+
+    /// Returns the `PrimitiveLinks` for a primitive with the specified inputs.
+    #[inline]
+    fn links_with_inputs(&self, inputs: Vec<Option<Input>>) -> PrimitiveLinks {
+        PrimitiveLinks {
+            inputs,
+            result: self.result.clone(),
+        }
+    }
 }
 
 impl NodeTrait for Primitive {
@@ -208,6 +256,12 @@ impl PrimitiveWithInput {
     ) -> Result<FilterInput, FilterError> {
         ctx.get_input(draw_ctx, self.in_.as_ref())
     }
+
+    /// Returns the `PrimitiveLinks` for a primitive whose only input is `in`.
+    #[inline]
+    fn links(&self) -> PrimitiveLinks {
+        self.base.links_with_inputs(vec![self.in_.clone()])
+    }
 }
 
 impl NodeTrait for PrimitiveWithInput {
@@ -236,7 +290,7 @@ impl Deref for PrimitiveWithInput {
 
 /// Creates a `SharedImageSurface` from an `ImageSurface`, even if the former
 /// does not have a reference count of 1.
-fn copy_to_shared_surface(
+pub fn copy_to_shared_surface(
     surface: &cairo::ImageSurface,
 ) -> Result<SharedImageSurface, cairo::Status> {
     let copy = cairo::ImageSurface::create(
@@ -252,11 +306,15 @@ fn copy_to_shared_surface(
     SharedImageSurface::new(copy, SurfaceType::SRgb)
 }
 
-/// Applies a filter and returns the resulting surface.
+/// Applies a filter to `source_surface` and returns the resulting surface.
+///
+/// The results are cached in the document, so that rendering the same element again with
+/// the same source graphic doesn't run the filter primitives again.
 pub fn render(
     filter_node: &RsvgNode,
+    node_being_filtered: &RsvgNode,
     computed_from_node_being_filtered: &ComputedValues,
-    source: &cairo::ImageSurface,
+    source_surface: SharedImageSurface,
     draw_ctx: &mut DrawingCtx,
     node_bbox: BoundingBox,
 ) -> Result<cairo::ImageSurface, RenderingError> {
@@ -264,9 +322,19 @@ pub fn render(
     assert_eq!(filter_node.borrow().get_type(), NodeType::Filter);
     assert!(!filter_node.borrow().is_in_error());
 
-    // The source surface has multiple references. We need to copy it to a new surface to have a
-    // unique reference to be able to safely access the pixel data.
-    let source_surface = copy_to_shared_surface(source)?;
+    let cache_key = FilterCacheKey::new(
+        filter_node,
+        node_being_filtered,
+        draw_ctx.get_cairo_context().get_matrix(),
+        node_bbox.rect,
+        &draw_ctx.get_view_params(),
+        &source_surface,
+    );
+
+    if let Some(surface) = draw_ctx.filter_cache().lookup(&cache_key) {
+        rsvg_log!("(using cached result of filter {})", filter_node);
+        return Ok(surface);
+    }
 
     let mut filter_ctx = FilterContext::new(
         filter_node,
@@ -278,7 +346,16 @@ pub fn render(
 
     // If paffine is non-invertible, we won't draw anything. Also bbox combining in bounds
     // computations will panic due to non-invertible martrix.
-    if filter_ctx.paffine().try_invert().is_err() {
+    //
+    // Likewise, an empty filter effects region, as happens with
+    // filterUnits="objectBoundingBox" on an element whose bounding box has zero width or
+    // height, means that the element does not get rendered.
+    if filter_ctx.paffine().try_invert().is_err()
+        || filter_ctx
+            .effects_region()
+            .rect
+            .map_or(true, |r| r.is_empty())
+    {
         return Ok(filter_ctx.into_output()?.into_image_surface()?);
     }
 
@@ -306,43 +383,116 @@ pub fn render(
             };
 
             (c, linear_rgb)
-        });
-
-    for (c, linear_rgb) in primitives {
-        let node_data = c.borrow();
-        let filter = node_data.get_node_trait().as_filter_effect().unwrap();
-
-        let mut render = |filter_ctx: &mut FilterContext| {
-            if let Err(err) = filter
-                .render(&c, filter_ctx, draw_ctx)
-                .and_then(|result| filter_ctx.store_result(result))
-            {
-                rsvg_log!("(filter primitive {} returned an error: {})", c, err);
-
-                // Exit early on Cairo errors. Continue rendering otherwise.
-                if let FilterError::CairoError(status) = err {
-                    return Err(status);
-                }
-            }
+        })
+        .collect::<Vec<_>>();
 
-            Ok(())
-        };
+    if let Some(r) = filter_ctx.effects_region().rect {
+        draw_ctx.resources().add_filter_work(r.width * r.height, primitives.len())?;
+    }
+
+    let links = primitives
+        .iter()
+        .map(|(c, _)| {
+            let node_data = c.borrow();
+            let filter = node_data.get_node_trait().as_filter_effect().unwrap();
+            filter.links(c)
+        })
+        .collect::<Vec<_>>();
+
+    // Primitives in the same batch don't depend on each other.  Those which can be
+    // prepared as jobs get their pixel operations run in parallel; the rest get rendered
+    // here, one after the other.
+    for batch in schedule::batches(&schedule::dependencies(&links)) {
+        let mut jobs = Vec::new();
+
+        for i in batch {
+            let (ref c, linear_rgb) = primitives[i];
+            let node_data = c.borrow();
+            let filter = node_data.get_node_trait().as_filter_effect().unwrap();
+
+            filter_ctx.set_current_primitive(i);
+
+            let mut render = |filter_ctx: &mut FilterContext| {
+                let start = Instant::now();
+
+                let res = match filter.prepare(c, filter_ctx, draw_ctx) {
+                    Ok(Some(job)) => {
+                        jobs.push((i, job));
+                        return Ok(());
+                    }
+
+                    Ok(None) => filter.render(c, filter_ctx, draw_ctx),
+                    Err(err) => Err(err),
+                };
+
+                store_primitive_result(filter_ctx, i, c, res, start.elapsed())
+            };
+
+            if filter.is_affected_by_color_interpolation_filters() && linear_rgb {
+                filter_ctx.with_linear_rgb(render)?;
+            } else {
+                render(&mut filter_ctx)?;
+            }
+        }
 
-        let start = Instant::now();
+        let results = jobs
+            .into_par_iter()
+            .map(|(i, job)| {
+                let start = Instant::now();
+                let res = job();
+                (i, res, start.elapsed())
+            })
+            .collect::<Vec<_>>();
 
-        if filter.is_affected_by_color_interpolation_filters() && linear_rgb {
-            filter_ctx.with_linear_rgb(render)?;
-        } else {
-            render(&mut filter_ctx)?;
+        for (i, res, elapsed) in results {
+            store_primitive_result(&mut filter_ctx, i, &primitives[i].0, res, elapsed)?;
         }
+    }
 
-        let elapsed = start.elapsed();
-        rsvg_log!(
-            "(rendered filter primitive {} in\n    {} seconds)",
-            c,
-            elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9
-        );
+    let uses_background = filter_ctx.uses_background();
+    let will_change = filter_ctx.uses_will_change()
+        || depends_on_will_change(node_being_filtered)
+        || depends_on_will_change(filter_node);
+    let surface = filter_ctx.into_output()?.into_image_surface()?;
+
+    if !uses_background {
+        draw_ctx
+            .filter_cache()
+            .insert(cache_key, surface.clone(), will_change);
     }
 
-    Ok(filter_ctx.into_output()?.into_image_surface()?)
+    Ok(surface)
+}
+
+/// Stores the result of the filter primitive at position `index`, and logs how long it took.
+///
+/// Errors from the primitive are logged and ignored, so that the rest of the chain can
+/// still be rendered, except for Cairo errors.
+fn store_primitive_result(
+    filter_ctx: &mut FilterContext,
+    index: usize,
+    node: &RsvgNode,
+    res: Result<FilterResult, FilterError>,
+    elapsed: Duration,
+) -> Result<(), cairo::Status> {
+    match res {
+        Ok(result) => filter_ctx.store_result(index, result),
+
+        Err(err) => {
+            rsvg_log!("(filter primitive {} returned an error: {})", node, err);
+
+            // Exit early on Cairo errors. Continue rendering otherwise.
+            if let FilterError::CairoError(status) = err {
+                return Err(status);
+            }
+        }
+    }
+
+    rsvg_log!(
+        "(rendered filter primitive {} in\n    {} seconds)",
+        node,
+        elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9
+    );
+
+    Ok(())
 }
@@ -18,7 +18,8 @@ use crate::surface_utils::{
 };
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
-use super::{FilterEffect, FilterError, PrimitiveWithInput};
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, FilterJob, PrimitiveWithInput};
 
 /// Enumeration of the possible morphology operations.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
@@ -75,18 +76,19 @@ impl NodeTrait for FeMorphology {
 }
 
 impl FilterEffect for FeMorphology {
-    fn render(
+    fn prepare(
         &self,
         _node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError> {
+    ) -> Result<Option<FilterJob>, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         let (rx, ry) = self.radius;
         let (rx, ry) = ctx.paffine().transform_distance(rx, ry);
@@ -95,63 +97,73 @@ impl FilterEffect for FeMorphology {
         let rx = rx.abs();
         let ry = ry.abs();
 
-        let mut output_surface = ImageSurface::create(
-            cairo::Format::ARgb32,
-            ctx.source_graphic().width(),
-            ctx.source_graphic().height(),
-        )?;
-
-        let output_stride = output_surface.get_stride() as usize;
-        {
-            let mut output_data = output_surface.get_data().unwrap();
-
-            for (x, y, _pixel) in Pixels::new(input.surface(), bounds) {
-                // Compute the kernel rectangle bounds.
-                let kernel_bounds = IRect::new(
-                    (f64::from(x) - rx).floor() as i32,
-                    (f64::from(y) - ry).floor() as i32,
-                    (f64::from(x) + rx).ceil() as i32 + 1,
-                    (f64::from(y) + ry).ceil() as i32 + 1,
-                );
-
-                // Compute the new pixel values.
-                let initial = match self.operator {
-                    Operator::Erode => u8::max_value(),
-                    Operator::Dilate => u8::min_value(),
-                };
-
-                let mut output_pixel = Pixel {
-                    r: initial,
-                    g: initial,
-                    b: initial,
-                    a: initial,
-                };
-
-                for (_x, _y, pixel) in
-                    PixelRectangle::new(&input.surface(), bounds, kernel_bounds, EdgeMode::None)
-                {
-                    let op = match self.operator {
-                        Operator::Erode => min,
-                        Operator::Dilate => max,
+        let input_surface = input.surface().clone();
+        let (width, height) = (ctx.source_graphic().width(), ctx.source_graphic().height());
+        let operator = self.operator;
+        let name = self.base.result.clone();
+
+        let job: FilterJob = Box::new(move || {
+            let mut output_surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+            let output_stride = output_surface.get_stride() as usize;
+            {
+                let mut output_data = output_surface.get_data().unwrap();
+
+                for (x, y, _pixel) in Pixels::new(&input_surface, bounds) {
+                    // Compute the kernel rectangle bounds.
+                    let kernel_bounds = IRect::new(
+                        (f64::from(x) - rx).floor() as i32,
+                        (f64::from(y) - ry).floor() as i32,
+                        (f64::from(x) + rx).ceil() as i32 + 1,
+                        (f64::from(y) + ry).ceil() as i32 + 1,
+                    );
+
+                    // Compute the new pixel values.
+                    let initial = match operator {
+                        Operator::Erode => u8::max_value(),
+                        Operator::Dilate => u8::min_value(),
                     };
 
-                    output_pixel.r = op(output_pixel.r, pixel.r);
-                    output_pixel.g = op(output_pixel.g, pixel.g);
-                    output_pixel.b = op(output_pixel.b, pixel.b);
-                    output_pixel.a = op(output_pixel.a, pixel.a);
-                }
+                    let mut output_pixel = Pixel {
+                        r: initial,
+                        g: initial,
+                        b: initial,
+                        a: initial,
+                    };
 
-                output_data.set_pixel(output_stride, output_pixel, x, y);
+                    for (_x, _y, pixel) in
+                        PixelRectangle::new(&input_surface, bounds, kernel_bounds, EdgeMode::None)
+                    {
+                        let op = match operator {
+                            Operator::Erode => min,
+                            Operator::Dilate => max,
+                        };
+
+                        output_pixel.r = op(output_pixel.r, pixel.r);
+                        output_pixel.g = op(output_pixel.g, pixel.g);
+                        output_pixel.b = op(output_pixel.b, pixel.b);
+                        output_pixel.a = op(output_pixel.a, pixel.a);
+                    }
+
+                    output_data.set_pixel(output_stride, output_pixel, x, y);
+                }
             }
-        }
 
-        Ok(FilterResult {
-            name: self.base.result.clone(),
-            output: FilterOutput {
-                surface: SharedImageSurface::new(output_surface, input.surface().surface_type())?,
-                bounds,
-            },
-        })
+            Ok(FilterResult {
+                name,
+                output: FilterOutput {
+                    surface: SharedImageSurface::new(output_surface, input_surface.surface_type())?,
+                    bounds,
+                    subregion,
+                },
+            })
+        });
+
+        Ok(Some(job))
+    }
+
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
     }
 
     #[inline]
@@ -6,10 +6,12 @@ use crate::error::AttributeResultExt;
 use crate::node::{NodeResult, NodeTrait, RsvgNode};
 use crate::parsers;
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::shared_surface::SharedImageSurface;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
-use super::{FilterEffect, FilterError, PrimitiveWithInput};
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, FilterJob, PrimitiveWithInput};
 
 /// The `feOffset` filter primitive.
 pub struct FeOffset {
@@ -49,49 +51,59 @@ impl NodeTrait for FeOffset {
 }
 
 impl FilterEffect for FeOffset {
-    fn render(
+    fn prepare(
         &self,
         _node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError> {
+    ) -> Result<Option<FilterJob>, FilterError> {
         let input = self.base.get_input(ctx, draw_ctx)?;
-        let bounds = self
+        let subregion = self
             .base
             .get_bounds(ctx)
             .add_input(&input)
-            .into_irect(draw_ctx);
+            .into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         let (ox, oy) = ctx.paffine().transform_distance(self.dx, self.dy);
 
-        let output_surface = ImageSurface::create(
-            cairo::Format::ARgb32,
-            ctx.source_graphic().width(),
-            ctx.source_graphic().height(),
-        )?;
-
-        // output_bounds contains all pixels within bounds,
-        // for which (x - ox) and (y - oy) also lie within bounds.
-        if let Some(output_bounds) = bounds
-            .translate((ox as i32, oy as i32))
-            .intersection(&bounds)
-        {
-            let cr = cairo::Context::new(&output_surface);
-            let r = cairo::Rectangle::from(output_bounds);
-            cr.rectangle(r.x, r.y, r.width, r.height);
-            cr.clip();
-
-            input.surface().set_as_source_surface(&cr, ox, oy);
-            cr.paint();
-        }
+        let input_surface = input.surface().clone();
+        let (width, height) = (ctx.source_graphic().width(), ctx.source_graphic().height());
+        let name = self.base.result.clone();
+
+        let job: FilterJob = Box::new(move || {
+            let output_surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+            // output_bounds contains all pixels within bounds,
+            // for which (x - ox) and (y - oy) also lie within bounds.
+            if let Some(output_bounds) = bounds
+                .translate((ox as i32, oy as i32))
+                .intersection(&bounds)
+            {
+                let cr = cairo::Context::new(&output_surface);
+                let r = cairo::Rectangle::from(output_bounds);
+                cr.rectangle(r.x, r.y, r.width, r.height);
+                cr.clip();
+
+                input_surface.set_as_source_surface(&cr, ox, oy);
+                cr.paint();
+            }
+
+            Ok(FilterResult {
+                name,
+                output: FilterOutput {
+                    surface: SharedImageSurface::new(output_surface, input_surface.surface_type())?,
+                    bounds,
+                    subregion,
+                },
+            })
+        });
+
+        Ok(Some(job))
+    }
 
-        Ok(FilterResult {
-            name: self.base.result.clone(),
-            output: FilterOutput {
-                surface: SharedImageSurface::new(output_surface, input.surface().surface_type())?,
-                bounds,
-            },
-        })
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
     }
 
     #[inline]
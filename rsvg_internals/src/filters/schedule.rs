@@ -0,0 +1,152 @@
+//! Scheduling of the filter primitives in a chain.
+//!
+//! A filter chain is really a graph: each primitive reads the outputs of earlier primitives,
+//! either by name with its `in` and `in2` attributes, or implicitly the output of the
+//! primitive before it.  Primitives that don't depend on each other can be rendered at the
+//! same time, so the branches of a chain which only meet at an `feComposite` or `feMerge`
+//! get rendered in parallel.
+
+use super::input::Input;
+
+/// The inputs and the result name of a filter primitive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimitiveLinks {
+    /// The inputs of the primitive; `None` stands for an omitted `in` attribute, which
+    /// means the output of the previous primitive.
+    pub inputs: Vec<Option<Input>>,
+
+    /// The value of the `result` attribute.
+    pub result: Option<String>,
+}
+
+/// Returns, for each primitive in a chain, the positions of the earlier primitives whose
+/// outputs it may read.
+///
+/// A primitive that fails to render doesn't produce an output, and then references to
+/// its result fall back to an earlier primitive.  Because of that, an input by name depends
+/// on all the earlier primitives with that result name, and an omitted `in` depends on all
+/// the earlier primitives.
+pub fn dependencies(links: &[PrimitiveLinks]) -> Vec<Vec<usize>> {
+    links
+        .iter()
+        .enumerate()
+        .map(|(i, primitive)| {
+            let mut deps = Vec::new();
+
+            for input in &primitive.inputs {
+                match *input {
+                    None => deps.extend(0..i),
+
+                    Some(Input::FilterOutput(ref name)) => deps.extend(
+                        links[..i]
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, l)| l.result.as_ref() == Some(name))
+                            .map(|(j, _)| j),
+                    ),
+
+                    Some(_) => (),
+                }
+            }
+
+            deps.sort();
+            deps.dedup();
+            deps
+        })
+        .collect()
+}
+
+/// Splits a chain into batches of primitives, such that the primitives in a batch only
+/// depend on primitives in earlier batches.
+///
+/// Rendering the batches in order, and the primitives within each batch in any order,
+/// produces the same result as rendering the primitives one after the other.
+pub fn batches(dependencies: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut levels: Vec<usize> = Vec::with_capacity(dependencies.len());
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+
+    for (i, deps) in dependencies.iter().enumerate() {
+        let level = deps.iter().map(|&j| levels[j] + 1).max().unwrap_or(0);
+        levels.push(level);
+
+        if level == batches.len() {
+            batches.push(Vec::new());
+        }
+
+        batches[level].push(i);
+    }
+
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(inputs: &[Option<&str>], result: Option<&str>) -> PrimitiveLinks {
+        PrimitiveLinks {
+            inputs: inputs
+                .iter()
+                .map(|input| input.map(|name| Input::FilterOutput(name.to_string())))
+                .collect(),
+            result: result.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn named_inputs_depend_on_primitives_with_that_result() {
+        let chain = vec![
+            links(&[], Some("a")),
+            links(&[], Some("b")),
+            links(&[Some("a")], Some("a")),
+            links(&[Some("a"), Some("b")], None),
+        ];
+
+        assert_eq!(
+            dependencies(&chain),
+            vec![vec![], vec![], vec![0], vec![0, 1, 2]]
+        );
+    }
+
+    #[test]
+    fn omitted_input_depends_on_all_previous_primitives() {
+        let chain = vec![
+            links(&[], Some("a")),
+            links(&[], None),
+            links(&[None], None),
+        ];
+
+        assert_eq!(dependencies(&chain), vec![vec![], vec![], vec![0, 1]]);
+    }
+
+    #[test]
+    fn standard_inputs_have_no_dependencies() {
+        let chain = vec![
+            links(&[], Some("SourceGraphic")),
+            PrimitiveLinks {
+                inputs: vec![Some(Input::SourceGraphic), Some(Input::BackgroundAlpha)],
+                result: None,
+            },
+        ];
+
+        assert_eq!(dependencies(&chain), vec![vec![], vec![]]);
+    }
+
+    #[test]
+    fn independent_branches_go_in_the_same_batch() {
+        // Two blurs of the source, offset each, then composite them.
+        let chain = vec![
+            links(&[], Some("blur1")),
+            links(&[], Some("blur2")),
+            links(&[Some("blur1")], Some("offset1")),
+            links(&[Some("blur2")], Some("offset2")),
+            links(&[Some("offset1"), Some("offset2")], None),
+            links(&[None], None),
+        ];
+
+        assert_eq!(
+            batches(&dependencies(&chain)),
+            vec![vec![0, 1], vec![2, 3], vec![4], vec![5]]
+        );
+    }
+}
@@ -3,9 +3,11 @@ use cairo::{self, ImageSurface, Matrix};
 use crate::drawing_ctx::DrawingCtx;
 use crate::node::{NodeResult, NodeTrait, RsvgNode};
 use crate::property_bag::PropertyBag;
+use crate::rect::IRect;
 use crate::surface_utils::shared_surface::SharedImageSurface;
 
 use super::context::{FilterContext, FilterInput, FilterOutput, FilterResult};
+use super::schedule::PrimitiveLinks;
 use super::{FilterEffect, FilterError, PrimitiveWithInput};
 
 /// The `feTile` filter primitive.
@@ -41,45 +43,58 @@ impl FilterEffect for FeTile {
         let input = self.base.get_input(ctx, draw_ctx)?;
 
         // feTile doesn't consider its inputs in the filter primitive subregion calculation.
-        let bounds = self.base.get_bounds(ctx).into_irect(draw_ctx);
+        let subregion = self.base.get_bounds(ctx).into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         let output_surface = match input {
             FilterInput::StandardInput(surface) => surface,
             FilterInput::PrimitiveOutput(FilterOutput {
                 surface,
-                bounds: input_bounds,
+                subregion: input_subregion,
+                ..
             }) => {
-                // Create a surface containing just the region to tile.
-                let bounded_input_surface = ImageSurface::create(
-                    cairo::Format::ARgb32,
-                    input_bounds.width(),
-                    input_bounds.height(),
-                )?;
-
-                {
-                    let cr = cairo::Context::new(&bounded_input_surface);
-                    surface.set_as_source_surface(
-                        &cr,
-                        f64::from(-input_bounds.x0),
-                        f64::from(-input_bounds.y0),
-                    );
-                    cr.paint();
-                }
-
-                // Make a pattern out of the tile region.
-                let ptn = cairo::SurfacePattern::create(&bounded_input_surface);
-                ptn.set_extend(cairo::Extend::Repeat);
-                let mut mat = Matrix::identity();
-                mat.translate(f64::from(-input_bounds.x0), f64::from(-input_bounds.y0));
-                ptn.set_matrix(mat);
-
+                // The tile is the exact subregion of the input, which does not fall on
+                // whole pixels when the filter is scaled.  Render the tile to a surface
+                // with whole pixels, and scale the pattern back so that it repeats
+                // exactly at the size of the subregion; otherwise, rounding the tile to
+                // pixels would leave gaps or overlaps between the tiles.
                 let output_surface = ImageSurface::create(
                     cairo::Format::ARgb32,
                     ctx.source_graphic().width(),
                     ctx.source_graphic().height(),
                 )?;
 
-                {
+                // An empty tile makes for an empty output.
+                if input_subregion.width > 0.0 && input_subregion.height > 0.0 {
+                    let tile_width = input_subregion.width.ceil();
+                    let tile_height = input_subregion.height.ceil();
+
+                    let mut tile_matrix = Matrix::identity();
+                    tile_matrix.scale(
+                        tile_width / input_subregion.width,
+                        tile_height / input_subregion.height,
+                    );
+                    tile_matrix.translate(-input_subregion.x, -input_subregion.y);
+
+                    // Create a surface containing just the region to tile.
+                    let bounded_input_surface = ImageSurface::create(
+                        cairo::Format::ARgb32,
+                        tile_width as i32,
+                        tile_height as i32,
+                    )?;
+
+                    {
+                        let cr = cairo::Context::new(&bounded_input_surface);
+                        cr.set_matrix(tile_matrix);
+                        surface.set_as_source_surface(&cr, 0.0, 0.0);
+                        cr.paint();
+                    }
+
+                    // Make a pattern out of the tile region.
+                    let ptn = cairo::SurfacePattern::create(&bounded_input_surface);
+                    ptn.set_extend(cairo::Extend::Repeat);
+                    ptn.set_matrix(tile_matrix);
+
                     let cr = cairo::Context::new(&output_surface);
                     let r = cairo::Rectangle::from(bounds);
                     cr.rectangle(r.x, r.y, r.width, r.height);
@@ -98,10 +113,15 @@ impl FilterEffect for FeTile {
             output: FilterOutput {
                 surface: output_surface,
                 bounds,
+                subregion,
             },
         })
     }
 
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links()
+    }
+
     #[inline]
     fn is_affected_by_color_interpolation_filters(&self) -> bool {
         false
@@ -1,5 +1,6 @@
 use cairo::{self, ImageSurface};
 use markup5ever::{expanded_name, local_name, namespace_url, ns, QualName};
+use rayon::prelude::*;
 
 use crate::drawing_ctx::DrawingCtx;
 use crate::error::{AttributeResultExt, NodeError};
@@ -7,15 +8,16 @@ use crate::node::{CascadedValues, NodeResult, NodeTrait, RsvgNode};
 use crate::parsers;
 use crate::property_bag::PropertyBag;
 use crate::property_defs::ColorInterpolationFilters;
+use crate::rect::IRect;
 use crate::surface_utils::{
     shared_surface::{SharedImageSurface, SurfaceType},
-    ImageSurfaceDataExt,
     Pixel,
 };
 use crate::util::clamp;
 
 use super::context::{FilterContext, FilterOutput, FilterResult};
-use super::{FilterEffect, FilterError, Primitive};
+use super::schedule::PrimitiveLinks;
+use super::{FilterEffect, FilterError, FilterJob, Primitive};
 
 /// Enumeration of the tile stitching modes.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
@@ -144,7 +146,10 @@ struct NoiseGenerator {
     tile_height: f64,
 
     lattice_selector: [usize; B_SIZE + B_SIZE + 2],
-    gradient: [[[f64; 2]; B_SIZE + B_SIZE + 2]; 4],
+
+    // Indexed by lattice point, gradient component, and color channel.  The four color
+    // channels are computed together, so they are next to each other for SIMD.
+    gradient: [[[f64; 4]; 2]; B_SIZE + B_SIZE + 2],
 }
 
 #[derive(Clone, Copy)]
@@ -175,7 +180,7 @@ impl NoiseGenerator {
             tile_height,
 
             lattice_selector: [0; B_SIZE + B_SIZE + 2],
-            gradient: [[[0.0; 2]; B_SIZE + B_SIZE + 2]; 4],
+            gradient: [[[0.0; 4]; 2]; B_SIZE + B_SIZE + 2],
         };
 
         let mut seed = setup_seed(seed);
@@ -185,14 +190,14 @@ impl NoiseGenerator {
                 rv.lattice_selector[i] = i;
                 for j in 0..2 {
                     seed = random(seed);
-                    rv.gradient[k][i][j] =
+                    rv.gradient[i][j][k] =
                         ((seed % (B_SIZE + B_SIZE) as i32) - B_SIZE as i32) as f64 / B_SIZE as f64;
                 }
-                let s = (rv.gradient[k][i][0] * rv.gradient[k][i][0]
-                    + rv.gradient[k][i][1] * rv.gradient[k][i][1])
+                let s = (rv.gradient[i][0][k] * rv.gradient[i][0][k]
+                    + rv.gradient[i][1][k] * rv.gradient[i][1][k])
                     .sqrt();
-                rv.gradient[k][i][0] /= s;
-                rv.gradient[k][i][1] /= s;
+                rv.gradient[i][0][k] /= s;
+                rv.gradient[i][1][k] /= s;
             }
         }
         for i in (1..B_SIZE).rev() {
@@ -204,17 +209,14 @@ impl NoiseGenerator {
         }
         for i in 0..B_SIZE + 2 {
             rv.lattice_selector[B_SIZE + i] = rv.lattice_selector[i];
-            for k in 0..4 {
-                for j in 0..2 {
-                    rv.gradient[k][B_SIZE + i][j] = rv.gradient[k][i][j];
-                }
-            }
+            rv.gradient[B_SIZE + i] = rv.gradient[i];
         }
 
         rv
     }
 
-    fn noise2(&self, color_channel: usize, vec: [f64; 2], stitch_info: Option<StitchInfo>) -> f64 {
+    /// Computes the noise at a point for the four color channels.
+    fn noise2(&self, vec: [f64; 2], stitch_info: Option<StitchInfo>) -> [f64; 4] {
         const BM: usize = 0xff;
 
         let s_curve = |t| t * t * (3. - 2. * t);
@@ -258,20 +260,28 @@ impl NoiseGenerator {
         let b11 = self.lattice_selector[j + by1];
         let sx = s_curve(rx0);
         let sy = s_curve(ry0);
-        let q = self.gradient[color_channel][b00];
-        let u = rx0 * q[0] + ry0 * q[1];
-        let q = self.gradient[color_channel][b10];
-        let v = rx1 * q[0] + ry0 * q[1];
-        let a = lerp(sx, u, v);
-        let q = self.gradient[color_channel][b01];
-        let u = rx0 * q[0] + ry1 * q[1];
-        let q = self.gradient[color_channel][b11];
-        let v = rx1 * q[0] + ry1 * q[1];
-        let b = lerp(sx, u, v);
-        lerp(sy, a, b)
+        let q00 = &self.gradient[b00];
+        let q10 = &self.gradient[b10];
+        let q01 = &self.gradient[b01];
+        let q11 = &self.gradient[b11];
+
+        // The lattice lookups are the same for all the channels; this loop is the part
+        // which gets computed in SIMD lanes.
+        let mut noise = [0.0; 4];
+        for c in 0..4 {
+            let u = rx0 * q00[0][c] + ry0 * q00[1][c];
+            let v = rx1 * q10[0][c] + ry0 * q10[1][c];
+            let a = lerp(sx, u, v);
+            let u = rx0 * q01[0][c] + ry1 * q01[1][c];
+            let v = rx1 * q11[0][c] + ry1 * q11[1][c];
+            let b = lerp(sx, u, v);
+            noise[c] = lerp(sy, a, b);
+        }
+        noise
     }
 
-    fn turbulence(&self, color_channel: usize, point: [f64; 2], tile_x: f64, tile_y: f64) -> f64 {
+    /// Computes the turbulence function at a point for the four color channels.
+    fn turbulence(&self, point: [f64; 2], tile_x: f64, tile_y: f64) -> [f64; 4] {
         let mut stitch_info = None;
         let mut base_frequency = self.base_frequency;
 
@@ -309,14 +319,18 @@ impl NoiseGenerator {
             });
         }
 
-        let mut sum = 0.0;
+        let mut sum = [0.0; 4];
         let mut vec = [point[0] * base_frequency.0, point[1] * base_frequency.1];
         let mut ratio = 1.0;
         for _ in 0..self.num_octaves {
-            if self.type_ == NoiseType::FractalNoise {
-                sum += self.noise2(color_channel, vec, stitch_info) / ratio;
-            } else {
-                sum += (self.noise2(color_channel, vec, stitch_info)).abs() / ratio;
+            let noise = self.noise2(vec, stitch_info);
+
+            for c in 0..4 {
+                if self.type_ == NoiseType::FractalNoise {
+                    sum[c] += noise[c] / ratio;
+                } else {
+                    sum[c] += noise[c].abs() / ratio;
+                }
             }
             vec[0] *= 2.0;
             vec[1] *= 2.0;
@@ -335,19 +349,22 @@ impl NoiseGenerator {
 }
 
 impl FilterEffect for FeTurbulence {
-    fn render(
+    fn prepare(
         &self,
         node: &RsvgNode,
         ctx: &FilterContext,
         draw_ctx: &mut DrawingCtx,
-    ) -> Result<FilterResult, FilterError> {
-        let bounds = self.base.get_bounds(ctx).into_irect(draw_ctx);
+    ) -> Result<Option<FilterJob>, FilterError> {
+        let subregion = self.base.get_bounds(ctx).into_rect(draw_ctx);
+        let bounds = IRect::from(subregion);
 
         let mut affine = ctx.paffine();
         affine.invert();
 
+        let seed = draw_ctx.turbulence_seed().unwrap_or(self.seed);
+
         let noise_generator = NoiseGenerator::new(
-            self.seed,
+            seed,
             self.base_frequency,
             self.num_octaves,
             self.type_,
@@ -356,50 +373,6 @@ impl FilterEffect for FeTurbulence {
             f64::from(bounds.height()),
         );
 
-        let mut output_surface = ImageSurface::create(
-            cairo::Format::ARgb32,
-            ctx.source_graphic().width(),
-            ctx.source_graphic().height(),
-        )?;
-
-        let output_stride = output_surface.get_stride() as usize;
-        {
-            let mut output_data = output_surface.get_data().unwrap();
-
-            for y in bounds.y_range() {
-                for x in bounds.x_range() {
-                    let point = affine.transform_point(f64::from(x), f64::from(y));
-                    let point = [point.0, point.1];
-
-                    let generate = |color_channel| {
-                        let v = noise_generator.turbulence(
-                            color_channel,
-                            point,
-                            f64::from(x - bounds.x0),
-                            f64::from(y - bounds.y0),
-                        );
-
-                        let v = match self.type_ {
-                            NoiseType::FractalNoise => (v * 255.0 + 255.0) / 2.0,
-                            NoiseType::Turbulence => v * 255.0,
-                        };
-
-                        (clamp(v, 0.0, 255.0) + 0.5) as u8
-                    };
-
-                    let pixel = Pixel {
-                        r: generate(0),
-                        g: generate(1),
-                        b: generate(2),
-                        a: generate(3),
-                    }
-                    .premultiply();
-
-                    output_data.set_pixel(output_stride, pixel, x as u32, y as u32);
-                }
-            }
-        }
-
         let cascaded = CascadedValues::new_from_node(node);
         let values = cascaded.get();
         // The generated color values are in the color space determined by
@@ -411,13 +384,75 @@ impl FilterEffect for FeTurbulence {
                 SurfaceType::SRgb
             };
 
-        Ok(FilterResult {
-            name: self.base.result.clone(),
-            output: FilterOutput {
-                surface: SharedImageSurface::new(output_surface, surface_type)?,
-                bounds,
-            },
-        })
+        let type_ = self.type_;
+        let (width, height) = (ctx.source_graphic().width(), ctx.source_graphic().height());
+        let name = self.base.result.clone();
+
+        let job: FilterJob = Box::new(move || {
+            let mut output_surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+            let output_stride = output_surface.get_stride() as usize;
+            {
+                let mut output_data = output_surface.get_data().unwrap();
+
+                // Each pixel only depends on its position, so the rows get computed in
+                // parallel.
+                output_data
+                    .par_chunks_mut(output_stride)
+                    .enumerate()
+                    .skip(bounds.y0 as usize)
+                    .take(bounds.height() as usize)
+                    .for_each(|(y, row)| {
+                        let y = y as i32;
+
+                        for x in bounds.x_range() {
+                            let point = affine.transform_point(f64::from(x), f64::from(y));
+                            let point = [point.0, point.1];
+
+                            let v = noise_generator.turbulence(
+                                point,
+                                f64::from(x - bounds.x0),
+                                f64::from(y - bounds.y0),
+                            );
+
+                            let generate = |v: f64| {
+                                let v = match type_ {
+                                    NoiseType::FractalNoise => (v * 255.0 + 255.0) / 2.0,
+                                    NoiseType::Turbulence => v * 255.0,
+                                };
+
+                                (clamp(v, 0.0, 255.0) + 0.5) as u8
+                            };
+
+                            let pixel = Pixel {
+                                r: generate(v[0]),
+                                g: generate(v[1]),
+                                b: generate(v[2]),
+                                a: generate(v[3]),
+                            }
+                            .premultiply();
+
+                            let offset = x as usize * 4;
+                            row[offset..offset + 4].copy_from_slice(&pixel.to_u32().to_ne_bytes());
+                        }
+                    });
+            }
+
+            Ok(FilterResult {
+                name,
+                output: FilterOutput {
+                    surface: SharedImageSurface::new(output_surface, surface_type)?,
+                    bounds,
+                    subregion,
+                },
+            })
+        });
+
+        Ok(Some(job))
+    }
+
+    fn links(&self, _node: &RsvgNode) -> PrimitiveLinks {
+        self.base.links_with_inputs(Vec::new())
     }
 
     #[inline]
@@ -0,0 +1,604 @@
+//! The `@font-face` rule, and the fonts that it loads.
+//!
+//! Self-contained documents carry their fonts in `@font-face` rules, usually as `data:`
+//! URLs, so that they look the same everywhere.  We load those fonts into a private
+//! fontconfig configuration which also has the system's fonts, and lay out the document's
+//! text with a Pango font map made from it, so the fonts don't leak to other documents.
+//!
+//! Only the `font-family` and `src` descriptors are supported.  Fontconfig picks the
+//! weight and style of each font from the font file itself.
+//!
+//! Applications can also supply the fonts for all the text with `ApplicationFonts`,
+//! for example to render the same way on servers that have no fonts installed.
+
+use cssparser::{
+    _cssparser_internal_to_lowercase,
+    match_ignore_ascii_case,
+    AtRuleParser,
+    BasicParseErrorKind,
+    CowRcStr,
+    DeclarationListParser,
+    DeclarationParser,
+    ParseError,
+    Parser,
+    Token,
+};
+use url::Url;
+
+use crate::allowed_url::AllowedUrl;
+use crate::error::ValueErrorKind;
+
+/// A `@font-face` rule as it appears in a stylesheet
+pub struct FontFaceRule {
+    family: String,
+    sources: Vec<String>,
+}
+
+/// A `@font-face` rule whose sources were checked against the document's base URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    /// Text whose `font-family` names this family uses the font
+    pub family: String,
+
+    /// The `url()` sources, in order of preference.  Sources with a `format()` hint
+    /// that we can't load are left out.
+    pub sources: Vec<AllowedUrl>,
+}
+
+enum FontFaceDescriptor {
+    Family(String),
+    Src(Vec<String>),
+}
+
+impl FontFaceRule {
+    /// Parses the descriptors in the block of a `@font-face` rule.
+    ///
+    /// Returns `None` if the block lacks `font-family` or `src`, since both are required.
+    pub fn parse_block(input: &mut Parser<'_, '_>) -> Option<FontFaceRule> {
+        let mut family = None;
+        let mut sources = None;
+
+        for descriptor in DeclarationListParser::new(input, FontFaceDeclParser) {
+            match descriptor {
+                Ok(FontFaceDescriptor::Family(f)) => family = Some(f),
+                Ok(FontFaceDescriptor::Src(s)) => sources = Some(s),
+
+                // ignore unsupported descriptors and invalid values
+                Err(_) => (),
+            }
+        }
+
+        Some(FontFaceRule {
+            family: family?,
+            sources: sources?,
+        })
+    }
+
+    /// Resolves the sources relative to the stylesheet's URL, and drops the ones which
+    /// the document is not allowed to load.
+    pub fn resolve(self, stylesheet_url: Option<&Url>, base_url: Option<&Url>) -> FontFace {
+        let sources = self
+            .sources
+            .iter()
+            .filter_map(|href| {
+                let aurl = Url::options()
+                    .base_url(stylesheet_url)
+                    .parse(href)
+                    .ok()
+                    .and_then(|url| AllowedUrl::from_href(url.as_str(), base_url).ok());
+
+                if aurl.is_none() {
+                    rsvg_log!("(font \"{}\" is not allowed to be loaded; ignoring)", href);
+                }
+
+                aurl
+            })
+            .collect();
+
+        FontFace {
+            family: self.family,
+            sources,
+        }
+    }
+}
+
+/// Implements `cssparser::DeclarationParser` for the descriptors of `@font-face`
+struct FontFaceDeclParser;
+
+impl<'i> DeclarationParser<'i> for FontFaceDeclParser {
+    type Declaration = FontFaceDescriptor;
+    type Error = ValueErrorKind;
+
+    fn parse_value<'t>(
+        &mut self,
+        name: CowRcStr<'i>,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<FontFaceDescriptor, ParseError<'i, ValueErrorKind>> {
+        match_ignore_ascii_case! { &name,
+            "font-family" => parse_family_name(input).map(FontFaceDescriptor::Family),
+
+            "src" => {
+                let sources = input.parse_comma_separated(parse_source)?;
+                Ok(FontFaceDescriptor::Src(sources.into_iter().flatten().collect()))
+            },
+
+            _ => Err(input.new_error(BasicParseErrorKind::UnexpectedToken(Token::Ident(name)))),
+        }
+    }
+}
+
+// Descriptors can't contain at-rules; we use the dummy implementations from cssparser.
+impl<'i> AtRuleParser<'i> for FontFaceDeclParser {
+    type PreludeBlock = ();
+    type PreludeNoBlock = ();
+    type AtRule = FontFaceDescriptor;
+    type Error = ValueErrorKind;
+}
+
+/// Parses a family name, which is either a string or a sequence of identifiers.
+fn parse_family_name<'i>(
+    input: &mut Parser<'i, '_>,
+) -> Result<String, ParseError<'i, ValueErrorKind>> {
+    if let Ok(s) = input.try_parse(|p| p.expect_string_cloned()) {
+        return Ok(s.as_ref().to_owned());
+    }
+
+    let mut name = input.expect_ident_cloned()?.as_ref().to_owned();
+
+    while let Ok(ident) = input.try_parse(|p| p.expect_ident_cloned()) {
+        name.push(' ');
+        name.push_str(&ident);
+    }
+
+    Ok(name)
+}
+
+/// Parses an item of `src`; returns `None` for the ones which don't need to be loaded.
+fn parse_source<'i>(
+    input: &mut Parser<'i, '_>,
+) -> Result<Option<String>, ParseError<'i, ValueErrorKind>> {
+    // local() names a font that is already installed, so there is nothing to load;
+    // its family name is available anyway.
+    if input
+        .try_parse(|p| p.expect_function_matching("local"))
+        .is_ok()
+    {
+        input.parse_nested_block(|p| {
+            while p.next().is_ok() {}
+            Ok::<_, ParseError<'i, ValueErrorKind>>(())
+        })?;
+
+        return Ok(None);
+    }
+
+    let url = input.expect_url()?.as_ref().to_owned();
+
+    let supported = if input
+        .try_parse(|p| p.expect_function_matching("format"))
+        .is_ok()
+    {
+        input
+            .parse_nested_block(|p| {
+                p.parse_comma_separated(|p| Ok(p.expect_string()?.as_ref().to_owned()))
+            })?
+            .iter()
+            .any(|format| is_supported_format(format))
+    } else {
+        true
+    };
+
+    Ok(if supported { Some(url) } else { None })
+}
+
+/// Whether FreeType can load fonts with a `format()` hint.
+fn is_supported_format(format: &str) -> bool {
+    ["truetype", "opentype", "woff", "woff2", "collection"]
+        .iter()
+        .any(|f| format.eq_ignore_ascii_case(f))
+}
+
+#[cfg(feature = "text")]
+pub use self::fonts::{ApplicationFonts, DocumentFonts};
+
+#[cfg(all(feature = "text", not(windows)))]
+mod fonts {
+    use glib::translate::*;
+    use libc::{c_char, c_int, c_uchar, c_void};
+    use pango;
+    use pangocairo;
+    use std::env;
+    use std::ffi::CString;
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::FontFace;
+    use crate::allowed_url::AllowedUrl;
+    use crate::io::acquire_data;
+
+    type FcConfig = c_void;
+    type FcPattern = c_void;
+
+    #[repr(C)]
+    struct FcFontSet {
+        nfont: c_int,
+        _sfont: c_int,
+        fonts: *mut *mut FcPattern,
+    }
+
+    const FC_SET_APPLICATION: c_int = 1;
+
+    #[link(name = "fontconfig")]
+    extern "C" {
+        fn FcInitLoadConfigAndFonts() -> *mut FcConfig;
+        fn FcConfigCreate() -> *mut FcConfig;
+        fn FcConfigDestroy(config: *mut FcConfig);
+        fn FcConfigAppFontAddFile(config: *mut FcConfig, file: *const c_uchar) -> c_int;
+        fn FcConfigAppFontAddDir(config: *mut FcConfig, dir: *const c_uchar) -> c_int;
+        fn FcConfigGetFonts(config: *mut FcConfig, set: c_int) -> *mut FcFontSet;
+        fn FcPatternAddString(p: *mut FcPattern, object: *const c_char, s: *const c_uchar)
+            -> c_int;
+    }
+
+    #[link(name = "pangoft2-1.0")]
+    extern "C" {
+        fn pango_fc_font_map_set_config(
+            fcfontmap: *mut pango_sys::PangoFontMap,
+            fcconfig: *mut FcConfig,
+        );
+    }
+
+    /// Fonts that the application supplies for all the text, instead of the system's fonts
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ApplicationFonts {
+        font_map: pango::FontMap,
+
+        // The fonts from a document's `@font-face` rules get added to a configuration
+        // of their own, which starts with the fonts in this directory.
+        directory: Option<PathBuf>,
+    }
+
+    impl ApplicationFonts {
+        /// Uses a font map that the application set up.
+        ///
+        /// Fonts can't be added to an arbitrary font map, so the `@font-face` rules of
+        /// documents are ignored.
+        pub fn from_font_map(font_map: pango::FontMap) -> ApplicationFonts {
+            ApplicationFonts {
+                font_map,
+                directory: None,
+            }
+        }
+
+        /// Uses the font files in `directory` and its subdirectories, and no other fonts.
+        pub fn from_directory<P: AsRef<Path>>(directory: P) -> io::Result<ApplicationFonts> {
+            let directory = directory.as_ref().to_path_buf();
+
+            if !fs::metadata(&directory)?.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "fonts must be in a directory",
+                ));
+            }
+
+            unsafe {
+                let config = new_config_for_directory(&directory);
+                if config.is_null() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "could not load the fonts in the directory",
+                    ));
+                }
+
+                let font_map = new_font_map(config);
+                FcConfigDestroy(config);
+
+                font_map
+                    .map(|font_map| ApplicationFonts {
+                        font_map,
+                        directory: Some(directory),
+                    })
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::Other,
+                            "cairo does not support FreeType fonts",
+                        )
+                    })
+            }
+        }
+
+        /// The font map for the text of documents
+        pub fn font_map(&self) -> &pango::FontMap {
+            &self.font_map
+        }
+    }
+
+    /// The fonts from a document's `@font-face` rules
+    pub struct DocumentFonts {
+        font_map: Option<pango::FontMap>,
+
+        // Fontconfig loads fonts from files, so the fonts stay in temporary files for
+        // as long as the font map may use them.
+        files: Vec<PathBuf>,
+    }
+
+    impl DocumentFonts {
+        /// Loads the first source that works from each of the `faces`.
+        ///
+        /// The fonts are available along with the `app_fonts` if there are any, or with
+        /// the system's fonts otherwise.
+        pub fn load(faces: &[&FontFace], app_fonts: Option<&ApplicationFonts>) -> DocumentFonts {
+            let mut fonts = DocumentFonts {
+                font_map: None,
+                files: Vec::new(),
+            };
+
+            if faces.is_empty() {
+                return fonts;
+            }
+
+            unsafe {
+                let config = match app_fonts {
+                    None => FcInitLoadConfigAndFonts(),
+
+                    Some(ApplicationFonts {
+                        directory: Some(ref directory),
+                        ..
+                    }) => new_config_for_directory(directory),
+
+                    Some(_) => {
+                        rsvg_log!("(ignoring @font-face rules with the application's font map)");
+                        ptr::null_mut()
+                    }
+                };
+
+                if config.is_null() {
+                    return fonts;
+                }
+
+                let mut num_loaded = 0;
+
+                for face in faces {
+                    if face
+                        .sources
+                        .iter()
+                        .any(|aurl| fonts.add_font(config, &face.family, aurl))
+                    {
+                        num_loaded += 1;
+                    } else {
+                        rsvg_log!("(could not load a font for @font-face \"{}\")", face.family);
+                    }
+                }
+
+                if num_loaded > 0 {
+                    fonts.font_map = new_font_map(config);
+                }
+
+                FcConfigDestroy(config);
+            }
+
+            fonts
+        }
+
+        /// The font map for the document's text, or `None` if no fonts were loaded.
+        pub fn font_map(&self) -> Option<&pango::FontMap> {
+            self.font_map.as_ref()
+        }
+
+        /// Adds the font at `aurl` to the `config` with the `family` name.
+        unsafe fn add_font(
+            &mut self,
+            config: *mut FcConfig,
+            family: &str,
+            aurl: &AllowedUrl,
+        ) -> bool {
+            let family = match CString::new(family) {
+                Ok(f) => f,
+                Err(_) => return false,
+            };
+
+            let path = match acquire_data(aurl, None)
+                .map_err(|e| e.to_string())
+                .and_then(|data| write_temporary_file(&data.data).map_err(|e| e.to_string()))
+            {
+                Ok(path) => path,
+                Err(e) => {
+                    rsvg_log!("(could not load font \"{}\": {})", aurl, e);
+                    return false;
+                }
+            };
+
+            self.files.push(path.clone());
+
+            let set = FcConfigGetFonts(config, FC_SET_APPLICATION);
+            let first_new = if set.is_null() { 0 } else { (*set).nfont };
+
+            let path_ptr: Stash<*const c_char, PathBuf> = path.to_glib_none();
+            if FcConfigAppFontAddFile(config, path_ptr.0 as *const c_uchar) == 0 {
+                rsvg_log!("(\"{}\" is not a font that can be loaded; ignoring)", aurl);
+                return false;
+            }
+
+            let set = FcConfigGetFonts(config, FC_SET_APPLICATION);
+            if set.is_null() {
+                return false;
+            }
+
+            // The font keeps its own family name, and gets the one from the rule as an
+            // alternative, so that both of them match.
+            for i in first_new..(*set).nfont {
+                let pattern = *(*set).fonts.offset(i as isize);
+
+                FcPatternAddString(
+                    pattern,
+                    b"family\0".as_ptr() as *const c_char,
+                    family.as_ptr() as *const c_uchar,
+                );
+            }
+
+            true
+        }
+    }
+
+    impl Drop for DocumentFonts {
+        fn drop(&mut self) {
+            // Drop the font map first, so that nothing reads the files afterwards
+            self.font_map = None;
+
+            for path in &self.files {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Creates a fontconfig configuration with only the fonts in `directory`.
+    ///
+    /// Returns a null pointer if the fonts could not be added.
+    unsafe fn new_config_for_directory(directory: &Path) -> *mut FcConfig {
+        let config = FcConfigCreate();
+        if config.is_null() {
+            return config;
+        }
+
+        let dir: Stash<*const c_char, Path> = directory.to_glib_none();
+        if FcConfigAppFontAddDir(config, dir.0 as *const c_uchar) == 0 {
+            FcConfigDestroy(config);
+            return ptr::null_mut();
+        }
+
+        config
+    }
+
+    /// Creates a font map with the fonts in `config`, which the font map keeps a reference to.
+    ///
+    /// This needs cairo with FreeType support, which may be missing on platforms with a
+    /// native font system.
+    unsafe fn new_font_map(config: *mut FcConfig) -> Option<pango::FontMap> {
+        let font_map = pangocairo::FontMap::new_for_font_type(cairo::FontType::FontTypeFt)?;
+        pango_fc_font_map_set_config(font_map.to_glib_none().0, config);
+
+        Some(font_map)
+    }
+
+    fn write_temporary_file(data: &[u8]) -> io::Result<PathBuf> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = env::temp_dir().join(format!(
+            "librsvg-font-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // Don't follow or overwrite a file that is already there
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        file.write_all(data)?;
+
+        Ok(path)
+    }
+}
+
+// There is no fontconfig on Windows, so the fonts don't get loaded there.
+#[cfg(all(feature = "text", windows))]
+mod fonts {
+    use pango;
+    use std::io;
+    use std::path::Path;
+
+    use super::FontFace;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ApplicationFonts {
+        font_map: pango::FontMap,
+    }
+
+    impl ApplicationFonts {
+        pub fn from_font_map(font_map: pango::FontMap) -> ApplicationFonts {
+            ApplicationFonts { font_map }
+        }
+
+        pub fn from_directory<P: AsRef<Path>>(_directory: P) -> io::Result<ApplicationFonts> {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "loading fonts from a directory needs fontconfig",
+            ))
+        }
+
+        pub fn font_map(&self) -> &pango::FontMap {
+            &self.font_map
+        }
+    }
+
+    pub struct DocumentFonts;
+
+    impl DocumentFonts {
+        pub fn load(_faces: &[&FontFace], _app_fonts: Option<&ApplicationFonts>) -> DocumentFonts {
+            DocumentFonts
+        }
+
+        pub fn font_map(&self) -> Option<&pango::FontMap> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cssparser::ParserInput;
+
+    fn parse(block: &str) -> Option<FontFaceRule> {
+        let mut input = ParserInput::new(block);
+        let mut parser = Parser::new(&mut input);
+
+        FontFaceRule::parse_block(&mut parser)
+    }
+
+    #[test]
+    fn parses_font_face_descriptors() {
+        let rule = parse(
+            "font-family: My Font; font-weight: bold;
+             src: local(\"My Font\"), url(a.woff2) format(\"woff2\"),
+                  url(\"a.svg\") format(\"svg\"), url(a.ttf)",
+        )
+        .unwrap();
+
+        assert_eq!(rule.family, "My Font");
+        assert_eq!(rule.sources, vec!["a.woff2", "a.ttf"]);
+
+        let rule =
+            parse("font-family: \"Quoted, Name\"; src: url(data:font/ttf;base64,AAAA)").unwrap();
+
+        assert_eq!(rule.family, "Quoted, Name");
+        assert_eq!(rule.sources, vec!["data:font/ttf;base64,AAAA"]);
+    }
+
+    #[test]
+    fn requires_family_and_src() {
+        assert!(parse("font-family: Foo").is_none());
+        assert!(parse("src: url(foo.ttf)").is_none());
+    }
+
+    #[test]
+    fn resolves_allowed_sources() {
+        let base_url = Url::parse("resource:///some/dir/doc.svg").unwrap();
+
+        let face =
+            parse("font-family: Foo; src: url(fonts/foo.ttf), url(http://example.com/foo.ttf)")
+                .unwrap()
+                .resolve(Some(&base_url), Some(&base_url));
+
+        assert_eq!(face.family, "Foo");
+        assert_eq!(face.sources.len(), 1);
+        assert_eq!(
+            face.sources[0].as_str(),
+            "resource:///some/dir/fonts/foo.ttf"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use cssparser::{BasicParseError, Parser, Token};
 
 use crate::drawing_ctx::ViewParams;
@@ -5,6 +7,8 @@ use crate::error::*;
 use crate::length::*;
 use crate::parsers::Parse;
 use crate::properties::ComputedValues;
+use crate::property_defs::FontFamily;
+use crate::property_macros::ToCssValue;
 
 // https://www.w3.org/TR/2008/REC-CSS2-20080411/fonts.html#propdef-font-size
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -95,6 +99,23 @@ impl Parse for FontSizeSpec {
     }
 }
 
+impl ToCssValue for FontSizeSpec {
+    fn to_css_value(&self) -> String {
+        match *self {
+            FontSizeSpec::Smaller => "smaller".to_string(),
+            FontSizeSpec::Larger => "larger".to_string(),
+            FontSizeSpec::XXSmall => "xx-small".to_string(),
+            FontSizeSpec::XSmall => "x-small".to_string(),
+            FontSizeSpec::Small => "small".to_string(),
+            FontSizeSpec::Medium => "medium".to_string(),
+            FontSizeSpec::Large => "large".to_string(),
+            FontSizeSpec::XLarge => "x-large".to_string(),
+            FontSizeSpec::XXLarge => "xx-large".to_string(),
+            FontSizeSpec::Value(ref l) => l.to_css_value(),
+        }
+    }
+}
+
 // https://www.w3.org/TR/2008/REC-CSS2-20080411/fonts.html#propdef-font-weight
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FontWeightSpec {
@@ -154,6 +175,27 @@ impl Parse for FontWeightSpec {
     }
 }
 
+impl ToCssValue for FontWeightSpec {
+    fn to_css_value(&self) -> String {
+        match *self {
+            FontWeightSpec::Normal => "normal",
+            FontWeightSpec::Bold => "bold",
+            FontWeightSpec::Bolder => "bolder",
+            FontWeightSpec::Lighter => "lighter",
+            FontWeightSpec::W100 => "100",
+            FontWeightSpec::W200 => "200",
+            FontWeightSpec::W300 => "300",
+            FontWeightSpec::W400 => "400",
+            FontWeightSpec::W500 => "500",
+            FontWeightSpec::W600 => "600",
+            FontWeightSpec::W700 => "700",
+            FontWeightSpec::W800 => "800",
+            FontWeightSpec::W900 => "900",
+        }
+        .to_string()
+    }
+}
+
 // https://www.w3.org/TR/css-text-3/#letter-spacing-property
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum LetterSpacingSpec {
@@ -213,6 +255,15 @@ impl Parse for LetterSpacingSpec {
     }
 }
 
+impl ToCssValue for LetterSpacingSpec {
+    fn to_css_value(&self) -> String {
+        match *self {
+            LetterSpacingSpec::Normal => "normal".to_string(),
+            LetterSpacingSpec::Value(ref l) => l.to_css_value(),
+        }
+    }
+}
+
 /// https://www.w3.org/TR/2008/REC-CSS2-20080411/fonts.html#propdef-font-family
 #[derive(Debug, Clone, PartialEq)]
 pub struct SingleFontFamily(pub String);
@@ -224,6 +275,12 @@ impl Parse for SingleFontFamily {
     }
 }
 
+impl ToCssValue for SingleFontFamily {
+    fn to_css_value(&self) -> String {
+        self.0.clone()
+    }
+}
+
 fn parse_single_font_family<'i>(
     parser: &'i mut Parser<'_, '_>,
 ) -> Result<SingleFontFamily, BasicParseError<'i>> {
@@ -243,6 +300,107 @@ fn parse_single_font_family<'i>(
     Ok(SingleFontFamily(value))
 }
 
+/// What happened to characters which didn't render with the requested font.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FontFallbackKind {
+    /// The requested font doesn't have glyphs for the characters, so they were
+    /// rendered with a different font.
+    DifferentFont,
+
+    /// No available font has glyphs for the characters, so they were rendered with
+    /// the "missing glyph" box.
+    MissingGlyph,
+}
+
+/// A run of text which didn't render with the font family that was asked for.
+///
+/// These are reported while rendering text elements, so that applications can detect
+/// that a document's fonts are not installed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFallback {
+    pub kind: FontFallbackKind,
+
+    /// The characters in the run.
+    pub characters: String,
+
+    /// The value of the `font-family` property for the text.
+    pub requested_family: String,
+
+    /// The family of the font that was used instead.
+    pub font_family: String,
+}
+
+/// A generic font family from CSS, which stands for a kind of font rather than for a
+/// specific one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GenericFontFamily {
+    Serif,
+    SansSerif,
+    Monospace,
+    Cursive,
+    Fantasy,
+    SystemUi,
+}
+
+impl GenericFontFamily {
+    /// Returns the generic family for a value of the `font-family` property, like
+    /// `sans-serif`, or `None` if it is not a generic family.
+    pub fn from_name(name: &str) -> Option<GenericFontFamily> {
+        let generic = match name.to_ascii_lowercase().as_str() {
+            "serif" => GenericFontFamily::Serif,
+            "sans-serif" => GenericFontFamily::SansSerif,
+            "monospace" => GenericFontFamily::Monospace,
+            "cursive" => GenericFontFamily::Cursive,
+            "fantasy" => GenericFontFamily::Fantasy,
+            "system-ui" => GenericFontFamily::SystemUi,
+            _ => return None,
+        };
+
+        Some(generic)
+    }
+}
+
+/// Concrete font families to render text with, instead of the generic families and the
+/// initial value of the `font-family` property.
+///
+/// By default, the generic families are resolved by the system's font configuration,
+/// which may be missing or minimal on servers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontFamilyMap {
+    default_family: Option<String>,
+    generic_families: HashMap<GenericFontFamily, String>,
+}
+
+impl FontFamilyMap {
+    pub fn new() -> FontFamilyMap {
+        FontFamilyMap::default()
+    }
+
+    /// Sets the family for text whose `font-family` is not specified in the document.
+    pub fn set_default_family(&mut self, family: &str) {
+        self.default_family = Some(family.to_string());
+    }
+
+    /// Sets the family that replaces the `generic` family.
+    pub fn set_generic_family(&mut self, generic: GenericFontFamily, family: &str) {
+        self.generic_families.insert(generic, family.to_string());
+    }
+
+    /// Returns the family to render text with, for a computed value of `font-family`.
+    pub fn resolve<'a>(&'a self, family: &'a str) -> &'a str {
+        if let Some(mapped) =
+            GenericFontFamily::from_name(family).and_then(|g| self.generic_families.get(&g))
+        {
+            return mapped;
+        }
+
+        match self.default_family {
+            Some(ref default) if family == (FontFamily::default().0).0 => default,
+            _ => family,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,6 +467,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolves_mapped_font_families() {
+        let mut map = FontFamilyMap::new();
+        map.set_default_family("Cantarell");
+        map.set_generic_family(GenericFontFamily::SansSerif, "DejaVu Sans");
+
+        assert_eq!(map.resolve("Sans-Serif"), "DejaVu Sans");
+        assert_eq!(map.resolve("serif"), "serif");
+        assert_eq!(map.resolve("Times New Roman"), "Cantarell");
+        assert_eq!(map.resolve("Helvetica"), "Helvetica");
+        assert_eq!(FontFamilyMap::new().resolve("Times New Roman"), "Times New Roman");
+    }
+
     #[test]
     fn detects_invalid_invalid_letter_spacing() {
         assert!(is_parse_error(&LetterSpacingSpec::parse_str("furlong")));
@@ -0,0 +1,584 @@
+//! Extents of paths, computed without a cairo context.
+//!
+//! The bounding boxes of shapes get computed here from their path data and stroke
+//! parameters, instead of asking cairo for the extents of a path that has been set
+//! on a context.  This way the geometry of a path can be known without creating a
+//! surface to render it.
+//!
+//! Curves are flattened into line segments to compute the extents of their stroke, in
+//! the same way that cairo does; the extents of the path itself are exact.
+
+use cairo;
+
+use crate::bbox::BoundingBox;
+use crate::drawing_ctx::ViewParams;
+use crate::paint_server::PaintServer;
+use crate::path_builder::{PathBuilder, PathSegment};
+use crate::properties::ComputedValues;
+use crate::property_defs::VectorEffect;
+use crate::rect::RectangleExt;
+
+/// Flattening tolerance for curves, in device units
+const DEVICE_TOLERANCE: f64 = 0.1;
+
+/// Maximum number of line segments into which a curve gets flattened
+const MAX_CURVE_PIECES: usize = 256;
+
+/// The parameters of a stroke that determine how far it extends from its path
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StrokeParams {
+    pub width: f64,
+    pub line_join: cairo::LineJoin,
+    pub line_cap: cairo::LineCap,
+    pub miter_limit: f64,
+}
+
+impl StrokeParams {
+    pub fn from_values(values: &ComputedValues, params: &ViewParams) -> StrokeParams {
+        StrokeParams {
+            width: values.stroke_width.0.normalize(values, params),
+            line_join: cairo::LineJoin::from(values.stroke_line_join),
+            line_cap: cairo::LineCap::from(values.stroke_line_cap),
+            miter_limit: values.stroke_miterlimit.0,
+        }
+    }
+}
+
+/// Accumulates points into a bounding rectangle
+#[derive(Default)]
+struct Extents(Option<(f64, f64, f64, f64)>);
+
+impl Extents {
+    fn add(&mut self, (x, y): (f64, f64)) {
+        self.0 = Some(match self.0 {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+
+    fn add_circle(&mut self, (x, y): (f64, f64), radius: f64) {
+        self.add((x - radius, y - radius));
+        self.add((x + radius, y + radius));
+    }
+
+    fn to_rectangle(&self) -> Option<cairo::Rectangle> {
+        self.0
+            .map(|(x0, y0, x1, y1)| cairo::Rectangle::from_extents(x0, y0, x1, y1))
+    }
+}
+
+fn cubic_point(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let u = 1.0 - t;
+    u * u * u * p0 + 3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t * p3
+}
+
+/// Returns the parameters in (0, 1) where one coordinate of a cubic curve has an extremum.
+fn cubic_extrema(p0: f64, p1: f64, p2: f64, p3: f64) -> Vec<f64> {
+    // The derivative, divided by 3, is a t² + b t + c
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+
+    let roots = if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            vec![]
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+
+        if discriminant < 0.0 {
+            vec![]
+        } else {
+            let d = discriminant.sqrt();
+            vec![(-b + d) / (2.0 * a), (-b - d) / (2.0 * a)]
+        }
+    };
+
+    roots.into_iter().filter(|&t| t > 0.0 && t < 1.0).collect()
+}
+
+/// Returns the extents of a path, like `cairo_path_extents()`.
+///
+/// A move-to that is not followed by anything else is not included.  Returns `None` for
+/// a path with no segments.
+pub fn path_extents(segments: &[PathSegment]) -> Option<cairo::Rectangle> {
+    let mut extents = Extents::default();
+    let mut current = (0.0, 0.0);
+    let mut subpath_start = (0.0, 0.0);
+    let mut pending_move = false;
+
+    for segment in segments {
+        if pending_move {
+            match *segment {
+                PathSegment::MoveTo(..) => (),
+                _ => extents.add(current),
+            }
+
+            pending_move = false;
+        }
+
+        match *segment {
+            PathSegment::MoveTo(x, y) => {
+                current = (x, y);
+                subpath_start = current;
+                pending_move = true;
+            }
+
+            PathSegment::LineTo(x, y) => {
+                current = (x, y);
+                extents.add(current);
+            }
+
+            PathSegment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                let (x0, y0) = current;
+
+                for t in cubic_extrema(x0, x1, x2, x3) {
+                    extents.add((
+                        cubic_point(x0, x1, x2, x3, t),
+                        cubic_point(y0, y1, y2, y3, t),
+                    ));
+                }
+
+                for t in cubic_extrema(y0, y1, y2, y3) {
+                    extents.add((
+                        cubic_point(x0, x1, x2, x3, t),
+                        cubic_point(y0, y1, y2, y3, t),
+                    ));
+                }
+
+                current = (x3, y3);
+                extents.add(current);
+            }
+
+            PathSegment::ClosePath => current = subpath_start,
+        }
+    }
+
+    extents.to_rectangle()
+}
+
+/// A subpath flattened into a polyline, without repeated points
+struct Polyline {
+    points: Vec<(f64, f64)>,
+    closed: bool,
+
+    // Whether anything was drawn after the initial move-to
+    has_segments: bool,
+}
+
+impl Polyline {
+    fn push(&mut self, p: (f64, f64)) {
+        if self.points.last() != Some(&p) {
+            self.points.push(p);
+        }
+    }
+}
+
+fn flatten(segments: &[PathSegment], tolerance: f64) -> Vec<Polyline> {
+    let mut polylines: Vec<Polyline> = Vec::new();
+    let mut current = (0.0, 0.0);
+
+    for segment in segments {
+        match *segment {
+            PathSegment::MoveTo(x, y) => {
+                current = (x, y);
+                polylines.push(Polyline {
+                    points: vec![current],
+                    closed: false,
+                    has_segments: false,
+                });
+                continue;
+            }
+
+            PathSegment::ClosePath => {
+                if let Some(polyline) = polylines.last_mut() {
+                    polyline.closed = true;
+                    polyline.has_segments = true;
+                    current = polyline.points[0];
+                }
+                continue;
+            }
+
+            _ => (),
+        }
+
+        // Segments after a close-path start a new subpath at the same point
+        if polylines.last().map_or(true, |p| p.closed) {
+            polylines.push(Polyline {
+                points: vec![current],
+                closed: false,
+                has_segments: false,
+            });
+        }
+
+        let polyline = polylines.last_mut().unwrap();
+        polyline.has_segments = true;
+
+        match *segment {
+            PathSegment::LineTo(x, y) => {
+                current = (x, y);
+                polyline.push(current);
+            }
+
+            PathSegment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                let (x0, y0) = current;
+
+                let control_length = distance((x0, y0), (x1, y1))
+                    + distance((x1, y1), (x2, y2))
+                    + distance((x2, y2), (x3, y3));
+
+                let pieces = ((control_length / tolerance).sqrt().ceil() as usize)
+                    .max(1)
+                    .min(MAX_CURVE_PIECES);
+
+                for i in 1..=pieces {
+                    let t = i as f64 / pieces as f64;
+                    polyline.push((
+                        cubic_point(x0, x1, x2, x3, t),
+                        cubic_point(y0, y1, y2, y3, t),
+                    ));
+                }
+
+                current = (x3, y3);
+            }
+
+            PathSegment::MoveTo(..) | PathSegment::ClosePath => unreachable!(),
+        }
+    }
+
+    polylines.retain(|p| p.has_segments);
+    polylines
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// Returns the unit vector from `a` to `b`; they must be different points.
+fn direction(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let d = distance(a, b);
+    ((b.0 - a.0) / d, (b.1 - a.1) / d)
+}
+
+fn offset(p: (f64, f64), v: (f64, f64), amount: f64) -> (f64, f64) {
+    (p.0 + v.0 * amount, p.1 + v.1 * amount)
+}
+
+fn add_join(
+    extents: &mut Extents,
+    p: (f64, f64),
+    d0: (f64, f64),
+    d1: (f64, f64),
+    stroke: &StrokeParams,
+) {
+    let half_width = stroke.width / 2.0;
+
+    match stroke.line_join {
+        cairo::LineJoin::Round => extents.add_circle(p, half_width),
+
+        cairo::LineJoin::Miter => {
+            let cross = d0.0 * d1.1 - d0.1 * d1.0;
+            let dot = d0.0 * d1.0 + d0.1 * d1.1;
+
+            // Straight joins have no miter; the bevel is covered by the segments.
+            if cross.abs() < 1e-12 || 2.0 > stroke.miter_limit.powi(2) * (1.0 + dot) {
+                return;
+            }
+
+            // Normals on the outer side of the turn
+            let side = if cross > 0.0 { -1.0 } else { 1.0 };
+            let n0 = (-d0.1 * side, d0.0 * side);
+            let n1 = (-d1.1 * side, d1.0 * side);
+
+            let scale = half_width / (1.0 + dot);
+            extents.add((p.0 + (n0.0 + n1.0) * scale, p.1 + (n0.1 + n1.1) * scale));
+        }
+
+        _ => (),
+    }
+}
+
+fn add_cap(extents: &mut Extents, p: (f64, f64), d: (f64, f64), stroke: &StrokeParams) {
+    let half_width = stroke.width / 2.0;
+
+    match stroke.line_cap {
+        cairo::LineCap::Round => extents.add_circle(p, half_width),
+
+        cairo::LineCap::Square => {
+            let n = (-d.1, d.0);
+            let end = offset(p, d, half_width);
+            extents.add(offset(end, n, half_width));
+            extents.add(offset(end, n, -half_width));
+        }
+
+        _ => (),
+    }
+}
+
+/// Returns the extents of the stroke of a path, like `cairo_stroke_extents()`.
+///
+/// Curves are flattened to within `tolerance`.  Dashes are not considered, so this is
+/// the extents of the stroke as if it were solid.
+pub fn stroke_extents(
+    segments: &[PathSegment],
+    stroke: &StrokeParams,
+    tolerance: f64,
+) -> Option<cairo::Rectangle> {
+    let half_width = stroke.width / 2.0;
+    let mut extents = Extents::default();
+
+    if half_width <= 0.0 {
+        return None;
+    }
+
+    for polyline in flatten(segments, tolerance) {
+        let mut points = polyline.points;
+
+        if polyline.closed && points.len() > 1 && points.last() != Some(&points[0]) {
+            points.push(points[0]);
+        }
+
+        // A degenerate subpath only has its caps, as a dot or a square
+        if points.len() < 2 {
+            add_cap(&mut extents, points[0], (1.0, 0.0), stroke);
+            add_cap(&mut extents, points[0], (-1.0, 0.0), stroke);
+            continue;
+        }
+
+        let directions: Vec<_> = points.windows(2).map(|w| direction(w[0], w[1])).collect();
+
+        for (w, d) in points.windows(2).zip(&directions) {
+            let n = (-d.1, d.0);
+
+            for &p in w {
+                extents.add(offset(p, n, half_width));
+                extents.add(offset(p, n, -half_width));
+            }
+        }
+
+        for (i, d) in directions.windows(2).enumerate() {
+            add_join(&mut extents, points[i + 1], d[0], d[1], stroke);
+        }
+
+        let first = directions[0];
+        let last = directions[directions.len() - 1];
+
+        if polyline.closed {
+            add_join(&mut extents, points[0], last, first, stroke);
+        } else {
+            add_cap(&mut extents, points[0], (-first.0, -first.1), stroke);
+            add_cap(&mut extents, points[points.len() - 1], last, stroke);
+        }
+    }
+
+    extents.to_rectangle()
+}
+
+fn transform_segments(segments: &[PathSegment], affine: &cairo::Matrix) -> Vec<PathSegment> {
+    segments
+        .iter()
+        .map(|segment| match *segment {
+            PathSegment::MoveTo(x, y) => {
+                let (x, y) = affine.transform_point(x, y);
+                PathSegment::MoveTo(x, y)
+            }
+
+            PathSegment::LineTo(x, y) => {
+                let (x, y) = affine.transform_point(x, y);
+                PathSegment::LineTo(x, y)
+            }
+
+            PathSegment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                let (x1, y1) = affine.transform_point(x1, y1);
+                let (x2, y2) = affine.transform_point(x2, y2);
+                let (x3, y3) = affine.transform_point(x3, y3);
+                PathSegment::CurveTo(x1, y1, x2, y2, x3, y3)
+            }
+
+            PathSegment::ClosePath => PathSegment::ClosePath,
+        })
+        .collect()
+}
+
+/// Computes the bounding box of a shape's path when it is filled and stroked with
+/// `values`, in the user space given by `affine`.
+///
+/// The fill is always part of the ink rectangle, even if the shape is not filled, so
+/// that invisible shapes still have extents.  The stroke is part of it only if the
+/// shape is stroked.
+pub fn path_bbox(
+    builder: &PathBuilder,
+    values: &ComputedValues,
+    params: &ViewParams,
+    affine: &cairo::Matrix,
+) -> BoundingBox {
+    let segments = builder.to_segments();
+
+    let mut bbox = BoundingBox::new(affine);
+
+    if let Some(rect) = path_extents(&segments) {
+        bbox.insert(&BoundingBox::new(affine).with_rect(rect).with_ink_rect(rect));
+    }
+
+    if values.stroke.0 != PaintServer::None {
+        let stroke = StrokeParams::from_values(values, params);
+
+        let stroke_bbox = match values.vector_effect {
+            VectorEffect::None => {
+                let (xx, yx, xy, yy) = (affine.xx, affine.yx, affine.xy, affine.yy);
+                let scale = (xx * yy - xy * yx).abs().sqrt();
+                let tolerance = if scale > 0.0 {
+                    DEVICE_TOLERANCE / scale
+                } else {
+                    DEVICE_TOLERANCE
+                };
+
+                stroke_extents(&segments, &stroke, tolerance)
+                    .map(|r| BoundingBox::new(affine).with_ink_rect(r))
+            }
+
+            // The stroke's width is in device space
+            VectorEffect::NonScalingStroke => {
+                let identity = cairo::Matrix::identity();
+
+                stroke_extents(
+                    &transform_segments(&segments, affine),
+                    &stroke,
+                    DEVICE_TOLERANCE,
+                )
+                .map(|r| BoundingBox::new(&identity).with_ink_rect(r))
+            }
+        };
+
+        if let Some(stroke_bbox) = stroke_bbox {
+            bbox.insert(&stroke_bbox);
+        }
+    }
+
+    bbox
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect_segments() -> Vec<PathSegment> {
+        vec![
+            PathSegment::MoveTo(10.0, 20.0),
+            PathSegment::LineTo(40.0, 20.0),
+            PathSegment::LineTo(40.0, 60.0),
+            PathSegment::LineTo(10.0, 60.0),
+            PathSegment::ClosePath,
+        ]
+    }
+
+    fn stroke(line_join: cairo::LineJoin, line_cap: cairo::LineCap) -> StrokeParams {
+        StrokeParams {
+            width: 10.0,
+            line_join,
+            line_cap,
+            miter_limit: 4.0,
+        }
+    }
+
+    #[test]
+    fn computes_path_extents() {
+        assert_eq!(path_extents(&[]), None);
+        assert_eq!(path_extents(&[PathSegment::MoveTo(5.0, 5.0)]), None);
+
+        assert_eq!(
+            path_extents(&rect_segments()),
+            Some(cairo::Rectangle::new(10.0, 20.0, 30.0, 40.0))
+        );
+
+        // The extrema of the curve are inside the hull of its control points
+        let curve = [
+            PathSegment::MoveTo(0.0, 0.0),
+            PathSegment::CurveTo(0.0, 40.0, 40.0, 40.0, 40.0, 0.0),
+        ];
+        assert_eq!(
+            path_extents(&curve),
+            Some(cairo::Rectangle::new(0.0, 0.0, 40.0, 30.0))
+        );
+    }
+
+    #[test]
+    fn computes_stroke_extents_of_closed_paths() {
+        let rect = rect_segments();
+
+        assert_eq!(
+            stroke_extents(
+                &rect,
+                &stroke(cairo::LineJoin::Miter, cairo::LineCap::Butt),
+                0.1
+            ),
+            Some(cairo::Rectangle::new(5.0, 15.0, 40.0, 50.0))
+        );
+
+        assert_eq!(
+            stroke_extents(
+                &rect,
+                &stroke(cairo::LineJoin::Bevel, cairo::LineCap::Butt),
+                0.1
+            ),
+            Some(cairo::Rectangle::new(5.0, 15.0, 40.0, 50.0))
+        );
+    }
+
+    #[test]
+    fn computes_stroke_extents_of_open_paths() {
+        let line = [
+            PathSegment::MoveTo(0.0, 0.0),
+            PathSegment::LineTo(10.0, 0.0),
+        ];
+
+        assert_eq!(
+            stroke_extents(
+                &line,
+                &stroke(cairo::LineJoin::Miter, cairo::LineCap::Butt),
+                0.1
+            ),
+            Some(cairo::Rectangle::new(0.0, -5.0, 10.0, 10.0))
+        );
+
+        assert_eq!(
+            stroke_extents(
+                &line,
+                &stroke(cairo::LineJoin::Miter, cairo::LineCap::Square),
+                0.1
+            ),
+            Some(cairo::Rectangle::new(-5.0, -5.0, 20.0, 10.0))
+        );
+
+        let dot = [PathSegment::MoveTo(0.0, 0.0), PathSegment::ClosePath];
+
+        assert_eq!(
+            stroke_extents(
+                &dot,
+                &stroke(cairo::LineJoin::Miter, cairo::LineCap::Round),
+                0.1
+            ),
+            Some(cairo::Rectangle::new(-5.0, -5.0, 10.0, 10.0))
+        );
+    }
+
+    #[test]
+    fn sharp_miters_fall_back_to_bevels() {
+        let spike = [
+            PathSegment::MoveTo(0.0, 0.0),
+            PathSegment::LineTo(100.0, 5.0),
+            PathSegment::LineTo(0.0, 10.0),
+        ];
+
+        let extents = stroke_extents(
+            &spike,
+            &stroke(cairo::LineJoin::Miter, cairo::LineCap::Butt),
+            0.1,
+        )
+        .unwrap();
+
+        assert!(extents.x + extents.width < 106.0);
+    }
+}
@@ -0,0 +1,44 @@
+//! Shaped glyphs from text layout, for the glyph run API.
+
+use cairo;
+
+/// A glyph that text layout placed in a `GlyphRun`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionedGlyph {
+    /// The index of the glyph in the run's font.
+    pub glyph: u32,
+
+    /// Byte offset, in the run's text, of the characters that the glyph renders.
+    pub cluster: usize,
+
+    /// Position of the glyph's origin in the run's coordinates.
+    pub x: f64,
+    pub y: f64,
+
+    /// How far the glyph advances the position of the next glyph.
+    pub advance: f64,
+}
+
+/// A sequence of glyphs that were shaped with a single font.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphRun {
+    /// The family of the font, as the font itself names it.
+    pub font_family: String,
+
+    /// The full description of the font, with its style, weight, and size; for example,
+    /// `"DejaVu Sans Bold 12"`.
+    pub font_description: String,
+
+    /// The size of the font, in the run's coordinates.
+    pub font_size: f64,
+
+    /// The characters that the glyphs render.
+    pub text: String,
+
+    /// Transformation from the run's coordinates, where the baseline is horizontal, to the
+    /// coordinates of the viewport.
+    pub transform: cairo::Matrix,
+
+    /// The glyphs, in visual order.
+    pub glyphs: Vec<PositionedGlyph>,
+}
@@ -1,21 +1,23 @@
 use cairo;
 use cssparser::{self, CowRcStr, Parser, Token};
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
-use std::cell::RefCell;
 
 use crate::allowed_url::Fragment;
 use crate::bbox::*;
 use crate::coord_units::CoordUnits;
-use crate::drawing_ctx::{AcquiredNode, DrawingCtx, NodeStack, ViewParams};
+use crate::drawing_ctx::{DrawingCtx, ViewParams};
 use crate::error::*;
 use crate::length::*;
+use crate::mesh_gradient::MeshEdge;
 use crate::node::{CascadedValues, NodeResult, NodeTrait, NodeType, RsvgNode};
-use crate::paint_server::{AsPaintSource, PaintSource};
+use crate::paint_server::{AsPaintSource, PaintSource, Resolve};
 use crate::parsers::{Parse, ParseValue};
 use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
-use crate::property_defs::StopColor;
+use crate::property_defs::{ColorInterpolation, StopColor};
 use crate::rect::RectangleExt;
+use crate::sanitize;
+use crate::srgb::{linearize_f64, unlinearize_f64};
 use crate::unit_interval::UnitInterval;
 
 /// Contents of a <stop> element for gradient color stops
@@ -31,6 +33,105 @@ struct ColorStop {
     opacity: UnitInterval,
 }
 
+/// Color space in which the colors between gradient stops are interpolated
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum InterpolationSpace {
+    Srgb,
+    LinearRgb,
+    Oklab,
+}
+
+impl InterpolationSpace {
+    /// Picks the space from the gradient element's `color-interpolation`
+    ///
+    /// For `auto` the choice is up to us; we use OKLab if the caller opted in
+    /// to it, or plain sRGB otherwise.
+    fn new(color_interpolation: ColorInterpolation, oklab_for_auto: bool) -> InterpolationSpace {
+        match color_interpolation {
+            ColorInterpolation::Auto if oklab_for_auto => InterpolationSpace::Oklab,
+            ColorInterpolation::Auto | ColorInterpolation::Srgb => InterpolationSpace::Srgb,
+            ColorInterpolation::LinearRgb => InterpolationSpace::LinearRgb,
+        }
+    }
+
+    /// Converts unpremultiplied sRGB components to this space
+    fn from_srgb(self, rgb: [f64; 3]) -> [f64; 3] {
+        match self {
+            InterpolationSpace::Srgb => rgb,
+            InterpolationSpace::LinearRgb => [
+                linearize_f64(rgb[0]),
+                linearize_f64(rgb[1]),
+                linearize_f64(rgb[2]),
+            ],
+            InterpolationSpace::Oklab => linear_rgb_to_oklab([
+                linearize_f64(rgb[0]),
+                linearize_f64(rgb[1]),
+                linearize_f64(rgb[2]),
+            ]),
+        }
+    }
+
+    /// Converts components in this space back to unpremultiplied sRGB
+    fn to_srgb(self, c: [f64; 3]) -> [f64; 3] {
+        let linear = match self {
+            InterpolationSpace::Srgb => return c,
+            InterpolationSpace::LinearRgb => c,
+            InterpolationSpace::Oklab => oklab_to_linear_rgb(c),
+        };
+
+        [
+            unlinearize_f64(clamp_component(linear[0])),
+            unlinearize_f64(clamp_component(linear[1])),
+            unlinearize_f64(clamp_component(linear[2])),
+        ]
+    }
+}
+
+fn clamp_component(c: f64) -> f64 {
+    c.max(0.0).min(1.0)
+}
+
+// The matrices for OKLab are from https://bottosson.github.io/posts/oklab/
+
+fn linear_rgb_to_oklab(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb;
+
+    let l = (0.412_221_470_8 * r + 0.536_332_536_3 * g + 0.051_445_992_9 * b).cbrt();
+    let m = (0.211_903_498_2 * r + 0.680_699_545_1 * g + 0.107_396_956_6 * b).cbrt();
+    let s = (0.088_302_461_9 * r + 0.281_718_837_6 * g + 0.629_978_700_5 * b).cbrt();
+
+    [
+        0.210_454_255_3 * l + 0.793_617_785_0 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205_0 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766_0 * s,
+    ]
+}
+
+fn oklab_to_linear_rgb(lab: [f64; 3]) -> [f64; 3] {
+    let [l, a, b] = lab;
+
+    let l_ = l + 0.396_337_777_4 * a + 0.215_803_757_3 * b;
+    let m_ = l - 0.105_561_345_8 * a - 0.063_854_172_8 * b;
+    let s_ = l - 0.089_484_177_5 * a - 1.291_485_548_0 * b;
+
+    let l = l_ * l_ * l_;
+    let m = m_ * m_ * m_;
+    let s = s_ * s_ * s_;
+
+    [
+        4.076_741_662_1 * l - 3.307_711_591_3 * m + 0.230_969_929_2 * s,
+        -1.268_438_004_6 * l + 2.609_757_401_1 * m - 0.341_319_396_5 * s,
+        -0.004_196_086_3 * l - 0.703_418_614_7 * m + 1.707_614_701_0 * s,
+    ]
+}
+
+/// Number of segments into which we split the space between two stops
+/// when interpolating in something other than sRGB.
+///
+/// Cairo always interpolates linearly in sRGB, so for other spaces we
+/// approximate the curve with this many intermediate stops.
+const INTERPOLATION_SEGMENTS: usize = 16;
+
 // gradientUnits attibute; its default is objectBoundingBox
 coord_units!(GradientUnits, CoordUnits::ObjectBoundingBox);
 
@@ -125,6 +226,8 @@ pub struct Stop {
     /// <stop offset="..."/>
     offset: UnitInterval,
 
+    /// <stop path="..."/>, only used for stops in a <meshpatch>
+    path: Option<MeshEdge>,
     // stop-color and stop-opacity are not attributes; they are properties, so
     // they go into property_defs.rs
 }
@@ -147,6 +250,9 @@ impl NodeTrait for Stop {
                         .parse_and_validate(value, validate_offset)
                         .map(|l| UnitInterval::clamp(l.length))?
                 }
+                expanded_name!(svg "path") => {
+                    self.path = Some(MeshEdge::parse_path_data(value).attribute(attr)?)
+                }
                 _ => (),
             }
         }
@@ -155,6 +261,12 @@ impl NodeTrait for Stop {
     }
 }
 
+impl Stop {
+    pub fn mesh_edge(&self) -> Option<MeshEdge> {
+        self.path
+    }
+}
+
 /// Parameters specific to each gradient type, before being resolved.
 /// These will be composed together with UnreseolvedVariant from fallback
 /// nodes (referenced with e.g. <linearGradient xlink:href="#fallback">) to form
@@ -283,7 +395,16 @@ impl Variant {
     /// Creates a cairo::Gradient corresponding to the gradient type of the
     /// &self Variant.  This does not have color stops set on it yet;
     /// call Gradient.add_color_stops_to_pattern() afterwards.
-    fn to_cairo_gradient(&self, values: &ComputedValues, params: &ViewParams) -> cairo::Gradient {
+    ///
+    /// For radial gradients, `periods` scales the outer circle away from the focus
+    /// point; this is used to emulate `spreadMethod` by repeating the stops.  See
+    /// `spread_periods()`.
+    fn to_cairo_gradient(
+        &self,
+        values: &ComputedValues,
+        params: &ViewParams,
+        periods: usize,
+    ) -> cairo::Gradient {
         match *self {
             Variant::Linear { x1, y1, x2, y2 } => {
                 cairo::Gradient::clone(&cairo::LinearGradient::new(
@@ -295,21 +416,43 @@ impl Variant {
             }
 
             Variant::Radial { cx, cy, r, fx, fy } => {
-                let n_cx = cx.normalize(values, params);
-                let n_cy = cy.normalize(values, params);
-                let n_r = r.normalize(values, params);
-                let n_fx = fx.normalize(values, params);
-                let n_fy = fy.normalize(values, params);
-                let (new_fx, new_fy) = fix_focus_point(n_fx, n_fy, n_cx, n_cy, n_r);
+                let (fx, fy, cx, cy, r) = radial_geometry(cx, cy, r, fx, fy, values, params);
+                let k = periods as f64;
 
                 cairo::Gradient::clone(&cairo::RadialGradient::new(
-                    new_fx, new_fy, 0.0, n_cx, n_cy, n_r,
+                    fx,
+                    fy,
+                    0.0,
+                    fx + (cx - fx) * k,
+                    fy + (cy - fy) * k,
+                    r * k,
                 ))
             }
         }
     }
 }
 
+/// Returns the normalized (fx, fy, cx, cy, r) of a radial gradient,
+/// with the focus point moved inside the circle if necessary.
+fn radial_geometry(
+    cx: Length<Horizontal>,
+    cy: Length<Vertical>,
+    r: Length<Both>,
+    fx: Length<Horizontal>,
+    fy: Length<Vertical>,
+    values: &ComputedValues,
+    params: &ViewParams,
+) -> (f64, f64, f64, f64, f64) {
+    let n_cx = cx.normalize(values, params);
+    let n_cy = cy.normalize(values, params);
+    let n_r = r.normalize(values, params);
+    let n_fx = fx.normalize(values, params);
+    let n_fy = fy.normalize(values, params);
+    let (new_fx, new_fy) = fix_focus_point(n_fx, n_fy, n_cx, n_cy, n_r);
+
+    (new_fx, new_fy, n_cx, n_cy, n_r)
+}
+
 /// Fields shared by all gradient nodes
 #[derive(Default)]
 struct Common {
@@ -318,8 +461,6 @@ struct Common {
     spread: Option<SpreadMethod>,
 
     fallback: Option<Fragment>,
-
-    resolved: RefCell<Option<Gradient>>,
 }
 
 /// Node for the <linearGradient> element
@@ -349,7 +490,7 @@ pub struct RadialGradient {
 /// gradients, we store all fields as Option<T> - if None, it means
 /// that the field is not specified; if Some(T), it means that the
 /// field was specified.
-struct UnresolvedGradient {
+pub struct UnresolvedGradient {
     units: Option<GradientUnits>,
     affine: Option<cairo::Matrix>,
     spread: Option<SpreadMethod>,
@@ -370,38 +511,6 @@ pub struct Gradient {
 }
 
 impl UnresolvedGradient {
-    fn to_resolved(self) -> Gradient {
-        assert!(self.is_resolved());
-
-        let UnresolvedGradient {
-            units,
-            affine,
-            spread,
-            stops,
-            variant,
-        } = self;
-
-        match variant {
-            UnresolvedVariant::Linear { .. } => Gradient {
-                units: units.unwrap(),
-                affine: affine.unwrap(),
-                spread: spread.unwrap(),
-                stops: stops.unwrap(),
-
-                variant: variant.to_resolved(),
-            },
-
-            UnresolvedVariant::Radial { .. } => Gradient {
-                units: units.unwrap(),
-                affine: affine.unwrap(),
-                spread: spread.unwrap(),
-                stops: stops.unwrap(),
-
-                variant: variant.to_resolved(),
-            },
-        }
-    }
-
     /// Helper for add_color_stops_from_node()
     fn add_color_stop(
         &mut self,
@@ -466,6 +575,68 @@ impl UnresolvedGradient {
             }
         }
     }
+}
+
+impl Resolve for UnresolvedGradient {
+    type Resolved = Gradient;
+
+    const FALLBACK_TYPES: &'static [NodeType] =
+        &[NodeType::LinearGradient, NodeType::RadialGradient];
+
+    fn from_node(node: &RsvgNode) -> (UnresolvedGradient, Option<Fragment>) {
+        let borrowed_node = node.borrow();
+
+        let Unresolved { gradient, fallback } = match borrowed_node.get_type() {
+            NodeType::LinearGradient => borrowed_node
+                .get_impl::<LinearGradient>()
+                .get_unresolved(node),
+
+            NodeType::RadialGradient => borrowed_node
+                .get_impl::<RadialGradient>()
+                .get_unresolved(node),
+
+            _ => unreachable!(),
+        };
+
+        (gradient, fallback)
+    }
+
+    fn to_resolved(self, node: &RsvgNode) -> Gradient {
+        assert!(self.is_resolved());
+
+        let UnresolvedGradient {
+            units,
+            affine,
+            spread,
+            stops,
+            variant,
+        } = self;
+
+        // A transform on the gradient element itself is applied after the
+        // gradientTransform, i.e. it maps the already-transformed gradient
+        // coordinate system onto the target coordinate system.
+        let affine = cairo::Matrix::multiply(&affine.unwrap(), &node.borrow().get_transform());
+
+        match variant {
+            UnresolvedVariant::Linear { .. } => Gradient {
+                units: units.unwrap(),
+                affine,
+                spread: spread.unwrap(),
+                stops: stops.unwrap(),
+
+                variant: variant.to_resolved(),
+            },
+
+            UnresolvedVariant::Radial { .. } => Gradient {
+                units: units.unwrap(),
+                affine,
+                spread: spread.unwrap(),
+                stops: stops.unwrap(),
+
+                variant: variant.to_resolved(),
+            },
+        }
+    }
 
     fn is_resolved(&self) -> bool {
         self.units.is_some()
@@ -611,82 +782,13 @@ impl NodeTrait for RadialGradient {
     }
 }
 
-macro_rules! impl_paint_source {
-    ($gradient:ty, $node_type:pat, $other_gradient:ty, $other_type:pat,) => {
-        impl PaintSource for $gradient {
-            type Resolved = Gradient;
-
-            fn resolve(
-                &self,
-                node: &RsvgNode,
-                draw_ctx: &mut DrawingCtx,
-            ) -> Result<Self::Resolved, AcquireError> {
-                let mut resolved = self.common.resolved.borrow_mut();
-                if let Some(ref gradient) = *resolved {
-                    return Ok(gradient.clone());
-                }
-
-                let Unresolved { mut gradient, mut fallback } = self.get_unresolved(node);
-
-                let mut stack = NodeStack::new();
-
-                while !gradient.is_resolved() {
-                    if let Some(fragment) = fallback {
-                        let acquired = acquire_gradient(draw_ctx, &fragment)?;
-                        let acquired_node = acquired.get();
-
-                        if stack.contains(acquired_node) {
-                            return Err(AcquireError::CircularReference(acquired_node.clone()));
-                        }
-
-                        let borrowed_node = acquired_node.borrow();
-                        let unresolved = match borrowed_node.get_type() {
-                            $node_type => {
-                                let a_gradient = borrowed_node.get_impl::<$gradient>();
-                                a_gradient.get_unresolved(&acquired_node)
-                            }
-
-                            $other_type => {
-                                let a_gradient = borrowed_node.get_impl::<$other_gradient>();
-                                a_gradient.get_unresolved(&acquired_node)
-                            }
-
-                            _ => unreachable!()
-                        };
-
-                        gradient = gradient.resolve_from_fallback(&unresolved.gradient);
-                        fallback = unresolved.fallback;
-
-                        stack.push(acquired_node);
-                    } else {
-                        gradient = gradient.resolve_from_defaults();
-                        break;
-                    }
-                }
-
-                let gradient = gradient.to_resolved();
-
-                *resolved = Some(gradient.clone());
-
-                Ok(gradient)
-            }
-        }
-    }
+impl PaintSource for LinearGradient {
+    type Unresolved = UnresolvedGradient;
 }
 
-impl_paint_source!(
-    LinearGradient,
-    NodeType::LinearGradient,
-    RadialGradient,
-    NodeType::RadialGradient,
-);
-
-impl_paint_source!(
-    RadialGradient,
-    NodeType::RadialGradient,
-    LinearGradient,
-    NodeType::LinearGradient,
-);
+impl PaintSource for RadialGradient {
+    type Unresolved = UnresolvedGradient;
+}
 
 impl AsPaintSource for Gradient {
     fn set_as_paint_source(
@@ -707,21 +809,32 @@ impl AsPaintSource for Gradient {
             draw_ctx.get_view_params()
         };
 
-        let p = match self.variant {
-            Variant::Linear { .. } => {
-                let g = self.variant.to_cairo_gradient(values, &params);
-                cairo::Gradient::clone(&g)
-            }
+        let affine = self.gradient_to_user_affine(bbox);
+
+        if !sanitize::is_valid_matrix(&affine) {
+            rsvg_log!("(not using gradient with an invalid transform)");
+            return Ok(false);
+        }
 
-            Variant::Radial { .. } => {
-                let g = self.variant.to_cairo_gradient(values, &params);
-                cairo::Gradient::clone(&g)
+        let cr = draw_ctx.get_cairo_context();
+
+        let periods = match self.variant {
+            Variant::Radial { cx, cy, r, fx, fy } if self.spread != SpreadMethod::Pad => {
+                let (fx, fy, cx, cy, r) = radial_geometry(cx, cy, r, fx, fy, values, &params);
+                spread_periods(fx, fy, cx, cy, r, &affine, cr.clip_extents())
             }
+
+            _ => None,
         };
 
-        self.set_on_cairo_pattern(&p, bbox, opacity);
+        let p = self
+            .variant
+            .to_cairo_gradient(values, &params, periods.unwrap_or(1));
+
+        let space = InterpolationSpace::new(values.color_interpolation, draw_ctx.oklab_gradients());
+
+        self.set_on_cairo_pattern(&p, affine, opacity, space, periods);
 
-        let cr = draw_ctx.get_cairo_context();
         cr.set_source(&p);
 
         Ok(true)
@@ -729,12 +842,8 @@ impl AsPaintSource for Gradient {
 }
 
 impl Gradient {
-    fn set_on_cairo_pattern(
-        &self,
-        pattern: &cairo::Gradient,
-        bbox: &BoundingBox,
-        opacity: UnitInterval,
-    ) {
+    /// Returns the transformation from the gradient's coordinate system to user space
+    fn gradient_to_user_affine(&self, bbox: &BoundingBox) -> cairo::Matrix {
         let mut affine = self.affine;
 
         if self.units == GradientUnits(CoordUnits::ObjectBoundingBox) {
@@ -750,36 +859,193 @@ impl Gradient {
             affine = cairo::Matrix::multiply(&affine, &bbox_matrix);
         }
 
+        affine
+    }
+
+    fn set_on_cairo_pattern(
+        &self,
+        pattern: &cairo::Gradient,
+        mut affine: cairo::Matrix,
+        opacity: UnitInterval,
+        space: InterpolationSpace,
+        periods: Option<usize>,
+    ) {
         affine.invert();
         pattern.set_matrix(affine);
-        pattern.set_extend(cairo::Extend::from(self.spread));
 
-        self.add_color_stops_to_pattern(pattern, opacity);
+        let mut stops = interpolated_stops(&self.stops, opacity, space);
+
+        if let Some(periods) = periods {
+            stops = spread_stops(&stops, self.spread, periods);
+            pattern.set_extend(cairo::Extend::Pad);
+        } else {
+            pattern.set_extend(cairo::Extend::from(self.spread));
+        }
+
+        for (offset, rgba) in stops {
+            pattern.add_color_stop_rgba(offset, rgba[0], rgba[1], rgba[2], rgba[3]);
+        }
+    }
+}
+
+/// Maximum number of times that we repeat the stops of a radial gradient
+/// to emulate `spreadMethod`; see `spread_periods()`.
+const MAX_SPREAD_PERIODS: usize = 128;
+
+/// Computes how many periods of a radial gradient are needed to cover an area
+///
+/// Cairo's `EXTEND_REPEAT` and `EXTEND_REFLECT` for radial gradients whose focus
+/// is not at the center do not match SVG in all cases (for example, the PDF backend
+/// cannot represent them), so for `spreadMethod="reflect|repeat"` we instead scale
+/// the circle and repeat the stops enough times to cover the `extents`, which are
+/// in user space, and use `EXTEND_PAD`.
+///
+/// Returns `None` if the stops would have to be repeated too many times; in that
+/// case we just use Cairo's extend mode.
+fn spread_periods(
+    fx: f64,
+    fy: f64,
+    cx: f64,
+    cy: f64,
+    r: f64,
+    gradient_to_user: &cairo::Matrix,
+    extents: (f64, f64, f64, f64),
+) -> Option<usize> {
+    let user_to_gradient = gradient_to_user.try_invert().ok()?;
+
+    // Each period is at least this wide along any ray that starts at the focus
+    let min_width = r - (cx - fx).hypot(cy - fy);
+    if min_width <= 0.0 {
+        return None;
     }
 
-    fn add_color_stops_to_pattern(&self, pattern: &cairo::Gradient, opacity: UnitInterval) {
-        for stop in &self.stops {
+    let (x1, y1, x2, y2) = extents;
+
+    let max_distance = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)]
+        .iter()
+        .map(|&(x, y)| {
+            let (gx, gy) = user_to_gradient.transform_point(x, y);
+            (gx - fx).hypot(gy - fy)
+        })
+        .fold(0.0, f64::max);
+
+    let periods = (max_distance / min_width).ceil().max(1.0);
+
+    if periods > MAX_SPREAD_PERIODS as f64 {
+        None
+    } else {
+        Some(periods as usize)
+    }
+}
+
+/// Repeats the stops of a gradient `periods` times within [0, 1]
+///
+/// Every other repetition is reversed for `SpreadMethod::Reflect`.  The first and
+/// last colors are extended to the start and end of each period, as with pad.
+fn spread_stops(
+    stops: &[(f64, [f64; 4])],
+    spread: SpreadMethod,
+    periods: usize,
+) -> Vec<(f64, [f64; 4])> {
+    if stops.is_empty() {
+        return Vec::new();
+    }
+
+    let first = stops[0].1;
+    let last = stops[stops.len() - 1].1;
+    let k = periods as f64;
+
+    let mut result = Vec::with_capacity((stops.len() + 2) * periods);
+
+    for i in 0..periods {
+        let base = i as f64;
+
+        if spread == SpreadMethod::Reflect && i % 2 == 1 {
+            result.push((base / k, last));
+            for &(offset, rgba) in stops.iter().rev() {
+                result.push(((base + 1.0 - offset) / k, rgba));
+            }
+            result.push(((base + 1.0) / k, first));
+        } else {
+            result.push((base / k, first));
+            for &(offset, rgba) in stops {
+                result.push(((base + offset) / k, rgba));
+            }
+            result.push(((base + 1.0) / k, last));
+        }
+    }
+
+    result
+}
+
+/// Computes the (offset, unpremultiplied sRGBA) stops to give to Cairo
+///
+/// For sRGB these are just the gradient's stops; for other spaces, intermediate
+/// stops are inserted between each pair of distinct stops.
+fn interpolated_stops(
+    stops: &[ColorStop],
+    opacity: UnitInterval,
+    space: InterpolationSpace,
+) -> Vec<(f64, [f64; 4])> {
+    let UnitInterval(o) = opacity;
+
+    let stops: Vec<(f64, [f64; 4])> = stops
+        .iter()
+        .map(|stop| {
             let UnitInterval(stop_offset) = stop.offset;
-            let UnitInterval(o) = opacity;
             let UnitInterval(stop_opacity) = stop.opacity;
 
-            pattern.add_color_stop_rgba(
+            (
                 stop_offset,
-                f64::from(stop.rgba.red_f32()),
-                f64::from(stop.rgba.green_f32()),
-                f64::from(stop.rgba.blue_f32()),
-                f64::from(stop.rgba.alpha_f32()) * stop_opacity * o,
-            );
+                [
+                    f64::from(stop.rgba.red_f32()),
+                    f64::from(stop.rgba.green_f32()),
+                    f64::from(stop.rgba.blue_f32()),
+                    f64::from(stop.rgba.alpha_f32()) * stop_opacity * o,
+                ],
+            )
+        })
+        .collect();
+
+    if space == InterpolationSpace::Srgb {
+        return stops;
+    }
+
+    let mut result = Vec::with_capacity(stops.len() * INTERPOLATION_SEGMENTS);
+
+    for (i, &(offset, rgba)) in stops.iter().enumerate() {
+        result.push((offset, rgba));
+
+        let (next_offset, next_rgba) = match stops.get(i + 1) {
+            Some(&next) => next,
+            None => break,
+        };
+
+        if next_offset <= offset || next_rgba[..3] == rgba[..3] {
+            continue;
+        }
+
+        let from = space.from_srgb([rgba[0], rgba[1], rgba[2]]);
+        let to = space.from_srgb([next_rgba[0], next_rgba[1], next_rgba[2]]);
+
+        for j in 1..INTERPOLATION_SEGMENTS {
+            let t = j as f64 / INTERPOLATION_SEGMENTS as f64;
+            let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+            let rgb = space.to_srgb([
+                lerp(from[0], to[0]),
+                lerp(from[1], to[1]),
+                lerp(from[2], to[2]),
+            ]);
+
+            result.push((
+                lerp(offset, next_offset),
+                [rgb[0], rgb[1], rgb[2], lerp(rgba[3], next_rgba[3])],
+            ));
         }
     }
-}
 
-/// Acquires a node of linearGradient or radialGradient type
-fn acquire_gradient<'a>(
-    draw_ctx: &'a mut DrawingCtx,
-    fragment: &Fragment,
-) -> Result<AcquiredNode, AcquireError> {
-    draw_ctx.acquire_node(fragment, &[NodeType::LinearGradient, NodeType::RadialGradient])
+    result
 }
 
 #[cfg(test)]
@@ -817,6 +1083,51 @@ mod tests {
         assert_tuples_equal(&fix_focus_point(1.0, 1.0, 3.0, 1.0, 1.0), &(2.0, 1.0));
     }
 
+    #[test]
+    fn computes_radial_geometry_with_offset_focus() {
+        let values = ComputedValues::default();
+        let params = ViewParams::new(96.0, 96.0, 100.0, 100.0);
+        let identity = cairo::Matrix::identity();
+        let extents = (0.0, 0.0, 100.0, 100.0);
+
+        let geometry = |fx, fy| {
+            radial_geometry(
+                Length::<Horizontal>::new(0.5, LengthUnit::Percent),
+                Length::<Vertical>::new(0.5, LengthUnit::Percent),
+                Length::<Both>::new(20.0, LengthUnit::Px),
+                Length::<Horizontal>::new(fx, LengthUnit::Px),
+                Length::<Vertical>::new(fy, LengthUnit::Px),
+                &values,
+                &params,
+            )
+        };
+
+        // A focus inside the end circle is kept
+        let (fx, fy, cx, cy, r) = geometry(40.0, 50.0);
+        assert_eq!((fx, fy, cx, cy, r), (40.0, 50.0, 50.0, 50.0, 20.0));
+
+        // Each period is at least 10 units wide, and the farthest corner is
+        // sqrt(60² + 50²) away from the focus
+        assert_eq!(
+            spread_periods(fx, fy, cx, cy, r, &identity, extents),
+            Some(8)
+        );
+
+        // A focus right on the edge of the end circle stays there...
+        let (fx, fy, cx, cy, r) = geometry(70.0, 50.0);
+        assert_eq!((fx, fy, cx, cy, r), (70.0, 50.0, 50.0, 50.0, 20.0));
+
+        // ... but the periods have no width along the ray towards that edge
+        assert_eq!(spread_periods(fx, fy, cx, cy, r, &identity, extents), None);
+
+        // A focus outside the end circle is moved to its edge...
+        let (fx, fy, cx, cy, r) = geometry(90.0, 50.0);
+        assert_eq!((fx, fy, cx, cy, r), (70.0, 50.0, 50.0, 50.0, 20.0));
+
+        // ... and then the stops can't be repeated to emulate the spread method
+        assert_eq!(spread_periods(fx, fy, cx, cy, r, &identity, extents), None);
+    }
+
     #[test]
     fn gradient_resolved_from_defaults_is_really_resolved() {
         let node = RsvgNode::new(NodeData::new(
@@ -847,4 +1158,162 @@ mod tests {
         let gradient = gradient.resolve_from_defaults();
         assert!(gradient.is_resolved());
     }
+
+    fn stop(offset: f64, rgba: cssparser::RGBA) -> ColorStop {
+        ColorStop {
+            offset: UnitInterval(offset),
+            rgba,
+            opacity: UnitInterval(1.0),
+        }
+    }
+
+    #[test]
+    fn srgb_interpolation_uses_stops_as_is() {
+        let stops = [
+            stop(0.0, cssparser::RGBA::new(255, 0, 0, 255)),
+            stop(1.0, cssparser::RGBA::new(0, 0, 255, 255)),
+        ];
+
+        let result = interpolated_stops(&stops, UnitInterval(0.5), InterpolationSpace::Srgb);
+
+        assert_eq!(
+            result,
+            vec![(0.0, [1.0, 0.0, 0.0, 0.5]), (1.0, [0.0, 0.0, 1.0, 0.5])]
+        );
+    }
+
+    #[test]
+    fn linear_rgb_interpolation_is_brighter_in_the_middle() {
+        let stops = [
+            stop(0.0, cssparser::RGBA::new(0, 0, 0, 255)),
+            stop(1.0, cssparser::RGBA::new(255, 255, 255, 255)),
+        ];
+
+        let result = interpolated_stops(&stops, UnitInterval(1.0), InterpolationSpace::LinearRgb);
+
+        assert_eq!(result.len(), INTERPOLATION_SEGMENTS + 1);
+        assert_eq!(result[0], (0.0, [0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(result[INTERPOLATION_SEGMENTS], (1.0, [1.0, 1.0, 1.0, 1.0]));
+
+        let (offset, rgba) = result[INTERPOLATION_SEGMENTS / 2];
+        assert_approx_eq_cairo!(offset, 0.5);
+
+        // 50% linear light is about 73.5% in sRGB
+        assert!((rgba[0] - 0.735).abs() < 0.001);
+        assert_approx_eq_cairo!(rgba[3], 1.0);
+    }
+
+    #[test]
+    fn oklab_interpolation_does_not_gray_out() {
+        let stops = [
+            stop(0.0, cssparser::RGBA::new(0, 0, 255, 255)),
+            stop(1.0, cssparser::RGBA::new(255, 255, 0, 255)),
+        ];
+
+        let result = interpolated_stops(&stops, UnitInterval(1.0), InterpolationSpace::Oklab);
+        let (_, mid) = result[INTERPOLATION_SEGMENTS / 2];
+
+        // Plain sRGB interpolation would give a flat gray (0.5, 0.5, 0.5) here
+        let chroma = mid[0].max(mid[1]).max(mid[2]) - mid[0].min(mid[1]).min(mid[2]);
+        assert!(chroma > 0.1);
+    }
+
+    #[test]
+    fn hard_stops_are_not_subdivided() {
+        let stops = [
+            stop(0.0, cssparser::RGBA::new(255, 0, 0, 255)),
+            stop(0.5, cssparser::RGBA::new(255, 0, 0, 255)),
+            stop(0.5, cssparser::RGBA::new(0, 0, 255, 255)),
+        ];
+
+        let result = interpolated_stops(&stops, UnitInterval(1.0), InterpolationSpace::LinearRgb);
+
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn oklab_round_trips() {
+        let rgb = [0.2, 0.5, 0.8];
+        let back = InterpolationSpace::Oklab.to_srgb(InterpolationSpace::Oklab.from_srgb(rgb));
+
+        for i in 0..3 {
+            assert!((rgb[i] - back[i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn computes_spread_periods() {
+        let identity = cairo::Matrix::identity();
+
+        // Concentric gradient with r=10; the farthest corner is 50*sqrt(2) away
+        let extents = (-50.0, -50.0, 50.0, 50.0);
+        assert_eq!(
+            spread_periods(0.0, 0.0, 0.0, 0.0, 10.0, &identity, extents),
+            Some(8)
+        );
+
+        // The focus is 5 units off center, so periods are at least 5 units wide
+        assert_eq!(
+            spread_periods(5.0, 0.0, 0.0, 0.0, 10.0, &identity, (0.0, 0.0, 20.0, 0.0)),
+            Some(3)
+        );
+
+        // The gradient gets scaled to user space
+        let mut scale = cairo::Matrix::identity();
+        scale.scale(10.0, 10.0);
+        assert_eq!(
+            spread_periods(0.0, 0.0, 0.0, 0.0, 1.0, &scale, (0.0, 0.0, 30.0, 40.0)),
+            Some(5)
+        );
+
+        // Focus on the edge of the circle
+        assert_eq!(
+            spread_periods(10.0, 0.0, 0.0, 0.0, 10.0, &identity, (0.0, 0.0, 1.0, 1.0)),
+            None
+        );
+
+        // Too many periods
+        assert_eq!(
+            spread_periods(0.0, 0.0, 0.0, 0.0, 1.0, &identity, (0.0, 0.0, 1000.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn spreads_stops() {
+        const A: [f64; 4] = [1.0, 0.0, 0.0, 1.0];
+        const B: [f64; 4] = [0.0, 0.0, 1.0, 1.0];
+
+        let stops = [(0.25, A), (0.75, B)];
+
+        assert_eq!(
+            spread_stops(&stops, SpreadMethod::Repeat, 2),
+            vec![
+                (0.0, A),
+                (0.125, A),
+                (0.375, B),
+                (0.5, B),
+                (0.5, A),
+                (0.625, A),
+                (0.875, B),
+                (1.0, B),
+            ]
+        );
+
+        assert_eq!(
+            spread_stops(&stops, SpreadMethod::Reflect, 2),
+            vec![
+                (0.0, A),
+                (0.125, A),
+                (0.375, B),
+                (0.5, B),
+                (0.5, B),
+                (0.625, B),
+                (0.875, A),
+                (1.0, A),
+            ]
+        );
+
+        assert!(spread_stops(&[], SpreadMethod::Repeat, 3).is_empty());
+    }
 }
@@ -1,24 +1,55 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::ptr;
 use std::rc::Rc;
 
-use cairo::{self, ImageSurface, Status};
+use cairo::{self, ImageSurface, PsSurface, Status};
 use gio;
 use glib;
 use libc;
 use locale_config::{LanguageRange, Locale};
 
 use crate::allowed_url::{AllowedUrl, Href};
+use crate::animated_image::{AnimatedImageEncoder, AnimatedImageFormat, AnimationOptions};
 use crate::bbox::BoundingBox;
+use crate::clip_path::ClipShape;
+use crate::complexity::ComplexityReport;
+use crate::css::RuleInfo;
+use crate::diff::{diff_trees, DocumentDiff};
 use crate::document::Document;
+use crate::document_cache::SharedSource;
 use crate::dpi::Dpi;
-use crate::drawing_ctx::DrawingCtx;
+use crate::drawing_ctx::{DrawingCtx, RenderQuality, StrokeMetrics};
+use crate::element_overrides::ElementOverrides;
 use crate::error::{DefsLookupErrorKind, LoadingError, RenderingError};
-use crate::node::{CascadedValues, RsvgNode};
-use crate::rect::RectangleExt;
-use crate::structure::{IntrinsicDimensions, Svg};
+#[cfg(feature = "text")]
+use crate::font_face::ApplicationFonts;
+use crate::font_props::{FontFallback, FontFamilyMap};
+use crate::glyph_run::GlyphRun;
+use crate::limits;
+use crate::marker::MarkerPosition;
+use crate::media::{ColorScheme, MediaFeatures};
+use crate::node::{CascadedValues, NodeCascade, NodeType, RsvgNode};
+use crate::path_builder::PathSegment;
+use crate::path_measure::{PathMeasure, PathPoint};
+use crate::path_parser::PathDataRecovery;
+use crate::postscript::PostScriptOptions;
+use crate::properties::ComputedValues;
+use crate::property_defs::{Display, Visibility};
+use crate::rect::{IRect, RectangleExt};
+use crate::render_hooks::RenderHooks;
+use crate::structure::{switch_child, IntrinsicDimensions, Svg};
+use crate::surface_utils::iterators::Pixels;
+use crate::surface_utils::shared_surface::{SharedImageSurface, SurfaceType};
+use crate::validate::{validate, SvgProfile, ValidationReport};
 use url::Url;
 
+/// Callback that maps the `href` of an `<image>` element to an image surface
+///
+/// This is called with the `href` exactly as it appears in the document, like
+/// `photo://user-avatar`.  If it returns `None`, the image is loaded from the `href`
+/// as usual.
+pub type ImageResolver = Rc<dyn Fn(&str) -> Option<cairo::ImageSurface>>;
+
 #[derive(Clone)]
 pub struct LoadOptions {
     /// Base URL
@@ -30,6 +61,34 @@ pub struct LoadOptions {
     /// Whether to keep original (undecoded) image data to embed in Cairo PDF surfaces
     pub keep_image_data: bool,
 
+    /// How to handle errors in path data
+    pub path_data_recovery: PathDataRecovery,
+
+    /// Whether gradients with `color-interpolation: auto` get interpolated in OKLab
+    pub oklab_gradients: bool,
+
+    /// Whether newlines in the character data of text elements start new lines
+    pub multiline_text: bool,
+
+    /// Application-supplied images for `<image>` elements
+    pub image_resolver: Option<ImageResolver>,
+
+    /// Environment against which CSS media queries are evaluated
+    pub media_features: MediaFeatures,
+
+    /// Concrete families for the generic font families
+    pub font_families: FontFamilyMap,
+
+    /// Fonts to use instead of the system's fonts
+    #[cfg(feature = "text")]
+    pub fonts: Option<ApplicationFonts>,
+
+    /// Maximum width and height of the surfaces for pattern tiles, in device pixels
+    pub max_pattern_tile_size: i32,
+
+    /// Maximum cost of a render, in device pixels; see `resources.rs`
+    pub resource_budget: Option<u64>,
+
     locale: Locale,
 }
 
@@ -39,6 +98,16 @@ impl LoadOptions {
             base_url,
             unlimited_size: false,
             keep_image_data: false,
+            path_data_recovery: PathDataRecovery::default(),
+            oklab_gradients: false,
+            multiline_text: false,
+            image_resolver: None,
+            media_features: MediaFeatures::default(),
+            font_families: FontFamilyMap::default(),
+            #[cfg(feature = "text")]
+            fonts: None,
+            max_pattern_tile_size: limits::MAX_PATTERN_TILE_SIZE,
+            resource_budget: None,
             locale: locale_from_environment(),
         }
     }
@@ -53,11 +122,77 @@ impl LoadOptions {
         self
     }
 
+    pub fn with_path_data_recovery(mut self, recovery: PathDataRecovery) -> Self {
+        self.path_data_recovery = recovery;
+        self
+    }
+
+    pub fn with_oklab_gradients(mut self, oklab: bool) -> Self {
+        self.oklab_gradients = oklab;
+        self
+    }
+
+    pub fn with_multiline_text(mut self, multiline: bool) -> Self {
+        self.multiline_text = multiline;
+        self
+    }
+
+    pub fn with_image_resolver(mut self, resolver: ImageResolver) -> Self {
+        self.image_resolver = Some(resolver);
+        self
+    }
+
+    pub fn with_media_features(mut self, features: MediaFeatures) -> Self {
+        self.media_features = features;
+        self
+    }
+
+    pub fn with_font_families(mut self, font_families: FontFamilyMap) -> Self {
+        self.font_families = font_families;
+        self
+    }
+
+    #[cfg(feature = "text")]
+    pub fn with_fonts(mut self, fonts: ApplicationFonts) -> Self {
+        self.fonts = Some(fonts);
+        self
+    }
+
+    pub fn with_max_pattern_tile_size(mut self, size: i32) -> Self {
+        self.max_pattern_tile_size = size;
+        self
+    }
+
+    pub fn with_resource_budget(mut self, budget: u64) -> Self {
+        self.resource_budget = Some(budget);
+        self
+    }
+
+    /// Overrides the user's languages, used to evaluate `systemLanguage` attributes.
+    ///
+    /// By default, the languages come from the environment (`LANGUAGE`, `LC_ALL`,
+    /// `LC_MESSAGES`, `LANG`).  Each element of `languages` is a BCP47 language
+    /// tag like `"es-MX"`, in order of preference; invalid tags are ignored.
+    pub fn with_languages<S: AsRef<str>>(mut self, languages: &[S]) -> Self {
+        self.locale = locale_from_language_tags(languages);
+        self
+    }
+
     pub fn copy_with_base_url(&self, base_url: &AllowedUrl) -> Self {
         LoadOptions {
             base_url: Some((**base_url).clone()),
             unlimited_size: self.unlimited_size,
             keep_image_data: self.keep_image_data,
+            path_data_recovery: self.path_data_recovery,
+            oklab_gradients: self.oklab_gradients,
+            multiline_text: self.multiline_text,
+            image_resolver: self.image_resolver.clone(),
+            media_features: self.media_features,
+            font_families: self.font_families.clone(),
+            #[cfg(feature = "text")]
+            fonts: self.fonts.clone(),
+            max_pattern_tile_size: self.max_pattern_tile_size,
+            resource_budget: self.resource_budget,
             locale: self.locale.clone(),
         }
     }
@@ -65,6 +200,60 @@ impl LoadOptions {
     pub fn locale(&self) -> &Locale {
         &self.locale
     }
+
+    /// Whether loading the same data with `self` and `other` produces the same document.
+    ///
+    /// Image resolvers and font maps are compared by identity, since callbacks and
+    /// font maps cannot be compared.
+    pub fn is_equivalent_to(&self, other: &LoadOptions) -> bool {
+        let same_resolver = match (&self.image_resolver, &other.image_resolver) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+
+        #[cfg(feature = "text")]
+        let same_fonts = self.fonts == other.fonts;
+        #[cfg(not(feature = "text"))]
+        let same_fonts = true;
+
+        self.base_url == other.base_url
+            && self.unlimited_size == other.unlimited_size
+            && self.keep_image_data == other.keep_image_data
+            && self.path_data_recovery == other.path_data_recovery
+            && self.oklab_gradients == other.oklab_gradients
+            && self.multiline_text == other.multiline_text
+            && same_resolver
+            && self.media_features == other.media_features
+            && self.font_families == other.font_families
+            && same_fonts
+            && self.max_pattern_tile_size == other.max_pattern_tile_size
+            && self.resource_budget == other.resource_budget
+            && self.locale == other.locale
+    }
+}
+
+/// How much of the context of its ancestors an element is rendered with, when it gets
+/// rendered or measured by itself.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ElementContext {
+    /// The element is rendered as if it were the only one in the document; it does not
+    /// inherit property values like `fill` from its ancestors.
+    Isolated,
+
+    /// The element inherits property values from its ancestors, but their transformations,
+    /// clipping paths, masks, filters and opacity are not applied.  This is the default.
+    InheritedStyles,
+
+    /// The element is rendered with the transformations, clipping paths, masks,
+    /// filters and opacity of its ancestors, as it appears in the whole document.
+    Full,
+}
+
+impl Default for ElementContext {
+    fn default() -> ElementContext {
+        ElementContext::InheritedStyles
+    }
 }
 
 // Keep in sync with rsvg.h:RsvgDimensionData
@@ -177,7 +366,10 @@ impl Drop for SizeCallback {
 }
 
 pub struct Handle {
-    document: Rc<Document>,
+    document: RefCell<Rc<Document>>,
+
+    /// Where the document came from, while it is shared with other handles
+    shared_source: RefCell<Option<SharedSource>>,
 }
 
 impl Handle {
@@ -187,10 +379,50 @@ impl Handle {
         cancellable: Option<&gio::Cancellable>,
     ) -> Result<Handle, LoadingError> {
         Ok(Handle {
-            document: Rc::new(Document::load_from_stream(load_options, stream, cancellable)?),
+            document: RefCell::new(Rc::new(Document::load_from_stream(
+                load_options,
+                stream,
+                cancellable,
+            )?)),
+            shared_source: RefCell::new(None),
         })
     }
 
+    /// Creates a handle that shares an already loaded document, which was loaded from
+    /// `source`.
+    ///
+    /// The handle never changes the shared document; see `own_document()`.
+    pub(crate) fn from_shared_document(document: Rc<Document>, source: SharedSource) -> Handle {
+        Handle {
+            document: RefCell::new(document),
+            shared_source: RefCell::new(Some(source)),
+        }
+    }
+
+    fn document(&self) -> Rc<Document> {
+        self.document.borrow().clone()
+    }
+
+    /// Returns the handle's document, to change its state.
+    ///
+    /// If the document is shared with other handles, this first loads a private copy of
+    /// it from the original data, so that the change is not seen by the others.
+    fn own_document(&self) -> Result<Rc<Document>, LoadingError> {
+        let mut shared_source = self.shared_source.borrow_mut();
+
+        if let Some(ref source) = *shared_source {
+            let document = source.load_document(None).map_err(|e| {
+                rsvg_log!("could not load a private copy of a shared document: {}", e);
+                e
+            })?;
+
+            *self.document.borrow_mut() = Rc::new(document);
+            *shared_source = None;
+        }
+
+        Ok(self.document())
+    }
+
     pub fn has_sub(&self, id: &str) -> Result<bool, RenderingError> {
         match self.lookup_node(id) {
             Ok(_) => Ok(true),
@@ -201,6 +433,178 @@ impl Handle {
         }
     }
 
+    /// Returns whether the element with the specified `id` would be painted when
+    /// rendering the whole document.
+    ///
+    /// This considers the `display` and `visibility` properties, elements that are
+    /// in error, conditional processing attributes and `<switch>`, zero-sized `<svg>`
+    /// viewports, and elements that are only rendered by reference, like those inside
+    /// `<defs>` or `<clipPath>`.  It does not consider opacity or actual paint.
+    pub fn is_element_visible(&self, id: &str) -> Result<bool, RenderingError> {
+        let node = self.lookup_node(id).map_err(RenderingError::InvalidId)?;
+
+        // visibility is inherited, and children can override it, so only the
+        // element's own value matters
+        let values = CascadedValues::new_from_node(&node);
+        let visible = values.get().visibility == Visibility::Visible;
+
+        Ok(visible && node.ancestors().all(|n| element_renders_in_parent(&n)))
+    }
+
+    /// Shows or hides the element with the specified `id`, or removes the override
+    /// with `None`.
+    ///
+    /// This overrides the element's `display` property, and only cascades the
+    /// element's subtree again, so that toggling layers doesn't need to reload the
+    /// document.
+    pub fn set_element_visible(
+        &self,
+        id: &str,
+        visible: Option<bool>,
+    ) -> Result<(), RenderingError> {
+        let document = self
+            .own_document()
+            .map_err(|_| RenderingError::HandleIsNotLoaded)?;
+
+        let mut node = self.lookup_node(id).map_err(RenderingError::InvalidId)?;
+
+        node.borrow_mut().set_visible_override(visible);
+        node.recascade();
+
+        if node.ancestors().any(|n| n.borrow().get_will_change()) {
+            document.invalidate_will_change_caches();
+        } else {
+            document.invalidate_caches();
+        }
+
+        Ok(())
+    }
+
+    /// Marks the element with the specified `id` as expected to change between renders,
+    /// or removes the mark.
+    ///
+    /// Results cached from previous renders that depend on marked elements are kept
+    /// apart from the others, so that changing a marked element only drops those.
+    pub fn set_will_change(&self, id: &str, will_change: bool) -> Result<(), RenderingError> {
+        let document = self
+            .own_document()
+            .map_err(|_| RenderingError::HandleIsNotLoaded)?;
+
+        let mut node = self.lookup_node(id).map_err(RenderingError::InvalidId)?;
+
+        node.borrow_mut().set_will_change(will_change);
+
+        // Results that were cached before the element was marked are not kept apart
+        document.invalidate_caches();
+
+        Ok(())
+    }
+
+    /// Returns the computed value of a CSS property for the element with the specified `id`.
+    ///
+    /// The value is serialized as CSS text.  Returns `Ok(None)` if `property` is not
+    /// the name of a supported property.
+    pub fn get_computed_property(
+        &self,
+        id: &str,
+        property: &str,
+    ) -> Result<Option<String>, RenderingError> {
+        let node = self.lookup_node(id).map_err(RenderingError::InvalidId)?;
+
+        let cascaded = CascadedValues::new_from_node(&node);
+        let values = cascaded.get();
+
+        Ok(values.get_property_value(property))
+    }
+
+    pub fn get_stylesheet_rules(&self) -> Vec<RuleInfo> {
+        self.document().get_stylesheet_rules()
+    }
+
+    /// Computes the structural differences from this document to the `other` one.
+    pub fn diff(&self, other: &Handle) -> DocumentDiff {
+        diff_trees(&self.document().root(), &other.document().root())
+    }
+
+    /// Checks the document against a profile of the SVG specification.
+    pub fn validate(&self, profile: SvgProfile) -> ValidationReport {
+        validate(&self.document().root(), profile)
+    }
+
+    /// Returns statistics about the elements in the document.
+    pub fn get_complexity(&self) -> ComplexityReport {
+        self.document().complexity().clone()
+    }
+
+    /// Seeks the document's SMIL and CSS animations to `time`, in seconds.
+    ///
+    /// With `None`, the document is rendered without the effect of its animations.
+    pub fn set_time(&self, time: Option<f64>) {
+        if self.document().get_time() != time {
+            if let Ok(document) = self.own_document() {
+                document.set_time(time);
+            }
+        }
+    }
+
+    /// Makes all `feTurbulence` elements use `seed` instead of their `seed` attribute,
+    /// or restores the attributes with `None`.
+    pub fn set_turbulence_seed(&self, seed: Option<i32>) {
+        if self.document().get_turbulence_seed() != seed {
+            if let Ok(document) = self.own_document() {
+                document.set_turbulence_seed(seed);
+            }
+        }
+    }
+
+    /// Makes the element with the specified `id` match the `:target` pseudo-class,
+    /// or no element with `None`.
+    pub fn set_target(&self, id: Option<&str>) -> Result<(), RenderingError> {
+        let node = match id {
+            Some(id) => Some(self.lookup_node(id).map_err(RenderingError::InvalidId)?),
+            None => None,
+        };
+
+        let target = node
+            .as_ref()
+            .and_then(|n| n.borrow().get_id().map(String::from));
+
+        if self.document().get_target() != target {
+            let document = self
+                .own_document()
+                .map_err(|_| RenderingError::HandleIsNotLoaded)?;
+
+            document.set_target(target.as_ref().map(String::as_str));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the id of the element that matches the `:target` pseudo-class, if any.
+    pub fn get_target(&self) -> Option<String> {
+        self.document().get_target()
+    }
+
+    /// Sets a CSS stylesheet with the user origin, and runs the cascade again.
+    pub fn set_stylesheet(&self, css: &str) -> Result<(), LoadingError> {
+        self.own_document()?.set_user_stylesheet(css)
+    }
+
+    /// Changes the value of the `prefers-color-scheme` media feature, and runs the
+    /// cascade again.
+    pub fn set_color_scheme(&self, color_scheme: ColorScheme) {
+        if self.get_color_scheme() != color_scheme {
+            if let Ok(document) = self.own_document() {
+                document.set_color_scheme(color_scheme);
+            }
+        }
+    }
+
+    /// Returns the value of the `prefers-color-scheme` media feature.
+    pub fn get_color_scheme(&self) -> ColorScheme {
+        self.document().media_features().color_scheme
+    }
+
     pub fn get_dimensions(
         &self,
         dpi: Dpi,
@@ -283,7 +687,7 @@ impl Handle {
         let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
         let cr = cairo::Context::new(&target);
         let mut draw_ctx = DrawingCtx::new(
-            self.document.clone(),
+            self.document(),
             Some(node),
             &cr,
             viewport,
@@ -291,7 +695,7 @@ impl Handle {
             true,
             is_testing,
         );
-        let root = self.document.root();
+        let root = self.document().root();
 
         let bbox = draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
 
@@ -310,7 +714,7 @@ impl Handle {
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
         let node = self.get_node_or_root(id)?;
 
-        let root = self.document.root();
+        let root = self.document().root();
         let is_root = node == root;
 
         if is_root {
@@ -336,7 +740,7 @@ impl Handle {
         if let Some(id) = id {
             self.lookup_node(id).map_err(RenderingError::InvalidId)
         } else {
-            Ok(self.document.root())
+            Ok(self.document().root())
         }
     }
 
@@ -351,6 +755,256 @@ impl Handle {
         self.get_node_geometry_with_viewport(&node, viewport, dpi, is_testing)
     }
 
+    /// Returns where the markers of an element and its descendants are placed, in the
+    /// same coordinates as the rectangles from `get_geometry_for_layer()`.
+    pub fn get_marker_positions_for_layer(
+        &self,
+        id: Option<&str>,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Vec<MarkerPosition>, RenderingError> {
+        let node = self.get_node_or_root(id)?;
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
+        let mut draw_ctx = DrawingCtx::new(
+            self.document(),
+            Some(&node),
+            &cr,
+            viewport,
+            dpi,
+            true,
+            is_testing,
+        );
+        let root = self.document().root();
+
+        draw_ctx.record_marker_positions();
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_marker_positions())
+    }
+
+    /// Returns the shaped glyphs of the text in an element and its descendants, with
+    /// transformations to the same coordinates as the rectangles from
+    /// `get_geometry_for_layer()`.
+    pub fn get_glyph_runs_for_layer(
+        &self,
+        id: Option<&str>,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Vec<GlyphRun>, RenderingError> {
+        let node = self.get_node_or_root(id)?;
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
+        let mut draw_ctx = DrawingCtx::new(
+            self.document(),
+            Some(&node),
+            &cr,
+            viewport,
+            dpi,
+            true,
+            is_testing,
+        );
+        let root = self.document().root();
+
+        draw_ctx.record_glyph_runs();
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_glyph_runs())
+    }
+
+    /// Returns the runs of text in the document that didn't render with the font
+    /// family they asked for, or that have characters which no font could render.
+    pub fn get_font_fallbacks(
+        &self,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Vec<FontFallback>, RenderingError> {
+        let root = self.document().root();
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
+        let mut draw_ctx =
+            DrawingCtx::new(self.document(), None, &cr, viewport, dpi, false, is_testing);
+
+        draw_ctx.record_font_fallbacks();
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_font_fallbacks())
+    }
+
+    /// Returns the shapes whose intersection is the clip region of an element, in the
+    /// same coordinates as the rectangles from `get_geometry_for_layer()`.
+    pub fn get_clip_region_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Vec<ClipShape>, RenderingError> {
+        let node = self.get_node_or_root(Some(id))?;
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
+        let mut draw_ctx = DrawingCtx::new(
+            self.document(),
+            Some(&node),
+            &cr,
+            viewport,
+            dpi,
+            true,
+            is_testing,
+        );
+        let root = self.document().root();
+
+        draw_ctx.record_clip_region_of(&node);
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_recorded_clip_region())
+    }
+
+    /// Returns the transformation from the user space of an element to the device space
+    /// of `cr`, as if the document were rendered to `viewport` with `render_document()`.
+    ///
+    /// If `id` is `None`, returns the transformation from the document's user space, that
+    /// is, the coordinate system of the toplevel `<svg>`'s contents.  Returns `None` if
+    /// the element is not rendered.
+    pub fn get_transform_for_element(
+        &self,
+        cr: &cairo::Context,
+        id: Option<&str>,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Option<cairo::Matrix>, RenderingError> {
+        check_cairo_context(cr)?;
+
+        let node = if let Some(id) = id {
+            Some(self.lookup_node(id).map_err(RenderingError::InvalidId)?)
+        } else {
+            None
+        };
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let target_cr = cairo::Context::new(&target);
+        target_cr.set_matrix(cr.get_matrix());
+
+        let mut draw_ctx = DrawingCtx::new(
+            self.document(),
+            node.as_ref(),
+            &target_cr,
+            viewport,
+            dpi,
+            false,
+            is_testing,
+        );
+        let root = self.document().root();
+
+        draw_ctx.record_transform_of(node.as_ref());
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_recorded_transform())
+    }
+
+    /// Returns how a shape element gets stroked, with the transformation from its stroke
+    /// space to the device space of `cr`.  Returns `None` if the element is not a shape,
+    /// if it has no stroke, or if it is not rendered.
+    pub fn get_stroke_for_element(
+        &self,
+        cr: &cairo::Context,
+        id: &str,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Option<StrokeMetrics>, RenderingError> {
+        check_cairo_context(cr)?;
+
+        let node = self.lookup_node(id).map_err(RenderingError::InvalidId)?;
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let target_cr = cairo::Context::new(&target);
+        target_cr.set_matrix(cr.get_matrix());
+
+        let mut draw_ctx = DrawingCtx::new(
+            self.document(),
+            Some(&node),
+            &target_cr,
+            viewport,
+            dpi,
+            false,
+            is_testing,
+        );
+        let root = self.document().root();
+
+        draw_ctx.record_stroke_of(&node);
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_recorded_stroke())
+    }
+
+    /// Returns the path of a shape element, in its own user space, with basic shapes
+    /// converted to paths.  Returns an empty list if the element is not a shape.
+    pub fn get_path_segments_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Vec<PathSegment>, RenderingError> {
+        let node = self.get_node_or_root(Some(id))?;
+
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
+        let mut draw_ctx = DrawingCtx::new(
+            self.document(),
+            Some(&node),
+            &cr,
+            viewport,
+            dpi,
+            true,
+            is_testing,
+        );
+        let root = self.document().root();
+
+        draw_ctx.record_path_of(&node);
+        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(draw_ctx.take_recorded_path())
+    }
+
+    /// Returns the length of the path of a shape element, in its own user space, like
+    /// SVG's `getTotalLength()`.
+    pub fn get_path_length_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<f64, RenderingError> {
+        let segments = self.get_path_segments_for_element(id, viewport, dpi, is_testing)?;
+
+        Ok(PathMeasure::new(&segments)?.total_length())
+    }
+
+    /// Returns the point at `length` along the path of a shape element, and the direction
+    /// of the path there, like SVG's `getPointAtLength()`.
+    pub fn get_point_at_length_for_element(
+        &self,
+        id: &str,
+        viewport: &cairo::Rectangle,
+        length: f64,
+        dpi: Dpi,
+        is_testing: bool,
+    ) -> Result<Option<PathPoint>, RenderingError> {
+        let segments = self.get_path_segments_for_element(id, viewport, dpi, is_testing)?;
+
+        Ok(PathMeasure::new(&segments)?.path_point_at_length(length))
+    }
+
     fn lookup_node(&self, id: &str) -> Result<RsvgNode, DefsLookupErrorKind> {
         match Href::parse(&id).map_err(DefsLookupErrorKind::HrefError)? {
             Href::PlainUrl(_) => Err(DefsLookupErrorKind::CannotLookupExternalReferences),
@@ -376,7 +1030,7 @@ impl Handle {
                     return Err(DefsLookupErrorKind::CannotLookupExternalReferences);
                 }
 
-                match self.document.lookup_node_by_id(fragment.fragment()) {
+                match self.document().lookup_node_by_id(fragment.fragment()) {
                     Some(n) => Ok(n),
                     None => Err(DefsLookupErrorKind::NotFound),
                 }
@@ -407,7 +1061,16 @@ impl Handle {
             height: f64::from(dimensions.height),
         };
 
-        self.render_layer(cr, id, &viewport, dpi, is_testing)
+        self.render_layer(
+            cr,
+            id,
+            &viewport,
+            dpi,
+            &RenderHooks::default(),
+            RenderQuality::Full,
+            &ElementOverrides::default(),
+            is_testing,
+        )
     }
 
     pub fn render_document(
@@ -415,9 +1078,196 @@ impl Handle {
         cr: &cairo::Context,
         viewport: &cairo::Rectangle,
         dpi: Dpi,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
         is_testing: bool,
     ) -> Result<(), RenderingError> {
-        self.render_layer(cr, None, viewport, dpi, is_testing)
+        self.render_layer(
+            cr, None, viewport, dpi, hooks, quality, overrides, is_testing,
+        )
+    }
+
+    /// Samples the document's animations into frames, and encodes them as an animated image.
+    ///
+    /// Each frame is rendered to fill a `width` by `height` viewport.  The document's
+    /// current time is restored after rendering all the frames.
+    pub fn render_animation(
+        &self,
+        width: i32,
+        height: i32,
+        dpi: Dpi,
+        format: AnimatedImageFormat,
+        options: &AnimationOptions,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
+        is_testing: bool,
+    ) -> Result<Vec<u8>, RenderingError> {
+        if width <= 0 || height <= 0 || width > 0xffff || height > 0xffff {
+            return Err(RenderingError::Cairo(Status::InvalidSize));
+        }
+
+        if !options.is_valid() {
+            return Err(RenderingError::Cairo(Status::InvalidSize));
+        }
+
+        let viewport = cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: f64::from(width),
+            height: f64::from(height),
+        };
+
+        let saved_time = self.document().get_time();
+
+        let res = (|| {
+            let mut encoder = AnimatedImageEncoder::new(format, width, height, options);
+
+            for i in 0..options.num_frames() {
+                self.set_time(Some(options.frame_time(i)));
+
+                let surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+                {
+                    let cr = cairo::Context::new(&surface);
+                    self.render_document(
+                        &cr, &viewport, dpi, hooks, quality, overrides, is_testing,
+                    )?;
+                }
+
+                encoder.add_frame(surface)?;
+            }
+
+            Ok(encoder.finish())
+        })();
+
+        self.set_time(saved_time);
+
+        res
+    }
+
+    /// Renders the document as PostScript or EPS, and returns the contents of the file.
+    ///
+    /// The document is fitted into a page of `width` by `height` points, unless
+    /// `options.tight_bounding_box` asks to crop the page to the ink extents of the drawing.
+    pub fn render_postscript(
+        &self,
+        width: f64,
+        height: f64,
+        dpi: Dpi,
+        options: &PostScriptOptions,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
+        is_testing: bool,
+    ) -> Result<Vec<u8>, RenderingError> {
+        let viewport = cairo::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        };
+
+        let page = if options.tight_bounding_box {
+            self.get_ink_rect_with_overrides(&viewport, dpi, overrides, is_testing)?
+                .filter(|r| !r.is_empty())
+                .unwrap_or(viewport)
+        } else {
+            viewport
+        };
+
+        let surface = PsSurface::for_stream(page.width, page.height, Vec::<u8>::new());
+        surface.restrict(options.level.into());
+        surface.set_eps(options.eps);
+
+        {
+            let cr = cairo::Context::new(&surface);
+            cr.translate(-page.x, -page.y);
+            self.render_document(&cr, &viewport, dpi, hooks, quality, overrides, is_testing)?;
+            cr.show_page();
+        }
+
+        surface
+            .finish_output_stream()
+            .map_err(|_| RenderingError::Cairo(Status::WriteError))?
+            .downcast::<Vec<u8>>()
+            .map(|output| *output)
+            .map_err(|_| RenderingError::Cairo(Status::WriteError))
+    }
+
+    /// Computes the ink rectangle of the whole document in a `viewport`, leaving out the
+    /// elements which `overrides` hides.
+    fn get_ink_rect_with_overrides(
+        &self,
+        viewport: &cairo::Rectangle,
+        dpi: Dpi,
+        overrides: &ElementOverrides,
+        is_testing: bool,
+    ) -> Result<Option<cairo::Rectangle>, RenderingError> {
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
+        let mut draw_ctx =
+            DrawingCtx::new(self.document(), None, &cr, viewport, dpi, true, is_testing);
+        draw_ctx.set_element_overrides(overrides);
+        let root = self.document().root();
+
+        let bbox =
+            draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(&root), &root, false)?;
+
+        Ok(bbox.ink_rect)
+    }
+
+    /// Computes the ink rectangle of what the document draws inside `region`, when it
+    /// gets rendered to `viewport`.
+    ///
+    /// Unlike the geometry functions, this renders the region and looks at the pixels
+    /// that got painted, so clipping, masking, filters, and transparency are taken into
+    /// account.  The result is aligned to the pixel grid that starts at the region's
+    /// origin.  Returns `None` if nothing gets drawn inside the region.
+    pub fn get_ink_rect_in_region(
+        &self,
+        viewport: &cairo::Rectangle,
+        region: &cairo::Rectangle,
+        dpi: Dpi,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
+        is_testing: bool,
+    ) -> Result<Option<cairo::Rectangle>, RenderingError> {
+        if !(region.width > 0.0 && region.height > 0.0) {
+            return Ok(None);
+        }
+
+        let width = region.width.ceil();
+        let height = region.height.ceil();
+
+        if !(region.x.is_finite() && region.y.is_finite() && width <= 65535.0 && height <= 65535.0)
+        {
+            return Err(RenderingError::Cairo(Status::InvalidSize));
+        }
+
+        let surface = ImageSurface::create(cairo::Format::ARgb32, width as i32, height as i32)?;
+
+        {
+            let cr = cairo::Context::new(&surface);
+            cr.translate(-region.x, -region.y);
+            self.render_document(&cr, viewport, dpi, hooks, quality, overrides, is_testing)?;
+        }
+
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb)?;
+
+        Ok(painted_pixel_bounds(&surface).and_then(|bounds| {
+            let r = cairo::Rectangle::new(
+                region.x + f64::from(bounds.x0),
+                region.y + f64::from(bounds.y0),
+                f64::from(bounds.width()),
+                f64::from(bounds.height()),
+            );
+
+            // The last row and column of pixels may extend past the region
+            r.intersection(region)
+        }))
     }
 
     pub fn render_layer(
@@ -426,6 +1276,9 @@ impl Handle {
         id: Option<&str>,
         viewport: &cairo::Rectangle,
         dpi: Dpi,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
         is_testing: bool,
     ) -> Result<(), RenderingError> {
         check_cairo_context(cr)?;
@@ -436,11 +1289,11 @@ impl Handle {
             None
         };
 
-        let root = self.document.root();
+        let root = self.document().root();
 
         cr.save();
         let mut draw_ctx = DrawingCtx::new(
-            self.document.clone(),
+            self.document(),
             node.as_ref(),
             cr,
             viewport,
@@ -448,6 +1301,9 @@ impl Handle {
             false,
             is_testing,
         );
+        draw_ctx.set_render_hooks(hooks);
+        draw_ctx.set_quality(quality);
+        draw_ctx.set_element_overrides(overrides);
         let cascaded = CascadedValues::new_from_node(&root);
         let res = draw_ctx
             .draw_node_from_stack(&cascaded, &root, false)
@@ -457,26 +1313,87 @@ impl Handle {
         res
     }
 
-    fn get_bbox_for_element(
+    /// Draws a single element with as much of its ancestors' context as `context` asks for.
+    fn draw_element(
         &self,
+        cr: &cairo::Context,
         node: &RsvgNode,
         dpi: Dpi,
+        context: ElementContext,
+        measuring: bool,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
         is_testing: bool,
     ) -> Result<BoundingBox, RenderingError> {
-        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
-        let cr = cairo::Context::new(&target);
+        if context == ElementContext::Full {
+            // Draw from the root down to the element, so that its ancestors apply their
+            // transformations and effects.  The toplevel <svg> is always laid out as when
+            // measuring, so that the element gets rendered where it was measured.
+            let mut draw_ctx = DrawingCtx::new(
+                self.document(),
+                Some(node),
+                cr,
+                &unit_rectangle(),
+                dpi,
+                true,
+                is_testing,
+            );
+            draw_ctx.set_render_hooks(hooks);
+            draw_ctx.set_quality(quality);
+            draw_ctx.set_element_overrides(overrides);
+            let root = self.document().root();
+
+            return draw_ctx.draw_node_from_stack(
+                &CascadedValues::new_from_node(&root),
+                &root,
+                false,
+            );
+        }
 
         let mut draw_ctx = DrawingCtx::new(
-            self.document.clone(),
+            self.document(),
             None,
-            &cr,
+            cr,
             &unit_rectangle(),
             dpi,
-            true,
+            measuring,
             is_testing,
         );
+        draw_ctx.set_render_hooks(hooks);
+        draw_ctx.set_quality(quality);
+        draw_ctx.set_element_overrides(overrides);
+
+        let cascaded = if context == ElementContext::Isolated {
+            CascadedValues::new_from_values(node, &ComputedValues::default())
+        } else {
+            CascadedValues::new_from_node(node)
+        };
+
+        draw_ctx.draw_node_from_stack(&cascaded, node, false)
+    }
+
+    fn get_bbox_for_element(
+        &self,
+        node: &RsvgNode,
+        dpi: Dpi,
+        context: ElementContext,
+        is_testing: bool,
+    ) -> Result<BoundingBox, RenderingError> {
+        let target = ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+        let cr = cairo::Context::new(&target);
 
-        draw_ctx.draw_node_from_stack(&CascadedValues::new_from_node(node), node, false)
+        self.draw_element(
+            &cr,
+            node,
+            dpi,
+            context,
+            true,
+            &RenderHooks::default(),
+            RenderQuality::Full,
+            &ElementOverrides::default(),
+            is_testing,
+        )
     }
 
     /// Returns (ink_rect, logical_rect)
@@ -484,11 +1401,12 @@ impl Handle {
         &self,
         id: Option<&str>,
         dpi: Dpi,
+        context: ElementContext,
         is_testing: bool,
     ) -> Result<(cairo::Rectangle, cairo::Rectangle), RenderingError> {
         let node = self.get_node_or_root(id)?;
 
-        let bbox = self.get_bbox_for_element(&node, dpi, is_testing)?;
+        let bbox = self.get_bbox_for_element(&node, dpi, context, is_testing)?;
 
         let ink_rect = bbox.ink_rect.unwrap_or_else(|| cairo::Rectangle::new(0.0, 0.0, 0.0, 0.0));
         let logical_rect = bbox.rect.unwrap_or_else(|| cairo::Rectangle::new(0.0, 0.0, 0.0, 0.0));
@@ -505,13 +1423,17 @@ impl Handle {
         id: Option<&str>,
         element_viewport: &cairo::Rectangle,
         dpi: Dpi,
+        context: ElementContext,
+        hooks: &RenderHooks,
+        quality: RenderQuality,
+        overrides: &ElementOverrides,
         is_testing: bool,
     ) -> Result<(), RenderingError> {
         check_cairo_context(cr)?;
 
         let node = self.get_node_or_root(id)?;
 
-        let bbox = self.get_bbox_for_element(&node, dpi, is_testing)?;
+        let bbox = self.get_bbox_for_element(&node, dpi, context, is_testing)?;
 
         if bbox.ink_rect.is_none() || bbox.rect.is_none() {
             // Nothing to draw
@@ -535,18 +1457,10 @@ impl Handle {
         cr.scale(factor, factor);
         cr.translate(-ink_r.x, -ink_r.y);
 
-        let mut draw_ctx = DrawingCtx::new(
-            self.document.clone(),
-            None,
-            &cr,
-            &unit_rectangle(),
-            dpi,
-            false,
-            is_testing,
-        );
-
-        let res = draw_ctx
-            .draw_node_from_stack(&CascadedValues::new_from_node(&node), &node, false)
+        let res = self
+            .draw_element(
+                cr, &node, dpi, context, false, hooks, quality, overrides, is_testing,
+            )
             .map(|_bbox| ());
 
         cr.restore();
@@ -555,7 +1469,65 @@ impl Handle {
     }
 
     pub fn get_intrinsic_dimensions(&self) -> IntrinsicDimensions {
-        self.document.get_intrinsic_dimensions()
+        self.document().get_intrinsic_dimensions()
+    }
+}
+
+/// Whether `node` gets drawn when its parent is drawn, without considering its ancestors.
+///
+/// This does not consider `visibility`, since a hidden element can have visible children.
+fn element_renders_in_parent(node: &RsvgNode) -> bool {
+    let node_data = node.borrow();
+
+    if node_data.is_in_error() || !node_data.get_cond() {
+        return false;
+    }
+
+    if CascadedValues::new_from_node(node).get().display == Display::None {
+        return false;
+    }
+
+    match node_data.get_type() {
+        NodeType::Animate
+        | NodeType::AnimateMotion
+        | NodeType::AnimateTransform
+        | NodeType::ClipPath
+        | NodeType::Filter
+        | NodeType::Hatch
+        | NodeType::HatchPath
+        | NodeType::LinearGradient
+        | NodeType::Marker
+        | NodeType::Mask
+        | NodeType::MeshGradient
+        | NodeType::MeshPatch
+        | NodeType::MeshRow
+        | NodeType::NonRendering
+        | NodeType::Pattern
+        | NodeType::RadialGradient
+        | NodeType::Set
+        | NodeType::Stop
+        | NodeType::Style
+        | NodeType::Symbol
+        | NodeType::Unsupported => return false,
+
+        NodeType::Svg => {
+            let cascaded = CascadedValues::new_from_node(node);
+
+            if node_data.get_impl::<Svg>().has_zero_size(cascaded.get()) {
+                return false;
+            }
+        }
+
+        _ => (),
+    }
+
+    match node.parent() {
+        Some(ref parent) if parent.borrow().get_type() == NodeType::Switch => {
+            // A <switch> only renders one of its direct children
+            switch_child(parent).map_or(false, |c| c == *node)
+        }
+
+        _ => true,
     }
 }
 
@@ -589,6 +1561,36 @@ fn locale_from_environment() -> Locale {
     locale
 }
 
+/// Creates a `Locale` out of a list of BCP47 language tags, in order of preference.
+fn locale_from_language_tags<S: AsRef<str>>(languages: &[S]) -> Locale {
+    let mut locale = Locale::invariant();
+
+    for tag in languages {
+        match LanguageRange::new(tag.as_ref()) {
+            Ok(range) => locale.add(&range),
+            Err(_) => rsvg_log!("ignoring invalid language tag \"{}\"", tag.as_ref()),
+        }
+    }
+
+    locale
+}
+
+/// Returns the bounds of the pixels of `surface` that are not fully transparent.
+fn painted_pixel_bounds(surface: &SharedImageSurface) -> Option<IRect> {
+    let bounds = IRect::from_size(surface.width(), surface.height());
+
+    Pixels::new(surface, bounds)
+        .filter(|&(_, _, pixel)| pixel.a > 0)
+        .fold(None, |acc: Option<IRect>, (x, y, _)| {
+            let (x, y) = (x as i32, y as i32);
+
+            Some(match acc {
+                None => IRect::new(x, y, x + 1, y + 1),
+                Some(r) => IRect::new(r.x0.min(x), r.y0.min(y), r.x1.max(x + 1), r.y1.max(y + 1)),
+            })
+        })
+}
+
 fn unit_rectangle() -> cairo::Rectangle {
     cairo::Rectangle {
         x: 0.0,
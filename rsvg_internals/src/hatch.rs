@@ -0,0 +1,639 @@
+//! The `hatch` element and its `hatchpath` children.
+//!
+//! A hatch fills an area with strokes that are repeated every `pitch` units
+//! along the x axis of the hatch's coordinate system.  Each `hatchpath` is
+//! either an infinite vertical line at its `offset`, or a path which gets
+//! repeated along the y axis as well, so that its copies join end to start.
+//! See https://www.w3.org/TR/2016/CR-SVG2-20160915/pservers.html#Hatches
+
+use cairo;
+use markup5ever::{expanded_name, local_name, namespace_url, ns, QualName};
+use std::ops::RangeInclusive;
+
+use crate::allowed_url::Fragment;
+use crate::angle::Angle;
+use crate::bbox::*;
+use crate::coord_units::CoordUnits;
+use crate::drawing_ctx::DrawingCtx;
+use crate::error::{AcquireError, AttributeResultExt, NodeError, RenderingError};
+use crate::length::*;
+use crate::node::*;
+use crate::paint_server::{AsPaintSource, PaintServer, PaintSource, Resolve};
+use crate::parsers::ParseValue;
+use crate::path_builder::{PathBuilder, PathCommand};
+use crate::path_parser::parse_path_into_builder;
+use crate::properties::ComputedValues;
+use crate::property_bag::PropertyBag;
+use crate::property_defs::Fill;
+use crate::rect::{IRect, RectangleExt, TransformRect};
+use crate::unit_interval::UnitInterval;
+
+coord_units!(HatchUnits, CoordUnits::ObjectBoundingBox);
+coord_units!(HatchContentUnits, CoordUnits::UserSpaceOnUse);
+
+/// Maximum number of copies of a single `hatchpath` that get drawn for one paint operation
+///
+/// A tiny pitch relative to the painted area would otherwise make us spend an unbounded
+/// amount of time stroking lines which are thinner than a pixel anyway.
+const MAX_HATCH_PATH_COPIES: f64 = 100_000.0;
+
+#[derive(Clone, Default)]
+struct Common {
+    units: Option<HatchUnits>,
+    content_units: Option<HatchContentUnits>,
+    affine: Option<cairo::Matrix>,
+    x: Option<Length<Horizontal>>,
+    y: Option<Length<Vertical>>,
+    pitch: Option<Length<Horizontal>>,
+    rotate: Option<Angle>,
+}
+
+/// Node for the `<hatch>` element
+#[derive(Default)]
+pub struct Hatch {
+    common: Common,
+    fallback: Option<Fragment>,
+}
+
+/// Node for the `<hatchpath>` element
+#[derive(Default)]
+pub struct HatchPath {
+    path: Option<PathBuilder>,
+    offset: Length<Horizontal>,
+}
+
+/// Whether `attr` is the SVG attribute called `name`
+///
+/// The hatch attributes are newer than the set of names that markup5ever
+/// has atoms for, so they can't be matched with `expanded_name!`.
+fn is_svg_attr(attr: &QualName, name: &str) -> bool {
+    attr.ns == ns!(svg) && &*attr.local == name
+}
+
+impl NodeTrait for Hatch {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                _ if is_svg_attr(&attr, "hatchUnits") => {
+                    self.common.units = Some(attr.parse(value)?)
+                }
+                _ if is_svg_attr(&attr, "hatchContentUnits") => {
+                    self.common.content_units = Some(attr.parse(value)?)
+                }
+                // Unlike patternTransform, the hatch's transform is its plain
+                // transform attribute; we also keep it here so that it can be
+                // inherited through href.
+                expanded_name!(svg "transform") => self.common.affine = Some(attr.parse(value)?),
+                expanded_name!(svg "x") => self.common.x = Some(attr.parse(value)?),
+                expanded_name!(svg "y") => self.common.y = Some(attr.parse(value)?),
+                _ if is_svg_attr(&attr, "pitch") => {
+                    self.common.pitch = Some(
+                        attr.parse_and_validate(value, Length::<Horizontal>::check_nonnegative)?,
+                    )
+                }
+                expanded_name!(svg "rotate") => self.common.rotate = Some(attr.parse(value)?),
+                expanded_name!(xlink "href") => {
+                    self.fallback = Some(Fragment::parse(value).attribute(attr)?);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl NodeTrait for HatchPath {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(svg "d") => {
+                    let mut builder = PathBuilder::new();
+                    parse_path_into_builder(value, &mut builder)
+                        .map_err(|e| NodeError::parse_error(attr, &e.to_string()))?;
+                    self.path = Some(builder);
+                }
+                expanded_name!(svg "offset") => self.offset = attr.parse(value)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct ResolvedHatch {
+    units: HatchUnits,
+    content_units: HatchContentUnits,
+    affine: cairo::Matrix,
+    x: Length<Horizontal>,
+    y: Length<Vertical>,
+    pitch: Length<Horizontal>,
+    rotate: Angle,
+
+    // The first node in the chain of fallbacks that has <hatchpath> children
+    paths: Option<RsvgWeakNode>,
+
+    // Whether the paths come from a fallback rather than from the hatch itself.
+    paths_from_fallback: bool,
+}
+
+fn has_paths(node: &RsvgNode) -> bool {
+    node.children()
+        .any(|child| child.borrow().get_type() == NodeType::HatchPath)
+}
+
+impl PaintSource for Hatch {
+    type Unresolved = UnresolvedHatch;
+}
+
+/// The attributes of a hatch while its fallbacks are being resolved
+pub struct UnresolvedHatch {
+    common: Common,
+
+    // The first node with <hatchpath> children, or Some(None) if no node in the
+    // chain of fallbacks has them
+    paths: Option<Option<RsvgWeakNode>>,
+}
+
+impl Resolve for UnresolvedHatch {
+    type Resolved = ResolvedHatch;
+
+    const FALLBACK_TYPES: &'static [NodeType] = &[NodeType::Hatch];
+
+    fn from_node(node: &RsvgNode) -> (UnresolvedHatch, Option<Fragment>) {
+        let borrowed_node = node.borrow();
+        let hatch = borrowed_node.get_impl::<Hatch>();
+
+        let paths = if has_paths(node) {
+            Some(Some(node.downgrade()))
+        } else {
+            None
+        };
+
+        let unresolved = UnresolvedHatch {
+            common: hatch.common.clone(),
+            paths,
+        };
+
+        (unresolved, hatch.fallback.clone())
+    }
+
+    fn is_resolved(&self) -> bool {
+        self.common.units.is_some()
+            && self.common.content_units.is_some()
+            && self.common.affine.is_some()
+            && self.common.x.is_some()
+            && self.common.y.is_some()
+            && self.common.pitch.is_some()
+            && self.common.rotate.is_some()
+            && self.paths.is_some()
+    }
+
+    fn resolve_from_fallback(&self, fallback: &UnresolvedHatch) -> UnresolvedHatch {
+        UnresolvedHatch {
+            common: Common {
+                units: self.common.units.or(fallback.common.units),
+                content_units: self.common.content_units.or(fallback.common.content_units),
+                affine: self.common.affine.or(fallback.common.affine),
+                x: self.common.x.or(fallback.common.x),
+                y: self.common.y.or(fallback.common.y),
+                pitch: self.common.pitch.or(fallback.common.pitch),
+                rotate: self.common.rotate.or(fallback.common.rotate),
+            },
+            paths: self.paths.clone().or_else(|| fallback.paths.clone()),
+        }
+    }
+
+    fn resolve_from_defaults(&self) -> UnresolvedHatch {
+        UnresolvedHatch {
+            common: Common {
+                units: self.common.units.or_else(|| Some(Default::default())),
+                content_units: self
+                    .common
+                    .content_units
+                    .or_else(|| Some(Default::default())),
+                affine: self
+                    .common
+                    .affine
+                    .or_else(|| Some(cairo::Matrix::identity())),
+                x: self.common.x.or_else(|| Some(Default::default())),
+                y: self.common.y.or_else(|| Some(Default::default())),
+                pitch: self.common.pitch.or_else(|| Some(Default::default())),
+                rotate: self.common.rotate.or_else(|| Some(Angle::new(0.0))),
+            },
+            paths: self.paths.clone().or(Some(None)),
+        }
+    }
+
+    fn to_resolved(self, node: &RsvgNode) -> ResolvedHatch {
+        assert!(self.is_resolved());
+
+        let paths = self.paths.unwrap();
+        let paths_from_fallback = paths
+            .as_ref()
+            .and_then(|weak| weak.upgrade())
+            .map_or(false, |paths_node| paths_node != *node);
+
+        ResolvedHatch {
+            units: self.common.units.unwrap(),
+            content_units: self.common.content_units.unwrap(),
+            affine: self.common.affine.unwrap(),
+            x: self.common.x.unwrap(),
+            y: self.common.y.unwrap(),
+            pitch: self.common.pitch.unwrap(),
+            rotate: self.common.rotate.unwrap(),
+            paths,
+            paths_from_fallback,
+        }
+    }
+}
+
+impl AsPaintSource for ResolvedHatch {
+    fn set_as_paint_source(
+        self,
+        values: &ComputedValues,
+        draw_ctx: &mut DrawingCtx,
+        opacity: UnitInterval,
+        bbox: &BoundingBox,
+    ) -> Result<bool, RenderingError> {
+        let paths_node = match self.paths.as_ref().and_then(|weak| weak.upgrade()) {
+            Some(node) => node,
+            None => return Ok(false),
+        };
+
+        let needs_bbox = self.units == HatchUnits(CoordUnits::ObjectBoundingBox)
+            || self.content_units == HatchContentUnits(CoordUnits::ObjectBoundingBox);
+
+        let bbrect = match bbox.rect {
+            Some(r) if !r.is_empty() => r,
+            _ if needs_bbox => return Ok(false),
+            _ => cairo::Rectangle::from_size(1.0, 1.0),
+        };
+
+        // Same as for patterns; see the comment there.
+        let _acquired_paths = if self.paths_from_fallback {
+            match draw_ctx.acquire_node_ref(&paths_node) {
+                Ok(acquired) => Some(acquired),
+
+                Err(AcquireError::CircularReference(node)) => {
+                    rsvg_log!("circular reference in the paths of hatch {}", node);
+                    return Ok(false);
+                }
+
+                Err(AcquireError::MaxReferencesExceeded) => {
+                    return Err(RenderingError::InstancingLimit);
+                }
+
+                Err(e) => {
+                    rsvg_log!("could not acquire the paths of hatch {}: {}", paths_node, e);
+                    return Ok(false);
+                }
+            }
+        } else {
+            None
+        };
+
+        let (x, y, pitch) = {
+            let params = if self.units == HatchUnits(CoordUnits::ObjectBoundingBox) {
+                draw_ctx.push_view_box(1.0, 1.0)
+            } else {
+                draw_ctx.get_view_params()
+            };
+
+            let x = self.x.normalize(values, &params);
+            let y = self.y.normalize(values, &params);
+            let pitch = self.pitch.normalize(values, &params);
+
+            match self.units {
+                HatchUnits(CoordUnits::ObjectBoundingBox) => (
+                    bbrect.x + x * bbrect.width,
+                    bbrect.y + y * bbrect.height,
+                    pitch * bbrect.width,
+                ),
+
+                HatchUnits(CoordUnits::UserSpaceOnUse) => (x, y, pitch),
+            }
+        };
+
+        // The hatch paths are drawn in the content coordinate system, which is
+        // scaled by the content units, then rotated about the hatch's origin,
+        // and finally transformed by the hatch's transform.
+        let (content_affine, content_pitch) = match self.content_units {
+            HatchContentUnits(CoordUnits::ObjectBoundingBox) => (
+                cairo::Matrix::new(bbrect.width, 0.0, 0.0, bbrect.height, 0.0, 0.0),
+                pitch / bbrect.width,
+            ),
+
+            HatchContentUnits(CoordUnits::UserSpaceOnUse) => (cairo::Matrix::identity(), pitch),
+        };
+
+        // A pitch of zero disables rendering of the hatch
+        if content_pitch <= 0.0 || !content_pitch.is_finite() {
+            return Ok(false);
+        }
+
+        let mut rotation = cairo::Matrix::identity();
+        rotation.rotate(self.rotate.radians());
+
+        let origin = cairo::Matrix::new(1.0, 0.0, 0.0, 1.0, x, y);
+
+        let content_to_user = cairo::Matrix::multiply(
+            &cairo::Matrix::multiply(
+                &cairo::Matrix::multiply(&content_affine, &rotation),
+                &origin,
+            ),
+            &self.affine,
+        );
+
+        // Since the hatch is not periodic in general, we draw it directly to a
+        // surface which covers the area that can be painted, instead of to a tile.
+
+        let cr_save = draw_ctx.get_cairo_context();
+        let user_affine = cr_save.get_matrix();
+
+        let (x0, y0, x1, y1) = cr_save.clip_extents();
+        let device_rect = IRect::from(
+            user_affine.transform_rect(&cairo::Rectangle::from_extents(x0, y0, x1, y1)),
+        );
+
+        if device_rect.width() < 1 || device_rect.height() < 1 {
+            return Ok(false);
+        }
+
+        let device_to_surface = cairo::Matrix::new(
+            1.0,
+            0.0,
+            0.0,
+            1.0,
+            -f64::from(device_rect.x0),
+            -f64::from(device_rect.y0),
+        );
+
+        let content_to_surface = cairo::Matrix::multiply(
+            &cairo::Matrix::multiply(&content_to_user, &user_affine),
+            &device_to_surface,
+        );
+
+        let region = match content_to_surface.try_invert() {
+            Ok(surface_to_content) => {
+                surface_to_content.transform_rect(&cairo::Rectangle::from_size(
+                    f64::from(device_rect.width()),
+                    f64::from(device_rect.height()),
+                ))
+            }
+
+            Err(_) => return Ok(false),
+        };
+
+        let surface = cr_save.get_target().create_similar(
+            cairo::Content::ColorAlpha,
+            device_rect.width(),
+            device_rect.height(),
+        );
+
+        let cr_hatch = cairo::Context::new(&surface);
+        cr_hatch.set_matrix(content_to_surface);
+
+        draw_ctx.set_cairo_context(&cr_hatch);
+
+        let _params = if self.content_units == HatchContentUnits(CoordUnits::ObjectBoundingBox) {
+            draw_ctx.push_view_box(1.0, 1.0)
+        } else {
+            draw_ctx.get_view_params()
+        };
+
+        let res = draw_hatch_paths(&paths_node, draw_ctx, content_pitch, &region);
+
+        draw_ctx.set_cairo_context(&cr_save);
+
+        // Apply fill-opacity or stroke-opacity by scaling the premultiplied hatch
+
+        let UnitInterval(o) = opacity;
+        if o < 1.0 {
+            cr_hatch.set_operator(cairo::Operator::DestIn);
+            cr_hatch.set_source_rgba(0.0, 0.0, 0.0, o);
+            cr_hatch.paint();
+        }
+
+        // The pattern's matrix maps user space to the hatch surface
+        let surface_pattern = cairo::SurfacePattern::create(&surface);
+        surface_pattern.set_matrix(cairo::Matrix::multiply(&user_affine, &device_to_surface));
+
+        cr_save.set_source(&surface_pattern);
+
+        res.and_then(|_| Ok(true))
+    }
+}
+
+/// Strokes each `<hatchpath>` child of `node`, with as many copies as are needed
+/// to cover `region`, which is in the hatch content's coordinate system.
+fn draw_hatch_paths(
+    node: &RsvgNode,
+    draw_ctx: &mut DrawingCtx,
+    pitch: f64,
+    region: &cairo::Rectangle,
+) -> Result<(), RenderingError> {
+    let cascaded = CascadedValues::new_from_node(node);
+
+    for child in node
+        .children()
+        .filter(|c| c.borrow().get_type() == NodeType::HatchPath)
+    {
+        if child.borrow().is_in_error() {
+            rsvg_log!("(not using hatchpath {} because it is in error)", child);
+            continue;
+        }
+
+        let child_cascaded = CascadedValues::new(&cascaded, &child);
+
+        // Hatch paths are only ever stroked
+        let mut values = child_cascaded.get().clone();
+        values.fill = Fill(PaintServer::None);
+
+        let borrowed = child.borrow();
+        let hatch_path = borrowed.get_impl::<HatchPath>();
+        let offset = hatch_path
+            .offset
+            .normalize(&values, &draw_ctx.get_view_params());
+
+        draw_ctx.with_discrete_layer(&child, &values, false, &mut |dc| {
+            let cr = dc.get_cairo_context();
+
+            dc.setup_cr_for_stroke(&cr, &values);
+
+            if hatch_path.add_copies(&cr, offset, pitch, region)? {
+                // The copies are made to cover the region; nothing uses the bbox of
+                // the hatch paths besides their stroke's paint server.
+                let bbox = BoundingBox::new(&cr.get_matrix())
+                    .with_rect(*region)
+                    .with_ink_rect(*region);
+
+                dc.stroke_and_fill(&cr, &values, bbox, &mut |_| Ok(()))
+            } else {
+                rsvg_log!("hatchpath {} needs too many copies; not drawing it", child);
+                Ok(dc.empty_bbox())
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+impl HatchPath {
+    /// Appends to the current path in `cr` as many copies of the hatch path as
+    /// are needed to cover the `region`, one every `pitch` units along the x axis.
+    ///
+    /// The stroke parameters must already be set up in `cr`, since they determine
+    /// how far a copy can reach.  Returns `false` if that would need an unreasonable
+    /// number of copies.
+    fn add_copies(
+        &self,
+        cr: &cairo::Context,
+        offset: f64,
+        pitch: f64,
+        region: &cairo::Rectangle,
+    ) -> Result<bool, cairo::Status> {
+        let (region_x0, region_x1) = (region.x, region.x + region.width);
+        let (region_y0, region_y1) = (region.y, region.y + region.height);
+
+        let (extents, period) = match self.path {
+            Some(ref builder) => {
+                cr.new_path();
+                builder.to_cairo(cr)?;
+                let extents = cr.stroke_extents();
+                cr.new_path();
+
+                (extents, vertical_period(builder))
+            }
+
+            None => {
+                // Without path data, the hatch path is a vertical line that extends
+                // past the region by enough that its caps are not visible.
+                let w = cr.get_line_width();
+                ((-w, region_y0 - w, w, region_y1 + w), None)
+            }
+        };
+
+        let (ex0, ey0, ex1, ey1) = extents;
+
+        let columns = copy_range((ex0 + offset, ex1 + offset), (region_x0, region_x1), pitch);
+        let rows = match period {
+            Some(period) => copy_range((ey0, ey1), (region_y0, region_y1), period),
+            None => 0..=0,
+        };
+
+        if range_len(&columns) * range_len(&rows) > MAX_HATCH_PATH_COPIES {
+            return Ok(false);
+        }
+
+        for column in columns {
+            let x = offset + column as f64 * pitch;
+
+            match self.path {
+                Some(ref builder) => {
+                    for row in rows.clone() {
+                        cr.save();
+                        cr.translate(x, row as f64 * period.unwrap_or(0.0));
+                        builder.to_cairo(cr)?;
+                        cr.restore();
+                    }
+                }
+
+                None => {
+                    cr.move_to(x, ey0);
+                    cr.line_to(x, ey1);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns the distance along the y axis between the start and end points of
+/// a path, which is how far apart its copies are placed so that they join up.
+///
+/// Returns `None` if the path ends at the same height at which it starts, in
+/// which case it does not get repeated along the y axis.
+fn vertical_period(builder: &PathBuilder) -> Option<f64> {
+    let commands = builder.get_path_commands();
+
+    let mut start = None;
+    let mut subpath_start = 0.0;
+    let mut end = 0.0;
+
+    for command in commands {
+        end = match *command {
+            PathCommand::MoveTo(_, y) => {
+                subpath_start = y;
+                y
+            }
+            PathCommand::LineTo(_, y) => y,
+            PathCommand::CurveTo(curve) => curve.to.1,
+            PathCommand::Arc(arc) => arc.to.1,
+            PathCommand::ClosePath => subpath_start,
+        };
+
+        if start.is_none() {
+            start = Some(end);
+        }
+    }
+
+    let period = (end - start?).abs();
+
+    if period > std::f64::EPSILON {
+        Some(period)
+    } else {
+        None
+    }
+}
+
+/// Returns the multiples of `step` by which something that spans `extent` must
+/// be shifted so that the copies cover `region`.
+fn copy_range(extent: (f64, f64), region: (f64, f64), step: f64) -> RangeInclusive<i64> {
+    let first = ((region.0 - extent.1) / step).ceil() as i64;
+    let last = ((region.1 - extent.0) / step).floor() as i64;
+
+    first..=last
+}
+
+fn range_len(range: &RangeInclusive<i64>) -> f64 {
+    if range.is_empty() {
+        0.0
+    } else {
+        (*range.end() as f64) - (*range.start() as f64) + 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> PathBuilder {
+        let mut builder = PathBuilder::new();
+        parse_path_into_builder(s, &mut builder).unwrap();
+        builder
+    }
+
+    #[test]
+    fn copies_cover_the_region() {
+        // a copy spans [-1, 1] and the region is [0, 10]
+        assert_eq!(copy_range((-1.0, 1.0), (0.0, 10.0), 5.0), 0..=2);
+
+        // copies which just touch the region are included
+        assert_eq!(copy_range((-1.0, 1.0), (1.0, 9.0), 5.0), 0..=2);
+
+        assert_eq!(copy_range((0.0, 1.0), (-10.0, -5.5), 5.0), -2..=-2);
+    }
+
+    #[test]
+    fn vertical_period_is_distance_between_endpoints() {
+        assert_eq!(vertical_period(&path("M 0 0 L 5 10")), Some(10.0));
+        assert_eq!(vertical_period(&path("M 0 10 C 5 5, 5 5, 0 0")), Some(10.0));
+        assert_eq!(vertical_period(&path("M 0 0 L 5 10 L 0 0")), None);
+        assert_eq!(vertical_period(&path("M 0 0 L 5 10 Z")), None);
+        assert_eq!(vertical_period(&PathBuilder::new()), None);
+    }
+}
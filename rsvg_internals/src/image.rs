@@ -69,10 +69,11 @@ impl NodeTrait for Image {
         let values = cascaded.get();
         let params = draw_ctx.get_view_params();
 
-        let x = self.x.normalize(values, &params);
-        let y = self.y.normalize(values, &params);
-        let w = self.w.normalize(values, &params);
-        let h = self.h.normalize(values, &params);
+        // The geometry properties override the attributes
+        let x = values.x.value_or(self.x).normalize(values, &params);
+        let y = values.y.value_or(self.y).normalize(values, &params);
+        let w = values.width.value_or(self.w).normalize(values, &params);
+        let h = values.height.value_or(self.h).normalize(values, &params);
 
         if w.approx_eq_cairo(0.0) || h.approx_eq_cairo(0.0) {
             return Ok(draw_ctx.empty_bbox());
@@ -122,6 +123,11 @@ impl NodeTrait for Image {
                     // transparent almost everywhere without this fix (which it shouldn't).
                     let ptn = surface.to_cairo_pattern();
                     ptn.set_extend(cairo::Extend::Pad);
+
+                    if dc.is_approximate() {
+                        ptn.set_filter(cairo::Filter::Fast);
+                    }
+
                     cr.set_source(&ptn);
 
                     // Clip is needed due to extend being set to pad.
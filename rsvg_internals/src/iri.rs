@@ -3,6 +3,7 @@ use cssparser::Parser;
 use crate::allowed_url::{Fragment, Href};
 use crate::error::ValueErrorKind;
 use crate::parsers::Parse;
+use crate::property_macros::ToCssValue;
 
 /// Used where style properties take a funciri or "none"
 ///
@@ -56,6 +57,15 @@ impl Parse for IRI {
     }
 }
 
+impl ToCssValue for IRI {
+    fn to_css_value(&self) -> String {
+        match *self {
+            IRI::None => "none".to_string(),
+            IRI::Resource(ref f) => format!("url({})", f),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,8 +40,15 @@
 //! [`Normalize`]: trait.Normalize.html
 //! [diag]: https://www.w3.org/TR/SVG/coords.html#Units
 //! [`normalize`]: struct.Length.html#method.normalize
+//!
+//! Lengths can also be [`calc()`] expressions like `calc(100% - 20px)`.  CSS only allows
+//! lengths to be multiplied or divided by plain numbers, so these expressions are reduced
+//! at parsing time to a sum of terms, one per unit, and the terms are added together at
+//! normalization time.
+//!
+//! [`calc()`]: https://www.w3.org/TR/css-values-3/#calc-notation
 
-use cssparser::{Parser, Token};
+use cssparser::{ParseError, Parser, Token};
 use std::f64::consts::*;
 use std::marker::PhantomData;
 
@@ -50,6 +57,7 @@ use crate::error::*;
 use crate::parsers::Parse;
 use crate::parsers::finite_f32;
 use crate::properties::ComputedValues;
+use crate::property_macros::ToCssValue;
 
 /// Units for length values.
 // This needs to be kept in sync with `rsvg.h:RsvgUnit`.
@@ -82,6 +90,41 @@ pub enum LengthUnit {
 
     /// Picas (12 points)
     Pc,
+
+    /// `1.0` means 1% of the width of the toplevel viewport
+    Vw,
+
+    /// `1.0` means 1% of the height of the toplevel viewport
+    Vh,
+
+    /// `1.0` means 1% of the smaller dimension of the toplevel viewport
+    Vmin,
+
+    /// `1.0` means 1% of the larger dimension of the toplevel viewport
+    Vmax,
+
+    /// Advance of the "0" glyph in the current font
+    Ch,
+
+    /// Font size of the root element
+    Rem,
+
+    /// Quarter-millimeters
+    Q,
+}
+
+impl LengthUnit {
+    /// Whether lengths in this unit depend on the size of a viewport.
+    pub fn is_viewport_relative(self) -> bool {
+        match self {
+            LengthUnit::Percent
+            | LengthUnit::Vw
+            | LengthUnit::Vh
+            | LengthUnit::Vmin
+            | LengthUnit::Vmax => true,
+            _ => false,
+        }
+    }
 }
 
 /// A CSS length value.
@@ -129,6 +172,39 @@ pub trait Normalize {
     fn normalize(x: f64, y: f64) -> f64;
 }
 
+/// Number of variants in `LengthUnit`
+const NUM_UNITS: usize = 16;
+
+/// All the `LengthUnit` variants, in the order in which they are declared
+const ALL_UNITS: [LengthUnit; NUM_UNITS] = [
+    LengthUnit::Percent,
+    LengthUnit::Px,
+    LengthUnit::Em,
+    LengthUnit::Ex,
+    LengthUnit::In,
+    LengthUnit::Cm,
+    LengthUnit::Mm,
+    LengthUnit::Pt,
+    LengthUnit::Pc,
+    LengthUnit::Vw,
+    LengthUnit::Vh,
+    LengthUnit::Vmin,
+    LengthUnit::Vmax,
+    LengthUnit::Ch,
+    LengthUnit::Rem,
+    LengthUnit::Q,
+];
+
+/// A `calc()` expression that mixes units, like `calc(100% - 2em + 5px)`
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Calc {
+    /// Coefficient for each unit, indexed by the `LengthUnit` as in `ALL_UNITS`
+    terms: [f64; NUM_UNITS],
+
+    /// Whether negative values get clamped to zero; see `Length::check_nonnegative()`
+    nonnegative: bool,
+}
+
 /// Allows declaring `Length<Horizontal>`.
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Horizontal;
@@ -187,12 +263,19 @@ impl Normalize for Both {
 /// During the rendering phase, a `Length` needs to be normalized into the current coordinate
 /// system's units with the [`normalize`] method.
 ///
+/// A `calc()` expression that mixes units, like `calc(100% - 20px)`, can only be resolved
+/// with [`normalize`].  For these, the [`unit`] is a percentage or a viewport-percentage
+/// unit if the expression has one, so that callers can know that the length depends on the
+/// viewport, and [`length`] is the coefficient for that unit.
+///
 /// [`Normalize`]: trait.Normalize.html
 /// [`Horizontal`]: struct.Horizontal.html
 /// [`Vertical`]: struct.Vertical.html
 /// [`Both`]: struct.Both.html
 /// [`new`]: #method.new
 /// [`normalize`]: #method.normalize
+/// [`unit`]: #structfield.unit
+/// [`length`]: #structfield.length
 /// [`cssparser::Parser`]: https://docs.rs/cssparser/0.27.1/cssparser/struct.Parser.html
 /// [`Parse`]: ../parsers/trait.Parse.html
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -203,6 +286,9 @@ pub struct Length<N: Normalize> {
     /// Unit part of the length
     pub unit: LengthUnit,
 
+    /// Terms of a `calc()` expression that mixes units
+    calc: Option<Calc>,
+
     /// Dummy; used internally for the type parameter `N`
     orientation: PhantomData<N>,
 }
@@ -226,14 +312,206 @@ pub const POINTS_PER_INCH: f64 = 72.0;
 const CM_PER_INCH: f64 = 2.54;
 const MM_PER_INCH: f64 = 25.4;
 const PICA_PER_INCH: f64 = 6.0;
+const Q_PER_INCH: f64 = MM_PER_INCH * 4.0;
 
 fn make_err() -> ValueErrorKind {
     ValueErrorKind::parse_error(
         "expected length: number(\"em\" | \"ex\" | \"px\" | \"in\" | \"cm\" | \"mm\" | \"pt\" | \
-         \"pc\" | \"%\")?",
+         \"pc\" | \"q\" | \"ch\" | \"rem\" | \"vw\" | \"vh\" | \"vmin\" | \"vmax\" | \"%\")?",
     )
 }
 
+fn parse_unit(unit: &str) -> Option<LengthUnit> {
+    match unit {
+        "px" => Some(LengthUnit::Px),
+        "em" => Some(LengthUnit::Em),
+        "ex" => Some(LengthUnit::Ex),
+        "in" => Some(LengthUnit::In),
+        "cm" => Some(LengthUnit::Cm),
+        "mm" => Some(LengthUnit::Mm),
+        "pt" => Some(LengthUnit::Pt),
+        "pc" => Some(LengthUnit::Pc),
+        "vw" => Some(LengthUnit::Vw),
+        "vh" => Some(LengthUnit::Vh),
+        "vmin" => Some(LengthUnit::Vmin),
+        "vmax" => Some(LengthUnit::Vmax),
+        "ch" => Some(LengthUnit::Ch),
+        "rem" => Some(LengthUnit::Rem),
+        "Q" | "q" => Some(LengthUnit::Q),
+        _ => None,
+    }
+}
+
+/// Intermediate value while parsing a `calc()` expression
+#[derive(Debug, Copy, Clone)]
+enum CalcValue {
+    Number(f64),
+    Length([f64; NUM_UNITS]),
+}
+
+type CalcResult<'i> = Result<CalcValue, ParseError<'i, ValueErrorKind>>;
+
+fn calc_error<'i>(parser: &Parser<'i, '_>, msg: &str) -> ParseError<'i, ValueErrorKind> {
+    parser.new_custom_error(ValueErrorKind::parse_error(msg))
+}
+
+fn calc_finite<'i>(parser: &Parser<'i, '_>, n: f32) -> Result<f64, ParseError<'i, ValueErrorKind>> {
+    finite_f32(n)
+        .map(f64::from)
+        .map_err(|e| parser.new_custom_error(e))
+}
+
+/// Parses `<calc-sum> = <calc-product> [ [ '+' | '-' ] <calc-product> ]*`
+///
+/// This is always called on a nested block, so the sum extends to the end of the input.
+fn parse_calc_sum<'i>(parser: &mut Parser<'i, '_>) -> CalcResult<'i> {
+    let mut sum = parse_calc_product(parser)?;
+
+    while !parser.is_exhausted() {
+        let sign = match *parser.next()? {
+            Token::Delim('+') => 1.0,
+            Token::Delim('-') => -1.0,
+            _ => return Err(calc_error(parser, "expected + or - in calc()")),
+        };
+
+        let rhs = parse_calc_product(parser)?;
+
+        sum = match (sum, rhs) {
+            (CalcValue::Number(a), CalcValue::Number(b)) => CalcValue::Number(a + sign * b),
+
+            (CalcValue::Length(mut a), CalcValue::Length(b)) => {
+                for (a, b) in a.iter_mut().zip(b.iter()) {
+                    *a += sign * b;
+                }
+
+                CalcValue::Length(a)
+            }
+
+            _ => return Err(calc_error(parser, "cannot add a number and a length")),
+        };
+    }
+
+    Ok(sum)
+}
+
+/// Parses `<calc-product> = <calc-value> [ '*' <calc-value> | '/' <number> ]*`
+fn parse_calc_product<'i>(parser: &mut Parser<'i, '_>) -> CalcResult<'i> {
+    let mut product = parse_calc_value(parser)?;
+
+    loop {
+        let state = parser.state();
+
+        let is_division = match parser.next() {
+            Ok(&Token::Delim('*')) => false,
+            Ok(&Token::Delim('/')) => true,
+            _ => {
+                parser.reset(&state);
+                break;
+            }
+        };
+
+        let rhs = parse_calc_value(parser)?;
+
+        product = match (product, rhs) {
+            (_, CalcValue::Number(n)) if is_division && n == 0.0 => {
+                return Err(calc_error(parser, "division by zero in calc()"));
+            }
+
+            (CalcValue::Number(a), CalcValue::Number(b)) if is_division => CalcValue::Number(a / b),
+
+            (CalcValue::Length(mut a), CalcValue::Number(b)) if is_division => {
+                a.iter_mut().for_each(|a| *a /= b);
+                CalcValue::Length(a)
+            }
+
+            (_, CalcValue::Length(_)) if is_division => {
+                return Err(calc_error(parser, "cannot divide by a length"));
+            }
+
+            (CalcValue::Number(a), CalcValue::Number(b)) => CalcValue::Number(a * b),
+
+            (CalcValue::Length(mut l), CalcValue::Number(n))
+            | (CalcValue::Number(n), CalcValue::Length(mut l)) => {
+                l.iter_mut().for_each(|l| *l *= n);
+                CalcValue::Length(l)
+            }
+
+            (CalcValue::Length(_), CalcValue::Length(_)) => {
+                return Err(calc_error(parser, "cannot multiply two lengths"));
+            }
+        };
+    }
+
+    Ok(product)
+}
+
+/// Parses `<calc-value> = <number> | <dimension> | <percentage> | ( <calc-sum> )`
+///
+/// Nested `calc()` functions are treated like parentheses.
+fn parse_calc_value<'i>(parser: &mut Parser<'i, '_>) -> CalcResult<'i> {
+    let token = parser.next()?.clone();
+
+    match token {
+        Token::Number { value, .. } => Ok(CalcValue::Number(calc_finite(parser, value)?)),
+
+        Token::Percentage { unit_value, .. } => {
+            let mut terms = [0.0; NUM_UNITS];
+            terms[LengthUnit::Percent as usize] = calc_finite(parser, unit_value)?;
+            Ok(CalcValue::Length(terms))
+        }
+
+        Token::Dimension {
+            value, ref unit, ..
+        } => {
+            let unit =
+                parse_unit(unit.as_ref()).ok_or_else(|| calc_error(parser, "invalid unit"))?;
+
+            let mut terms = [0.0; NUM_UNITS];
+            terms[unit as usize] = calc_finite(parser, value)?;
+            Ok(CalcValue::Length(terms))
+        }
+
+        Token::ParenthesisBlock => parser.parse_nested_block(parse_calc_sum),
+
+        Token::Function(ref name) if name.eq_ignore_ascii_case("calc") => {
+            parser.parse_nested_block(parse_calc_sum)
+        }
+
+        t => Err(parser.new_unexpected_token_error(t)),
+    }
+}
+
+impl<N: Normalize> Length<N> {
+    /// Creates a length out of the terms of a `calc()` expression.
+    fn from_calc_terms(terms: [f64; NUM_UNITS]) -> Length<N> {
+        let mut units = ALL_UNITS.iter().filter(|&&u| terms[u as usize] != 0.0);
+
+        match (units.next(), units.next()) {
+            (None, _) => Length::new(0.0, LengthUnit::Px),
+
+            (Some(&unit), None) => Length::new(terms[unit as usize], unit),
+
+            (Some(&first), Some(_)) => {
+                let unit = ALL_UNITS
+                    .iter()
+                    .cloned()
+                    .find(|&u| u.is_viewport_relative() && terms[u as usize] != 0.0)
+                    .unwrap_or(first);
+
+                Length {
+                    length: terms[unit as usize],
+                    unit,
+                    calc: Some(Calc {
+                        terms,
+                        nonnegative: false,
+                    }),
+                    orientation: PhantomData,
+                }
+            }
+        }
+    }
+}
+
 impl<N: Normalize> Parse for Length<N> {
     fn parse(parser: &mut Parser<'_, '_>) -> Result<Length<N>, ValueErrorKind> {
         let length = {
@@ -257,17 +535,22 @@ impl<N: Normalize> Parse for Length<N> {
                 } => {
                     let value = f64::from(finite_f32(value)?);
 
-                    match unit.as_ref() {
-                        "px" => Length::new(value, LengthUnit::Px),
-                        "em" => Length::new(value, LengthUnit::Em),
-                        "ex" => Length::new(value, LengthUnit::Ex),
-                        "in" => Length::new(value, LengthUnit::In),
-                        "cm" => Length::new(value, LengthUnit::Cm),
-                        "mm" => Length::new(value, LengthUnit::Mm),
-                        "pt" => Length::new(value, LengthUnit::Pt),
-                        "pc" => Length::new(value, LengthUnit::Pc),
-
-                        _ => return Err(make_err()),
+                    match parse_unit(unit.as_ref()) {
+                        Some(unit) => Length::new(value, unit),
+                        None => return Err(make_err()),
+                    }
+                }
+
+                Token::Function(ref name) if name.eq_ignore_ascii_case("calc") => {
+                    match parser.parse_nested_block(parse_calc_sum)? {
+                        CalcValue::Length(terms) => Length::from_calc_terms(terms),
+
+                        // Plain numbers are lengths in pixels, but only outside of calc()
+                        CalcValue::Number(_) => {
+                            return Err(ValueErrorKind::parse_error(
+                                "calc() expression must be a length",
+                            ));
+                        }
                     }
                 }
 
@@ -279,6 +562,60 @@ impl<N: Normalize> Parse for Length<N> {
     }
 }
 
+impl<N: Normalize> ToCssValue for Length<N> {
+    fn to_css_value(&self) -> String {
+        if let Some(ref calc) = self.calc {
+            let mut s = String::from("calc(");
+
+            for &unit in ALL_UNITS.iter() {
+                let coefficient = calc.terms[unit as usize];
+
+                if coefficient == 0.0 {
+                    continue;
+                }
+
+                let term = Length::<N>::new(coefficient.abs(), unit).to_css_value();
+
+                if s.len() == "calc(".len() {
+                    if coefficient < 0.0 {
+                        s.push('-');
+                    }
+                } else if coefficient < 0.0 {
+                    s.push_str(" - ");
+                } else {
+                    s.push_str(" + ");
+                }
+
+                s.push_str(&term);
+            }
+
+            s.push(')');
+            return s;
+        }
+
+        let (value, unit) = match self.unit {
+            LengthUnit::Percent => (self.length * 100.0, "%"),
+            LengthUnit::Px => (self.length, "px"),
+            LengthUnit::Em => (self.length, "em"),
+            LengthUnit::Ex => (self.length, "ex"),
+            LengthUnit::In => (self.length, "in"),
+            LengthUnit::Cm => (self.length, "cm"),
+            LengthUnit::Mm => (self.length, "mm"),
+            LengthUnit::Pt => (self.length, "pt"),
+            LengthUnit::Pc => (self.length, "pc"),
+            LengthUnit::Vw => (self.length, "vw"),
+            LengthUnit::Vh => (self.length, "vh"),
+            LengthUnit::Vmin => (self.length, "vmin"),
+            LengthUnit::Vmax => (self.length, "vmax"),
+            LengthUnit::Ch => (self.length, "ch"),
+            LengthUnit::Rem => (self.length, "rem"),
+            LengthUnit::Q => (self.length, "Q"),
+        };
+
+        format!("{}{}", value, unit)
+    }
+}
+
 impl<N: Normalize> Length<N> {
     /// Creates a Length.
     ///
@@ -296,6 +633,7 @@ impl<N: Normalize> Length<N> {
         Length {
             length: l,
             unit,
+            calc: None,
             orientation: PhantomData,
         }
     }
@@ -309,8 +647,20 @@ impl<N: Normalize> Length<N> {
     ///
     /// let length = Length::<Horizontal>::parse(&mut parser).and_then(Length::check_nonnegative)?;
     /// ```
+    ///
+    /// The sign of a `calc()` expression that mixes units is not known until it is
+    /// normalized, so in that case the length is accepted, and clamped to zero if it
+    /// turns out to be negative.
     pub fn check_nonnegative(self) -> Result<Self, ValueErrorKind> {
-        if self.length >= 0.0 {
+        if let Some(calc) = self.calc {
+            Ok(Length {
+                calc: Some(Calc {
+                    nonnegative: true,
+                    ..calc
+                }),
+                ..self
+            })
+        } else if self.length >= 0.0 {
             Ok(self)
         } else {
             Err(ValueErrorKind::Value(
@@ -326,6 +676,17 @@ impl<N: Normalize> Length<N> {
     /// based on the current element's set of `ComputedValues` (e.g. for lengths with `Em`
     /// units that need to be resolved against the current font size).
     pub fn normalize(&self, values: &ComputedValues, params: &ViewParams) -> f64 {
+        if let Some(ref calc) = self.calc {
+            let sum: f64 = ALL_UNITS
+                .iter()
+                .map(|&unit| {
+                    Length::<N>::new(calc.terms[unit as usize], unit).normalize(values, params)
+                })
+                .sum();
+
+            return if calc.nonnegative { sum.max(0.0) } else { sum };
+        }
+
         match self.unit {
             LengthUnit::Px => self.length,
 
@@ -361,13 +722,70 @@ impl<N: Normalize> Length<N> {
                 self.length * <N as Normalize>::normalize(params.dpi_x, params.dpi_y)
                     / PICA_PER_INCH
             }
+
+            LengthUnit::Vw | LengthUnit::Vh | LengthUnit::Vmin | LengthUnit::Vmax => {
+                self.length * viewport_unit_size(self.unit, params)
+            }
+
+            LengthUnit::Ch => self.length * ch_size(values, params),
+
+            LengthUnit::Rem => self.length * root_font_size_from_values(values, params),
+
+            LengthUnit::Q => {
+                self.length * <N as Normalize>::normalize(params.dpi_x, params.dpi_y) / Q_PER_INCH
+            }
         }
     }
 }
 
 fn font_size_from_values(values: &ComputedValues, params: &ViewParams) -> f64 {
-    let v = &values.font_size.0.value();
+    let root = values.root_font_size.0.value();
+
+    font_size_to_pixels(&values.font_size.0.value(), Some(&root), params)
+}
+
+fn root_font_size_from_values(values: &ComputedValues, params: &ViewParams) -> f64 {
+    // rem units in the root element's own font size refer to the initial font size
+    font_size_to_pixels(&values.root_font_size.0.value(), None, params)
+}
 
+/// Advance of the "0" glyph in the current font, which is what `ch` units refer to
+fn ch_size(values: &ComputedValues, params: &ViewParams) -> f64 {
+    let font_size = font_size_from_values(values, params);
+
+    #[cfg(feature = "text")]
+    {
+        if let Some(advance) = crate::text::measure_zero_advance(values, font_size) {
+            return advance;
+        }
+    }
+
+    // https://www.w3.org/TR/css-values-3/#ch says to use 0.5em if the advance
+    // can't be measured.
+    font_size / 2.0
+}
+
+fn font_size_to_pixels(v: &Length<Both>, root: Option<&Length<Both>>, params: &ViewParams) -> f64 {
+    if let Some(ref calc) = v.calc {
+        return ALL_UNITS
+            .iter()
+            .map(|&unit| {
+                let term = calc.terms[unit as usize];
+
+                match unit {
+                    // The percentage of a calc() font size is relative to the parent's
+                    // font size, which we don't know here; use the same default as for em.
+                    LengthUnit::Percent => term * 12.0,
+                    _ => font_size_in_pixels(&Length::<Both>::new(term, unit), root, params),
+                }
+            })
+            .sum();
+    }
+
+    font_size_in_pixels(v, root, params)
+}
+
+fn font_size_in_pixels(v: &Length<Both>, root: Option<&Length<Both>>, params: &ViewParams) -> f64 {
     match v.unit {
         LengthUnit::Percent => unreachable!("ComputedValues can't have a relative font size"),
 
@@ -379,15 +797,41 @@ fn font_size_from_values(values: &ComputedValues, params: &ViewParams) -> f64 {
         // This is the same default as used in Svg::get_size()
         LengthUnit::Ex => v.length * 12.0 / 2.0,
 
+        // Like Ex, this uses the fallback of 0.5em from the default font size
+        LengthUnit::Ch => v.length * 12.0 / 2.0,
+
+        LengthUnit::Rem => v.length * root.map_or(12.0, |r| font_size_to_pixels(r, None, params)),
+
         // FontSize always is a Both, per properties.rs
         LengthUnit::In => v.length * Both::normalize(params.dpi_x, params.dpi_y),
         LengthUnit::Cm => v.length * Both::normalize(params.dpi_x, params.dpi_y) / CM_PER_INCH,
         LengthUnit::Mm => v.length * Both::normalize(params.dpi_x, params.dpi_y) / MM_PER_INCH,
+        LengthUnit::Q => v.length * Both::normalize(params.dpi_x, params.dpi_y) / Q_PER_INCH,
         LengthUnit::Pt => v.length * Both::normalize(params.dpi_x, params.dpi_y) / POINTS_PER_INCH,
         LengthUnit::Pc => v.length * Both::normalize(params.dpi_x, params.dpi_y) / PICA_PER_INCH,
+
+        LengthUnit::Vw | LengthUnit::Vh | LengthUnit::Vmin | LengthUnit::Vmax => {
+            v.length * viewport_unit_size(v.unit, params)
+        }
     }
 }
 
+/// Size of one viewport-percentage unit like `vw`; these are relative to the toplevel
+/// viewport, not to the nearest one, and don't depend on a length's orientation.
+fn viewport_unit_size(unit: LengthUnit, params: &ViewParams) -> f64 {
+    let (w, h) = (params.viewport_width, params.viewport_height);
+
+    let size = match unit {
+        LengthUnit::Vw => w,
+        LengthUnit::Vh => h,
+        LengthUnit::Vmin => w.min(h),
+        LengthUnit::Vmax => w.max(h),
+        _ => unreachable!(),
+    };
+
+    size / 100.0
+}
+
 fn viewport_percentage(x: f64, y: f64) -> f64 {
     // https://www.w3.org/TR/SVG/coords.html#Units
     // "For any other length value expressed as a percentage of the viewport, the
@@ -401,6 +845,8 @@ mod tests {
     use super::*;
 
     use crate::float_eq_cairo::ApproxEqCairo;
+    use crate::font_props::FontSizeSpec;
+    use crate::property_defs::FontSize;
 
     #[test]
     fn parses_default() {
@@ -477,6 +923,45 @@ mod tests {
         assert!(is_parse_error(&Length::<Both>::parse_str("8furlong")));
     }
 
+    #[test]
+    fn parses_calc() {
+        assert_eq!(
+            Length::<Horizontal>::parse_str("calc(10px + 2 * 5px)"),
+            Ok(Length::<Horizontal>::new(20.0, LengthUnit::Px))
+        );
+
+        assert_eq!(
+            Length::<Horizontal>::parse_str("CALC((50% + 50%) / 4)"),
+            Ok(Length::<Horizontal>::new(0.25, LengthUnit::Percent))
+        );
+
+        assert_eq!(
+            Length::<Horizontal>::parse_str("calc(1em - 1em)"),
+            Ok(Length::<Horizontal>::new(0.0, LengthUnit::Px))
+        );
+
+        let l = Length::<Horizontal>::parse_str("calc(100% - 2 * calc(5px + 1em))").unwrap();
+        assert_eq!(l.unit, LengthUnit::Percent);
+        assert_eq!(l.length, 1.0);
+        assert_eq!(l.to_css_value(), "calc(100% - 10px - 2em)");
+    }
+
+    #[test]
+    fn invalid_calc_yields_error() {
+        assert!(is_parse_error(&Length::<Both>::parse_str("calc()")));
+        assert!(is_parse_error(&Length::<Both>::parse_str("calc(10)")));
+        assert!(is_parse_error(&Length::<Both>::parse_str("calc(10px + 5)")));
+        assert!(is_parse_error(&Length::<Both>::parse_str(
+            "calc(10px * 5px)"
+        )));
+        assert!(is_parse_error(&Length::<Both>::parse_str("calc(10px / 0)")));
+        assert!(is_parse_error(&Length::<Both>::parse_str(
+            "calc(10px / 1px)"
+        )));
+        assert!(is_parse_error(&Length::<Both>::parse_str("calc(10% -5px)")));
+        assert!(is_parse_error(&Length::<Both>::parse_str("calc(8furlong)")));
+    }
+
     #[test]
     fn check_nonnegative_works() {
         // and_then with anonymous function
@@ -551,6 +1036,134 @@ mod tests {
         );
     }
 
+    #[test]
+    fn normalize_calc_works() {
+        let params = ViewParams::new(40.0, 40.0, 100.0, 200.0);
+
+        let values = ComputedValues::default();
+
+        let l = Length::<Horizontal>::parse_str("calc(100% - 20px)").unwrap();
+        assert_approx_eq_cairo!(l.normalize(&values, &params), 80.0);
+
+        let l = Length::<Vertical>::parse_str("calc(50% + 1em)").unwrap();
+        assert_approx_eq_cairo!(l.normalize(&values, &params), 112.0);
+
+        let l = Length::<Horizontal>::parse_str("calc(10% - 1in)").unwrap();
+        assert_approx_eq_cairo!(l.normalize(&values, &params), -30.0);
+
+        let l = l.check_nonnegative().unwrap();
+        assert_approx_eq_cairo!(l.normalize(&values, &params), 0.0);
+    }
+
+    #[test]
+    fn parses_viewport_units() {
+        assert_eq!(
+            Length::<Horizontal>::parse_str("50vw"),
+            Ok(Length::<Horizontal>::new(50.0, LengthUnit::Vw))
+        );
+
+        assert_eq!(
+            Length::<Vertical>::parse_str("-2.5vh"),
+            Ok(Length::<Vertical>::new(-2.5, LengthUnit::Vh))
+        );
+
+        assert_eq!(
+            Length::<Both>::parse_str("10vmin"),
+            Ok(Length::<Both>::new(10.0, LengthUnit::Vmin))
+        );
+
+        assert_eq!(
+            Length::<Both>::parse_str("10vmax"),
+            Ok(Length::<Both>::new(10.0, LengthUnit::Vmax))
+        );
+
+        let l = Length::<Horizontal>::parse_str("calc(10px + 5vw)").unwrap();
+        assert_eq!(l.unit, LengthUnit::Vw);
+        assert_eq!(l.to_css_value(), "calc(10px + 5vw)");
+    }
+
+    #[test]
+    fn normalize_viewport_units_works() {
+        // A nested viewport of 100x200 inside a toplevel one of 400x300
+        let mut params = ViewParams::new(40.0, 40.0, 100.0, 200.0);
+        params.viewport_width = 400.0;
+        params.viewport_height = 300.0;
+
+        let values = ComputedValues::default();
+
+        assert_approx_eq_cairo!(
+            Length::<Vertical>::new(10.0, LengthUnit::Vw).normalize(&values, &params),
+            40.0
+        );
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(10.0, LengthUnit::Vh).normalize(&values, &params),
+            30.0
+        );
+        assert_approx_eq_cairo!(
+            Length::<Both>::new(10.0, LengthUnit::Vmin).normalize(&values, &params),
+            30.0
+        );
+        assert_approx_eq_cairo!(
+            Length::<Both>::new(10.0, LengthUnit::Vmax).normalize(&values, &params),
+            40.0
+        );
+
+        let l = Length::<Horizontal>::parse_str("calc(50% + 10vw)").unwrap();
+        assert_approx_eq_cairo!(l.normalize(&values, &params), 90.0);
+    }
+
+    #[test]
+    fn parses_font_relative_units_and_q() {
+        assert_eq!(
+            Length::<Both>::parse_str("2ch"),
+            Ok(Length::<Both>::new(2.0, LengthUnit::Ch))
+        );
+
+        assert_eq!(
+            Length::<Both>::parse_str("1.5rem"),
+            Ok(Length::<Both>::new(1.5, LengthUnit::Rem))
+        );
+
+        assert_eq!(
+            Length::<Both>::parse_str("4Q"),
+            Ok(Length::<Both>::new(4.0, LengthUnit::Q))
+        );
+
+        assert_eq!(Length::<Both>::new(4.0, LengthUnit::Q).to_css_value(), "4Q");
+    }
+
+    #[test]
+    fn normalize_rem_and_q_works() {
+        let params = ViewParams::new(40.0, 40.0, 100.0, 200.0);
+
+        let mut values = ComputedValues::default();
+
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(2.0, LengthUnit::Rem).normalize(&values, &params),
+            24.0
+        );
+
+        values.root_font_size = FontSize(FontSizeSpec::Value(Length::new(20.0, LengthUnit::Px)));
+
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(2.0, LengthUnit::Rem).normalize(&values, &params),
+            40.0
+        );
+
+        // rem units in the root's font size refer to the initial font size
+        values.root_font_size = FontSize(FontSizeSpec::Value(Length::new(2.0, LengthUnit::Rem)));
+
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(1.0, LengthUnit::Rem).normalize(&values, &params),
+            24.0
+        );
+
+        assert_approx_eq_cairo!(
+            Length::<Horizontal>::new(10.0, LengthUnit::Q).normalize(&values, &params),
+            400.0 / Q_PER_INCH
+        );
+    }
+
     #[test]
     fn normalize_font_em_ex_works() {
         let params = ViewParams::new(40.0, 40.0, 100.0, 200.0);
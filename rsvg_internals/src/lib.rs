@@ -40,20 +40,70 @@
 #![warn(unused)]
 use ::xml as xml_rs;
 
+pub use crate::animated_image::{AnimatedImageFormat, AnimationOptions};
+
+pub use crate::clip_path::ClipShape;
+
 pub use crate::color::{rsvg_css_parse_color, ColorKind, ColorSpec};
 
+pub use crate::complexity::ComplexityReport;
+
+pub use crate::css::{DeclarationInfo, Origin, RuleInfo, SelectorInfo};
+
+pub use crate::diff::{AttributeChange, DiffElement, DocumentDiff, ElementChange};
+
+pub use crate::document_cache::DocumentCache;
+
 pub use crate::dpi::{rsvg_rust_set_default_dpi_x_y, Dpi};
 
-pub use crate::error::{DefsLookupErrorKind, HrefError, LoadingError, RenderingError};
+pub use crate::drawing_ctx::{RenderQuality, StrokeMetrics};
+
+pub use crate::element_overrides::ElementOverrides;
+
+pub use crate::error::{
+    DefsLookupErrorKind,
+    HrefError,
+    LoadingError,
+    RenderingError,
+    SelectorError,
+};
+
+#[cfg(feature = "text")]
+pub use crate::font_face::ApplicationFonts;
+
+pub use crate::font_props::{FontFallback, FontFallbackKind, FontFamilyMap, GenericFontFamily};
+
+pub use crate::glyph_run::{GlyphRun, PositionedGlyph};
 
 pub use crate::handle::{
-    Handle, LoadOptions, RsvgDimensionData, RsvgPositionData, RsvgSizeFunc, SizeCallback,
+    ElementContext,
+    Handle,
+    ImageResolver,
+    LoadOptions,
+    RsvgDimensionData,
+    RsvgPositionData,
+    RsvgSizeFunc,
+    SizeCallback,
 };
 
 pub use crate::length::{Length, LengthUnit, RsvgLength};
 
+pub use crate::marker::{MarkerPosition, MarkerType};
+
+pub use crate::media::{ColorScheme, MediaFeatures};
+
+pub use crate::path_builder::PathSegment;
+
+pub use crate::path_measure::PathPoint;
+
+pub use crate::path_parser::PathDataRecovery;
+
+pub use crate::postscript::{PostScriptLevel, PostScriptOptions};
+
 pub use crate::rect::IRect;
 
+pub use crate::render_hooks::{HookAction, RenderHookElement, RenderHooks};
+
 pub use crate::structure::IntrinsicDimensions;
 
 pub use crate::surface_utils::{
@@ -61,6 +111,8 @@ pub use crate::surface_utils::{
     shared_surface::{SharedImageSurface, SurfaceType},
 };
 
+pub use crate::validate::{SvgProfile, ValidationIssue, ValidationIssueKind, ValidationReport};
+
 pub use crate::viewbox::ViewBox;
 
 #[macro_use]
@@ -77,24 +129,45 @@ mod property_macros;
 
 mod allowed_url;
 mod angle;
+mod animated_image;
+mod animation;
 mod aspect_ratio;
 mod bbox;
+mod chars;
 mod clip_path;
 mod color;
+mod complexity;
 mod cond;
 mod create_node;
 mod css;
+mod css_animation;
 mod dasharray;
+mod diff;
 mod document;
+mod document_cache;
 mod dpi;
 mod drawing_ctx;
+mod element_overrides;
 mod error;
+
+#[cfg(feature = "filters")]
 mod filter;
+
+#[cfg(feature = "filters")]
 pub mod filters;
+
+mod filter_functions;
+mod font_face;
 mod font_props;
+mod geometry;
+mod glyph_run;
 mod gradient;
 mod handle;
+mod hatch;
+
+#[cfg(feature = "raster-images")]
 mod image;
+
 mod io;
 mod iri;
 mod length;
@@ -102,27 +175,39 @@ mod limits;
 mod link;
 mod marker;
 mod mask;
+mod media;
+mod mesh_gradient;
+mod motion_path;
 mod node;
 mod number_list;
 mod paint_server;
 mod parsers;
 mod path_builder;
+mod path_measure;
 mod path_parser;
 mod pattern;
+mod postscript;
 mod properties;
 mod property_bag;
 mod property_defs;
 pub mod rect;
+mod render_hooks;
+mod resources;
+mod sanitize;
 mod shapes;
 mod space;
 pub mod srgb;
 mod structure;
 mod style;
 pub mod surface_utils;
+
+#[cfg(feature = "text")]
 mod text;
+
 mod transform;
 mod unit_interval;
 mod util;
+mod validate;
 mod viewbox;
 mod xml;
 mod xml2;
@@ -26,3 +26,16 @@ pub const MAX_REFERENCED_ELEMENTS: usize = 500_000;
 /// in an attempt to exhaust memory.  We don't allow loading more than
 /// this number of elements during the initial streaming load process.
 pub const MAX_LOADED_ELEMENTS: usize = 200_000;
+
+/// This is a mitigation for SVG files with patterns that have huge
+/// `patternTransform` scales, which would make us allocate enormous
+/// surfaces for the pattern tiles.  Tiles get rendered at a lower
+/// resolution if they would be larger than this, in device pixels,
+/// on either side.  It can be changed with
+/// `LoadOptions::with_max_pattern_tile_size()`.
+pub const MAX_PATTERN_TILE_SIZE: i32 = 4096;
+
+/// This is a mitigation for animations with huge frame rates or durations, which
+/// would take a really long time to render and encode.  Rendering an animation with
+/// more frames than this fails instead.
+pub const MAX_ANIMATION_FRAMES: u32 = 10_000;
@@ -8,7 +8,7 @@ use crate::allowed_url::Fragment;
 use crate::angle::Angle;
 use crate::aspect_ratio::*;
 use crate::bbox::BoundingBox;
-use crate::drawing_ctx::DrawingCtx;
+use crate::drawing_ctx::{stroke_matrix, DrawingCtx};
 use crate::error::*;
 use crate::float_eq_cairo::ApproxEqCairo;
 use crate::iri::IRI;
@@ -59,6 +59,7 @@ impl Parse for MarkerUnits {
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum MarkerOrient {
     Auto,
+    AutoStartReverse,
     Angle(Angle),
 }
 
@@ -75,6 +76,11 @@ impl Parse for MarkerOrient {
             .is_ok()
         {
             Ok(MarkerOrient::Auto)
+        } else if parser
+            .try_parse(|p| p.expect_ident_matching("auto-start-reverse"))
+            .is_ok()
+        {
+            Ok(MarkerOrient::AutoStartReverse)
         } else {
             Angle::parse(parser).map(MarkerOrient::Angle)
         }
@@ -116,6 +122,7 @@ impl Marker {
         xpos: f64,
         ypos: f64,
         computed_angle: Angle,
+        marker_type: MarkerType,
         line_width: f64,
         clipping: bool,
     ) -> Result<BoundingBox, RenderingError> {
@@ -140,6 +147,10 @@ impl Marker {
 
             let rotation = match self.orient {
                 MarkerOrient::Auto => computed_angle,
+                MarkerOrient::AutoStartReverse if marker_type == MarkerType::Start => {
+                    computed_angle.flip()
+                }
+                MarkerOrient::AutoStartReverse => computed_angle,
                 MarkerOrient::Angle(a) => a,
             };
 
@@ -149,8 +160,11 @@ impl Marker {
                 cr.scale(line_width, line_width);
             }
 
+            // The marker's viewport, in the coordinates of its content
+            let viewport;
+
             let params = if let Some(vbox) = self.vbox {
-                let (_, _, w, h) = self.aspect.compute(
+                let (x, y, w, h) = self.aspect.compute(
                     &vbox,
                     &cairo::Rectangle::from_size(marker_width, marker_height),
                 );
@@ -159,10 +173,25 @@ impl Marker {
                     return Ok(dc.empty_bbox());
                 }
 
-                cr.scale(w / vbox.width, h / vbox.height);
+                let sx = w / vbox.width;
+                let sy = h / vbox.height;
+
+                cr.scale(sx, sy);
+
+                // The offset from preserveAspectRatio's alignment cancels out when
+                // the reference point is moved to the vertex, but it still determines
+                // where the viewport lies with respect to the viewBox.
+                viewport = cairo::Rectangle::new(
+                    vbox.x - x / sx,
+                    vbox.y - y / sy,
+                    marker_width / sx,
+                    marker_height / sy,
+                );
 
                 dc.push_view_box(vbox.width, vbox.height)
             } else {
+                viewport = cairo::Rectangle::from_size(marker_width, marker_height);
+
                 dc.push_view_box(marker_width, marker_height)
             };
 
@@ -171,12 +200,9 @@ impl Marker {
                 -self.ref_y.normalize(&values, &params),
             );
 
+            // Markers clip to their viewport unless overflow is visible
             if !values.is_overflow() {
-                if let Some(vbox) = self.vbox {
-                    dc.clip(vbox.x, vbox.y, vbox.width, vbox.height);
-                } else {
-                    dc.clip(0.0, 0.0, marker_width, marker_height);
-                }
+                dc.clip(viewport.x, viewport.y, viewport.width, viewport.height);
             }
 
             dc.with_discrete_layer(node, values, clipping, &mut |dc| {
@@ -352,6 +378,43 @@ impl Deref for Segments {
 // both just point in the same direction).
 impl<'a> From<&'a PathBuilder> for Segments {
     fn from(builder: &PathBuilder) -> Segments {
+        Segments::from_path_builder(builder).0
+    }
+}
+
+/// A run of `Segment::LineOrCurve` that form a subpath, as indices into `Segments`
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Subpath {
+    first: usize,
+    last: usize,
+
+    // Whether the subpath ends with a closepath; its last segment is then the closing one.
+    closed: bool,
+
+    // Whether the subpath starts with a moveto, as opposed to continuing right after
+    // a closepath, in which case it starts at the previous subpath's closing vertex.
+    after_moveto: bool,
+}
+
+// Records that a LineOrCurve segment is about to be pushed at `index`
+fn add_to_subpath(subpath: &mut Option<Subpath>, index: usize, after_moveto: bool) {
+    match *subpath {
+        Some(ref mut s) => s.last = index,
+        None => {
+            *subpath = Some(Subpath {
+                first: index,
+                last: index,
+                closed: false,
+                after_moveto,
+            })
+        }
+    }
+}
+
+impl Segments {
+    /// Converts a path builder into segments, and also returns the subpaths
+    /// that they form.
+    fn from_path_builder(builder: &PathBuilder) -> (Segments, Vec<Subpath>) {
         let mut last_x: f64;
         let mut last_y: f64;
         let mut cur_x: f64;
@@ -360,6 +423,9 @@ impl<'a> From<&'a PathBuilder> for Segments {
         let mut subpath_start_y: f64;
         let mut segments: Vec<Segment>;
         let mut state: SegmentState;
+        let mut subpath: Option<Subpath> = None;
+        let mut subpaths = Vec::new();
+        let mut after_moveto = true;
 
         cur_x = 0.0;
         cur_y = 0.0;
@@ -381,6 +447,9 @@ impl<'a> From<&'a PathBuilder> for Segments {
                     subpath_start_x = cur_x;
                     subpath_start_y = cur_y;
 
+                    subpaths.extend(subpath.take());
+                    after_moveto = true;
+
                     match state {
                         SegmentState::Initial | SegmentState::InSubpath => {
                             // Ignore the very first moveto in a sequence (Initial state),
@@ -415,6 +484,7 @@ impl<'a> From<&'a PathBuilder> for Segments {
                     cur_x = x;
                     cur_y = y;
 
+                    add_to_subpath(&mut subpath, segments.len(), after_moveto);
                     segments.push(Segment::line(last_x, last_y, cur_x, cur_y));
 
                     state = SegmentState::InSubpath;
@@ -429,6 +499,7 @@ impl<'a> From<&'a PathBuilder> for Segments {
                     cur_x = to.0;
                     cur_y = to.1;
 
+                    add_to_subpath(&mut subpath, segments.len(), after_moveto);
                     segments.push(Segment::curve(last_x, last_y, x2, y2, x3, y3, cur_x, cur_y));
 
                     state = SegmentState::InSubpath;
@@ -457,12 +528,14 @@ impl<'a> From<&'a PathBuilder> for Segments {
 
                             let (x2, y2) = segment1.pt1;
                             let (x3, y3) = segment2.pt2;
+                            add_to_subpath(&mut subpath, segments.len(), after_moveto);
                             segments
                                 .push(Segment::curve(last_x, last_y, x2, y2, x3, y3, cur_x, cur_y));
 
                             state = SegmentState::InSubpath;
                         }
                         ArcParameterization::LineTo => {
+                            add_to_subpath(&mut subpath, segments.len(), after_moveto);
                             segments.push(Segment::line(last_x, last_y, cur_x, cur_y));
 
                             state = SegmentState::InSubpath;
@@ -475,8 +548,15 @@ impl<'a> From<&'a PathBuilder> for Segments {
                     cur_x = subpath_start_x;
                     cur_y = subpath_start_y;
 
+                    add_to_subpath(&mut subpath, segments.len(), after_moveto);
                     segments.push(Segment::line(last_x, last_y, cur_x, cur_y));
 
+                    if let Some(mut s) = subpath.take() {
+                        s.closed = true;
+                        subpaths.push(s);
+                    }
+                    after_moveto = false;
+
                     state = SegmentState::ClosedSubpath;
                 }
             }
@@ -488,7 +568,9 @@ impl<'a> From<&'a PathBuilder> for Segments {
             segments.push(Segment::degenerate(cur_x, cur_y));
         };
 
-        Segments(segments)
+        subpaths.extend(subpath);
+
+        (Segments(segments), subpaths)
     }
 }
 
@@ -564,20 +646,43 @@ impl Segments {
     }
 }
 
-// From SVG's marker-start, marker-mid, marker-end properties
+/// Which of the `marker-start`, `marker-mid`, or `marker-end` properties a marker comes from
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum MarkerType {
+pub enum MarkerType {
+    /// At the first vertex of a path
     Start,
+
+    /// At every vertex other than the first and the last
     Middle,
+
+    /// At the last vertex of a path
     End,
 }
 
+/// Where a marker is placed on a shape
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MarkerPosition {
+    pub marker_type: MarkerType,
+
+    /// Horizontal position of the vertex, where the marker's reference point goes
+    pub x: f64,
+
+    /// Vertical position of the vertex, where the marker's reference point goes
+    pub y: f64,
+
+    /// Direction of the path at the vertex, in degrees clockwise from the x axis
+    ///
+    /// Markers with `orient="auto"` are rotated by this angle.
+    pub angle: f64,
+}
+
 fn emit_marker_by_name(
     draw_ctx: &mut DrawingCtx,
     name: &Fragment,
     xpos: f64,
     ypos: f64,
     computed_angle: Angle,
+    marker_type: MarkerType,
     line_width: f64,
     clipping: bool,
 ) -> Result<BoundingBox, RenderingError> {
@@ -590,6 +695,7 @@ fn emit_marker_by_name(
             xpos,
             ypos,
             computed_angle,
+            marker_type,
             line_width,
             clipping,
         )
@@ -638,7 +744,16 @@ pub fn render_markers_for_path_builder(
         .0
         .normalize(values, &draw_ctx.get_view_params());
 
-    if line_width.approx_eq_cairo(0.0) {
+    // markerUnits="strokeWidth" refers to the width of the stroke as it gets rendered,
+    // which is not the specified one in user space for vector-effect="non-scaling-stroke".
+    let line_width = {
+        let cr = draw_ctx.get_cairo_context();
+        let scale = |m: cairo::Matrix| (m.xx * m.yy - m.xy * m.yx).abs().sqrt();
+
+        line_width * scale(stroke_matrix(&cr, values)) / scale(cr.get_matrix())
+    };
+
+    if !line_width.is_finite() || line_width.approx_eq_cairo(0.0) {
         return Ok(draw_ctx.empty_bbox());
     }
 
@@ -650,21 +765,34 @@ pub fn render_markers_for_path_builder(
         return Ok(draw_ctx.empty_bbox());
     }
 
-    emit_markers_for_path_builder(
-        builder,
-        draw_ctx.empty_bbox(),
-        &mut |marker_type: MarkerType, x: f64, y: f64, computed_angle: Angle| {
-            if let &IRI::Resource(ref marker) = match marker_type {
-                MarkerType::Start => &values.marker_start.0,
-                MarkerType::Middle => &values.marker_mid.0,
-                MarkerType::End => &values.marker_end.0,
-            } {
-                emit_marker_by_name(draw_ctx, marker, x, y, computed_angle, line_width, clipping)
-            } else {
-                Ok(draw_ctx.empty_bbox())
-            }
-        },
-    )
+    // The shape is the context element for the content of its markers
+    draw_ctx.with_context_paint(values, &mut |dc| {
+        emit_markers_for_path_builder(
+            builder,
+            dc.empty_bbox(),
+            &mut |marker_type: MarkerType, x: f64, y: f64, computed_angle: Angle| {
+                if let &IRI::Resource(ref marker) = match marker_type {
+                    MarkerType::Start => &values.marker_start.0,
+                    MarkerType::Middle => &values.marker_mid.0,
+                    MarkerType::End => &values.marker_end.0,
+                } {
+                    dc.add_marker_position(marker_type, x, y, computed_angle);
+                    emit_marker_by_name(
+                        dc,
+                        marker,
+                        x,
+                        y,
+                        computed_angle,
+                        marker_type,
+                        line_width,
+                        clipping,
+                    )
+                } else {
+                    Ok(dc.empty_bbox())
+                }
+            },
+        )
+    })
 }
 
 fn emit_markers_for_path_builder<E>(
@@ -683,7 +811,56 @@ where
     let mut bbox = empty_bbox;
 
     // Convert the path to a list of segments and bare points
-    let segments = Segments::from(builder);
+    let (segments, subpaths) = Segments::from_path_builder(builder);
+
+    // At the start vertex of a closed subpath, the incoming direction is that of
+    // the closing segment; likewise, at its closing vertex the outgoing direction
+    // is that of the first segment.  So markers there are oriented halfway between.
+    let closed_subpath_angle = |subpath: &Subpath| {
+        let (_, incoming_vx, incoming_vy) =
+            segments.find_incoming_directionality_backwards(subpath.last);
+        let (_, outgoing_vx, outgoing_vy) =
+            segments.find_outgoing_directionality_forwards(subpath.first);
+        let incoming = Angle::from_vector(incoming_vx, incoming_vy);
+        let outgoing = Angle::from_vector(outgoing_vx, outgoing_vy);
+        incoming.bisect(outgoing)
+    };
+
+    // The closed subpaths that start and end at each segment, and whether a subpath
+    // starts at each segment with a moveto; built once so that looking them up in the
+    // loop below doesn't need to go through all the subpaths for every segment.
+    let mut closed_starting_at = vec![None; segments.len()];
+    let mut closed_ending_at = vec![None; segments.len()];
+    let mut starts_after_moveto = vec![false; segments.len()];
+
+    for subpath in subpaths.iter().rev() {
+        if subpath.closed {
+            closed_starting_at[subpath.first] = Some(subpath);
+            closed_ending_at[subpath.last] = Some(subpath);
+        }
+
+        if subpath.after_moveto {
+            starts_after_moveto[subpath.first] = true;
+        }
+    }
+
+    // Orientation at the first vertex of the subpath that starts at segment `i`
+    let start_angle = |i: usize| match closed_starting_at[i] {
+        Some(subpath) => closed_subpath_angle(subpath),
+        None => {
+            let (_, outgoing_vx, outgoing_vy) = segments.find_outgoing_directionality_forwards(i);
+            Angle::from_vector(outgoing_vx, outgoing_vy)
+        }
+    };
+
+    // Orientation at the last vertex of the subpath that ends at segment `i`
+    let end_angle = |i: usize| match closed_ending_at[i] {
+        Some(subpath) => closed_subpath_angle(subpath),
+        None => {
+            let (_, incoming_vx, incoming_vy) = segments.find_incoming_directionality_backwards(i);
+            Angle::from_vector(incoming_vx, incoming_vy)
+        }
+    };
 
     let mut subpath_state = SubpathState::NoSubpath;
 
@@ -694,13 +871,11 @@ where
                     assert!(i > 0);
 
                     // Got a lone point after a subpath; render the subpath's end marker first
-                    let (_, incoming_vx, incoming_vy) =
-                        segments.find_incoming_directionality_backwards(i - 1);
                     let marker_bbox = emit_marker(
                         &segments[i - 1],
                         MarkerEndpoint::End,
                         MarkerType::End,
-                        Angle::from_vector(incoming_vx, incoming_vy),
+                        end_angle(i - 1),
                         emit_fn,
                     )?;
                     bbox.insert(&marker_bbox);
@@ -723,13 +898,11 @@ where
                 // Not a degenerate segment
                 match subpath_state {
                     SubpathState::NoSubpath => {
-                        let (_, outgoing_vx, outgoing_vy) =
-                            segments.find_outgoing_directionality_forwards(i);
                         let marker_bbox = emit_marker(
                             segment,
                             MarkerEndpoint::Start,
                             MarkerType::Start,
-                            Angle::from_vector(outgoing_vx, outgoing_vy),
+                            start_angle(i),
                             emit_fn,
                         )?;
                         bbox.insert(&marker_bbox);
@@ -737,6 +910,31 @@ where
                         subpath_state = SubpathState::InSubpath;
                     }
 
+                    SubpathState::InSubpath if starts_after_moveto[i] => {
+                        assert!(i > 0);
+
+                        // A moveto started a new subpath, so the last vertex of the
+                        // previous subpath and the first one of this subpath are
+                        // distinct vertices, and both get a mid marker.
+                        let marker_bbox = emit_marker(
+                            &segments[i - 1],
+                            MarkerEndpoint::End,
+                            MarkerType::Middle,
+                            end_angle(i - 1),
+                            emit_fn,
+                        )?;
+                        bbox.insert(&marker_bbox);
+
+                        let marker_bbox = emit_marker(
+                            segment,
+                            MarkerEndpoint::Start,
+                            MarkerType::Middle,
+                            start_angle(i),
+                            emit_fn,
+                        )?;
+                        bbox.insert(&marker_bbox);
+                    }
+
                     SubpathState::InSubpath => {
                         assert!(i > 0);
 
@@ -776,28 +974,14 @@ where
 
     // Finally, render the last point
     if !segments.is_empty() {
-        let segment = &segments[segments.len() - 1];
+        let last = segments.len() - 1;
+        let segment = &segments[last];
         if let Segment::LineOrCurve { .. } = *segment {
-            let (_, incoming_vx, incoming_vy) =
-                segments.find_incoming_directionality_backwards(segments.len() - 1);
-
-            let angle = {
-                if let PathCommand::ClosePath = builder.get_path_commands()[segments.len()] {
-                    let (_, outgoing_vx, outgoing_vy) =
-                        segments.find_outgoing_directionality_forwards(0);
-                    let incoming = Angle::from_vector(incoming_vx, incoming_vy);
-                    let outgoing = Angle::from_vector(outgoing_vx, outgoing_vy);
-                    incoming.bisect(outgoing)
-                } else {
-                    Angle::from_vector(incoming_vx, incoming_vy)
-                }
-            };
-
             let marker_bbox = emit_marker(
                 segment,
                 MarkerEndpoint::End,
                 MarkerType::End,
-                angle,
+                end_angle(last),
                 emit_fn,
             )?;
             bbox.insert(&marker_bbox);
@@ -849,6 +1033,10 @@ mod parser_tests {
     #[test]
     fn parses_marker_orient() {
         assert_eq!(MarkerOrient::parse_str("auto"), Ok(MarkerOrient::Auto));
+        assert_eq!(
+            MarkerOrient::parse_str("auto-start-reverse"),
+            Ok(MarkerOrient::AutoStartReverse)
+        );
 
         assert_eq!(
             MarkerOrient::parse_str("0"),
@@ -1188,7 +1376,7 @@ mod marker_tests {
         assert_eq!(
             v,
             vec![
-                (MarkerType::Start, 0.0, 0.0, Angle::new(0.0)),
+                (MarkerType::Start, 0.0, 0.0, Angle::from_vector(1.0, -1.0)),
                 (MarkerType::Middle, 1.0, 0.0, Angle::from_vector(1.0, 1.0)),
                 (MarkerType::Middle, 1.0, 1.0, Angle::from_vector(-1.0, 1.0)),
                 (MarkerType::Middle, 0.0, 1.0, Angle::from_vector(-1.0, -1.0)),
@@ -1196,4 +1384,52 @@ mod marker_tests {
             ]
         );
     }
+
+    #[test]
+    fn emits_mid_markers_at_both_vertices_of_a_moveto() {
+        let mut builder = PathBuilder::new();
+        builder.move_to(0.0, 0.0);
+        builder.line_to(0.0, 1.0);
+        builder.move_to(5.0, 5.0);
+        builder.line_to(6.0, 5.0);
+        builder.line_to(6.0, 6.0);
+        builder.close_path();
+
+        let mut v = Vec::new();
+
+        assert!(emit_markers_for_path_builder(
+            &builder,
+            BoundingBox::new(&cairo::Matrix::identity()),
+            &mut |marker_type: MarkerType,
+                  x: f64,
+                  y: f64,
+                  computed_angle: Angle|
+             -> Result<BoundingBox, RenderingError> {
+                v.push((marker_type, x, y, computed_angle));
+                Ok(BoundingBox::new(&cairo::Matrix::identity()))
+            }
+        )
+        .is_ok());
+
+        // The second subpath is closed, so its first vertex is oriented between
+        // the closing segment and the first segment.
+        let closing_vertex = Angle::from_vector(-1.0, -1.0).bisect(Angle::from_vector(1.0, 0.0));
+
+        assert_eq!(
+            v,
+            vec![
+                (MarkerType::Start, 0.0, 0.0, Angle::from_vector(0.0, 1.0)),
+                (MarkerType::Middle, 0.0, 1.0, Angle::from_vector(0.0, 1.0)),
+                (MarkerType::Middle, 5.0, 5.0, closing_vertex),
+                (MarkerType::Middle, 6.0, 5.0, Angle::from_vector(1.0, 1.0)),
+                (
+                    MarkerType::Middle,
+                    6.0,
+                    6.0,
+                    Angle::from_vector(0.0, 1.0).bisect(Angle::from_vector(-1.0, -1.0))
+                ),
+                (MarkerType::End, 5.0, 5.0, closing_vertex),
+            ]
+        );
+    }
 }
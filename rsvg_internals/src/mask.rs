@@ -9,7 +9,8 @@ use crate::length::*;
 use crate::node::{CascadedValues, NodeDraw, NodeResult, NodeTrait, RsvgNode};
 use crate::parsers::{Parse, ParseValue};
 use crate::property_bag::PropertyBag;
-use crate::property_defs::Opacity;
+use crate::property_defs::{MaskType, Opacity};
+use crate::rect::RectangleExt;
 use crate::surface_utils::{shared_surface::SharedImageSurface, shared_surface::SurfaceType};
 
 coord_units!(MaskUnits, CoordUnits::ObjectBoundingBox);
@@ -48,13 +49,27 @@ impl Mask {
         draw_ctx: &mut DrawingCtx,
         bbox: &BoundingBox,
     ) -> Result<Option<cairo::ImageSurface>, RenderingError> {
-        if bbox.rect.is_none() {
+        let mask_units = CoordUnits::from(self.units);
+        let content_units = CoordUnits::from(self.content_units);
+
+        let bbox_rect = match bbox.rect {
             // The node being masked is empty / doesn't have a
             // bounding box, so there's nothing to mask!
-            return Ok(None);
-        }
+            None => return Ok(None),
+
+            // With a bounding box of zero width or height, objectBoundingBox
+            // units yield an empty mask, so the node does not get rendered.
+            Some(r)
+                if r.is_empty()
+                    && (mask_units == CoordUnits::ObjectBoundingBox
+                        || content_units == CoordUnits::ObjectBoundingBox) =>
+            {
+                return Ok(None);
+            }
+
+            Some(r) => r,
+        };
 
-        let bbox_rect = bbox.rect.as_ref().unwrap();
         let (bb_x, bb_y) = (bbox_rect.x, bbox_rect.y);
         let (bb_w, bb_h) = (bbox_rect.width, bbox_rect.height);
 
@@ -63,9 +78,6 @@ impl Mask {
 
         let mask_content_surface = draw_ctx.create_surface_for_toplevel_viewport()?;
 
-        let mask_units = CoordUnits::from(self.units);
-        let content_units = CoordUnits::from(self.content_units);
-
         let (x, y, w, h) = {
             let params = if mask_units == CoordUnits::ObjectBoundingBox {
                 draw_ctx.push_view_box(1.0, 1.0)
@@ -120,10 +132,15 @@ impl Mask {
         }?;
 
         let Opacity(opacity) = values.opacity;
+        let opacity = u8::from(opacity);
+
+        let mask_content = SharedImageSurface::new(mask_content_surface, SurfaceType::SRgb)?;
 
-        let mask = SharedImageSurface::new(mask_content_surface, SurfaceType::SRgb)?
-            .to_mask(u8::from(opacity))?
-            .into_image_surface()?;
+        let mask = match values.mask_type {
+            MaskType::Luminance => mask_content.to_mask(opacity)?,
+            MaskType::Alpha => mask_content.to_alpha_mask(opacity)?,
+        }
+        .into_image_surface()?;
 
         Ok(Some(mask))
     }
@@ -0,0 +1,535 @@
+//! CSS media queries, for `@media` rules and the `media` attribute of `<style>`.
+//!
+//! Media queries are evaluated against the `MediaFeatures` that the application passes
+//! when loading a document; since the CSS cascade happens at loading time, the rules
+//! whose media queries don't match are simply left out of the stylesheets.
+//!
+//! https://www.w3.org/TR/mediaqueries-4/
+
+use cssparser::{CowRcStr, Delimiter, ParseError, Parser, Token};
+
+use crate::error::ValueErrorKind;
+use crate::parsers::Parse;
+
+/// The user's preference for light or dark themes, for the `prefers-color-scheme`
+/// media feature
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl Default for ColorScheme {
+    fn default() -> ColorScheme {
+        ColorScheme::Light
+    }
+}
+
+/// The environment against which media queries are evaluated
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MediaFeatures {
+    /// Width of the viewport in CSS pixels, for the `width` and `orientation` features
+    ///
+    /// If this is `None`, queries on the width of the viewport don't match.
+    pub width: Option<f64>,
+
+    /// Height of the viewport in CSS pixels, for the `height` and `orientation` features
+    ///
+    /// If this is `None`, queries on the height of the viewport don't match.
+    pub height: Option<f64>,
+
+    /// Resolution of the output device in dots per inch, for the `resolution` feature
+    pub resolution: f64,
+
+    /// Value of the `prefers-color-scheme` feature
+    pub color_scheme: ColorScheme,
+}
+
+impl Default for MediaFeatures {
+    fn default() -> MediaFeatures {
+        MediaFeatures {
+            width: None,
+            height: None,
+            resolution: 96.0,
+            color_scheme: ColorScheme::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MediaType {
+    All,
+    Screen,
+    Other,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Feature {
+    Width,
+    Height,
+    Resolution,
+    Orientation,
+    PrefersColorScheme,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+/// Value of a media feature in a query; lengths are in CSS pixels and resolutions
+/// in dots per inch.
+#[derive(Debug, Clone, PartialEq)]
+enum FeatureValue {
+    Length(f64),
+    Resolution(f64),
+    Ident(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MediaCondition {
+    /// A feature by itself like `(width)`, or compared to a value
+    Feature(Feature, Option<(Comparison, FeatureValue)>),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MediaQuery {
+    negated: bool,
+    media_type: MediaType,
+    condition: Option<MediaCondition>,
+}
+
+/// A comma-separated list of media queries, which matches if any of them matches
+///
+/// Invalid queries never match, but they don't invalidate the rest of the list.  An
+/// empty list matches everything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQueryList(Vec<MediaQuery>);
+
+type MediaResult<'i, T> = Result<T, ParseError<'i, ()>>;
+
+impl MediaQueryList {
+    /// Parses a media query list until the end of the `parser`'s input.
+    pub fn parse_forgiving(parser: &mut Parser<'_, '_>) -> MediaQueryList {
+        let mut queries = Vec::new();
+
+        if parser.is_exhausted() {
+            return MediaQueryList(queries);
+        }
+
+        loop {
+            let query = parser
+                .parse_until_before(Delimiter::Comma, |p| {
+                    let query = parse_query(p)?;
+                    p.expect_exhausted()?;
+                    Ok(query)
+                })
+                .unwrap_or(MediaQuery {
+                    negated: true,
+                    media_type: MediaType::All,
+                    condition: None,
+                });
+
+            queries.push(query);
+
+            if parser.next().is_err() {
+                break;
+            }
+        }
+
+        MediaQueryList(queries)
+    }
+
+    pub fn matches(&self, features: &MediaFeatures) -> bool {
+        self.0.is_empty() || self.0.iter().any(|q| q.matches(features))
+    }
+}
+
+impl Parse for MediaQueryList {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<MediaQueryList, ValueErrorKind> {
+        Ok(MediaQueryList::parse_forgiving(parser))
+    }
+}
+
+fn parse_query<'i>(parser: &mut Parser<'i, '_>) -> MediaResult<'i, MediaQuery> {
+    if let Ok(condition) = parser.try_parse(|p| parse_condition(p, true)) {
+        return Ok(MediaQuery {
+            negated: false,
+            media_type: MediaType::All,
+            condition: Some(condition),
+        });
+    }
+
+    let negated = parser.try_parse(|p| p.expect_ident_matching("not")).is_ok();
+
+    if !negated {
+        let _ = parser.try_parse(|p| p.expect_ident_matching("only"));
+    }
+
+    let location = parser.current_source_location();
+    let ident = parser.expect_ident_cloned()?;
+
+    let media_type = match ident.to_ascii_lowercase().as_str() {
+        "all" => MediaType::All,
+        "screen" => MediaType::Screen,
+        "not" | "only" | "and" | "or" => {
+            return Err(location.new_unexpected_token_error(Token::Ident(ident)))
+        }
+        _ => MediaType::Other,
+    };
+
+    let condition = if parser.try_parse(|p| p.expect_ident_matching("and")).is_ok() {
+        Some(parse_condition(parser, false)?)
+    } else {
+        None
+    };
+
+    Ok(MediaQuery {
+        negated,
+        media_type,
+        condition,
+    })
+}
+
+fn parse_condition<'i>(
+    parser: &mut Parser<'i, '_>,
+    allow_or: bool,
+) -> MediaResult<'i, MediaCondition> {
+    if parser.try_parse(|p| p.expect_ident_matching("not")).is_ok() {
+        return Ok(MediaCondition::Not(Box::new(parse_in_parens(parser)?)));
+    }
+
+    let mut conditions = vec![parse_in_parens(parser)?];
+    let mut is_and = None;
+
+    loop {
+        let location = parser.current_source_location();
+
+        let keyword = match parser.try_parse(|p| p.expect_ident_cloned()) {
+            Ok(keyword) => keyword,
+            Err(_) => break,
+        };
+
+        let and = if keyword.eq_ignore_ascii_case("and") {
+            true
+        } else if allow_or && keyword.eq_ignore_ascii_case("or") {
+            false
+        } else {
+            return Err(location.new_unexpected_token_error(Token::Ident(keyword)));
+        };
+
+        // "and" and "or" cannot be mixed without parentheses
+        if is_and.map_or(false, |is_and| is_and != and) {
+            return Err(location.new_unexpected_token_error(Token::Ident(keyword)));
+        }
+
+        is_and = Some(and);
+        conditions.push(parse_in_parens(parser)?);
+    }
+
+    Ok(match is_and {
+        None => conditions.pop().unwrap(),
+        Some(true) => MediaCondition::And(conditions),
+        Some(false) => MediaCondition::Or(conditions),
+    })
+}
+
+fn parse_in_parens<'i>(parser: &mut Parser<'i, '_>) -> MediaResult<'i, MediaCondition> {
+    parser.expect_parenthesis_block()?;
+
+    parser.parse_nested_block(|p| {
+        if let Ok(condition) = p.try_parse(|p| parse_condition(p, true)) {
+            return Ok(condition);
+        }
+
+        parse_feature(p)
+    })
+}
+
+fn parse_feature<'i>(parser: &mut Parser<'i, '_>) -> MediaResult<'i, MediaCondition> {
+    let location = parser.current_source_location();
+    let name = parser.expect_ident_cloned()?;
+
+    let (prefix, feature_name) = if let Some(rest) = strip_prefix(&name, "min-") {
+        (Some(Comparison::GreaterOrEqual), rest)
+    } else if let Some(rest) = strip_prefix(&name, "max-") {
+        (Some(Comparison::LessOrEqual), rest)
+    } else {
+        (None, name.as_ref())
+    };
+
+    let feature = match feature_name.to_ascii_lowercase().as_str() {
+        "width" => Feature::Width,
+        "height" => Feature::Height,
+        "resolution" => Feature::Resolution,
+        "orientation" => Feature::Orientation,
+        "prefers-color-scheme" => Feature::PrefersColorScheme,
+        _ => return Err(location.new_unexpected_token_error(Token::Ident(name.clone()))),
+    };
+
+    let is_range = match feature {
+        Feature::Width | Feature::Height | Feature::Resolution => true,
+        Feature::Orientation | Feature::PrefersColorScheme => false,
+    };
+
+    if parser.is_exhausted() {
+        if prefix.is_some() {
+            return Err(location.new_unexpected_token_error(Token::Ident(name)));
+        }
+
+        return Ok(MediaCondition::Feature(feature, None));
+    }
+
+    let location = parser.current_source_location();
+
+    let comparison = match parser.next()?.clone() {
+        Token::Colon => prefix.unwrap_or(Comparison::Equal),
+
+        Token::Delim(c) if prefix.is_none() && is_range => {
+            let or_equal = parser.try_parse(|p| p.expect_delim('=')).is_ok();
+
+            match (c, or_equal) {
+                ('<', false) => Comparison::Less,
+                ('<', true) => Comparison::LessOrEqual,
+                ('>', false) => Comparison::Greater,
+                ('>', true) => Comparison::GreaterOrEqual,
+                ('=', false) => Comparison::Equal,
+                _ => return Err(location.new_unexpected_token_error(Token::Delim(c))),
+            }
+        }
+
+        t => return Err(location.new_unexpected_token_error(t)),
+    };
+
+    if comparison != Comparison::Equal && !is_range {
+        return Err(location.new_unexpected_token_error(Token::Ident(name)));
+    }
+
+    let location = parser.current_source_location();
+    let value = parse_feature_value(parser)?;
+
+    let valid = match (feature, &value) {
+        (Feature::Width, FeatureValue::Length(_)) => true,
+        (Feature::Height, FeatureValue::Length(_)) => true,
+        (Feature::Resolution, FeatureValue::Resolution(_)) => true,
+        (Feature::Orientation, FeatureValue::Ident(v)) => v == "portrait" || v == "landscape",
+        (Feature::PrefersColorScheme, FeatureValue::Ident(v)) => v == "light" || v == "dark",
+        _ => false,
+    };
+
+    if !valid {
+        return Err(location.new_unexpected_token_error(Token::Ident(name)));
+    }
+
+    Ok(MediaCondition::Feature(feature, Some((comparison, value))))
+}
+
+fn strip_prefix<'a>(name: &'a CowRcStr<'_>, prefix: &str) -> Option<&'a str> {
+    if name.len() > prefix.len() && name[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(&name[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+fn parse_feature_value<'i>(parser: &mut Parser<'i, '_>) -> MediaResult<'i, FeatureValue> {
+    let location = parser.current_source_location();
+
+    match parser.next()?.clone() {
+        // Unitless zero is a valid length
+        Token::Number { value, .. } if value == 0.0 => Ok(FeatureValue::Length(0.0)),
+
+        Token::Ident(ident) => Ok(FeatureValue::Ident(ident.to_ascii_lowercase())),
+
+        Token::Dimension {
+            value, ref unit, ..
+        } => {
+            let value = f64::from(value);
+
+            // Font-relative lengths are relative to the initial font size, 16px
+            match unit.to_ascii_lowercase().as_str() {
+                "px" => Ok(FeatureValue::Length(value)),
+                "in" => Ok(FeatureValue::Length(value * 96.0)),
+                "cm" => Ok(FeatureValue::Length(value * 96.0 / 2.54)),
+                "mm" => Ok(FeatureValue::Length(value * 96.0 / 25.4)),
+                "q" => Ok(FeatureValue::Length(value * 96.0 / 101.6)),
+                "pt" => Ok(FeatureValue::Length(value * 96.0 / 72.0)),
+                "pc" => Ok(FeatureValue::Length(value * 16.0)),
+                "em" | "rem" => Ok(FeatureValue::Length(value * 16.0)),
+                "dpi" => Ok(FeatureValue::Resolution(value)),
+                "dpcm" => Ok(FeatureValue::Resolution(value * 2.54)),
+                "dppx" | "x" => Ok(FeatureValue::Resolution(value * 96.0)),
+                _ => Err(location.new_unexpected_token_error(Token::Ident(unit.clone()))),
+            }
+        }
+
+        t => Err(location.new_unexpected_token_error(t)),
+    }
+}
+
+impl MediaQuery {
+    fn matches(&self, features: &MediaFeatures) -> bool {
+        // librsvg renders to screens or to images, never to other media
+        let type_matches = match self.media_type {
+            MediaType::All | MediaType::Screen => true,
+            MediaType::Other => false,
+        };
+
+        let matches = type_matches
+            && self
+                .condition
+                .as_ref()
+                .map_or(true, |c| c.matches(features));
+
+        matches != self.negated
+    }
+}
+
+impl MediaCondition {
+    fn matches(&self, features: &MediaFeatures) -> bool {
+        match *self {
+            MediaCondition::Feature(feature, ref comparison) => {
+                feature_matches(feature, comparison.as_ref(), features)
+            }
+            MediaCondition::Not(ref c) => !c.matches(features),
+            MediaCondition::And(ref cs) => cs.iter().all(|c| c.matches(features)),
+            MediaCondition::Or(ref cs) => cs.iter().any(|c| c.matches(features)),
+        }
+    }
+}
+
+fn feature_matches(
+    feature: Feature,
+    comparison: Option<&(Comparison, FeatureValue)>,
+    features: &MediaFeatures,
+) -> bool {
+    let compare = |actual: f64, comparison: Comparison, expected: f64| match comparison {
+        Comparison::Less => actual < expected,
+        Comparison::LessOrEqual => actual <= expected,
+        Comparison::Equal => actual == expected,
+        Comparison::GreaterOrEqual => actual >= expected,
+        Comparison::Greater => actual > expected,
+    };
+
+    let orientation = match (features.width, features.height) {
+        (Some(w), Some(h)) if h >= w => Some("portrait"),
+        (Some(_), Some(_)) => Some("landscape"),
+        _ => None,
+    };
+
+    let color_scheme = match features.color_scheme {
+        ColorScheme::Light => "light",
+        ColorScheme::Dark => "dark",
+    };
+
+    match (feature, comparison) {
+        // In a boolean context, features match unless they are zero
+        (Feature::Width, None) => features.width.map_or(false, |w| w != 0.0),
+        (Feature::Height, None) => features.height.map_or(false, |h| h != 0.0),
+        (Feature::Resolution, None) => features.resolution != 0.0,
+        (Feature::Orientation, None) => orientation.is_some(),
+        (Feature::PrefersColorScheme, None) => true,
+
+        (Feature::Width, Some(&(c, FeatureValue::Length(v)))) => {
+            features.width.map_or(false, |w| compare(w, c, v))
+        }
+
+        (Feature::Height, Some(&(c, FeatureValue::Length(v)))) => {
+            features.height.map_or(false, |h| compare(h, c, v))
+        }
+
+        (Feature::Resolution, Some(&(c, FeatureValue::Resolution(v)))) => {
+            compare(features.resolution, c, v)
+        }
+
+        (Feature::Orientation, Some((_, FeatureValue::Ident(v)))) => {
+            orientation == Some(v.as_str())
+        }
+
+        (Feature::PrefersColorScheme, Some((_, FeatureValue::Ident(v)))) => {
+            v.as_str() == color_scheme
+        }
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, features: &MediaFeatures) -> bool {
+        MediaQueryList::parse_str(query).unwrap().matches(features)
+    }
+
+    #[test]
+    fn evaluates_media_types() {
+        let features = MediaFeatures::default();
+
+        assert!(matches("", &features));
+        assert!(matches("all", &features));
+        assert!(matches("only screen", &features));
+        assert!(!matches("print", &features));
+        assert!(matches("not print", &features));
+        assert!(matches("print, screen", &features));
+    }
+
+    #[test]
+    fn evaluates_media_features() {
+        let features = MediaFeatures {
+            width: Some(200.0),
+            height: Some(100.0),
+            resolution: 192.0,
+            color_scheme: ColorScheme::Dark,
+        };
+
+        assert!(matches("(prefers-color-scheme: dark)", &features));
+        assert!(!matches("(prefers-color-scheme: light)", &features));
+        assert!(matches(
+            "screen and (prefers-color-scheme: dark)",
+            &features
+        ));
+        assert!(matches(
+            "(min-width: 200px) and (max-height: 1in)",
+            &features
+        ));
+        assert!(!matches("(min-width: 201px)", &features));
+        assert!(matches("(width > 100px) and (height <= 100px)", &features));
+        assert!(matches("(orientation: landscape)", &features));
+        assert!(matches("(min-resolution: 2dppx)", &features));
+        assert!(matches("not (resolution: 96dpi)", &features));
+        assert!(matches("(width < 100px) or (height = 100px)", &features));
+        assert!(matches("(width)", &features));
+    }
+
+    #[test]
+    fn unknown_viewport_size_does_not_match() {
+        let features = MediaFeatures::default();
+
+        assert!(!matches("(min-width: 0)", &features));
+        assert!(!matches("(orientation: portrait)", &features));
+        assert!(!matches("(orientation: landscape)", &features));
+        assert!(matches("(prefers-color-scheme: light)", &features));
+    }
+
+    #[test]
+    fn invalid_queries_do_not_match() {
+        let features = MediaFeatures::default();
+
+        assert!(!matches("(foo: bar)", &features));
+        assert!(!matches("(prefers-color-scheme: blue)", &features));
+        assert!(!matches("(width: red)", &features));
+        assert!(!matches("screen and (width) or (height)", &features));
+        assert!(!matches("not (color-gamut: p3)", &features));
+
+        // other queries in the list are still valid
+        assert!(matches("(foo), screen", &features));
+    }
+}
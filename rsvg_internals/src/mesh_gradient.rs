@@ -0,0 +1,638 @@
+//! The `meshgradient` element and its `meshrow` and `meshpatch` children.
+//!
+//! A mesh gradient is a grid of Coons patches.  Each patch has four curved
+//! edges and a color at each corner; patches share edges and corner colors
+//! with their neighbors to the left and above, so only the first patch in the
+//! mesh specifies all four of its edges.  See
+//! https://www.w3.org/TR/2016/CR-SVG2-20160915/pservers.html#MeshGradients
+
+use cairo;
+use markup5ever::{expanded_name, local_name, namespace_url, ns};
+
+use crate::allowed_url::Fragment;
+use crate::bbox::*;
+use crate::coord_units::CoordUnits;
+use crate::drawing_ctx::DrawingCtx;
+use crate::error::{AttributeResultExt, RenderingError, ValueErrorKind};
+use crate::gradient::Stop;
+use crate::length::*;
+use crate::node::*;
+use crate::paint_server::{AsPaintSource, PaintSource, Resolve};
+use crate::parsers::ParseValue;
+use crate::path_builder::{CubicBezierCurve, PathBuilder, PathCommand};
+use crate::path_parser::parse_path_into_builder;
+use crate::properties::ComputedValues;
+use crate::property_bag::PropertyBag;
+use crate::property_defs::StopColor;
+use crate::rect::RectangleExt;
+use crate::sanitize;
+use crate::unit_interval::UnitInterval;
+
+coord_units!(MeshGradientUnits, CoordUnits::ObjectBoundingBox);
+
+type Point = (f64, f64);
+
+/// A cubic Bézier edge of a patch, as its start point, two control points, and end point
+type Edge = [Point; 4];
+
+/// Unpremultiplied RGBA, with components in [0, 1]
+type Rgba = [f64; 4];
+
+/// The `path` attribute of a `<stop>` inside a `<meshpatch>`
+///
+/// This is a single `l`, `L`, `c`, or `C` path command, which describes
+/// one edge of a patch.  The lowercase commands are relative to the point
+/// where the edge starts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshEdge {
+    absolute: bool,
+    segment: Segment,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Segment {
+    Line(Point),
+    Curve(CubicBezierCurve),
+}
+
+impl MeshEdge {
+    pub fn parse_path_data(s: &str) -> Result<MeshEdge, ValueErrorKind> {
+        let s = s.trim_start();
+
+        let absolute = match s.chars().next() {
+            Some('l') | Some('c') => false,
+            Some('L') | Some('C') => true,
+            _ => {
+                return Err(ValueErrorKind::parse_error(
+                    "expected 'l' | 'L' | 'c' | 'C'",
+                ))
+            }
+        };
+
+        let mut builder = PathBuilder::new();
+        parse_path_into_builder(&format!("M 0 0 {}", s), &mut builder)
+            .map_err(|_| ValueErrorKind::parse_error("invalid path data for mesh edge"))?;
+
+        let segment = match *builder.get_path_commands() {
+            [PathCommand::MoveTo(..), PathCommand::LineTo(x, y)] => Segment::Line((x, y)),
+            [PathCommand::MoveTo(..), PathCommand::CurveTo(curve)] => Segment::Curve(curve),
+
+            _ => {
+                return Err(ValueErrorKind::parse_error(
+                    "mesh edge must be a single path segment",
+                ))
+            }
+        };
+
+        Ok(MeshEdge { absolute, segment })
+    }
+
+    fn to_edge(self, start: Point) -> Edge {
+        let (x0, y0) = start;
+
+        let offset = |(x, y): Point| {
+            if self.absolute {
+                (x, y)
+            } else {
+                (x0 + x, y0 + y)
+            }
+        };
+
+        match self.segment {
+            Segment::Line(to) => {
+                let (x1, y1) = offset(to);
+                let (dx, dy) = (x1 - x0, y1 - y0);
+
+                [
+                    start,
+                    (x0 + dx / 3.0, y0 + dy / 3.0),
+                    (x0 + dx * 2.0 / 3.0, y0 + dy * 2.0 / 3.0),
+                    (x1, y1),
+                ]
+            }
+
+            Segment::Curve(CubicBezierCurve { pt1, pt2, to }) => {
+                [start, offset(pt1), offset(pt2), offset(to)]
+            }
+        }
+    }
+}
+
+fn reverse_edge(edge: &Edge) -> Edge {
+    [edge[3], edge[2], edge[1], edge[0]]
+}
+
+/// A fully specified Coons patch
+#[derive(Debug, Clone, PartialEq)]
+struct Patch {
+    /// Edges in order: top, right, bottom, left; they go around the patch clockwise
+    edges: [Edge; 4],
+
+    /// Colors in order: top-left, top-right, bottom-right, bottom-left
+    colors: [Rgba; 4],
+}
+
+/// What a `<stop>` inside a `<meshpatch>` specifies: the edge that
+/// starts at a corner, and the color of that corner.
+type PatchStop = (Option<MeshEdge>, Rgba);
+
+/// Computes the patches of a mesh from the stops in each `<meshpatch>`
+///
+/// `rows` has the stops of each patch, row by row.  Returns `None` if a patch
+/// does not have enough stops to define its edges.
+fn build_patches(x: f64, y: f64, rows: &[Vec<Vec<PatchStop>>]) -> Option<Vec<Vec<Patch>>> {
+    let mut result: Vec<Vec<Patch>> = Vec::new();
+
+    for (r, row) in rows.iter().enumerate() {
+        let mut patches: Vec<Patch> = Vec::new();
+
+        for (c, stops) in row.iter().enumerate() {
+            let above = if r > 0 { result[r - 1].get(c) } else { None };
+            let left = if c > 0 { patches.last() } else { None };
+
+            if r > 0 && above.is_none() {
+                // Rows can't be longer than the first one
+                return None;
+            }
+
+            let mut edges = [[(0.0, 0.0); 4]; 4];
+            let mut colors = [[0.0; 4]; 4];
+
+            // Edges and corners shared with neighbors come from them
+
+            if let Some(above) = above {
+                edges[0] = reverse_edge(&above.edges[2]);
+                colors[0] = above.colors[3];
+                colors[1] = above.colors[2];
+            }
+
+            if let Some(left) = left {
+                edges[3] = reverse_edge(&left.edges[1]);
+                colors[3] = left.colors[2];
+
+                if above.is_none() {
+                    colors[0] = left.colors[1];
+                }
+            }
+
+            // The rest come from the stops, which start at the first edge
+            // that is not shared.
+
+            let first = if above.is_some() { 1 } else { 0 };
+            let last = if left.is_some() { 3 } else { 4 };
+
+            if stops.len() < last - first {
+                return None;
+            }
+
+            let mut start = match (above, left) {
+                (Some(_), _) => edges[0][3],
+                (None, Some(left)) => left.edges[0][3],
+                (None, None) => (x, y),
+            };
+
+            for (side, &(edge, color)) in (first..last).zip(stops.iter()) {
+                edges[side] = edge?.to_edge(start);
+                start = edges[side][3];
+
+                let shared = match side {
+                    0 => left.is_some(),
+                    1 => above.is_some(),
+                    _ => false,
+                };
+
+                if !shared {
+                    colors[side] = color;
+                }
+            }
+
+            patches.push(Patch { edges, colors });
+        }
+
+        result.push(patches);
+    }
+
+    Some(result)
+}
+
+#[derive(Clone, Default)]
+struct Common {
+    units: Option<MeshGradientUnits>,
+    affine: Option<cairo::Matrix>,
+    x: Option<Length<Horizontal>>,
+    y: Option<Length<Vertical>>,
+}
+
+/// Node for the `<meshgradient>` element
+#[derive(Default)]
+pub struct MeshGradient {
+    common: Common,
+    fallback: Option<Fragment>,
+}
+
+/// Node for the `<meshrow>` element
+#[derive(Default)]
+pub struct MeshRow;
+
+/// Node for the `<meshpatch>` element
+#[derive(Default)]
+pub struct MeshPatch;
+
+impl NodeTrait for MeshRow {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, _: &PropertyBag<'_>) -> NodeResult {
+        Ok(())
+    }
+}
+
+impl NodeTrait for MeshPatch {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, _: &PropertyBag<'_>) -> NodeResult {
+        Ok(())
+    }
+}
+
+impl NodeTrait for MeshGradient {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(svg "gradientUnits") => self.common.units = Some(attr.parse(value)?),
+                expanded_name!(svg "gradientTransform") => {
+                    self.common.affine = Some(attr.parse(value)?)
+                }
+                expanded_name!(svg "x") => self.common.x = Some(attr.parse(value)?),
+                expanded_name!(svg "y") => self.common.y = Some(attr.parse(value)?),
+                expanded_name!(xlink "href") => {
+                    self.fallback = Some(Fragment::parse(value).attribute(attr)?);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct ResolvedMeshGradient {
+    units: MeshGradientUnits,
+    affine: cairo::Matrix,
+    x: Length<Horizontal>,
+    y: Length<Vertical>,
+
+    // The first node in the chain of fallbacks that has <meshrow> children
+    rows: Option<RsvgWeakNode>,
+}
+
+fn has_rows(node: &RsvgNode) -> bool {
+    node.children()
+        .any(|child| child.borrow().get_type() == NodeType::MeshRow)
+}
+
+impl PaintSource for MeshGradient {
+    type Unresolved = UnresolvedMeshGradient;
+}
+
+/// The attributes of a mesh gradient while its fallbacks are being resolved
+pub struct UnresolvedMeshGradient {
+    common: Common,
+
+    // The first node with <meshrow> children, or Some(None) if no node in the
+    // chain of fallbacks has them
+    rows: Option<Option<RsvgWeakNode>>,
+}
+
+impl Resolve for UnresolvedMeshGradient {
+    type Resolved = ResolvedMeshGradient;
+
+    const FALLBACK_TYPES: &'static [NodeType] = &[NodeType::MeshGradient];
+
+    fn from_node(node: &RsvgNode) -> (UnresolvedMeshGradient, Option<Fragment>) {
+        let borrowed_node = node.borrow();
+        let mesh = borrowed_node.get_impl::<MeshGradient>();
+
+        let rows = if has_rows(node) {
+            Some(Some(node.downgrade()))
+        } else {
+            None
+        };
+
+        let unresolved = UnresolvedMeshGradient {
+            common: mesh.common.clone(),
+            rows,
+        };
+
+        (unresolved, mesh.fallback.clone())
+    }
+
+    fn is_resolved(&self) -> bool {
+        self.common.units.is_some()
+            && self.common.affine.is_some()
+            && self.common.x.is_some()
+            && self.common.y.is_some()
+            && self.rows.is_some()
+    }
+
+    fn resolve_from_fallback(&self, fallback: &UnresolvedMeshGradient) -> UnresolvedMeshGradient {
+        UnresolvedMeshGradient {
+            common: Common {
+                units: self.common.units.or(fallback.common.units),
+                affine: self.common.affine.or(fallback.common.affine),
+                x: self.common.x.or(fallback.common.x),
+                y: self.common.y.or(fallback.common.y),
+            },
+            rows: self.rows.clone().or_else(|| fallback.rows.clone()),
+        }
+    }
+
+    fn resolve_from_defaults(&self) -> UnresolvedMeshGradient {
+        UnresolvedMeshGradient {
+            common: Common {
+                units: self.common.units.or_else(|| Some(Default::default())),
+                affine: self
+                    .common
+                    .affine
+                    .or_else(|| Some(cairo::Matrix::identity())),
+                x: self.common.x.or_else(|| Some(Default::default())),
+                y: self.common.y.or_else(|| Some(Default::default())),
+            },
+            rows: self.rows.clone().or(Some(None)),
+        }
+    }
+
+    fn to_resolved(self, node: &RsvgNode) -> ResolvedMeshGradient {
+        assert!(self.is_resolved());
+
+        // As for other paint servers, a transform on the element itself is
+        // applied after the gradientTransform.
+        let affine =
+            cairo::Matrix::multiply(&self.common.affine.unwrap(), &node.borrow().get_transform());
+
+        ResolvedMeshGradient {
+            units: self.common.units.unwrap(),
+            affine,
+            x: self.common.x.unwrap(),
+            y: self.common.y.unwrap(),
+            rows: self.rows.unwrap(),
+        }
+    }
+}
+
+/// Collects the stops of each `<meshpatch>` in each `<meshrow>` of a mesh gradient
+fn collect_stops(node: &RsvgNode, opacity: UnitInterval) -> Vec<Vec<Vec<PatchStop>>> {
+    let UnitInterval(o) = opacity;
+
+    node.children()
+        .filter(|row| row.borrow().get_type() == NodeType::MeshRow)
+        .map(|row| {
+            row.children()
+                .filter(|patch| patch.borrow().get_type() == NodeType::MeshPatch)
+                .map(|patch| {
+                    patch
+                        .children()
+                        .filter(|stop| stop.borrow().get_type() == NodeType::Stop)
+                        .map(|stop_node| {
+                            let stop = stop_node.borrow();
+
+                            let edge = if stop.is_in_error() {
+                                rsvg_log!("(not using mesh stop {} because it is in error)", stop);
+                                None
+                            } else {
+                                stop.get_impl::<Stop>().mesh_edge()
+                            };
+
+                            let cascaded = CascadedValues::new_from_node(&stop_node);
+                            let values = cascaded.get();
+                            let rgba = match values.stop_color {
+                                StopColor(cssparser::Color::CurrentColor) => values.color.0,
+                                StopColor(cssparser::Color::RGBA(ref rgba)) => *rgba,
+                            };
+                            let UnitInterval(stop_opacity) = values.stop_opacity.0;
+
+                            (
+                                edge,
+                                [
+                                    f64::from(rgba.red_f32()),
+                                    f64::from(rgba.green_f32()),
+                                    f64::from(rgba.blue_f32()),
+                                    f64::from(rgba.alpha_f32()) * stop_opacity * o,
+                                ],
+                            )
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl AsPaintSource for ResolvedMeshGradient {
+    fn set_as_paint_source(
+        self,
+        values: &ComputedValues,
+        draw_ctx: &mut DrawingCtx,
+        opacity: UnitInterval,
+        bbox: &BoundingBox,
+    ) -> Result<bool, RenderingError> {
+        let rows_node = match self.rows.as_ref().and_then(|weak| weak.upgrade()) {
+            Some(node) => node,
+            None => return Ok(false),
+        };
+
+        let params = if self.units == MeshGradientUnits(CoordUnits::ObjectBoundingBox) {
+            if bbox.rect.map_or(true, |r| r.is_empty()) {
+                // objectBoundingBox requires a non-empty bbox, same as for other gradients
+                return Ok(false);
+            }
+
+            draw_ctx.push_view_box(1.0, 1.0)
+        } else {
+            draw_ctx.get_view_params()
+        };
+
+        let x = self.x.normalize(values, &params);
+        let y = self.y.normalize(values, &params);
+
+        let patches = match build_patches(x, y, &collect_stops(&rows_node, opacity)) {
+            Some(patches) => patches,
+            None => {
+                rsvg_log!("mesh gradient {} has patches with missing edges", rows_node);
+                return Ok(false);
+            }
+        };
+
+        // Bicubic interpolation (type="bicubic") is not supported; Cairo's
+        // mesh patterns always interpolate colors bilinearly.
+        let mesh = cairo::Mesh::new();
+
+        for patch in patches.iter().flatten() {
+            mesh.begin_patch();
+
+            let (x0, y0) = patch.edges[0][0];
+            mesh.move_to(x0, y0);
+
+            for edge in &patch.edges {
+                mesh.curve_to(
+                    edge[1].0, edge[1].1, edge[2].0, edge[2].1, edge[3].0, edge[3].1,
+                );
+            }
+
+            let corners = [
+                cairo::MeshCorner::MeshCorner0,
+                cairo::MeshCorner::MeshCorner1,
+                cairo::MeshCorner::MeshCorner2,
+                cairo::MeshCorner::MeshCorner3,
+            ];
+
+            for (&corner, color) in corners.iter().zip(patch.colors.iter()) {
+                mesh.set_corner_color_rgba(corner, color[0], color[1], color[2], color[3]);
+            }
+
+            mesh.end_patch();
+        }
+
+        let mut affine = self.affine;
+
+        if self.units == MeshGradientUnits(CoordUnits::ObjectBoundingBox) {
+            let bbox_rect = bbox.rect.unwrap();
+            let bbox_matrix = cairo::Matrix::new(
+                bbox_rect.width,
+                0.0,
+                0.0,
+                bbox_rect.height,
+                bbox_rect.x,
+                bbox_rect.y,
+            );
+            affine = cairo::Matrix::multiply(&affine, &bbox_matrix);
+        }
+
+        if !sanitize::is_valid_matrix(&affine) {
+            rsvg_log!("(not using mesh gradient with an invalid transform)");
+            return Ok(false);
+        }
+
+        affine.invert();
+        mesh.set_matrix(affine);
+
+        let cr = draw_ctx.get_cairo_context();
+        cr.set_source(&mesh);
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mesh_edges() {
+        let edge = MeshEdge::parse_path_data("c 25,-25 75,25 100,0").unwrap();
+        assert_eq!(
+            edge.to_edge((10.0, 20.0)),
+            [(10.0, 20.0), (35.0, -5.0), (85.0, 45.0), (110.0, 20.0)]
+        );
+
+        let edge = MeshEdge::parse_path_data("l 30,60").unwrap();
+        assert_eq!(
+            edge.to_edge((10.0, 20.0)),
+            [(10.0, 20.0), (20.0, 40.0), (30.0, 60.0), (40.0, 80.0)]
+        );
+
+        let edge = MeshEdge::parse_path_data("L 40,80").unwrap();
+        assert_eq!(
+            edge.to_edge((10.0, 20.0)),
+            [(10.0, 20.0), (20.0, 40.0), (30.0, 60.0), (40.0, 80.0)]
+        );
+
+        assert!(MeshEdge::parse_path_data("M 10 10").is_err());
+        assert!(MeshEdge::parse_path_data("l 10 10 l 20 20").is_err());
+        assert!(MeshEdge::parse_path_data("a 1 1 0 0 0 10 10").is_err());
+    }
+
+    fn line(dx: f64, dy: f64) -> Option<MeshEdge> {
+        Some(MeshEdge::parse_path_data(&format!("l {} {}", dx, dy)).unwrap())
+    }
+
+    const RED: Rgba = [1.0, 0.0, 0.0, 1.0];
+    const GREEN: Rgba = [0.0, 1.0, 0.0, 1.0];
+    const BLUE: Rgba = [0.0, 0.0, 1.0, 1.0];
+    const WHITE: Rgba = [1.0, 1.0, 1.0, 1.0];
+    const BLACK: Rgba = [0.0, 0.0, 0.0, 1.0];
+    const IGNORED: Rgba = [0.5, 0.5, 0.5, 0.5];
+
+    #[test]
+    fn builds_patches_sharing_edges_and_colors() {
+        let rows = vec![
+            vec![
+                vec![
+                    (line(10.0, 0.0), RED),
+                    (line(0.0, 10.0), GREEN),
+                    (line(-10.0, 0.0), BLUE),
+                    (line(0.0, -10.0), WHITE),
+                ],
+                vec![
+                    (line(10.0, 0.0), IGNORED),
+                    (line(0.0, 10.0), BLACK),
+                    (line(-10.0, 0.0), RED),
+                ],
+            ],
+            vec![
+                vec![
+                    (line(0.0, 10.0), IGNORED),
+                    (line(-10.0, 0.0), GREEN),
+                    (line(0.0, -10.0), BLUE),
+                ],
+                vec![(line(0.0, 10.0), IGNORED), (line(-10.0, 0.0), WHITE)],
+            ],
+        ];
+
+        let patches = build_patches(0.0, 0.0, &rows).unwrap();
+
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].len(), 2);
+        assert_eq!(patches[1].len(), 2);
+
+        let corners = |p: &Patch| -> Vec<Point> { p.edges.iter().map(|e| e[0]).collect() };
+
+        assert_eq!(
+            corners(&patches[0][0]),
+            vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]
+        );
+        assert_eq!(
+            corners(&patches[0][1]),
+            vec![(10.0, 0.0), (20.0, 0.0), (20.0, 10.0), (10.0, 10.0)]
+        );
+        assert_eq!(
+            corners(&patches[1][0]),
+            vec![(0.0, 10.0), (10.0, 10.0), (10.0, 20.0), (0.0, 20.0)]
+        );
+        assert_eq!(
+            corners(&patches[1][1]),
+            vec![(10.0, 10.0), (20.0, 10.0), (20.0, 20.0), (10.0, 20.0)]
+        );
+
+        assert_eq!(patches[0][0].colors, [RED, GREEN, BLUE, WHITE]);
+        assert_eq!(patches[0][1].colors, [GREEN, BLACK, RED, BLUE]);
+        assert_eq!(patches[1][0].colors, [WHITE, BLUE, GREEN, BLUE]);
+        assert_eq!(patches[1][1].colors, [BLUE, RED, WHITE, GREEN]);
+    }
+
+    #[test]
+    fn patches_with_missing_edges_are_rejected() {
+        let rows = vec![vec![vec![
+            (line(10.0, 0.0), RED),
+            (line(0.0, 10.0), GREEN),
+            (line(-10.0, 0.0), BLUE),
+        ]]];
+
+        assert!(build_patches(0.0, 0.0, &rows).is_none());
+
+        let rows = vec![vec![vec![
+            (line(10.0, 0.0), RED),
+            (None, GREEN),
+            (line(-10.0, 0.0), BLUE),
+            (line(0.0, -10.0), WHITE),
+        ]]];
+
+        assert!(build_patches(0.0, 0.0, &rows).is_none());
+    }
+}
@@ -0,0 +1,204 @@
+//! CSS Motion Path: the `offset-path`, `offset-distance` and `offset-rotate` properties.
+//!
+//! https://www.w3.org/TR/motion-1/
+
+use cssparser::Parser;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::angle::Angle;
+use crate::drawing_ctx::ViewParams;
+use crate::error::ValueErrorKind;
+use crate::length::*;
+use crate::parsers::Parse;
+use crate::path_builder::PathBuilder;
+use crate::path_measure::PathMeasure;
+use crate::path_parser;
+use crate::properties::ComputedValues;
+use crate::property_defs::OffsetRotate;
+use crate::property_macros::ToCssValue;
+
+/// The value of `path()` in the `offset-path` and `d` properties.
+#[derive(Clone)]
+pub struct MotionPath {
+    source: String,
+    builder: Rc<PathBuilder>,
+}
+
+impl fmt::Debug for MotionPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MotionPath({:?})", self.source)
+    }
+}
+
+impl PartialEq for MotionPath {
+    fn eq(&self, other: &MotionPath) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Parse for MotionPath {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<MotionPath, ValueErrorKind> {
+        parser.expect_function_matching("path")?;
+
+        let source = parser
+            .parse_nested_block(|p| Ok(p.expect_string()?.as_ref().to_string()))
+            .map_err(cssparser::ParseError::<()>::basic)
+            .map_err(ValueErrorKind::from)?;
+
+        MotionPath::from_path_data(&source)
+    }
+}
+
+impl ToCssValue for MotionPath {
+    fn to_css_value(&self) -> String {
+        let mut s = String::from("path(");
+        cssparser::serialize_string(&self.source, &mut s).unwrap();
+        s.push(')');
+        s
+    }
+}
+
+impl MotionPath {
+    /// Creates a motion path from SVG path data, like the `path` attribute of `<animateMotion>`.
+    pub fn from_path_data(source: &str) -> Result<MotionPath, ValueErrorKind> {
+        let mut builder = PathBuilder::new();
+        path_parser::parse_path_into_builder(source, &mut builder)
+            .map_err(|e| ValueErrorKind::parse_error(&e.to_string()))?;
+
+        Ok(MotionPath {
+            source: source.to_string(),
+            builder: Rc::new(builder),
+        })
+    }
+
+    pub fn builder(&self) -> &PathBuilder {
+        &self.builder
+    }
+
+    /// Returns the SVG path data that this path was created from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the point at `distance` along the path, and the direction of the path there.
+    ///
+    /// Per the spec, the distance gets clamped to the length of the path for open paths,
+    /// and it wraps around for closed ones.
+    fn point_at_distance(
+        &self,
+        distance: &Length<Horizontal>,
+        values: &ComputedValues,
+        params: &ViewParams,
+    ) -> Option<((f64, f64), Angle)> {
+        self.point_along(|total_length| {
+            if distance.unit == LengthUnit::Percent {
+                distance.length * total_length
+            } else {
+                distance.normalize(values, params)
+            }
+        })
+    }
+
+    /// Returns the point at `fraction` of the length of the path, and the direction
+    /// of the path there.
+    pub fn point_at_fraction(&self, fraction: f64) -> Option<((f64, f64), Angle)> {
+        self.point_along(|total_length| fraction * total_length)
+    }
+
+    /// Computes a point along the path; `get_distance` gets the total length of the
+    /// path and returns the distance at which the point is.
+    fn point_along<F>(&self, get_distance: F) -> Option<((f64, f64), Angle)>
+    where
+        F: FnOnce(f64) -> f64,
+    {
+        let measure = PathMeasure::new(&self.builder.to_segments()).ok()?;
+
+        let total_length = measure.total_length();
+
+        let distance = get_distance(total_length);
+
+        let distance = if measure.is_closed() && total_length > 0.0 {
+            ((distance % total_length) + total_length) % total_length
+        } else {
+            distance
+        };
+
+        measure.point_at_length(distance)
+    }
+}
+
+/// Computes the transformation that places an element along its `offset-path`.
+///
+/// Returns `None` if the element does not have an `offset-path`.  SVG elements use
+/// the origin of their user space as the anchor point, so the transformation is
+/// just a translation to the point on the path followed by the `offset-rotate`.
+pub fn offset_transform(values: &ComputedValues, params: &ViewParams) -> Option<cairo::Matrix> {
+    let path = values.offset_path.path.as_ref()?;
+
+    let ((x, y), direction) = path.point_at_distance(&values.offset_distance.0, values, params)?;
+
+    let OffsetRotate { auto, angle } = values.offset_rotate;
+
+    let rotation = if auto {
+        direction.radians() + angle.radians()
+    } else {
+        angle.radians()
+    };
+
+    let mut affine = cairo::Matrix::identity();
+    affine.translate(x, y);
+    affine.rotate(rotation);
+
+    Some(affine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float_eq_cairo::ApproxEqCairo;
+
+    fn assert_point_at(path: &str, distance: &str, point: (f64, f64), degrees: f64) {
+        let path = MotionPath::parse_str(path).unwrap();
+        let distance = Length::<Horizontal>::parse_str(distance).unwrap();
+        let values = ComputedValues::default();
+        let params = ViewParams::new(96.0, 96.0, 100.0, 100.0);
+
+        let ((x, y), angle) = path.point_at_distance(&distance, &values, &params).unwrap();
+
+        assert!(x.approx_eq_cairo(point.0) && y.approx_eq_cairo(point.1));
+        assert!(angle.radians().approx_eq_cairo(degrees.to_radians()));
+    }
+
+    #[test]
+    fn parses_path_function() {
+        let path = MotionPath::parse_str("path('M 0 0 L 10 0')").unwrap();
+        assert_eq!(path.to_css_value(), "path(\"M 0 0 L 10 0\")");
+
+        assert!(MotionPath::parse_str("path()").is_err());
+        assert!(MotionPath::parse_str("path('L 10 0')").is_err());
+        assert!(MotionPath::parse_str("circle(10px)").is_err());
+    }
+
+    #[test]
+    fn computes_points_along_open_path() {
+        let path = "path('M 10 10 L 110 10 L 110 110')";
+
+        assert_point_at(path, "0", (10.0, 10.0), 0.0);
+        assert_point_at(path, "50", (60.0, 10.0), 0.0);
+        assert_point_at(path, "150", (110.0, 60.0), 90.0);
+        assert_point_at(path, "25%", (60.0, 10.0), 0.0);
+
+        // clamped to the ends of the path
+        assert_point_at(path, "-10", (10.0, 10.0), 0.0);
+        assert_point_at(path, "300", (110.0, 110.0), 90.0);
+    }
+
+    #[test]
+    fn wraps_around_closed_path() {
+        let path = "path('M 0 0 L 100 0 L 100 100 L 0 100 Z')";
+
+        assert_point_at(path, "450", (50.0, 0.0), 0.0);
+        assert_point_at(path, "-50", (0.0, 50.0), 270.0);
+    }
+}
@@ -6,15 +6,19 @@ use std::collections::HashSet;
 use std::fmt;
 
 use crate::bbox::BoundingBox;
-use crate::cond::{RequiredExtensions, RequiredFeatures, SystemLanguage};
+use crate::cond::{LanguageRank, RequiredExtensions, RequiredFeatures, SystemLanguage};
 use crate::css::Declaration;
 use crate::drawing_ctx::DrawingCtx;
 use crate::error::*;
+#[cfg(feature = "filters")]
 use crate::filters::FilterEffect;
+use crate::motion_path;
 use crate::parsers::Parse;
-use crate::properties::{ComputedValues, SpecifiedValue, SpecifiedValues};
+use crate::properties::{ComputedValues, ParsedProperty, SpecifiedValue, SpecifiedValues};
 use crate::property_bag::PropertyBag;
-use crate::property_defs::Overflow;
+use crate::property_defs::{Display, Overflow};
+use crate::render_hooks::{HookAction, RenderHookElement};
+use crate::sanitize;
 use locale_config::Locale;
 use rctree;
 
@@ -28,12 +32,18 @@ pub struct NodeData {
     element_name: QualName,
     id: Option<String>,    // id attribute from XML element
     class: Option<String>, // class attribute from XML element
+    attributes: Vec<(QualName, String)>,
     specified_values: SpecifiedValues,
     important_styles: HashSet<QualName>,
     result: NodeResult,
     transform: Matrix,
+    animated_properties: Vec<ParsedProperty>,
+    animated_transform: Option<Matrix>,
+    visible_override: Option<bool>,
+    will_change: bool,
     values: ComputedValues,
     cond: bool,
+    language_rank: Option<LanguageRank>,
     style_attr: String,
     node_impl: Box<dyn NodeTrait>,
 }
@@ -51,12 +61,18 @@ impl NodeData {
             element_name: element_name.clone(),
             id: id.map(str::to_string),
             class: class.map(str::to_string),
+            attributes: Vec::new(),
             specified_values: Default::default(),
             important_styles: Default::default(),
             transform: Matrix::identity(),
+            animated_properties: Vec::new(),
+            animated_transform: None,
+            visible_override: None,
+            will_change: false,
             result: Ok(()),
             values: ComputedValues::default(),
             cond: true,
+            language_rank: None,
             style_attr: String::new(),
             node_impl,
         }
@@ -74,6 +90,14 @@ impl NodeData {
         }
     }
 
+    pub fn get_impl_mut<T: NodeTrait>(&mut self) -> &mut T {
+        if let Some(t) = (&mut self.node_impl).downcast_mut::<T>() {
+            t
+        } else {
+            panic!("could not downcast");
+        }
+    }
+
     pub fn get_type(&self) -> NodeType {
         self.node_type
     }
@@ -90,12 +114,78 @@ impl NodeData {
         self.class.as_ref().map(String::as_str)
     }
 
+    /// Returns the element's attributes as they appear in the XML, for introspection.
+    pub fn get_attributes(&self) -> &[(QualName, String)] {
+        &self.attributes
+    }
+
     pub fn get_cond(&self) -> bool {
         self.cond
     }
 
+    /// Returns how well the element's `systemLanguage` matches the user's languages,
+    /// or `None` if the element does not have that attribute.
+    pub fn get_language_rank(&self) -> Option<LanguageRank> {
+        self.language_rank
+    }
+
     pub fn get_transform(&self) -> Matrix {
-        self.transform
+        self.animated_transform.unwrap_or(self.transform)
+    }
+
+    /// Removes the effect of animations from this node; see `animation::seek()`.
+    pub fn clear_animated_values(&mut self) {
+        self.animated_properties.clear();
+        self.animated_transform = None;
+    }
+
+    /// Overrides a property with its animated value; later calls take precedence.
+    pub fn set_animated_property(&mut self, prop: ParsedProperty) {
+        self.animated_properties.push(prop);
+    }
+
+    /// Overrides the `transform` attribute with its animated value.
+    pub fn set_animated_transform(&mut self, affine: Matrix) {
+        self.animated_transform = Some(affine);
+    }
+
+    /// Shows or hides this node by overriding its `display` property.
+    ///
+    /// `Some(false)` makes the node behave as if it had `display: none`, and `Some(true)`
+    /// turns a `display: none` into `inline`.  `None` removes the override.  Unlike
+    /// animated values, this is not cleared by `animation::seek()`.
+    pub fn set_visible_override(&mut self, visible: Option<bool>) {
+        self.visible_override = visible;
+    }
+
+    /// Marks this node as expected to change between renders; see `FilterCache`.
+    pub fn set_will_change(&mut self, will_change: bool) {
+        self.will_change = will_change;
+    }
+
+    pub fn get_will_change(&self) -> bool {
+        self.will_change
+    }
+
+    /// Computes this node's values, including animated ones, from its parent's `values`.
+    fn to_computed_values(&self, values: &mut ComputedValues) {
+        if self.animated_properties.is_empty() {
+            self.specified_values.to_computed_values(values);
+        } else {
+            let mut specified_values = self.specified_values.clone();
+
+            for prop in &self.animated_properties {
+                specified_values.set_parsed_property(prop);
+            }
+
+            specified_values.to_computed_values(values);
+        }
+
+        match self.visible_override {
+            Some(false) => values.display = Display::None,
+            Some(true) if values.display == Display::None => values.display = Display::Inline,
+            _ => (),
+        }
     }
 
     pub fn is_overflow(&self) -> bool {
@@ -107,6 +197,11 @@ impl NodeData {
             self.specified_values.overflow = SpecifiedValue::Specified(Overflow::Hidden);
         }
 
+        self.attributes = pbag
+            .iter()
+            .map(|(attr, value)| (attr, value.to_string()))
+            .collect();
+
         self.save_style_attribute(pbag);
 
         if let Err(e) = self
@@ -153,6 +248,7 @@ impl NodeData {
         locale: &Locale,
     ) -> Result<(), NodeError> {
         let mut cond = self.cond;
+        let mut language_rank = self.language_rank;
 
         for (attr, value) in pbag.iter() {
             // FIXME: move this to "try {}" when we can bump the rustc version dependency
@@ -169,8 +265,9 @@ impl NodeData {
                     }
 
                     expanded_name!(svg "systemLanguage") if cond => {
-                        cond = SystemLanguage::from_attribute(value, locale)
-                            .map(|SystemLanguage(res)| res)?;
+                        let SystemLanguage(rank) = SystemLanguage::from_attribute(value, locale)?;
+                        language_rank = rank;
+                        cond = rank.is_some();
                     }
 
                     _ => {}
@@ -184,6 +281,8 @@ impl NodeData {
                 .map_err(|e| NodeError::new(attr, e))?;
         }
 
+        self.language_rank = language_rank;
+
         Ok(())
     }
 
@@ -216,6 +315,9 @@ impl NodeData {
     }
 
     /// Applies CSS styles from the saved value of the "style" attribute
+    ///
+    /// The value is kept, so that the styles can be applied again after
+    /// `reset_specified_values()`.
     pub fn set_style_attribute(&mut self) {
         if !self.style_attr.is_empty() {
             if let Err(e) = self
@@ -224,10 +326,29 @@ impl NodeData {
             {
                 self.set_error(e);
             }
+        }
+    }
 
-            self.style_attr.clear();
-            self.style_attr.shrink_to_fit();
+    /// Discards the values from stylesheets and from the "style" attribute, and
+    /// goes back to the ones from the presentation attributes.
+    ///
+    /// This is used to run the CSS cascade again when the stylesheets change.
+    pub fn reset_specified_values(&mut self) {
+        let pbag = PropertyBag::new_from_attributes(&self.attributes);
+
+        self.specified_values = Default::default();
+        self.important_styles.clear();
+
+        if self.node_impl.overflow_hidden() {
+            self.specified_values.overflow = SpecifiedValue::Specified(Overflow::Hidden);
         }
+
+        if let Err(e) = self.specified_values.parse_presentation_attributes(&pbag) {
+            rsvg_log!("(attribute error: {})", e);
+        }
+
+        self.node_impl
+            .set_overridden_properties(&mut self.specified_values);
     }
 
     fn set_error(&mut self, error: NodeError) {
@@ -237,6 +358,11 @@ impl NodeData {
     pub fn is_in_error(&self) -> bool {
         self.result.is_err()
     }
+
+    /// Returns the error from parsing the element's attributes, if there was one.
+    pub fn get_error(&self) -> Option<&NodeError> {
+        self.result.as_ref().err()
+    }
 }
 
 impl fmt::Display for NodeData {
@@ -303,7 +429,7 @@ impl<'a> CascadedValues<'a> {
     /// `<use>`'s own cascade, not wih the element's original cascade.
     pub fn new_from_values(node: &'a RsvgNode, values: &ComputedValues) -> CascadedValues<'a> {
         let mut v = values.clone();
-        node.borrow().specified_values.to_computed_values(&mut v);
+        node.borrow().to_computed_values(&mut v);
 
         CascadedValues {
             inner: CascadedInner::FromValues(v),
@@ -351,6 +477,7 @@ pub trait NodeTrait: Downcast {
     }
 
     /// Returns the FilterEffect trait if this node is a filter primitive
+    #[cfg(feature = "filters")]
     fn as_filter_effect(&self) -> Option<&dyn FilterEffect> {
         None
     }
@@ -382,18 +509,26 @@ pub type NodeResult = Result<(), NodeError>;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum NodeType {
+    Animate,
+    AnimateMotion,
+    AnimateTransform,
     Chars,
     Circle,
     ClipPath,
     Ellipse,
     Filter,
     Group,
+    Hatch,
+    HatchPath,
     Image,
     Line,
     LinearGradient,
     Link,
     Marker,
     Mask,
+    MeshGradient,
+    MeshPatch,
+    MeshRow,
     NonRendering,
     Path,
     Pattern,
@@ -401,14 +536,17 @@ pub enum NodeType {
     Polyline,
     RadialGradient,
     Rect,
+    Set,
     Stop,
     Style,
     Svg,
     Switch,
     Symbol,
     Text,
+    TextPath,
     TRef,
     TSpan,
+    Unsupported,
     Use,
 
     // Filter primitives, these start with "Fe" as element names are e.g. "feBlend"
@@ -420,6 +558,7 @@ pub enum NodeType {
     FeDiffuseLighting,
     FeDisplacementMap,
     FeDistantLight,
+    FeDropShadow,
     FeFlood,
     FeFuncA,
     FeFuncB,
@@ -441,16 +580,26 @@ pub enum NodeType {
 /// Helper trait for cascading recursively
 pub trait NodeCascade {
     fn cascade(&mut self, values: &ComputedValues);
+
+    /// Cascades this node and its descendants again from their parent's values,
+    /// for example after the node's visibility override changed.
+    fn recascade(&mut self);
 }
 
 impl NodeCascade for RsvgNode {
     fn cascade(&mut self, values: &ComputedValues) {
         let mut values = values.clone();
+        let is_root = self.parent().is_none();
 
         {
             let mut node_mut = self.borrow_mut();
 
-            node_mut.specified_values.to_computed_values(&mut values);
+            node_mut.to_computed_values(&mut values);
+
+            if is_root {
+                values.root_font_size = values.font_size.clone();
+            }
+
             node_mut.values = values.clone();
         }
 
@@ -458,6 +607,15 @@ impl NodeCascade for RsvgNode {
             child.cascade(&values);
         }
     }
+
+    fn recascade(&mut self) {
+        let parent_values = self
+            .parent()
+            .map(|p| CascadedValues::new_from_node(&p).get().clone())
+            .unwrap_or_default();
+
+        self.cascade(&parent_values);
+    }
 }
 
 /// Helper trait for drawing recursively
@@ -487,11 +645,58 @@ impl NodeDraw for RsvgNode {
         if !self.borrow().is_in_error() {
             draw_ctx.with_saved_matrix(&mut |dc| {
                 let cr = dc.get_cairo_context();
-                cr.transform(self.borrow().get_transform());
 
-                self.borrow()
-                    .get_node_trait()
-                    .draw(self, cascaded, dc, clipping)
+                let offset = motion_path::offset_transform(cascaded.get(), &dc.get_view_params())
+                    .unwrap_or_else(Matrix::identity);
+
+                let transform = match dc.get_css_transform(cascaded.get()) {
+                    // https://www.w3.org/TR/css-transforms-1/#transform-function-lists
+                    Some(affine) if affine.try_invert().is_err() => return Ok(dc.empty_bbox()),
+                    Some(affine) => affine,
+                    None => self.borrow().get_transform(),
+                };
+
+                let affine = Matrix::multiply(&transform, &offset);
+
+                // A transform that cairo can't handle would put the context in an error
+                // state, and nothing else could be drawn after this element.
+                if !sanitize::is_valid_matrix(&Matrix::multiply(&affine, &cr.get_matrix())) {
+                    rsvg_log!(
+                        "(not rendering element {} because its transform is invalid)",
+                        self
+                    );
+                    return Ok(dc.empty_bbox());
+                }
+
+                cr.transform(affine);
+
+                if !clipping {
+                    dc.add_element_transform(self);
+                }
+
+                if clipping || dc.get_render_hooks().is_empty() {
+                    return self
+                        .borrow()
+                        .get_node_trait()
+                        .draw(self, cascaded, dc, clipping);
+                }
+
+                // Clone the hooks, since the element may draw other elements recursively
+                let hooks = dc.get_render_hooks().clone();
+                let values = cascaded.get();
+                let element = RenderHookElement::new(self, values);
+
+                let res = match hooks.run_pre_draw(&cr, &element) {
+                    HookAction::Continue => self
+                        .borrow()
+                        .get_node_trait()
+                        .draw(self, cascaded, dc, clipping),
+                    HookAction::Skip => Ok(dc.empty_bbox()),
+                };
+
+                hooks.run_post_draw(&cr, &element);
+
+                res
             })
         } else {
             rsvg_log!("(not rendering element {} because it is in error)", self);
@@ -1,12 +1,17 @@
+//! Paint servers, and the resolution of their `xlink:href` references.
+
 use cssparser::{self, Parser};
+use std::any::Any;
+use std::collections::HashMap;
 
 use crate::allowed_url::Fragment;
 use crate::bbox::BoundingBox;
-use crate::drawing_ctx::DrawingCtx;
+use crate::drawing_ctx::{DrawingCtx, NodeStack};
 use crate::error::*;
-use crate::node::{CascadedValues, RsvgNode};
+use crate::node::{CascadedValues, NodeData, NodeType, RsvgNode};
 use crate::parsers::Parse;
 use crate::properties::ComputedValues;
+use crate::property_macros::ToCssValue;
 use crate::unit_interval::UnitInterval;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,6 +22,12 @@ pub enum PaintServer {
         alternate: Option<cssparser::Color>,
     },
     SolidColor(cssparser::Color),
+
+    /// `context-fill`; the fill of the context element, such as a shape with markers
+    ContextFill,
+
+    /// `context-stroke`; the stroke of the context element
+    ContextStroke,
 }
 
 impl Parse for PaintServer {
@@ -26,6 +37,16 @@ impl Parse for PaintServer {
             .is_ok()
         {
             Ok(PaintServer::None)
+        } else if parser
+            .try_parse(|i| i.expect_ident_matching("context-fill"))
+            .is_ok()
+        {
+            Ok(PaintServer::ContextFill)
+        } else if parser
+            .try_parse(|i| i.expect_ident_matching("context-stroke"))
+            .is_ok()
+        {
+            Ok(PaintServer::ContextStroke)
         } else if let Ok(url) = parser.try_parse(|i| i.expect_url()) {
             let alternate = if !parser.is_exhausted() {
                 if parser
@@ -52,14 +73,33 @@ impl Parse for PaintServer {
     }
 }
 
-pub trait PaintSource {
-    type Resolved: AsPaintSource;
+impl ToCssValue for PaintServer {
+    fn to_css_value(&self) -> String {
+        match *self {
+            PaintServer::None => "none".to_string(),
 
-    fn resolve(
-        &self,
-        node: &RsvgNode,
-        draw_ctx: &mut DrawingCtx,
-    ) -> Result<Self::Resolved, AcquireError>;
+            PaintServer::Iri {
+                ref iri,
+                alternate: None,
+            } => format!("url({})", iri),
+
+            PaintServer::Iri {
+                ref iri,
+                alternate: Some(ref color),
+            } => format!("url({}) {}", iri, color.to_css_value()),
+
+            PaintServer::SolidColor(ref color) => color.to_css_value(),
+
+            PaintServer::ContextFill => "context-fill".to_string(),
+
+            PaintServer::ContextStroke => "context-stroke".to_string(),
+        }
+    }
+}
+
+pub trait PaintSource {
+    /// The attributes of this kind of paint server, as they get resolved
+    type Unresolved: Resolve;
 
     fn resolve_fallbacks_and_set_pattern(
         &self,
@@ -68,7 +108,7 @@ pub trait PaintSource {
         opacity: UnitInterval,
         bbox: &BoundingBox,
     ) -> Result<bool, RenderingError> {
-        match self.resolve(&node, draw_ctx) {
+        match resolve::<Self::Unresolved>(&node, draw_ctx) {
             Ok(resolved) => {
                 let cascaded = CascadedValues::new_from_node(node);
                 let values = cascaded.get();
@@ -105,15 +145,125 @@ pub trait AsPaintSource {
     ) -> Result<bool, RenderingError>;
 }
 
-// Any of the attributes in gradient and pattern elements may be omitted.
-// The missing ones are resolved from the "fallback" IRI. If still missing,
-// they are resolved to the default value
-pub trait Resolve {
+/// The attributes of a paint server element, some of which may be missing.
+///
+/// Any of the attributes in paint server elements may be omitted.  The missing ones
+/// are resolved from the "fallback" IRI in the element's `xlink:href`, and so on along
+/// the chain of fallbacks.  If still missing, they are resolved to the default value.
+pub trait Resolve: Sized {
+    /// The paint server with all of its attributes
+    type Resolved: AsPaintSource + Clone + 'static;
+
+    /// Types of the elements from which this kind of paint server can take attributes
+    const FALLBACK_TYPES: &'static [NodeType];
+
+    /// Returns the attributes of `node`, which is one of the `FALLBACK_TYPES`, and the
+    /// fallback from its `xlink:href`.
+    fn from_node(node: &RsvgNode) -> (Self, Option<Fragment>);
+
     fn is_resolved(&self) -> bool;
 
     fn resolve_from_fallback(&self, fallback: &Self) -> Self;
 
     fn resolve_from_defaults(&self) -> Self;
+
+    /// Creates the paint server for `node` from the attributes, which must be resolved.
+    fn to_resolved(self, node: &RsvgNode) -> Self::Resolved;
+}
+
+/// Resolves the paint server in `node` by following its chain of fallbacks.
+///
+/// Resolved paint servers are kept in the document's `PaintServerCache`, so this
+/// only walks the chain the first time that a paint server is used after the
+/// document's styles change.
+///
+/// Fallbacks that don't exist or that are not of the right type are ignored, and the
+/// attributes that are still missing get their default values.  A chain of fallbacks
+/// that loops back on itself is an error.
+pub fn resolve<R: Resolve>(
+    node: &RsvgNode,
+    draw_ctx: &mut DrawingCtx,
+) -> Result<R::Resolved, AcquireError> {
+    if let Some(resolved) = draw_ctx.paint_server_cache().lookup::<R::Resolved>(node) {
+        return Ok(resolved);
+    }
+
+    let (mut unresolved, mut fallback) = R::from_node(node);
+
+    let mut stack = NodeStack::new();
+    stack.push(node);
+
+    while !unresolved.is_resolved() {
+        if let Some(fragment) = fallback {
+            match draw_ctx.acquire_node(&fragment, R::FALLBACK_TYPES) {
+                Ok(acquired) => {
+                    let acquired_node = acquired.get();
+
+                    if stack.contains(acquired_node) {
+                        return Err(AcquireError::CircularReference(acquired_node.clone()));
+                    }
+
+                    let (fallback_attributes, next) = R::from_node(acquired_node);
+
+                    unresolved = unresolved.resolve_from_fallback(&fallback_attributes);
+                    fallback = next;
+
+                    stack.push(acquired_node);
+                }
+
+                Err(e @ AcquireError::MaxReferencesExceeded)
+                | Err(e @ AcquireError::CircularReference(_)) => return Err(e),
+
+                Err(e) => {
+                    rsvg_log!("stopping paint server resolution: {}", e);
+                    unresolved = unresolved.resolve_from_defaults();
+                    break;
+                }
+            }
+        } else {
+            unresolved = unresolved.resolve_from_defaults();
+            break;
+        }
+    }
+
+    let resolved = unresolved.to_resolved(node);
+
+    draw_ctx.paint_server_cache().insert(node, resolved.clone());
+
+    Ok(resolved)
+}
+
+/// Paint servers that have already been resolved, by node
+///
+/// The resolved paint servers depend on the styles of the elements, like the
+/// `stop-color` of gradient stops, so the cache must be cleared when the styles change.
+#[derive(Default)]
+pub struct PaintServerCache {
+    // Keyed by the address of the node's data, which does not move while the entry
+    // keeps the node alive.
+    entries: HashMap<*const NodeData, (RsvgNode, Box<dyn Any>)>,
+}
+
+impl PaintServerCache {
+    pub fn lookup<T: Clone + 'static>(&self, node: &RsvgNode) -> Option<T> {
+        self.entries
+            .get(&node_key(node))
+            .and_then(|(_, resolved)| resolved.downcast_ref::<T>())
+            .cloned()
+    }
+
+    pub fn insert<T: 'static>(&mut self, node: &RsvgNode, resolved: T) {
+        self.entries
+            .insert(node_key(node), (node.clone(), Box::new(resolved)));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn node_key(node: &RsvgNode) -> *const NodeData {
+    &*node.borrow() as *const NodeData
 }
 
 #[cfg(test)]
@@ -132,6 +282,18 @@ mod tests {
         assert_eq!(PaintServer::parse_str("none"), Ok(PaintServer::None));
     }
 
+    #[test]
+    fn parses_context_paint() {
+        assert_eq!(
+            PaintServer::parse_str("context-fill"),
+            Ok(PaintServer::ContextFill)
+        );
+        assert_eq!(
+            PaintServer::parse_str("context-stroke"),
+            Ok(PaintServer::ContextStroke)
+        );
+    }
+
     #[test]
     fn parses_solid_color() {
         assert_eq!(
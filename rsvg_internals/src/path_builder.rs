@@ -4,6 +4,7 @@ use std::f64;
 use std::f64::consts::*;
 
 use crate::float_eq_cairo::ApproxEqCairo;
+use crate::sanitize::DeviceClamp;
 use crate::util::clamp;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -25,13 +26,6 @@ pub struct CubicBezierCurve {
     pub to: (f64, f64),
 }
 
-impl CubicBezierCurve {
-    fn to_cairo(self, cr: &cairo::Context) {
-        let Self { pt1, pt2, to } = self;
-        cr.curve_to(pt1.0, pt1.1, pt2.0, pt2.1, to.0, to.1);
-    }
-}
-
 /// When attempting to compute the center parameterization of the arc,
 /// out of range parameters may see an arc omited or treated as a line.
 pub enum ArcParameterization {
@@ -198,7 +192,7 @@ impl EllipticalArc {
         }
     }
 
-    fn to_cairo(self, cr: &cairo::Context) {
+    fn to_segments(self, segments: &mut Vec<PathSegment>) {
         match self.center_parameterization() {
             ArcParameterization::CenterParameters {
                 center,
@@ -211,14 +205,15 @@ impl EllipticalArc {
 
                 let mut theta = theta1;
                 for _ in 0..n_segs {
-                    arc_segment(center, radii, self.x_axis_rotation, theta, theta + d_theta)
-                        .to_cairo(cr);
+                    let curve =
+                        arc_segment(center, radii, self.x_axis_rotation, theta, theta + d_theta);
+                    segments.push(PathSegment::from(curve));
                     theta += d_theta;
                 }
             }
             ArcParameterization::LineTo => {
                 let (x2, y2) = self.to;
-                cr.line_to(x2, y2);
+                segments.push(PathSegment::LineTo(x2, y2));
             }
             ArcParameterization::Omit => {}
         }
@@ -278,18 +273,45 @@ pub enum PathCommand {
     ClosePath,
 }
 
-impl PathCommand {
-    fn to_cairo(&self, cr: &cairo::Context) {
+/// A segment of a path in absolute coordinates, as returned by the path data API
+///
+/// Elliptical arcs are converted to cubic Bézier curves, so there are only four
+/// kinds of segments, like in Cairo paths.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    /// Starts a new subpath at `(x, y)`
+    MoveTo(f64, f64),
+
+    /// A straight line to `(x, y)`
+    LineTo(f64, f64),
+
+    /// A cubic Bézier curve with control points `(x1, y1)` and `(x2, y2)`, ending at
+    /// `(x, y)`; the arguments are `(x1, y1, x2, y2, x, y)`
+    CurveTo(f64, f64, f64, f64, f64, f64),
+
+    /// Closes the current subpath with a straight line to its start point
+    ClosePath,
+}
+
+impl PathSegment {
+    fn is_finite(&self) -> bool {
         match *self {
-            PathCommand::MoveTo(x, y) => cr.move_to(x, y),
-            PathCommand::LineTo(x, y) => cr.line_to(x, y),
-            PathCommand::CurveTo(curve) => curve.to_cairo(cr),
-            PathCommand::Arc(arc) => arc.to_cairo(cr),
-            PathCommand::ClosePath => cr.close_path(),
+            PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => x.is_finite() && y.is_finite(),
+            PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                [x1, y1, x2, y2, x, y].iter().all(|v| v.is_finite())
+            }
+            PathSegment::ClosePath => true,
         }
     }
 }
 
+impl From<CubicBezierCurve> for PathSegment {
+    fn from(curve: CubicBezierCurve) -> PathSegment {
+        let CubicBezierCurve { pt1, pt2, to } = curve;
+        PathSegment::CurveTo(pt1.0, pt1.1, pt2.0, pt2.1, to.0, to.1)
+    }
+}
+
 #[derive(Default)]
 pub struct PathBuilder {
     path_commands: Vec<PathCommand>,
@@ -352,11 +374,67 @@ impl PathBuilder {
         self.path_commands.is_empty()
     }
 
+    /// Converts the path into segments, with its elliptical arcs turned into curves.
+    pub fn to_segments(&self) -> Vec<PathSegment> {
+        let mut segments = Vec::with_capacity(self.path_commands.len());
+
+        for command in &self.path_commands {
+            match *command {
+                PathCommand::MoveTo(x, y) => segments.push(PathSegment::MoveTo(x, y)),
+                PathCommand::LineTo(x, y) => segments.push(PathSegment::LineTo(x, y)),
+                PathCommand::CurveTo(curve) => segments.push(PathSegment::from(curve)),
+                PathCommand::Arc(arc) => arc.to_segments(&mut segments),
+                PathCommand::ClosePath => segments.push(PathSegment::ClosePath),
+            }
+        }
+
+        segments
+    }
+
+    /// Whether all the coordinates in the path are finite numbers.
+    ///
+    /// Arcs with huge radii can produce non-finite curves even if their end points
+    /// are finite, so this checks the path after turning its arcs into curves.
+    pub fn is_finite(&self) -> bool {
+        self.to_segments().iter().all(PathSegment::is_finite)
+    }
+
     pub fn to_cairo(&self, cr: &cairo::Context) -> Result<(), cairo::Status> {
         assert!(!self.is_empty());
 
-        for s in &self.path_commands {
-            s.to_cairo(cr);
+        let segments = self.to_segments();
+
+        // Cairo would turn NaNs or infinities into garbage, so don't draw those paths at all
+        if !segments.iter().all(PathSegment::is_finite) {
+            rsvg_log!("(not drawing a path with non-finite coordinates)");
+            return Ok(());
+        }
+
+        // Points that are out of the range of cairo's device coordinates get moved to its edge
+        let clamp = DeviceClamp::new(&cr.get_matrix());
+        let point = |x, y| clamp.as_ref().map_or((x, y), |c| c.clamp_point(x, y));
+
+        for segment in segments {
+            match segment {
+                PathSegment::MoveTo(x, y) => {
+                    let (x, y) = point(x, y);
+                    cr.move_to(x, y);
+                }
+
+                PathSegment::LineTo(x, y) => {
+                    let (x, y) = point(x, y);
+                    cr.line_to(x, y);
+                }
+
+                PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                    let (x1, y1) = point(x1, y1);
+                    let (x2, y2) = point(x2, y2);
+                    let (x, y) = point(x, y);
+                    cr.curve_to(x1, y1, x2, y2, x, y);
+                }
+
+                PathSegment::ClosePath => cr.close_path(),
+            }
         }
 
         // We check the cr's status right after feeding it a new path for a few reasons:
@@ -381,6 +459,54 @@ impl PathBuilder {
 mod tests {
     use super::*;
 
+    #[test]
+    fn converts_arcs_to_curves_in_segments() {
+        let mut builder = PathBuilder::new();
+        builder.move_to(0.0, 0.0);
+        builder.line_to(10.0, 0.0);
+        builder.arc(
+            10.0,
+            0.0,
+            10.0,
+            10.0,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            20.0,
+            10.0,
+        );
+        builder.arc(
+            20.0,
+            10.0,
+            0.0,
+            0.0,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            30.0,
+            10.0,
+        );
+        builder.close_path();
+
+        let segments = builder.to_segments();
+
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[0], PathSegment::MoveTo(0.0, 0.0));
+        assert_eq!(segments[1], PathSegment::LineTo(10.0, 0.0));
+
+        // a quarter of a circle becomes a single curve
+        match segments[2] {
+            PathSegment::CurveTo(_, _, _, _, x, y) => {
+                assert!(x.approx_eq_cairo(20.0) && y.approx_eq_cairo(10.0));
+            }
+            _ => unreachable!(),
+        }
+
+        // an arc with zero radii is a straight line
+        assert_eq!(segments[3], PathSegment::LineTo(30.0, 10.0));
+        assert_eq!(segments[4], PathSegment::ClosePath);
+    }
+
     #[test]
     fn survives_degenerate_arcs() {
         let mut builder = PathBuilder::new();
@@ -0,0 +1,223 @@
+//! Measuring paths: their length, and the points and directions along them.
+//!
+//! This is the machinery behind motion paths, and behind the public APIs that mirror
+//! SVG's `getTotalLength()` and `getPointAtLength()`.
+
+use cairo;
+
+use crate::angle::Angle;
+use crate::path_builder::PathSegment;
+
+/// A point along a path, as returned by the path measurement API
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathPoint {
+    /// Horizontal position of the point
+    pub x: f64,
+
+    /// Vertical position of the point
+    pub y: f64,
+
+    /// Direction of the tangent to the path at the point, in degrees clockwise
+    /// from the x axis
+    pub angle: f64,
+}
+
+/// A straight piece of a flattened path
+struct Line {
+    from: (f64, f64),
+    to: (f64, f64),
+    length: f64,
+}
+
+/// A path flattened into straight lines, so that it can be measured
+pub struct PathMeasure {
+    lines: Vec<Line>,
+    closed: bool,
+}
+
+impl PathMeasure {
+    pub fn new(segments: &[PathSegment]) -> Result<PathMeasure, cairo::Status> {
+        // Let cairo do the flattening of curves for us
+        let surface = cairo::ImageSurface::create(cairo::Format::A8, 1, 1)?;
+        let cr = cairo::Context::new(&surface);
+
+        for segment in segments {
+            match *segment {
+                PathSegment::MoveTo(x, y) => cr.move_to(x, y),
+                PathSegment::LineTo(x, y) => cr.line_to(x, y),
+                PathSegment::CurveTo(x1, y1, x2, y2, x, y) => cr.curve_to(x1, y1, x2, y2, x, y),
+                PathSegment::ClosePath => cr.close_path(),
+            }
+        }
+
+        let status = cr.status();
+        if status != cairo::Status::Success {
+            return Err(status);
+        }
+
+        let mut lines = Vec::new();
+        let mut closed = false;
+        let mut start = (0.0, 0.0);
+        let mut current = (0.0, 0.0);
+
+        for segment in cr.copy_path_flat().iter() {
+            let to = match segment {
+                // cairo emits a moveto after each closepath, so this does not
+                // mean that the path is open.
+                cairo::PathSegment::MoveTo(p) => {
+                    start = p;
+                    current = p;
+                    continue;
+                }
+
+                cairo::PathSegment::LineTo(p) | cairo::PathSegment::CurveTo(_, _, p) => {
+                    closed = false;
+                    p
+                }
+
+                cairo::PathSegment::ClosePath => {
+                    closed = true;
+                    start
+                }
+            };
+
+            let length = (to.0 - current.0).hypot(to.1 - current.1);
+            lines.push(Line {
+                from: current,
+                to,
+                length,
+            });
+
+            current = to;
+        }
+
+        Ok(PathMeasure { lines, closed })
+    }
+
+    /// Whether the last subpath of the path is closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn total_length(&self) -> f64 {
+        self.lines.iter().map(|l| l.length).sum()
+    }
+
+    /// Returns the point at `distance` along the path, and the direction of the path there.
+    ///
+    /// The distance gets clamped to the length of the path.  Returns `None` for an
+    /// empty path.
+    pub fn point_at_length(&self, distance: f64) -> Option<((f64, f64), Angle)> {
+        let mut distance = distance.max(0.0).min(self.total_length());
+
+        // Zero-length lines don't have a direction, so skip them unless the
+        // whole path is degenerate.
+        let mut last = None;
+
+        for line in self.lines.iter().filter(|l| l.length > 0.0) {
+            if distance <= line.length {
+                let t = distance / line.length;
+                let (x0, y0) = line.from;
+                let (x1, y1) = line.to;
+
+                return Some((
+                    (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t),
+                    Angle::from_vector(x1 - x0, y1 - y0),
+                ));
+            }
+
+            distance -= line.length;
+            last = Some(line);
+        }
+
+        match last {
+            Some(line) => {
+                let (x0, y0) = line.from;
+                let (x1, y1) = line.to;
+                Some(((x1, y1), Angle::from_vector(x1 - x0, y1 - y0)))
+            }
+
+            None => self.lines.first().map(|l| (l.from, Angle::new(0.0))),
+        }
+    }
+
+    /// Like `point_at_length()`, but returns a `PathPoint` for the public API.
+    pub fn path_point_at_length(&self, distance: f64) -> Option<PathPoint> {
+        self.point_at_length(distance)
+            .map(|((x, y), angle)| PathPoint {
+                x,
+                y,
+                angle: angle.radians().to_degrees(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float_eq_cairo::ApproxEqCairo;
+    use crate::path_builder::{LargeArc, PathBuilder, Sweep};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn measures_lines() {
+        let measure = PathMeasure::new(&[
+            PathSegment::MoveTo(0.0, 0.0),
+            PathSegment::LineTo(30.0, 40.0),
+            PathSegment::MoveTo(100.0, 100.0),
+            PathSegment::LineTo(100.0, 110.0),
+        ])
+        .unwrap();
+
+        assert!(measure.total_length().approx_eq_cairo(60.0));
+        assert!(!measure.is_closed());
+
+        assert_eq!(
+            measure.path_point_at_length(25.0),
+            Some(PathPoint {
+                x: 15.0,
+                y: 20.0,
+                angle: 4.0f64.atan2(3.0).to_degrees(),
+            })
+        );
+
+        // subpaths are not joined
+        let ((x, y), angle) = measure.point_at_length(55.0).unwrap();
+        assert!(x.approx_eq_cairo(100.0) && y.approx_eq_cairo(105.0));
+        assert!(angle.radians().approx_eq_cairo(PI / 2.0));
+
+        // clamped to the ends of the path
+        assert_eq!(measure.point_at_length(-10.0).unwrap().0, (0.0, 0.0));
+        assert_eq!(measure.point_at_length(100.0).unwrap().0, (100.0, 110.0));
+    }
+
+    #[test]
+    fn measures_curves() {
+        let mut builder = PathBuilder::new();
+        builder.move_to(100.0, 0.0);
+        builder.arc(
+            100.0,
+            0.0,
+            100.0,
+            100.0,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            0.0,
+            100.0,
+        );
+
+        let measure = PathMeasure::new(&builder.to_segments()).unwrap();
+
+        // a quarter of a circle, up to the flattening tolerance
+        assert!((measure.total_length() - PI * 50.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn empty_path_has_no_points() {
+        let measure = PathMeasure::new(&[]).unwrap();
+
+        assert_eq!(measure.total_length(), 0.0);
+        assert!(measure.point_at_length(0.0).is_none());
+    }
+}
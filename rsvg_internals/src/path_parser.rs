@@ -30,6 +30,9 @@ struct PathParser<'b> {
     // used for closepath.
     subpath_start_x: f64,
     subpath_start_y: f64,
+
+    // Whether we have seen a moveto yet; used when recovering from errors.
+    has_moveto: bool,
 }
 
 // This is a recursive descent parser for path data in SVG files,
@@ -75,6 +78,8 @@ impl<'b> PathParser<'b> {
 
             subpath_start_x: 0.0,
             subpath_start_y: 0.0,
+
+            has_moveto: false,
         }
     }
 
@@ -85,6 +90,66 @@ impl<'b> PathParser<'b> {
         self.moveto_drawto_command_groups()
     }
 
+    // Parses the whole path data, skipping to the next command after each error.
+    // Returns all the errors that were found.
+    fn parse_skipping_errors(&mut self) -> Vec<ParseError> {
+        let mut errors = Vec::new();
+
+        let mut result = self.parse();
+
+        while let Err(e) = result {
+            errors.push(e);
+
+            if !self.skip_to_next_command() {
+                break;
+            }
+
+            result = self.commands_after_error();
+        }
+
+        errors
+    }
+
+    // Skips characters until the next command letter, or until the next moveto if
+    // there has not been a valid one yet.  Returns false if we reached EOF.
+    fn skip_to_next_command(&mut self) -> bool {
+        while let Some(c) = self.lookahead {
+            let is_command = if self.has_moveto {
+                "MmZzLlHhVvCcSsQqTtAa".contains(c)
+            } else {
+                c == 'M' || c == 'm'
+            };
+
+            if is_command {
+                return true;
+            }
+
+            self.getchar();
+        }
+
+        false
+    }
+
+    // Like moveto_drawto_command_groups(), but it allows for drawto commands
+    // without a preceding moveto, since the moveto may have been skipped due to
+    // an error.  Every command consumes at least its command letter before it can
+    // fail, so the caller always makes progress.
+    fn commands_after_error(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.optional_whitespace()?;
+            if self.lookahead.is_none() {
+                break;
+            }
+
+            if !self.drawto_command()? {
+                let initial = !self.has_moveto;
+                self.moveto(initial)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn getchar(&mut self) {
         if let Some((pos, c)) = self.chars_enumerator.next() {
             self.lookahead = Some(c);
@@ -328,6 +393,7 @@ impl<'b> PathParser<'b> {
 
         self.subpath_start_x = self.current_x;
         self.subpath_start_y = self.current_y;
+        self.has_moveto = true;
 
         self.builder.move_to(self.current_x, self.current_y);
     }
@@ -897,6 +963,28 @@ fn char_to_digit(c: char) -> i32 {
     c as i32 - '0' as i32
 }
 
+/// How to handle errors in path data.
+///
+/// The SVG specification says that a path must be rendered up to the first
+/// error in its path data, and this is the default.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathDataRecovery {
+    /// Do not render a path at all if its path data has errors.
+    Strict,
+
+    /// Render the path up to the first error in its path data.
+    RenderPrefix,
+
+    /// Skip over erroneous data to the next command, and keep parsing from there.
+    SkipToken,
+}
+
+impl Default for PathDataRecovery {
+    fn default() -> PathDataRecovery {
+        PathDataRecovery::RenderPrefix
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ErrorKind {
     UnexpectedToken,
@@ -938,6 +1026,40 @@ pub fn parse_path_into_builder(
     parser.parse()
 }
 
+/// Parses path data with the specified strategy for recovering from errors.
+///
+/// Returns the errors which were recovered from, so that the caller can report them.
+/// With `PathDataRecovery::Strict`, the first error is returned as an `Err` instead,
+/// and the `builder` is reset to an empty path.
+pub fn parse_path_into_builder_with_recovery(
+    path_str: &str,
+    builder: &mut PathBuilder,
+    recovery: PathDataRecovery,
+) -> Result<Vec<ParseError>, ParseError> {
+    match recovery {
+        PathDataRecovery::Strict => {
+            let result = parse_path_into_builder(path_str, builder);
+
+            if result.is_err() {
+                *builder = PathBuilder::new();
+            }
+
+            result.map(|_| Vec::new())
+        }
+
+        PathDataRecovery::RenderPrefix => Ok(parse_path_into_builder(path_str, builder)
+            .err()
+            .into_iter()
+            .collect()),
+
+        PathDataRecovery::SkipToken => {
+            let mut parser = PathParser::new(builder, path_str);
+
+            Ok(parser.parse_skipping_errors())
+        }
+    }
+}
+
 #[cfg(test)]
 #[cfg_attr(rustfmt, rustfmt_skip)]
 mod tests {
@@ -2199,4 +2321,90 @@ mod tests {
             Some(ErrorKind::UnexpectedToken),
         );
     }
+
+    fn test_recovery(
+        path_str: &str,
+        recovery: PathDataRecovery,
+        expected_commands: &[PathCommand],
+        expected_error_positions: &[usize],
+    ) {
+        let mut builder = PathBuilder::new();
+        let result = parse_path_into_builder_with_recovery(path_str, &mut builder, recovery);
+
+        let positions: Vec<usize> = match result {
+            Ok(errors) => errors.iter().map(|e| e.position).collect(),
+            Err(e) => vec![e.position],
+        };
+
+        assert_eq!(expected_commands, builder.get_path_commands());
+        assert_eq!(expected_error_positions, &positions[..]);
+    }
+
+    #[test]
+    fn strict_recovery_discards_path() {
+        test_recovery(
+            "M10 20 L30 40",
+            PathDataRecovery::Strict,
+            &vec![moveto(10.0, 20.0), lineto(30.0, 40.0)],
+            &[],
+        );
+
+        test_recovery(
+            "M10 20 L30 40 L x",
+            PathDataRecovery::Strict,
+            &vec![],
+            &[16],
+        );
+    }
+
+    #[test]
+    fn render_prefix_recovery_keeps_prefix() {
+        test_recovery(
+            "M10 20 L30 40 L x L 50 60",
+            PathDataRecovery::RenderPrefix,
+            &vec![moveto(10.0, 20.0), lineto(30.0, 40.0)],
+            &[16],
+        );
+    }
+
+    #[test]
+    fn skip_token_recovery_resumes_at_next_command() {
+        test_recovery(
+            "M10 20 L30 40 L x L 50 60",
+            PathDataRecovery::SkipToken,
+            &vec![moveto(10.0, 20.0), lineto(30.0, 40.0), lineto(50.0, 60.0)],
+            &[16],
+        );
+
+        test_recovery(
+            "M10 20 L L50 60 Z",
+            PathDataRecovery::SkipToken,
+            &vec![moveto(10.0, 20.0), lineto(50.0, 60.0), closepath()],
+            &[9],
+        );
+
+        test_recovery(
+            "M10 20 L30 x 40 L50 60 Q",
+            PathDataRecovery::SkipToken,
+            &vec![moveto(10.0, 20.0), lineto(50.0, 60.0)],
+            &[11, 24],
+        );
+    }
+
+    #[test]
+    fn skip_token_recovery_needs_moveto() {
+        test_recovery(
+            "L10 20 x m30 40 l10 10",
+            PathDataRecovery::SkipToken,
+            &vec![moveto(30.0, 40.0), lineto(40.0, 50.0)],
+            &[0],
+        );
+
+        test_recovery(
+            "x",
+            PathDataRecovery::SkipToken,
+            &vec![],
+            &[0],
+        );
+    }
 }
@@ -1,22 +1,22 @@
 use cairo;
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
-use std::cell::RefCell;
 use std::f64;
 
 use crate::allowed_url::Fragment;
 use crate::aspect_ratio::*;
 use crate::bbox::*;
 use crate::coord_units::CoordUnits;
-use crate::drawing_ctx::{DrawingCtx, NodeStack};
+use crate::drawing_ctx::DrawingCtx;
 use crate::error::{AcquireError, AttributeResultExt, RenderingError};
 use crate::float_eq_cairo::ApproxEqCairo;
 use crate::length::*;
 use crate::node::*;
-use crate::paint_server::{AsPaintSource, PaintSource};
+use crate::paint_server::{AsPaintSource, PaintSource, Resolve};
 use crate::parsers::ParseValue;
 use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
 use crate::rect::RectangleExt;
+use crate::sanitize;
 use crate::unit_interval::UnitInterval;
 use crate::viewbox::*;
 
@@ -78,7 +78,7 @@ enum Children {
 /// patterns, we store all fields as Option<T> - if None, it means
 /// that the field is not specified; if Some(T), it means that the
 /// field was specified.
-struct UnresolvedPattern {
+pub struct UnresolvedPattern {
     common: Common,
 
     // Point back to our corresponding node, or to the fallback node which has children.
@@ -105,13 +105,15 @@ pub struct ResolvedPattern {
 
     // Link to the node whose children are the pattern's resolved children.
     children: Children,
+
+    // Whether the children come from a fallback rather than from the pattern itself.
+    children_from_fallback: bool,
 }
 
 #[derive(Default)]
 pub struct Pattern {
     common: Common,
     fallback: Option<Fragment>,
-    resolved: RefCell<Option<ResolvedPattern>>,
 }
 
 impl NodeTrait for Pattern {
@@ -153,67 +155,23 @@ impl NodeTrait for Pattern {
 }
 
 impl PaintSource for Pattern {
-    type Resolved = ResolvedPattern;
-
-    fn resolve(
-        &self,
-        node: &RsvgNode,
-        draw_ctx: &mut DrawingCtx,
-    ) -> Result<Self::Resolved, AcquireError> {
-        let mut resolved = self.resolved.borrow_mut();
-        if let Some(ref pattern) = *resolved {
-            return Ok(pattern.clone());
-        }
-
-        let Unresolved {
-            mut pattern,
-            mut fallback,
-        } = self.get_unresolved(node);
-
-        let mut stack = NodeStack::new();
-
-        while !pattern.is_resolved() {
-            if let Some(ref fragment) = fallback {
-                match draw_ctx.acquire_node(&fragment, &[NodeType::Pattern]) {
-                    Ok(acquired) => {
-                        let acquired_node = acquired.get();
-
-                        if stack.contains(acquired_node) {
-                            return Err(AcquireError::CircularReference(acquired_node.clone()));
-                        }
-
-                        let borrowed_node = acquired_node.borrow();
-                        let borrowed_pattern = borrowed_node.get_impl::<Pattern>();
-                        let unresolved = borrowed_pattern.get_unresolved(&acquired_node);
-
-                        pattern = pattern.resolve_from_fallback(&unresolved.pattern);
-                        fallback = unresolved.fallback;
-
-                        stack.push(acquired_node);
-                    }
-
-                    Err(AcquireError::MaxReferencesExceeded) => {
-                        return Err(AcquireError::MaxReferencesExceeded)
-                    }
-
-                    Err(e) => {
-                        rsvg_log!("Stopping pattern resolution: {}", e);
-                        pattern = pattern.resolve_from_defaults();
-                        break;
-                    }
-                }
-            } else {
-                pattern = pattern.resolve_from_defaults();
-                break;
-            }
-        }
-
-        let pattern = pattern.to_resolved();
-
-        *resolved = Some(pattern.clone());
+    type Unresolved = UnresolvedPattern;
+}
 
-        Ok(pattern)
-    }
+/// What a rendered pattern tile depends on, to find tiles that can be reused
+///
+/// The position of the tile is not part of this, since it is given by the matrix of the
+/// surface pattern.
+#[derive(Clone, PartialEq)]
+pub struct PatternTileKey {
+    node: RsvgNode,
+    surface_type: cairo::SurfaceType,
+    width: i32,
+    height: i32,
+    content_affine: cairo::Matrix,
+    view_box_size: (f64, f64),
+    opacity: UnitInterval,
+    overflow_visible: bool,
 }
 
 impl AsPaintSource for ResolvedPattern {
@@ -221,7 +179,7 @@ impl AsPaintSource for ResolvedPattern {
         self,
         values: &ComputedValues,
         draw_ctx: &mut DrawingCtx,
-        _opacity: UnitInterval,
+        opacity: UnitInterval,
         bbox: &BoundingBox,
     ) -> Result<bool, RenderingError> {
         let node_with_children = if let Some(n) = self.children.node_with_children() {
@@ -232,12 +190,42 @@ impl AsPaintSource for ResolvedPattern {
             return Ok(false);
         };
 
+        // When the children come from a pattern further down the chain of
+        // fallbacks, that pattern is not in the stack of acquired nodes.  Keep
+        // it acquired while its children are drawn, so that content which
+        // references it again gets detected as a reference cycle.
+        let _acquired_children = if self.children_from_fallback {
+            match draw_ctx.acquire_node_ref(&node_with_children) {
+                Ok(acquired) => Some(acquired),
+
+                Err(AcquireError::CircularReference(node)) => {
+                    rsvg_log!("circular reference in the content of pattern {}", node);
+                    return Ok(false);
+                }
+
+                Err(_) => unreachable!(),
+            }
+        } else {
+            None
+        };
+
         let units = self.units;
         let content_units = self.content_units;
         let pattern_affine = self.affine;
         let vbox = self.vbox;
         let preserve_aspect_ratio = self.preserve_aspect_ratio;
 
+        // objectBoundingBox requires a non-empty bbox, just like for gradients
+        let needs_bbox = units == PatternUnits(CoordUnits::ObjectBoundingBox)
+            || (vbox.is_none()
+                && content_units == PatternContentUnits(CoordUnits::ObjectBoundingBox));
+
+        let bbrect = match bbox.rect {
+            Some(r) if !r.is_empty() => r,
+            _ if needs_bbox => return Ok(false),
+            _ => cairo::Rectangle::from_size(1.0, 1.0),
+        };
+
         let (pattern_x, pattern_y, pattern_width, pattern_height) = {
             let params = if units == PatternUnits(CoordUnits::ObjectBoundingBox) {
                 draw_ctx.push_view_box(1.0, 1.0)
@@ -260,7 +248,6 @@ impl AsPaintSource for ResolvedPattern {
 
         match units {
             PatternUnits(CoordUnits::ObjectBoundingBox) => {
-                let bbrect = bbox.rect.unwrap();
                 bbwscale = bbrect.width;
                 bbhscale = bbrect.height;
             }
@@ -272,18 +259,27 @@ impl AsPaintSource for ResolvedPattern {
         }
 
         let cr = draw_ctx.get_cairo_context();
-        let affine = cr.get_matrix();
-        let taffine = cairo::Matrix::multiply(&pattern_affine, &affine);
-
-        let mut scwscale = (taffine.xx * taffine.xx + taffine.xy * taffine.xy).sqrt();
-        let mut schscale = (taffine.yx * taffine.yx + taffine.yy * taffine.yy).sqrt();
-
-        let pw: i32 = (pattern_width * bbwscale * scwscale) as i32;
-        let ph: i32 = (pattern_height * bbhscale * schscale) as i32;
 
         let scaled_width = pattern_width * bbwscale;
         let scaled_height = pattern_height * bbhscale;
 
+        // The current matrix does not include the device scale of the target surface,
+        // as for HiDPI surfaces; without it, the tile would be rendered at a lower
+        // resolution and upscaled.
+        let (x_device_scale, y_device_scale) = cr.get_target().get_device_scale();
+        let device_affine = cairo::Matrix::multiply(
+            &cr.get_matrix(),
+            &cairo::Matrix::new(x_device_scale, 0.0, 0.0, y_device_scale, 0.0, 0.0),
+        );
+
+        let (pw, ph) = device_tile_size(
+            &pattern_affine,
+            &device_affine,
+            scaled_width,
+            scaled_height,
+            draw_ctx.max_pattern_tile_size(),
+        );
+
         if scaled_width.abs() < f64::EPSILON
             || scaled_height.abs() < f64::EPSILON
             || pw < 1
@@ -292,15 +288,14 @@ impl AsPaintSource for ResolvedPattern {
             return Ok(false);
         }
 
-        scwscale = f64::from(pw) / scaled_width;
-        schscale = f64::from(ph) / scaled_height;
+        let scwscale = f64::from(pw) / scaled_width;
+        let schscale = f64::from(ph) / scaled_height;
 
         let mut affine: cairo::Matrix = cairo::Matrix::identity();
 
         // Create the pattern coordinate system
         match units {
             PatternUnits(CoordUnits::ObjectBoundingBox) => {
-                let bbrect = bbox.rect.unwrap();
                 affine.translate(
                     bbrect.x + pattern_x * bbrect.width,
                     bbrect.y + pattern_y * bbrect.height,
@@ -318,7 +313,7 @@ impl AsPaintSource for ResolvedPattern {
         let mut caffine: cairo::Matrix;
 
         // Create the pattern contents coordinate system
-        let _params = if let Some(vbox) = vbox {
+        let params = if let Some(vbox) = vbox {
             // If there is a vbox, use that
             let (mut x, mut y, w, h) = preserve_aspect_ratio.compute(
                 &vbox,
@@ -333,8 +328,6 @@ impl AsPaintSource for ResolvedPattern {
             draw_ctx.push_view_box(vbox.width, vbox.height)
         } else if content_units == PatternContentUnits(CoordUnits::ObjectBoundingBox) {
             // If coords are in terms of the bounding box, use them
-            let bbrect = bbox.rect.unwrap();
-
             caffine = cairo::Matrix::identity();
             caffine.scale(bbrect.width, bbrect.height);
 
@@ -355,34 +348,102 @@ impl AsPaintSource for ResolvedPattern {
             affine = cairo::Matrix::multiply(&scalematrix, &affine);
         }
 
-        // Draw to another surface
+        if !sanitize::is_valid_matrix(&affine) || !sanitize::is_valid_matrix(&caffine) {
+            rsvg_log!("(not using pattern with an invalid transform)");
+            return Ok(false);
+        }
 
         let cr_save = draw_ctx.get_cairo_context();
 
-        let surface = cr_save
-            .get_target()
-            .create_similar(cairo::Content::ColorAlpha, pw, ph);
+        let overflow_visible = values.is_overflow();
+
+        // Shapes with the same pattern often get the same tile, so reuse it if it has
+        // been rendered already
+        let tile_key = PatternTileKey {
+            node: node_with_children.clone(),
+            surface_type: cr_save.get_target().get_type(),
+            width: pw,
+            height: ph,
+            content_affine: caffine,
+            view_box_size: (params.view_box_width, params.view_box_height),
+            opacity,
+            overflow_visible,
+        };
+
+        let (surface, res) = if let Some(surface) = draw_ctx.lookup_pattern_tile(&tile_key) {
+            (surface, Ok(()))
+        } else {
+            // Draw to another surface
+
+            draw_ctx.resources().add_surface(pw, ph)?;
 
-        let cr_pattern = cairo::Context::new(&surface);
+            let surface = create_tile_surface(&cr_save.get_target(), pw, ph);
 
-        draw_ctx.set_cairo_context(&cr_pattern);
+            let cr_pattern = cairo::Context::new(&surface);
 
-        // Set up transformations to be determined by the contents units
+            draw_ctx.set_cairo_context(&cr_pattern);
 
-        // Draw everything
-        let pattern_cascaded = CascadedValues::new_from_node(&node_with_children);
-        let pattern_values = pattern_cascaded.get();
+            // Set up transformations to be determined by the contents units
 
-        cr_pattern.set_matrix(caffine);
+            // Draw everything
+            let pattern_cascaded = CascadedValues::new_from_node(&node_with_children);
+            let pattern_values = pattern_cascaded.get();
 
-        let res =
-            draw_ctx.with_discrete_layer(&node_with_children, pattern_values, false, &mut |dc| {
-                node_with_children.draw_children(&pattern_cascaded, dc, false)
-            });
+            // With overflow:visible, the content that sticks out of a tile is not clipped
+            // away, but shows up over the neighboring tiles.  Since all the tiles are the
+            // same, we get that by drawing the content again in this tile, offset by one
+            // tile in each direction.
+            let offsets: &[f64] = if overflow_visible {
+                &[-1.0, 0.0, 1.0]
+            } else {
+                &[0.0]
+            };
+
+            let res = offsets
+                .iter()
+                .flat_map(|&ty| offsets.iter().map(move |&tx| (tx, ty)))
+                .try_for_each(|(tx, ty)| {
+                    let offset = cairo::Matrix::new(
+                        1.0,
+                        0.0,
+                        0.0,
+                        1.0,
+                        tx * f64::from(pw),
+                        ty * f64::from(ph),
+                    );
+                    cr_pattern.set_matrix(cairo::Matrix::multiply(&caffine, &offset));
+
+                    draw_ctx
+                        .with_discrete_layer(
+                            &node_with_children,
+                            pattern_values,
+                            false,
+                            &mut |dc| {
+                                node_with_children.draw_children(&pattern_cascaded, dc, false)
+                            },
+                        )
+                        .map(|_| ())
+                });
+
+            // Apply fill-opacity or stroke-opacity by scaling the premultiplied tile
+
+            let UnitInterval(o) = opacity;
+            if o < 1.0 {
+                cr_pattern.set_operator(cairo::Operator::DestIn);
+                cr_pattern.set_source_rgba(0.0, 0.0, 0.0, o);
+                cr_pattern.paint();
+            }
+
+            // Return to the original coordinate system and rendering context
 
-        // Return to the original coordinate system and rendering context
+            draw_ctx.set_cairo_context(&cr_save);
 
-        draw_ctx.set_cairo_context(&cr_save);
+            if res.is_ok() {
+                draw_ctx.cache_pattern_tile(tile_key, &surface);
+            }
+
+            (surface, res)
+        };
 
         // Set the final surface as a Cairo pattern into the Cairo context
 
@@ -393,30 +454,54 @@ impl AsPaintSource for ResolvedPattern {
         matrix.invert();
 
         surface_pattern.set_matrix(matrix);
-        surface_pattern.set_filter(cairo::Filter::Best);
+        surface_pattern.set_filter(if draw_ctx.is_approximate() {
+            cairo::Filter::Fast
+        } else {
+            cairo::Filter::Best
+        });
 
         cr_save.set_source(&surface_pattern);
 
-        res.and_then(|_| Ok(true))
+        res.map(|_| true)
     }
 }
 
-impl UnresolvedPattern {
-    fn to_resolved(self) -> ResolvedPattern {
+impl Resolve for UnresolvedPattern {
+    type Resolved = ResolvedPattern;
+
+    const FALLBACK_TYPES: &'static [NodeType] = &[NodeType::Pattern];
+
+    fn from_node(node: &RsvgNode) -> (UnresolvedPattern, Option<Fragment>) {
+        let Unresolved { pattern, fallback } =
+            node.borrow().get_impl::<Pattern>().get_unresolved(node);
+
+        (pattern, fallback)
+    }
+
+    fn to_resolved(self, node: &RsvgNode) -> ResolvedPattern {
         assert!(self.is_resolved());
 
+        let children = self.children.to_resolved();
+        let children_from_fallback = children.node_with_children().map_or(false, |n| n != *node);
+
+        // Like for gradients, a transform on the pattern element itself is applied
+        // after the patternTransform.
+        let affine =
+            cairo::Matrix::multiply(&self.common.affine.unwrap(), &node.borrow().get_transform());
+
         ResolvedPattern {
             units: self.common.units.unwrap(),
             content_units: self.common.content_units.unwrap(),
             vbox: self.common.vbox.unwrap(),
             preserve_aspect_ratio: self.common.preserve_aspect_ratio.unwrap(),
-            affine: self.common.affine.unwrap(),
+            affine,
             x: self.common.x.unwrap(),
             y: self.common.y.unwrap(),
             width: self.common.width.unwrap(),
             height: self.common.height.unwrap(),
 
-            children: self.children.to_resolved(),
+            children,
+            children_from_fallback,
         }
     }
 
@@ -579,6 +664,54 @@ impl Pattern {
     }
 }
 
+/// Computes the size in device pixels of the surface for a pattern tile.
+///
+/// The tile is `width` by `height` in pattern space; the `pattern_affine` and
+/// the current `user_affine` together map it to device space.  Each axis
+/// is scaled by the length of the transformed unit vector for that axis, so
+/// that tiles which end up stretched by the `patternTransform` get rendered
+/// at the full device resolution instead of being upscaled afterwards.
+///
+/// Each side is clamped to `max_size`, so that huge transforms do not allocate huge
+/// surfaces; such tiles get rendered at a lower resolution instead.
+fn device_tile_size(
+    pattern_affine: &cairo::Matrix,
+    user_affine: &cairo::Matrix,
+    width: f64,
+    height: f64,
+    max_size: i32,
+) -> (i32, i32) {
+    let taffine = cairo::Matrix::multiply(pattern_affine, user_affine);
+
+    let xscale = taffine.xx.hypot(taffine.yx);
+    let yscale = taffine.xy.hypot(taffine.yy);
+
+    let max_size = f64::from(max_size);
+
+    let pw = (width * xscale).round().min(max_size) as i32;
+    let ph = (height * yscale).round().min(max_size) as i32;
+
+    (pw, ph)
+}
+
+/// Creates a surface like `target` for a pattern tile of `width` by `height` pixels.
+///
+/// `cairo_surface_create_similar()` gives the new surface the device scale of the
+/// original one, and multiplies its size by that scale.  The tile's size already
+/// includes the device scale, so it is created from a surface without one.
+fn create_tile_surface(target: &cairo::Surface, width: i32, height: i32) -> cairo::Surface {
+    let (x_scale, y_scale) = target.get_device_scale();
+
+    if x_scale.approx_eq_cairo(1.0) && y_scale.approx_eq_cairo(1.0) {
+        return target.create_similar(cairo::Content::ColorAlpha, width, height);
+    }
+
+    let unscaled = target.create_similar(cairo::Content::ColorAlpha, 1, 1);
+    unscaled.set_device_scale(1.0, 1.0);
+
+    unscaled.create_similar(cairo::Content::ColorAlpha, width, height)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -601,4 +734,42 @@ mod tests {
         let pattern = pattern.resolve_from_defaults();
         assert!(pattern.is_resolved());
     }
+
+    #[test]
+    fn tile_size_accounts_for_scale_in_pattern_transform() {
+        let identity = cairo::Matrix::identity();
+        let mut scale = cairo::Matrix::identity();
+        scale.scale(20.0, 3.0);
+
+        assert_eq!(device_tile_size(&scale, &identity, 2.0, 2.0, 4096), (40, 6));
+        assert_eq!(device_tile_size(&identity, &scale, 2.0, 2.0, 4096), (40, 6));
+    }
+
+    #[test]
+    fn tile_size_accounts_for_rotation_in_pattern_transform() {
+        // rotate(90) scale(25, 1)
+        let mut affine = cairo::Matrix::identity();
+        affine.rotate(std::f64::consts::FRAC_PI_2);
+        affine.scale(25.0, 1.0);
+
+        assert_eq!(
+            device_tile_size(&affine, &cairo::Matrix::identity(), 2.0, 2.0, 4096),
+            (50, 2)
+        );
+
+        let mut device = cairo::Matrix::identity();
+        device.scale(1.0, 4.0);
+
+        assert_eq!(device_tile_size(&affine, &device, 2.0, 2.0, 4096), (200, 2));
+    }
+
+    #[test]
+    fn tile_size_is_clamped() {
+        let identity = cairo::Matrix::identity();
+        let mut huge = cairo::Matrix::identity();
+        huge.scale(1e9, 3.0);
+
+        assert_eq!(device_tile_size(&huge, &identity, 2.0, 2.0, 4096), (4096, 6));
+        assert_eq!(device_tile_size(&huge, &identity, 2.0, 2.0, 100), (100, 6));
+    }
 }
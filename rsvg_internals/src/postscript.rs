@@ -0,0 +1,50 @@
+//! Options for rendering documents as PostScript or Encapsulated PostScript.
+//!
+//! Documents are rendered through cairo's PostScript surface.  For EPS files that get
+//! embedded in other documents, the page can be cropped to the ink extents of the
+//! drawing, so that the `%%BoundingBox` doesn't include the empty parts of the canvas.
+
+use cairo;
+
+/// Level of the PostScript language that the output may use
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PostScriptLevel {
+    /// PostScript Level 2, for older printers and interpreters
+    Level2,
+
+    /// PostScript Level 3
+    Level3,
+}
+
+impl From<PostScriptLevel> for cairo::PsLevel {
+    fn from(level: PostScriptLevel) -> cairo::PsLevel {
+        match level {
+            PostScriptLevel::Level2 => cairo::PsLevel::_2,
+            PostScriptLevel::Level3 => cairo::PsLevel::_3,
+        }
+    }
+}
+
+/// How to render a document as PostScript
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PostScriptOptions {
+    /// Highest level of the PostScript language to use
+    pub level: PostScriptLevel,
+
+    /// Whether to write Encapsulated PostScript instead of a PostScript document
+    pub eps: bool,
+
+    /// Whether to crop the page to the ink extents of the drawing
+    pub tight_bounding_box: bool,
+}
+
+impl Default for PostScriptOptions {
+    /// A PostScript Level 3 document with the whole canvas
+    fn default() -> PostScriptOptions {
+        PostScriptOptions {
+            level: PostScriptLevel::Level3,
+            eps: false,
+            tight_bounding_box: false,
+        }
+    }
+}
@@ -7,7 +7,7 @@ use crate::error::*;
 use crate::parsers::Parse;
 use crate::property_bag::PropertyBag;
 use crate::property_defs::*;
-use crate::property_macros::Property;
+use crate::property_macros::{Property, ToCssValue};
 
 /// Representation of a single CSS property value.
 ///
@@ -61,13 +61,39 @@ where
     }
 }
 
+impl<T> ToCssValue for SpecifiedValue<T>
+where
+    T: Property<ComputedValues> + Clone + Default + ToCssValue,
+{
+    fn to_css_value(&self) -> String {
+        match *self {
+            SpecifiedValue::Unspecified => "".to_string(),
+            SpecifiedValue::Inherit => "inherit".to_string(),
+            SpecifiedValue::Specified(ref v) => v.to_css_value(),
+        }
+    }
+}
+
 /// Embodies "which property is this" plus the property's value
 pub enum ParsedProperty {
+    Animation(SpecifiedValue<Animation>), // this is a shorthand property
+    AnimationDelay(SpecifiedValue<AnimationDelay>),
+    AnimationDirection(SpecifiedValue<AnimationDirection>),
+    AnimationDuration(SpecifiedValue<AnimationDuration>),
+    AnimationFillMode(SpecifiedValue<AnimationFillMode>),
+    AnimationIterationCount(SpecifiedValue<AnimationIterationCount>),
+    AnimationName(SpecifiedValue<AnimationName>),
+    AnimationPlayState(SpecifiedValue<AnimationPlayState>),
+    AnimationTimingFunction(SpecifiedValue<AnimationTimingFunction>),
     BaselineShift(SpecifiedValue<BaselineShift>),
     ClipPath(SpecifiedValue<ClipPath>),
     ClipRule(SpecifiedValue<ClipRule>),
     Color(SpecifiedValue<Color>),
+    ColorInterpolation(SpecifiedValue<ColorInterpolation>),
     ColorInterpolationFilters(SpecifiedValue<ColorInterpolationFilters>),
+    Cx(SpecifiedValue<Cx>),
+    Cy(SpecifiedValue<Cy>),
+    D(SpecifiedValue<D>),
     Direction(SpecifiedValue<Direction>),
     Display(SpecifiedValue<Display>),
     EnableBackground(SpecifiedValue<EnableBackground>),
@@ -83,6 +109,8 @@ pub enum ParsedProperty {
     FontStyle(SpecifiedValue<FontStyle>),
     FontVariant(SpecifiedValue<FontVariant>),
     FontWeight(SpecifiedValue<FontWeight>),
+    Height(SpecifiedValue<Height>),
+    Isolation(SpecifiedValue<Isolation>),
     LetterSpacing(SpecifiedValue<LetterSpacing>),
     LightingColor(SpecifiedValue<LightingColor>),
     Marker(SpecifiedValue<Marker>), // this is a shorthand property
@@ -90,8 +118,17 @@ pub enum ParsedProperty {
     MarkerMid(SpecifiedValue<MarkerMid>),
     MarkerStart(SpecifiedValue<MarkerStart>),
     Mask(SpecifiedValue<Mask>),
+    MaskType(SpecifiedValue<MaskType>),
+    MixBlendMode(SpecifiedValue<MixBlendMode>),
+    OffsetDistance(SpecifiedValue<OffsetDistance>),
+    OffsetPath(SpecifiedValue<OffsetPath>),
+    OffsetRotate(SpecifiedValue<OffsetRotate>),
     Opacity(SpecifiedValue<Opacity>),
     Overflow(SpecifiedValue<Overflow>),
+    PaintOrder(SpecifiedValue<PaintOrder>),
+    R(SpecifiedValue<R>),
+    Rx(SpecifiedValue<Rx>),
+    Ry(SpecifiedValue<Ry>),
     ShapeRendering(SpecifiedValue<ShapeRendering>),
     StopColor(SpecifiedValue<StopColor>),
     StopOpacity(SpecifiedValue<StopOpacity>),
@@ -106,9 +143,115 @@ pub enum ParsedProperty {
     TextAnchor(SpecifiedValue<TextAnchor>),
     TextDecoration(SpecifiedValue<TextDecoration>),
     TextRendering(SpecifiedValue<TextRendering>),
+    Transform(SpecifiedValue<Transform>),
+    TransformOrigin(SpecifiedValue<TransformOrigin>),
+    Transition(SpecifiedValue<Transition>), // this is a shorthand property
+    TransitionDelay(SpecifiedValue<TransitionDelay>),
+    TransitionDuration(SpecifiedValue<TransitionDuration>),
+    TransitionProperty(SpecifiedValue<TransitionProperty>),
+    TransitionTimingFunction(SpecifiedValue<TransitionTimingFunction>),
     UnicodeBidi(SpecifiedValue<UnicodeBidi>),
+    VectorEffect(SpecifiedValue<VectorEffect>),
     Visibility(SpecifiedValue<Visibility>),
+    WhiteSpace(SpecifiedValue<WhiteSpace>),
+    Width(SpecifiedValue<Width>),
+    WordSpacing(SpecifiedValue<WordSpacing>),
     WritingMode(SpecifiedValue<WritingMode>),
+    X(SpecifiedValue<X>),
+    Y(SpecifiedValue<Y>),
+}
+
+impl ToCssValue for ParsedProperty {
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    fn to_css_value(&self) -> String {
+        use crate::properties::ParsedProperty::*;
+
+        match *self {
+            Animation(ref x)                 => x.to_css_value(),
+            AnimationDelay(ref x)            => x.to_css_value(),
+            AnimationDirection(ref x)        => x.to_css_value(),
+            AnimationDuration(ref x)         => x.to_css_value(),
+            AnimationFillMode(ref x)         => x.to_css_value(),
+            AnimationIterationCount(ref x)   => x.to_css_value(),
+            AnimationName(ref x)             => x.to_css_value(),
+            AnimationPlayState(ref x)        => x.to_css_value(),
+            AnimationTimingFunction(ref x)   => x.to_css_value(),
+            BaselineShift(ref x)             => x.to_css_value(),
+            ClipPath(ref x)                  => x.to_css_value(),
+            ClipRule(ref x)                  => x.to_css_value(),
+            Color(ref x)                     => x.to_css_value(),
+            ColorInterpolation(ref x)        => x.to_css_value(),
+            ColorInterpolationFilters(ref x) => x.to_css_value(),
+            Cx(ref x)                        => x.to_css_value(),
+            Cy(ref x)                        => x.to_css_value(),
+            D(ref x)                         => x.to_css_value(),
+            Direction(ref x)                 => x.to_css_value(),
+            Display(ref x)                   => x.to_css_value(),
+            EnableBackground(ref x)          => x.to_css_value(),
+            Fill(ref x)                      => x.to_css_value(),
+            FillOpacity(ref x)               => x.to_css_value(),
+            FillRule(ref x)                  => x.to_css_value(),
+            Filter(ref x)                    => x.to_css_value(),
+            FloodColor(ref x)                => x.to_css_value(),
+            FloodOpacity(ref x)              => x.to_css_value(),
+            FontFamily(ref x)                => x.to_css_value(),
+            FontSize(ref x)                  => x.to_css_value(),
+            FontStretch(ref x)               => x.to_css_value(),
+            FontStyle(ref x)                 => x.to_css_value(),
+            FontVariant(ref x)               => x.to_css_value(),
+            FontWeight(ref x)                => x.to_css_value(),
+            Height(ref x)                    => x.to_css_value(),
+            Isolation(ref x)                 => x.to_css_value(),
+            LetterSpacing(ref x)             => x.to_css_value(),
+            LightingColor(ref x)             => x.to_css_value(),
+            Marker(ref x)                    => x.to_css_value(),
+            MarkerEnd(ref x)                 => x.to_css_value(),
+            MarkerMid(ref x)                 => x.to_css_value(),
+            MarkerStart(ref x)               => x.to_css_value(),
+            Mask(ref x)                      => x.to_css_value(),
+            MaskType(ref x)                  => x.to_css_value(),
+            MixBlendMode(ref x)              => x.to_css_value(),
+            OffsetDistance(ref x)            => x.to_css_value(),
+            OffsetPath(ref x)                => x.to_css_value(),
+            OffsetRotate(ref x)              => x.to_css_value(),
+            Opacity(ref x)                   => x.to_css_value(),
+            Overflow(ref x)                  => x.to_css_value(),
+            PaintOrder(ref x)                => x.to_css_value(),
+            R(ref x)                         => x.to_css_value(),
+            Rx(ref x)                        => x.to_css_value(),
+            Ry(ref x)                        => x.to_css_value(),
+            ShapeRendering(ref x)            => x.to_css_value(),
+            StopColor(ref x)                 => x.to_css_value(),
+            StopOpacity(ref x)               => x.to_css_value(),
+            Stroke(ref x)                    => x.to_css_value(),
+            StrokeDasharray(ref x)           => x.to_css_value(),
+            StrokeDashoffset(ref x)          => x.to_css_value(),
+            StrokeLinecap(ref x)             => x.to_css_value(),
+            StrokeLinejoin(ref x)            => x.to_css_value(),
+            StrokeOpacity(ref x)             => x.to_css_value(),
+            StrokeMiterlimit(ref x)          => x.to_css_value(),
+            StrokeWidth(ref x)               => x.to_css_value(),
+            TextAnchor(ref x)                => x.to_css_value(),
+            TextDecoration(ref x)            => x.to_css_value(),
+            TextRendering(ref x)             => x.to_css_value(),
+            Transform(ref x)                 => x.to_css_value(),
+            TransformOrigin(ref x)           => x.to_css_value(),
+            Transition(ref x)                => x.to_css_value(),
+            TransitionDelay(ref x)           => x.to_css_value(),
+            TransitionDuration(ref x)        => x.to_css_value(),
+            TransitionProperty(ref x)        => x.to_css_value(),
+            TransitionTimingFunction(ref x)  => x.to_css_value(),
+            UnicodeBidi(ref x)               => x.to_css_value(),
+            VectorEffect(ref x)              => x.to_css_value(),
+            Visibility(ref x)                => x.to_css_value(),
+            WhiteSpace(ref x)                => x.to_css_value(),
+            Width(ref x)                     => x.to_css_value(),
+            WordSpacing(ref x)               => x.to_css_value(),
+            WritingMode(ref x)               => x.to_css_value(),
+            X(ref x)                         => x.to_css_value(),
+            Y(ref x)                         => x.to_css_value(),
+        }
+    }
 }
 
 /// Holds the specified CSS properties
@@ -122,11 +265,23 @@ pub enum ParsedProperty {
 /// `Default` and `parsers::Parse`.
 #[derive(Default, Clone)]
 pub struct SpecifiedValues {
+    pub animation_delay: SpecifiedValue<AnimationDelay>,
+    pub animation_direction: SpecifiedValue<AnimationDirection>,
+    pub animation_duration: SpecifiedValue<AnimationDuration>,
+    pub animation_fill_mode: SpecifiedValue<AnimationFillMode>,
+    pub animation_iteration_count: SpecifiedValue<AnimationIterationCount>,
+    pub animation_name: SpecifiedValue<AnimationName>,
+    pub animation_play_state: SpecifiedValue<AnimationPlayState>,
+    pub animation_timing_function: SpecifiedValue<AnimationTimingFunction>,
     pub baseline_shift: SpecifiedValue<BaselineShift>,
     pub clip_path: SpecifiedValue<ClipPath>,
     pub clip_rule: SpecifiedValue<ClipRule>,
     pub color: SpecifiedValue<Color>,
+    pub color_interpolation: SpecifiedValue<ColorInterpolation>,
     pub color_interpolation_filters: SpecifiedValue<ColorInterpolationFilters>,
+    pub cx: SpecifiedValue<Cx>,
+    pub cy: SpecifiedValue<Cy>,
+    pub d: SpecifiedValue<D>,
     pub direction: SpecifiedValue<Direction>,
     pub display: SpecifiedValue<Display>,
     pub enable_background: SpecifiedValue<EnableBackground>,
@@ -142,14 +297,25 @@ pub struct SpecifiedValues {
     pub font_style: SpecifiedValue<FontStyle>,
     pub font_variant: SpecifiedValue<FontVariant>,
     pub font_weight: SpecifiedValue<FontWeight>,
+    pub height: SpecifiedValue<Height>,
+    pub isolation: SpecifiedValue<Isolation>,
     pub letter_spacing: SpecifiedValue<LetterSpacing>,
     pub lighting_color: SpecifiedValue<LightingColor>,
     pub marker_end: SpecifiedValue<MarkerEnd>,
     pub marker_mid: SpecifiedValue<MarkerMid>,
     pub marker_start: SpecifiedValue<MarkerStart>,
     pub mask: SpecifiedValue<Mask>,
+    pub mask_type: SpecifiedValue<MaskType>,
+    pub mix_blend_mode: SpecifiedValue<MixBlendMode>,
+    pub offset_distance: SpecifiedValue<OffsetDistance>,
+    pub offset_path: SpecifiedValue<OffsetPath>,
+    pub offset_rotate: SpecifiedValue<OffsetRotate>,
     pub opacity: SpecifiedValue<Opacity>,
     pub overflow: SpecifiedValue<Overflow>,
+    pub paint_order: SpecifiedValue<PaintOrder>,
+    pub r: SpecifiedValue<R>,
+    pub rx: SpecifiedValue<Rx>,
+    pub ry: SpecifiedValue<Ry>,
     pub shape_rendering: SpecifiedValue<ShapeRendering>,
     pub stop_color: SpecifiedValue<StopColor>,
     pub stop_opacity: SpecifiedValue<StopOpacity>,
@@ -164,20 +330,44 @@ pub struct SpecifiedValues {
     pub text_anchor: SpecifiedValue<TextAnchor>,
     pub text_decoration: SpecifiedValue<TextDecoration>,
     pub text_rendering: SpecifiedValue<TextRendering>,
+    pub transform: SpecifiedValue<Transform>,
+    pub transform_origin: SpecifiedValue<TransformOrigin>,
+    pub transition_delay: SpecifiedValue<TransitionDelay>,
+    pub transition_duration: SpecifiedValue<TransitionDuration>,
+    pub transition_property: SpecifiedValue<TransitionProperty>,
+    pub transition_timing_function: SpecifiedValue<TransitionTimingFunction>,
     pub unicode_bidi: SpecifiedValue<UnicodeBidi>,
+    pub vector_effect: SpecifiedValue<VectorEffect>,
     pub visibility: SpecifiedValue<Visibility>,
+    pub white_space: SpecifiedValue<WhiteSpace>,
+    pub width: SpecifiedValue<Width>,
+    pub word_spacing: SpecifiedValue<WordSpacing>,
     pub writing_mode: SpecifiedValue<WritingMode>,
+    pub x: SpecifiedValue<X>,
+    pub y: SpecifiedValue<Y>,
     pub xml_lang: SpecifiedValue<XmlLang>, // not a property, but a non-presentation attribute
     pub xml_space: SpecifiedValue<XmlSpace>, // not a property, but a non-presentation attribute
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct ComputedValues {
+    pub animation_delay: AnimationDelay,
+    pub animation_direction: AnimationDirection,
+    pub animation_duration: AnimationDuration,
+    pub animation_fill_mode: AnimationFillMode,
+    pub animation_iteration_count: AnimationIterationCount,
+    pub animation_name: AnimationName,
+    pub animation_play_state: AnimationPlayState,
+    pub animation_timing_function: AnimationTimingFunction,
     pub baseline_shift: BaselineShift,
     pub clip_path: ClipPath,
     pub clip_rule: ClipRule,
     pub color: Color,
+    pub color_interpolation: ColorInterpolation,
     pub color_interpolation_filters: ColorInterpolationFilters,
+    pub cx: Cx,
+    pub cy: Cy,
+    pub d: D,
     pub direction: Direction,
     pub display: Display,
     pub enable_background: EnableBackground,
@@ -193,14 +383,25 @@ pub struct ComputedValues {
     pub font_style: FontStyle,
     pub font_variant: FontVariant,
     pub font_weight: FontWeight,
+    pub height: Height,
+    pub isolation: Isolation,
     pub letter_spacing: LetterSpacing,
     pub lighting_color: LightingColor,
     pub marker_end: MarkerEnd,
     pub marker_mid: MarkerMid,
     pub marker_start: MarkerStart,
     pub mask: Mask,
+    pub mask_type: MaskType,
+    pub mix_blend_mode: MixBlendMode,
+    pub offset_distance: OffsetDistance,
+    pub offset_path: OffsetPath,
+    pub offset_rotate: OffsetRotate,
     pub opacity: Opacity,
     pub overflow: Overflow,
+    pub paint_order: PaintOrder,
+    pub r: R,
+    pub rx: Rx,
+    pub ry: Ry,
     pub shape_rendering: ShapeRendering,
     pub stop_color: StopColor,
     pub stop_opacity: StopOpacity,
@@ -215,17 +416,62 @@ pub struct ComputedValues {
     pub text_anchor: TextAnchor,
     pub text_decoration: TextDecoration,
     pub text_rendering: TextRendering,
+    pub transform: Transform,
+    pub transform_origin: TransformOrigin,
+    pub transition_delay: TransitionDelay,
+    pub transition_duration: TransitionDuration,
+    pub transition_property: TransitionProperty,
+    pub transition_timing_function: TransitionTimingFunction,
     pub unicode_bidi: UnicodeBidi,
+    pub vector_effect: VectorEffect,
     pub visibility: Visibility,
+    pub white_space: WhiteSpace,
+    pub width: Width,
+    pub word_spacing: WordSpacing,
     pub writing_mode: WritingMode,
+    pub x: X,
+    pub y: Y,
     pub xml_lang: XmlLang,   // not a property, but a non-presentation attribute
     pub xml_space: XmlSpace, // not a property, but a non-presentation attribute
+    pub root_font_size: FontSize, // not a property; the root element's font size, for rem units
 }
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
 pub fn parse_property(prop_name: &QualName, input: &mut Parser, accept_shorthands: bool) -> Result<ParsedProperty, ValueErrorKind> {
     // please keep these sorted
     match prop_name.expanded() {
+        _ if is_svg_property(prop_name, "animation") => {
+            if accept_shorthands {
+                Ok(ParsedProperty::Animation(parse_input(input)?))
+            } else {
+                Err(ValueErrorKind::UnknownProperty)
+            }
+        }
+
+        _ if is_svg_property(prop_name, "animation-delay") =>
+            Ok(ParsedProperty::AnimationDelay(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-direction") =>
+            Ok(ParsedProperty::AnimationDirection(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-duration") =>
+            Ok(ParsedProperty::AnimationDuration(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-fill-mode") =>
+            Ok(ParsedProperty::AnimationFillMode(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-iteration-count") =>
+            Ok(ParsedProperty::AnimationIterationCount(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-name") =>
+            Ok(ParsedProperty::AnimationName(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-play-state") =>
+            Ok(ParsedProperty::AnimationPlayState(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "animation-timing-function") =>
+            Ok(ParsedProperty::AnimationTimingFunction(parse_input(input)?)),
+
         expanded_name!(svg "baseline-shift") =>
             Ok(ParsedProperty::BaselineShift(parse_input(input)?)),
 
@@ -238,9 +484,21 @@ pub fn parse_property(prop_name: &QualName, input: &mut Parser, accept_shorthand
         expanded_name!(svg "color") =>
             Ok(ParsedProperty::Color(parse_input(input)?)),
 
+        expanded_name!(svg "color-interpolation") =>
+            Ok(ParsedProperty::ColorInterpolation(parse_input(input)?)),
+
         expanded_name!(svg "color-interpolation-filters") =>
             Ok(ParsedProperty::ColorInterpolationFilters(parse_input(input)?)),
 
+        expanded_name!(svg "cx") =>
+            Ok(ParsedProperty::Cx(parse_input(input)?)),
+
+        expanded_name!(svg "cy") =>
+            Ok(ParsedProperty::Cy(parse_input(input)?)),
+
+        expanded_name!(svg "d") =>
+            Ok(ParsedProperty::D(parse_input(input)?)),
+
         expanded_name!(svg "direction") =>
             Ok(ParsedProperty::Direction(parse_input(input)?)),
 
@@ -286,6 +544,12 @@ pub fn parse_property(prop_name: &QualName, input: &mut Parser, accept_shorthand
         expanded_name!(svg "font-weight") =>
             Ok(ParsedProperty::FontWeight(parse_input(input)?)),
 
+        expanded_name!(svg "height") =>
+            Ok(ParsedProperty::Height(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "isolation") =>
+            Ok(ParsedProperty::Isolation(parse_input(input)?)),
+
         expanded_name!(svg "letter-spacing") =>
             Ok(ParsedProperty::LetterSpacing(parse_input(input)?)),
 
@@ -312,12 +576,39 @@ pub fn parse_property(prop_name: &QualName, input: &mut Parser, accept_shorthand
         expanded_name!(svg "mask") =>
             Ok(ParsedProperty::Mask(parse_input(input)?)),
 
+        _ if is_svg_property(prop_name, "mask-type") =>
+            Ok(ParsedProperty::MaskType(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "mix-blend-mode") =>
+            Ok(ParsedProperty::MixBlendMode(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "offset-distance") =>
+            Ok(ParsedProperty::OffsetDistance(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "offset-path") =>
+            Ok(ParsedProperty::OffsetPath(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "offset-rotate") =>
+            Ok(ParsedProperty::OffsetRotate(parse_input(input)?)),
+
         expanded_name!(svg "opacity") =>
             Ok(ParsedProperty::Opacity(parse_input(input)?)),
 
         expanded_name!(svg "overflow") =>
             Ok(ParsedProperty::Overflow(parse_input(input)?)),
 
+        _ if is_svg_property(prop_name, "paint-order") =>
+            Ok(ParsedProperty::PaintOrder(parse_input(input)?)),
+
+        expanded_name!(svg "r") =>
+            Ok(ParsedProperty::R(parse_input(input)?)),
+
+        expanded_name!(svg "rx") =>
+            Ok(ParsedProperty::Rx(parse_input(input)?)),
+
+        expanded_name!(svg "ry") =>
+            Ok(ParsedProperty::Ry(parse_input(input)?)),
+
         expanded_name!(svg "shape-rendering") =>
             Ok(ParsedProperty::ShapeRendering(parse_input(input)?)),
 
@@ -360,19 +651,114 @@ pub fn parse_property(prop_name: &QualName, input: &mut Parser, accept_shorthand
         expanded_name!(svg "text-rendering") =>
             Ok(ParsedProperty::TextRendering(parse_input(input)?)),
 
+        expanded_name!(svg "transform") =>
+            Ok(ParsedProperty::Transform(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "transform-origin") =>
+            Ok(ParsedProperty::TransformOrigin(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "transition") => {
+            if accept_shorthands {
+                Ok(ParsedProperty::Transition(parse_input(input)?))
+            } else {
+                Err(ValueErrorKind::UnknownProperty)
+            }
+        }
+
+        _ if is_svg_property(prop_name, "transition-delay") =>
+            Ok(ParsedProperty::TransitionDelay(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "transition-duration") =>
+            Ok(ParsedProperty::TransitionDuration(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "transition-property") =>
+            Ok(ParsedProperty::TransitionProperty(parse_input(input)?)),
+
+        _ if is_svg_property(prop_name, "transition-timing-function") =>
+            Ok(ParsedProperty::TransitionTimingFunction(parse_input(input)?)),
+
         expanded_name!(svg "unicode-bidi") =>
             Ok(ParsedProperty::UnicodeBidi(parse_input(input)?)),
 
+        _ if is_svg_property(prop_name, "vector-effect") =>
+            Ok(ParsedProperty::VectorEffect(parse_input(input)?)),
+
         expanded_name!(svg "visibility") =>
             Ok(ParsedProperty::Visibility(parse_input(input)?)),
 
+        _ if is_svg_property(prop_name, "white-space") =>
+            Ok(ParsedProperty::WhiteSpace(parse_input(input)?)),
+
+        expanded_name!(svg "width") =>
+            Ok(ParsedProperty::Width(parse_input(input)?)),
+
+        expanded_name!(svg "word-spacing") =>
+            Ok(ParsedProperty::WordSpacing(parse_input(input)?)),
+
         expanded_name!(svg "writing-mode") =>
             Ok(ParsedProperty::WritingMode(parse_input(input)?)),
 
+        expanded_name!(svg "x") =>
+            Ok(ParsedProperty::X(parse_input(input)?)),
+
+        expanded_name!(svg "y") =>
+            Ok(ParsedProperty::Y(parse_input(input)?)),
+
         _ => Err(ValueErrorKind::UnknownProperty)
     }
 }
 
+/// Whether an attribute with the name of a property is parsed by the node instead of as
+/// a presentation attribute.
+fn is_parsed_by_node(attr: &QualName) -> bool {
+    match attr.expanded() {
+        // The transform and d attributes have a different syntax than the CSS
+        // properties; the nodes parse them by themselves.
+        expanded_name!(svg "transform") | expanded_name!(svg "d") => true,
+
+        // The geometry attributes are not presentation attributes in every element
+        // that has them, so the nodes parse them by themselves too.
+        expanded_name!(svg "x")
+        | expanded_name!(svg "y")
+        | expanded_name!(svg "cx")
+        | expanded_name!(svg "cy")
+        | expanded_name!(svg "r")
+        | expanded_name!(svg "rx")
+        | expanded_name!(svg "ry")
+        | expanded_name!(svg "width")
+        | expanded_name!(svg "height") => true,
+
+        _ => false,
+    }
+}
+
+/// Checks the value of a presentation attribute, without setting it anywhere.
+///
+/// Returns `Ok(false)` if the attribute is not a presentation attribute, and `Ok(true)`
+/// if it is one and its value is valid.
+pub fn check_presentation_attribute(attr: &QualName, value: &str) -> Result<bool, ValueErrorKind> {
+    match attr.expanded() {
+        expanded_name!(svg "xml:lang") | expanded_name!(svg "xml:space") => return Ok(false),
+        _ if is_parsed_by_node(attr) => return Ok(false),
+        _ => (),
+    }
+
+    let mut input = ParserInput::new(value);
+    let mut parser = Parser::new(&mut input);
+
+    match parse_property(attr, &mut parser, false) {
+        Ok(_) => Ok(true),
+        Err(ValueErrorKind::UnknownProperty) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Properties from newer specs may not have a static atom in markup5ever,
+// so they can't be matched with expanded_name!().
+fn is_svg_property(prop_name: &QualName, name: &str) -> bool {
+    prop_name.ns == ns!(svg) && &*prop_name.local == name
+}
+
 impl ComputedValues {
     pub fn is_overflow(&self) -> bool {
         match self.overflow {
@@ -388,6 +774,100 @@ impl ComputedValues {
             _ => false,
         }
     }
+
+    /// Returns the computed value of the property called `prop_name`, serialized as CSS.
+    ///
+    /// Returns `None` if `prop_name` is not a supported longhand property.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    pub fn get_property_value(&self, prop_name: &str) -> Option<String> {
+        // please keep these sorted
+        let value = match prop_name {
+            "animation-delay"             => self.animation_delay.to_css_value(),
+            "animation-direction"         => self.animation_direction.to_css_value(),
+            "animation-duration"          => self.animation_duration.to_css_value(),
+            "animation-fill-mode"         => self.animation_fill_mode.to_css_value(),
+            "animation-iteration-count"   => self.animation_iteration_count.to_css_value(),
+            "animation-name"              => self.animation_name.to_css_value(),
+            "animation-play-state"        => self.animation_play_state.to_css_value(),
+            "animation-timing-function"   => self.animation_timing_function.to_css_value(),
+            "baseline-shift"              => self.baseline_shift.to_css_value(),
+            "clip-path"                   => self.clip_path.to_css_value(),
+            "clip-rule"                   => self.clip_rule.to_css_value(),
+            "color"                       => self.color.to_css_value(),
+            "color-interpolation"         => self.color_interpolation.to_css_value(),
+            "color-interpolation-filters" => self.color_interpolation_filters.to_css_value(),
+            "cx"                          => self.cx.to_css_value(),
+            "cy"                          => self.cy.to_css_value(),
+            "d"                           => self.d.to_css_value(),
+            "direction"                   => self.direction.to_css_value(),
+            "display"                     => self.display.to_css_value(),
+            "enable-background"           => self.enable_background.to_css_value(),
+            "fill"                        => self.fill.to_css_value(),
+            "fill-opacity"                => self.fill_opacity.to_css_value(),
+            "fill-rule"                   => self.fill_rule.to_css_value(),
+            "filter"                      => self.filter.to_css_value(),
+            "flood-color"                 => self.flood_color.to_css_value(),
+            "flood-opacity"               => self.flood_opacity.to_css_value(),
+            "font-family"                 => self.font_family.to_css_value(),
+            "font-size"                   => self.font_size.to_css_value(),
+            "font-stretch"                => self.font_stretch.to_css_value(),
+            "font-style"                  => self.font_style.to_css_value(),
+            "font-variant"                => self.font_variant.to_css_value(),
+            "font-weight"                 => self.font_weight.to_css_value(),
+            "height"                      => self.height.to_css_value(),
+            "isolation"                   => self.isolation.to_css_value(),
+            "letter-spacing"              => self.letter_spacing.to_css_value(),
+            "lighting-color"              => self.lighting_color.to_css_value(),
+            "marker-end"                  => self.marker_end.to_css_value(),
+            "marker-mid"                  => self.marker_mid.to_css_value(),
+            "marker-start"                => self.marker_start.to_css_value(),
+            "mask"                        => self.mask.to_css_value(),
+            "mask-type"                   => self.mask_type.to_css_value(),
+            "mix-blend-mode"              => self.mix_blend_mode.to_css_value(),
+            "offset-distance"             => self.offset_distance.to_css_value(),
+            "offset-path"                 => self.offset_path.to_css_value(),
+            "offset-rotate"               => self.offset_rotate.to_css_value(),
+            "opacity"                     => self.opacity.to_css_value(),
+            "overflow"                    => self.overflow.to_css_value(),
+            "paint-order"                 => self.paint_order.to_css_value(),
+            "r"                           => self.r.to_css_value(),
+            "rx"                          => self.rx.to_css_value(),
+            "ry"                          => self.ry.to_css_value(),
+            "shape-rendering"             => self.shape_rendering.to_css_value(),
+            "stop-color"                  => self.stop_color.to_css_value(),
+            "stop-opacity"                => self.stop_opacity.to_css_value(),
+            "stroke"                      => self.stroke.to_css_value(),
+            "stroke-dasharray"            => self.stroke_dasharray.to_css_value(),
+            "stroke-dashoffset"           => self.stroke_dashoffset.to_css_value(),
+            "stroke-linecap"              => self.stroke_line_cap.to_css_value(),
+            "stroke-linejoin"             => self.stroke_line_join.to_css_value(),
+            "stroke-miterlimit"           => self.stroke_miterlimit.to_css_value(),
+            "stroke-opacity"              => self.stroke_opacity.to_css_value(),
+            "stroke-width"                => self.stroke_width.to_css_value(),
+            "text-anchor"                 => self.text_anchor.to_css_value(),
+            "text-decoration"             => self.text_decoration.to_css_value(),
+            "text-rendering"              => self.text_rendering.to_css_value(),
+            "transform"                   => self.transform.to_css_value(),
+            "transform-origin"            => self.transform_origin.to_css_value(),
+            "transition-delay"            => self.transition_delay.to_css_value(),
+            "transition-duration"         => self.transition_duration.to_css_value(),
+            "transition-property"         => self.transition_property.to_css_value(),
+            "transition-timing-function"  => self.transition_timing_function.to_css_value(),
+            "unicode-bidi"                => self.unicode_bidi.to_css_value(),
+            "vector-effect"               => self.vector_effect.to_css_value(),
+            "visibility"                  => self.visibility.to_css_value(),
+            "white-space"                 => self.white_space.to_css_value(),
+            "width"                       => self.width.to_css_value(),
+            "word-spacing"                => self.word_spacing.to_css_value(),
+            "writing-mode"                => self.writing_mode.to_css_value(),
+            "x"                           => self.x.to_css_value(),
+            "y"                           => self.y.to_css_value(),
+
+            _ => return None,
+        };
+
+        Some(value)
+    }
 }
 
 macro_rules! compute_value {
@@ -404,11 +884,35 @@ impl SpecifiedValues {
         use crate::properties as p;
 
         match *prop {
+            Animation(ref x) => if let SpecifiedValue::Specified(p::Animation(ref v)) = *x {
+                // Since "animation" is a shorthand property, we'll just expand it here
+                self.animation_delay = SpecifiedValue::Specified(p::AnimationDelay(v.map(|a| a.delay)));
+                self.animation_direction = SpecifiedValue::Specified(p::AnimationDirection(v.map(|a| a.direction)));
+                self.animation_duration = SpecifiedValue::Specified(p::AnimationDuration(v.map(|a| a.duration)));
+                self.animation_fill_mode = SpecifiedValue::Specified(p::AnimationFillMode(v.map(|a| a.fill_mode)));
+                self.animation_iteration_count = SpecifiedValue::Specified(p::AnimationIterationCount(v.map(|a| a.iteration_count)));
+                self.animation_name = SpecifiedValue::Specified(p::AnimationName(v.map(|a| a.name.clone())));
+                self.animation_play_state = SpecifiedValue::Specified(p::AnimationPlayState(v.map(|a| a.play_state)));
+                self.animation_timing_function = SpecifiedValue::Specified(p::AnimationTimingFunction(v.map(|a| a.timing_function)));
+            },
+
+            AnimationDelay(ref x)            => self.animation_delay              = x.clone(),
+            AnimationDirection(ref x)        => self.animation_direction          = x.clone(),
+            AnimationDuration(ref x)         => self.animation_duration           = x.clone(),
+            AnimationFillMode(ref x)         => self.animation_fill_mode          = x.clone(),
+            AnimationIterationCount(ref x)   => self.animation_iteration_count    = x.clone(),
+            AnimationName(ref x)             => self.animation_name               = x.clone(),
+            AnimationPlayState(ref x)        => self.animation_play_state         = x.clone(),
+            AnimationTimingFunction(ref x)   => self.animation_timing_function    = x.clone(),
             BaselineShift(ref x)             => self.baseline_shift               = x.clone(),
             ClipPath(ref x)                  => self.clip_path                    = x.clone(),
             ClipRule(ref x)                  => self.clip_rule                    = x.clone(),
             Color(ref x)                     => self.color                        = x.clone(),
+            ColorInterpolation(ref x)        => self.color_interpolation          = x.clone(),
             ColorInterpolationFilters(ref x) => self.color_interpolation_filters  = x.clone(),
+            Cx(ref x)                        => self.cx                           = x.clone(),
+            Cy(ref x)                        => self.cy                           = x.clone(),
+            D(ref x)                         => self.d                            = x.clone(),
             Direction(ref x)                 => self.direction                    = x.clone(),
             Display(ref x)                   => self.display                      = x.clone(),
             EnableBackground(ref x)          => self.enable_background            = x.clone(),
@@ -424,22 +928,33 @@ impl SpecifiedValues {
             FontStyle(ref x)                 => self.font_style                   = x.clone(),
             FontVariant(ref x)               => self.font_variant                 = x.clone(),
             FontWeight(ref x)                => self.font_weight                  = x.clone(),
+            Height(ref x)                    => self.height                       = x.clone(),
+            Isolation(ref x)                 => self.isolation                    = x.clone(),
             LetterSpacing(ref x)             => self.letter_spacing               = x.clone(),
             LightingColor(ref x)             => self.lighting_color               = x.clone(),
 
-            Marker(ref x) => if let SpecifiedValue::Specified(p::Marker(ref v)) = *x {
+            Marker(ref x) => {
                 // Since "marker" is a shorthand property, we'll just expand it here
-                self.marker_end = SpecifiedValue::Specified(p::MarkerEnd(v.clone()));
-                self.marker_mid = SpecifiedValue::Specified(p::MarkerMid(v.clone()));
-                self.marker_start = SpecifiedValue::Specified(p::MarkerStart(v.clone()));
+                for (_, longhand) in marker_longhands(x) {
+                    self.set_parsed_property(&longhand);
+                }
             },
 
             MarkerEnd(ref x)                 => self.marker_end                   = x.clone(),
             MarkerMid(ref x)                 => self.marker_mid                   = x.clone(),
             MarkerStart(ref x)               => self.marker_start                 = x.clone(),
             Mask(ref x)                      => self.mask                         = x.clone(),
+            MaskType(ref x)                  => self.mask_type                    = x.clone(),
+            MixBlendMode(ref x)              => self.mix_blend_mode               = x.clone(),
+            OffsetDistance(ref x)            => self.offset_distance              = x.clone(),
+            OffsetPath(ref x)                => self.offset_path                  = x.clone(),
+            OffsetRotate(ref x)              => self.offset_rotate                = x.clone(),
             Opacity(ref x)                   => self.opacity                      = x.clone(),
             Overflow(ref x)                  => self.overflow                     = x.clone(),
+            PaintOrder(ref x)                => self.paint_order                  = x.clone(),
+            R(ref x)                         => self.r                            = x.clone(),
+            Rx(ref x)                        => self.rx                           = x.clone(),
+            Ry(ref x)                        => self.ry                           = x.clone(),
             ShapeRendering(ref x)            => self.shape_rendering              = x.clone(),
             StopColor(ref x)                 => self.stop_color                   = x.clone(),
             StopOpacity(ref x)               => self.stop_opacity                 = x.clone(),
@@ -454,18 +969,51 @@ impl SpecifiedValues {
             TextAnchor(ref x)                => self.text_anchor                  = x.clone(),
             TextDecoration(ref x)            => self.text_decoration              = x.clone(),
             TextRendering(ref x)             => self.text_rendering               = x.clone(),
+            Transform(ref x)                 => self.transform                    = x.clone(),
+            TransformOrigin(ref x)           => self.transform_origin             = x.clone(),
+
+            Transition(ref x) => if let SpecifiedValue::Specified(p::Transition(ref v)) = *x {
+                // Since "transition" is a shorthand property, we'll just expand it here
+                self.transition_delay = SpecifiedValue::Specified(p::TransitionDelay(v.map(|t| t.delay)));
+                self.transition_duration = SpecifiedValue::Specified(p::TransitionDuration(v.map(|t| t.duration)));
+                self.transition_property = SpecifiedValue::Specified(p::TransitionProperty(v.map(|t| t.property.clone())));
+                self.transition_timing_function = SpecifiedValue::Specified(p::TransitionTimingFunction(v.map(|t| t.timing_function)));
+            },
+
+            TransitionDelay(ref x)           => self.transition_delay             = x.clone(),
+            TransitionDuration(ref x)        => self.transition_duration          = x.clone(),
+            TransitionProperty(ref x)        => self.transition_property          = x.clone(),
+            TransitionTimingFunction(ref x)  => self.transition_timing_function   = x.clone(),
             UnicodeBidi(ref x)               => self.unicode_bidi                 = x.clone(),
+            VectorEffect(ref x)              => self.vector_effect                = x.clone(),
             Visibility(ref x)                => self.visibility                   = x.clone(),
+            WhiteSpace(ref x)                => self.white_space                  = x.clone(),
+            Width(ref x)                     => self.width                        = x.clone(),
+            WordSpacing(ref x)               => self.word_spacing                 = x.clone(),
             WritingMode(ref x)               => self.writing_mode                 = x.clone(),
+            X(ref x)                         => self.x                            = x.clone(),
+            Y(ref x)                         => self.y                            = x.clone(),
         }
     }
 
     pub fn to_computed_values(&self, computed: &mut ComputedValues) {
+        compute_value!(self, computed, animation_delay);
+        compute_value!(self, computed, animation_direction);
+        compute_value!(self, computed, animation_duration);
+        compute_value!(self, computed, animation_fill_mode);
+        compute_value!(self, computed, animation_iteration_count);
+        compute_value!(self, computed, animation_name);
+        compute_value!(self, computed, animation_play_state);
+        compute_value!(self, computed, animation_timing_function);
         compute_value!(self, computed, baseline_shift);
         compute_value!(self, computed, clip_path);
         compute_value!(self, computed, clip_rule);
         compute_value!(self, computed, color);
+        compute_value!(self, computed, color_interpolation);
         compute_value!(self, computed, color_interpolation_filters);
+        compute_value!(self, computed, cx);
+        compute_value!(self, computed, cy);
+        compute_value!(self, computed, d);
         compute_value!(self, computed, direction);
         compute_value!(self, computed, display);
         compute_value!(self, computed, enable_background);
@@ -481,14 +1029,25 @@ impl SpecifiedValues {
         compute_value!(self, computed, font_style);
         compute_value!(self, computed, font_variant);
         compute_value!(self, computed, font_weight);
+        compute_value!(self, computed, height);
+        compute_value!(self, computed, isolation);
         compute_value!(self, computed, letter_spacing);
         compute_value!(self, computed, lighting_color);
         compute_value!(self, computed, marker_end);
         compute_value!(self, computed, marker_mid);
         compute_value!(self, computed, marker_start);
         compute_value!(self, computed, mask);
+        compute_value!(self, computed, mask_type);
+        compute_value!(self, computed, mix_blend_mode);
+        compute_value!(self, computed, offset_distance);
+        compute_value!(self, computed, offset_path);
+        compute_value!(self, computed, offset_rotate);
         compute_value!(self, computed, opacity);
         compute_value!(self, computed, overflow);
+        compute_value!(self, computed, paint_order);
+        compute_value!(self, computed, r);
+        compute_value!(self, computed, rx);
+        compute_value!(self, computed, ry);
         compute_value!(self, computed, shape_rendering);
         compute_value!(self, computed, stop_color);
         compute_value!(self, computed, stop_opacity);
@@ -503,9 +1062,21 @@ impl SpecifiedValues {
         compute_value!(self, computed, text_anchor);
         compute_value!(self, computed, text_decoration);
         compute_value!(self, computed, text_rendering);
+        compute_value!(self, computed, transform);
+        compute_value!(self, computed, transform_origin);
+        compute_value!(self, computed, transition_delay);
+        compute_value!(self, computed, transition_duration);
+        compute_value!(self, computed, transition_property);
+        compute_value!(self, computed, transition_timing_function);
         compute_value!(self, computed, unicode_bidi);
+        compute_value!(self, computed, vector_effect);
         compute_value!(self, computed, visibility);
+        compute_value!(self, computed, white_space);
+        compute_value!(self, computed, width);
+        compute_value!(self, computed, word_spacing);
         compute_value!(self, computed, writing_mode);
+        compute_value!(self, computed, x);
+        compute_value!(self, computed, y);
         compute_value!(self, computed, xml_lang);
         compute_value!(self, computed, xml_space);
     }
@@ -575,6 +1146,8 @@ impl SpecifiedValues {
                         SpecifiedValue::Specified(XmlSpace::parse_str(value).attribute(attr)?);
                 }
 
+                _ if is_parsed_by_node(&attr) => (),
+
                 _ => self.parse_one_presentation_attribute(attr, value)?,
             }
         }
@@ -587,15 +1160,43 @@ impl SpecifiedValues {
         declaration: &Declaration,
         important_styles: &mut HashSet<QualName>,
     ) {
-        if !declaration.important && important_styles.contains(&declaration.prop_name) {
+        // Each longhand of a shorthand property has its own importance, as if it had
+        // been declared by itself
+        if let ParsedProperty::Marker(ref x) = declaration.property {
+            for (prop_name, longhand) in marker_longhands(x) {
+                self.set_property_with_importance(
+                    &prop_name,
+                    &longhand,
+                    declaration.important,
+                    important_styles,
+                );
+            }
+        } else {
+            self.set_property_with_importance(
+                &declaration.prop_name,
+                &declaration.property,
+                declaration.important,
+                important_styles,
+            );
+        }
+    }
+
+    fn set_property_with_importance(
+        &mut self,
+        prop_name: &QualName,
+        property: &ParsedProperty,
+        important: bool,
+        important_styles: &mut HashSet<QualName>,
+    ) {
+        if !important && important_styles.contains(prop_name) {
             return;
         }
 
-        if declaration.important {
-            important_styles.insert(declaration.prop_name.clone());
+        if important {
+            important_styles.insert(prop_name.clone());
         }
 
-        self.set_parsed_property(&declaration.property);
+        self.set_parsed_property(property);
     }
 
     pub fn parse_style_declarations(
@@ -614,6 +1215,40 @@ impl SpecifiedValues {
     }
 }
 
+/// Expands the `marker` shorthand into `marker-start`, `marker-mid` and `marker-end`.
+fn marker_longhands(marker: &SpecifiedValue<Marker>) -> Vec<(QualName, ParsedProperty)> {
+    let (start, mid, end) = match *marker {
+        SpecifiedValue::Unspecified => return Vec::new(),
+
+        SpecifiedValue::Inherit => (
+            SpecifiedValue::Inherit,
+            SpecifiedValue::Inherit,
+            SpecifiedValue::Inherit,
+        ),
+
+        SpecifiedValue::Specified(Marker(ref v)) => (
+            SpecifiedValue::Specified(MarkerStart(v.clone())),
+            SpecifiedValue::Specified(MarkerMid(v.clone())),
+            SpecifiedValue::Specified(MarkerEnd(v.clone())),
+        ),
+    };
+
+    vec![
+        (
+            QualName::new(None, ns!(svg), local_name!("marker-start")),
+            ParsedProperty::MarkerStart(start),
+        ),
+        (
+            QualName::new(None, ns!(svg), local_name!("marker-mid")),
+            ParsedProperty::MarkerMid(mid),
+        ),
+        (
+            QualName::new(None, ns!(svg), local_name!("marker-end")),
+            ParsedProperty::MarkerEnd(end),
+        ),
+    ]
+}
+
 // Parses the value for the type `T` of the property out of the Parser, including `inherit` values.
 fn parse_input<T>(input: &mut Parser) -> Result<SpecifiedValue<T>, ValueErrorKind>
 where
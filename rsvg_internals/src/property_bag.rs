@@ -92,6 +92,16 @@ impl<'a> PropertyBag<'a> {
         PropertyBag(array)
     }
 
+    /// Creates a `PropertyBag` that borrows attributes which were saved from the XML.
+    pub fn new_from_attributes(attributes: &'a [(QualName, String)]) -> PropertyBag<'a> {
+        PropertyBag(
+            attributes
+                .iter()
+                .map(|(attr, value)| (attr.clone(), value.as_str()))
+                .collect(),
+        )
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
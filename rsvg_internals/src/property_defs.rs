@@ -1,16 +1,151 @@
+use cairo;
 use cssparser::{self, Parser, Token};
 
+use crate::angle::Angle;
+use crate::clip_path::ClipSource;
+use crate::css_animation::*;
 use crate::error::*;
+use crate::filter_functions::FilterValue;
 use crate::font_props::{FontSizeSpec, FontWeightSpec, LetterSpacingSpec, SingleFontFamily};
 use crate::dasharray::Dasharray;
 use crate::iri::IRI;
 use crate::length::*;
+use crate::motion_path::MotionPath;
 use crate::paint_server::PaintServer;
 use crate::parsers::Parse;
 use crate::properties::ComputedValues;
-use crate::property_macros::Property;
+use crate::property_macros::{Property, ToCssValue};
+use crate::shapes::LengthOrAuto;
+use crate::transform::TransformFunction;
 use crate::unit_interval::UnitInterval;
 
+/// Makes a property for one of the geometry properties of SVG 2, like `cx` or `width`.
+///
+/// These properties override the element's attributes of the same name, so the computed
+/// value is `None` when the property is not specified; the attribute applies then.  The
+/// `$validate` function checks the parsed value.
+macro_rules! make_geometry_property {
+    ($name: ident, $type: ty, $validate: expr) => {
+        make_property!(
+            ComputedValues,
+            $name,
+            inherits_automatically: false,
+
+            fields: {
+                length: Option<$type>, default: None,
+            }
+
+            parse_impl: {
+                impl Parse for $name {
+                    fn parse(parser: &mut Parser<'_, '_>) -> Result<$name, ValueErrorKind> {
+                        let validate: fn($type) -> Result<$type, ValueErrorKind> = $validate;
+
+                        Ok($name {
+                            length: Some(validate(<$type>::parse(parser)?)?),
+                        })
+                    }
+                }
+            }
+        );
+
+        impl $name {
+            /// Returns the specified value, or `attribute` if the property is not specified.
+            pub fn value_or(&self, attribute: $type) -> $type {
+                self.length.unwrap_or(attribute)
+            }
+        }
+
+        impl ToCssValue for $name {
+            fn to_css_value(&self) -> String {
+                self.length
+                    .as_ref()
+                    .map_or_else(|| "auto".to_string(), ToCssValue::to_css_value)
+            }
+        }
+    };
+}
+
+// https://www.w3.org/TR/css-animations-1/#animation
+make_property!(
+    ComputedValues,
+    Animation,
+    default: CommaSeparated(vec![SingleAnimation::default()]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<SingleAnimation>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-delay
+make_property!(
+    ComputedValues,
+    AnimationDelay,
+    default: CommaSeparated(vec![Time(0.0)]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<Time>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-direction
+make_property!(
+    ComputedValues,
+    AnimationDirection,
+    default: CommaSeparated(vec![PlaybackDirection::Normal]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<PlaybackDirection>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-duration
+make_property!(
+    ComputedValues,
+    AnimationDuration,
+    default: CommaSeparated(vec![Time(0.0)]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<Time>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-fill-mode
+make_property!(
+    ComputedValues,
+    AnimationFillMode,
+    default: CommaSeparated(vec![FillMode::None]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<FillMode>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-iteration-count
+make_property!(
+    ComputedValues,
+    AnimationIterationCount,
+    default: CommaSeparated(vec![IterationCount::Count(1.0)]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<IterationCount>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-name
+make_property!(
+    ComputedValues,
+    AnimationName,
+    default: CommaSeparated(vec![KeyframesName(None)]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<KeyframesName>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-play-state
+make_property!(
+    ComputedValues,
+    AnimationPlayState,
+    default: CommaSeparated(vec![PlayState::Running]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<PlayState>,
+);
+
+// https://www.w3.org/TR/css-animations-1/#animation-timing-function
+make_property!(
+    ComputedValues,
+    AnimationTimingFunction,
+    default: CommaSeparated(vec![TimingFunction::default()]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<TimingFunction>,
+);
+
 // https://www.w3.org/TR/SVG/text.html#BaselineShiftProperty
 make_property!(
     ComputedValues,
@@ -77,13 +212,13 @@ make_property!(
     }
 );
 
-// https://www.w3.org/TR/SVG/masking.html#ClipPathProperty
+// https://www.w3.org/TR/css-masking-1/#the-clip-path
 make_property!(
     ComputedValues,
     ClipPath,
-    default: IRI::None,
+    default: ClipSource::None,
     inherits_automatically: false,
-    newtype_parse: IRI,
+    newtype_parse: ClipSource,
 );
 
 // https://www.w3.org/TR/SVG/masking.html#ClipRuleProperty
@@ -114,6 +249,19 @@ make_property!(
 );
 
 // https://www.w3.org/TR/SVG11/painting.html#ColorInterpolationProperty
+make_property!(
+    ComputedValues,
+    ColorInterpolation,
+    default: Srgb,
+    inherits_automatically: true,
+
+    identifiers:
+    "auto" => Auto,
+    "sRGB" => Srgb,
+    "linearRGB" => LinearRgb,
+);
+
+// https://www.w3.org/TR/SVG11/filters.html#ColorInterpolationFiltersProperty
 make_property!(
     ComputedValues,
     ColorInterpolationFilters,
@@ -126,6 +274,63 @@ make_property!(
     "sRGB" => Srgb,
 );
 
+// https://www.w3.org/TR/SVG2/geometry.html#CxProperty
+make_geometry_property!(Cx, Length<Horizontal>, Ok);
+
+// https://www.w3.org/TR/SVG2/geometry.html#CyProperty
+make_geometry_property!(Cy, Length<Vertical>, Ok);
+
+// https://www.w3.org/TR/SVG2/paths.html#TheDProperty
+make_property!(
+    ComputedValues,
+    D,
+    inherits_automatically: false,
+
+    fields: {
+        // None if the property is not specified; the element's d attribute applies then.
+        // Some(None) is "d: none", which makes an empty path.
+        path: Option<Option<MotionPath>>, default: None,
+    }
+
+    parse_impl: {
+        impl Parse for D {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<D, ValueErrorKind> {
+                if parser.try_parse(|p| p.expect_ident_matching("none")).is_ok() {
+                    Ok(D { path: Some(None) })
+                } else {
+                    Ok(D {
+                        path: Some(Some(MotionPath::parse(parser)?)),
+                    })
+                }
+            }
+        }
+    }
+);
+
+impl ToCssValue for D {
+    fn to_css_value(&self) -> String {
+        match self.path {
+            Some(Some(ref path)) => path.to_css_value(),
+            _ => "none".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_d() {
+    assert_eq!(D::parse_str("none").unwrap(), D { path: Some(None) });
+    assert_eq!(
+        D::parse_str("path('M 0 0 L 10 10')")
+            .unwrap()
+            .to_css_value(),
+        "path(\"M 0 0 L 10 10\")"
+    );
+
+    assert!(D::parse_str("M 0 0 L 10 10").is_err());
+    assert!(D::parse_str("path('L 10 10')").is_err());
+}
+
 // https://www.w3.org/TR/SVG/text.html#DirectionProperty
 make_property!(
     ComputedValues,
@@ -169,14 +374,80 @@ make_property!(
 make_property!(
     ComputedValues,
     EnableBackground,
-    default: Accumulate,
     inherits_automatically: false,
 
-    identifiers:
-    "accumulate" => Accumulate,
-    "new" => New,
+    fields: {
+        new: bool, default: false,
+        region: Option<cairo::Rectangle>, default: None,
+    }
+
+    parse_impl: {
+        impl Parse for EnableBackground {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<EnableBackground, ValueErrorKind> {
+                if parser.try_parse(|p| p.expect_ident_matching("accumulate")).is_ok() {
+                    return Ok(EnableBackground::default());
+                }
+
+                parser
+                    .expect_ident_matching("new")
+                    .map_err(|_| ValueErrorKind::parse_error("expected accumulate or new"))?;
+
+                if parser.is_exhausted() {
+                    return Ok(EnableBackground {
+                        new: true,
+                        region: None,
+                    });
+                }
+
+                let x = f64::parse(parser)?;
+                let y = f64::parse(parser)?;
+                let width = f64::parse(parser)?;
+                let height = f64::parse(parser)?;
+
+                if width < 0.0 || height < 0.0 {
+                    return Err(ValueErrorKind::value_error(
+                        "region width and height can't be negative",
+                    ));
+                }
+
+                Ok(EnableBackground {
+                    new: true,
+                    region: Some(cairo::Rectangle {
+                        x,
+                        y,
+                        width,
+                        height,
+                    }),
+                })
+            }
+        }
+    }
 );
 
+impl ToCssValue for EnableBackground {
+    fn to_css_value(&self) -> String {
+        match (self.new, self.region) {
+            (false, _) => "accumulate".to_string(),
+            (true, None) => "new".to_string(),
+            (true, Some(r)) => format!("new {} {} {} {}", r.x, r.y, r.width, r.height),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_enable_background() {
+    let to_css = |s| EnableBackground::parse_str(s).unwrap().to_css_value();
+
+    assert_eq!(to_css("accumulate"), "accumulate");
+    assert_eq!(to_css("new"), "new");
+    assert_eq!(to_css("new 0 0 1179.5 687.25"), "new 0 0 1179.5 687.25");
+
+    assert!(EnableBackground::parse_str("").is_err());
+    assert!(EnableBackground::parse_str("new 0 0 10").is_err());
+    assert!(EnableBackground::parse_str("new 0 0 -1 10").is_err());
+}
+
 // https://www.w3.org/TR/SVG/painting.html#FillProperty
 make_property!(
     ComputedValues,
@@ -211,9 +482,9 @@ make_property!(
 make_property!(
     ComputedValues,
     Filter,
-    default: IRI::None,
+    default: FilterValue::None,
     inherits_automatically: false,
-    newtype_parse: IRI,
+    newtype_parse: FilterValue,
 );
 
 // https://www.w3.org/TR/SVG/filters.html#FloodColorProperty
@@ -317,6 +588,21 @@ make_property!(
     newtype_parse: FontWeightSpec,
 );
 
+// https://www.w3.org/TR/SVG2/geometry.html#Sizing
+make_geometry_property!(Height, Length<Vertical>, Length::check_nonnegative);
+
+// https://www.w3.org/TR/compositing-1/#isolation
+make_property!(
+    ComputedValues,
+    Isolation,
+    default: Auto,
+    inherits_automatically: false,
+
+    identifiers:
+    "auto" => Auto,
+    "isolate" => Isolate,
+);
+
 // https://www.w3.org/TR/SVG/text.html#LetterSpacingProperty
 make_property!(
     ComputedValues,
@@ -389,6 +675,194 @@ make_property!(
     newtype_parse: IRI,
 );
 
+// https://www.w3.org/TR/css-masking-1/#the-mask-type
+make_property!(
+    ComputedValues,
+    MaskType,
+    default: Luminance,
+    inherits_automatically: false,
+
+    identifiers:
+    "luminance" => Luminance,
+    "alpha" => Alpha,
+);
+
+// https://www.w3.org/TR/compositing-1/#mix-blend-mode
+make_property!(
+    ComputedValues,
+    MixBlendMode,
+    default: Normal,
+    inherits_automatically: false,
+
+    identifiers:
+    "normal" => Normal,
+    "multiply" => Multiply,
+    "screen" => Screen,
+    "overlay" => Overlay,
+    "darken" => Darken,
+    "lighten" => Lighten,
+    "color-dodge" => ColorDodge,
+    "color-burn" => ColorBurn,
+    "hard-light" => HardLight,
+    "soft-light" => SoftLight,
+    "difference" => Difference,
+    "exclusion" => Exclusion,
+    "hue" => Hue,
+    "saturation" => Saturation,
+    "color" => Color,
+    "luminosity" => Luminosity,
+);
+
+impl From<MixBlendMode> for cairo::Operator {
+    fn from(m: MixBlendMode) -> cairo::Operator {
+        use cairo::Operator;
+
+        match m {
+            MixBlendMode::Normal => Operator::Over,
+            MixBlendMode::Multiply => Operator::Multiply,
+            MixBlendMode::Screen => Operator::Screen,
+            MixBlendMode::Overlay => Operator::Overlay,
+            MixBlendMode::Darken => Operator::Darken,
+            MixBlendMode::Lighten => Operator::Lighten,
+            MixBlendMode::ColorDodge => Operator::ColorDodge,
+            MixBlendMode::ColorBurn => Operator::ColorBurn,
+            MixBlendMode::HardLight => Operator::HardLight,
+            MixBlendMode::SoftLight => Operator::SoftLight,
+            MixBlendMode::Difference => Operator::Difference,
+            MixBlendMode::Exclusion => Operator::Exclusion,
+            MixBlendMode::Hue => Operator::HslHue,
+            MixBlendMode::Saturation => Operator::HslSaturation,
+            MixBlendMode::Color => Operator::HslColor,
+            MixBlendMode::Luminosity => Operator::HslLuminosity,
+        }
+    }
+}
+
+// https://www.w3.org/TR/motion-1/#offset-distance-property
+make_property!(
+    ComputedValues,
+    OffsetDistance,
+    default: Length::<Horizontal>::default(),
+    inherits_automatically: false,
+    newtype_parse: Length<Horizontal>,
+);
+
+// https://www.w3.org/TR/motion-1/#offset-path-property
+make_property!(
+    ComputedValues,
+    OffsetPath,
+    inherits_automatically: false,
+
+    fields: {
+        path: Option<MotionPath>, default: None,
+    }
+
+    parse_impl: {
+        impl Parse for OffsetPath {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<OffsetPath, ValueErrorKind> {
+                if parser.try_parse(|p| p.expect_ident_matching("none")).is_ok() {
+                    Ok(OffsetPath { path: None })
+                } else {
+                    Ok(OffsetPath {
+                        path: Some(MotionPath::parse(parser)?),
+                    })
+                }
+            }
+        }
+    }
+);
+
+impl ToCssValue for OffsetPath {
+    fn to_css_value(&self) -> String {
+        match self.path {
+            None => "none".to_string(),
+            Some(ref path) => path.to_css_value(),
+        }
+    }
+}
+
+// https://www.w3.org/TR/motion-1/#offset-rotate-property
+make_property!(
+    ComputedValues,
+    OffsetRotate,
+    inherits_automatically: false,
+
+    fields: {
+        auto: bool, default: true,
+        angle: Angle, default: Angle::new(0.0),
+    }
+
+    parse_impl: {
+        impl Parse for OffsetRotate {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<OffsetRotate, ValueErrorKind> {
+                if parser.try_parse(|p| p.expect_ident_matching("reverse")).is_ok() {
+                    return Ok(OffsetRotate {
+                        auto: true,
+                        angle: Angle::from_degrees(180.0),
+                    });
+                }
+
+                let mut auto = parser.try_parse(|p| p.expect_ident_matching("auto")).is_ok();
+                let angle = parser.try_parse(|p| Angle::parse(p));
+
+                if !auto {
+                    auto = parser.try_parse(|p| p.expect_ident_matching("auto")).is_ok();
+                }
+
+                match angle {
+                    Ok(angle) => Ok(OffsetRotate { auto, angle }),
+                    Err(_) if auto => Ok(OffsetRotate::default()),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+);
+
+impl ToCssValue for OffsetRotate {
+    fn to_css_value(&self) -> String {
+        let degrees = self.angle.radians().to_degrees();
+
+        match (self.auto, degrees) {
+            (true, d) if d == 0.0 => "auto".to_string(),
+            (true, d) => format!("auto {}deg", d),
+            (false, d) => format!("{}deg", d),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_offset_rotate() {
+    assert_eq!(
+        OffsetRotate::parse_str("auto").unwrap(),
+        OffsetRotate::default()
+    );
+
+    assert_eq!(
+        OffsetRotate::parse_str("reverse").unwrap(),
+        OffsetRotate {
+            auto: true,
+            angle: Angle::from_degrees(180.0),
+        }
+    );
+
+    assert_eq!(
+        OffsetRotate::parse_str("90deg").unwrap(),
+        OffsetRotate {
+            auto: false,
+            angle: Angle::from_degrees(90.0),
+        }
+    );
+
+    assert_eq!(
+        OffsetRotate::parse_str("auto 45deg").unwrap(),
+        OffsetRotate::parse_str("45deg auto").unwrap()
+    );
+
+    assert!(OffsetRotate::parse_str("sideways").is_err());
+}
+
 // https://www.w3.org/TR/SVG/masking.html#OpacityProperty
 make_property!(
     ComputedValues,
@@ -412,6 +886,163 @@ make_property!(
     "auto" => Auto,
 );
 
+/// One of the layers that make up a shape or text, for the `paint-order` property
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PaintTarget {
+    Fill,
+    Stroke,
+    Markers,
+}
+
+const DEFAULT_PAINT_ORDER: [PaintTarget; 3] =
+    [PaintTarget::Fill, PaintTarget::Stroke, PaintTarget::Markers];
+
+// https://www.w3.org/TR/SVG2/painting.html#PaintOrder
+make_property!(
+    ComputedValues,
+    PaintOrder,
+    inherits_automatically: true,
+
+    fields: {
+        targets: [PaintTarget; 3], default: DEFAULT_PAINT_ORDER,
+    }
+
+    parse_impl: {
+        impl Parse for PaintOrder {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<PaintOrder, ValueErrorKind> {
+                if parser.try_parse(|p| p.expect_ident_matching("normal")).is_ok() {
+                    return Ok(PaintOrder::default());
+                }
+
+                let mut targets = Vec::with_capacity(3);
+
+                while !parser.is_exhausted() {
+                    let cow = parser.expect_ident().map_err(|_| {
+                        crate::error::ValueErrorKind::parse_error("expected identifier")
+                    })?;
+
+                    let target = match cow.as_ref() {
+                        "fill" => PaintTarget::Fill,
+                        "stroke" => PaintTarget::Stroke,
+                        "markers" => PaintTarget::Markers,
+                        _ => return Err(ValueErrorKind::parse_error("invalid syntax")),
+                    };
+
+                    if targets.contains(&target) {
+                        return Err(ValueErrorKind::parse_error("duplicated paint target"));
+                    }
+
+                    targets.push(target);
+                }
+
+                if targets.is_empty() {
+                    return Err(ValueErrorKind::parse_error("expected paint target"));
+                }
+
+                // Omitted targets are painted afterwards, in their default order
+                for target in DEFAULT_PAINT_ORDER.iter() {
+                    if !targets.contains(target) {
+                        targets.push(*target);
+                    }
+                }
+
+                Ok(PaintOrder {
+                    targets: [targets[0], targets[1], targets[2]],
+                })
+            }
+        }
+    }
+);
+
+impl ToCssValue for PaintOrder {
+    fn to_css_value(&self) -> String {
+        if self.targets == DEFAULT_PAINT_ORDER {
+            return "normal".to_string();
+        }
+
+        let default_index = |t: PaintTarget| DEFAULT_PAINT_ORDER.iter().position(|d| *d == t);
+
+        // The last target is always implied, and so is the second one if the
+        // last two are in their default order.
+        let num_targets = if default_index(self.targets[1]) < default_index(self.targets[2]) {
+            1
+        } else {
+            2
+        };
+
+        self.targets[..num_targets]
+            .iter()
+            .map(|t| match *t {
+                PaintTarget::Fill => "fill",
+                PaintTarget::Stroke => "stroke",
+                PaintTarget::Markers => "markers",
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_paint_order() {
+    use PaintTarget::*;
+
+    assert_eq!(
+        PaintOrder::parse_str("normal").unwrap(),
+        PaintOrder::default()
+    );
+
+    assert_eq!(
+        PaintOrder::parse_str("stroke").unwrap(),
+        PaintOrder {
+            targets: [Stroke, Fill, Markers]
+        }
+    );
+
+    assert_eq!(
+        PaintOrder::parse_str("markers stroke").unwrap(),
+        PaintOrder {
+            targets: [Markers, Stroke, Fill]
+        }
+    );
+
+    assert_eq!(
+        PaintOrder::parse_str("fill markers stroke").unwrap(),
+        PaintOrder {
+            targets: [Fill, Markers, Stroke]
+        }
+    );
+
+    assert!(PaintOrder::parse_str("").is_err());
+    assert!(PaintOrder::parse_str("fill fill").is_err());
+    assert!(PaintOrder::parse_str("outline").is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn serializes_paint_order() {
+    let to_css = |s| PaintOrder::parse_str(s).unwrap().to_css_value();
+
+    assert_eq!(to_css("fill stroke markers"), "normal");
+    assert_eq!(to_css("stroke fill markers"), "stroke");
+    assert_eq!(to_css("stroke markers"), "stroke markers");
+    assert_eq!(to_css("markers"), "markers");
+    assert_eq!(to_css("fill markers"), "fill markers");
+}
+
+// https://www.w3.org/TR/SVG2/geometry.html#R
+make_geometry_property!(R, Length<Both>, Length::check_nonnegative);
+
+// https://www.w3.org/TR/SVG2/geometry.html#RX
+make_geometry_property!(
+    Rx,
+    LengthOrAuto<Horizontal>,
+    LengthOrAuto::check_nonnegative
+);
+
+// https://www.w3.org/TR/SVG2/geometry.html#RY
+make_geometry_property!(Ry, LengthOrAuto<Vertical>, LengthOrAuto::check_nonnegative);
+
 // https://www.w3.org/TR/SVG/painting.html#ShapeRenderingProperty
 make_property!(
     ComputedValues,
@@ -583,6 +1214,30 @@ make_property!(
     }
 );
 
+impl ToCssValue for TextDecoration {
+    fn to_css_value(&self) -> String {
+        let mut values = Vec::new();
+
+        if self.underline {
+            values.push("underline");
+        }
+
+        if self.overline {
+            values.push("overline");
+        }
+
+        if self.strike {
+            values.push("line-through");
+        }
+
+        if values.is_empty() {
+            "none".to_string()
+        } else {
+            values.join(" ")
+        }
+    }
+}
+
 #[cfg(test)]
 #[test]
 fn parses_text_decoration() {
@@ -648,6 +1303,271 @@ make_property!(
     "geometricPrecision" => GeometricPrecision,
 );
 
+// https://www.w3.org/TR/css-transforms-1/#transform-property
+make_property!(
+    ComputedValues,
+    Transform,
+    inherits_automatically: false,
+
+    fields: {
+        // None if the property is not specified; the element's transform attribute applies then
+        functions: Option<Vec<TransformFunction>>, default: None,
+    }
+
+    parse_impl: {
+        impl Parse for Transform {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<Transform, ValueErrorKind> {
+                if parser.try_parse(|p| p.expect_ident_matching("none")).is_ok() {
+                    return Ok(Transform {
+                        functions: Some(Vec::new()),
+                    });
+                }
+
+                let mut functions = vec![TransformFunction::parse(parser)?];
+
+                while !parser.is_exhausted() {
+                    functions.push(TransformFunction::parse(parser)?);
+                }
+
+                Ok(Transform {
+                    functions: Some(functions),
+                })
+            }
+        }
+    }
+);
+
+impl ToCssValue for Transform {
+    fn to_css_value(&self) -> String {
+        match self.functions {
+            Some(ref functions) if !functions.is_empty() => functions
+                .iter()
+                .map(ToCssValue::to_css_value)
+                .collect::<Vec<_>>()
+                .join(" "),
+
+            _ => "none".to_string(),
+        }
+    }
+}
+
+// https://www.w3.org/TR/css-transforms-1/#transform-origin-property
+//
+// The initial value is "0 0" for SVG elements, since they don't have a CSS layout box.
+make_property!(
+    ComputedValues,
+    TransformOrigin,
+    inherits_automatically: false,
+
+    fields: {
+        x: Length<Horizontal>, default: Length::<Horizontal>::default(),
+        y: Length<Vertical>, default: Length::<Vertical>::default(),
+    }
+
+    parse_impl: {
+        impl Parse for TransformOrigin {
+            fn parse(parser: &mut Parser<'_, '_>) -> Result<TransformOrigin, ValueErrorKind> {
+                let (x, y) = match parser.try_parse(parse_origin_keyword).ok() {
+                    // "top", "bottom right", etc.
+                    Some(OriginKeyword::Vertical(fy)) => {
+                        let fx = match parser.try_parse(parse_origin_keyword) {
+                            Ok(OriginKeyword::Horizontal(fx)) => fx,
+                            Ok(OriginKeyword::Center) | Err(_) => 0.5,
+                            Ok(OriginKeyword::Vertical(_)) => {
+                                return Err(ValueErrorKind::parse_error(
+                                    "expected left | center | right",
+                                ));
+                            }
+                        };
+
+                        (origin_percent(fx), origin_percent(fy))
+                    }
+
+                    // "center", "center left", "center 10px", etc.
+                    Some(OriginKeyword::Center) => {
+                        let (fx, fy) = match parser.try_parse(parse_origin_keyword) {
+                            Ok(OriginKeyword::Horizontal(fx)) => (fx, 0.5),
+                            Ok(OriginKeyword::Vertical(fy)) => (0.5, fy),
+                            Ok(OriginKeyword::Center) => (0.5, 0.5),
+                            Err(_) => return Ok(TransformOrigin {
+                                x: origin_percent(0.5),
+                                y: parse_origin_y(parser)?,
+                            }),
+                        };
+
+                        (origin_percent(fx), origin_percent(fy))
+                    }
+
+                    Some(OriginKeyword::Horizontal(fx)) => {
+                        (origin_percent(fx), parse_origin_y(parser)?)
+                    }
+
+                    None => (Length::<Horizontal>::parse(parser)?, parse_origin_y(parser)?),
+                };
+
+                Ok(TransformOrigin { x, y })
+            }
+        }
+    }
+);
+
+enum OriginKeyword {
+    Horizontal(f64),
+    Vertical(f64),
+    Center,
+}
+
+fn parse_origin_keyword(parser: &mut Parser<'_, '_>) -> Result<OriginKeyword, ValueErrorKind> {
+    let ident = parser.expect_ident()?.clone();
+
+    match ident.to_ascii_lowercase().as_ref() {
+        "left" => Ok(OriginKeyword::Horizontal(0.0)),
+        "right" => Ok(OriginKeyword::Horizontal(1.0)),
+        "top" => Ok(OriginKeyword::Vertical(0.0)),
+        "bottom" => Ok(OriginKeyword::Vertical(1.0)),
+        "center" => Ok(OriginKeyword::Center),
+        _ => Err(ValueErrorKind::parse_error(
+            "expected left | center | right | top | bottom",
+        )),
+    }
+}
+
+fn parse_origin_y(parser: &mut Parser<'_, '_>) -> Result<Length<Vertical>, ValueErrorKind> {
+    match parser.try_parse(parse_origin_keyword) {
+        Ok(OriginKeyword::Vertical(fy)) => Ok(origin_percent(fy)),
+        Ok(OriginKeyword::Center) => Ok(origin_percent(0.5)),
+        Ok(OriginKeyword::Horizontal(_)) => Err(ValueErrorKind::parse_error(
+            "expected top | center | bottom",
+        )),
+        Err(_) => Ok(parser
+            .try_parse(Length::<Vertical>::parse)
+            .unwrap_or_else(|_| origin_percent(0.5))),
+    }
+}
+
+fn origin_percent<N: Normalize>(fraction: f64) -> Length<N> {
+    Length::<N>::new(fraction, LengthUnit::Percent)
+}
+
+impl ToCssValue for TransformOrigin {
+    fn to_css_value(&self) -> String {
+        format!("{} {}", self.x.to_css_value(), self.y.to_css_value())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn parses_transform() {
+    assert_eq!(Transform::default().functions, None);
+    assert_eq!(
+        Transform::parse_str("none").unwrap().functions,
+        Some(Vec::new())
+    );
+
+    assert_eq!(
+        Transform::parse_str("translate(10px, 2em) rotate(0.25turn) scaleX(2)")
+            .unwrap()
+            .functions,
+        Some(vec![
+            TransformFunction::Translate(
+                Length::<Horizontal>::new(10.0, LengthUnit::Px),
+                Length::<Vertical>::new(2.0, LengthUnit::Em),
+            ),
+            TransformFunction::Rotate(Angle::from_degrees(90.0)),
+            TransformFunction::Scale(2.0, 1.0),
+        ])
+    );
+
+    assert_eq!(
+        Transform::parse_str("skew(10deg)").unwrap().functions,
+        Some(vec![TransformFunction::Skew(
+            Angle::from_degrees(10.0),
+            Angle::new(0.0)
+        )])
+    );
+
+    // the CSS syntax requires commas between arguments
+    assert!(Transform::parse_str("translate(10 20)").is_err());
+    assert!(Transform::parse_str("rotate(10deg, 5, 5)").is_err());
+    assert!(Transform::parse_str("frobnicate(1)").is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn parses_transform_origin() {
+    let origin = |x, y| TransformOrigin { x, y };
+    let px = Length::<Horizontal>::new;
+    let py = Length::<Vertical>::new;
+
+    assert_eq!(
+        TransformOrigin::parse_str("10px 20%").unwrap(),
+        origin(px(10.0, LengthUnit::Px), py(0.2, LengthUnit::Percent))
+    );
+
+    assert_eq!(
+        TransformOrigin::parse_str("center").unwrap(),
+        origin(px(0.5, LengthUnit::Percent), py(0.5, LengthUnit::Percent))
+    );
+
+    assert_eq!(
+        TransformOrigin::parse_str("bottom right").unwrap(),
+        origin(px(1.0, LengthUnit::Percent), py(1.0, LengthUnit::Percent))
+    );
+
+    assert_eq!(
+        TransformOrigin::parse_str("left 5px").unwrap(),
+        origin(px(0.0, LengthUnit::Percent), py(5.0, LengthUnit::Px))
+    );
+
+    assert!(TransformOrigin::parse_str("top bottom").is_err());
+    assert!(TransformOrigin::parse_str("left right").is_err());
+}
+
+// https://www.w3.org/TR/css-transitions-1/#transition-shorthand-property
+make_property!(
+    ComputedValues,
+    Transition,
+    default: CommaSeparated(vec![SingleTransition::default()]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<SingleTransition>,
+);
+
+// https://www.w3.org/TR/css-transitions-1/#transition-delay-property
+make_property!(
+    ComputedValues,
+    TransitionDelay,
+    default: CommaSeparated(vec![Time(0.0)]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<Time>,
+);
+
+// https://www.w3.org/TR/css-transitions-1/#transition-duration-property
+make_property!(
+    ComputedValues,
+    TransitionDuration,
+    default: CommaSeparated(vec![Time(0.0)]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<Time>,
+);
+
+// https://www.w3.org/TR/css-transitions-1/#transition-property-property
+make_property!(
+    ComputedValues,
+    TransitionProperty,
+    default: CommaSeparated(vec![TransitionTarget::All]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<TransitionTarget>,
+);
+
+// https://www.w3.org/TR/css-transitions-1/#transition-timing-function-property
+make_property!(
+    ComputedValues,
+    TransitionTimingFunction,
+    default: CommaSeparated(vec![TimingFunction::default()]),
+    inherits_automatically: false,
+    newtype_parse: CommaSeparated<TimingFunction>,
+);
+
 // https://www.w3.org/TR/SVG/text.html#UnicodeBidiProperty
 make_property!(
     ComputedValues,
@@ -661,6 +1581,18 @@ make_property!(
     "bidi-override" => Override,
 );
 
+// https://www.w3.org/TR/SVG2/coords.html#VectorEffects
+make_property!(
+    ComputedValues,
+    VectorEffect,
+    default: None,
+    inherits_automatically: false,
+
+    identifiers:
+    "none" => None,
+    "non-scaling-stroke" => NonScalingStroke,
+);
+
 // https://www.w3.org/TR/SVG/painting.html#VisibilityProperty
 make_property!(
     ComputedValues,
@@ -674,6 +1606,55 @@ make_property!(
     "collapse" => Collapse,
 );
 
+// https://www.w3.org/TR/css-text-3/#white-space-property
+make_property!(
+    ComputedValues,
+    WhiteSpace,
+    default: Normal,
+    inherits_automatically: true,
+
+    identifiers:
+    "normal" => Normal,
+    "pre" => Pre,
+    "nowrap" => Nowrap,
+    "pre-wrap" => PreWrap,
+    "pre-line" => PreLine,
+);
+
+impl WhiteSpace {
+    /// Whether newlines in the text start new lines
+    pub fn preserves_newlines(self) -> bool {
+        match self {
+            WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine => true,
+            WhiteSpace::Normal | WhiteSpace::Nowrap => false,
+        }
+    }
+}
+
+// https://www.w3.org/TR/SVG2/geometry.html#Sizing
+make_geometry_property!(Width, Length<Horizontal>, Length::check_nonnegative);
+
+// https://www.w3.org/TR/SVG/text.html#WordSpacingProperty
+//
+// This has the same syntax as letter-spacing.
+make_property!(
+    ComputedValues,
+    WordSpacing,
+    default: LetterSpacingSpec::Normal,
+    newtype_parse: LetterSpacingSpec,
+    property_impl: {
+        impl Property<ComputedValues> for WordSpacing {
+            fn inherits_automatically() -> bool {
+                true
+            }
+
+            fn compute(&self, _v: &ComputedValues) -> Self {
+                WordSpacing(self.0.compute())
+            }
+        }
+    }
+);
+
 // https://www.w3.org/TR/SVG/text.html#WritingModeProperty
 make_property!(
     ComputedValues,
@@ -699,6 +1680,34 @@ impl WritingMode {
     }
 }
 
+// https://www.w3.org/TR/SVG2/geometry.html#X
+make_geometry_property!(X, Length<Horizontal>, Ok);
+
+// https://www.w3.org/TR/SVG2/geometry.html#Y
+make_geometry_property!(Y, Length<Vertical>, Ok);
+
+#[cfg(test)]
+#[test]
+fn parses_geometry_properties() {
+    assert_eq!(
+        X::parse_str("-10px").unwrap().length,
+        Some(Length::new(-10.0, LengthUnit::Px))
+    );
+    assert_eq!(
+        R::parse_str("50%").unwrap().length,
+        Some(Length::new(0.5, LengthUnit::Percent))
+    );
+
+    assert!(R::parse_str("-1").is_err());
+    assert!(Width::parse_str("auto").is_err());
+    assert_eq!(
+        Width::default()
+            .value_or(Length::new(2.0, LengthUnit::Px))
+            .length,
+        2.0
+    );
+}
+
 make_property!(
     ComputedValues,
     XmlLang,
@@ -3,6 +3,38 @@ pub trait Property<T> {
     fn compute(&self, _: &T) -> Self;
 }
 
+/// Serializes a property value back to CSS syntax.
+///
+/// This is used to report computed values to callers, for example when
+/// querying the computed style of an element.
+pub trait ToCssValue {
+    fn to_css_value(&self) -> String;
+}
+
+impl ToCssValue for f64 {
+    fn to_css_value(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+impl ToCssValue for String {
+    fn to_css_value(&self) -> String {
+        self.clone()
+    }
+}
+
+impl ToCssValue for cssparser::RGBA {
+    fn to_css_value(&self) -> String {
+        cssparser::ToCss::to_css_string(self)
+    }
+}
+
+impl ToCssValue for cssparser::Color {
+    fn to_css_value(&self) -> String {
+        cssparser::ToCss::to_css_string(self)
+    }
+}
+
 /// Generates a property definition that simply parses strings to enum variants
 /// or to a tuple struct of the given type.
 ///
@@ -43,6 +75,15 @@ macro_rules! make_property {
         impl_default!($name, $name::$default);
         impl_property!($computed_values_type, $name, $inherits_automatically);
 
+        impl crate::property_macros::ToCssValue for $name {
+            fn to_css_value(&self) -> String {
+                match *self {
+                    $($name::$variant => $str_prop,)+
+                }
+                .to_string()
+            }
+        }
+
         impl crate::parsers::Parse for $name {
             fn parse(parser: &mut ::cssparser::Parser<'_, '_>) -> Result<$name, crate::error::ValueErrorKind> {
                 let loc = parser.current_source_location();
@@ -76,6 +117,7 @@ macro_rules! make_property {
         pub struct $name(pub $type);
 
         impl_default!($name, $name($default));
+        impl_newtype_to_css_value!($name);
         impl_property!($computed_values_type, $name, $inherits_automatically);
 
         impl crate::parsers::Parse for $name {
@@ -95,6 +137,7 @@ macro_rules! make_property {
         pub struct $name(pub $type);
 
         impl_default!($name, $name($default));
+        impl_newtype_to_css_value!($name);
 
         $prop
 
@@ -116,6 +159,7 @@ macro_rules! make_property {
         pub struct $name(pub $type);
 
         impl_default!($name, $name($default));
+        impl_newtype_to_css_value!($name);
 
         $prop
 
@@ -133,6 +177,7 @@ macro_rules! make_property {
         pub struct $name(pub $type);
 
         impl_default!($name, $name($default));
+        impl_newtype_to_css_value!($name);
         impl_property!($computed_values_type, $name, $inherits_automatically);
 
         $parse
@@ -168,6 +213,16 @@ macro_rules! impl_default {
     };
 }
 
+macro_rules! impl_newtype_to_css_value {
+    ($name:ident) => {
+        impl crate::property_macros::ToCssValue for $name {
+            fn to_css_value(&self) -> String {
+                crate::property_macros::ToCssValue::to_css_value(&self.0)
+            }
+        }
+    };
+}
+
 macro_rules! impl_property {
     ($computed_values_type:ty, $name:ident, $inherits_automatically:expr) => {
         impl crate::property_macros::Property<$computed_values_type> for $name {
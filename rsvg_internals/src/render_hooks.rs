@@ -0,0 +1,233 @@
+//! Callbacks that embedders can run before and after individual elements are drawn.
+//!
+//! Hooks are keyed by a CSS selector like `#foo` or `g.annotated > rect`.  They receive
+//! the cairo context with the element's user space coordinates, so they can draw
+//! annotations on top of an element, or draw placeholder content instead of it.
+//!
+//! Render hooks can also provide a placeholder for elements that librsvg does not
+//! support, like `<foreignObject>` or elements in unknown namespaces.  The placeholder gets
+//! drawn in the rectangle given by the element's `x`, `y`, `width`, and `height`
+//! attributes; elements without a size are ignored as usual.
+
+use cairo;
+use std::rc::Rc;
+
+use crate::css::ElementSelector;
+use crate::error::SelectorError;
+use crate::node::RsvgNode;
+use crate::properties::ComputedValues;
+
+/// What to do with an element after running the hooks that are called before drawing it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HookAction {
+    /// Draw the element normally
+    Continue,
+
+    /// Don't draw the element, for example because a hook drew a placeholder instead
+    Skip,
+}
+
+/// The element that is being drawn, as seen by a render hook
+pub struct RenderHookElement<'a> {
+    node: &'a RsvgNode,
+    values: &'a ComputedValues,
+}
+
+impl<'a> RenderHookElement<'a> {
+    pub fn new(node: &'a RsvgNode, values: &'a ComputedValues) -> RenderHookElement<'a> {
+        RenderHookElement { node, values }
+    }
+
+    /// Returns the element's `id` attribute, if it has one.
+    pub fn id(&self) -> Option<String> {
+        self.node.borrow().get_id().map(str::to_string)
+    }
+
+    /// Returns the name of the element, like `rect`.
+    pub fn element_name(&self) -> String {
+        self.node.borrow().element_name().local.to_string()
+    }
+
+    /// Returns the computed value of a CSS property like `fill` for the element, or
+    /// `None` if librsvg does not support that property.
+    pub fn get_computed_property(&self, property: &str) -> Option<String> {
+        self.values.get_property_value(property)
+    }
+}
+
+type PreDrawCallback = dyn Fn(&cairo::Context, &RenderHookElement<'_>) -> HookAction;
+type PostDrawCallback = dyn Fn(&cairo::Context, &RenderHookElement<'_>);
+type PlaceholderCallback = dyn Fn(&cairo::Context, &RenderHookElement<'_>, &cairo::Rectangle);
+
+/// Size of the squares in the checkerboard placeholder, in user units
+const CHECKERBOARD_SQUARE_SIZE: f64 = 8.0;
+
+#[derive(Clone)]
+enum Placeholder {
+    Checkerboard,
+    Custom(Rc<PlaceholderCallback>),
+}
+
+struct Hook<F: ?Sized> {
+    selector: ElementSelector,
+    callback: Box<F>,
+}
+
+/// A set of callbacks to run around the drawing of elements
+///
+/// Hooks run in the order in which they were added.  They don't run for elements
+/// that are not rendered, like the contents of `<defs>` or invisible elements, nor
+/// for the contents of clipping paths.
+#[derive(Clone, Default)]
+pub struct RenderHooks {
+    pre_draw: Vec<Rc<Hook<PreDrawCallback>>>,
+    post_draw: Vec<Rc<Hook<PostDrawCallback>>>,
+    placeholder: Option<Placeholder>,
+}
+
+impl RenderHooks {
+    pub fn new() -> RenderHooks {
+        RenderHooks::default()
+    }
+
+    /// Adds a callback to run before drawing the elements that match `selector`.
+    ///
+    /// If any of the callbacks for an element returns `HookAction::Skip`, the element
+    /// does not get drawn.
+    pub fn add_pre_draw_hook<F>(&mut self, selector: &str, callback: F) -> Result<(), SelectorError>
+    where
+        F: Fn(&cairo::Context, &RenderHookElement<'_>) -> HookAction + 'static,
+    {
+        self.pre_draw.push(Rc::new(Hook {
+            selector: ElementSelector::parse(selector)?,
+            callback: Box::new(callback),
+        }));
+
+        Ok(())
+    }
+
+    /// Adds a callback to run after drawing the elements that match `selector`.
+    ///
+    /// The callback also runs for elements that were skipped by a pre-draw hook.
+    pub fn add_post_draw_hook<F>(
+        &mut self,
+        selector: &str,
+        callback: F,
+    ) -> Result<(), SelectorError>
+    where
+        F: Fn(&cairo::Context, &RenderHookElement<'_>) + 'static,
+    {
+        self.post_draw.push(Rc::new(Hook {
+            selector: ElementSelector::parse(selector)?,
+            callback: Box::new(callback),
+        }));
+
+        Ok(())
+    }
+
+    /// Draws a checkerboard in place of unsupported elements that have a size.
+    pub fn draw_placeholders(&mut self) {
+        self.placeholder = Some(Placeholder::Checkerboard);
+    }
+
+    /// Adds a callback to draw the placeholder for unsupported elements that have a size.
+    ///
+    /// The callback receives the element's rectangle in user space.  This replaces the
+    /// checkerboard from `draw_placeholders()`.
+    pub fn set_placeholder_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&cairo::Context, &RenderHookElement<'_>, &cairo::Rectangle) + 'static,
+    {
+        self.placeholder = Some(Placeholder::Custom(Rc::new(callback)));
+    }
+
+    /// Whether there are no pre-draw or post-draw hooks; this does not consider the
+    /// placeholder.
+    pub fn is_empty(&self) -> bool {
+        self.pre_draw.is_empty() && self.post_draw.is_empty()
+    }
+
+    pub fn has_placeholder(&self) -> bool {
+        self.placeholder.is_some()
+    }
+
+    /// Draws the placeholder for an unsupported `element` in `rect`, if there is one.
+    pub fn draw_placeholder(
+        &self,
+        cr: &cairo::Context,
+        element: &RenderHookElement<'_>,
+        rect: &cairo::Rectangle,
+    ) {
+        let placeholder = match self.placeholder {
+            Some(ref p) => p,
+            None => return,
+        };
+
+        cr.save();
+
+        match *placeholder {
+            Placeholder::Checkerboard => draw_checkerboard(cr, rect),
+            Placeholder::Custom(ref callback) => callback(cr, element, rect),
+        }
+
+        cr.restore();
+    }
+
+    /// Runs the pre-draw hooks that match the `element`.
+    pub fn run_pre_draw(&self, cr: &cairo::Context, element: &RenderHookElement<'_>) -> HookAction {
+        let mut action = HookAction::Continue;
+
+        for hook in self
+            .pre_draw
+            .iter()
+            .filter(|h| h.selector.matches(element.node))
+        {
+            cr.save();
+            if (hook.callback)(cr, element) == HookAction::Skip {
+                action = HookAction::Skip;
+            }
+            cr.restore();
+        }
+
+        action
+    }
+
+    /// Runs the post-draw hooks that match the `element`.
+    pub fn run_post_draw(&self, cr: &cairo::Context, element: &RenderHookElement<'_>) {
+        for hook in self
+            .post_draw
+            .iter()
+            .filter(|h| h.selector.matches(element.node))
+        {
+            cr.save();
+            (hook.callback)(cr, element);
+            cr.restore();
+        }
+    }
+}
+
+fn draw_checkerboard(cr: &cairo::Context, rect: &cairo::Rectangle) {
+    cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+    cr.clip();
+
+    cr.set_source_rgb(0.8, 0.8, 0.8);
+    cr.paint();
+
+    cr.set_source_rgb(0.6, 0.6, 0.6);
+
+    let columns = (rect.width / CHECKERBOARD_SQUARE_SIZE).ceil() as i32;
+    let rows = (rect.height / CHECKERBOARD_SQUARE_SIZE).ceil() as i32;
+
+    for row in 0..rows {
+        for col in (row % 2..columns).step_by(2) {
+            cr.rectangle(
+                rect.x + f64::from(col) * CHECKERBOARD_SQUARE_SIZE,
+                rect.y + f64::from(row) * CHECKERBOARD_SQUARE_SIZE,
+                CHECKERBOARD_SQUARE_SIZE,
+                CHECKERBOARD_SQUARE_SIZE,
+            );
+        }
+    }
+
+    cr.fill();
+}
@@ -0,0 +1,164 @@
+//! Accounting of the resources used while rendering a document.
+//!
+//! The limits in `limits.rs` guard against specific attacks, but a document can still
+//! be very expensive to render without tripping any of them: lots of large temporary
+//! surfaces, shapes that cover the whole canvas thousands of times, long chains of
+//! filter primitives.  Services that render untrusted documents want a single knob for
+//! the worst-case cost of a render, so we keep count of the pixels that get allocated,
+//! filled and filtered, and abort the render when their sum goes over a budget.
+
+use std::cell::Cell;
+
+use crate::error::RenderingError;
+
+/// Resources used so far during a render.
+///
+/// All the quantities are in device pixels.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ResourceUsage {
+    /// Number of temporary surfaces that were allocated
+    pub surfaces: u64,
+
+    /// Total size of the temporary surfaces that were allocated
+    pub surface_pixels: u64,
+
+    /// Total area of the shapes that were filled or stroked, after clipping
+    pub filled_pixels: u64,
+
+    /// Total area of the filter effects regions, times the number of primitives in each filter
+    pub filter_pixels: u64,
+}
+
+impl ResourceUsage {
+    /// The cost of the render so far, which is what gets compared against the budget.
+    pub fn total(&self) -> u64 {
+        self.surface_pixels
+            .saturating_add(self.filled_pixels)
+            .saturating_add(self.filter_pixels)
+    }
+}
+
+/// Keeps the `ResourceUsage` of a render, and checks it against an optional budget.
+pub struct ResourceTracker {
+    usage: Cell<ResourceUsage>,
+    budget: Option<u64>,
+}
+
+impl ResourceTracker {
+    pub fn new(budget: Option<u64>) -> ResourceTracker {
+        ResourceTracker {
+            usage: Cell::new(ResourceUsage::default()),
+            budget,
+        }
+    }
+
+    pub fn usage(&self) -> ResourceUsage {
+        self.usage.get()
+    }
+
+    /// Accounts for a temporary surface of `width` by `height` pixels.
+    pub fn add_surface(&self, width: i32, height: i32) -> Result<(), RenderingError> {
+        let pixels = pixel_count(width, height);
+
+        self.update(|usage| {
+            usage.surfaces += 1;
+            usage.surface_pixels = usage.surface_pixels.saturating_add(pixels);
+        })
+    }
+
+    /// Accounts for painting `area` device pixels of a shape.
+    pub fn add_filled_area(&self, area: f64) -> Result<(), RenderingError> {
+        let pixels = area_to_pixels(area);
+
+        self.update(|usage| usage.filled_pixels = usage.filled_pixels.saturating_add(pixels))
+    }
+
+    /// Accounts for running `num_primitives` filter primitives over `area` device pixels.
+    pub fn add_filter_work(&self, area: f64, num_primitives: usize) -> Result<(), RenderingError> {
+        let pixels = area_to_pixels(area).saturating_mul(num_primitives as u64);
+
+        self.update(|usage| usage.filter_pixels = usage.filter_pixels.saturating_add(pixels))
+    }
+
+    fn update<F: FnOnce(&mut ResourceUsage)>(&self, f: F) -> Result<(), RenderingError> {
+        let mut usage = self.usage.get();
+        f(&mut usage);
+        self.usage.set(usage);
+
+        match self.budget {
+            Some(budget) if usage.total() > budget => {
+                rsvg_log!(
+                    "(resource budget of {} pixels exceeded: {:?})",
+                    budget,
+                    usage
+                );
+                Err(RenderingError::ResourceBudgetExceeded)
+            }
+
+            _ => Ok(()),
+        }
+    }
+}
+
+fn pixel_count(width: i32, height: i32) -> u64 {
+    u64::from(width.max(0) as u32) * u64::from(height.max(0) as u32)
+}
+
+fn area_to_pixels(area: f64) -> u64 {
+    if area.is_nan() || area <= 0.0 {
+        0
+    } else if area >= u64::max_value() as f64 {
+        u64::max_value()
+    } else {
+        area.ceil() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+
+    #[test]
+    fn adds_up_usage() {
+        let tracker = ResourceTracker::new(None);
+
+        tracker.add_surface(10, 20).unwrap();
+        tracker.add_surface(-5, 20).unwrap();
+        tracker.add_filled_area(49.5).unwrap();
+        tracker.add_filled_area(f64::NAN).unwrap();
+        tracker.add_filter_work(100.0, 3).unwrap();
+
+        assert_eq!(
+            tracker.usage(),
+            ResourceUsage {
+                surfaces: 2,
+                surface_pixels: 200,
+                filled_pixels: 50,
+                filter_pixels: 300,
+            }
+        );
+        assert_eq!(tracker.usage().total(), 550);
+    }
+
+    #[test]
+    fn saturates_instead_of_overflowing() {
+        let tracker = ResourceTracker::new(None);
+
+        tracker.add_filled_area(f64::INFINITY).unwrap();
+        tracker.add_filter_work(1e300, usize::max_value()).unwrap();
+
+        assert_eq!(tracker.usage().total(), u64::max_value());
+    }
+
+    #[test]
+    fn errors_out_when_over_budget() {
+        let tracker = ResourceTracker::new(Some(100));
+
+        assert!(tracker.add_surface(10, 10).is_ok());
+        assert_eq!(
+            tracker.add_filled_area(1.0),
+            Err(RenderingError::ResourceBudgetExceeded)
+        );
+    }
+}
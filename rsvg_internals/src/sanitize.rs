@@ -0,0 +1,101 @@
+//! Checks for numbers that cairo can't handle.
+//!
+//! Cairo puts a context in an error state if it gets a transformation that is not
+//! invertible, or that has NaN or infinite values, and a context in error can't be
+//! drawn on anymore.  Also, cairo stores device-space coordinates as 24.8 fixed-point
+//! numbers, so astronomically large coordinates wrap around to garbage.
+//!
+//! Transforms and lengths in SVG documents can easily produce such values, so we check
+//! for them before they get to cairo.  Elements with invalid transforms are skipped,
+//! and huge coordinates get clamped, so that one bad element doesn't abort the whole
+//! render.
+
+use cairo;
+
+/// Largest device-space coordinate that fits in cairo's fixed-point numbers
+pub const MAX_DEVICE_COORDINATE: f64 = 8_388_607.0;
+
+/// Whether `matrix` can be used as a transformation for cairo.
+pub fn is_valid_matrix(matrix: &cairo::Matrix) -> bool {
+    let cairo::Matrix {
+        xx,
+        yx,
+        xy,
+        yy,
+        x0,
+        y0,
+    } = *matrix;
+
+    [xx, yx, xy, yy, x0, y0].iter().all(|v| v.is_finite()) && matrix.try_invert().is_ok()
+}
+
+/// Clamps points in user space, so that they fall within cairo's range of device coordinates
+pub struct DeviceClamp {
+    user_to_device: cairo::Matrix,
+    device_to_user: cairo::Matrix,
+}
+
+impl DeviceClamp {
+    /// Creates a `DeviceClamp` for the transformation from user space to device space.
+    ///
+    /// Returns `None` if the transformation is not valid; see `is_valid_matrix()`.
+    pub fn new(user_to_device: &cairo::Matrix) -> Option<DeviceClamp> {
+        if !is_valid_matrix(user_to_device) {
+            return None;
+        }
+
+        user_to_device
+            .try_invert()
+            .ok()
+            .map(|device_to_user| DeviceClamp {
+                user_to_device: *user_to_device,
+                device_to_user,
+            })
+    }
+
+    pub fn clamp_point(&self, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy) = self.user_to_device.transform_point(x, y);
+
+        if dx.abs() <= MAX_DEVICE_COORDINATE && dy.abs() <= MAX_DEVICE_COORDINATE {
+            (x, y)
+        } else {
+            self.device_to_user.transform_point(
+                dx.max(-MAX_DEVICE_COORDINATE).min(MAX_DEVICE_COORDINATE),
+                dy.max(-MAX_DEVICE_COORDINATE).min(MAX_DEVICE_COORDINATE),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+
+    #[test]
+    fn detects_invalid_matrices() {
+        let is_valid =
+            |xx, yx, xy, yy, x0, y0| is_valid_matrix(&cairo::Matrix::new(xx, yx, xy, yy, x0, y0));
+
+        assert!(is_valid(1.0, 0.0, 0.0, 1.0, 0.0, 0.0));
+        assert!(is_valid(1e6, 0.0, 0.0, 1e-6, 1e20, 0.0));
+
+        assert!(!is_valid(0.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+        assert!(!is_valid(1.0, 0.0, 0.0, 1.0, f64::NAN, 0.0));
+        assert!(!is_valid(f64::INFINITY, 0.0, 0.0, 1.0, 0.0, 0.0));
+        assert!(!is_valid(1e200, 0.0, 0.0, 1e200, 0.0, 0.0));
+    }
+
+    #[test]
+    fn clamps_points_in_device_space() {
+        let clamp = DeviceClamp::new(&cairo::Matrix::new(2.0, 0.0, 0.0, 2.0, 10.0, 0.0)).unwrap();
+
+        assert_eq!(clamp.clamp_point(1.0, 2.0), (1.0, 2.0));
+
+        let (x, y) = clamp.clamp_point(1e10, -1e300);
+        assert_eq!(x, (MAX_DEVICE_COORDINATE - 10.0) / 2.0);
+        assert_eq!(y, -MAX_DEVICE_COORDINATE / 2.0);
+
+        assert!(DeviceClamp::new(&cairo::Matrix::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0)).is_none());
+    }
+}
@@ -3,16 +3,18 @@ use markup5ever::{expanded_name, local_name, namespace_url, ns};
 use std::ops::Deref;
 
 use crate::bbox::BoundingBox;
-use crate::drawing_ctx::DrawingCtx;
+use crate::drawing_ctx::{DrawingCtx, ViewParams};
 use crate::error::*;
+use crate::geometry;
 use crate::length::*;
 use crate::marker;
 use crate::node::*;
 use crate::parsers::{CssParserExt, Parse, ParseValue};
 use crate::path_builder::*;
-use crate::path_parser;
+use crate::path_parser::{self, PathDataRecovery};
 use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
+use crate::property_macros::ToCssValue;
 use cssparser::{Parser, Token};
 
 fn render_path_builder(
@@ -23,6 +25,17 @@ fn render_path_builder(
     render_markers: bool,
     clipping: bool,
 ) -> Result<BoundingBox, RenderingError> {
+    draw_ctx.add_path(node, builder);
+    draw_ctx.add_stroke(node, values);
+
+    if !builder.is_empty() && !builder.is_finite() {
+        rsvg_log!(
+            "(not rendering element {} because its path has non-finite coordinates)",
+            node
+        );
+        return Ok(draw_ctx.empty_bbox());
+    }
+
     if !builder.is_empty() {
         let bbox = draw_ctx.with_discrete_layer(node, values, clipping, &mut |dc| {
             let cr = dc.get_cairo_context();
@@ -34,11 +47,22 @@ fn render_path_builder(
                 Ok(dc.empty_bbox())
             } else {
                 cr.set_fill_rule(cairo::FillRule::from(values.fill_rule));
-                dc.stroke_and_fill(&cr, values)
+
+                let bbox =
+                    geometry::path_bbox(builder, values, &dc.get_view_params(), &cr.get_matrix());
+
+                // Markers are painted in the same group as the fill and the stroke, so
+                // that opacity, filters, etc. apply to them whatever their paint-order.
+                dc.stroke_and_fill(&cr, values, bbox, &mut |dc| {
+                    if render_markers {
+                        marker::render_markers_for_path_builder(builder, dc, values, clipping)?;
+                    }
+                    Ok(())
+                })
             }
         })?;
 
-        if render_markers {
+        if render_markers && clipping {
             marker::render_markers_for_path_builder(builder, draw_ctx, values, clipping)?;
         }
 
@@ -48,25 +72,11 @@ fn render_path_builder(
     }
 }
 
-fn render_ellipse(
-    cx: f64,
-    cy: f64,
-    rx: f64,
-    ry: f64,
-    draw_ctx: &mut DrawingCtx,
-    node: &RsvgNode,
-    values: &ComputedValues,
-    clipping: bool,
-) -> Result<BoundingBox, RenderingError> {
-    // Per the spec, rx and ry must be nonnegative
-    if rx <= 0.0 || ry <= 0.0 {
-        return Ok(draw_ctx.empty_bbox());
-    }
-
+/// Makes the outline of an ellipse, approximated with four Bézier curves.
+pub fn ellipse_path(cx: f64, cy: f64, rx: f64, ry: f64) -> PathBuilder {
     // 4/3 * (1-cos 45°)/sin 45° = 4/3 * sqrt(2) - 1
     let arc_magic: f64 = 0.5522847498;
 
-    // approximate an ellipse using 4 Bézier curves
     let mut builder = PathBuilder::new();
 
     builder.move_to(cx + rx, cy);
@@ -109,22 +119,60 @@ fn render_ellipse(
 
     builder.close_path();
 
+    builder
+}
+
+fn render_ellipse(
+    cx: f64,
+    cy: f64,
+    rx: f64,
+    ry: f64,
+    draw_ctx: &mut DrawingCtx,
+    node: &RsvgNode,
+    values: &ComputedValues,
+    clipping: bool,
+) -> Result<BoundingBox, RenderingError> {
+    // Per the spec, rx and ry must be nonnegative
+    if rx <= 0.0 || ry <= 0.0 {
+        return Ok(draw_ctx.empty_bbox());
+    }
+
+    let builder = ellipse_path(cx, cy, rx, ry);
+
     render_path_builder(&builder, draw_ctx, node, values, false, clipping)
 }
 
 #[derive(Default)]
 pub struct Path {
     builder: PathBuilder,
+    recovery: PathDataRecovery,
+}
+
+impl Path {
+    /// Sets how to handle errors in the `d` attribute; this must be called before `set_atts`.
+    pub fn set_path_data_recovery(&mut self, recovery: PathDataRecovery) {
+        self.recovery = recovery;
+    }
+
+    /// Returns the path data from the `d` attribute.
+    pub fn builder(&self) -> &PathBuilder {
+        &self.builder
+    }
 }
 
 impl NodeTrait for Path {
     fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
         for (attr, value) in pbag.iter() {
             if attr.expanded() == expanded_name!(svg "d") {
-                if let Err(e) = path_parser::parse_path_into_builder(value, &mut self.builder) {
-                    // FIXME: we don't propagate errors upstream, but creating a partial
-                    // path is OK per the spec
-
+                let errors = path_parser::parse_path_into_builder_with_recovery(
+                    value,
+                    &mut self.builder,
+                    self.recovery,
+                )
+                .map_err(|e| NodeError::parse_error(attr, &e.to_string()))?;
+
+                // Rendering a partial path is OK per the spec, so these are just warnings
+                for e in errors {
                     rsvg_log!("could not parse path: {}", e);
                 }
             }
@@ -141,7 +189,16 @@ impl NodeTrait for Path {
         clipping: bool,
     ) -> Result<BoundingBox, RenderingError> {
         let values = cascaded.get();
-        render_path_builder(&self.builder, draw_ctx, node, values, true, clipping)
+
+        // The d property overrides the d attribute
+        let empty = PathBuilder::new();
+        let builder = match values.d.path {
+            Some(Some(ref path)) => path.builder(),
+            Some(None) => &empty,
+            None => &self.builder,
+        };
+
+        render_path_builder(builder, draw_ctx, node, values, true, clipping)
     }
 }
 
@@ -330,6 +387,205 @@ impl NodeTrait for Line {
     }
 }
 
+/// A length or the `auto` keyword, for the radiuses of `<rect>` and `<ellipse>`
+///
+/// In SVG 2, an `auto` radius takes the used value of the other radius.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LengthOrAuto<N: Normalize> {
+    Length(Length<N>),
+    Auto,
+}
+
+impl<N: Normalize> Default for LengthOrAuto<N> {
+    fn default() -> LengthOrAuto<N> {
+        LengthOrAuto::Auto
+    }
+}
+
+impl<N: Normalize> LengthOrAuto<N> {
+    pub fn check_nonnegative(self) -> Result<Self, ValueErrorKind> {
+        match self {
+            LengthOrAuto::Length(l) => l.check_nonnegative().map(LengthOrAuto::Length),
+            LengthOrAuto::Auto => Ok(self),
+        }
+    }
+
+    fn normalize(&self, values: &ComputedValues, params: &ViewParams) -> Option<f64> {
+        match *self {
+            LengthOrAuto::Length(ref l) => Some(l.normalize(values, params)),
+            LengthOrAuto::Auto => None,
+        }
+    }
+}
+
+impl<N: Normalize> Parse for LengthOrAuto<N> {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<LengthOrAuto<N>, ValueErrorKind> {
+        if parser
+            .try_parse(|p| p.expect_ident_matching("auto"))
+            .is_ok()
+        {
+            Ok(LengthOrAuto::Auto)
+        } else {
+            Ok(LengthOrAuto::Length(Length::parse(parser)?))
+        }
+    }
+}
+
+impl<N: Normalize> ToCssValue for LengthOrAuto<N> {
+    fn to_css_value(&self) -> String {
+        match *self {
+            LengthOrAuto::Length(ref l) => l.to_css_value(),
+            LengthOrAuto::Auto => "auto".to_string(),
+        }
+    }
+}
+
+/// Resolves a pair of radiuses where each one can be `auto`.
+///
+/// An `auto` radius takes the value of the other one; if both are `auto`, they are zero.
+fn resolve_radiuses(rx: Option<f64>, ry: Option<f64>) -> (f64, f64) {
+    match (rx, ry) {
+        (None, None) => (0.0, 0.0),
+        (Some(rx), None) => (rx, rx),
+        (None, Some(ry)) => (ry, ry),
+        (Some(rx), Some(ry)) => (rx, ry),
+    }
+}
+
+/// Makes the outline of a rectangle, with corners rounded by the `rx` and `ry` radiuses.
+///
+/// The radiuses are clamped to half the width and height of the rectangle.
+pub fn rect_path(x: f64, y: f64, w: f64, h: f64, rx: f64, ry: f64) -> PathBuilder {
+    let (mut rx, mut ry) = (rx, ry);
+
+    let half_w = w / 2.0;
+    let half_h = h / 2.0;
+
+    if rx > half_w {
+        rx = half_w;
+    }
+
+    if ry > half_h {
+        ry = half_h;
+    }
+
+    if rx == 0.0 {
+        ry = 0.0;
+    } else if ry == 0.0 {
+        rx = 0.0;
+    }
+
+    let mut builder = PathBuilder::new();
+
+    if rx == 0.0 {
+        // Easy case, no rounded corners
+        builder.move_to(x, y);
+        builder.line_to(x + w, y);
+        builder.line_to(x + w, y + h);
+        builder.line_to(x, y + h);
+        builder.line_to(x, y);
+        builder.close_path();
+    } else {
+        /* Hard case, rounded corners
+         *
+         *      (top_x1, top_y)                   (top_x2, top_y)
+         *     *--------------------------------*
+         *    /                                  \
+         *   * (left_x, left_y1)                  * (right_x, right_y1)
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   |                                    |
+         *   * (left_x, left_y2)                  * (right_x, right_y2)
+         *    \                                  /
+         *     *--------------------------------*
+         *      (bottom_x1, bottom_y)            (bottom_x2, bottom_y)
+         */
+
+        let top_x1 = x + rx;
+        let top_x2 = x + w - rx;
+        let top_y = y;
+
+        let bottom_x1 = top_x1;
+        let bottom_x2 = top_x2;
+        let bottom_y = y + h;
+
+        let left_x = x;
+        let left_y1 = y + ry;
+        let left_y2 = y + h - ry;
+
+        let right_x = x + w;
+        let right_y1 = left_y1;
+        let right_y2 = left_y2;
+
+        builder.move_to(top_x1, top_y);
+        builder.line_to(top_x2, top_y);
+
+        builder.arc(
+            top_x2,
+            top_y,
+            rx,
+            ry,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            right_x,
+            right_y1,
+        );
+
+        builder.line_to(right_x, right_y2);
+
+        builder.arc(
+            right_x,
+            right_y2,
+            rx,
+            ry,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            bottom_x2,
+            bottom_y,
+        );
+
+        builder.line_to(bottom_x1, bottom_y);
+
+        builder.arc(
+            bottom_x1,
+            bottom_y,
+            rx,
+            ry,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            left_x,
+            left_y2,
+        );
+
+        builder.line_to(left_x, left_y1);
+
+        builder.arc(
+            left_x,
+            left_y1,
+            rx,
+            ry,
+            0.0,
+            LargeArc(false),
+            Sweep::Positive,
+            top_x1,
+            top_y,
+        );
+
+        builder.close_path();
+    }
+
+    builder
+}
+
 #[derive(Default)]
 pub struct Rect {
     x: Length<Horizontal>,
@@ -338,8 +594,8 @@ pub struct Rect {
     h: Length<Vertical>,
 
     // Radiuses for rounded corners
-    rx: Option<Length<Horizontal>>,
-    ry: Option<Length<Vertical>>,
+    rx: LengthOrAuto<Horizontal>,
+    ry: LengthOrAuto<Vertical>,
 }
 
 impl NodeTrait for Rect {
@@ -355,14 +611,10 @@ impl NodeTrait for Rect {
                     self.h = attr.parse_and_validate(value, Length::<Vertical>::check_nonnegative)?
                 }
                 expanded_name!(svg "rx") => {
-                    self.rx = attr
-                        .parse_and_validate(value, Length::<Horizontal>::check_nonnegative)
-                        .map(Some)?
+                    self.rx = attr.parse_and_validate(value, LengthOrAuto::check_nonnegative)?
                 }
                 expanded_name!(svg "ry") => {
-                    self.ry = attr
-                        .parse_and_validate(value, Length::<Vertical>::check_nonnegative)
-                        .map(Some)?
+                    self.ry = attr.parse_and_validate(value, LengthOrAuto::check_nonnegative)?
                 }
                 _ => (),
             }
@@ -382,35 +634,16 @@ impl NodeTrait for Rect {
 
         let params = draw_ctx.get_view_params();
 
-        let x = self.x.normalize(values, &params);
-        let y = self.y.normalize(values, &params);
-        let w = self.w.normalize(values, &params);
-        let h = self.h.normalize(values, &params);
-
-        let mut rx;
-        let mut ry;
-
-        match (self.rx, self.ry) {
-            (None, None) => {
-                rx = 0.0;
-                ry = 0.0;
-            }
-
-            (Some(_rx), None) => {
-                rx = _rx.normalize(values, &params);
-                ry = _rx.normalize(values, &params);
-            }
-
-            (None, Some(_ry)) => {
-                rx = _ry.normalize(values, &params);
-                ry = _ry.normalize(values, &params);
-            }
+        // The geometry properties override the attributes
+        let x = values.x.value_or(self.x).normalize(values, &params);
+        let y = values.y.value_or(self.y).normalize(values, &params);
+        let w = values.width.value_or(self.w).normalize(values, &params);
+        let h = values.height.value_or(self.h).normalize(values, &params);
 
-            (Some(_rx), Some(_ry)) => {
-                rx = _rx.normalize(values, &params);
-                ry = _ry.normalize(values, &params);
-            }
-        }
+        let (rx, ry) = resolve_radiuses(
+            values.rx.value_or(self.rx).normalize(values, &params),
+            values.ry.value_or(self.ry).normalize(values, &params),
+        );
 
         // Per the spec, w,h must be >= 0
         if w <= 0.0 || h <= 0.0 {
@@ -422,130 +655,7 @@ impl NodeTrait for Rect {
             return Ok(draw_ctx.empty_bbox());
         }
 
-        let half_w = w / 2.0;
-        let half_h = h / 2.0;
-
-        if rx > half_w {
-            rx = half_w;
-        }
-
-        if ry > half_h {
-            ry = half_h;
-        }
-
-        if rx == 0.0 {
-            ry = 0.0;
-        } else if ry == 0.0 {
-            rx = 0.0;
-        }
-
-        let mut builder = PathBuilder::new();
-
-        if rx == 0.0 {
-            // Easy case, no rounded corners
-            builder.move_to(x, y);
-            builder.line_to(x + w, y);
-            builder.line_to(x + w, y + h);
-            builder.line_to(x, y + h);
-            builder.line_to(x, y);
-            builder.close_path();
-        } else {
-            /* Hard case, rounded corners
-             *
-             *      (top_x1, top_y)                   (top_x2, top_y)
-             *     *--------------------------------*
-             *    /                                  \
-             *   * (left_x, left_y1)                  * (right_x, right_y1)
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   |                                    |
-             *   * (left_x, left_y2)                  * (right_x, right_y2)
-             *    \                                  /
-             *     *--------------------------------*
-             *      (bottom_x1, bottom_y)            (bottom_x2, bottom_y)
-             */
-
-            let top_x1 = x + rx;
-            let top_x2 = x + w - rx;
-            let top_y = y;
-
-            let bottom_x1 = top_x1;
-            let bottom_x2 = top_x2;
-            let bottom_y = y + h;
-
-            let left_x = x;
-            let left_y1 = y + ry;
-            let left_y2 = y + h - ry;
-
-            let right_x = x + w;
-            let right_y1 = left_y1;
-            let right_y2 = left_y2;
-
-            builder.move_to(top_x1, top_y);
-            builder.line_to(top_x2, top_y);
-
-            builder.arc(
-                top_x2,
-                top_y,
-                rx,
-                ry,
-                0.0,
-                LargeArc(false),
-                Sweep::Positive,
-                right_x,
-                right_y1,
-            );
-
-            builder.line_to(right_x, right_y2);
-
-            builder.arc(
-                right_x,
-                right_y2,
-                rx,
-                ry,
-                0.0,
-                LargeArc(false),
-                Sweep::Positive,
-                bottom_x2,
-                bottom_y,
-            );
-
-            builder.line_to(bottom_x1, bottom_y);
-
-            builder.arc(
-                bottom_x1,
-                bottom_y,
-                rx,
-                ry,
-                0.0,
-                LargeArc(false),
-                Sweep::Positive,
-                left_x,
-                left_y2,
-            );
-
-            builder.line_to(left_x, left_y1);
-
-            builder.arc(
-                left_x,
-                left_y1,
-                rx,
-                ry,
-                0.0,
-                LargeArc(false),
-                Sweep::Positive,
-                top_x1,
-                top_y,
-            );
-
-            builder.close_path();
-        }
+        let builder = rect_path(x, y, w, h, rx, ry);
 
         render_path_builder(&builder, draw_ctx, node, values, false, clipping)
     }
@@ -585,9 +695,9 @@ impl NodeTrait for Circle {
 
         let params = draw_ctx.get_view_params();
 
-        let cx = self.cx.normalize(values, &params);
-        let cy = self.cy.normalize(values, &params);
-        let r = self.r.normalize(values, &params);
+        let cx = values.cx.value_or(self.cx).normalize(values, &params);
+        let cy = values.cy.value_or(self.cy).normalize(values, &params);
+        let r = values.r.value_or(self.r).normalize(values, &params);
 
         render_ellipse(cx, cy, r, r, draw_ctx, node, values, clipping)
     }
@@ -597,8 +707,8 @@ impl NodeTrait for Circle {
 pub struct Ellipse {
     cx: Length<Horizontal>,
     cy: Length<Vertical>,
-    rx: Length<Horizontal>,
-    ry: Length<Vertical>,
+    rx: LengthOrAuto<Horizontal>,
+    ry: LengthOrAuto<Vertical>,
 }
 
 impl NodeTrait for Ellipse {
@@ -608,10 +718,10 @@ impl NodeTrait for Ellipse {
                 expanded_name!(svg "cx") => self.cx = attr.parse(value)?,
                 expanded_name!(svg "cy") => self.cy = attr.parse(value)?,
                 expanded_name!(svg "rx") => {
-                    self.rx = attr.parse_and_validate(value, Length::<Horizontal>::check_nonnegative)?
+                    self.rx = attr.parse_and_validate(value, LengthOrAuto::check_nonnegative)?
                 }
                 expanded_name!(svg "ry") => {
-                    self.ry = attr.parse_and_validate(value, Length::<Vertical>::check_nonnegative)?
+                    self.ry = attr.parse_and_validate(value, LengthOrAuto::check_nonnegative)?
                 }
                 _ => (),
             }
@@ -631,10 +741,12 @@ impl NodeTrait for Ellipse {
 
         let params = draw_ctx.get_view_params();
 
-        let cx = self.cx.normalize(values, &params);
-        let cy = self.cy.normalize(values, &params);
-        let rx = self.rx.normalize(values, &params);
-        let ry = self.ry.normalize(values, &params);
+        let cx = values.cx.value_or(self.cx).normalize(values, &params);
+        let cy = values.cy.value_or(self.cy).normalize(values, &params);
+        let (rx, ry) = resolve_radiuses(
+            values.rx.value_or(self.rx).normalize(values, &params),
+            values.ry.value_or(self.ry).normalize(values, &params),
+        );
 
         render_ellipse(cx, cy, rx, ry, draw_ctx, node, values, clipping)
     }
@@ -674,4 +786,28 @@ mod tests {
         assert!(Points::parse_str("-1-2-3-4").is_err());
         assert!(Points::parse_str("1 2-3,-4").is_err());
     }
+
+    #[test]
+    fn parses_length_or_auto() {
+        assert_eq!(
+            LengthOrAuto::<Horizontal>::parse_str("auto"),
+            Ok(LengthOrAuto::Auto)
+        );
+        assert_eq!(
+            LengthOrAuto::<Horizontal>::parse_str("5px"),
+            Ok(LengthOrAuto::Length(Length::new(5.0, LengthUnit::Px)))
+        );
+        assert!(LengthOrAuto::<Horizontal>::parse_str("-5px")
+            .and_then(LengthOrAuto::check_nonnegative)
+            .is_err());
+        assert!(LengthOrAuto::<Horizontal>::parse_str("foo").is_err());
+    }
+
+    #[test]
+    fn auto_radius_takes_the_other_one() {
+        assert_eq!(resolve_radiuses(None, None), (0.0, 0.0));
+        assert_eq!(resolve_radiuses(Some(2.0), None), (2.0, 2.0));
+        assert_eq!(resolve_radiuses(None, Some(3.0)), (3.0, 3.0));
+        assert_eq!(resolve_radiuses(Some(2.0), Some(3.0)), (2.0, 3.0));
+    }
 }
@@ -8,6 +8,12 @@ pub struct NormalizeDefault {
 pub enum XmlSpaceNormalize {
     Default(NormalizeDefault),
     Preserve,
+
+    /// `white-space: nowrap`; newlines are turned into spaces and then collapsed
+    Collapse(NormalizeDefault),
+
+    /// `white-space: pre-line`; spaces are collapsed but newlines start new lines
+    PreLine(NormalizeDefault),
 }
 
 /// Implements `xml:space` handling per the SVG spec
@@ -19,6 +25,65 @@ pub fn xml_space_normalize(mode: XmlSpaceNormalize, s: &str) -> String {
     match mode {
         XmlSpaceNormalize::Default(d) => normalize_default(d, s),
         XmlSpaceNormalize::Preserve => normalize_preserve(s),
+        XmlSpaceNormalize::Collapse(d) | XmlSpaceNormalize::PreLine(d) => normalize_collapse(d, s),
+    }
+}
+
+/// Like `xml_space_normalize()`, but splits the string into lines at newline characters
+///
+/// This is for the multi-line text extension, where each newline in the character
+/// data starts a new line of text.  Whitespace at the start and end of the string as
+/// a whole is handled as usual, so the newlines that just indent the character data
+/// don't create empty lines.
+pub fn xml_space_normalize_lines(mode: XmlSpaceNormalize, s: &str) -> Vec<String> {
+    match mode {
+        XmlSpaceNormalize::Default(d) => {
+            let mut s = s;
+
+            if !d.has_element_before {
+                s = s.trim_start();
+            }
+
+            if !d.has_element_after {
+                s = s.trim_end();
+            }
+
+            let num_lines = s.split('\n').count();
+
+            s.split('\n')
+                .enumerate()
+                .map(|(i, line)| {
+                    let elements = NormalizeDefault {
+                        has_element_before: i == 0 && d.has_element_before,
+                        has_element_after: i == num_lines - 1 && d.has_element_after,
+                    };
+
+                    normalize_default(elements, line)
+                })
+                .collect()
+        }
+
+        XmlSpaceNormalize::Preserve => s.split('\n').map(normalize_preserve).collect(),
+
+        XmlSpaceNormalize::Collapse(d) => vec![normalize_collapse(d, s)],
+
+        // Unlike the multi-line extension, pre-line keeps the newlines at the start
+        // and end of the string, since they are significant.
+        XmlSpaceNormalize::PreLine(d) => {
+            let num_lines = s.split('\n').count();
+
+            s.split('\n')
+                .enumerate()
+                .map(|(i, line)| {
+                    let elements = NormalizeDefault {
+                        has_element_before: i == 0 && d.has_element_before,
+                        has_element_after: i == num_lines - 1 && d.has_element_after,
+                    };
+
+                    normalize_collapse(elements, line)
+                })
+                .collect()
+        }
     }
 }
 
@@ -52,6 +117,32 @@ fn normalize_default(elements: NormalizeDefault, mut s: &str) -> String {
         .collect::<String>()
 }
 
+// From https://www.w3.org/TR/css-text-3/#white-space-phase-1
+//
+// For `white-space: nowrap` and `pre-line`, newlines are not removed like with
+// xml:space="default"; they are segment breaks, which get turned into spaces
+// before collapsing.
+fn normalize_collapse(elements: NormalizeDefault, mut s: &str) -> String {
+    if !elements.has_element_before {
+        s = s.trim_start();
+    }
+
+    if !elements.has_element_after {
+        s = s.trim_end();
+    }
+
+    s.chars()
+        .map(|ch| match ch {
+            '\n' | '\t' => ' ',
+            c => c,
+        })
+        .coalesce(|current, next| match (current, next) {
+            (' ', ' ') => Ok(' '),
+            (_, _) => Err((current, next)),
+        })
+        .collect::<String>()
+}
+
 // From https://www.w3.org/TR/SVG/text.html#WhiteSpace
 //
 // When xml:space="preserve", the SVG user agent will do the following
@@ -179,4 +270,75 @@ mod tests {
             "       WS    example      duplicate letters       "
         );
     }
+
+    #[test]
+    fn xml_space_lines() {
+        let no_elements = || {
+            XmlSpaceNormalize::Default(NormalizeDefault {
+                has_element_before: false,
+                has_element_after: false,
+            })
+        };
+
+        assert_eq!(
+            xml_space_normalize_lines(no_elements(), "\n    First  line\n\t  second line  \n  "),
+            vec!["First line", "second line"]
+        );
+
+        assert_eq!(
+            xml_space_normalize_lines(no_elements(), "one\n\nthree"),
+            vec!["one", "", "three"]
+        );
+
+        assert_eq!(
+            xml_space_normalize_lines(
+                XmlSpaceNormalize::Default(NormalizeDefault {
+                    has_element_before: true,
+                    has_element_after: true,
+                }),
+                " foo \n bar "
+            ),
+            vec![" foo", "bar "]
+        );
+
+        assert_eq!(
+            xml_space_normalize_lines(XmlSpaceNormalize::Preserve, " foo\t\n bar "),
+            vec![" foo ", " bar "]
+        );
+    }
+
+    #[test]
+    fn white_space_collapse() {
+        let no_elements = || NormalizeDefault {
+            has_element_before: false,
+            has_element_after: false,
+        };
+
+        assert_eq!(
+            xml_space_normalize(
+                XmlSpaceNormalize::Collapse(no_elements()),
+                "\nWS example\nnon-indented  lines\n  "
+            ),
+            "WS example non-indented lines"
+        );
+
+        assert_eq!(
+            xml_space_normalize_lines(
+                XmlSpaceNormalize::PreLine(no_elements()),
+                "\n  First  line\n\t  second line  \n"
+            ),
+            vec!["", "First line", "second line", ""]
+        );
+
+        assert_eq!(
+            xml_space_normalize_lines(
+                XmlSpaceNormalize::PreLine(NormalizeDefault {
+                    has_element_before: true,
+                    has_element_after: true,
+                }),
+                " foo \n bar "
+            ),
+            vec![" foo", "bar "]
+        );
+    }
 }
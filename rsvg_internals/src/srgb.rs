@@ -26,6 +26,29 @@ pub fn unlinearize(c: u8) -> u8 {
     UNLINEARIZE[usize::from(c)]
 }
 
+/// Converts an sRGB color value in the range [0, 1] to a linear sRGB color value.
+///
+/// Unlike `linearize`, this does not go through a lookup table, so it is suitable
+/// for computing intermediate colors that must not lose precision.
+#[inline]
+pub fn linearize_f64(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear sRGB color value in the range [0, 1] to a normal sRGB color value.
+#[inline]
+pub fn unlinearize_f64(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 /// Processing loop of `map_unpremultiplied_components`. Extracted (and public) for benchmarking.
 #[inline]
 pub fn map_unpremultiplied_components_loop<F: Fn(u8) -> u8>(
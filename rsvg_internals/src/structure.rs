@@ -4,6 +4,7 @@ use markup5ever::{expanded_name, local_name, namespace_url, ns};
 use crate::allowed_url::Fragment;
 use crate::aspect_ratio::*;
 use crate::bbox::BoundingBox;
+use crate::cond::LanguageRank;
 use crate::dpi::Dpi;
 use crate::drawing_ctx::{ClipMode, DrawingCtx, ViewParams};
 use crate::error::{AcquireError, AttributeResultExt, RenderingError};
@@ -15,6 +16,7 @@ use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
 use crate::property_defs::Overflow;
 use crate::rect::RectangleExt;
+use crate::render_hooks::RenderHookElement;
 use crate::viewbox::*;
 
 #[derive(Default)]
@@ -53,6 +55,86 @@ impl NodeTrait for NonRendering {
     }
 }
 
+/// An element that librsvg does not know how to render, like `<foreignObject>`
+///
+/// Like `NonRendering`, this does not render its children.  However, if the element has
+/// a `width` and `height`, and the render hooks provide a placeholder, the placeholder
+/// gets drawn in the element's rectangle.
+#[derive(Default)]
+pub struct Unsupported {
+    x: Option<Length<Horizontal>>,
+    y: Option<Length<Vertical>>,
+    width: Option<Length<Horizontal>>,
+    height: Option<Length<Vertical>>,
+}
+
+impl NodeTrait for Unsupported {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        // Elements in other namespaces have their attributes in that namespace as
+        // well, so only look at the local names.  Since we don't really support these
+        // elements, invalid values are ignored instead of putting the element in error.
+        for (attr, value) in pbag.iter() {
+            match attr.local {
+                local_name!("x") => self.x = Length::parse_str(value).ok(),
+                local_name!("y") => self.y = Length::parse_str(value).ok(),
+                local_name!("width") => {
+                    self.width = Length::<Horizontal>::parse_str(value)
+                        .and_then(Length::<Horizontal>::check_nonnegative)
+                        .ok()
+                }
+                local_name!("height") => {
+                    self.height = Length::<Vertical>::parse_str(value)
+                        .and_then(Length::<Vertical>::check_nonnegative)
+                        .ok()
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(
+        &self,
+        node: &RsvgNode,
+        cascaded: &CascadedValues<'_>,
+        draw_ctx: &mut DrawingCtx,
+        clipping: bool,
+    ) -> Result<BoundingBox, RenderingError> {
+        let (width, height) = match (self.width, self.height) {
+            (Some(w), Some(h)) => (w, h),
+            _ => return Ok(draw_ctx.empty_bbox()),
+        };
+
+        if clipping || !draw_ctx.get_render_hooks().has_placeholder() {
+            return Ok(draw_ctx.empty_bbox());
+        }
+
+        let values = cascaded.get();
+        let params = draw_ctx.get_view_params();
+
+        let x = self.x.map_or(0.0, |x| x.normalize(values, &params));
+        let y = self.y.map_or(0.0, |y| y.normalize(values, &params));
+        let w = width.normalize(values, &params);
+        let h = height.normalize(values, &params);
+
+        if w.approx_eq_cairo(0.0) || h.approx_eq_cairo(0.0) {
+            return Ok(draw_ctx.empty_bbox());
+        }
+
+        let rect = Rectangle::new(x, y, w, h);
+
+        draw_ctx.with_discrete_layer(node, values, clipping, &mut |dc| {
+            let cr = dc.get_cairo_context();
+            let hooks = dc.get_render_hooks().clone();
+
+            hooks.draw_placeholder(&cr, &RenderHookElement::new(node, values), &rect);
+
+            Ok(dc.empty_bbox().with_rect(rect))
+        })
+    }
+}
+
 #[derive(Default)]
 pub struct Switch();
 
@@ -71,11 +153,7 @@ impl NodeTrait for Switch {
         let values = cascaded.get();
 
         draw_ctx.with_discrete_layer(node, values, clipping, &mut |dc| {
-            if let Some(child) = node
-                .children()
-                .filter(|c| c.borrow().get_type() != NodeType::Chars)
-                .find(|c| c.borrow().get_cond())
-            {
+            if let Some(child) = switch_child(node) {
                 dc.draw_node_from_stack(&CascadedValues::new(cascaded, &child), &child, clipping)
             } else {
                 Ok(dc.empty_bbox())
@@ -84,6 +162,43 @@ impl NodeTrait for Switch {
     }
 }
 
+/// Chooses the child of a `<switch>` element to render.
+///
+/// Among the children whose conditions are met, the one whose `systemLanguage`
+/// best matches the user's languages is chosen; if there are several with the same
+/// rank, the first one wins.  If none of the children have a matching
+/// `systemLanguage`, the first child without that attribute is chosen.
+pub fn switch_child(node: &RsvgNode) -> Option<RsvgNode> {
+    let mut best: Option<(LanguageRank, RsvgNode)> = None;
+    let mut default = None;
+
+    for child in node
+        .children()
+        .filter(|c| c.borrow().get_type() != NodeType::Chars)
+        .filter(|c| c.borrow().get_cond())
+    {
+        let rank = child.borrow().get_language_rank();
+
+        match rank {
+            Some(rank) => {
+                let is_better = best.as_ref().map_or(true, |(best_rank, _)| rank < *best_rank);
+
+                if is_better {
+                    best = Some((rank, child));
+                }
+            }
+
+            None => {
+                if default.is_none() {
+                    default = Some(child);
+                }
+            }
+        }
+    }
+
+    best.map(|(_, child)| child).or(default)
+}
+
 /// Intrinsic dimensions of an SVG document fragment
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct IntrinsicDimensions {
@@ -104,7 +219,7 @@ pub struct Svg {
 
 impl Svg {
     pub fn get_size(&self, values: &ComputedValues, dpi: Dpi) -> Option<(i32, i32)> {
-        let (_, _, w, h) = self.get_unnormalized_viewport();
+        let (_, _, w, h) = self.get_unnormalized_viewport(values, false);
 
         match (w, h, self.vbox) {
             (w, h, Some(vbox)) => {
@@ -116,7 +231,7 @@ impl Svg {
                 ))
             }
 
-            (w, h, None) if w.unit != LengthUnit::Percent && h.unit != LengthUnit::Percent => {
+            (w, h, None) if !w.unit.is_viewport_relative() && !h.unit.is_viewport_relative() => {
                 let params = ViewParams::new(dpi.x(), dpi.y(), 0.0, 0.0);
 
                 Some((
@@ -128,42 +243,72 @@ impl Svg {
         }
     }
 
-    pub fn get_intrinsic_dimensions(&self) -> IntrinsicDimensions {
+    /// Whether the `width` or `height` attributes are zero, which disables rendering
+    /// of the element.
+    pub fn has_zero_size(&self, values: &ComputedValues) -> bool {
+        let (w, h) = self.get_specified_size(values);
+
+        w.map_or(false, |w| w.length.approx_eq_cairo(0.0))
+            || h.map_or(false, |h| h.length.approx_eq_cairo(0.0))
+    }
+
+    pub fn get_intrinsic_dimensions(&self, values: &ComputedValues) -> IntrinsicDimensions {
+        let (w, h) = self.get_specified_size(values);
+
         IntrinsicDimensions {
-            width: self.w.map(Into::into),
-            height: self.h.map(Into::into),
+            width: w.map(Into::into),
+            height: h.map(Into::into),
             vbox: self.vbox,
         }
     }
 
+    // The width and height properties override the attributes
+    fn get_specified_size(
+        &self,
+        values: &ComputedValues,
+    ) -> (Option<Length<Horizontal>>, Option<Length<Vertical>>) {
+        (
+            values.width.length.or(self.w),
+            values.height.length.or(self.h),
+        )
+    }
+
     // returns (x, y, w, h)
     fn get_unnormalized_viewport(
         &self,
+        values: &ComputedValues,
+        is_inner_svg: bool,
     ) -> (
         Length<Horizontal>,
         Length<Vertical>,
         Length<Horizontal>,
         Length<Vertical>,
     ) {
+        // x & y have no effect on outermost svg, like the attributes
+        let (x, y) = if is_inner_svg {
+            (values.x.length.or(self.x), values.y.length.or(self.y))
+        } else {
+            (None, None)
+        };
+
+        let (w, h) = self.get_specified_size(values);
+
         // these defaults are per the spec
-        let x = self
-            .x
-            .unwrap_or_else(|| Length::<Horizontal>::parse_str("0").unwrap());
-        let y = self
-            .y
-            .unwrap_or_else(|| Length::<Vertical>::parse_str("0").unwrap());
-        let w = self
-            .w
-            .unwrap_or_else(|| Length::<Horizontal>::parse_str("100%").unwrap());
-        let h = self
-            .h
-            .unwrap_or_else(|| Length::<Vertical>::parse_str("100%").unwrap());
+        let x = x.unwrap_or_else(|| Length::<Horizontal>::parse_str("0").unwrap());
+        let y = y.unwrap_or_else(|| Length::<Vertical>::parse_str("0").unwrap());
+        let w = w.unwrap_or_else(|| Length::<Horizontal>::parse_str("100%").unwrap());
+        let h = h.unwrap_or_else(|| Length::<Vertical>::parse_str("100%").unwrap());
 
         (x, y, w, h)
     }
 
-    fn get_viewport(&self, values: &ComputedValues, params: &ViewParams) -> Rectangle {
-        let (x, y, w, h) = self.get_unnormalized_viewport();
+    fn get_viewport(
+        &self,
+        values: &ComputedValues,
+        params: &ViewParams,
+        is_inner_svg: bool,
+    ) -> Rectangle {
+        let (x, y, w, h) = self.get_unnormalized_viewport(values, is_inner_svg);
 
         Rectangle::new(
             x.normalize(values, &params),
@@ -226,7 +371,7 @@ impl NodeTrait for Svg {
             None
         };
 
-        let svg_viewport = self.get_viewport(values, &params);
+        let svg_viewport = self.get_viewport(values, &params, has_parent);
 
         let is_measuring_toplevel_svg = !has_parent && draw_ctx.is_measuring();
 
@@ -255,9 +400,18 @@ impl NodeTrait for Svg {
         };
 
         draw_ctx.with_discrete_layer(node, values, clipping, &mut |dc| {
-            let _params =
+            let params =
                 dc.push_new_viewport(vbox, &viewport, self.preserve_aspect_ratio, clip_mode);
 
+            if !has_parent && dc.is_recording_document_transform() {
+                if params.is_some() {
+                    dc.add_document_transform();
+                }
+
+                // The contents don't matter when only asking for the coordinate system
+                return Ok(dc.empty_bbox());
+            }
+
             node.draw_children(cascaded, dc, clipping)
         })
     }
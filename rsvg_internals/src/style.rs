@@ -2,6 +2,7 @@ use cssparser::Parser;
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
 
 use crate::error::*;
+use crate::media::MediaQueryList;
 use crate::node::{NodeResult, NodeTrait, RsvgNode};
 use crate::parsers::{Parse, ParseValue};
 use crate::property_bag::PropertyBag;
@@ -35,19 +36,27 @@ impl Parse for StyleType {
 #[derive(Default)]
 pub struct Style {
     type_: Option<StyleType>,
+    media: Option<MediaQueryList>,
 }
 
 impl Style {
     pub fn style_type(&self) -> Option<StyleType> {
         self.type_
     }
+
+    /// The media queries from the `media` attribute, if it is present
+    pub fn media(&self) -> Option<&MediaQueryList> {
+        self.media.as_ref()
+    }
 }
 
 impl NodeTrait for Style {
     fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
         for (attr, value) in pbag.iter() {
-            if attr.expanded() == expanded_name!(svg "type") {
-                self.type_ = Some(attr.parse(value)?);
+            match attr.expanded() {
+                expanded_name!(svg "type") => self.type_ = Some(attr.parse(value)?),
+                expanded_name!(svg "media") => self.media = Some(attr.parse(value)?),
+                _ => (),
             }
         }
 
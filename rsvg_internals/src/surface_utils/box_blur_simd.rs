@@ -0,0 +1,240 @@
+//! SIMD implementation of the box blur.
+//!
+//! The box blur is the inner loop of `feGaussianBlur`.  The scalar implementation in
+//! `SharedImageSurface::box_blur_loop()` keeps a running sum for each channel of a pixel.
+//! Here, the four channels of a pixel are summed in the lanes of one SSE register, and the
+//! vertical blur processes four adjacent columns at once, as rows of 16 bytes.
+//!
+//! The sums are divided by the kernel size with the same double-precision arithmetic as
+//! the scalar code, so both implementations produce identical pixels.
+
+/// Returns whether the SIMD implementation can be used on this CPU.
+#[inline]
+pub fn is_available() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        is_x86_feature_detected!("sse4.1")
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        false
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use self::sse41::{box_blur_line, box_blur_line_x4};
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub use self::unsupported::{box_blur_line, box_blur_line_x4};
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse41 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use std::cmp::min;
+
+    /// Loads an ARGB32 pixel, with each channel in a 32-bit lane.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn load_pixel(ptr: *const u8) -> __m128i {
+        _mm_cvtepu8_epi32(_mm_cvtsi32_si128((ptr as *const i32).read_unaligned()))
+    }
+
+    /// Stores the four 32-bit lanes of `v` as an ARGB32 pixel.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn store_pixel(ptr: *mut u8, v: __m128i) {
+        let v = _mm_packus_epi16(_mm_packus_epi32(v, v), v);
+        (ptr as *mut i32).write_unaligned(_mm_cvtsi128_si32(v));
+    }
+
+    /// Loads four adjacent ARGB32 pixels, with each channel in a 32-bit lane.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn load_pixels_x4(ptr: *const u8) -> [__m128i; 4] {
+        let v = _mm_loadu_si128(ptr as *const __m128i);
+
+        [
+            _mm_cvtepu8_epi32(v),
+            _mm_cvtepu8_epi32(_mm_srli_si128(v, 4)),
+            _mm_cvtepu8_epi32(_mm_srli_si128(v, 8)),
+            _mm_cvtepu8_epi32(_mm_srli_si128(v, 12)),
+        ]
+    }
+
+    /// Stores the channels of four pixels as four adjacent ARGB32 pixels.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn store_pixels_x4(ptr: *mut u8, v: [__m128i; 4]) {
+        let v = _mm_packus_epi16(_mm_packus_epi32(v[0], v[1]), _mm_packus_epi32(v[2], v[3]));
+        _mm_storeu_si128(ptr as *mut __m128i, v);
+    }
+
+    /// Divides the sums in the four lanes by the kernel size.
+    ///
+    /// This rounds like `box_blur_loop()`, which computes `(sum as f64 / kernel_size + 0.5)
+    /// as u8`.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn average(sum: __m128i, kernel_size: __m128d) -> __m128i {
+        let half = _mm_set1_pd(0.5);
+
+        let low = _mm_cvtepi32_pd(sum);
+        let high = _mm_cvtepi32_pd(_mm_srli_si128(sum, 8));
+
+        let low = _mm_cvttpd_epi32(_mm_add_pd(_mm_div_pd(low, kernel_size), half));
+        let high = _mm_cvttpd_epi32(_mm_add_pd(_mm_div_pd(high, kernel_size), half));
+
+        _mm_unpacklo_epi64(low, high)
+    }
+
+    /// Adds four adjacent pixels to the sums of their channels.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn add_x4(sums: &mut [__m128i; 4], ptr: *const u8) {
+        for (sum, v) in sums.iter_mut().zip(load_pixels_x4(ptr).iter()) {
+            *sum = _mm_add_epi32(*sum, *v);
+        }
+    }
+
+    /// Subtracts four adjacent pixels from the sums of their channels.
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn subtract_x4(sums: &mut [__m128i; 4], ptr: *const u8) {
+        for (sum, v) in sums.iter_mut().zip(load_pixels_x4(ptr).iter()) {
+            *sum = _mm_sub_epi32(*sum, *v);
+        }
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn average_x4(sums: &[__m128i; 4], kernel_size: __m128d) -> [__m128i; 4] {
+        [
+            average(sums[0], kernel_size),
+            average(sums[1], kernel_size),
+            average(sums[2], kernel_size),
+            average(sums[3], kernel_size),
+        ]
+    }
+
+    /// Box-blurs a line of `len` pixels.
+    ///
+    /// The pixels of the line are `input_step` bytes apart in the input, and `output_step`
+    /// bytes apart in the output: 4 for a row, or the stride for a column.  `kernel_size`
+    /// and `target` are as for `SharedImageSurface::box_blur()`.  Pixels outside of the
+    /// line are taken to be transparent.
+    ///
+    /// # Safety
+    /// The CPU must support SSE4.1, and `input` and `output` must point to lines of `len`
+    /// pixels.
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn box_blur_line(
+        input: *const u8,
+        input_step: isize,
+        output: *mut u8,
+        output_step: isize,
+        len: usize,
+        kernel_size: usize,
+        target: usize,
+    ) {
+        let shift = kernel_size - target;
+        let divisor = _mm_set1_pd(kernel_size as f64);
+        let mut sum = _mm_setzero_si128();
+
+        for j in 0..min(len, shift) {
+            sum = _mm_add_epi32(sum, load_pixel(input.offset(j as isize * input_step)));
+        }
+
+        store_pixel(output, average(sum, divisor));
+
+        for j in 1..len {
+            if j > target {
+                let old = input.offset((j - target - 1) as isize * input_step);
+                sum = _mm_sub_epi32(sum, load_pixel(old));
+            }
+
+            if j + shift - 1 < len {
+                let new = input.offset((j + shift - 1) as isize * input_step);
+                sum = _mm_add_epi32(sum, load_pixel(new));
+            }
+
+            store_pixel(output.offset(j as isize * output_step), average(sum, divisor));
+        }
+    }
+
+    /// Box-blurs four adjacent lines of `len` pixels, which must be columns.
+    ///
+    /// This is like `box_blur_line()`, but each step reads and writes four adjacent pixels.
+    ///
+    /// # Safety
+    /// The CPU must support SSE4.1, and `input` and `output` must point to four adjacent
+    /// columns of `len` pixels.
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn box_blur_line_x4(
+        input: *const u8,
+        input_step: isize,
+        output: *mut u8,
+        output_step: isize,
+        len: usize,
+        kernel_size: usize,
+        target: usize,
+    ) {
+        let shift = kernel_size - target;
+        let divisor = _mm_set1_pd(kernel_size as f64);
+        let mut sums = [_mm_setzero_si128(); 4];
+
+        for j in 0..min(len, shift) {
+            add_x4(&mut sums, input.offset(j as isize * input_step));
+        }
+
+        store_pixels_x4(output, average_x4(&sums, divisor));
+
+        for j in 1..len {
+            if j > target {
+                subtract_x4(&mut sums, input.offset((j - target - 1) as isize * input_step));
+            }
+
+            if j + shift - 1 < len {
+                add_x4(&mut sums, input.offset((j + shift - 1) as isize * input_step));
+            }
+
+            let averages = average_x4(&sums, divisor);
+            store_pixels_x4(output.offset(j as isize * output_step), averages);
+        }
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+mod unsupported {
+    /// # Safety
+    /// Never call this; `is_available()` is false on this architecture.
+    pub unsafe fn box_blur_line(
+        _input: *const u8,
+        _input_step: isize,
+        _output: *mut u8,
+        _output_step: isize,
+        _len: usize,
+        _kernel_size: usize,
+        _target: usize,
+    ) {
+        unreachable!("no SIMD box blur on this architecture");
+    }
+
+    /// # Safety
+    /// Never call this; `is_available()` is false on this architecture.
+    pub unsafe fn box_blur_line_x4(
+        _input: *const u8,
+        _input_step: isize,
+        _output: *mut u8,
+        _output_step: isize,
+        _len: usize,
+        _kernel_size: usize,
+        _target: usize,
+    ) {
+        unreachable!("no SIMD box blur on this architecture");
+    }
+}
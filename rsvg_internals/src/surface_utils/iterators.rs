@@ -15,6 +15,17 @@ pub struct Pixels<'a> {
     offset: isize,
 }
 
+/// Iterator over the rows of pixel data of a `SharedImageSurface`.
+///
+/// Each item is the `y` coordinate of a row and the row's data within the bounds, in the
+/// surface's format; see `SharedImageSurface::row()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Rows<'a> {
+    surface: &'a SharedImageSurface,
+    bounds: IRect,
+    y: u32,
+}
+
 /// Iterator over a (potentially out of bounds) rectangle of pixels of a `SharedImageSurface`.
 #[derive(Debug, Clone, Copy)]
 pub struct PixelRectangle<'a> {
@@ -45,7 +56,30 @@ impl<'a> Pixels<'a> {
             bounds,
             x: bounds.x0 as u32,
             y: bounds.y0 as u32,
-            offset: bounds.y0 as isize * surface.stride() as isize + bounds.x0 as isize * 4,
+            offset: bounds.y0 as isize * surface.stride() as isize
+                + bounds.x0 as isize * surface.bytes_per_pixel(),
+        }
+    }
+}
+
+impl<'a> Rows<'a> {
+    /// Creates an iterator over the rows of the image surface, constrained within the given bounds.
+    #[inline]
+    pub fn new(surface: &'a SharedImageSurface, bounds: IRect) -> Self {
+        // Sanity checks.
+        assert!(bounds.x0 >= 0);
+        assert!(bounds.x0 <= surface.width());
+        assert!(bounds.x1 >= bounds.x0);
+        assert!(bounds.x1 <= surface.width());
+        assert!(bounds.y0 >= 0);
+        assert!(bounds.y0 <= surface.height());
+        assert!(bounds.y1 >= bounds.y0);
+        assert!(bounds.y1 <= surface.height());
+
+        Self {
+            surface,
+            bounds,
+            y: bounds.y0 as u32,
         }
     }
 }
@@ -105,19 +139,43 @@ impl<'a> Iterator for Pixels<'a> {
             self.surface.get_pixel_by_offset(self.offset),
         ));
 
+        let bytes_per_pixel = self.surface.bytes_per_pixel();
+
         if self.x + 1 == self.bounds.x1 as u32 {
             self.x = self.bounds.x0 as u32;
             self.y += 1;
-            self.offset += self.surface.stride() - (self.bounds.width() - 1) as isize * 4;
+            self.offset +=
+                self.surface.stride() - (self.bounds.width() - 1) as isize * bytes_per_pixel;
         } else {
             self.x += 1;
-            self.offset += 4;
+            self.offset += bytes_per_pixel;
         }
 
         rv
     }
 }
 
+impl<'a> Iterator for Rows<'a> {
+    type Item = (u32, &'a [u8]);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.y >= self.bounds.y1 as u32 || self.bounds.x0 == self.bounds.x1 {
+            return None;
+        }
+
+        let bytes_per_pixel = self.surface.bytes_per_pixel() as usize;
+        let start = self.bounds.x0 as usize * bytes_per_pixel;
+        let end = self.bounds.x1 as usize * bytes_per_pixel;
+
+        let rv = Some((self.y, &self.surface.row(self.y)[start..end]));
+
+        self.y += 1;
+
+        rv
+    }
+}
+
 impl<'a> Iterator for PixelRectangle<'a> {
     type Item = (i32, i32, Pixel);
 
@@ -179,6 +237,7 @@ impl<'a> Iterator for PixelRectangle<'a> {
 mod tests {
     use super::*;
     use crate::surface_utils::shared_surface::SurfaceType;
+    use crate::surface_utils::ImageSurfaceDataExt;
     use cairo::{self, ImageSurface};
 
     #[test]
@@ -224,6 +283,69 @@ mod tests {
         assert_eq!(Pixels::new(&surface, bounds).count(), 0);
     }
 
+    #[test]
+    fn pixels_of_other_formats() {
+        const WIDTH: i32 = 5;
+        const HEIGHT: i32 = 3;
+
+        let mut a8 = ImageSurface::create(cairo::Format::A8, WIDTH, HEIGHT).unwrap();
+        let mut rgb24 = ImageSurface::create(cairo::Format::Rgb24, WIDTH, HEIGHT).unwrap();
+
+        {
+            let stride = a8.get_stride() as usize;
+            let mut data = a8.get_data().unwrap();
+            data.set_alpha(stride, 0x80, 3, 2);
+        }
+
+        {
+            let stride = rgb24.get_stride() as usize;
+            let mut data = rgb24.get_data().unwrap();
+            let pixel = Pixel {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 0,
+            };
+            data.set_pixel(stride, pixel, 3, 2);
+        }
+
+        let a8 = SharedImageSurface::new(a8, SurfaceType::AlphaOnly).unwrap();
+        let rgb24 = SharedImageSurface::new(rgb24, SurfaceType::SRgb).unwrap();
+
+        assert_eq!(a8.bytes_per_pixel(), 1);
+        assert_eq!(
+            a8.get_pixel(3, 2),
+            Pixel {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0x80,
+            }
+        );
+        assert_eq!(
+            rgb24.get_pixel(3, 2),
+            Pixel {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 0xff,
+            }
+        );
+
+        let bounds = IRect::new(1, 1, 4, 3);
+
+        let alphas: Vec<u8> = Pixels::new(&a8, bounds).map(|(_, _, p)| p.a).collect();
+        assert_eq!(alphas, vec![0, 0, 0, 0, 0, 0x80]);
+
+        let rows: Vec<(u32, &[u8])> = Rows::new(&a8, bounds).collect();
+        assert_eq!(rows, vec![(1, &[0, 0, 0][..]), (2, &[0, 0, 0x80][..])]);
+
+        let lengths: Vec<usize> = Rows::new(&rgb24, bounds)
+            .map(|(_, row)| row.len())
+            .collect();
+        assert_eq!(lengths, vec![12, 12]);
+    }
+
     #[test]
     fn pixel_rectangle() {
         const WIDTH: i32 = 32;
@@ -1,8 +1,10 @@
 //! Various utilities for working with Cairo image surfaces.
 use std::ops::DerefMut;
+use std::slice;
 
 use cairo;
 
+pub mod box_blur_simd;
 pub mod iterators;
 pub mod shared_surface;
 
@@ -38,6 +40,131 @@ pub trait ImageSurfaceDataExt: DerefMut<Target = [u8]> {
             *(&mut self[y as usize * stride + x as usize * 4] as *mut u8 as *mut u32) = value;
         }
     }
+
+    /// Sets the alpha value at the given coordinates. Assumes the `A8` format.
+    #[inline]
+    fn set_alpha(&mut self, stride: usize, alpha: u8, x: u32, y: u32) {
+        self[y as usize * stride + x as usize] = alpha;
+    }
+
+    /// Returns the first `width` pixels of row `y` as `u32` values. Assumes the `ARgb32` format.
+    #[inline]
+    fn argb_row_mut(&mut self, stride: usize, y: u32, width: usize) -> &mut [u32] {
+        let start = y as usize * stride;
+        let row = &mut self[start..start + width * 4];
+
+        // Cairo aligns the rows of image surfaces to 4 bytes, so this cast is fine.
+        unsafe { slice::from_raw_parts_mut(row.as_mut_ptr() as *mut u32, width) }
+    }
+}
+
+// The conversions below work on pixels in the `ARgb32` format, and they only use integer
+// arithmetic without branches or divisions, so that the compiler can vectorize the loops
+// over whole rows in the *_row() functions.
+
+/// Computes `round(x * a / 255)` for the 8-bit values in bits 0-7 and 16-23 of `x` at once.
+#[inline(always)]
+fn multiply_two_channels(x: u32, a: u32) -> u32 {
+    // Each product fits in 16 bits, so the two lanes don't overflow into each other.
+    let t = (x & 0x00ff_00ff) * a + 0x0080_0080;
+    ((t + ((t >> 8) & 0x00ff_00ff)) >> 8) & 0x00ff_00ff
+}
+
+/// Premultiplies an `ARgb32` pixel.
+#[inline(always)]
+pub fn premultiply_argb(pixel: u32) -> u32 {
+    let a = pixel >> 24;
+    let rb = multiply_two_channels(pixel, a);
+    let g = (multiply_two_channels(pixel >> 8, a) & 0xff) << 8;
+
+    (pixel & 0xff00_0000) | rb | g
+}
+
+/// Returns the fixed-point factor with which `unpremultiply_argb()` divides by `alpha`.
+///
+/// This is `ceil(255 * 2^16 / alpha)`, which gives correctly rounded results for all the
+/// premultiplied values.
+#[inline]
+fn unpremultiply_factor(alpha: u8) -> u32 {
+    if alpha == 0 {
+        0
+    } else {
+        let a = u32::from(alpha);
+        (255 * 65536 + a - 1) / a
+    }
+}
+
+/// Factors for `unpremultiply_argb()`, indexed by alpha.
+pub fn unpremultiply_factors() -> [u32; 256] {
+    let mut factors = [0; 256];
+
+    for (alpha, factor) in factors.iter_mut().enumerate() {
+        *factor = unpremultiply_factor(alpha as u8);
+    }
+
+    factors
+}
+
+/// Unpremultiplies an `ARgb32` pixel, given `factor = unpremultiply_factors()[alpha]`.
+///
+/// Fully transparent pixels become zero.
+#[inline(always)]
+pub fn unpremultiply_argb(pixel: u32, factor: u32) -> u32 {
+    let channel = |shift: u32| {
+        let x = (pixel >> shift) & 0xff;
+        ((x * factor + 0x8000) >> 16).min(0xff) << shift
+    };
+
+    (pixel & 0xff00_0000) | channel(16) | channel(8) | channel(0)
+}
+
+/// Computes the alpha of a luminance mask from an `ARgb32` pixel; see `Pixel::to_mask()`.
+#[inline(always)]
+pub fn luminance_to_alpha(pixel: u32, opacity: u8) -> u8 {
+    let r = (pixel >> 16) & 0xff;
+    let g = (pixel >> 8) & 0xff;
+    let b = pixel & 0xff;
+    let o = u32::from(opacity);
+
+    (((r * 14042 + g * 47240 + b * 4769) * o) >> 24) as u8
+}
+
+/// Computes the alpha of an alpha mask from an `ARgb32` pixel, which is its alpha
+/// multiplied by `opacity`.
+#[inline(always)]
+pub fn alpha_to_mask(pixel: u32, opacity: u8) -> u8 {
+    let a = pixel >> 24;
+    let o = u32::from(opacity);
+
+    ((a * o + 127) / 255) as u8
+}
+
+/// Unpremultiplies a row of `ARgb32` pixels from `src` into `dst`.
+pub fn unpremultiply_row(src: &[u32], dst: &mut [u32], factors: &[u32; 256]) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = unpremultiply_argb(s, factors[(s >> 24) as usize]);
+    }
+}
+
+/// Copies the alpha channel of a row of `ARgb32` pixels from `src` into an `A8` row in `dst`.
+pub fn alpha_row(src: &[u32], dst: &mut [u8]) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = (s >> 24) as u8;
+    }
+}
+
+/// Computes a luminance mask from a row of `ARgb32` pixels in `src` into an `A8` row in `dst`.
+pub fn luminance_to_alpha_row(src: &[u32], dst: &mut [u8], opacity: u8) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = luminance_to_alpha(s, opacity);
+    }
+}
+
+/// Computes an alpha mask from a row of `ARgb32` pixels in `src` into an `A8` row in `dst`.
+pub fn alpha_to_mask_row(src: &[u32], dst: &mut [u8], opacity: u8) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d = alpha_to_mask(s, opacity);
+    }
 }
 
 impl Pixel {
@@ -47,30 +174,17 @@ impl Pixel {
         if self.a == 0 {
             self
         } else {
-            let alpha = f64::from(self.a) / 255.0;
-            let unpremultiply = |x| ((f64::from(x) / alpha) + 0.5) as u8;
-
-            Self {
-                r: unpremultiply(self.r),
-                g: unpremultiply(self.g),
-                b: unpremultiply(self.b),
-                a: self.a,
-            }
+            Self::from_u32(unpremultiply_argb(
+                self.to_u32(),
+                unpremultiply_factor(self.a),
+            ))
         }
     }
 
     /// Returns a premultiplied value of this pixel.
     #[inline]
     pub fn premultiply(self) -> Self {
-        let alpha = f64::from(self.a) / 255.0;
-        let premultiply = |x| ((f64::from(x) * alpha) + 0.5) as u8;
-
-        Self {
-            r: premultiply(self.r),
-            g: premultiply(self.g),
-            b: premultiply(self.b),
-            a: self.a,
-        }
+        Self::from_u32(premultiply_argb(self.to_u32()))
     }
 
     /// Returns the pixel value as a `u32`, in the same format as `cairo::Format::ARgb32`.
@@ -113,16 +227,11 @@ impl Pixel {
     /// if pixel = 0x00020202, pixel' = 0x02......
     /// if pixel = 0x00000000, pixel' = 0x00......
     pub fn to_mask(self, opacity: u8) -> Self {
-        let r = u32::from(self.r);
-        let g = u32::from(self.g);
-        let b = u32::from(self.b);
-        let o = u32::from(opacity);
-
         Self {
             r: 0,
             g: 0,
             b: 0,
-            a: (((r * 14042 + g * 47240 + b * 4769) * o) >> 24) as u8,
+            a: luminance_to_alpha(self.to_u32(), opacity),
         }
     }
 
@@ -149,3 +258,56 @@ impl Pixel {
 
 impl<'a> ImageSurfaceDataExt for cairo::ImageSurfaceData<'a> {}
 impl<'a> ImageSurfaceDataExt for &'a mut [u8] {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiply_matches_float_math() {
+        for a in 0..=255u32 {
+            for x in 0..=255u32 {
+                let pixel = (a << 24) | (x << 16) | (x << 8) | x;
+                let expected = ((f64::from(x) * f64::from(a) / 255.0) + 0.5) as u32;
+
+                assert_eq!(
+                    premultiply_argb(pixel),
+                    (a << 24) | (expected << 16) | (expected << 8) | expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unpremultiply_rounds_correctly() {
+        let factors = unpremultiply_factors();
+
+        for a in 1..=255u32 {
+            for x in 0..=a {
+                let pixel = (a << 24) | (x << 16) | (x << 8) | x;
+                let expected = (x * 255 * 2 + a) / (a * 2);
+
+                assert_eq!(
+                    unpremultiply_argb(pixel, factors[a as usize]),
+                    (a << 24) | (expected << 16) | (expected << 8) | expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn premultiply_round_trips() {
+        let factors = unpremultiply_factors();
+        let src: Vec<u32> = (0..=255u32)
+            .map(|x| premultiply_argb(0xff00_0000 | (x << 16) | ((255 - x) << 8) | x))
+            .collect();
+        let mut dst = vec![0; src.len()];
+
+        unpremultiply_row(&src, &mut dst, &factors);
+        assert_eq!(src, dst);
+
+        let mut alpha = vec![0; src.len()];
+        alpha_row(&src, &mut alpha);
+        assert!(alpha.iter().all(|&a| a == 0xff));
+    }
+}
@@ -2,6 +2,7 @@
 use std::cmp::min;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
+use std::slice;
 
 use cairo::{self, ImageSurface};
 use cairo_sys;
@@ -9,13 +10,21 @@ use gdk_pixbuf::{Colorspace, Pixbuf};
 use glib::translate::{Stash, ToGlibPtr};
 use nalgebra::{storage::Storage, Dim, Matrix};
 use rayon;
+use rayon::prelude::*;
 
 use crate::rect::IRect;
 use crate::srgb;
 use crate::util::clamp;
 
 use super::{
+    alpha_row,
+    alpha_to_mask,
+    alpha_to_mask_row,
+    box_blur_simd,
     iterators::{PixelRectangle, Pixels},
+    luminance_to_alpha_row,
+    unpremultiply_factors,
+    unpremultiply_row,
     EdgeMode,
     ImageSurfaceDataExt,
     Pixel,
@@ -56,6 +65,9 @@ pub enum SurfaceType {
 ///
 /// Having just read-only access simplifies things further dropping the need for `Rc<RefCell<>>`
 /// altogether.
+///
+/// Since nothing writes to the pixel data, `SharedImageSurface` is `Send` and `Sync`: clones can be
+/// handed to other threads, and several threads can read the same surface at once.
 #[derive(Debug, Clone)]
 pub struct SharedImageSurface {
     surface: ImageSurface,
@@ -64,6 +76,7 @@ pub struct SharedImageSurface {
     width: i32,
     height: i32,
     stride: isize,
+    format: cairo::Format,
 
     surface_type: SurfaceType,
 }
@@ -71,6 +84,13 @@ pub struct SharedImageSurface {
 // The access is read-only, the ref-counting on an `ImageSurface` is atomic.
 unsafe impl Sync for SharedImageSurface {}
 
+// SAFETY: the cairo surface is exclusively owned by the `SharedImageSurface` and its clones,
+// since `new()` checks that nothing else references it, and it is never aliased across threads
+// through anything that could write to it.  For the same reasons as above, a clone can be
+// dropped on a different thread than the original; cairo destroys the surface when the last
+// reference goes away, whichever thread that happens on.
+unsafe impl Send for SharedImageSurface {}
+
 /// A compile-time blur direction variable.
 pub trait BlurDirection {
     const IS_VERTICAL: bool;
@@ -110,13 +130,21 @@ impl IsAlphaOnly for NotAlphaOnly {
 impl SharedImageSurface {
     /// Creates a `SharedImageSurface` from a unique `ImageSurface`.
     ///
+    /// The surface can be in the `ARgb32`, `Rgb24`, or `A8` formats.  Pixels from `Rgb24`
+    /// surfaces are opaque, and pixels from `A8` surfaces are black.
+    ///
     /// # Panics
-    /// Panics if the surface format isn't `ARgb32` and if the surface is not unique, that is, its
-    /// reference count isn't 1.
+    /// Panics if the surface format isn't one of the above and if the surface is not unique, that
+    /// is, its reference count isn't 1.
     #[inline]
     pub fn new(surface: ImageSurface, surface_type: SurfaceType) -> Result<Self, cairo::Status> {
-        // get_pixel() assumes ARgb32.
-        assert_eq!(surface.get_format(), cairo::Format::ARgb32);
+        // get_pixel_by_offset() knows how to read these formats.
+        let format = surface.get_format();
+        assert!(
+            format == cairo::Format::ARgb32
+                || format == cairo::Format::Rgb24
+                || format == cairo::Format::A8
+        );
 
         let reference_count =
             unsafe { cairo_sys::cairo_surface_get_reference_count(surface.to_raw_none()) };
@@ -146,11 +174,14 @@ impl SharedImageSurface {
             width,
             height,
             stride,
+            format,
             surface_type,
         })
     }
 
     /// Converts this `SharedImageSurface` back into a Cairo image surface.
+    ///
+    /// The result has the same format as the original surface, unless it had to be copied.
     #[inline]
     pub fn into_image_surface(self) -> Result<ImageSurface, cairo::Status> {
         let reference_count =
@@ -261,6 +292,21 @@ impl SharedImageSurface {
         self.stride
     }
 
+    /// Returns the format of the pixel data.
+    #[inline]
+    pub fn format(&self) -> cairo::Format {
+        self.format
+    }
+
+    /// Returns the number of bytes that each pixel takes in the pixel data.
+    #[inline]
+    pub fn bytes_per_pixel(&self) -> isize {
+        match self.format {
+            cairo::Format::A8 => 1,
+            _ => 4,
+        }
+    }
+
     /// Returns `true` if the surface contains meaningful data only in the alpha channel.
     #[inline]
     pub fn is_alpha_only(&self) -> bool {
@@ -273,20 +319,20 @@ impl SharedImageSurface {
         self.surface_type
     }
 
+    /// Returns `true` if both surfaces share the same pixel data, i.e. one is a clone of the
+    /// other.
+    #[inline]
+    pub fn is_same_surface(&self, other: &SharedImageSurface) -> bool {
+        self.data_ptr == other.data_ptr
+    }
+
     /// Retrieves the pixel value at the given coordinates.
     #[inline]
     pub fn get_pixel(&self, x: u32, y: u32) -> Pixel {
         assert!(x < self.width as u32);
         assert!(y < self.height as u32);
 
-        let value = unsafe {
-            *(self
-                .data_ptr
-                .as_ptr()
-                .offset(y as isize * self.stride + x as isize * 4) as *const u32)
-        };
-
-        Pixel::from_u32(value)
+        self.get_pixel_by_offset(y as isize * self.stride + x as isize * self.bytes_per_pixel())
     }
 
     /// Retrieves the pixel value by offset into the pixel data array.
@@ -294,8 +340,49 @@ impl SharedImageSurface {
     pub fn get_pixel_by_offset(&self, offset: isize) -> Pixel {
         assert!(offset < self.stride as isize * self.height as isize);
 
-        let value = unsafe { *(self.data_ptr.as_ptr().offset(offset) as *const u32) };
-        Pixel::from_u32(value)
+        let ptr = unsafe { self.data_ptr.as_ptr().offset(offset) };
+
+        match self.format {
+            cairo::Format::A8 => Pixel {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: unsafe { *ptr },
+            },
+
+            cairo::Format::Rgb24 => Pixel {
+                a: 0xff,
+                ..Pixel::from_u32(unsafe { *(ptr as *const u32) })
+            },
+
+            _ => Pixel::from_u32(unsafe { *(ptr as *const u32) }),
+        }
+    }
+
+    /// Returns the pixel data of row `y`, without the padding at the end of the row.
+    ///
+    /// The layout of the data depends on the `format()`.  For `ARgb32` and `Rgb24`, each pixel
+    /// is a native-endian `u32`; for `A8`, each pixel is a byte.  Processing whole rows at a time
+    /// lets the compiler vectorize loops, which it can't do with `get_pixel()`.
+    #[inline]
+    pub fn row(&self, y: u32) -> &[u8] {
+        assert!(y < self.height as u32);
+
+        unsafe {
+            slice::from_raw_parts(
+                self.data_ptr.as_ptr().offset(y as isize * self.stride),
+                self.width as usize * self.bytes_per_pixel() as usize,
+            )
+        }
+    }
+
+    /// Returns row `y` as `u32` pixels.  Assumes the `ARgb32` or `Rgb24` format.
+    #[inline]
+    fn argb_row(&self, y: u32) -> &[u32] {
+        let row = self.row(y);
+
+        // Cairo aligns the rows of image surfaces to 4 bytes, so this cast is fine.
+        unsafe { slice::from_raw_parts(row.as_ptr() as *const u32, row.len() / 4) }
     }
 
     /// Calls `set_source_surface()` on the given Cairo context.
@@ -311,6 +398,8 @@ impl SharedImageSurface {
 
     /// Returns a new `ImageSurface` with the same contents as the one stored in this
     /// `SharedImageSurface` within the given bounds.
+    ///
+    /// The result is always an `ARgb32` surface, so that callers can draw on it.
     pub fn copy_surface(&self, bounds: IRect) -> Result<ImageSurface, cairo::Status> {
         let output_surface = ImageSurface::create(cairo::Format::ARgb32, self.width, self.height)?;
 
@@ -335,7 +424,7 @@ impl SharedImageSurface {
         x: f64,
         y: f64,
     ) -> Result<SharedImageSurface, cairo::Status> {
-        let output_surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+        let output_surface = ImageSurface::create(self.format, width, height)?;
 
         {
             let cr = cairo::Context::new(&output_surface);
@@ -370,22 +459,29 @@ impl SharedImageSurface {
     }
 
     /// Returns a surface with black background and alpha channel matching this surface.
+    ///
+    /// The result is an `A8` surface, which takes a quarter of the memory of an `ARgb32` one.
     pub fn extract_alpha(&self, bounds: IRect) -> Result<SharedImageSurface, cairo::Status> {
-        let mut output_surface =
-            ImageSurface::create(cairo::Format::ARgb32, self.width, self.height)?;
+        let mut output_surface = ImageSurface::create(cairo::Format::A8, self.width, self.height)?;
 
         let output_stride = output_surface.get_stride() as usize;
         {
             let mut output_data = output_surface.get_data().unwrap();
 
-            for (x, y, Pixel { a, .. }) in Pixels::new(self, bounds) {
-                let output_pixel = Pixel {
-                    r: 0,
-                    g: 0,
-                    b: 0,
-                    a,
-                };
-                output_data.set_pixel(output_stride, output_pixel, x, y);
+            if self.format == cairo::Format::ARgb32 {
+                let (x0, x1) = (bounds.x0 as usize, bounds.x1 as usize);
+
+                for y in bounds.y0 as u32..bounds.y1 as u32 {
+                    let start = y as usize * output_stride;
+                    alpha_row(
+                        &self.argb_row(y)[x0..x1],
+                        &mut output_data[start + x0..start + x1],
+                    );
+                }
+            } else {
+                for (x, y, Pixel { a, .. }) in Pixels::new(self, bounds) {
+                    output_data.set_alpha(output_stride, a, x, y);
+                }
             }
         }
 
@@ -397,23 +493,61 @@ impl SharedImageSurface {
     /// surface's RGB values are not meanignful; only the alpha channel has
     /// useful luminance data.
     ///
-    /// This is to get a mask suitable for use with cairo_mask_surface().
+    /// This is to get a mask suitable for use with cairo_mask_surface().  The result is an `A8`
+    /// surface, since cairo only uses the alpha channel of masks.
     pub fn to_mask(&self, opacity: u8) -> Result<SharedImageSurface, cairo::Status> {
-        let bounds = IRect::from_size(self.width, self.height);
+        self.map_to_alpha(
+            |src, dst| luminance_to_alpha_row(src, dst, opacity),
+            |pixel| pixel.to_mask(opacity).a,
+        )
+    }
 
-        let mut output_surface =
-            ImageSurface::create(cairo::Format::ARgb32, self.width, self.height)?;
+    /// Returns a surface whose alpha channel is this surface's alpha channel, multiplied by
+    /// `opacity`.
+    ///
+    /// This is the mask for `mask-type: alpha`; like `to_mask()`, the result is an `A8` surface.
+    pub fn to_alpha_mask(&self, opacity: u8) -> Result<SharedImageSurface, cairo::Status> {
+        self.map_to_alpha(
+            |src, dst| alpha_to_mask_row(src, dst, opacity),
+            |pixel| alpha_to_mask(pixel.to_u32(), opacity),
+        )
+    }
+
+    /// Computes an `A8` surface with the same size as this one.
+    ///
+    /// `row_fn` computes a row of the result from a row of `ARgb32` pixels, and runs on the
+    /// rows in parallel.  Surfaces in other formats go through `pixel_fn` instead.
+    fn map_to_alpha<R, P>(
+        &self,
+        row_fn: R,
+        pixel_fn: P,
+    ) -> Result<SharedImageSurface, cairo::Status>
+    where
+        R: Fn(&[u32], &mut [u8]) + Sync,
+        P: Fn(Pixel) -> u8,
+    {
+        let mut output_surface = ImageSurface::create(cairo::Format::A8, self.width, self.height)?;
 
         let stride = output_surface.get_stride() as usize;
         {
             let mut data = output_surface.get_data().unwrap();
 
-            for (x, y, pixel) in Pixels::new(self, bounds) {
-                data.set_pixel(stride, pixel.to_mask(opacity), x, y);
+            if self.format == cairo::Format::ARgb32 {
+                let width = self.width as usize;
+
+                data.par_chunks_mut(stride)
+                    .zip(0..self.height as u32)
+                    .for_each(|(row, y)| row_fn(self.argb_row(y), &mut row[..width]));
+            } else {
+                let bounds = IRect::from_size(self.width, self.height);
+
+                for (x, y, pixel) in Pixels::new(self, bounds) {
+                    data.set_alpha(stride, pixel_fn(pixel), x, y);
+                }
             }
         }
 
-        SharedImageSurface::new(output_surface, self.surface_type)
+        SharedImageSurface::new(output_surface, SurfaceType::AlphaOnly)
     }
 
     /// Returns a surface with pre-multiplication of color values undone.
@@ -433,8 +567,18 @@ impl SharedImageSurface {
         {
             let mut data = output_surface.get_data().unwrap();
 
-            for (x, y, pixel) in Pixels::new(self, bounds) {
-                data.set_pixel(stride, pixel.unpremultiply(), x, y);
+            if self.format == cairo::Format::ARgb32 {
+                let factors = unpremultiply_factors();
+                let (x0, x1) = (bounds.x0 as usize, bounds.x1 as usize);
+
+                for y in bounds.y0 as u32..bounds.y1 as u32 {
+                    let output_row = data.argb_row_mut(stride, y, x1);
+                    unpremultiply_row(&self.argb_row(y)[x0..x1], &mut output_row[x0..], &factors);
+                }
+            } else {
+                for (x, y, pixel) in Pixels::new(self, bounds) {
+                    data.set_pixel(stride, pixel.unpremultiply(), x, y);
+                }
             }
         }
 
@@ -858,6 +1002,101 @@ impl SharedImageSurface {
         unsafe { cairo_sys::cairo_surface_mark_dirty(output_surface.to_raw_none()) }
     }
 
+    /// Performs a horizontal or vertical box blur with the SIMD implementation.
+    ///
+    /// This produces the same pixels as `box_blur_loop()`, for `ARgb32` surfaces which are
+    /// not alpha-only.  Rows are blurred one at a time, and columns four at a time.
+    ///
+    /// # Panics
+    /// Panics if `kernel_size` is `0`, if `target >= kernel_size`, if the surface is not
+    /// `ARgb32`, or if the SIMD implementation is not available on this CPU.
+    // This is public for the purpose of accessing it from the benchmarks, like box_blur_loop().
+    pub fn box_blur_loop_simd<B: BlurDirection>(
+        &self,
+        output_surface: &mut cairo::ImageSurface,
+        bounds: IRect,
+        kernel_size: usize,
+        target: usize,
+    ) {
+        assert_ne!(kernel_size, 0);
+        assert!(target < kernel_size);
+        assert_eq!(self.format, cairo::Format::ARgb32);
+        assert!(box_blur_simd::is_available());
+        assert_eq!(output_surface.get_format(), cairo::Format::ARgb32);
+
+        if bounds.x0 >= bounds.x1 || bounds.y0 >= bounds.y1 {
+            return;
+        }
+
+        // Raw pointers to the pixel data, which the threads below read and write in
+        // disjoint rows or columns.
+        #[derive(Clone, Copy)]
+        struct SendPtr<T>(T);
+
+        unsafe impl<T> Send for SendPtr<T> {}
+        unsafe impl<T> Sync for SendPtr<T> {}
+
+        let input_stride = self.stride;
+        let output_stride = output_surface.get_stride() as isize;
+
+        let mut output_data = output_surface.get_data().unwrap();
+
+        let input = SendPtr(self.data_ptr.as_ptr() as *const u8);
+        let output = SendPtr(output_data.as_mut_ptr());
+
+        let offset = |stride: isize, x: i32, y: i32| y as isize * stride + x as isize * 4;
+
+        if B::IS_VERTICAL {
+            let len = (bounds.y1 - bounds.y0) as usize;
+            let columns = (bounds.x1 - bounds.x0) as usize;
+
+            (0..(columns + 3) / 4).into_par_iter().for_each(|group| {
+                let x0 = bounds.x0 + group as i32 * 4;
+                let x1 = min(x0 + 4, bounds.x1);
+
+                unsafe {
+                    if x1 - x0 == 4 {
+                        box_blur_simd::box_blur_line_x4(
+                            input.0.offset(offset(input_stride, x0, bounds.y0)),
+                            input_stride,
+                            output.0.offset(offset(output_stride, x0, bounds.y0)),
+                            output_stride,
+                            len,
+                            kernel_size,
+                            target,
+                        );
+                    } else {
+                        for x in x0..x1 {
+                            box_blur_simd::box_blur_line(
+                                input.0.offset(offset(input_stride, x, bounds.y0)),
+                                input_stride,
+                                output.0.offset(offset(output_stride, x, bounds.y0)),
+                                output_stride,
+                                len,
+                                kernel_size,
+                                target,
+                            );
+                        }
+                    }
+                }
+            });
+        } else {
+            let len = (bounds.x1 - bounds.x0) as usize;
+
+            (bounds.y0..bounds.y1).into_par_iter().for_each(|y| unsafe {
+                box_blur_simd::box_blur_line(
+                    input.0.offset(offset(input_stride, bounds.x0, y)),
+                    4,
+                    output.0.offset(offset(output_stride, bounds.x0, y)),
+                    4,
+                    len,
+                    kernel_size,
+                    target,
+                );
+            });
+        }
+    }
+
     /// Performs a horizontal or vertical box blur.
     ///
     /// The `target` parameter determines the position of the kernel relative to each pixel of the
@@ -879,6 +1118,8 @@ impl SharedImageSurface {
 
         if self.is_alpha_only() {
             self.box_blur_loop::<B, AlphaOnly>(&mut output_surface, bounds, kernel_size, target);
+        } else if self.format == cairo::Format::ARgb32 && box_blur_simd::is_available() {
+            self.box_blur_loop_simd::<B>(&mut output_surface, bounds, kernel_size, target);
         } else {
             self.box_blur_loop::<B, NotAlphaOnly>(&mut output_surface, bounds, kernel_size, target);
         }
@@ -895,3 +1136,133 @@ impl SharedImageSurface {
         self.surface.to_glib_none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    use crate::surface_utils::ImageSurfaceDataExt;
+
+    #[test]
+    fn can_be_read_from_other_threads() {
+        let mut surface = ImageSurface::create(cairo::Format::ARgb32, 4, 4).unwrap();
+        let stride = surface.get_stride() as usize;
+        {
+            let mut data = surface.get_data().unwrap();
+            data.set_pixel(
+                stride,
+                Pixel {
+                    r: 1,
+                    g: 2,
+                    b: 3,
+                    a: 4,
+                },
+                2,
+                3,
+            );
+        }
+
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap();
+
+        let handles = (0..4)
+            .map(|_| {
+                let surface = surface.clone();
+                thread::spawn(move || surface.get_pixel(2, 3))
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(
+                handle.join().unwrap(),
+                Pixel {
+                    r: 1,
+                    g: 2,
+                    b: 3,
+                    a: 4,
+                }
+            );
+        }
+
+        // All the clones were dropped in the other threads.
+        assert_eq!(
+            unsafe { cairo_sys::cairo_surface_get_reference_count(surface.surface.to_raw_none()) },
+            1
+        );
+    }
+
+    #[test]
+    fn clones_are_the_same_surface() {
+        let surface = ImageSurface::create(cairo::Format::ARgb32, 4, 4).unwrap();
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap();
+        let bounds = IRect::from_size(4, 4);
+
+        assert!(surface.is_same_surface(&surface.clone()));
+        assert!(!surface.is_same_surface(&surface.to_linear_rgb(bounds).unwrap()));
+    }
+
+    #[test]
+    fn simd_box_blur_matches_scalar_box_blur() {
+        if !box_blur_simd::is_available() {
+            return;
+        }
+
+        const SIDE: i32 = 37;
+
+        let mut surface = ImageSurface::create(cairo::Format::ARgb32, SIDE, SIDE).unwrap();
+        let stride = surface.get_stride() as usize;
+        {
+            let mut data = surface.get_data().unwrap();
+            let mut seed = 1u32;
+
+            for y in 0..SIDE as u32 {
+                for x in 0..SIDE as u32 {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    let a = (seed >> 16) as u8;
+                    let c = |shift: u32| ((seed >> shift) as u8).min(a);
+
+                    data.set_pixel(
+                        stride,
+                        Pixel {
+                            r: c(0),
+                            g: c(8),
+                            b: c(24),
+                            a,
+                        },
+                        x,
+                        y,
+                    );
+                }
+            }
+        }
+
+        let surface = SharedImageSurface::new(surface, SurfaceType::SRgb).unwrap();
+
+        let bounds = IRect {
+            x0: 3,
+            y0: 5,
+            x1: SIDE - 3,
+            y1: SIDE - 1,
+        };
+
+        fn compare<B: BlurDirection>(
+            surface: &SharedImageSurface,
+            bounds: IRect,
+            kernel_size: usize,
+            target: usize,
+        ) {
+            let mut scalar = ImageSurface::create(cairo::Format::ARgb32, SIDE, SIDE).unwrap();
+            let mut simd = ImageSurface::create(cairo::Format::ARgb32, SIDE, SIDE).unwrap();
+
+            surface.box_blur_loop::<B, NotAlphaOnly>(&mut scalar, bounds, kernel_size, target);
+            surface.box_blur_loop_simd::<B>(&mut simd, bounds, kernel_size, target);
+
+            assert!(scalar.get_data().unwrap()[..] == simd.get_data().unwrap()[..]);
+        }
+
+        for &(kernel_size, target) in &[(1, 0), (3, 1), (4, 1), (4, 2), (9, 4), (40, 20)] {
+            compare::<Horizontal>(&surface, bounds, kernel_size, target);
+            compare::<Vertical>(&surface, bounds, kernel_size, target);
+        }
+    }
+}
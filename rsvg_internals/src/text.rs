@@ -1,19 +1,33 @@
+use cssparser::{CowRcStr, Parser, Token};
 use glib::translate::*;
 use markup5ever::{expanded_name, local_name, namespace_url, ns};
-use pango::{self, FontMapExt};
+use pango::{self, FontExt, FontMapExt};
 use pango_sys;
 use pangocairo;
-use std::cell::RefCell;
+use std::rc::Rc;
 
 use crate::allowed_url::Fragment;
+use crate::angle::Angle;
 use crate::bbox::BoundingBox;
-use crate::drawing_ctx::DrawingCtx;
-use crate::error::{AttributeResultExt, RenderingError};
+use crate::chars::NodeChars;
+use crate::drawing_ctx::{stroke_matrix, with_stroke_matrix, DrawingCtx};
+use crate::error::{AttributeResultExt, RenderingError, ValueErrorKind};
 use crate::float_eq_cairo::ApproxEqCairo;
-use crate::font_props::FontWeightSpec;
+use crate::font_props::{
+    FontFallback,
+    FontFallbackKind,
+    FontFamilyMap,
+    FontWeightSpec,
+    GenericFontFamily,
+    LetterSpacingSpec,
+};
+use crate::glyph_run::{GlyphRun, PositionedGlyph};
 use crate::length::*;
+use crate::motion_path::MotionPath;
 use crate::node::{CascadedValues, NodeResult, NodeTrait, NodeType, RsvgNode};
-use crate::parsers::ParseValue;
+use crate::parsers::{Parse, ParseValue};
+use crate::path_builder::PathSegment;
+use crate::path_measure::PathMeasure;
 use crate::properties::ComputedValues;
 use crate::property_bag::PropertyBag;
 use crate::property_defs::{
@@ -21,15 +35,17 @@ use crate::property_defs::{
     FontStretch,
     FontStyle,
     FontVariant,
+    LetterSpacing,
+    PaintTarget,
     TextAnchor,
+    TextDecoration,
     TextRendering,
     UnicodeBidi,
     WritingMode,
     XmlLang,
-    XmlSpace,
 };
 use crate::rect::RectangleExt;
-use crate::space::{xml_space_normalize, NormalizeDefault, XmlSpaceNormalize};
+use crate::shapes::Path;
 
 /// An absolutely-positioned array of `Span`s
 ///
@@ -44,10 +60,14 @@ use crate::space::{xml_space_normalize, NormalizeDefault, XmlSpaceNormalize};
 /// whenever they have `x` or `y` attributes.
 ///
 /// [text chunk]: https://www.w3.org/TR/SVG11/text.html#TextLayoutIntroduction
+///
+/// The contents of a `<textPath>` are a chunk of their own, which gets laid
+/// out along the `path` instead of along a straight line.
 struct Chunk {
     values: ComputedValues,
     x: Option<Length<Horizontal>>,
     y: Option<Length<Vertical>>,
+    path: Option<Rc<TextPathGeometry>>,
     spans: Vec<Span>,
 }
 
@@ -55,6 +75,8 @@ struct MeasuredChunk {
     values: ComputedValues,
     x: Option<Length<Horizontal>>,
     y: Option<Length<Vertical>>,
+    path: Option<Rc<TextPathGeometry>>,
+    base_dir: pango::Direction,
     advance: (f64, f64),
     spans: Vec<MeasuredSpan>,
 }
@@ -71,6 +93,10 @@ struct Span {
     dx: Option<Length<Horizontal>>,
     dy: Option<Length<Vertical>>,
     _depth: usize,
+
+    /// Values of the span's element and its ancestors that have a `text-decoration`,
+    /// from the outermost one
+    decorations: Vec<ComputedValues>,
 }
 
 struct MeasuredSpan {
@@ -80,6 +106,11 @@ struct MeasuredSpan {
     advance: (f64, f64),
     dx: Option<Length<Horizontal>>,
     dy: Option<Length<Vertical>>,
+
+    /// For text on a path, a layout and an advance for each character
+    char_layouts: Vec<(pango::Layout, f64)>,
+
+    decoration_lines: Vec<DecorationLine>,
 }
 
 struct PositionedSpan {
@@ -89,6 +120,30 @@ struct PositionedSpan {
     rendered_position: (f64, f64),
     next_span_x: f64,
     next_span_y: f64,
+
+    /// For text on a path, the x of `rendered_position` is the distance along it
+    path: Option<Rc<TextPathGeometry>>,
+    char_layouts: Vec<(pango::Layout, f64)>,
+
+    decoration_lines: Vec<DecorationLine>,
+}
+
+/// A line of a `text-decoration`, measured with the font of the element that declared it
+///
+/// The lines go across the text of the element's descendants, and they are painted with
+/// the element's fill and stroke, not with the ones of the text they go across.
+#[derive(Clone)]
+struct DecorationLine {
+    /// Values of the element that declared the `text-decoration`
+    values: ComputedValues,
+
+    /// Distance from the baseline to the top edge of the line, positive upwards
+    position: f64,
+
+    thickness: f64,
+
+    /// Whether the line is painted over the glyphs, as for `line-through`, or under them
+    over_text: bool,
 }
 
 impl Chunk {
@@ -101,17 +156,32 @@ impl Chunk {
             values: values.clone(),
             x,
             y,
+            path: None,
             spans: Vec::new(),
         }
     }
 }
 
 impl MeasuredChunk {
-    fn from_chunk(chunk: &Chunk, draw_ctx: &DrawingCtx) -> MeasuredChunk {
+    /// Measures the spans of a chunk; `extra_letter_spacing` is added to their
+    /// `letter-spacing` to fit the text to a `textLength`.
+    fn from_chunk(
+        chunk: &Chunk,
+        draw_ctx: &DrawingCtx,
+        extra_letter_spacing: f64,
+    ) -> MeasuredChunk {
+        let on_path = chunk.path.is_some();
+
+        // All the spans in a chunk are part of the same paragraph for the bidi
+        // algorithm, so they get the chunk's base direction.
+        let base_dir = base_direction(&chunk.values);
+
         let measured_spans: Vec<MeasuredSpan> = chunk
             .spans
             .iter()
-            .map(|span| MeasuredSpan::from_span(span, draw_ctx))
+            .map(|span| {
+                MeasuredSpan::from_span(span, draw_ctx, base_dir, on_path, extra_letter_spacing)
+            })
             .collect();
 
         let advance = measured_spans.iter().fold((0.0, 0.0), |acc, measured| {
@@ -122,6 +192,8 @@ impl MeasuredChunk {
             values: chunk.values.clone(),
             x: chunk.x,
             y: chunk.y,
+            path: chunk.path.clone(),
+            base_dir,
             advance,
             spans: measured_spans,
         }
@@ -137,11 +209,17 @@ impl PositionedChunk {
     ) -> PositionedChunk {
         let mut positioned = Vec::new();
 
+        // Right-to-left chunks start at their right edge, and their spans go leftwards
+        let rtl = measured.base_dir == pango::Direction::Rtl
+            && measured.path.is_none()
+            && !measured.values.writing_mode.is_vertical();
+
         // Adjust the specified coordinates with the text_anchor
 
         let adjusted_advance = text_anchor_advance(
             measured.values.text_anchor,
             measured.values.writing_mode,
+            rtl,
             measured.advance,
         );
 
@@ -151,7 +229,8 @@ impl PositionedChunk {
         // Position each span
 
         for measured_span in &measured.spans {
-            let positioned_span = PositionedSpan::from_measured(measured_span, draw_ctx, x, y);
+            let positioned_span =
+                PositionedSpan::from_measured(measured_span, draw_ctx, &measured.path, rtl, x, y);
 
             x = positioned_span.next_span_x;
             y = positioned_span.next_span_y;
@@ -170,6 +249,7 @@ impl PositionedChunk {
 fn text_anchor_advance(
     anchor: TextAnchor,
     writing_mode: WritingMode,
+    rtl: bool,
     advance: (f64, f64),
 ) -> (f64, f64) {
     if writing_mode.is_vertical() {
@@ -178,6 +258,13 @@ fn text_anchor_advance(
             TextAnchor::Middle => (0.0, -advance.1 / 2.0),
             TextAnchor::End => (0.0, -advance.1),
         }
+    } else if rtl {
+        // The start of right-to-left text is its right edge
+        match anchor {
+            TextAnchor::Start => (0.0, 0.0),
+            TextAnchor::Middle => (advance.0 / 2.0, 0.0),
+            TextAnchor::End => (advance.0, 0.0),
+        }
     } else {
         match anchor {
             TextAnchor::Start => (0.0, 0.0),
@@ -194,6 +281,7 @@ impl Span {
         dx: Option<Length<Horizontal>>,
         dy: Option<Length<Vertical>>,
         depth: usize,
+        decorations: &[ComputedValues],
     ) -> Span {
         Span {
             values,
@@ -201,26 +289,60 @@ impl Span {
             dx,
             dy,
             _depth: depth,
+            decorations: decorations.to_vec(),
         }
     }
 }
 
 impl MeasuredSpan {
-    fn from_span(span: &Span, draw_ctx: &DrawingCtx) -> MeasuredSpan {
-        let values = span.values.clone();
+    fn from_span(
+        span: &Span,
+        draw_ctx: &DrawingCtx,
+        base_dir: pango::Direction,
+        on_path: bool,
+        extra_letter_spacing: f64,
+    ) -> MeasuredSpan {
+        let mut values = span.values.clone();
 
-        let layout = create_pango_layout(draw_ctx, &values, &span.text);
+        let params = draw_ctx.get_view_params();
+        let letter_spacing = values.letter_spacing.0.normalize(&values, &params);
+        values.letter_spacing = LetterSpacing(LetterSpacingSpec::Value(Length::new(
+            letter_spacing + extra_letter_spacing,
+            LengthUnit::Px,
+        )));
+
+        let layout = create_pango_layout(draw_ctx, &values, base_dir, &span.text);
         let (w, h) = layout.get_size();
 
         let w = f64::from(w) / f64::from(pango::SCALE);
         let h = f64::from(h) / f64::from(pango::SCALE);
 
-        let advance = if values.writing_mode.is_vertical() {
+        // Each character on a path gets placed and rotated separately, so lay them out
+        // one by one.  This loses kerning and ligatures, but not much else.
+        let char_layouts: Vec<(pango::Layout, f64)> = if on_path {
+            span.text
+                .chars()
+                .map(|c| {
+                    let layout = create_pango_layout(draw_ctx, &values, base_dir, &c.to_string());
+                    let (w, _) = layout.get_size();
+                    (layout, f64::from(w) / f64::from(pango::SCALE))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let advance = if on_path {
+            (char_layouts.iter().map(|&(_, w)| w).sum(), 0.0)
+        } else if values.writing_mode.is_vertical() {
             (0.0, w)
         } else {
             (w, 0.0)
         };
 
+        let decoration_lines =
+            measure_decoration_lines(draw_ctx, &layout.get_context().unwrap(), &span.decorations);
+
         MeasuredSpan {
             values,
             layout,
@@ -228,6 +350,8 @@ impl MeasuredSpan {
             advance,
             dx: span.dx,
             dy: span.dy,
+            char_layouts,
+            decoration_lines,
         }
     }
 }
@@ -236,6 +360,8 @@ impl PositionedSpan {
     fn from_measured(
         measured: &MeasuredSpan,
         draw_ctx: &DrawingCtx,
+        path: &Option<Rc<TextPathGeometry>>,
+        rtl: bool,
         x: f64,
         y: f64,
     ) -> PositionedSpan {
@@ -257,19 +383,32 @@ impl PositionedSpan {
             .map(|l| l.normalize(&values, &params))
             .unwrap_or(0.0);
 
-        let (render_x, render_y) = if values.writing_mode.is_vertical() {
+        // Text on a path is always laid out horizontally along it
+        let (render_x, render_y) = if values.writing_mode.is_vertical() && path.is_none() {
             (x + offset + dx, y + dy)
+        } else if rtl {
+            (x - measured.advance.0 + dx, y - offset + dy)
         } else {
             (x + dx, y - offset + dy)
         };
 
+        // In right-to-left text, the next span goes to the left of this one
+        let next_span_x = if rtl {
+            render_x
+        } else {
+            x + measured.advance.0 + dx
+        };
+
         PositionedSpan {
             layout: measured.layout.clone(),
             values,
             _position: (x, y),
             rendered_position: (render_x, render_y),
-            next_span_x: x + measured.advance.0 + dx,
+            next_span_x,
             next_span_y: y + measured.advance.1 + dy,
+            path: path.clone(),
+            char_layouts: measured.char_layouts.clone(),
+            decoration_lines: measured.decoration_lines.clone(),
         }
     }
 
@@ -277,6 +416,82 @@ impl PositionedSpan {
         &self,
         draw_ctx: &mut DrawingCtx,
         clipping: bool,
+    ) -> Result<BoundingBox, RenderingError> {
+        if !clipping && (crate::log::log_enabled() || draw_ctx.is_recording_font_fallbacks()) {
+            for fallback in find_font_fallbacks(draw_ctx, &self.layout, &self.values) {
+                match fallback.kind {
+                    FontFallbackKind::DifferentFont => rsvg_log!(
+                        "font family \"{}\" has no glyphs for {:?}, using \"{}\" instead",
+                        fallback.requested_family,
+                        fallback.characters,
+                        fallback.font_family
+                    ),
+
+                    FontFallbackKind::MissingGlyph => rsvg_log!(
+                        "no font has glyphs for {:?}, rendering missing glyphs instead",
+                        fallback.characters
+                    ),
+                }
+
+                draw_ctx.add_font_fallback(fallback);
+            }
+        }
+
+        match self.path {
+            Some(ref path) => self.draw_on_path(draw_ctx, clipping, path),
+            None => self.draw_layout(draw_ctx, clipping),
+        }
+    }
+
+    /// Draws each character centered on its point along the path, and rotated to
+    /// follow the direction of the path there.
+    fn draw_on_path(
+        &self,
+        draw_ctx: &mut DrawingCtx,
+        clipping: bool,
+        path: &TextPathGeometry,
+    ) -> Result<BoundingBox, RenderingError> {
+        let mut bbox = draw_ctx.empty_bbox();
+        let (mut distance, y) = self.rendered_position;
+
+        for &(ref layout, advance) in &self.char_layouts {
+            let half_advance = advance / 2.0;
+
+            // Characters whose midpoint is off the end of the path are not rendered
+            if let Some(((px, py), angle)) = path.point_at(distance + half_advance) {
+                let glyph = PositionedSpan {
+                    layout: layout.clone(),
+                    values: self.values.clone(),
+                    _position: (0.0, 0.0),
+                    rendered_position: (-half_advance, y),
+                    next_span_x: 0.0,
+                    next_span_y: 0.0,
+                    path: None,
+                    char_layouts: Vec::new(),
+                    decoration_lines: self.decoration_lines.clone(),
+                };
+
+                let glyph_bbox = draw_ctx.with_saved_cr(&mut |dc| {
+                    let cr = dc.get_cairo_context();
+                    cr.translate(px, py);
+                    cr.rotate(angle.radians());
+
+                    glyph.draw_layout(dc, clipping)
+                })?;
+
+                bbox.insert(&glyph_bbox);
+            }
+
+            distance += advance;
+        }
+
+        Ok(bbox)
+    }
+
+    fn draw_layout(
+        &self,
+        draw_ctx: &mut DrawingCtx,
+        clipping: bool,
     ) -> Result<BoundingBox, RenderingError> {
         draw_ctx.with_saved_cr(&mut |dc| {
             let cr = dc.get_cairo_context();
@@ -285,83 +500,232 @@ impl PositionedSpan {
 
             let gravity = self.layout.get_context().unwrap().get_gravity();
             let bbox = self.compute_text_bbox(&affine, gravity);
-            if bbox.is_none() {
+
+            // Spaces have no ink, but decoration lines still go across them
+            if bbox.is_none() && self.decoration_lines.is_empty() {
                 return Ok(dc.empty_bbox());
             }
 
             let mut bbox = if clipping {
                 dc.empty_bbox()
             } else {
-                bbox.unwrap()
+                bbox.unwrap_or_else(|| dc.empty_bbox())
             };
 
             cr.set_antialias(cairo::Antialias::from(self.values.text_rendering));
 
+            let rotation = unsafe { pango_sys::pango_gravity_to_rotation(gravity.to_glib()) };
+
+            // Underlines and overlines go below the glyphs
+            if !clipping {
+                self.paint_decoration_lines(dc, &affine, rotation, false, &mut bbox)?;
+            }
+
             dc.setup_cr_for_stroke(&cr, &self.values);
 
             cr.move_to(self.rendered_position.0, self.rendered_position.1);
 
-            let rotation = unsafe { pango_sys::pango_gravity_to_rotation(gravity.to_glib()) };
             if !rotation.approx_eq_cairo(0.0) {
                 cr.rotate(-rotation);
             }
 
+            if !clipping && dc.is_recording_glyph_runs() {
+                // Pango renders the layout from the current point, with the rotated axes
+                let mut run_affine = affine;
+                run_affine.translate(self.rendered_position.0, self.rendered_position.1);
+                run_affine.rotate(-rotation);
+
+                for run in layout_glyph_runs(&self.layout) {
+                    dc.add_glyph_run(run, run_affine);
+                }
+            }
+
             let current_color = self.values.color.0;
 
-            let res = if !clipping {
-                dc.set_source_paint_server(
-                    &self.values.fill.0,
-                    self.values.fill_opacity.0,
-                    &bbox,
-                    current_color,
-                )
-                .and_then(|had_paint_server| {
-                    if had_paint_server {
-                        pangocairo::functions::update_layout(&cr, &self.layout);
-                        pangocairo::functions::show_layout(&cr, &self.layout);
-                    };
-                    Ok(())
-                })
-            } else {
+            let res = if clipping {
+                pangocairo::functions::update_layout(&cr, &self.layout);
+                pangocairo::functions::layout_path(&cr, &self.layout);
+
+                for line in &self.decoration_lines {
+                    self.decoration_line_path(&cr, &affine, rotation, line);
+                }
+
                 Ok(())
+            } else {
+                // Stroking clears the current point, so keep it around in case
+                // paint-order asks for the fill to be painted after the stroke.
+                let (x, y) = cr.get_current_point();
+
+                self.values
+                    .paint_order
+                    .targets
+                    .iter()
+                    .try_for_each(|target| match *target {
+                        PaintTarget::Fill => dc
+                            .set_source_paint_server(
+                                &self.values.fill.0,
+                                self.values.fill_opacity.0,
+                                &bbox,
+                                current_color,
+                            )
+                            .and_then(|had_paint_server| {
+                                if had_paint_server {
+                                    pangocairo::functions::update_layout(&cr, &self.layout);
+                                    pangocairo::functions::show_layout(&cr, &self.layout);
+                                };
+                                Ok(())
+                            }),
+
+                        PaintTarget::Stroke => dc
+                            .set_source_paint_server(
+                                &self.values.stroke.0,
+                                self.values.stroke_opacity.0,
+                                &bbox,
+                                current_color,
+                            )
+                            .and_then(|had_paint_server| {
+                                if had_paint_server {
+                                    pangocairo::functions::update_layout(&cr, &self.layout);
+                                    pangocairo::functions::layout_path(&cr, &self.layout);
+
+                                    let (x0, y0, x1, y1) =
+                                        with_stroke_matrix(&cr, &self.values, || {
+                                            cr.stroke_extents()
+                                        });
+                                    let r = cairo::Rectangle::from_extents(x0, y0, x1, y1);
+                                    let ib = BoundingBox::new(&stroke_matrix(&cr, &self.values))
+                                        .with_ink_rect(r);
+                                    with_stroke_matrix(&cr, &self.values, || cr.stroke());
+                                    bbox.insert(&ib);
+
+                                    cr.move_to(x, y);
+                                }
+                                Ok(())
+                            }),
+
+                        // text has no markers
+                        PaintTarget::Markers => Ok(()),
+                    })
+                    .and_then(|_| {
+                        self.paint_decoration_lines(dc, &affine, rotation, true, &mut bbox)
+                    })
             };
 
-            if res.is_ok() {
-                let mut need_layout_path = clipping;
+            res.and_then(|_: ()| Ok(bbox))
+        })
+    }
 
-                let res = if !clipping {
-                    dc.set_source_paint_server(
-                        &self.values.stroke.0,
-                        self.values.stroke_opacity.0,
-                        &bbox,
-                        current_color,
-                    )
-                    .and_then(|had_paint_server| {
-                        if had_paint_server {
-                            need_layout_path = true;
-                        }
-                        Ok(())
-                    })
-                } else {
-                    Ok(())
-                };
+    /// Paints the decoration lines that go under the glyphs, or the ones that go over
+    /// them, with the fill and stroke of the elements that declared them.
+    ///
+    /// Leaves the current transformation matrix set to `affine`.
+    fn paint_decoration_lines(
+        &self,
+        dc: &mut DrawingCtx,
+        affine: &cairo::Matrix,
+        rotation: f64,
+        over_text: bool,
+        bbox: &mut BoundingBox,
+    ) -> Result<(), RenderingError> {
+        let cr = dc.get_cairo_context();
+
+        for line in self
+            .decoration_lines
+            .iter()
+            .filter(|l| l.over_text == over_text)
+        {
+            let values = &line.values;
+
+            cr.set_matrix(*affine);
+
+            // Paint servers get set up in the same space as the glyphs' own
+            let text_bbox = *bbox;
+
+            values
+                .paint_order
+                .targets
+                .iter()
+                .try_for_each(|target| match *target {
+                    PaintTarget::Fill => dc
+                        .set_source_paint_server(
+                            &values.fill.0,
+                            values.fill_opacity.0,
+                            &text_bbox,
+                            values.color.0,
+                        )
+                        .map(|had_paint_server| {
+                            if had_paint_server {
+                                self.decoration_line_path(&cr, affine, rotation, line);
+                                cr.fill();
+                            }
+                        }),
+
+                    PaintTarget::Stroke => dc
+                        .set_source_paint_server(
+                            &values.stroke.0,
+                            values.stroke_opacity.0,
+                            &text_bbox,
+                            values.color.0,
+                        )
+                        .map(|had_paint_server| {
+                            if had_paint_server {
+                                dc.setup_cr_for_stroke(&cr, values);
+                                self.decoration_line_path(&cr, affine, rotation, line);
+                                with_stroke_matrix(&cr, values, || cr.stroke());
+                            }
+                        }),
+
+                    PaintTarget::Markers => Ok(()),
+                })?;
+
+            let mut line_affine = *affine;
+            line_affine.translate(self.rendered_position.0, self.rendered_position.1);
+            line_affine.rotate(-rotation);
+
+            let rect = self.decoration_line_rect(line);
+            bbox.insert(
+                &BoundingBox::new(&line_affine)
+                    .with_rect(rect)
+                    .with_ink_rect(rect),
+            );
+        }
 
-                if res.is_ok() && need_layout_path {
-                    pangocairo::functions::update_layout(&cr, &self.layout);
-                    pangocairo::functions::layout_path(&cr, &self.layout);
+        Ok(())
+    }
 
-                    if !clipping {
-                        let (x0, y0, x1, y1) = cr.stroke_extents();
-                        let r = cairo::Rectangle::from_extents(x0, y0, x1, y1);
-                        let ib = BoundingBox::new(&affine).with_ink_rect(r);
-                        cr.stroke();
-                        bbox.insert(&ib);
-                    }
-                }
-            }
+    /// Adds a rectangle for the decoration `line` to the current path.
+    fn decoration_line_path(
+        &self,
+        cr: &cairo::Context,
+        affine: &cairo::Matrix,
+        rotation: f64,
+        line: &DecorationLine,
+    ) {
+        let matrix = cr.get_matrix();
 
-            res.and_then(|_: ()| Ok(bbox))
-        })
+        // The lines are measured in the layout's space, like the glyphs
+        cr.set_matrix(*affine);
+        cr.translate(self.rendered_position.0, self.rendered_position.1);
+        cr.rotate(-rotation);
+
+        let rect = self.decoration_line_rect(line);
+        cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+
+        cr.set_matrix(matrix);
+    }
+
+    /// The rectangle of the decoration `line`, which goes across the whole layout
+    fn decoration_line_rect(&self, line: &DecorationLine) -> cairo::Rectangle {
+        let (_, logical) = self.layout.get_extents();
+        let pango_scale = f64::from(pango::SCALE);
+        let baseline = f64::from(self.layout.get_baseline()) / pango_scale;
+
+        cairo::Rectangle::new(
+            f64::from(logical.x) / pango_scale,
+            baseline - line.position,
+            f64::from(logical.width) / pango_scale,
+            line.thickness,
+        )
     }
 
     fn compute_text_bbox(
@@ -404,6 +768,224 @@ impl PositionedSpan {
     }
 }
 
+/// A run of a Pango layout: a sequence of glyphs shaped with a single font.
+struct LayoutRun {
+    /// The characters of the run.
+    text: String,
+
+    font: Option<pango::Font>,
+
+    /// The glyphs, in visual order.
+    glyphs: Vec<pango_sys::PangoGlyphInfo>,
+
+    /// For each glyph, the byte offset in `text` of its characters.
+    log_clusters: Vec<usize>,
+
+    /// Where the run starts, in Pango units from the layout's origin.
+    x: i32,
+    baseline: i32,
+}
+
+/// Returns the runs of a layout, in the order of its lines.
+fn layout_runs(layout: &pango::Layout) -> Vec<LayoutRun> {
+    let text = match layout.get_text() {
+        Some(text) => text.to_string(),
+        None => return Vec::new(),
+    };
+
+    let mut iter = match layout.get_iter() {
+        Some(iter) => iter,
+        None => return Vec::new(),
+    };
+
+    let mut runs = Vec::new();
+
+    loop {
+        if let Some(run) = iter.get_run_readonly() {
+            let (_, logical) = iter.get_run_extents();
+            let baseline = iter.get_baseline();
+
+            // pango::GlyphItem doesn't expose its item and glyph string
+            let run = unsafe {
+                let glyph_item: *const pango_sys::PangoGlyphItem = run.to_glib_none().0;
+                let item = &*(*glyph_item).item;
+                let glyphs = &*(*glyph_item).glyphs;
+                let num_glyphs = glyphs.num_glyphs as usize;
+
+                let start = item.offset as usize;
+                let end = start + item.length as usize;
+
+                let font = if item.analysis.font.is_null() {
+                    None
+                } else {
+                    Some(from_glib_none(item.analysis.font))
+                };
+
+                LayoutRun {
+                    text: text.get(start..end).unwrap_or("").to_string(),
+                    font,
+                    glyphs: std::slice::from_raw_parts(glyphs.glyphs, num_glyphs).to_vec(),
+                    log_clusters: std::slice::from_raw_parts(glyphs.log_clusters, num_glyphs)
+                        .iter()
+                        .map(|&cluster| cluster as usize)
+                        .collect(),
+                    x: logical.x,
+                    baseline,
+                }
+            };
+
+            runs.push(run);
+        }
+
+        if !iter.next_run() {
+            break;
+        }
+    }
+
+    runs
+}
+
+/// Returns the shaped glyphs of a layout.
+///
+/// The transformation of the runs is the identity; the caller knows where the layout
+/// is placed.
+fn layout_glyph_runs(layout: &pango::Layout) -> Vec<GlyphRun> {
+    let pango_scale = f64::from(pango::SCALE);
+
+    layout_runs(layout)
+        .into_iter()
+        .map(|run| {
+            let description = run.font.as_ref().and_then(|font| font.describe());
+
+            let mut x = f64::from(run.x) / pango_scale;
+            let y = f64::from(run.baseline) / pango_scale;
+
+            let glyphs = run
+                .glyphs
+                .iter()
+                .zip(run.log_clusters.iter())
+                .map(|(info, &cluster)| {
+                    let advance = f64::from(info.geometry.width) / pango_scale;
+
+                    let glyph = PositionedGlyph {
+                        glyph: info.glyph,
+                        cluster,
+                        x: x + f64::from(info.geometry.x_offset) / pango_scale,
+                        y: y + f64::from(info.geometry.y_offset) / pango_scale,
+                        advance,
+                    };
+
+                    x += advance;
+                    glyph
+                })
+                .collect();
+
+            GlyphRun {
+                font_family: description
+                    .as_ref()
+                    .and_then(|desc| desc.get_family())
+                    .map(|family| family.to_string())
+                    .unwrap_or_default(),
+                font_description: description
+                    .as_ref()
+                    .map(|desc| desc.to_string())
+                    .unwrap_or_default(),
+                font_size: description
+                    .as_ref()
+                    .map(|desc| f64::from(desc.get_size()) / pango_scale)
+                    .unwrap_or(0.0),
+                text: run.text,
+                transform: cairo::Matrix::identity(),
+                glyphs,
+            }
+        })
+        .collect()
+}
+
+fn font_family_name(font: &pango::Font) -> Option<String> {
+    font.describe()
+        .and_then(|desc| desc.get_family())
+        .map(|family| family.to_string())
+}
+
+/// Finds the runs of a layout which Pango rendered with a font other than the one for
+/// the `font-family` property, and the characters for which it found no glyphs at all.
+fn find_font_fallbacks(
+    draw_ctx: &DrawingCtx,
+    layout: &pango::Layout,
+    values: &ComputedValues,
+) -> Vec<FontFallback> {
+    let requested_family = &(values.font_family.0).0;
+
+    // The layout has the family from the application's FontFamilyMap, if any.
+    let layout_family = layout
+        .get_font_description()
+        .and_then(|desc| desc.get_family())
+        .map(|family| family.to_string())
+        .unwrap_or_else(|| requested_family.clone());
+
+    // A generic family is honored as long as the text uses the font that it maps to, and
+    // so is a family from @font-face, whose font has a family name of its own.
+    let expected_family = if GenericFontFamily::from_name(&layout_family).is_some()
+        || draw_ctx.has_font_face(&layout_family)
+    {
+        layout
+            .get_context()
+            .and_then(|context| {
+                layout
+                    .get_font_description()
+                    .and_then(|desc| context.load_font(&desc))
+            })
+            .and_then(|font| font_family_name(&font))
+    } else {
+        Some(layout_family)
+    };
+
+    let mut fallbacks = Vec::new();
+
+    for run in layout_runs(layout) {
+        let font_family = run
+            .font
+            .as_ref()
+            .and_then(font_family_name)
+            .unwrap_or_default();
+
+        let missing: String = run
+            .glyphs
+            .iter()
+            .filter(|info| info.glyph & pango_sys::PANGO_GLYPH_UNKNOWN_FLAG != 0)
+            .filter_map(|info| {
+                std::char::from_u32(info.glyph & !pango_sys::PANGO_GLYPH_UNKNOWN_FLAG)
+            })
+            .collect();
+
+        if !missing.is_empty() {
+            fallbacks.push(FontFallback {
+                kind: FontFallbackKind::MissingGlyph,
+                characters: missing,
+                requested_family: requested_family.clone(),
+                font_family,
+            });
+        } else if let Some(ref expected) = expected_family {
+            let has_visible_text = run
+                .text
+                .chars()
+                .any(|c| !c.is_whitespace() && !is_bidi_control(c));
+
+            if has_visible_text && !font_family.eq_ignore_ascii_case(expected) {
+                fallbacks.push(FontFallback {
+                    kind: FontFallbackKind::DifferentFont,
+                    characters: run.text,
+                    requested_family: requested_family.clone(),
+                    font_family,
+                });
+            }
+        }
+    }
+
+    fallbacks
+}
+
 // FIXME: should the pango crate provide this like PANGO_GRAVITY_IS_VERTICAL() ?
 fn gravity_is_vertical(gravity: pango::Gravity) -> bool {
     match gravity {
@@ -412,7 +994,7 @@ fn gravity_is_vertical(gravity: pango::Gravity) -> bool {
     }
 }
 
-/// Walks the children of a `<text>`, `<tspan>`, or `<tref>` element
+/// Walks the children of a `<text>`, `<tspan>`, `<tref>`, or `<textPath>` element
 /// and appends chunks/spans from them into the specified `chunks`
 /// array.
 ///
@@ -427,15 +1009,25 @@ fn children_to_chunks(
     dx: Option<Length<Horizontal>>,
     dy: Option<Length<Vertical>>,
     depth: usize,
+    decorations: &[ComputedValues],
 ) {
+    let decorations = add_decoration(decorations, cascaded.get());
+
     for child in node.children() {
         match child.borrow().get_type() {
             NodeType::Chars => {
                 let values = cascaded.get();
-                child
-                    .borrow()
-                    .get_impl::<NodeChars>()
-                    .to_chunks(&child, values, chunks, dx, dy, depth);
+                let multiline = draw_ctx.multiline_text();
+                child.borrow().get_impl::<NodeChars>().to_chunks(
+                    &child,
+                    values,
+                    chunks,
+                    dx,
+                    dy,
+                    depth,
+                    multiline,
+                    &decorations,
+                );
             }
 
             NodeType::TSpan => {
@@ -446,6 +1038,7 @@ fn children_to_chunks(
                     draw_ctx,
                     chunks,
                     depth + 1,
+                    &decorations,
                 );
             }
 
@@ -457,76 +1050,44 @@ fn children_to_chunks(
                     draw_ctx,
                     chunks,
                     depth + 1,
+                    &decorations,
                 );
             }
 
-            _ => (),
-        }
-    }
-}
-
-/// In SVG text elements, we use `NodeChars` to store character data.  For example,
-/// an element like `<text>Foo Bar</text>` will be a `Text` with a single child,
-/// and the child will be a `NodeChars` with "Foo Bar" for its contents.
-///
-/// Text elements can contain `<tspan>` sub-elements.  In this case,
-/// those `tspan` nodes will also contain `NodeChars` children.
-///
-/// A text or tspan element can contain more than one `NodeChars` child, for example,
-/// if there is an XML comment that splits the character contents in two:
-///
-/// ```xml
-/// <text>
-///   This sentence will create a NodeChars.
-///   <!-- this comment is ignored -->
-///   This sentence will cretea another NodeChars.
-/// </text>
-/// ```
-///
-/// When rendering a text element, it will take care of concatenating the strings
-/// in its `NodeChars` children as appropriate, depending on the
-/// `xml:space="preserve"` attribute.  A `NodeChars` stores the characters verbatim
-/// as they come out of the XML parser, after ensuring that they are valid UTF-8.
+            NodeType::TextPath => {
+                let child_cascaded = CascadedValues::new(cascaded, &child);
+                child.borrow().get_impl::<TextPath>().to_chunks(
+                    &child,
+                    &child_cascaded,
+                    draw_ctx,
+                    chunks,
+                    depth + 1,
+                    &decorations,
+                );
 
-pub struct NodeChars {
-    string: RefCell<String>,
-    space_normalized: RefCell<Option<String>>,
-}
+                // The text after the path continues in a straight line
+                chunks.push(Chunk::new(cascaded.get(), None, None));
+            }
 
-impl NodeChars {
-    pub fn new() -> NodeChars {
-        NodeChars {
-            string: RefCell::new(String::new()),
-            space_normalized: RefCell::new(None),
+            _ => (),
         }
     }
+}
 
-    pub fn is_empty(&self) -> bool {
-        self.string.borrow().is_empty()
-    }
+/// Returns the `decorations` of the ancestors of an element, plus the element's own
+/// `text-decoration` if it has one.
+fn add_decoration(decorations: &[ComputedValues], values: &ComputedValues) -> Vec<ComputedValues> {
+    let mut decorations = decorations.to_vec();
 
-    pub fn append(&self, s: &str) {
-        self.string.borrow_mut().push_str(s);
-        *self.space_normalized.borrow_mut() = None;
+    if values.text_decoration != TextDecoration::default() {
+        decorations.push(values.clone());
     }
 
-    fn ensure_normalized_string(&self, node: &RsvgNode, values: &ComputedValues) {
-        let mut normalized = self.space_normalized.borrow_mut();
-
-        if (*normalized).is_none() {
-            let mode = match values.xml_space {
-                XmlSpace::Default => XmlSpaceNormalize::Default(NormalizeDefault {
-                    has_element_before: node.previous_sibling().is_some(),
-                    has_element_after: node.next_sibling().is_some(),
-                }),
-
-                XmlSpace::Preserve => XmlSpaceNormalize::Preserve,
-            };
-
-            *normalized = Some(xml_space_normalize(mode, &self.string.borrow()));
-        }
-    }
+    decorations
+}
 
+// Text layout for the character data inside text elements
+impl NodeChars {
     fn make_span(
         &self,
         node: &RsvgNode,
@@ -534,15 +1095,15 @@ impl NodeChars {
         dx: Option<Length<Horizontal>>,
         dy: Option<Length<Vertical>>,
         depth: usize,
+        decorations: &[ComputedValues],
     ) -> Span {
-        self.ensure_normalized_string(node, values);
-
         Span::new(
-            self.space_normalized.borrow().as_ref().unwrap(),
+            &self.space_normalized_string(node, values),
             values.clone(),
             dx,
             dy,
             depth,
+            decorations,
         )
     }
 
@@ -554,23 +1115,77 @@ impl NodeChars {
         dx: Option<Length<Horizontal>>,
         dy: Option<Length<Vertical>>,
         depth: usize,
+        multiline: bool,
+        decorations: &[ComputedValues],
     ) {
-        let span = self.make_span(&node, values, dx, dy, depth);
-
         let num_chunks = chunks.len();
         assert!(num_chunks > 0);
 
-        chunks[num_chunks - 1].spans.push(span);
+        // white-space: pre and friends make newlines start new lines, without the
+        // multi-line text extension being enabled.
+        let multiline = multiline || values.white_space.preserves_newlines();
+
+        if !multiline || values.writing_mode.is_vertical() {
+            let span = self.make_span(&node, values, dx, dy, depth, decorations);
+            chunks[num_chunks - 1].spans.push(span);
+            return;
+        }
+
+        for (i, line) in self.space_normalized_lines(node, values).iter().enumerate() {
+            if i == 0 {
+                let span = Span::new(line, values.clone(), dx, dy, depth, decorations);
+                chunks[num_chunks - 1].spans.push(span);
+            } else {
+                // Each new line starts at the x position of the current line, 1em below it
+                let line_x = chunks.iter().rev().find_map(|chunk| chunk.x);
+                let line_dy = Length::<Vertical>::new(1.0, LengthUnit::Em);
+
+                let mut chunk = Chunk::new(values, line_x, None);
+                chunk.spans.push(Span::new(
+                    line,
+                    values.clone(),
+                    None,
+                    Some(line_dy),
+                    depth,
+                    decorations,
+                ));
+                chunks.push(chunk);
+            }
+        }
     }
+}
+
+/// The `lengthAdjust` attribute
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum LengthAdjust {
+    Spacing,
+    SpacingAndGlyphs,
+}
 
-    pub fn get_string(&self) -> String {
-        self.string.borrow().clone()
+impl Default for LengthAdjust {
+    fn default() -> LengthAdjust {
+        LengthAdjust::Spacing
     }
 }
 
-impl NodeTrait for NodeChars {
-    fn set_atts(&mut self, _: Option<&RsvgNode>, _: &PropertyBag<'_>) -> NodeResult {
-        Ok(())
+impl Parse for LengthAdjust {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<LengthAdjust, ValueErrorKind> {
+        let loc = parser.current_source_location();
+
+        parser
+            .expect_ident()
+            .and_then(|cow| match cow.as_ref() {
+                "spacing" => Ok(LengthAdjust::Spacing),
+                "spacingAndGlyphs" => Ok(LengthAdjust::SpacingAndGlyphs),
+                _ => Err(
+                    loc.new_basic_unexpected_token_error(Token::Ident(CowRcStr::from(
+                        cow.as_ref().to_string(),
+                    ))),
+                ),
+            })
+            .map_err(|_| {
+                ValueErrorKind::parse_error("expected \"spacing\" or \"spacingAndGlyphs\"")
+            })
     }
 }
 
@@ -580,6 +1195,8 @@ pub struct Text {
     y: Length<Vertical>,
     dx: Option<Length<Horizontal>>,
     dy: Option<Length<Vertical>>,
+    text_length: Option<Length<Horizontal>>,
+    length_adjust: LengthAdjust,
 }
 
 impl Text {
@@ -591,9 +1208,80 @@ impl Text {
     ) -> Vec<Chunk> {
         let mut chunks = Vec::new();
         chunks.push(Chunk::new(cascaded.get(), Some(self.x), Some(self.y)));
-        children_to_chunks(&mut chunks, node, cascaded, draw_ctx, self.dx, self.dy, 0);
+        children_to_chunks(
+            &mut chunks,
+            node,
+            cascaded,
+            draw_ctx,
+            self.dx,
+            self.dy,
+            0,
+            &[],
+        );
         chunks
     }
+
+    /// Measures the chunks, and fits them to the `textLength` if there is one.
+    ///
+    /// With `lengthAdjust="spacingAndGlyphs"` the text gets measured normally, and
+    /// this also returns the factor by which the text must be scaled along its
+    /// direction to fit.
+    fn measure_chunks(
+        &self,
+        chunks: &[Chunk],
+        values: &ComputedValues,
+        draw_ctx: &DrawingCtx,
+    ) -> (Vec<MeasuredChunk>, Option<f64>) {
+        let measure = |extra_letter_spacing: f64| -> Vec<MeasuredChunk> {
+            chunks
+                .iter()
+                .map(|chunk| MeasuredChunk::from_chunk(chunk, draw_ctx, extra_letter_spacing))
+                .collect()
+        };
+
+        let measured = measure(0.0);
+
+        let text_length = match self.text_length {
+            Some(ref l) => l.normalize(values, &draw_ctx.get_view_params()),
+            None => return (measured, None),
+        };
+
+        let vertical = values.writing_mode.is_vertical();
+        let length = total_advance(&measured, vertical);
+
+        if !(length > 0.0 && text_length > 0.0) {
+            return (measured, None);
+        }
+
+        match self.length_adjust {
+            LengthAdjust::SpacingAndGlyphs => (measured, Some(text_length / length)),
+
+            LengthAdjust::Spacing => {
+                // Pango decides between which characters the letter spacing goes, so
+                // find out how much each unit of it adds to the length of the text.
+                let per_unit = total_advance(&measure(1.0), vertical) - length;
+
+                if per_unit > 0.0 {
+                    (measure((text_length - length) / per_unit), None)
+                } else {
+                    (measured, None)
+                }
+            }
+        }
+    }
+}
+
+fn total_advance(chunks: &[MeasuredChunk], vertical: bool) -> f64 {
+    chunks
+        .iter()
+        .map(|chunk| {
+            if vertical {
+                chunk.advance.1
+            } else {
+                chunk.advance.0
+            }
+        })
+        .sum()
 }
 
 impl NodeTrait for Text {
@@ -604,6 +1292,12 @@ impl NodeTrait for Text {
                 expanded_name!(svg "y") => self.y = attr.parse(value)?,
                 expanded_name!(svg "dx") => self.dx = attr.parse(value).map(Some)?,
                 expanded_name!(svg "dy") => self.dy = attr.parse(value).map(Some)?,
+                expanded_name!(svg "textLength") => {
+                    self.text_length = Some(
+                        attr.parse_and_validate(value, Length::<Horizontal>::check_nonnegative)?,
+                    )
+                }
+                expanded_name!(svg "lengthAdjust") => self.length_adjust = attr.parse(value)?,
                 _ => (),
             }
         }
@@ -625,40 +1319,74 @@ impl NodeTrait for Text {
         let mut y = self.y.normalize(values, &params);
 
         let chunks = self.make_chunks(node, cascaded, draw_ctx);
+        let (measured_chunks, glyph_scale) = self.measure_chunks(&chunks, values, draw_ctx);
 
-        let mut measured_chunks = Vec::new();
-        for chunk in &chunks {
-            measured_chunks.push(MeasuredChunk::from_chunk(chunk, draw_ctx));
-        }
+        let (anchor_x, anchor_y) = (x, y);
 
         let mut positioned_chunks = Vec::new();
         for chunk in &measured_chunks {
-            let chunk_x = chunk
-                .x
-                .map_or_else(|| x, |l| l.normalize(&chunk.values, &params));
-            let chunk_y = chunk
-                .y
-                .map_or_else(|| y, |l| l.normalize(&chunk.values, &params));
+            let (chunk_x, chunk_y) = match chunk.path {
+                // Along a path, x is the distance from its start
+                Some(ref path) => (path.start_offset, 0.0),
+
+                None => (
+                    chunk
+                        .x
+                        .map_or_else(|| x, |l| l.normalize(&chunk.values, &params)),
+                    chunk
+                        .y
+                        .map_or_else(|| y, |l| l.normalize(&chunk.values, &params)),
+                ),
+            };
 
             let positioned = PositionedChunk::from_measured(&chunk, draw_ctx, chunk_x, chunk_y);
 
-            x = positioned.next_chunk_x;
-            y = positioned.next_chunk_y;
+            match chunk.path {
+                Some(ref path) => {
+                    if let Some(((end_x, end_y), _)) =
+                        path.point_at_clamped(positioned.next_chunk_x)
+                    {
+                        x = end_x;
+                        y = end_y;
+                    }
+                }
+
+                None => {
+                    x = positioned.next_chunk_x;
+                    y = positioned.next_chunk_y;
+                }
+            }
 
             positioned_chunks.push(positioned);
         }
 
         draw_ctx.with_discrete_layer(node, values, clipping, &mut |dc| {
-            let mut bbox = dc.empty_bbox();
+            dc.with_saved_cr(&mut |dc| {
+                if let Some(scale) = glyph_scale {
+                    // Stretch the text along its direction, keeping its anchor in place
+                    let cr = dc.get_cairo_context();
+                    cr.translate(anchor_x, anchor_y);
+
+                    if values.writing_mode.is_vertical() {
+                        cr.scale(1.0, scale);
+                    } else {
+                        cr.scale(scale, 1.0);
+                    }
 
-            for chunk in &positioned_chunks {
-                for span in &chunk.spans {
-                    let span_bbox = span.draw(dc, clipping)?;
-                    bbox.insert(&span_bbox);
+                    cr.translate(-anchor_x, -anchor_y);
                 }
-            }
 
-            Ok(bbox)
+                let mut bbox = dc.empty_bbox();
+
+                for chunk in &positioned_chunks {
+                    for span in &chunk.spans {
+                        let span_bbox = span.draw(dc, clipping)?;
+                        bbox.insert(&span_bbox);
+                    }
+                }
+
+                Ok(bbox)
+            })
         })
     }
 }
@@ -676,6 +1404,7 @@ impl TRef {
         draw_ctx: &mut DrawingCtx,
         chunks: &mut Vec<Chunk>,
         depth: usize,
+        decorations: &[ComputedValues],
     ) {
         if self.link.is_none() {
             return;
@@ -683,10 +1412,19 @@ impl TRef {
 
         let link = self.link.as_ref().unwrap();
         let values = cascaded.get();
+        let decorations = add_decoration(decorations, values);
 
         if let Ok(acquired) = draw_ctx.acquire_node(link, &[]) {
             let c = acquired.get();
-            extract_chars_children_to_chunks_recursively(chunks, &c, values, depth);
+            let multiline = draw_ctx.multiline_text();
+            extract_chars_children_to_chunks_recursively(
+                chunks,
+                &c,
+                values,
+                depth,
+                multiline,
+                &decorations,
+            );
         } else {
             rsvg_log!(
                 "element {} references a nonexistent text source \"{}\"",
@@ -702,14 +1440,29 @@ fn extract_chars_children_to_chunks_recursively(
     node: &RsvgNode,
     values: &ComputedValues,
     depth: usize,
+    multiline: bool,
+    decorations: &[ComputedValues],
 ) {
     for child in node.children() {
         match child.borrow().get_type() {
-            NodeType::Chars => child
-                .borrow()
-                .get_impl::<NodeChars>()
-                .to_chunks(&child, values, chunks, None, None, depth),
-            _ => extract_chars_children_to_chunks_recursively(chunks, &child, values, depth + 1),
+            NodeType::Chars => child.borrow().get_impl::<NodeChars>().to_chunks(
+                &child,
+                values,
+                chunks,
+                None,
+                None,
+                depth,
+                multiline,
+                decorations,
+            ),
+            _ => extract_chars_children_to_chunks_recursively(
+                chunks,
+                &child,
+                values,
+                depth + 1,
+                multiline,
+                decorations,
+            ),
         }
     }
 }
@@ -745,6 +1498,7 @@ impl TSpan {
         draw_ctx: &mut DrawingCtx,
         chunks: &mut Vec<Chunk>,
         depth: usize,
+        decorations: &[ComputedValues],
     ) {
         if self.x.is_some() || self.y.is_some() {
             // Any absolute position creates a new chunk
@@ -752,7 +1506,16 @@ impl TSpan {
             chunks.push(Chunk::new(values, self.x, self.y));
         }
 
-        children_to_chunks(chunks, node, cascaded, draw_ctx, self.dx, self.dy, depth);
+        children_to_chunks(
+            chunks,
+            node,
+            cascaded,
+            draw_ctx,
+            self.dx,
+            self.dy,
+            depth,
+            decorations,
+        );
     }
 }
 
@@ -772,6 +1535,213 @@ impl NodeTrait for TSpan {
     }
 }
 
+/// The `side` attribute of `<textPath>`
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TextPathSide {
+    Left,
+    Right,
+}
+
+impl Default for TextPathSide {
+    fn default() -> TextPathSide {
+        TextPathSide::Left
+    }
+}
+
+impl Parse for TextPathSide {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<TextPathSide, ValueErrorKind> {
+        let loc = parser.current_source_location();
+
+        parser
+            .expect_ident()
+            .and_then(|cow| match cow.as_ref() {
+                "left" => Ok(TextPathSide::Left),
+                "right" => Ok(TextPathSide::Right),
+                _ => Err(
+                    loc.new_basic_unexpected_token_error(Token::Ident(CowRcStr::from(
+                        cow.as_ref().to_string(),
+                    ))),
+                ),
+            })
+            .map_err(|_| ValueErrorKind::parse_error("expected \"left\" or \"right\""))
+    }
+}
+
+/// The resolved path of a `<textPath>`, in the user space of the text
+struct TextPathGeometry {
+    measure: PathMeasure,
+    side: TextPathSide,
+    start_offset: f64,
+}
+
+impl TextPathGeometry {
+    /// Returns the point at `distance` along the path, and the direction in which the
+    /// text runs there.
+    ///
+    /// Returns `None` if the distance falls outside of the path.
+    fn point_at(&self, distance: f64) -> Option<((f64, f64), Angle)> {
+        if distance < 0.0 || distance > self.measure.total_length() {
+            None
+        } else {
+            self.point_at_clamped(distance)
+        }
+    }
+
+    /// Like `point_at()`, but the distance gets clamped to the length of the path.
+    fn point_at_clamped(&self, distance: f64) -> Option<((f64, f64), Angle)> {
+        match self.side {
+            TextPathSide::Left => self.measure.point_at_length(distance),
+
+            // The text goes along the reversed path, so it ends up on the other side
+            TextPathSide::Right => self
+                .measure
+                .point_at_length(self.measure.total_length() - distance)
+                .map(|(point, angle)| (point, angle.flip())),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TextPath {
+    link: Option<Fragment>,
+    path: Option<MotionPath>,
+    start_offset: Length<Horizontal>,
+    side: TextPathSide,
+}
+
+impl TextPath {
+    fn to_chunks(
+        &self,
+        node: &RsvgNode,
+        cascaded: &CascadedValues<'_>,
+        draw_ctx: &mut DrawingCtx,
+        chunks: &mut Vec<Chunk>,
+        depth: usize,
+        decorations: &[ComputedValues],
+    ) {
+        let values = cascaded.get();
+
+        // Per the spec, a textPath without a valid path is not rendered
+        if let Some(geometry) = self.geometry(node, values, draw_ctx) {
+            let mut chunk = Chunk::new(values, None, None);
+            chunk.path = Some(Rc::new(geometry));
+            chunks.push(chunk);
+
+            children_to_chunks(
+                chunks,
+                node,
+                cascaded,
+                draw_ctx,
+                None,
+                None,
+                depth,
+                decorations,
+            );
+        }
+    }
+
+    fn geometry(
+        &self,
+        node: &RsvgNode,
+        values: &ComputedValues,
+        draw_ctx: &mut DrawingCtx,
+    ) -> Option<TextPathGeometry> {
+        // The path attribute takes precedence over href
+        let segments = if let Some(ref path) = self.path {
+            path.builder().to_segments()
+        } else if let Some(ref link) = self.link {
+            match draw_ctx.acquire_node(link, &[NodeType::Path]) {
+                Ok(acquired) => {
+                    let path_node = acquired.get().borrow();
+
+                    // The referenced path's transform applies to the text too
+                    let affine = path_node.get_transform();
+
+                    path_node
+                        .get_impl::<Path>()
+                        .builder()
+                        .to_segments()
+                        .iter()
+                        .map(|segment| transform_segment(segment, &affine))
+                        .collect()
+                }
+
+                Err(_) => {
+                    rsvg_log!(
+                        "element {} references a nonexistent path \"{}\"",
+                        node,
+                        link
+                    );
+                    return None;
+                }
+            }
+        } else {
+            return None;
+        };
+
+        let measure = PathMeasure::new(&segments).ok()?;
+
+        let start_offset = if self.start_offset.unit == LengthUnit::Percent {
+            self.start_offset.length * measure.total_length()
+        } else {
+            let params = draw_ctx.get_view_params();
+            self.start_offset.normalize(values, &params)
+        };
+
+        Some(TextPathGeometry {
+            measure,
+            side: self.side,
+            start_offset,
+        })
+    }
+}
+
+fn transform_segment(segment: &PathSegment, affine: &cairo::Matrix) -> PathSegment {
+    match *segment {
+        PathSegment::MoveTo(x, y) => {
+            let (x, y) = affine.transform_point(x, y);
+            PathSegment::MoveTo(x, y)
+        }
+
+        PathSegment::LineTo(x, y) => {
+            let (x, y) = affine.transform_point(x, y);
+            PathSegment::LineTo(x, y)
+        }
+
+        PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+            let (x1, y1) = affine.transform_point(x1, y1);
+            let (x2, y2) = affine.transform_point(x2, y2);
+            let (x, y) = affine.transform_point(x, y);
+            PathSegment::CurveTo(x1, y1, x2, y2, x, y)
+        }
+
+        PathSegment::ClosePath => PathSegment::ClosePath,
+    }
+}
+
+impl NodeTrait for TextPath {
+    fn set_atts(&mut self, _: Option<&RsvgNode>, pbag: &PropertyBag<'_>) -> NodeResult {
+        for (attr, value) in pbag.iter() {
+            match attr.expanded() {
+                expanded_name!(xlink "href") => {
+                    self.link = Some(Fragment::parse(value).attribute(attr)?)
+                }
+                expanded_name!(svg "path") => {
+                    self.path = Some(MotionPath::from_path_data(value).attribute(attr)?)
+                }
+                expanded_name!(svg "startOffset") => self.start_offset = attr.parse(value)?,
+                // markup5ever has no atom for this SVG 2 attribute
+                _ if attr.ns == ns!(svg) && &*attr.local == "side" => {
+                    self.side = attr.parse(value)?
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
 fn to_pango_units(v: f64) -> i32 {
     (v * f64::from(pango::SCALE) + 0.5) as i32
 }
@@ -890,8 +1860,13 @@ impl From<WritingMode> for pango::Gravity {
     }
 }
 
-fn get_pango_context(cr: &cairo::Context, is_testing: bool) -> pango::Context {
-    let font_map = pangocairo::FontMap::get_default().unwrap();
+fn get_pango_context(
+    cr: &cairo::Context,
+    font_map: Option<pango::FontMap>,
+    is_testing: bool,
+) -> pango::Context {
+    // Documents with @font-face rules, and applications, can have a font map of their own
+    let font_map = font_map.unwrap_or_else(|| pangocairo::FontMap::get_default().unwrap());
     let context = font_map.create_context().unwrap();
     pangocairo::functions::update_context(&cr, &context);
 
@@ -926,13 +1901,207 @@ fn get_pango_context(cr: &cairo::Context, is_testing: bool) -> pango::Context {
     context
 }
 
+fn set_font_properties(
+    font_desc: &mut pango::FontDescription,
+    values: &ComputedValues,
+    font_families: &FontFamilyMap,
+) {
+    font_desc.set_family(font_families.resolve(&(values.font_family.0).0));
+    font_desc.set_style(pango::Style::from(values.font_style));
+    font_desc.set_variant(pango::Variant::from(values.font_variant));
+    font_desc.set_weight(pango::Weight::from(values.font_weight.0));
+    font_desc.set_stretch(pango::Stretch::from(values.font_stretch));
+}
+
+/// Measures the advance of the "0" glyph in the font given by `values`, at `font_size`
+/// pixels; this is what the `ch` length unit refers to.
+///
+/// Returns `None` if the advance can't be measured.  Lengths are normalized without a
+/// drawing context, so this uses the family from `values` even if the application mapped it
+/// to another one.
+pub fn measure_zero_advance(values: &ComputedValues, font_size: f64) -> Option<f64> {
+    let font_map = pangocairo::FontMap::get_default()?;
+    let context = font_map.create_context()?;
+
+    // See get_pango_context() for why this is 72.
+    pangocairo::functions::context_set_resolution(&context, 72.0);
+
+    let mut font_desc = pango::FontDescription::new();
+    set_font_properties(&mut font_desc, values, &FontFamilyMap::default());
+    font_desc.set_size(to_pango_units(font_size));
+
+    let layout = pango::Layout::new(&context);
+    layout.set_font_description(Some(&font_desc));
+    layout.set_text("0");
+
+    let (width, _) = layout.get_size();
+
+    if width > 0 {
+        Some(f64::from(width) / f64::from(pango::SCALE))
+    } else {
+        None
+    }
+}
+
+/// Widens the spaces in `text` by `word_spacing`.
+///
+/// Pango has no attribute for word spacing, so the spaces get replaced with blank
+/// shapes which are as tall as a space, and `word_spacing` wider.
+fn add_word_spacing(
+    attr_list: &pango::AttrList,
+    pango_context: &pango::Context,
+    font_desc: &pango::FontDescription,
+    text: &str,
+    word_spacing: f64,
+) {
+    let space = pango::Layout::new(pango_context);
+    space.set_font_description(Some(font_desc));
+    space.set_text(" ");
+
+    let (width, height) = space.get_size();
+
+    let ink_rect = pango_sys::PangoRectangle {
+        x: 0,
+        y: 0,
+        width: 0,
+        height: 0,
+    };
+
+    let logical_rect = pango_sys::PangoRectangle {
+        x: 0,
+        y: -space.get_baseline(),
+        width: width + to_pango_units(word_spacing),
+        height,
+    };
+
+    for (index, c) in text.char_indices() {
+        if c != ' ' && c != '\u{a0}' {
+            continue;
+        }
+
+        let attr: Option<pango::Attribute> =
+            unsafe { from_glib_full(pango_sys::pango_attr_shape_new(&ink_rect, &logical_rect)) };
+
+        if let Some(mut attr) = attr {
+            attr.set_start_index(index as u32);
+            attr.set_end_index((index + c.len_utf8()) as u32);
+            attr_list.insert(attr);
+        }
+    }
+}
+
+/// The base direction of the paragraph for a chunk of text.
+fn base_direction(values: &ComputedValues) -> pango::Direction {
+    match values.direction {
+        Direction::Rtl if !values.writing_mode.is_vertical() => pango::Direction::Rtl,
+        _ => pango::Direction::from(values.writing_mode),
+    }
+}
+
+const LEFT_TO_RIGHT_EMBEDDING: char = '\u{202a}';
+const RIGHT_TO_LEFT_EMBEDDING: char = '\u{202b}';
+const POP_DIRECTIONAL_FORMATTING: char = '\u{202c}';
+const LEFT_TO_RIGHT_OVERRIDE: char = '\u{202d}';
+const RIGHT_TO_LEFT_OVERRIDE: char = '\u{202e}';
+
+fn is_bidi_control(c: char) -> bool {
+    match c {
+        LEFT_TO_RIGHT_EMBEDDING
+        | RIGHT_TO_LEFT_EMBEDDING
+        | POP_DIRECTIONAL_FORMATTING
+        | LEFT_TO_RIGHT_OVERRIDE
+        | RIGHT_TO_LEFT_OVERRIDE => true,
+        _ => false,
+    }
+}
+
+/// Implements the `unicode-bidi` property of a span.
+///
+/// Pango runs the bidi algorithm over each layout, so we open an embedding or
+/// an override with the span's `direction`, as described in
+/// <https://www.w3.org/TR/css-writing-modes-3/#unicode-bidi>, by wrapping the
+/// text in the equivalent Unicode control characters.
+fn apply_unicode_bidi(values: &ComputedValues, text: &str) -> String {
+    let start = match (values.unicode_bidi, values.direction) {
+        (UnicodeBidi::Normal, _) => return text.to_string(),
+        (UnicodeBidi::Embed, Direction::Ltr) => LEFT_TO_RIGHT_EMBEDDING,
+        (UnicodeBidi::Embed, Direction::Rtl) => RIGHT_TO_LEFT_EMBEDDING,
+        (UnicodeBidi::Override, Direction::Ltr) => LEFT_TO_RIGHT_OVERRIDE,
+        (UnicodeBidi::Override, Direction::Rtl) => RIGHT_TO_LEFT_OVERRIDE,
+    };
+
+    format!("{}{}{}", start, text, POP_DIRECTIONAL_FORMATTING)
+}
+
+/// Measures the lines of the `text-decoration` of each of the `decorations`, with the
+/// metrics of the element's font.
+fn measure_decoration_lines(
+    draw_ctx: &DrawingCtx,
+    pango_context: &pango::Context,
+    decorations: &[ComputedValues],
+) -> Vec<DecorationLine> {
+    let params = draw_ctx.get_view_params();
+    let pango_scale = f64::from(pango::SCALE);
+
+    let mut lines = Vec::new();
+
+    for values in decorations {
+        let mut font_desc = pango_context.get_font_description().unwrap();
+        set_font_properties(&mut font_desc, values, draw_ctx.font_families());
+        font_desc.set_size(to_pango_units(
+            values.font_size.0.normalize(values, &params),
+        ));
+
+        let metrics = match pango_context.get_metrics(Some(&font_desc), None) {
+            Some(m) => m,
+            None => continue,
+        };
+
+        let underline_thickness = f64::from(metrics.get_underline_thickness()) / pango_scale;
+
+        let mut add_line = |position: i32, thickness: f64, over_text: bool| {
+            lines.push(DecorationLine {
+                values: values.clone(),
+                position: f64::from(position) / pango_scale,
+                thickness,
+                over_text,
+            })
+        };
+
+        let TextDecoration {
+            overline,
+            underline,
+            strike,
+        } = values.text_decoration;
+
+        if underline {
+            add_line(metrics.get_underline_position(), underline_thickness, false);
+        }
+
+        if overline {
+            add_line(metrics.get_ascent(), underline_thickness, false);
+        }
+
+        if strike {
+            add_line(
+                metrics.get_strikethrough_position(),
+                f64::from(metrics.get_strikethrough_thickness()) / pango_scale,
+                true,
+            );
+        }
+    }
+
+    lines
+}
+
 fn create_pango_layout(
     draw_ctx: &DrawingCtx,
     values: &ComputedValues,
+    base_dir: pango::Direction,
     text: &str,
 ) -> pango::Layout {
     let cr = draw_ctx.get_cairo_context();
-    let pango_context = get_pango_context(&cr, draw_ctx.is_testing());
+    let pango_context = get_pango_context(&cr, draw_ctx.font_map(), draw_ctx.is_testing());
 
     // See the construction of the XmlLang property
     // We use "" there as the default value; this means that the language is not set.
@@ -943,26 +2112,12 @@ fn create_pango_layout(
 
     pango_context.set_base_gravity(pango::Gravity::from(values.writing_mode));
 
-    match (values.unicode_bidi, values.direction) {
-        (UnicodeBidi::Override, _) | (UnicodeBidi::Embed, _) => {
-            pango_context.set_base_dir(pango::Direction::from(values.direction));
-        }
-
-        (_, direction) if direction != Direction::Ltr => {
-            pango_context.set_base_dir(pango::Direction::from(direction));
-        }
+    pango_context.set_base_dir(base_dir);
 
-        (_, _) => {
-            pango_context.set_base_dir(pango::Direction::from(values.writing_mode));
-        }
-    }
+    let text = &apply_unicode_bidi(values, text);
 
     let mut font_desc = pango_context.get_font_description().unwrap();
-    font_desc.set_family(&(values.font_family.0).0);
-    font_desc.set_style(pango::Style::from(values.font_style));
-    font_desc.set_variant(pango::Variant::from(values.font_variant));
-    font_desc.set_weight(pango::Weight::from(values.font_weight.0));
-    font_desc.set_stretch(pango::Stretch::from(values.font_stretch));
+    set_font_properties(&mut font_desc, values, draw_ctx.font_families());
 
     let params = draw_ctx.get_view_params();
 
@@ -983,12 +2138,9 @@ fn create_pango_layout(
         .unwrap(),
     );
 
-    if values.text_decoration.underline {
-        attr_list.insert(pango::Attribute::new_underline(pango::Underline::Single).unwrap());
-    }
-
-    if values.text_decoration.strike {
-        attr_list.insert(pango::Attribute::new_strikethrough(true).unwrap());
+    let word_spacing = values.word_spacing.0.normalize(values, &params);
+    if !word_spacing.approx_eq_cairo(0.0) {
+        add_word_spacing(&attr_list, &pango_context, &font_desc, text, word_spacing);
     }
 
     layout.set_attributes(Some(&attr_list));
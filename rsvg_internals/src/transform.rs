@@ -2,10 +2,15 @@ use cairo;
 
 use std::f64::consts::*;
 
-use cssparser::{ParseError as CssParseError, Parser, Token};
+use cssparser::{Delimiter, ParseError as CssParseError, Parser, Token};
 
+use crate::angle::Angle;
+use crate::drawing_ctx::ViewParams;
 use crate::error::*;
+use crate::length::*;
 use crate::parsers::{finite_f32, CssParserExt, Parse};
+use crate::properties::ComputedValues;
+use crate::property_macros::ToCssValue;
 
 impl Parse for cairo::Matrix {
     fn parse(parser: &mut Parser<'_, '_>) -> Result<cairo::Matrix, ValueErrorKind> {
@@ -224,6 +229,194 @@ fn parse_skewy_args(parser: &mut Parser<'_, '_>) -> Result<cairo::Matrix, ValueE
         })
 }
 
+/// A single function in the value of the CSS `transform` property
+///
+/// Unlike the `transform` attribute, the CSS syntax allows lengths and angles with
+/// units, so translations are kept unresolved until the element is rendered.
+///
+/// https://www.w3.org/TR/css-transforms-1/#transform-functions
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformFunction {
+    Matrix(cairo::Matrix),
+    Translate(Length<Horizontal>, Length<Vertical>),
+    Scale(f64, f64),
+    Rotate(Angle),
+    Skew(Angle, Angle),
+}
+
+impl Parse for TransformFunction {
+    fn parse(parser: &mut Parser<'_, '_>) -> Result<TransformFunction, ValueErrorKind> {
+        let name = parser.expect_function()?.clone();
+
+        parser
+            .parse_nested_block(|p| {
+                let function = parse_css_transform_function(&name, p);
+                function.map_err(|e| p.new_custom_error(e))
+            })
+            .map_err(ValueErrorKind::from)
+    }
+}
+
+fn parse_css_transform_function(
+    name: &str,
+    p: &mut Parser<'_, '_>,
+) -> Result<TransformFunction, ValueErrorKind> {
+    match name.to_ascii_lowercase().as_ref() {
+        "matrix" => {
+            let mut args = [0.0; 6];
+
+            for (i, arg) in args.iter_mut().enumerate() {
+                if i > 0 {
+                    p.expect_comma()?;
+                }
+
+                *arg = f64::from(p.expect_finite_number()?);
+            }
+
+            let [xx, yx, xy, yy, x0, y0] = args;
+            Ok(TransformFunction::Matrix(cairo::Matrix::new(
+                xx, yx, xy, yy, x0, y0,
+            )))
+        }
+
+        "translate" => {
+            let tx = Length::<Horizontal>::parse(p)?;
+
+            let ty = if p.try_parse(|p| p.expect_comma()).is_ok() {
+                Length::<Vertical>::parse(p)?
+            } else {
+                Length::<Vertical>::default()
+            };
+
+            Ok(TransformFunction::Translate(tx, ty))
+        }
+
+        "translatex" => Ok(TransformFunction::Translate(
+            Length::<Horizontal>::parse(p)?,
+            Length::<Vertical>::default(),
+        )),
+
+        "translatey" => Ok(TransformFunction::Translate(
+            Length::<Horizontal>::default(),
+            Length::<Vertical>::parse(p)?,
+        )),
+
+        "scale" => {
+            let sx = f64::from(p.expect_finite_number()?);
+
+            let sy = if p.try_parse(|p| p.expect_comma()).is_ok() {
+                f64::from(p.expect_finite_number()?)
+            } else {
+                sx
+            };
+
+            Ok(TransformFunction::Scale(sx, sy))
+        }
+
+        "scalex" => Ok(TransformFunction::Scale(
+            f64::from(p.expect_finite_number()?),
+            1.0,
+        )),
+
+        "scaley" => Ok(TransformFunction::Scale(
+            1.0,
+            f64::from(p.expect_finite_number()?),
+        )),
+
+        "rotate" => Ok(TransformFunction::Rotate(parse_angle_arg(p)?)),
+
+        "skew" => {
+            let ax = parse_angle_arg(p)?;
+
+            let ay = if p.try_parse(|p| p.expect_comma()).is_ok() {
+                parse_angle_arg(p)?
+            } else {
+                Angle::new(0.0)
+            };
+
+            Ok(TransformFunction::Skew(ax, ay))
+        }
+
+        "skewx" => Ok(TransformFunction::Skew(
+            parse_angle_arg(p)?,
+            Angle::new(0.0),
+        )),
+
+        "skewy" => Ok(TransformFunction::Skew(
+            Angle::new(0.0),
+            parse_angle_arg(p)?,
+        )),
+
+        _ => Err(ValueErrorKind::parse_error(
+            "expected matrix|translate[XY]|scale[XY]|rotate|skew[XY]",
+        )),
+    }
+}
+
+// Angle::parse() wants to consume all of its input, so stop it at the next argument
+fn parse_angle_arg(p: &mut Parser<'_, '_>) -> Result<Angle, ValueErrorKind> {
+    p.parse_until_before(Delimiter::Comma, |p| {
+        Angle::parse(p).map_err(|e| p.new_custom_error(e))
+    })
+    .map_err(ValueErrorKind::from)
+}
+
+impl TransformFunction {
+    /// Resolves the function's lengths and returns the corresponding matrix.
+    pub fn to_matrix(&self, values: &ComputedValues, params: &ViewParams) -> cairo::Matrix {
+        match *self {
+            TransformFunction::Matrix(m) => m,
+
+            TransformFunction::Translate(ref tx, ref ty) => cairo::Matrix::new(
+                1.0,
+                0.0,
+                0.0,
+                1.0,
+                tx.normalize(values, params),
+                ty.normalize(values, params),
+            ),
+
+            TransformFunction::Scale(sx, sy) => cairo::Matrix::new(sx, 0.0, 0.0, sy, 0.0, 0.0),
+
+            TransformFunction::Rotate(angle) => {
+                let (s, c) = angle.radians().sin_cos();
+                cairo::Matrix::new(c, s, -s, c, 0.0, 0.0)
+            }
+
+            TransformFunction::Skew(ax, ay) => {
+                cairo::Matrix::new(1.0, ay.radians().tan(), ax.radians().tan(), 1.0, 0.0, 0.0)
+            }
+        }
+    }
+}
+
+impl ToCssValue for TransformFunction {
+    fn to_css_value(&self) -> String {
+        match *self {
+            TransformFunction::Matrix(m) => format!(
+                "matrix({}, {}, {}, {}, {}, {})",
+                m.xx, m.yx, m.xy, m.yy, m.x0, m.y0
+            ),
+
+            TransformFunction::Translate(ref tx, ref ty) => {
+                format!("translate({}, {})", tx.to_css_value(), ty.to_css_value())
+            }
+
+            TransformFunction::Scale(sx, sy) => format!("scale({}, {})", sx, sy),
+
+            TransformFunction::Rotate(angle) => {
+                format!("rotate({}deg)", angle.radians().to_degrees())
+            }
+
+            TransformFunction::Skew(ax, ay) => format!(
+                "skew({}deg, {}deg)",
+                ax.radians().to_degrees(),
+                ay.radians().to_degrees()
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
 fn make_rotation_matrix(angle_degrees: f64, tx: f64, ty: f64) -> cairo::Matrix {
     let angle = angle_degrees * PI / 180.0;
@@ -2,6 +2,7 @@ use cssparser::Parser;
 
 use crate::error::*;
 use crate::parsers::{CssParserExt, Parse};
+use crate::property_macros::ToCssValue;
 use crate::util;
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
@@ -25,6 +26,12 @@ impl Parse for UnitInterval {
     }
 }
 
+impl ToCssValue for UnitInterval {
+    fn to_css_value(&self) -> String {
+        self.0.to_css_value()
+    }
+}
+
 impl From<UnitInterval> for u8 {
     fn from(val: UnitInterval) -> u8 {
         let UnitInterval(x) = val;
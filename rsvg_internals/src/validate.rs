@@ -0,0 +1,290 @@
+//! Checking documents against a profile of the SVG specification.
+//!
+//! This walks the tree of elements and reuses the attribute parsers that ran when the
+//! document was loaded, so it does not render anything.
+
+use markup5ever::{ns, QualName};
+
+use crate::create_node::is_supported_element;
+use crate::node::{NodeType, RsvgNode};
+use crate::properties::check_presentation_attribute;
+
+/// A version or profile of the SVG specification to validate against
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SvgProfile {
+    /// SVG 1.1 Full
+    Svg11Full,
+
+    /// SVG 2
+    Svg2,
+
+    /// SVG Tiny 1.2
+    SvgTiny12,
+}
+
+/// What is wrong in a [`ValidationIssue`]
+///
+/// [`ValidationIssue`]: struct.ValidationIssue.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// The element is not part of the profile.
+    ElementNotInProfile,
+
+    /// The element is part of the profile, but librsvg does not support it, so it is
+    /// ignored when rendering.
+    UnsupportedElement,
+
+    /// A presentation attribute sets a property that is not part of the profile.
+    PropertyNotInProfile,
+
+    /// An attribute has an invalid value, so the element is in error and does not render.
+    InvalidAttribute,
+
+    /// A presentation attribute has an invalid value, so it is ignored.
+    InvalidPropertyValue,
+}
+
+/// An unsupported or invalid construct found by [`validate`]
+///
+/// [`validate`]: fn.validate.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub kind: ValidationIssueKind,
+
+    /// Name of the element, like `rect`
+    pub element_name: String,
+
+    /// The element's `id` attribute, if it has one
+    pub id: Option<String>,
+
+    /// Name of the offending attribute, if the issue is about an attribute
+    pub attribute: Option<String>,
+
+    /// Human-readable description of the issue
+    pub message: String,
+}
+
+/// Result of checking a document against an [`SvgProfile`]
+///
+/// [`SvgProfile`]: enum.SvgProfile.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub profile: SvgProfile,
+
+    /// The issues, in document order
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the document has no issues for the profile
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SVG11_FULL_ELEMENTS: &[&str] = &[
+    "a", "altGlyph", "altGlyphDef", "altGlyphItem", "animate", "animateColor",
+    "animateMotion", "animateTransform", "circle", "clipPath", "color-profile", "cursor",
+    "defs", "desc", "ellipse", "feBlend", "feColorMatrix", "feComponentTransfer",
+    "feComposite", "feConvolveMatrix", "feDiffuseLighting", "feDisplacementMap",
+    "feDistantLight", "feFlood", "feFuncA", "feFuncB", "feFuncG", "feFuncR",
+    "feGaussianBlur", "feImage", "feMerge", "feMergeNode", "feMorphology", "feOffset",
+    "fePointLight", "feSpecularLighting", "feSpotLight", "feTile", "feTurbulence", "filter",
+    "font", "font-face", "font-face-format", "font-face-name", "font-face-src",
+    "font-face-uri", "foreignObject", "g", "glyph", "glyphRef", "hkern", "image", "line",
+    "linearGradient", "marker", "mask", "metadata", "missing-glyph", "mpath", "path",
+    "pattern", "polygon", "polyline", "radialGradient", "rect", "script", "set", "stop",
+    "style", "svg", "switch", "symbol", "text", "textPath", "title", "tref", "tspan", "use",
+    "view", "vkern",
+];
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SVG2_ELEMENTS: &[&str] = &[
+    "a", "animate", "animateMotion", "animateTransform", "circle", "clipPath", "defs",
+    "desc", "discard", "ellipse", "feBlend", "feColorMatrix", "feComponentTransfer",
+    "feComposite", "feConvolveMatrix", "feDiffuseLighting", "feDisplacementMap",
+    "feDistantLight", "feDropShadow", "feFlood", "feFuncA", "feFuncB", "feFuncG", "feFuncR",
+    "feGaussianBlur", "feImage", "feMerge", "feMergeNode", "feMorphology", "feOffset",
+    "fePointLight", "feSpecularLighting", "feSpotLight", "feTile", "feTurbulence", "filter",
+    "foreignObject", "g", "hatch", "hatchpath", "image", "line", "linearGradient", "marker",
+    "mask", "mesh", "meshgradient", "meshpatch", "meshrow", "metadata", "mpath", "path",
+    "pattern", "polygon", "polyline", "radialGradient", "rect", "script", "set",
+    "solidcolor", "stop", "style", "svg", "switch", "symbol", "text", "textPath", "title",
+    "tspan", "unknown", "use", "view",
+];
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SVG_TINY12_ELEMENTS: &[&str] = &[
+    "a", "animate", "animateColor", "animateMotion", "animateTransform", "animation",
+    "audio", "circle", "defs", "desc", "discard", "ellipse", "font", "font-face",
+    "font-face-src", "font-face-uri", "foreignObject", "g", "glyph", "handler", "hkern",
+    "image", "line", "linearGradient", "listener", "metadata", "missing-glyph", "mpath",
+    "path", "polygon", "polyline", "prefetch", "radialGradient", "rect", "script", "set",
+    "solidColor", "stop", "svg", "switch", "tbreak", "text", "textArea", "title", "tspan",
+    "use", "video",
+];
+
+/// Elements that don't render anything by design, so it doesn't matter that librsvg
+/// ignores them.
+const NON_RENDERING_ELEMENTS: &[&str] = &["desc", "metadata", "title"];
+
+/// Properties from CSS and SVG 2, which are not in SVG 1.1.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SVG2_PROPERTIES: &[&str] = &[
+    "animation", "animation-delay", "animation-direction", "animation-duration",
+    "animation-fill-mode", "animation-iteration-count", "animation-name",
+    "animation-play-state", "animation-timing-function", "isolation", "mask-type",
+    "mix-blend-mode", "offset-distance", "offset-path", "offset-rotate", "paint-order",
+    "transform-origin", "transition", "transition-delay", "transition-duration",
+    "transition-property", "transition-timing-function", "vector-effect", "white-space",
+];
+
+/// Properties from SVG 1.1 which are not in SVG Tiny 1.2.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SVG_TINY12_MISSING_PROPERTIES: &[&str] = &[
+    "baseline-shift", "clip-path", "clip-rule", "color-interpolation",
+    "color-interpolation-filters", "enable-background", "filter", "flood-color",
+    "flood-opacity", "font-stretch", "letter-spacing", "lighting-color", "marker",
+    "marker-end", "marker-mid", "marker-start", "mask", "opacity", "overflow",
+    "text-decoration", "word-spacing", "writing-mode",
+];
+
+impl SvgProfile {
+    fn name(self) -> &'static str {
+        match self {
+            SvgProfile::Svg11Full => "SVG 1.1 Full",
+            SvgProfile::Svg2 => "SVG 2",
+            SvgProfile::SvgTiny12 => "SVG Tiny 1.2",
+        }
+    }
+
+    fn has_element(self, name: &str) -> bool {
+        let elements = match self {
+            SvgProfile::Svg11Full => SVG11_FULL_ELEMENTS,
+            SvgProfile::Svg2 => SVG2_ELEMENTS,
+            SvgProfile::SvgTiny12 => SVG_TINY12_ELEMENTS,
+        };
+
+        elements.contains(&name)
+    }
+
+    fn has_property(self, name: &str) -> bool {
+        match self {
+            SvgProfile::Svg11Full => !SVG2_PROPERTIES.contains(&name),
+            SvgProfile::Svg2 => true,
+            SvgProfile::SvgTiny12 => {
+                (name == "vector-effect" || !SVG2_PROPERTIES.contains(&name))
+                    && !SVG_TINY12_MISSING_PROPERTIES.contains(&name)
+            }
+        }
+    }
+}
+
+fn attribute_name(attr: &QualName) -> String {
+    match attr.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, attr.local),
+        None => attr.local.to_string(),
+    }
+}
+
+/// Checks the elements of the tree at `root` against `profile`.
+///
+/// Only elements in the SVG namespace are checked; foreign content is skipped.  Properties
+/// are checked in presentation attributes, but not in stylesheets.
+pub fn validate(root: &RsvgNode, profile: SvgProfile) -> ValidationReport {
+    let mut issues = Vec::new();
+
+    for node in root.descendants() {
+        let node = node.borrow();
+
+        if node.get_type() == NodeType::Chars || node.element_name().ns != ns!(svg) {
+            continue;
+        }
+
+        let element_name = node.element_name().local.to_string();
+        let id = node.get_id().map(str::to_string);
+
+        let mut issue = |kind, attribute: Option<String>, message: String| {
+            issues.push(ValidationIssue {
+                kind,
+                element_name: element_name.clone(),
+                id: id.clone(),
+                attribute,
+                message,
+            })
+        };
+
+        if !profile.has_element(&element_name) {
+            issue(
+                ValidationIssueKind::ElementNotInProfile,
+                None,
+                format!("{} does not have the {} element", profile.name(), element_name),
+            );
+        } else if !is_supported_element(node.element_name())
+            && !NON_RENDERING_ELEMENTS.contains(&element_name.as_str())
+        {
+            issue(
+                ValidationIssueKind::UnsupportedElement,
+                None,
+                format!("the {} element is not supported and will be ignored", element_name),
+            );
+        }
+
+        if let Some(error) = node.get_error() {
+            issue(
+                ValidationIssueKind::InvalidAttribute,
+                Some(attribute_name(&error.attr)),
+                format!("{}; the element will not be rendered", error.err),
+            );
+        }
+
+        for (attr, value) in node.get_attributes() {
+            match check_presentation_attribute(attr, value) {
+                Ok(false) => (),
+
+                Ok(true) => {
+                    if !profile.has_property(&attr.local) {
+                        issue(
+                            ValidationIssueKind::PropertyNotInProfile,
+                            Some(attribute_name(attr)),
+                            format!(
+                                "{} does not have the {} property",
+                                profile.name(),
+                                attr.local
+                            ),
+                        );
+                    }
+                }
+
+                Err(e) => issue(
+                    ValidationIssueKind::InvalidPropertyValue,
+                    Some(attribute_name(attr)),
+                    format!("invalid value \"{}\": {}; it will be ignored", value, e),
+                ),
+            }
+        }
+    }
+
+    ValidationReport { profile, issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_profile_has_vector_effect_but_not_filters() {
+        assert!(SvgProfile::SvgTiny12.has_property("vector-effect"));
+        assert!(!SvgProfile::SvgTiny12.has_property("filter"));
+        assert!(!SvgProfile::SvgTiny12.has_property("paint-order"));
+        assert!(SvgProfile::SvgTiny12.has_property("fill"));
+    }
+
+    #[test]
+    fn svg11_does_not_have_svg2_properties() {
+        assert!(!SvgProfile::Svg11Full.has_property("paint-order"));
+        assert!(SvgProfile::Svg11Full.has_property("filter"));
+        assert!(SvgProfile::Svg2.has_property("paint-order"));
+    }
+}
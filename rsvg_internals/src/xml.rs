@@ -9,6 +9,7 @@ use std::rc::{Rc, Weak};
 use std::str;
 
 use crate::allowed_url::AllowedUrl;
+use crate::chars::NodeChars;
 use crate::document::{Document, DocumentBuilder};
 use crate::error::LoadingError;
 use crate::io::{self, get_input_stream_for_loading};
@@ -16,7 +17,6 @@ use crate::limits::MAX_LOADED_ELEMENTS;
 use crate::node::{NodeType, RsvgNode};
 use crate::property_bag::PropertyBag;
 use crate::style::{Style, StyleType};
-use crate::text::NodeChars;
 use crate::xml2_load::Xml2Parser;
 
 #[derive(Clone)]
@@ -354,11 +354,15 @@ impl XmlState {
 
         assert!(current_node.borrow().get_type() == NodeType::Style);
 
-        let style_type = current_node
-            .borrow()
-            .get_impl::<Style>()
-            .style_type()
-            .unwrap_or(StyleType::TextCss);
+        let (style_type, media) = {
+            let node_data = current_node.borrow();
+            let style = node_data.get_impl::<Style>();
+
+            (
+                style.style_type().unwrap_or(StyleType::TextCss),
+                style.media().cloned(),
+            )
+        };
 
         if style_type == StyleType::TextCss {
             let stylesheet_text = current_node.children()
@@ -371,7 +375,7 @@ impl XmlState {
                 .collect::<String>();
 
             let builder = inner.document_builder.as_mut().unwrap();
-            builder.append_stylesheet_from_text(&stylesheet_text);
+            builder.append_stylesheet_from_text(&stylesheet_text, media.as_ref());
         }
     }
 
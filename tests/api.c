@@ -59,7 +59,7 @@ flags_registration (void)
     g_assert (G_FLAGS_CLASS_TYPE (type_class) == ty);
 
     flags_class = G_FLAGS_CLASS (type_class);
-    g_assert (flags_class->n_values == 3);
+    g_assert (flags_class->n_values == 4);
 
     g_assert (flags_value_matches(&flags_class->values[0],
                                   RSVG_HANDLE_FLAGS_NONE,
@@ -76,6 +76,11 @@ flags_registration (void)
                                   "RSVG_HANDLE_FLAG_KEEP_IMAGE_DATA",
                                   "flag-keep-image-data"));
 
+    g_assert (flags_value_matches(&flags_class->values[3],
+                                  RSVG_HANDLE_FLAG_MULTILINE_TEXT,
+                                  "RSVG_HANDLE_FLAG_MULTILINE_TEXT",
+                                  "flag-multiline-text"));
+
     g_type_class_unref (type_class);
 }
 
@@ -1234,6 +1239,7 @@ property_flags (void)
     test_flags (RSVG_HANDLE_FLAG_UNLIMITED);
     test_flags (RSVG_HANDLE_FLAG_KEEP_IMAGE_DATA);
     test_flags (RSVG_HANDLE_FLAG_UNLIMITED | RSVG_HANDLE_FLAG_KEEP_IMAGE_DATA);
+    test_flags (RSVG_HANDLE_FLAG_MULTILINE_TEXT);
 }
 
 static void